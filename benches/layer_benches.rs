@@ -0,0 +1,125 @@
+#[macro_use]
+extern crate criterion;
+extern crate collenchyma as co;
+extern crate leaf;
+
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use criterion::{Bencher, BenchmarkId, Criterion};
+use co::prelude::*;
+use leaf::layer::*;
+use leaf::layers::*;
+use leaf::util::{ArcLock, LayerOps, tensor_from_slice};
+
+/// Create a container with the single layer `layer_type` for inputs of `input_shape`
+/// and an input filled with constant values.
+fn single_layer_network<B: IBackend + LayerOps<f32> + 'static>(backend: Rc<B>, layer_type: LayerType, input_shape: &[usize]) -> (Layer<B>, ArcLock<SharedTensor<f32>>) {
+    let mut net_cfg = SequentialConfig::default();
+    net_cfg.add_input("data", input_shape);
+    net_cfg.add_layer(LayerConfig::new("layer", layer_type));
+    let network = Layer::from_config(backend.clone(), &LayerConfig::new("network", net_cfg));
+
+    let size = input_shape.iter().fold(1, |prod, dim| prod * dim);
+    let input = tensor_from_slice(&*backend, input_shape, &vec![0.5f32; size]).unwrap();
+    (network, Arc::new(RwLock::new(input)))
+}
+
+fn batch_shape(batch_size: usize, sample_shape: &[usize]) -> Vec<usize> {
+    let mut shape = vec![batch_size];
+    shape.extend(sample_shape);
+    shape
+}
+
+fn bench_forward<B: IBackend + LayerOps<f32> + 'static>(b: &mut Bencher, backend: Rc<B>, layer_type: LayerType, input_shape: &[usize]) {
+    let (mut network, input) = single_layer_network(backend, layer_type, input_shape);
+    b.iter(|| {
+        network.forward(&[input.clone()]);
+        network.synchronize();
+    });
+}
+
+fn bench_backward<B: IBackend + LayerOps<f32> + 'static>(b: &mut Bencher, backend: Rc<B>, layer_type: LayerType, input_shape: &[usize]) {
+    let (mut network, input) = single_layer_network(backend, layer_type, input_shape);
+    network.forward(&[input]);
+    b.iter(|| {
+        network.backward_input(&[]);
+        network.backward_parameters();
+        network.synchronize();
+    });
+}
+
+/// Benchmark the forward and the backward pass of a layer for each of the batch sizes 1, 32 and 128.
+fn bench_layer<B: IBackend + LayerOps<f32> + 'static>(c: &mut Criterion, name: &str, backend: Rc<B>, layer_type: LayerType, sample_shape: &[usize]) {
+    let mut group = c.benchmark_group(name);
+    for &batch_size in [1, 32, 128].iter() {
+        let input_shape = batch_shape(batch_size, sample_shape);
+        group.bench_with_input(BenchmarkId::new("forward", batch_size), &input_shape, |b, input_shape| {
+            bench_forward(b, backend.clone(), layer_type.clone(), input_shape)
+        });
+        group.bench_with_input(BenchmarkId::new("backward", batch_size), &input_shape, |b, input_shape| {
+            bench_backward(b, backend.clone(), layer_type.clone(), input_shape)
+        });
+    }
+    group.finish();
+}
+
+#[cfg(feature = "native")]
+mod native {
+    use std::rc::Rc;
+    use criterion::Criterion;
+    use co::prelude::*;
+    use leaf::layer::*;
+    use leaf::layers::*;
+    use super::bench_layer;
+
+    fn native_backend() -> Rc<Backend<Native>> {
+        Rc::new(Backend::<Native>::default().unwrap())
+    }
+
+    pub fn benches(c: &mut Criterion) {
+        let backend = native_backend();
+        bench_layer(c, "native/linear", backend.clone(), LayerType::Linear(LinearConfig::new(512)), &[784]);
+        bench_layer(c, "native/softmax", backend.clone(), LayerType::Softmax(SoftmaxConfig::default()), &[1000]);
+        bench_layer(c, "native/log_softmax", backend.clone(), LayerType::LogSoftmax(LogSoftmaxConfig::default()), &[1000]);
+        bench_layer(c, "native/relu", backend.clone(), LayerType::ReLU, &[4096]);
+        bench_layer(c, "native/sigmoid", backend, LayerType::Sigmoid, &[4096]);
+    }
+}
+
+#[cfg(feature = "cuda")]
+mod cuda {
+    use std::rc::Rc;
+    use criterion::Criterion;
+    use co::prelude::*;
+    use leaf::layer::*;
+    use leaf::layers::*;
+    use super::bench_layer;
+
+    fn cuda_backend() -> Rc<Backend<Cuda>> {
+        Rc::new(Backend::<Cuda>::default().unwrap())
+    }
+
+    pub fn benches(c: &mut Criterion) {
+        let backend = cuda_backend();
+        bench_layer(c, "cuda/linear", backend.clone(), LayerType::Linear(LinearConfig::new(512)), &[784]);
+        #[cfg(not(feature = "native"))]
+        bench_layer(c, "cuda/convolution", backend.clone(), LayerType::Convolution(ConvolutionConfig::new(64, &[3]).padding(&[1])), &[3, 64, 64]);
+        #[cfg(not(feature = "native"))]
+        bench_layer(c, "cuda/pooling", backend.clone(), LayerType::Pooling(PoolingConfig::new(PoolingMode::Max, &[2])), &[64, 56, 56]);
+        bench_layer(c, "cuda/softmax", backend.clone(), LayerType::Softmax(SoftmaxConfig::default()), &[1000]);
+        bench_layer(c, "cuda/log_softmax", backend.clone(), LayerType::LogSoftmax(LogSoftmaxConfig::default()), &[1000]);
+        bench_layer(c, "cuda/relu", backend.clone(), LayerType::ReLU, &[4096]);
+        bench_layer(c, "cuda/sigmoid", backend, LayerType::Sigmoid, &[4096]);
+    }
+}
+
+#[allow(unused_variables)]
+fn benches(c: &mut Criterion) {
+    #[cfg(feature = "native")]
+    native::benches(c);
+    #[cfg(feature = "cuda")]
+    cuda::benches(c);
+}
+
+criterion_group!(layer_benches, benches);
+criterion_main!(layer_benches);
@@ -1,14 +1,13 @@
-#![feature(test)]
-
-extern crate test;
 #[macro_use]
-extern crate timeit;
+extern crate criterion;
 extern crate collenchyma as co;
 extern crate leaf;
 
+use criterion::Criterion;
+
 #[cfg(feature = "cuda")]
 mod cuda {
-    use test::Bencher;
+    use criterion::{Bencher, Criterion};
     use co::prelude::*;
 
     use std::sync::{Arc, RwLock};
@@ -28,14 +27,14 @@ mod cuda {
     }
 
     #[inline(never)]
-    #[allow(unused_variables)]
     fn bench_profile<F: FnMut() -> ()>(
         b: &mut Bencher,
-        mut bench_func: F,
-        times: usize) {
-        timeit_loops!(times, {
-            bench_func();
-        });
+        mut bench_func: F) {
+        b.iter(|| bench_func());
+    }
+
+    pub fn benches(c: &mut Criterion) {
+        c.bench_function("alexnet_forward", alexnet_forward);
     }
 
     // #[inline(never)]
@@ -66,9 +65,8 @@ mod cuda {
     //     });
     // }
 
-    #[bench]
-    #[ignore]
-    #[cfg(feature = "cuda")]
+    // not run by default, add it to `benches` to run it
+    #[allow(dead_code)]
     fn bench_mnsit_forward_1(b: &mut Bencher) {
         let mut cfg = SequentialConfig::default();
         // set up input
@@ -80,7 +78,7 @@ mod cuda {
         sig_cfg.add_output("sig_out");
         cfg.add_layer(sig_cfg);
 
-        let fc_layer_cfg = LinearConfig { output_size: 10 };
+        let fc_layer_cfg = LinearConfig::new(10);
         let mut fc_cfg = LayerConfig::new("fully_connected", LayerType::Linear(fc_layer_cfg));
         fc_cfg.add_input("sig_out");
         fc_cfg.add_output("fc_out");
@@ -95,11 +93,12 @@ mod cuda {
         let mut network = Layer::from_config(
             backend.clone(), &LayerConfig::new("network", LayerType::Sequential(cfg)));
 
-        let _ = timeit_loops!(10, {
+        b.iter(|| {
             let inp = SharedTensor::<f32>::new(backend.device(), &vec![1, 30, 30]).unwrap();
             let inp_lock = Arc::new(RwLock::new(inp));
 
             network.forward(&[inp_lock]);
+            network.synchronize();
         });
         // b.iter(|| {
         //     for _ in 0..1 {
@@ -114,20 +113,12 @@ mod cuda {
         // });
     }
 
-    #[bench]
-    // #[ignore]
-    #[cfg(feature = "cuda")]
     fn alexnet_forward(b: &mut Bencher) {
         let mut cfg = SequentialConfig::default();
         // Layer: data
         cfg.add_input("data", &vec![128, 3, 224, 224]);
         // Layer: conv1
-        let conv1_layer_cfg = ConvolutionConfig {
-            num_output: 64,
-            filter_shape: vec![11],
-            padding: vec![2],
-            stride: vec![4]
-        };
+        let conv1_layer_cfg = ConvolutionConfig::new(64, &[11]).stride(&[4]).padding(&[2]);
         let mut conv1_cfg = LayerConfig::new("conv1", LayerType::Convolution(conv1_layer_cfg));
         conv1_cfg.add_input("data");
         conv1_cfg.add_output("conv1_preac");
@@ -138,23 +129,13 @@ mod cuda {
         conv1_relu_cfg.add_output("conv1_out");
         cfg.add_layer(conv1_relu_cfg);
         // Layer: pool1
-        let pool1_layer_cfg = PoolingConfig {
-            mode: PoolingMode::Max,
-            filter_shape: vec![3],
-            stride: vec![2],
-            padding: vec![0], // TODO: make optional
-        };
+        let pool1_layer_cfg = PoolingConfig::new(PoolingMode::Max, &[3]).stride(&[2]);
         let mut pool1_cfg = LayerConfig::new("pool1", LayerType::Pooling(pool1_layer_cfg));
         pool1_cfg.add_input("conv1_out");
         pool1_cfg.add_output("pool1_out");
         cfg.add_layer(pool1_cfg);
         // Layer: conv2
-        let conv2_layer_cfg = ConvolutionConfig {
-            num_output: 192,
-            filter_shape: vec![5],
-            padding: vec![2],
-            stride: vec![1]
-        };
+        let conv2_layer_cfg = ConvolutionConfig::new(192, &[5]).padding(&[2]);
         let mut conv2_cfg = LayerConfig::new("conv2", LayerType::Convolution(conv2_layer_cfg));
         conv2_cfg.add_input("pool1_out");
         conv2_cfg.add_output("conv2_preac");
@@ -165,23 +146,13 @@ mod cuda {
         conv2_relu_cfg.add_output("conv2_out");
         cfg.add_layer(conv2_relu_cfg);
         // Layer: pool2
-        let pool2_layer_cfg = PoolingConfig {
-            mode: PoolingMode::Max,
-            filter_shape: vec![3],
-            stride: vec![2],
-            padding: vec![0], // TODO: make optional
-        };
+        let pool2_layer_cfg = PoolingConfig::new(PoolingMode::Max, &[3]).stride(&[2]);
         let mut pool2_cfg = LayerConfig::new("pool2", LayerType::Pooling(pool2_layer_cfg));
         pool2_cfg.add_input("conv2_out");
         pool2_cfg.add_output("pool2_out");
         cfg.add_layer(pool2_cfg);
         // Layer: conv3
-        let conv3_layer_cfg = ConvolutionConfig {
-            num_output: 384,
-            filter_shape: vec![3],
-            padding: vec![1],
-            stride: vec![1]
-        };
+        let conv3_layer_cfg = ConvolutionConfig::new(384, &[3]).padding(&[1]);
         let mut conv3_cfg = LayerConfig::new("conv3", LayerType::Convolution(conv3_layer_cfg));
         conv3_cfg.add_input("pool2_out");
         conv3_cfg.add_output("conv3_preac");
@@ -192,12 +163,7 @@ mod cuda {
         conv3_relu_cfg.add_output("conv3_out");
         cfg.add_layer(conv3_relu_cfg);
         // Layer: conv4
-        let conv4_layer_cfg = ConvolutionConfig {
-            num_output: 256,
-            filter_shape: vec![3],
-            padding: vec![1],
-            stride: vec![1]
-        };
+        let conv4_layer_cfg = ConvolutionConfig::new(256, &[3]).padding(&[1]);
         let mut conv4_cfg = LayerConfig::new("conv4", LayerType::Convolution(conv4_layer_cfg));
         conv4_cfg.add_input("conv3_out");
         conv4_cfg.add_output("conv4_preac");
@@ -208,12 +174,7 @@ mod cuda {
         conv4_relu_cfg.add_output("conv4_out");
         cfg.add_layer(conv4_relu_cfg);
         // Layer: conv5
-        let conv5_layer_cfg = ConvolutionConfig {
-            num_output: 256,
-            filter_shape: vec![3],
-            padding: vec![1],
-            stride: vec![1]
-        };
+        let conv5_layer_cfg = ConvolutionConfig::new(256, &[3]).padding(&[1]);
         let mut conv5_cfg = LayerConfig::new("conv5", LayerType::Convolution(conv5_layer_cfg));
         conv5_cfg.add_input("conv4_out");
         conv5_cfg.add_output("conv5_preac");
@@ -224,30 +185,25 @@ mod cuda {
         conv5_relu_cfg.add_output("conv5_out");
         cfg.add_layer(conv5_relu_cfg);
         // Layer: pool3
-        let pool3_layer_cfg = PoolingConfig {
-            mode: PoolingMode::Max,
-            filter_shape: vec![3],
-            stride: vec![2],
-            padding: vec![0], // TODO: make optional
-        };
+        let pool3_layer_cfg = PoolingConfig::new(PoolingMode::Max, &[3]).stride(&[2]);
         let mut pool3_cfg = LayerConfig::new("pool3", LayerType::Pooling(pool3_layer_cfg));
         pool3_cfg.add_input("conv5_out");
         pool3_cfg.add_output("pool3_out");
         cfg.add_layer(pool3_cfg);
         // Layer: fc1
-        let fc1_layer_cfg = LinearConfig { output_size: 4096 };
+        let fc1_layer_cfg = LinearConfig::new(4096);
         let mut fc1_cfg = LayerConfig::new("fc1", LayerType::Linear(fc1_layer_cfg));
         fc1_cfg.add_input("pool3_out");
         fc1_cfg.add_output("fc1_out");
         cfg.add_layer(fc1_cfg);
         // Layer: fc2
-        let fc2_layer_cfg = LinearConfig { output_size: 4096 };
+        let fc2_layer_cfg = LinearConfig::new(4096);
         let mut fc2_cfg = LayerConfig::new("fc2", LayerType::Linear(fc2_layer_cfg));
         fc2_cfg.add_input("fc1_out");
         fc2_cfg.add_output("fc2_out");
         cfg.add_layer(fc2_cfg);
         // Layer: fc3
-        let fc3_layer_cfg = LinearConfig { output_size: 1000 };
+        let fc3_layer_cfg = LinearConfig::new(1000);
         let mut fc3_cfg = LayerConfig::new("fc3", LayerType::Linear(fc3_layer_cfg));
         fc3_cfg.add_input("fc2_out");
         fc3_cfg.add_output("fc3_out");
@@ -259,32 +215,24 @@ mod cuda {
             backend.clone(), &LayerConfig::new("network", LayerType::Sequential(cfg)));
 
         let func = || {
-            let forward_time = timeit_loops!(1, {
-                let inp = SharedTensor::<f32>::new(backend.device(), &vec![128, 3, 112, 112]).unwrap();
+            let inp = SharedTensor::<f32>::new(backend.device(), &vec![128, 3, 112, 112]).unwrap();
 
-                let inp_lock = Arc::new(RwLock::new(inp));
-                network.forward(&[inp_lock]);
-            });
-            println!("Forward step: {}", forward_time);
+            let inp_lock = Arc::new(RwLock::new(inp));
+            network.forward(&[inp_lock]);
+            network.synchronize();
         };
-        { bench_profile(b, func, 10); }
+        { bench_profile(b, func); }
     }
 
-    #[bench]
-    #[ignore]
-    #[cfg(feature = "cuda")]
+    // not run by default, add it to `benches` to run it
+    #[allow(dead_code)]
     fn small_alexnet_forward(b: &mut Bencher) {
         // let _ = env_logger::init();
         let mut cfg = SequentialConfig::default();
         // Layer: data
         cfg.add_input("data", &vec![128, 3, 112, 112]);
         // Layer: conv1
-        let conv1_layer_cfg = ConvolutionConfig {
-            num_output: 32,
-            filter_shape: vec![11],
-            padding: vec![2],
-            stride: vec![4]
-        };
+        let conv1_layer_cfg = ConvolutionConfig::new(32, &[11]).stride(&[4]).padding(&[2]);
         let mut conv1_cfg = LayerConfig::new("conv1", LayerType::Convolution(conv1_layer_cfg));
         conv1_cfg.add_input("data");
         conv1_cfg.add_output("conv1_preac");
@@ -295,23 +243,13 @@ mod cuda {
         conv1_relu_cfg.add_output("conv1_out");
         cfg.add_layer(conv1_relu_cfg);
         // Layer: pool1
-        let pool1_layer_cfg = PoolingConfig {
-            mode: PoolingMode::Max,
-            filter_shape: vec![3],
-            stride: vec![2],
-            padding: vec![0], // TODO: make optional
-        };
+        let pool1_layer_cfg = PoolingConfig::new(PoolingMode::Max, &[3]).stride(&[2]);
         let mut pool1_cfg = LayerConfig::new("pool1", LayerType::Pooling(pool1_layer_cfg));
         pool1_cfg.add_input("conv1_out");
         pool1_cfg.add_output("pool1_out");
         cfg.add_layer(pool1_cfg);
         // Layer: conv2
-        let conv2_layer_cfg = ConvolutionConfig {
-            num_output: 96,
-            filter_shape: vec![5],
-            padding: vec![2],
-            stride: vec![1]
-        };
+        let conv2_layer_cfg = ConvolutionConfig::new(96, &[5]).padding(&[2]);
         let mut conv2_cfg = LayerConfig::new("conv2", LayerType::Convolution(conv2_layer_cfg));
         conv2_cfg.add_input("pool1_out");
         conv2_cfg.add_output("conv2_preac");
@@ -322,23 +260,13 @@ mod cuda {
         conv2_relu_cfg.add_output("conv2_out");
         cfg.add_layer(conv2_relu_cfg);
         // Layer: pool2
-        let pool2_layer_cfg = PoolingConfig {
-            mode: PoolingMode::Max,
-            filter_shape: vec![3],
-            stride: vec![2],
-            padding: vec![0], // TODO: make optional
-        };
+        let pool2_layer_cfg = PoolingConfig::new(PoolingMode::Max, &[3]).stride(&[2]);
         let mut pool2_cfg = LayerConfig::new("pool2", LayerType::Pooling(pool2_layer_cfg));
         pool2_cfg.add_input("conv2_out");
         pool2_cfg.add_output("pool2_out");
         cfg.add_layer(pool2_cfg);
         // Layer: conv3
-        let conv3_layer_cfg = ConvolutionConfig {
-            num_output: 142,
-            filter_shape: vec![3],
-            padding: vec![1],
-            stride: vec![1]
-        };
+        let conv3_layer_cfg = ConvolutionConfig::new(142, &[3]).padding(&[1]);
         let mut conv3_cfg = LayerConfig::new("conv3", LayerType::Convolution(conv3_layer_cfg));
         conv3_cfg.add_input("pool2_out");
         conv3_cfg.add_output("conv3_preac");
@@ -349,12 +277,7 @@ mod cuda {
         conv3_relu_cfg.add_output("conv3_out");
         cfg.add_layer(conv3_relu_cfg);
         // Layer: conv4
-        let conv4_layer_cfg = ConvolutionConfig {
-            num_output: 128,
-            filter_shape: vec![3],
-            padding: vec![1],
-            stride: vec![1]
-        };
+        let conv4_layer_cfg = ConvolutionConfig::new(128, &[3]).padding(&[1]);
         let mut conv4_cfg = LayerConfig::new("conv4", LayerType::Convolution(conv4_layer_cfg));
         conv4_cfg.add_input("conv3_out");
         conv4_cfg.add_output("conv4_preac");
@@ -365,12 +288,7 @@ mod cuda {
         conv4_relu_cfg.add_output("conv4_out");
         cfg.add_layer(conv4_relu_cfg);
         // Layer: conv5
-        let conv5_layer_cfg = ConvolutionConfig {
-            num_output: 128,
-            filter_shape: vec![3],
-            padding: vec![1],
-            stride: vec![1]
-        };
+        let conv5_layer_cfg = ConvolutionConfig::new(128, &[3]).padding(&[1]);
         let mut conv5_cfg = LayerConfig::new("conv5", LayerType::Convolution(conv5_layer_cfg));
         conv5_cfg.add_input("conv4_out");
         conv5_cfg.add_output("conv5_preac");
@@ -381,30 +299,25 @@ mod cuda {
         conv5_relu_cfg.add_output("conv5_out");
         cfg.add_layer(conv5_relu_cfg);
         // Layer: pool3
-        let pool3_layer_cfg = PoolingConfig {
-            mode: PoolingMode::Max,
-            filter_shape: vec![3],
-            stride: vec![2],
-            padding: vec![0], // TODO: make optional
-        };
+        let pool3_layer_cfg = PoolingConfig::new(PoolingMode::Max, &[3]).stride(&[2]);
         let mut pool3_cfg = LayerConfig::new("pool3", LayerType::Pooling(pool3_layer_cfg));
         pool3_cfg.add_input("conv5_out");
         pool3_cfg.add_output("pool3_out");
         cfg.add_layer(pool3_cfg);
         // Layer: fc1
-        let fc1_layer_cfg = LinearConfig { output_size: 2048 };
+        let fc1_layer_cfg = LinearConfig::new(2048);
         let mut fc1_cfg = LayerConfig::new("fc1", LayerType::Linear(fc1_layer_cfg));
         fc1_cfg.add_input("pool3_out");
         fc1_cfg.add_output("fc1_out");
         cfg.add_layer(fc1_cfg);
         // Layer: fc2
-        let fc2_layer_cfg = LinearConfig { output_size: 2048 };
+        let fc2_layer_cfg = LinearConfig::new(2048);
         let mut fc2_cfg = LayerConfig::new("fc2", LayerType::Linear(fc2_layer_cfg));
         fc2_cfg.add_input("fc1_out");
         fc2_cfg.add_output("fc2_out");
         cfg.add_layer(fc2_cfg);
         // Layer: fc3
-        let fc3_layer_cfg = LinearConfig { output_size: 500 };
+        let fc3_layer_cfg = LinearConfig::new(500);
         let mut fc3_cfg = LayerConfig::new("fc3", LayerType::Linear(fc3_layer_cfg));
         fc3_cfg.add_input("fc2_out");
         fc3_cfg.add_output("fc3_out");
@@ -420,8 +333,60 @@ mod cuda {
 
             let inp_lock = Arc::new(RwLock::new(inp));
             network.forward(&[inp_lock]);
+            network.synchronize();
         };
-        { func(); bench_profile(b, func, 10); }
+        { func(); bench_profile(b, func); }
+    }
+
+}
+
+#[cfg(feature = "native")]
+mod native {
+    use criterion::{Bencher, Criterion};
+    use co::prelude::*;
+
+    use std::sync::{Arc, RwLock};
+    use leaf::layers::*;
+    use leaf::layer::*;
+    use std::rc::Rc;
+
+    fn native_backend() -> Rc<Backend<Native>> {
+        Rc::new(Backend::<Native>::default().unwrap())
     }
 
+    /// Forward and backward pass of a MNIST sized MLP with `num_threads` native threads.
+    fn mnist_mlp_train(b: &mut Bencher, num_threads: usize) {
+        let mut cfg = SequentialConfig::default();
+        cfg.add_input("data", &vec![128, 784]);
+        cfg.add_layer(LayerConfig::new("fc1", LinearConfig::new(512).num_threads(num_threads)));
+        cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+        cfg.add_layer(LayerConfig::new("fc2", LinearConfig::new(10).num_threads(num_threads)));
+
+        let backend = native_backend();
+        let mut network = Layer::from_config(
+            backend.clone(), &LayerConfig::new("network", LayerType::Sequential(cfg)));
+
+        let inp = SharedTensor::<f32>::new(backend.device(), &vec![128, 784]).unwrap();
+        let inp_lock = Arc::new(RwLock::new(inp));
+        b.iter(|| {
+            let output = network.forward(&[inp_lock.clone()])[0].clone();
+            network.backward(&[output]);
+        });
+    }
+
+    pub fn benches(c: &mut Criterion) {
+        c.bench_function("mnist_mlp_train_1_thread", |b| mnist_mlp_train(b, 1));
+        c.bench_function("mnist_mlp_train_4_threads", |b| mnist_mlp_train(b, 4));
+    }
+}
+
+#[allow(unused_variables)]
+fn benches(c: &mut Criterion) {
+    #[cfg(feature = "native")]
+    native::benches(c);
+    #[cfg(feature = "cuda")]
+    cuda::benches(c);
 }
+
+criterion_group!(network_benches, benches);
+criterion_main!(network_benches);
@@ -0,0 +1,93 @@
+extern crate env_logger;
+extern crate collenchyma as co;
+extern crate leaf;
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use co::prelude::*;
+
+use leaf::inference::ChunkedInference;
+use leaf::layer::*;
+use leaf::layers::*;
+use leaf::mnist::load_mnist;
+use leaf::models;
+use leaf::solver::*;
+use leaf::util::{LayerOps, native_backend};
+
+const BATCH_SIZE: usize = 32;
+const EPOCHS: usize = 2;
+
+fn main() {
+    env_logger::init().unwrap();
+
+    let data_dir = match env::args().nth(1) {
+        Some(data_dir) => PathBuf::from(data_dir),
+        None => {
+            println!("No data directory specified. Default: `target/mnist`.");
+            PathBuf::from("target/mnist")
+        }
+    };
+    run_mnist(&data_dir);
+}
+
+/// Load the decompressed MNIST files `<name>-images-idx3-ubyte` and `<name>-labels-idx1-ubyte` from `data_dir`.
+fn load_part(data_dir: &Path, name: &str) -> Dataset {
+    let images = data_dir.join(format!("{}-images-idx3-ubyte", name));
+    let labels = data_dir.join(format!("{}-labels-idx1-ubyte", name));
+    match load_mnist(&images, &labels) {
+        Ok(dataset) => dataset,
+        Err(err) => panic!("Failed to load {} and {}: {}. Download and decompress the MNIST files from http://yann.lecun.com/exdb/mnist/ first.",
+                           images.display(), labels.display(), err),
+    }
+}
+
+/// Return the fraction of the samples in `dataset` that `network` classifies correctly.
+fn accuracy<B: IBackend + LayerOps<f32> + 'static>(network: &Layer<B>, dataset: &Dataset) -> f32 {
+    let mut inputs = Vec::new();
+    let mut labels = Vec::new();
+    for index in 0..dataset.len() {
+        let (input, target) = dataset.sample(index);
+        inputs.extend(input);
+        labels.push(target[0] as usize);
+    }
+
+    let mut inference = ChunkedInference::new(network, 64 * 1024 * 1024).unwrap();
+    let outputs = inference.run(&inputs).unwrap();
+    let num_correct = outputs.chunks(10).zip(labels.iter()).filter(|&(scores, &label)| {
+        let prediction = scores.iter().enumerate().fold(0, |best, (class, &score)| if score > scores[best] { class } else { best });
+        prediction == label
+    }).count();
+    num_correct as f32 / labels.len() as f32
+}
+
+fn run_mnist(data_dir: &Path) {
+    let train = load_part(data_dir, "train");
+    let test = load_part(data_dir, "t10k");
+    println!("Loaded {} training and {} test samples.", train.len(), test.len());
+
+    let mut objective_cfg = SequentialConfig::default();
+    objective_cfg.add_input("network_out", &[BATCH_SIZE, 10]);
+    objective_cfg.add_input("label", &[BATCH_SIZE]);
+    objective_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(10) }));
+
+    let solver_cfg = SolverConfig {
+        network: LayerConfig::new("mnist", models::mlp(BATCH_SIZE, &[784, 100, 10])),
+        objective: LayerConfig::new("classifier", objective_cfg),
+        base_lr: 0.01f32,
+        ..SolverConfig::default()
+    };
+    let backend = Rc::new(native_backend());
+    let mut solver = Solver::from_config(backend.clone(), backend.clone(), &solver_cfg).unwrap();
+
+    solver.fit_with_callback(&train, EPOCHS, |solver, summary| {
+        println!("Epoch {}: loss {:.4}, test accuracy {:.2}%",
+                 summary.epoch, summary.loss, accuracy(solver.network(), &test) * 100f32);
+    });
+
+    let path = "target/mnist_network";
+    solver.mut_network().save(path).unwrap();
+    let loaded = Layer::<Backend<Native>>::load(backend.clone(), path).unwrap();
+    println!("Test accuracy of the network loaded from {}: {:.2}%", path, accuracy(&loaded, &test) * 100f32);
+}
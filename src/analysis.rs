@@ -0,0 +1,121 @@
+//! Provides diagnostics of the activations of a network.
+//!
+//! [activation_stats][1] runs a minibatch through a network and summarizes the outputs
+//! of every layer, e.g. to find layers whose activations vanish or explode during training.
+//! Units of ReLU layers that are zero for every sample of the minibatch are reported as
+//! dead units, as they don't pass on any gradient either.
+//! [1]: ./fn.activation_stats.html
+use std::sync::Arc;
+use co::{IBackend, SharedTensor};
+use layer::{Layer, LayerType};
+use layers::FusedActivation;
+use logging::Subsystem;
+use log::LogLevel;
+use util::{ArcLock, LayerOps, tensor_to_vec};
+
+#[derive(Debug, Clone)]
+/// Summary of one output of a layer for a minibatch.
+pub struct ActivationStats {
+    /// The name of the layer.
+    pub layer: String,
+    /// The name of the output blob.
+    pub output: String,
+    /// The fraction of values that are exactly zero.
+    pub fraction_zero: f32,
+    /// The smallest value.
+    pub min: f32,
+    /// The largest value.
+    pub max: f32,
+    /// The mean of the values.
+    pub mean: f32,
+    /// The number of values in each of the bins of equal width between `min` and `max`.
+    pub histogram: Vec<usize>,
+    /// The units that are zero for every sample of the minibatch, if the layer is a ReLU layer.
+    ///
+    /// A unit is an index into a single sample, i.e. the output without the batch dimension.
+    pub dead_units: Vec<usize>,
+}
+
+impl ActivationStats {
+    /// Returns whether the output has units that are zero for every sample.
+    pub fn has_dead_units(&self) -> bool {
+        !self.dead_units.is_empty()
+    }
+}
+
+/// Run the minibatch `inputs` through `network` and summarize the outputs of every layer.
+///
+/// The outputs are listed in the order of [Layer::layer_outputs][1]. Outputs that are computed
+/// in-place are only summarized once, for the last layer that writes them, so a Linear layer
+/// followed by an in-place ReLU is summarized as the ReLU. The histograms have `num_bins` bins.
+/// [1]: ../layer/struct.Layer.html#method.layer_outputs
+///
+/// ReLU layers with dead units are logged as warnings.
+pub fn activation_stats<B: IBackend + LayerOps<f32> + 'static>(network: &mut Layer<B>,
+                                                             inputs: &[ArcLock<SharedTensor<f32>>],
+                                                             num_bins: usize) -> Vec<ActivationStats> {
+    network.forward(inputs);
+    network.synchronize();
+
+    let layer_outputs = network.layer_outputs();
+    let mut stats = Vec::new();
+    for (n, layer) in layer_outputs.iter().enumerate() {
+        for (output_id, output) in layer.outputs.iter().enumerate() {
+            let is_overwritten = layer_outputs[n + 1..].iter()
+                .any(|later_layer| later_layer.outputs.iter().any(|later_output| Arc::ptr_eq(output, later_output)));
+            if is_overwritten {
+                continue;
+            }
+            let output_name = layer.output_names.get(output_id).cloned().unwrap_or_else(|| layer.name.clone());
+            let batch_size = output.read().unwrap().desc().get(0).cloned().unwrap_or(1);
+            let summary = summarize(&layer.name, &output_name, &tensor_to_vec(output), batch_size, is_relu(&layer.layer_type), num_bins);
+            if summary.has_dead_units() {
+                log_event!(Subsystem::Forward, LogLevel::Warn, "dead_units", layer = layer.name, output = output_name,
+                           dead_units = summary.dead_units.len());
+            }
+            stats.push(summary);
+        }
+    }
+    stats
+}
+
+fn is_relu(layer_type: &LayerType) -> bool {
+    match *layer_type {
+        LayerType::ReLU => true,
+        LayerType::FusedLinear(ref config) => config.activation == FusedActivation::ReLU,
+        _ => false,
+    }
+}
+
+fn summarize(layer: &str, output: &str, values: &[f32], batch_size: usize, find_dead_units: bool, num_bins: usize) -> ActivationStats {
+    let min = values.iter().cloned().fold(::std::f32::INFINITY, f32::min);
+    let max = values.iter().cloned().fold(::std::f32::NEG_INFINITY, f32::max);
+    let num_zero = values.iter().filter(|&&value| value == 0f32).count();
+
+    let mut histogram = vec![0; num_bins];
+    if num_bins > 0 {
+        for &value in values {
+            let bin = if max > min { ((value - min) / (max - min) * num_bins as f32) as usize } else { 0 };
+            histogram[::std::cmp::min(bin, num_bins - 1)] += 1;
+        }
+    }
+
+    let mut dead_units = Vec::new();
+    if find_dead_units && batch_size > 0 {
+        let num_units = values.len() / batch_size;
+        dead_units = (0..num_units)
+            .filter(|&unit| (0..batch_size).all(|sample| values[sample * num_units + unit] == 0f32))
+            .collect();
+    }
+
+    ActivationStats {
+        layer: layer.to_owned(),
+        output: output.to_owned(),
+        fraction_zero: num_zero as f32 / values.len() as f32,
+        min: min,
+        max: max,
+        mean: values.iter().fold(0f32, |sum, value| sum + value) / values.len() as f32,
+        histogram: histogram,
+        dead_units: dead_units,
+    }
+}
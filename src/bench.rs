@@ -0,0 +1,208 @@
+//! Provides throughput benchmarks of networks.
+//!
+//! [bench_network][1] measures how long the forward, backward-input and backward-parameters
+//! steps of a network take for a given batch size and returns a [BenchResult][2] with
+//! [statistics][3] of the measurements, so the results can be compared across versions
+//! and backends without an external benchmark harness.
+//! [1]: ./fn.bench_network.html
+//! [2]: ./struct.BenchResult.html
+//! [3]: ./struct.TimingStats.html
+//!
+//! ```
+//! # extern crate leaf;
+//! # #[cfg(feature = "native")]
+//! # mod native {
+//! # use std::rc::Rc;
+//! # use leaf::bench::{bench_network, BenchConfig};
+//! # use leaf::layer::LayerConfig;
+//! # use leaf::models;
+//! # use leaf::util;
+//! # pub fn test() {
+//! let backend = Rc::new(util::native_backend());
+//! let config = LayerConfig::new("mlp", models::mlp(1, &[20, 50, 10]));
+//! let result = bench_network(backend, &config, 64, &BenchConfig::default()).unwrap();
+//! println!("{}", result);
+//! # }}
+//! #
+//! # #[cfg(not(feature = "native"))]
+//! # mod native {
+//! # pub fn test() {}
+//! # }
+//! #
+//! # fn main() {
+//! #     if cfg!(feature = "native") {
+//! #         ::native::test();
+//! #    }
+//! # }
+//! ```
+use std::fmt;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use co::{IBackend, ITensorDesc};
+use layer::{Layer, LayerConfig, LayerType};
+use util::{LayerOps, tensor_from_slice};
+
+#[derive(Debug, Clone, Copy)]
+/// Specifies how a benchmark is run.
+pub struct BenchConfig {
+    /// The number of runs of each step before the measurements start.
+    ///
+    /// The warmup allocates memory and syncs the input to the device of the network,
+    /// which would otherwise be included in the first measurement.
+    ///
+    /// Default: `10`
+    pub warmup: usize,
+    /// The number of measured runs of each step.
+    ///
+    /// Default: `10`
+    pub iterations: usize,
+}
+
+impl Default for BenchConfig {
+    fn default() -> BenchConfig {
+        BenchConfig {
+            warmup: 10,
+            iterations: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Statistics of the durations of a benchmarked step, in seconds.
+pub struct TimingStats {
+    /// The duration of every measured run.
+    pub samples: Vec<f64>,
+    /// The mean duration.
+    pub mean: f64,
+    /// The standard deviation of the durations.
+    pub std_dev: f64,
+    /// The shortest duration.
+    pub min: f64,
+    /// The longest duration.
+    pub max: f64,
+}
+
+impl TimingStats {
+    /// Compute the statistics of the durations `samples`.
+    pub fn from_samples(samples: Vec<f64>) -> TimingStats {
+        let num_samples = ::std::cmp::max(samples.len(), 1) as f64;
+        let mean = samples.iter().fold(0f64, |sum, sample| sum + sample) / num_samples;
+        let variance = samples.iter().fold(0f64, |sum, sample| sum + (sample - mean).powi(2)) / num_samples;
+        TimingStats {
+            mean: mean,
+            std_dev: variance.sqrt(),
+            min: samples.iter().cloned().fold(::std::f64::INFINITY, f64::min),
+            max: samples.iter().cloned().fold(0f64, f64::max),
+            samples: samples,
+        }
+    }
+}
+
+impl fmt::Display for TimingStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ± {} (min {}, max {})",
+               autoscale_time(self.mean), autoscale_time(self.std_dev), autoscale_time(self.min), autoscale_time(self.max))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The measurements of a benchmarked network.
+pub struct BenchResult {
+    /// The name of the network.
+    pub name: String,
+    /// The batch size the network was run with.
+    pub batch_size: usize,
+    /// The durations of the forward step.
+    pub forward: TimingStats,
+    /// The durations of the backward step that computes the input gradients.
+    pub backward_input: TimingStats,
+    /// The durations of the backward step that computes the weight gradients.
+    pub backward_parameters: TimingStats,
+}
+
+impl BenchResult {
+    /// Returns the number of samples per second that can be trained on, based on the mean
+    /// durations of all three steps.
+    pub fn samples_per_second(&self) -> f64 {
+        self.batch_size as f64 / (self.forward.mean + self.backward_input.mean + self.backward_parameters.mean)
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "{} with batch size {}:", self.name, self.batch_size));
+        try!(writeln!(f, "  forward:             {}", self.forward));
+        try!(writeln!(f, "  backward input:      {}", self.backward_input));
+        try!(writeln!(f, "  backward parameters: {}", self.backward_parameters));
+        write!(f, "  throughput:          {:.1} samples/s", self.samples_per_second())
+    }
+}
+
+/// Benchmark the network described by `config` with a batch size of `batch_size`.
+///
+/// The container described by `config` is created with the batch size `batch_size`
+/// (see [SequentialConfig::set_batch_size][1]) and its inputs are filled with zeros.
+/// Every step is run `warmup` times and then measured `iterations` times, waiting for the
+/// backend to finish after every run (see [Layer::synchronize][2]).
+/// [1]: ../layers/container/struct.SequentialConfig.html#method.set_batch_size
+/// [2]: ../layer/struct.Layer.html#method.synchronize
+///
+/// Returns an error if `config` is not a container or its batch size can't be changed.
+pub fn bench_network<B: IBackend + LayerOps<f32> + 'static>(backend: Rc<B>,
+                                                            config: &LayerConfig,
+                                                            batch_size: usize,
+                                                            bench_config: &BenchConfig) -> Result<BenchResult, String> {
+    let mut config = config.clone();
+    match config.layer_type {
+        LayerType::Sequential(ref mut container_config) => try!(container_config.set_batch_size(batch_size)),
+        _ => return Err(format!("Layer {} is not a container, so it can not be benchmarked with batch size {}", config.name, batch_size)),
+    }
+    let mut network = Layer::from_config(backend.clone(), &config);
+
+    let mut inputs = Vec::new();
+    for (_, shape) in network.input_shapes() {
+        let input = try!(tensor_from_slice(&*backend, &shape, &vec![0f32; shape.size()]));
+        inputs.push(Arc::new(RwLock::new(input)));
+    }
+
+    let forward = measure(&mut network, bench_config, |network| { network.forward(&inputs); });
+    let backward_input = measure(&mut network, bench_config, |network| { network.backward_input(&[]); });
+    let backward_parameters = measure(&mut network, bench_config, |network| network.backward_parameters());
+
+    Ok(BenchResult {
+        name: config.name.clone(),
+        batch_size: batch_size,
+        forward: forward,
+        backward_input: backward_input,
+        backward_parameters: backward_parameters,
+    })
+}
+
+/// Run `step` on `network` `warmup` times and measure `iterations` further runs of it.
+fn measure<B: IBackend + LayerOps<f32> + 'static, F: FnMut(&mut Layer<B>)>(network: &mut Layer<B>, bench_config: &BenchConfig, mut step: F) -> TimingStats {
+    for _ in 0..bench_config.warmup {
+        step(network);
+        network.synchronize();
+    }
+    let samples = (0..bench_config.iterations).map(|_| {
+        timeit_loops!(1, {
+            step(network);
+            network.synchronize();
+        })
+    }).collect();
+    TimingStats::from_samples(samples)
+}
+
+/// Format a duration in seconds with a fitting unit.
+fn autoscale_time(sec: f64) -> String {
+    let (div, unit) = if sec > 1.0 {
+        (1.0, "s")
+    } else if sec > 0.001 {
+        (0.001, "ms")
+    } else if sec > 0.000_001 {
+        (0.000_001, "µs")
+    } else {
+        (0.000_000_001, "ns")
+    };
+    format!("{:.5} {}", sec / div, unit)
+}
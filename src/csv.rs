@@ -0,0 +1,300 @@
+//! Provides reading of tabular data from CSV files.
+//!
+//! A [CsvReader][1] parses each row of a CSV file into the features and the label of a sample.
+//! The resulting [TabularDataset][2] can be used with [Solver::fit][3], or converted
+//! to minibatches for [Solver::train_minibatch][4].
+//! [1]: ./struct.CsvReader.html
+//! [2]: ./struct.TabularDataset.html
+//! [3]: ../solver/struct.Solver.html#method.fit
+//! [4]: ../solver/struct.Solver.html#method.train_minibatch
+//!
+//! Feature values have to be numbers or booleans (`true`/`false`), which are read as `1` and `0`.
+//! Labels can also be categorical, i.e. arbitrary text, in which case they are converted to
+//! class indices in the order of their first appearance. Fields may be quoted, but quoted
+//! fields can not span multiple lines.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use solver::IDataset;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Selects a column of a CSV file.
+pub enum Column {
+    /// The column at the index, starting at 0.
+    Index(usize),
+    /// The column with the name in the header row.
+    Name(String),
+}
+
+impl<'a> From<&'a str> for Column {
+    fn from(name: &'a str) -> Column {
+        Column::Name(name.to_owned())
+    }
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Column {
+        Column::Index(index)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Reads a [TabularDataset][1] from CSV.
+/// [1]: ./struct.TabularDataset.html
+pub struct CsvReader {
+    label_column: Column,
+    feature_columns: Option<Vec<Column>>,
+    delimiter: char,
+    has_header: bool,
+    one_hot: bool,
+}
+
+impl CsvReader {
+    /// Create a CsvReader that reads the label of each sample from `label_column`.
+    ///
+    /// By default all other columns are used as features, the first row is a header
+    /// and fields are separated by `,`.
+    pub fn new<C: Into<Column>>(label_column: C) -> CsvReader {
+        CsvReader {
+            label_column: label_column.into(),
+            feature_columns: None,
+            delimiter: ',',
+            has_header: true,
+            one_hot: false,
+        }
+    }
+
+    /// Only use `columns` as features, in the provided order.
+    pub fn feature_columns<C: Into<Column>>(mut self, columns: Vec<C>) -> CsvReader {
+        self.feature_columns = Some(columns.into_iter().map(|column| column.into()).collect());
+        self
+    }
+
+    /// Set the character that separates the fields of a row.
+    pub fn delimiter(mut self, delimiter: char) -> CsvReader {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set whether the first row is a header that contains the names of the columns.
+    pub fn has_header(mut self, has_header: bool) -> CsvReader {
+        self.has_header = has_header;
+        self
+    }
+
+    /// Set whether the labels should be one-hot encoded.
+    ///
+    /// Integer labels are used as the index of the class; categorical labels
+    /// are assigned class indices in the order of their first appearance.
+    pub fn one_hot(mut self, one_hot: bool) -> CsvReader {
+        self.one_hot = one_hot;
+        self
+    }
+
+    /// Read the CSV file at the specified path.
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> io::Result<TabularDataset> {
+        let file = try!(File::open(path.as_ref()));
+        self.read(BufReader::new(file))
+    }
+
+    /// Read CSV from `reader`.
+    pub fn read<R: BufRead>(&self, reader: R) -> io::Result<TabularDataset> {
+        let mut lines = reader.lines().enumerate().filter(|&(_, ref line)| {
+            line.as_ref().map(|line| !line.trim().is_empty()).unwrap_or(true)
+        });
+
+        let header = if self.has_header {
+            match lines.next() {
+                Some((_, line)) => Some(self.split(&try!(line))),
+                None => None,
+            }
+        } else {
+            None
+        };
+
+        let mut features = Vec::new();
+        let mut raw_labels = Vec::new();
+        let mut columns = None;
+        for (line_number, line) in lines {
+            let fields = self.split(&try!(line));
+            if columns.is_none() {
+                columns = Some(try!(self.resolve_columns(header.as_ref(), fields.len())));
+            }
+            let &(label_index, ref feature_indices) = columns.as_ref().unwrap();
+            let error = |message: String| invalid_data(&format!("Line {}: {}", line_number + 1, message));
+
+            let mut row = Vec::with_capacity(feature_indices.len());
+            for &index in feature_indices {
+                let field = try!(fields.get(index).ok_or(error(format!("Missing column {}", index))));
+                row.push(try!(parse_value(field).ok_or(error(format!("Could not parse '{}' as a number", field)))));
+            }
+            features.push(row);
+            raw_labels.push(try!(fields.get(label_index).ok_or(error(format!("Missing column {}", label_index)))).clone());
+        }
+
+        let numeric_labels = raw_labels.iter().map(|label| parse_value(label)).collect::<Option<Vec<f32>>>();
+        let integer_labels = numeric_labels.as_ref().map_or(false, |labels| labels.iter().all(|&label| label >= 0f32 && label.fract() == 0f32));
+        let (labels, classes) = match numeric_labels {
+            Some(labels) if !self.one_hot || integer_labels => (labels, Vec::new()),
+            _ => {
+                let mut classes: Vec<String> = Vec::new();
+                let labels = raw_labels.iter().map(|label| {
+                    match classes.iter().position(|class| class == label) {
+                        Some(index) => index as f32,
+                        None => {
+                            classes.push(label.clone());
+                            (classes.len() - 1) as f32
+                        }
+                    }
+                }).collect();
+                (labels, classes)
+            }
+        };
+
+        let num_classes = if !classes.is_empty() {
+            classes.len()
+        } else {
+            labels.iter().fold(0f32, |max, &label| max.max(label + 1f32)) as usize
+        };
+
+        Ok(TabularDataset {
+            num_features: features.get(0).map_or(0, |row| row.len()),
+            features: features,
+            labels: labels,
+            classes: classes,
+            num_classes: if self.one_hot { Some(num_classes) } else { None },
+        })
+    }
+
+    /// Split a row into its fields.
+    fn split(&self, line: &str) -> Vec<String> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if quoted {
+                match c {
+                    '"' if chars.peek() == Some(&'"') => {
+                        field.push('"');
+                        chars.next();
+                    }
+                    '"' => quoted = false,
+                    _ => field.push(c),
+                }
+            } else if c == '"' {
+                quoted = true;
+            } else if c == self.delimiter {
+                fields.push(field.trim().to_owned());
+                field.clear();
+            } else {
+                field.push(c);
+            }
+        }
+        fields.push(field.trim().to_owned());
+        fields
+    }
+
+    /// Return the index of the label column and the indices of the feature columns.
+    fn resolve_columns(&self, header: Option<&Vec<String>>, num_columns: usize) -> io::Result<(usize, Vec<usize>)> {
+        let resolve = |column: &Column| {
+            match *column {
+                Column::Index(index) => Ok(index),
+                Column::Name(ref name) => {
+                    match header.and_then(|header| header.iter().position(|column_name| column_name == name)) {
+                        Some(index) => Ok(index),
+                        None => Err(invalid_data(&format!("Unknown column '{}'", name))),
+                    }
+                }
+            }
+        };
+
+        let label_index = try!(resolve(&self.label_column));
+        let feature_indices = match self.feature_columns {
+            Some(ref columns) => {
+                let mut indices = Vec::with_capacity(columns.len());
+                for column in columns {
+                    indices.push(try!(resolve(column)));
+                }
+                indices
+            }
+            None => (0..num_columns).filter(|&index| index != label_index).collect(),
+        };
+        Ok((label_index, feature_indices))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Samples with numeric features and a label, e.g. read from a CSV file by a [CsvReader][1].
+/// [1]: ./struct.CsvReader.html
+pub struct TabularDataset {
+    features: Vec<Vec<f32>>,
+    num_features: usize,
+    labels: Vec<f32>,
+    classes: Vec<String>,
+    num_classes: Option<usize>,
+}
+
+impl TabularDataset {
+    /// Return the number of features of each sample.
+    pub fn num_features(&self) -> usize {
+        self.num_features
+    }
+
+    /// Return the features of all samples.
+    pub fn features(&self) -> &[Vec<f32>] {
+        &self.features
+    }
+
+    /// Return the labels of all samples.
+    ///
+    /// Categorical labels are returned as their class index.
+    pub fn labels(&self) -> &[f32] {
+        &self.labels
+    }
+
+    /// Return the names of the classes if the labels are categorical.
+    ///
+    /// The index of a name is its class index.
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+}
+
+impl IDataset for TabularDataset {
+    fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    fn target_shape(&self) -> Vec<usize> {
+        match self.num_classes {
+            Some(num_classes) => vec![num_classes],
+            None => vec![],
+        }
+    }
+
+    fn sample(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        let target = match self.num_classes {
+            Some(num_classes) => {
+                let mut one_hot = vec![0f32; num_classes];
+                one_hot[self.labels[index] as usize] = 1f32;
+                one_hot
+            }
+            None => vec![self.labels[index]],
+        };
+        (self.features[index].clone(), target)
+    }
+}
+
+/// Parse a number or a boolean.
+fn parse_value(field: &str) -> Option<f32> {
+    match field {
+        "true" | "True" | "TRUE" => Some(1f32),
+        "false" | "False" | "FALSE" => Some(0f32),
+        _ => field.parse::<f32>().ok(),
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
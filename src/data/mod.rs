@@ -0,0 +1,5 @@
+//! Provides preprocessing of raw data into the numeric samples and tensors a network consumes.
+//!
+//! - [text][1] builds a vocabulary of words and converts texts into fixed length sequences of word ids.
+//! [1]: ./text/index.html
+pub mod text;
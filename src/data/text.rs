@@ -0,0 +1,214 @@
+//! Provides word-level preprocessing of text.
+//!
+//! A [Tokenizer][1] splits a text into words. A [VocabularyBuilder][2] counts the words of a
+//! corpus and assigns an id to each of them, and the resulting [Vocabulary][3] converts texts
+//! into sequences of word ids, e.g. as input for a word embedding.
+//! [1]: ./struct.Tokenizer.html
+//! [2]: ./struct.VocabularyBuilder.html
+//! [3]: ./struct.Vocabulary.html
+//!
+//! Two ids are reserved: [PADDING_ID][4] fills up sequences that are shorter than the
+//! requested length and [UNKNOWN_ID][5] replaces words that are not part of the vocabulary.
+//! [4]: ./constant.PADDING_ID.html
+//! [5]: ./constant.UNKNOWN_ID.html
+use std::collections::HashMap;
+use co::SharedTensor;
+use util::{native_backend, write_to_memory};
+
+/// The id of the padding after the end of a sequence.
+pub const PADDING_ID: usize = 0;
+/// The id of all words that are not part of a vocabulary.
+pub const UNKNOWN_ID: usize = 1;
+
+const PADDING_WORD: &'static str = "<pad>";
+const UNKNOWN_WORD: &'static str = "<unk>";
+
+#[derive(Debug, Clone, Copy)]
+/// Splits text into words.
+///
+/// A word is a sequence of alphanumeric characters; all other characters separate words
+/// and are dropped.
+pub struct Tokenizer {
+    lowercase: bool,
+}
+
+impl Tokenizer {
+    /// Create a Tokenizer that converts words to lowercase.
+    pub fn new() -> Tokenizer {
+        Tokenizer {
+            lowercase: true,
+        }
+    }
+
+    /// Set whether words are converted to lowercase.
+    pub fn lowercase(mut self, lowercase: bool) -> Tokenizer {
+        self.lowercase = lowercase;
+        self
+    }
+
+    /// Split `text` into its words.
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| if self.lowercase { word.to_lowercase() } else { word.to_owned() })
+            .collect()
+    }
+}
+
+impl ::std::default::Default for Tokenizer {
+    fn default() -> Tokenizer {
+        Tokenizer::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// Builds a [Vocabulary][1] from a corpus.
+/// [1]: ./struct.Vocabulary.html
+pub struct VocabularyBuilder {
+    tokenizer: Tokenizer,
+    min_count: usize,
+    max_size: Option<usize>,
+}
+
+impl VocabularyBuilder {
+    /// Create a VocabularyBuilder that includes every word of the corpus.
+    pub fn new() -> VocabularyBuilder {
+        VocabularyBuilder {
+            tokenizer: Tokenizer::new(),
+            min_count: 1,
+            max_size: None,
+        }
+    }
+
+    /// Set the Tokenizer that splits the corpus and later the encoded texts into words.
+    pub fn tokenizer(mut self, tokenizer: Tokenizer) -> VocabularyBuilder {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Only include words that appear at least `min_count` times in the corpus.
+    pub fn min_count(mut self, min_count: usize) -> VocabularyBuilder {
+        self.min_count = min_count;
+        self
+    }
+
+    /// Limit the number of ids, including the reserved ones, to `max_size`.
+    ///
+    /// The most frequent words are kept.
+    pub fn max_size(mut self, max_size: usize) -> VocabularyBuilder {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Count the words of `texts` and build the Vocabulary.
+    ///
+    /// Words are assigned ids in the order of their frequency; words with the same
+    /// frequency are ordered by their first appearance.
+    pub fn build<S: AsRef<str>>(&self, texts: &[S]) -> Vocabulary {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for text in texts {
+            for word in self.tokenizer.tokenize(text.as_ref()) {
+                if let Some(&position) = positions.get(&word) {
+                    counts[position].1 += 1;
+                } else {
+                    positions.insert(word.clone(), counts.len());
+                    counts.push((word, 1));
+                }
+            }
+        }
+        // the sort is stable, so words with the same count keep the order of their first appearance
+        counts.sort_by(|a, b| b.1.cmp(&a.1));
+
+        let mut words = vec![PADDING_WORD.to_owned(), UNKNOWN_WORD.to_owned()];
+        let max_size = self.max_size.unwrap_or(::std::usize::MAX);
+        words.extend(counts.into_iter()
+                           .filter(|&(_, count)| count >= self.min_count)
+                           .map(|(word, _)| word)
+                           .take(max_size.saturating_sub(words.len())));
+        Vocabulary::new(words, self.tokenizer)
+    }
+}
+
+impl ::std::default::Default for VocabularyBuilder {
+    fn default() -> VocabularyBuilder {
+        VocabularyBuilder::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Maps words to ids and converts texts to sequences of ids.
+pub struct Vocabulary {
+    words: Vec<String>,
+    ids: HashMap<String, usize>,
+    tokenizer: Tokenizer,
+}
+
+impl Vocabulary {
+    fn new(words: Vec<String>, tokenizer: Tokenizer) -> Vocabulary {
+        let ids = words.iter().enumerate().map(|(id, word)| (word.clone(), id)).collect();
+        Vocabulary {
+            words: words,
+            ids: ids,
+            tokenizer: tokenizer,
+        }
+    }
+
+    /// Returns the number of ids, including the reserved ones.
+    ///
+    /// This is the input size of a word embedding for the Vocabulary.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns the id of `word`, or [UNKNOWN_ID][1] if it is not part of the Vocabulary.
+    /// [1]: ./constant.UNKNOWN_ID.html
+    pub fn id(&self, word: &str) -> usize {
+        self.ids.get(word).cloned().unwrap_or(UNKNOWN_ID)
+    }
+
+    /// Returns the word with the id `id`.
+    pub fn word(&self, id: usize) -> Option<&str> {
+        self.words.get(id).map(|word| word.as_str())
+    }
+
+    /// Split `text` into words and return their ids.
+    pub fn encode(&self, text: &str) -> Vec<usize> {
+        self.tokenizer.tokenize(text).iter().map(|word| self.id(word)).collect()
+    }
+
+    /// Convert `ids` back to words, skipping the padding.
+    pub fn decode(&self, ids: &[usize]) -> Vec<&str> {
+        ids.iter().filter(|&&id| id != PADDING_ID).map(|&id| self.word(id).unwrap_or(UNKNOWN_WORD)).collect()
+    }
+
+    /// Encode `texts` into one batch of sequences with exactly `length` ids.
+    ///
+    /// Longer texts are truncated at the end, shorter ones are padded at the end with
+    /// [PADDING_ID][1]. The ids have the shape `[batch_size, length]`. As with
+    /// [pad_sequences][2], the mask has the same shape and is `1` for the words of a text and
+    /// `0` for the padding.
+    /// [1]: ./constant.PADDING_ID.html
+    /// [2]: ../../util/fn.pad_sequences.html
+    ///
+    /// Both tensors are allocated on a Native Backend.
+    pub fn encode_batch<S: AsRef<str>>(&self, texts: &[S], length: usize) -> (SharedTensor<f32>, SharedTensor<f32>) {
+        let native = native_backend();
+        let mut ids = vec![PADDING_ID as f32; texts.len() * length];
+        let mut mask = vec![0f32; texts.len() * length];
+        for (i, text) in texts.iter().enumerate() {
+            for (j, id) in self.encode(text.as_ref()).into_iter().take(length).enumerate() {
+                ids[i * length + j] = id as f32;
+                mask[i * length + j] = 1f32;
+            }
+        }
+
+        let mut ids_tensor = SharedTensor::<f32>::new(native.device(), &vec![texts.len(), length]).unwrap();
+        write_to_memory(ids_tensor.get_mut(native.device()).unwrap(), &ids);
+        let mut mask_tensor = SharedTensor::<f32>::new(native.device(), &vec![texts.len(), length]).unwrap();
+        write_to_memory(mask_tensor.get_mut(native.device()).unwrap(), &mask);
+
+        (ids_tensor, mask_tensor)
+    }
+}
@@ -0,0 +1,164 @@
+//! Provides predictions that combine the outputs of several trained networks.
+//!
+//! An [Ensemble][1] holds its members together with a weight for each of them. Every
+//! member gets the same input and their first outputs are combined as configured
+//! by a [Combination][2], which often gives a slightly better accuracy than the best
+//! single member.
+//! [1]: ./struct.Ensemble.html
+//! [2]: ./enum.Combination.html
+use std::cmp;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use co::{IBackend, ITensorDesc};
+use layer::{Layer, WeightTransfer};
+use util::{LayerOps, tensor_to_vec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Defines how the outputs of the members of an [Ensemble][1] are combined.
+/// [1]: ./struct.Ensemble.html
+pub enum Combination {
+    /// The weighted average of the outputs of the members.
+    Average,
+    /// Every member votes for the class with its highest output value.
+    ///
+    /// For every sample the share of the weighted votes that each class received is returned,
+    /// so the class with the highest value is the one chosen by the majority.
+    MajorityVote,
+}
+
+/// Combines the predictions of several networks with the same input and output shapes.
+pub struct Ensemble<B: IBackend + LayerOps<f32> + 'static> {
+    combination: Combination,
+    members: Vec<Layer<B>>,
+    weights: Vec<f32>,
+}
+
+impl<B: IBackend + LayerOps<f32> + 'static> Ensemble<B> {
+    /// Create an Ensemble without members that combines their outputs with `combination`.
+    pub fn new(combination: Combination) -> Ensemble<B> {
+        Ensemble {
+            combination: combination,
+            members: Vec::new(),
+            weights: Vec::new(),
+        }
+    }
+
+    /// Returns how the outputs of the members are combined.
+    pub fn combination(&self) -> Combination {
+        self.combination
+    }
+
+    /// Returns the members of the Ensemble in the order they were added.
+    pub fn members(&self) -> &[Layer<B>] {
+        &self.members
+    }
+
+    /// Returns the weights of the members of the Ensemble.
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// Add a trained network to the Ensemble whose output counts with `weight`.
+    ///
+    /// Panics if `weight` is negative.
+    pub fn add_member(&mut self, member: Layer<B>, weight: f32) {
+        if weight < 0f32 {
+            panic!("The weight of ensemble member {} must not be negative, but is {}", member.name, weight);
+        }
+        self.members.push(member);
+        self.weights.push(weight);
+    }
+
+    /// Load a network that was [saved][1] at `path` and add it as member with `weight`.
+    /// [1]: ../layer/struct.Layer.html#method.save
+    pub fn load_member<P: AsRef<Path>>(&mut self, backend: Rc<B>, path: P, weight: f32) -> io::Result<()> {
+        let member = try!(Layer::<B>::load(backend, path));
+        self.add_member(member, weight);
+        Ok(())
+    }
+
+    /// Copy the weights of a network that was saved at `path` into the member with
+    /// the index `member_id`, matching them by name like [Layer::transplant_weights][1].
+    /// [1]: ../layer/struct.Layer.html#method.transplant_weights
+    ///
+    /// This allows to create the members from the same configuration and restore
+    /// the weights of each of them from a different training run.
+    ///
+    /// Panics if the Ensemble has no member with the index `member_id`.
+    pub fn load_member_weights<P: AsRef<Path>>(&mut self, member_id: usize, path: P) -> io::Result<Vec<WeightTransfer>> {
+        self.members[member_id].transplant_weights(path)
+    }
+
+    /// Run the minibatch `inputs` through every member and return their combined first outputs.
+    ///
+    /// `inputs` is written into the first input of each member, so it has to hold
+    /// the values of a complete minibatch. The result has the size of the first output
+    /// of a member, with the batchsize as its first dimension.
+    ///
+    /// Returns an error if the Ensemble has no members, if the weights of the members
+    /// sum up to zero or if the inputs or outputs of the members don't match.
+    pub fn predict(&mut self, inputs: &[f32]) -> Result<Vec<f32>, String> {
+        if self.members.is_empty() {
+            return Err("The ensemble has no members".to_owned());
+        }
+        let total_weight = self.weights.iter().fold(0f32, |sum, weight| sum + weight);
+        if total_weight <= 0f32 {
+            return Err("The weights of the ensemble members sum up to zero".to_owned());
+        }
+
+        let mut combined: Vec<f32> = Vec::new();
+        let mut output_shape: Option<Vec<usize>> = None;
+        for (member, &weight) in self.members.iter_mut().zip(&self.weights) {
+            let input_name = match member.input_shapes().into_iter().next() {
+                Some((input_name, _)) => input_name,
+                None => return Err(format!("Ensemble member {} has no input", member.name)),
+            };
+            try!(member.fill_input(&input_name, inputs));
+
+            let output = member.forward(&[])[0].clone();
+            let shape = output.read().unwrap().desc().clone();
+            match output_shape {
+                Some(ref expected) if *expected != shape => {
+                    return Err(format!("Ensemble member {} has an output of shape {:?}, but the other members have {:?}",
+                                       member.name, shape, expected));
+                },
+                Some(_) => {},
+                None => {
+                    combined = vec![0f32; shape.size()];
+                    output_shape = Some(shape.clone());
+                },
+            }
+
+            let values = tensor_to_vec(&output);
+            match self.combination {
+                Combination::Average => {
+                    for (combined_value, value) in combined.iter_mut().zip(&values) {
+                        *combined_value += weight * value;
+                    }
+                },
+                Combination::MajorityVote => {
+                    let num_classes = shape.size() / cmp::max(shape.get(0).cloned().unwrap_or(1), 1);
+                    for (votes, sample) in combined.chunks_mut(num_classes).zip(values.chunks(num_classes)) {
+                        // ties are won by the first class, like in an argmax
+                        let vote = sample.iter().enumerate()
+                            .fold(0, |best, (class, value)| if *value > sample[best] { class } else { best });
+                        votes[vote] += weight;
+                    }
+                },
+            }
+        }
+
+        for value in &mut combined {
+            *value /= total_weight;
+        }
+        Ok(combined)
+    }
+}
+
+impl<B: IBackend + LayerOps<f32> + 'static> ::std::fmt::Debug for Ensemble<B> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let names = self.members.iter().map(|member| member.name.as_str()).collect::<Vec<_>>();
+        write!(f, "Ensemble {{ combination: {:?}, members: {:?}, weights: {:?} }}", self.combination, names, self.weights)
+    }
+}
@@ -0,0 +1,1220 @@
+//! Provides decoding and preprocessing of images for image classification.
+//!
+//! [ImageDataset][1] reads image files and converts them to the input of a network,
+//! so images can be used for training with [Solver::fit][2] directly.
+//! [1]: ./struct.ImageDataset.html
+//! [2]: ../solver/struct.Solver.html#method.fit
+//!
+//! PNG and baseline JPEG images are supported. Interlaced PNG images, PNG images with
+//! a bit depth of less than 8 bits and progressive or lossless JPEG images are not supported.
+//! Images with more than [MAX_PIXELS][6] pixels are rejected before they are decoded,
+//! so a small but malicious file can't allocate huge amounts of memory.
+//!
+//! Images can also be written as PNG files, e.g. to inspect the learned weights of a network
+//! with [filter_grid][3], [matrix_image][4] or [save_weight_images][5].
+//! [3]: ./fn.filter_grid.html
+//! [4]: ./fn.matrix_image.html
+//! [5]: ./fn.save_weight_images.html
+//! [6]: ./constant.MAX_PIXELS.html
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use co::{IBackend, SharedTensor};
+use layer::Layer;
+use solver::IDataset;
+use util::{ArcLock, LayerOps, crc32, tensor_to_vec};
+
+const PNG_SIGNATURE: &'static [u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const JPEG_SIGNATURE: &'static [u8] = &[0xFF, 0xD8];
+
+/// The largest number of pixels of an image that is decoded, e.g. 8192 x 8192.
+pub const MAX_PIXELS: usize = 1 << 26;
+
+#[derive(Debug, Clone)]
+/// A decoded image with 8 bits per channel.
+///
+/// The pixels are stored row by row, with the channels of each pixel interleaved.
+pub struct Image {
+    /// The width of the image in pixels.
+    pub width: usize,
+    /// The height of the image in pixels.
+    pub height: usize,
+    /// The number of channels per pixel, e.g. `3` for RGB.
+    pub channels: usize,
+    /// The pixel data.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Load and decode the image at the specified path.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Image> {
+        let mut data = Vec::new();
+        try!(try!(File::open(path.as_ref())).read_to_end(&mut data));
+        Image::decode(&data)
+    }
+
+    /// Decode an image from the contents of an image file.
+    ///
+    /// Returns an error of kind `InvalidData` if the image is corrupted, has an unsupported format
+    /// or has more than [MAX_PIXELS][1] pixels.
+    /// [1]: ./constant.MAX_PIXELS.html
+    pub fn decode(data: &[u8]) -> io::Result<Image> {
+        if data.starts_with(PNG_SIGNATURE) {
+            decode_png(&data[PNG_SIGNATURE.len()..])
+        } else if data.starts_with(JPEG_SIGNATURE) {
+            decode_jpeg(&data[JPEG_SIGNATURE.len()..])
+        } else {
+            Err(invalid_data("Unknown image format"))
+        }
+    }
+
+    /// Encode the image as PNG and write it to the specified path.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = try!(File::create(path.as_ref()));
+        file.write_all(&self.encode_png())
+    }
+
+    /// Encode the image as PNG.
+    ///
+    /// The image data is stored without compression.
+    pub fn encode_png(&self) -> Vec<u8> {
+        let color_type = match self.channels {
+            1 => 0,
+            2 => 4,
+            3 => 2,
+            4 => 6,
+            _ => panic!("PNG images can't have {} channels", self.channels),
+        };
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend(&to_be(self.width as u32));
+        ihdr.extend(&to_be(self.height as u32));
+        ihdr.extend(&[8, color_type, 0, 0, 0]);
+
+        // every scanline starts with the filter type None
+        let mut scanlines = Vec::with_capacity(self.pixels.len() + self.height);
+        for row in self.pixels.chunks(self.width * self.channels) {
+            scanlines.push(0);
+            scanlines.extend(row);
+        }
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_png_chunk(&mut png, b"IHDR", &ihdr);
+        write_png_chunk(&mut png, b"IDAT", &deflate_stored(&scanlines));
+        write_png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Convert the image to `channels` channels.
+    ///
+    /// Supports conversion to grayscale (`1`) and RGB (`3`). Alpha channels are dropped.
+    pub fn to_channels(&self, channels: usize) -> Image {
+        let color = match self.channels {
+            1 | 2 => 1,
+            _ => 3,
+        };
+        let pixels = self.pixels.chunks(self.channels).flat_map(|pixel| {
+            match (color, channels) {
+                (1, 1) | (3, 3) => pixel[..channels].to_vec(),
+                (1, _) => vec![pixel[0]; channels],
+                (_, _) => {
+                    let luma = 0.299f32 * pixel[0] as f32 + 0.587f32 * pixel[1] as f32 + 0.114f32 * pixel[2] as f32;
+                    vec![luma.round() as u8; channels]
+                }
+            }
+        }).collect();
+
+        Image {
+            width: self.width,
+            height: self.height,
+            channels: channels,
+            pixels: pixels,
+        }
+    }
+
+    /// Resize the image to `width` x `height` pixels using bilinear interpolation.
+    ///
+    /// Returns an error if the image or the new size has no pixels.
+    pub fn resize(&self, width: usize, height: usize) -> Result<Image, String> {
+        if width == 0 || height == 0 || self.width == 0 || self.height == 0 {
+            return Err(format!("Can't resize an image of {} x {} pixels to {} x {} pixels", self.width, self.height, width, height));
+        }
+        let scale_x = self.width as f32 / width as f32;
+        let scale_y = self.height as f32 / height as f32;
+        let mut pixels = Vec::with_capacity(width * height * self.channels);
+        for y in 0..height {
+            let src_y = ((y as f32 + 0.5f32) * scale_y - 0.5f32).max(0f32);
+            let y0 = (src_y as usize).min(self.height - 1);
+            let y1 = (y0 + 1).min(self.height - 1);
+            let dy = src_y - y0 as f32;
+            for x in 0..width {
+                let src_x = ((x as f32 + 0.5f32) * scale_x - 0.5f32).max(0f32);
+                let x0 = (src_x as usize).min(self.width - 1);
+                let x1 = (x0 + 1).min(self.width - 1);
+                let dx = src_x - x0 as f32;
+                for c in 0..self.channels {
+                    let at = |x: usize, y: usize| self.pixels[(y * self.width + x) * self.channels + c] as f32;
+                    let top = at(x0, y0) * (1f32 - dx) + at(x1, y0) * dx;
+                    let bottom = at(x0, y1) * (1f32 - dx) + at(x1, y1) * dx;
+                    pixels.push((top * (1f32 - dy) + bottom * dy).round() as u8);
+                }
+            }
+        }
+
+        Ok(Image {
+            width: width,
+            height: height,
+            channels: self.channels,
+            pixels: pixels,
+        })
+    }
+
+    /// Return the pixel values in CHW order, i.e. one plane per channel.
+    pub fn to_chw(&self) -> Vec<f32> {
+        let mut data = Vec::with_capacity(self.pixels.len());
+        for c in 0..self.channels {
+            data.extend(self.pixels.iter().skip(c).step_by(self.channels).map(|&value| value as f32));
+        }
+        data
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A dataset of labeled image files for image classification.
+///
+/// The images are decoded when they are added, converted to the configured
+/// number of channels and resized to the configured shape, so only the resized images
+/// are kept in memory and a broken image is reported by [add_image][1].
+/// [1]: #method.add_image
+///
+/// The input of a sample has the shape `[channels, height, width]` with pixel values
+/// in the range `0..255`, so a minibatch of samples has the NCHW layout.
+pub struct ImageDataset {
+    images: Vec<(Vec<f32>, usize)>,
+    width: usize,
+    height: usize,
+    channels: usize,
+    mean: Option<Vec<f32>>,
+}
+
+impl ImageDataset {
+    /// Create an empty ImageDataset that produces images of `channels` x `height` x `width`.
+    pub fn new(width: usize, height: usize, channels: usize) -> ImageDataset {
+        ImageDataset {
+            images: Vec::new(),
+            width: width,
+            height: height,
+            channels: channels,
+            mean: None,
+        }
+    }
+
+    /// Subtract the per-channel `mean` from the pixel values of all images.
+    ///
+    /// Panics if `mean` doesn't have one value per channel.
+    pub fn with_mean(mut self, mean: &[f32]) -> ImageDataset {
+        if mean.len() != self.channels {
+            panic!("The mean has {} values, but the images have {} channels", mean.len(), self.channels);
+        }
+        self.mean = Some(mean.to_owned());
+        self
+    }
+
+    /// Load the image at `path` and add it with the class `label`.
+    ///
+    /// Returns an error if the image can not be loaded.
+    pub fn add_image<P: AsRef<Path>>(&mut self, path: P, label: usize) -> io::Result<()> {
+        let path = path.as_ref();
+        let data = try!(self.resized_image(path).map_err(|e| io::Error::new(e.kind(), format!("Could not load image {:?}: {}", path, e))));
+        self.images.push((data, label));
+        Ok(())
+    }
+
+    /// Load an image and convert it to the input of a network.
+    pub fn load_image<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<f32>> {
+        let mut data = try!(self.resized_image(path));
+        self.subtract_mean(&mut data);
+        Ok(data)
+    }
+
+    /// Load an image, convert it to the configured channels and size and return it in CHW order.
+    fn resized_image<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<f32>> {
+        let image = try!(Image::load(path));
+        let resized = try!(image.to_channels(self.channels).resize(self.width, self.height).map_err(|e| invalid_data(&e)));
+        Ok(resized.to_chw())
+    }
+
+    fn subtract_mean(&self, data: &mut [f32]) {
+        if let Some(ref mean) = self.mean {
+            let plane_size = self.width * self.height;
+            for (c, plane) in data.chunks_mut(plane_size).enumerate() {
+                for value in plane {
+                    *value -= mean[c];
+                }
+            }
+        }
+    }
+}
+
+impl IDataset for ImageDataset {
+    fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    fn target_shape(&self) -> Vec<usize> {
+        vec![]
+    }
+
+    fn sample(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        let (ref data, label) = self.images[index];
+        let mut data = data.clone();
+        self.subtract_mean(&mut data);
+        (data, vec![label as f32])
+    }
+}
+
+/// Arrange the filters of a weight in a grid image.
+///
+/// The weight is split into filters of the shape `filter_shape`, which is either
+/// `[height, width]` or `[channels, height, width]` with one or three channels, e.g. the
+/// weight of a Convolution layer with the shape `[num_output, channels, height, width]`
+/// or the weight of a Linear layer on images, where every row is one filter.
+/// The values of every filter are scaled to the full range of the image and the filters
+/// are separated by a black line of one pixel.
+///
+/// Returns an error if the weight can not be split into filters of that shape.
+pub fn filter_grid(weight: &ArcLock<SharedTensor<f32>>, filter_shape: &[usize]) -> Result<Image, String> {
+    let (channels, height, width) = match filter_shape.len() {
+        2 => (1, filter_shape[0], filter_shape[1]),
+        3 if filter_shape[0] == 1 || filter_shape[0] == 3 => (filter_shape[0], filter_shape[1], filter_shape[2]),
+        _ => return Err(format!("Filters of the shape {:?} can't be shown as an image", filter_shape)),
+    };
+    let values = tensor_to_vec(weight);
+    let filter_size = channels * height * width;
+    if filter_size == 0 || values.len() % filter_size != 0 {
+        return Err(format!("A weight of the shape {:?} can't be split into filters of the shape {:?}",
+                           weight.read().unwrap().desc(), filter_shape));
+    }
+
+    let num_filters = values.len() / filter_size;
+    let columns = (1..num_filters + 1).find(|columns| columns * columns >= num_filters).unwrap_or(1);
+    let rows = (num_filters + columns - 1) / columns;
+    let grid_width = columns * (width + 1) - 1;
+    let grid_height = rows * (height + 1) - 1;
+    let mut pixels = vec![0u8; grid_width * grid_height * channels];
+    for (n, filter) in values.chunks(filter_size).enumerate() {
+        let scaled = scale_to_pixels(filter);
+        let (left, top) = ((n % columns) * (width + 1), (n / columns) * (height + 1));
+        for c in 0..channels {
+            for y in 0..height {
+                for x in 0..width {
+                    pixels[((top + y) * grid_width + left + x) * channels + c] = scaled[(c * height + y) * width + x];
+                }
+            }
+        }
+    }
+
+    Ok(Image {
+        width: grid_width,
+        height: grid_height,
+        channels: channels,
+        pixels: pixels,
+    })
+}
+
+/// Show a two-dimensional weight, e.g. of a Linear layer, as a grayscale image
+/// with one pixel per value.
+///
+/// Every row of the weight is a row of the image. The values are scaled to the full range
+/// of the image.
+///
+/// Returns an error if the weight does not have two dimensions.
+pub fn matrix_image(weight: &ArcLock<SharedTensor<f32>>) -> Result<Image, String> {
+    let shape = weight.read().unwrap().desc().clone();
+    if shape.len() != 2 {
+        return Err(format!("A weight of the shape {:?} is not a matrix", shape));
+    }
+    Ok(Image {
+        width: shape[1],
+        height: shape[0],
+        channels: 1,
+        pixels: scale_to_pixels(&tensor_to_vec(weight)),
+    })
+}
+
+/// Save the learnable weights of `layer` as PNG images in `directory`.
+///
+/// Weights with four dimensions, like those of Convolution layers, are saved with
+/// [filter_grid][1]; filters with other than one or three channels are shown as one
+/// grayscale filter per channel. Weights with two dimensions, like those of Linear layers,
+/// are saved with [matrix_image][2]. Other weights are skipped.
+/// [1]: ./fn.filter_grid.html
+/// [2]: ./fn.matrix_image.html
+///
+/// The images are named after the weights, with `/` replaced by `_`.
+pub fn save_weight_images<B: IBackend + LayerOps<f32> + 'static, P: AsRef<Path>>(layer: &Layer<B>, directory: P) -> io::Result<()> {
+    let directory = directory.as_ref();
+    try!(fs::create_dir_all(directory));
+    for (name, weight) in layer.learnable_weights_names().iter().zip(layer.learnable_weights_data()) {
+        let shape = weight.read().unwrap().desc().clone();
+        let image = match shape.len() {
+            4 if shape[1] == 1 || shape[1] == 3 => filter_grid(&weight, &shape[1..]),
+            4 => filter_grid(&weight, &shape[2..]),
+            2 => matrix_image(&weight),
+            _ => continue,
+        };
+        let image = try!(image.map_err(|e| invalid_data(&e)));
+        try!(image.save(directory.join(format!("{}.png", name.replace("/", "_")))));
+    }
+    Ok(())
+}
+
+/// Scale `values` linearly to the range `0..255`; constant values become gray.
+fn scale_to_pixels(values: &[f32]) -> Vec<u8> {
+    let min = values.iter().cloned().fold(::std::f32::INFINITY, f32::min);
+    let max = values.iter().cloned().fold(::std::f32::NEG_INFINITY, f32::max);
+    values.iter().map(|&value| {
+        if max > min { ((value - min) / (max - min) * 255f32).round() as u8 } else { 128 }
+    }).collect()
+}
+
+/// Append a PNG chunk with its length and CRC to `png`.
+fn write_png_chunk(png: &mut Vec<u8>, chunk_type: &[u8], data: &[u8]) {
+    png.extend(&to_be(data.len() as u32));
+    let start = png.len();
+    png.extend(chunk_type);
+    png.extend(data);
+    let crc = crc32(0, &png[start..]);
+    png.extend(&to_be(crc));
+}
+
+/// Wrap `data` in a zlib stream of stored (uncompressed) deflate blocks.
+fn deflate_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = if blocks.peek().is_none() { 1 } else { 0 };
+        let len = block.len() as u16;
+        out.extend(&[is_final, len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]);
+        out.extend(block);
+    }
+    out.extend(&to_be(adler32(data)));
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn to_be(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+/// Decode a PNG image, without its signature.
+fn decode_png(data: &[u8]) -> io::Result<Image> {
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut compressed = Vec::new();
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let length = read_be(&data[pos..pos + 4]) as usize;
+        let chunk_type = &data[pos + 4..pos + 8];
+        if pos + 12 + length > data.len() {
+            return Err(invalid_data("Truncated PNG chunk"));
+        }
+        let chunk = &data[pos + 8..pos + 8 + length];
+        match chunk_type {
+            b"IHDR" => header = Some(try!(PngHeader::parse(chunk))),
+            b"PLTE" => palette = chunk.to_vec(),
+            b"IDAT" => compressed.extend(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+        pos += 12 + length;
+    }
+    let header = try!(header.ok_or(invalid_data("Missing PNG header")));
+
+    // skip the zlib header, the adler32 checksum at the end is not verified
+    if compressed.len() < 2 {
+        return Err(invalid_data("Missing PNG image data"));
+    }
+    let stride = header.width * header.channels() * header.bit_depth as usize / 8;
+    let filtered = try!(inflate(&compressed[2..], header.height * (stride + 1)));
+    let samples = try!(unfilter(&header, &filtered));
+
+    let (channels, pixels) = match header.color_type {
+        3 => {
+            let mut pixels = Vec::with_capacity(samples.len() * 3);
+            for &index in &samples {
+                let index = index as usize * 3;
+                if index + 3 > palette.len() {
+                    return Err(invalid_data("Invalid PNG palette index"));
+                }
+                pixels.extend(&palette[index..index + 3]);
+            }
+            (3, pixels)
+        }
+        _ => (header.channels(), samples),
+    };
+
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        channels: channels,
+        pixels: pixels,
+    })
+}
+
+#[derive(Debug, Copy, Clone)]
+struct PngHeader {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+}
+
+impl PngHeader {
+    fn parse(chunk: &[u8]) -> io::Result<PngHeader> {
+        if chunk.len() < 13 {
+            return Err(invalid_data("Invalid PNG header"));
+        }
+        let header = PngHeader {
+            width: read_be(&chunk[0..4]) as usize,
+            height: read_be(&chunk[4..8]) as usize,
+            bit_depth: chunk[8],
+            color_type: chunk[9],
+        };
+        if chunk[12] != 0 {
+            return Err(invalid_data("Interlaced PNG images are not supported"));
+        }
+        try!(check_size(header.width, header.height));
+        match (header.color_type, header.bit_depth) {
+            (0, 8) | (0, 16) | (2, 8) | (2, 16) | (3, 8) | (4, 8) | (4, 16) | (6, 8) | (6, 16) => Ok(header),
+            _ => Err(invalid_data(&format!("Unsupported PNG color type {} with bit depth {}", header.color_type, header.bit_depth))),
+        }
+    }
+
+    /// Return the number of samples per pixel.
+    fn channels(&self) -> usize {
+        match self.color_type {
+            0 | 3 => 1,
+            2 => 3,
+            4 => 2,
+            _ => 4,
+        }
+    }
+}
+
+/// Reverse the PNG scanline filters and reduce 16 bit samples to 8 bits.
+fn unfilter(header: &PngHeader, filtered: &[u8]) -> io::Result<Vec<u8>> {
+    let bytes_per_sample = header.bit_depth as usize / 8;
+    let bpp = header.channels() * bytes_per_sample;
+    let stride = header.width * bpp;
+    if filtered.len() < header.height * (stride + 1) {
+        return Err(invalid_data("Truncated PNG image data"));
+    }
+
+    let mut previous = vec![0u8; stride];
+    let mut samples = Vec::with_capacity(header.width * header.height * header.channels());
+    for line in filtered.chunks(stride + 1).take(header.height) {
+        let mut current = line[1..].to_vec();
+        for i in 0..stride {
+            let left = if i >= bpp { current[i - bpp] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+            let predictor = match line[0] {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                filter => return Err(invalid_data(&format!("Invalid PNG filter type {}", filter))),
+            };
+            current[i] = current[i].wrapping_add(predictor);
+        }
+        samples.extend(current.iter().step_by(bytes_per_sample));
+        previous = current;
+    }
+    Ok(samples)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reads the bits of a deflate stream, starting with the least significant bit.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: usize) -> io::Result<usize> {
+        let mut value = 0;
+        for i in 0..count {
+            if self.pos >= self.data.len() {
+                return Err(invalid_data("Truncated deflate stream"));
+            }
+            value |= ((self.data[self.pos] >> self.bit) as usize & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// Canonical Huffman code of a deflate block.
+struct Huffman {
+    /// number of codes of each length
+    counts: Vec<usize>,
+    /// symbols ordered by their code
+    symbols: Vec<usize>,
+}
+
+impl Huffman {
+    fn new(lengths: &[usize]) -> Huffman {
+        let mut counts = vec![0; 16];
+        for &length in lengths {
+            counts[length] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for length in 1..16 {
+            for (symbol, _) in lengths.iter().enumerate().filter(|&(_, &l)| l == length) {
+                symbols.push(symbol);
+            }
+        }
+        Huffman { counts: counts, symbols: symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> io::Result<usize> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for length in 1..16 {
+            code |= try!(reader.bits(1));
+            let count = self.counts[length];
+            if code < first + count {
+                return Ok(self.symbols[index + code - first]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("Invalid Huffman code in deflate stream"))
+    }
+}
+
+const LENGTH_BASE: [usize; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+                                  35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [usize; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+                                   3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [usize; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+                                    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [usize; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+                                     7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// order in which the code lengths of the code length alphabet are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decompress a raw [deflate][1] stream that decompresses to at most `max_len` bytes.
+/// [1]: https://tools.ietf.org/html/rfc1951
+fn inflate(data: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+    let mut reader = BitReader { data: data, pos: 0, bit: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = try!(reader.bits(1)) == 1;
+        match try!(reader.bits(2)) {
+            0 => {
+                reader.align_to_byte();
+                let pos = reader.pos;
+                if pos + 4 > data.len() {
+                    return Err(invalid_data("Truncated deflate stream"));
+                }
+                let length = data[pos] as usize | (data[pos + 1] as usize) << 8;
+                if pos + 4 + length > data.len() {
+                    return Err(invalid_data("Truncated deflate stream"));
+                }
+                if out.len() + length > max_len {
+                    return Err(invalid_data("Too much data in deflate stream"));
+                }
+                out.extend(&data[pos + 4..pos + 4 + length]);
+                reader.pos = pos + 4 + length;
+            }
+            1 => {
+                let mut lengths = vec![8; 144];
+                lengths.extend(vec![9; 112]);
+                lengths.extend(vec![7; 24]);
+                lengths.extend(vec![8; 8]);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                try!(inflate_block(&mut reader, &mut out, max_len, &literals, &distances));
+            }
+            2 => {
+                let (literals, distances) = try!(read_dynamic_codes(&mut reader));
+                try!(inflate_block(&mut reader, &mut out, max_len, &literals, &distances));
+            }
+            _ => return Err(invalid_data("Invalid deflate block type")),
+        }
+        if last {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+fn read_dynamic_codes(reader: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let num_literals = try!(reader.bits(5)) + 257;
+    let num_distances = try!(reader.bits(5)) + 1;
+    let num_code_lengths = try!(reader.bits(4)) + 4;
+
+    let mut code_lengths = [0; 19];
+    for &symbol in CODE_LENGTH_ORDER.iter().take(num_code_lengths) {
+        code_lengths[symbol] = try!(reader.bits(3));
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(num_literals + num_distances);
+    while lengths.len() < num_literals + num_distances {
+        let (length, repeat) = match try!(code_length_code.decode(reader)) {
+            symbol if symbol < 16 => (symbol, 1),
+            16 => {
+                let previous = try!(lengths.last().cloned().ok_or(invalid_data("Invalid code lengths in deflate stream")));
+                (previous, 3 + try!(reader.bits(2)))
+            }
+            17 => (0, 3 + try!(reader.bits(3))),
+            _ => (0, 11 + try!(reader.bits(7))),
+        };
+        lengths.extend(vec![length; repeat]);
+    }
+    if lengths.len() > num_literals + num_distances {
+        return Err(invalid_data("Invalid code lengths in deflate stream"));
+    }
+
+    Ok((Huffman::new(&lengths[..num_literals]), Huffman::new(&lengths[num_literals..])))
+}
+
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, max_len: usize, literals: &Huffman, distances: &Huffman) -> io::Result<()> {
+    loop {
+        let symbol = try!(literals.decode(reader));
+        if out.len() >= max_len && symbol != 256 {
+            return Err(invalid_data("Too much data in deflate stream"));
+        }
+        if symbol < 256 {
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let symbol = symbol - 257;
+            if symbol >= LENGTH_BASE.len() {
+                return Err(invalid_data("Invalid length in deflate stream"));
+            }
+            let length = LENGTH_BASE[symbol] + try!(reader.bits(LENGTH_EXTRA[symbol]));
+            let symbol = try!(distances.decode(reader));
+            if symbol >= DISTANCE_BASE.len() {
+                return Err(invalid_data("Invalid distance in deflate stream"));
+            }
+            let distance = DISTANCE_BASE[symbol] + try!(reader.bits(DISTANCE_EXTRA[symbol]));
+            if distance > out.len() {
+                return Err(invalid_data("Invalid distance in deflate stream"));
+            }
+            if out.len() + length > max_len {
+                return Err(invalid_data("Too much data in deflate stream"));
+            }
+            let start = out.len() - distance;
+            for i in 0..length {
+                let byte = out[start + i];
+                out.push(byte);
+            }
+        }
+    }
+}
+
+/// Position of the coefficients of a block in zigzag order.
+const ZIGZAG: [usize; 64] = [0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5,
+                             12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28,
+                             35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51,
+                             58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63];
+
+/// A color component of a JPEG image.
+#[derive(Debug, Clone)]
+struct JpegComponent {
+    id: u8,
+    horizontal: usize,
+    vertical: usize,
+    quantization_table: usize,
+    /// the number of blocks per line and column that are stored, including the padding of partial MCUs
+    blocks_per_line: usize,
+    blocks_per_column: usize,
+    /// the decoded samples, `blocks_per_line * 8` per line
+    samples: Vec<u8>,
+    /// the prediction of the DC coefficient
+    dc_prediction: i32,
+}
+
+#[derive(Debug, Clone)]
+struct JpegFrame {
+    width: usize,
+    height: usize,
+    max_horizontal: usize,
+    max_vertical: usize,
+    mcus_per_line: usize,
+    mcus_per_column: usize,
+    components: Vec<JpegComponent>,
+}
+
+/// Huffman table of a JPEG image, see Annex C of the [JPEG standard][1].
+/// [1]: https://www.w3.org/Graphics/JPEG/itu-t81.pdf
+#[derive(Debug, Clone)]
+struct JpegHuffman {
+    /// the largest code of each length, or -1 if there is none
+    max_code: [i32; 17],
+    /// the index of the first value of each length minus the first code of that length
+    offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl JpegHuffman {
+    fn parse(counts: &[u8], values: &[u8]) -> JpegHuffman {
+        let mut max_code = [-1; 17];
+        let mut offset = [0; 17];
+        let mut code = 0i32;
+        let mut index = 0i32;
+        for length in 1..17 {
+            let count = counts[length - 1] as i32;
+            offset[length] = index - code;
+            code += count;
+            index += count;
+            if count > 0 {
+                max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        JpegHuffman { max_code: max_code, offset: offset, values: values.to_vec() }
+    }
+
+    fn decode(&self, reader: &mut JpegBitReader) -> io::Result<u8> {
+        let mut code = 0i32;
+        for length in 1..17 {
+            code = (code << 1) | try!(reader.bit()) as i32;
+            if code <= self.max_code[length] {
+                return match self.values.get((self.offset[length] + code) as usize) {
+                    Some(&value) => Ok(value),
+                    None => Err(invalid_data("Invalid Huffman code in JPEG image")),
+                };
+            }
+        }
+        Err(invalid_data("Invalid Huffman code in JPEG image"))
+    }
+}
+
+/// Reads the bits of the entropy-coded data of a JPEG scan, starting with the most significant bit.
+///
+/// Stuffed zero bytes after `0xFF` are skipped. A marker ends the data, after which only
+/// zero bits are read.
+struct JpegBitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    byte: u8,
+    bits_left: usize,
+}
+
+impl<'a> JpegBitReader<'a> {
+    fn bit(&mut self) -> io::Result<u8> {
+        if self.bits_left == 0 {
+            self.byte = match (self.data.get(self.pos).cloned(), self.data.get(self.pos + 1).cloned()) {
+                (Some(0xFF), Some(0)) => { self.pos += 2; 0xFF },
+                (Some(0xFF), Some(_)) => 0,
+                (Some(byte), _) => { self.pos += 1; byte },
+                (None, _) => return Err(invalid_data("Truncated JPEG image data")),
+            };
+            self.bits_left = 8;
+        }
+        self.bits_left -= 1;
+        Ok((self.byte >> self.bits_left) & 1)
+    }
+
+    /// Read a `count` bit value and extend its sign as described in F.2.2.1 of the JPEG standard.
+    fn receive_extend(&mut self, count: u8) -> io::Result<i32> {
+        if count == 0 {
+            return Ok(0);
+        }
+        if count > 16 {
+            return Err(invalid_data("Invalid coefficient in JPEG image"));
+        }
+        let mut value = 0i32;
+        for _ in 0..count {
+            value = (value << 1) | try!(self.bit()) as i32;
+        }
+        if value < 1 << (count - 1) {
+            value += 1 - (1 << count);
+        }
+        Ok(value)
+    }
+
+    /// Skip the restart marker that is expected after the current byte.
+    fn restart(&mut self) -> io::Result<()> {
+        self.bits_left = 0;
+        match (self.data.get(self.pos).cloned(), self.data.get(self.pos + 1).cloned()) {
+            (Some(0xFF), Some(marker)) if marker >= 0xD0 && marker <= 0xD7 => {
+                self.pos += 2;
+                Ok(())
+            }
+            _ => Err(invalid_data("Missing restart marker in JPEG image")),
+        }
+    }
+}
+
+/// Decode a baseline JPEG image, without its start of image marker.
+fn decode_jpeg(data: &[u8]) -> io::Result<Image> {
+    let mut quantization_tables = vec![[0u16; 64]; 4];
+    let mut dc_tables: Vec<Option<JpegHuffman>> = vec![None; 4];
+    let mut ac_tables: Vec<Option<JpegHuffman>> = vec![None; 4];
+    let mut frame: Option<JpegFrame> = None;
+    let mut restart_interval = 0;
+    let mut pos = 0;
+    loop {
+        // markers may be preceded by any number of fill bytes
+        while data.get(pos) == Some(&0xFF) && data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        if pos + 2 > data.len() || data[pos] != 0xFF {
+            return Err(invalid_data("Invalid marker in JPEG image"));
+        }
+        let marker = data[pos + 1];
+        pos += 2;
+        if marker == 0xD9 {
+            break;
+        }
+        if marker >= 0xD0 && marker <= 0xD8 || marker == 0x01 {
+            continue;
+        }
+        if pos + 2 > data.len() {
+            return Err(invalid_data("Truncated JPEG segment"));
+        }
+        let length = (data[pos] as usize) << 8 | data[pos + 1] as usize;
+        if length < 2 || pos + length > data.len() {
+            return Err(invalid_data("Truncated JPEG segment"));
+        }
+        let segment = &data[pos + 2..pos + length];
+        pos += length;
+        match marker {
+            0xC0 | 0xC1 => frame = Some(try!(parse_jpeg_frame(segment))),
+            0xC2 | 0xC3 | 0xC5 | 0xC6 | 0xC7 | 0xC9 | 0xCA | 0xCB | 0xCD | 0xCE | 0xCF => {
+                return Err(invalid_data("Only baseline JPEG images are supported"));
+            }
+            0xC4 => try!(parse_jpeg_huffman_tables(segment, &mut dc_tables, &mut ac_tables)),
+            0xDB => try!(parse_jpeg_quantization_tables(segment, &mut quantization_tables)),
+            0xDD => {
+                if segment.len() < 2 {
+                    return Err(invalid_data("Invalid JPEG restart interval"));
+                }
+                restart_interval = (segment[0] as usize) << 8 | segment[1] as usize;
+            }
+            0xDA => {
+                let frame = try!(frame.as_mut().ok_or(invalid_data("JPEG scan before frame header")));
+                let scan = try!(parse_jpeg_scan(segment, frame));
+                let mut reader = JpegBitReader { data: data, pos: pos, byte: 0, bits_left: 0 };
+                try!(decode_jpeg_scan(&mut reader, frame, &scan, &dc_tables, &ac_tables, &quantization_tables, restart_interval));
+                pos = reader.pos;
+                // skip to the marker that ends the scan
+                while pos + 1 < data.len() && !(data[pos] == 0xFF && data[pos + 1] != 0 && (data[pos + 1] < 0xD0 || data[pos + 1] > 0xD7)) {
+                    pos += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let frame = try!(frame.ok_or(invalid_data("Missing JPEG frame header")));
+    jpeg_pixels(&frame)
+}
+
+fn parse_jpeg_frame(segment: &[u8]) -> io::Result<JpegFrame> {
+    if segment.len() < 6 || segment[0] != 8 {
+        return Err(invalid_data("Only JPEG images with 8 bit samples are supported"));
+    }
+    let height = (segment[1] as usize) << 8 | segment[2] as usize;
+    let width = (segment[3] as usize) << 8 | segment[4] as usize;
+    try!(check_size(width, height));
+    let num_components = segment[5] as usize;
+    if num_components != 1 && num_components != 3 {
+        return Err(invalid_data(&format!("JPEG images with {} components are not supported", num_components)));
+    }
+    if segment.len() < 6 + 3 * num_components {
+        return Err(invalid_data("Invalid JPEG frame header"));
+    }
+
+    let mut components = Vec::with_capacity(num_components);
+    for component in segment[6..].chunks(3).take(num_components) {
+        let (horizontal, vertical) = ((component[1] >> 4) as usize, (component[1] & 15) as usize);
+        if horizontal < 1 || horizontal > 4 || vertical < 1 || vertical > 4 || component[2] > 3 {
+            return Err(invalid_data("Invalid JPEG component"));
+        }
+        components.push(JpegComponent {
+            id: component[0],
+            horizontal: horizontal,
+            vertical: vertical,
+            quantization_table: component[2] as usize,
+            blocks_per_line: 0,
+            blocks_per_column: 0,
+            samples: Vec::new(),
+            dc_prediction: 0,
+        });
+    }
+    let max_horizontal = components.iter().map(|component| component.horizontal).max().unwrap_or(1);
+    let max_vertical = components.iter().map(|component| component.vertical).max().unwrap_or(1);
+    let mcus_per_line = (width + 8 * max_horizontal - 1) / (8 * max_horizontal);
+    let mcus_per_column = (height + 8 * max_vertical - 1) / (8 * max_vertical);
+    for component in &mut components {
+        component.blocks_per_line = mcus_per_line * component.horizontal;
+        component.blocks_per_column = mcus_per_column * component.vertical;
+        component.samples = vec![0; component.blocks_per_line * component.blocks_per_column * 64];
+    }
+
+    Ok(JpegFrame {
+        width: width,
+        height: height,
+        max_horizontal: max_horizontal,
+        max_vertical: max_vertical,
+        mcus_per_line: mcus_per_line,
+        mcus_per_column: mcus_per_column,
+        components: components,
+    })
+}
+
+fn parse_jpeg_huffman_tables(segment: &[u8], dc_tables: &mut [Option<JpegHuffman>], ac_tables: &mut [Option<JpegHuffman>]) -> io::Result<()> {
+    let mut pos = 0;
+    while pos < segment.len() {
+        if pos + 17 > segment.len() {
+            return Err(invalid_data("Invalid JPEG Huffman table"));
+        }
+        let (class, id) = (segment[pos] >> 4, (segment[pos] & 15) as usize);
+        let counts = &segment[pos + 1..pos + 17];
+        let num_values = counts.iter().fold(0, |sum, &count| sum + count as usize);
+        if class > 1 || id > 3 || pos + 17 + num_values > segment.len() {
+            return Err(invalid_data("Invalid JPEG Huffman table"));
+        }
+        let table = JpegHuffman::parse(counts, &segment[pos + 17..pos + 17 + num_values]);
+        match class {
+            0 => dc_tables[id] = Some(table),
+            _ => ac_tables[id] = Some(table),
+        }
+        pos += 17 + num_values;
+    }
+    Ok(())
+}
+
+fn parse_jpeg_quantization_tables(segment: &[u8], tables: &mut [[u16; 64]]) -> io::Result<()> {
+    let mut pos = 0;
+    while pos < segment.len() {
+        let (precision, id) = (segment[pos] >> 4, (segment[pos] & 15) as usize);
+        let value_size = precision as usize + 1;
+        if precision > 1 || id > 3 || pos + 1 + 64 * value_size > segment.len() {
+            return Err(invalid_data("Invalid JPEG quantization table"));
+        }
+        for (i, value) in segment[pos + 1..pos + 1 + 64 * value_size].chunks(value_size).enumerate() {
+            tables[id][i] = value.iter().fold(0u16, |sum, &byte| (sum << 8) | byte as u16);
+        }
+        pos += 1 + 64 * value_size;
+    }
+    Ok(())
+}
+
+/// The components of a scan with the ids of their DC and AC Huffman tables.
+fn parse_jpeg_scan(segment: &[u8], frame: &JpegFrame) -> io::Result<Vec<(usize, usize, usize)>> {
+    let num_components = *try!(segment.first().ok_or(invalid_data("Invalid JPEG scan header"))) as usize;
+    if num_components == 0 || segment.len() < 1 + 2 * num_components {
+        return Err(invalid_data("Invalid JPEG scan header"));
+    }
+    let mut scan = Vec::with_capacity(num_components);
+    for component in segment[1..].chunks(2).take(num_components) {
+        let component_id = try!(frame.components.iter().position(|frame_component| frame_component.id == component[0])
+                                .ok_or(invalid_data("Unknown component in JPEG scan")));
+        scan.push((component_id, (component[1] >> 4) as usize & 3, (component[1] & 15) as usize & 3));
+    }
+    Ok(scan)
+}
+
+fn decode_jpeg_scan(reader: &mut JpegBitReader, frame: &mut JpegFrame, scan: &[(usize, usize, usize)],
+                    dc_tables: &[Option<JpegHuffman>], ac_tables: &[Option<JpegHuffman>],
+                    quantization_tables: &[[u16; 64]], restart_interval: usize) -> io::Result<()> {
+    for &(component_id, dc_id, ac_id) in scan {
+        if dc_tables[dc_id].is_none() || ac_tables[ac_id].is_none() {
+            return Err(invalid_data("Missing Huffman table for JPEG scan"));
+        }
+        frame.components[component_id].dc_prediction = 0;
+    }
+
+    // a scan of a single component is not interleaved and only covers the blocks inside the image
+    let (mcus_per_line, mcus_per_column) = if scan.len() == 1 {
+        let component = &frame.components[scan[0].0];
+        let component_width = (frame.width * component.horizontal + frame.max_horizontal - 1) / frame.max_horizontal;
+        let component_height = (frame.height * component.vertical + frame.max_vertical - 1) / frame.max_vertical;
+        ((component_width + 7) / 8, (component_height + 7) / 8)
+    } else {
+        (frame.mcus_per_line, frame.mcus_per_column)
+    };
+
+    let mut block = [0f32; 64];
+    for mcu in 0..mcus_per_line * mcus_per_column {
+        if restart_interval > 0 && mcu > 0 && mcu % restart_interval == 0 {
+            try!(reader.restart());
+            for &(component_id, _, _) in scan {
+                frame.components[component_id].dc_prediction = 0;
+            }
+        }
+        let (mcu_x, mcu_y) = (mcu % mcus_per_line, mcu / mcus_per_line);
+        for &(component_id, dc_id, ac_id) in scan {
+            let component = &mut frame.components[component_id];
+            let (horizontal, vertical) = if scan.len() == 1 { (1, 1) } else { (component.horizontal, component.vertical) };
+            for block_y in 0..vertical {
+                for block_x in 0..horizontal {
+                    try!(decode_jpeg_block(reader, component, dc_tables[dc_id].as_ref().unwrap(), ac_tables[ac_id].as_ref().unwrap(),
+                                           &quantization_tables[component.quantization_table], &mut block));
+                    let (x, y) = (mcu_x * horizontal + block_x, mcu_y * vertical + block_y);
+                    let line_length = component.blocks_per_line * 8;
+                    for (row, values) in block.chunks(8).enumerate() {
+                        let start = (y * 8 + row) * line_length + x * 8;
+                        for (sample, &value) in component.samples[start..start + 8].iter_mut().zip(values) {
+                            *sample = (value + 128f32).round().max(0f32).min(255f32) as u8;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decode the coefficients of a block, dequantize them and return the samples of the block.
+fn decode_jpeg_block(reader: &mut JpegBitReader, component: &mut JpegComponent, dc_table: &JpegHuffman, ac_table: &JpegHuffman,
+                     quantization_table: &[u16; 64], block: &mut [f32; 64]) -> io::Result<()> {
+    let mut coefficients = [0f32; 64];
+    let dc_size = try!(dc_table.decode(reader));
+    component.dc_prediction += try!(reader.receive_extend(dc_size));
+    coefficients[0] = component.dc_prediction as f32 * quantization_table[0] as f32;
+
+    let mut k = 1;
+    while k < 64 {
+        let run_size = try!(ac_table.decode(reader));
+        let (run, size) = ((run_size >> 4) as usize, run_size & 15);
+        if size == 0 {
+            if run == 15 {
+                k += 16;
+                continue;
+            }
+            break;
+        }
+        k += run;
+        if k > 63 {
+            return Err(invalid_data("Invalid coefficient in JPEG image"));
+        }
+        coefficients[ZIGZAG[k]] = try!(reader.receive_extend(size)) as f32 * quantization_table[k] as f32;
+        k += 1;
+    }
+    inverse_dct(&coefficients, block);
+    Ok(())
+}
+
+/// Compute the two-dimensional inverse DCT of a block of 8 x 8 coefficients.
+fn inverse_dct(coefficients: &[f32; 64], samples: &mut [f32; 64]) {
+    let mut cosines = [0f32; 64];
+    for x in 0..8 {
+        for u in 0..8 {
+            let scale = if u == 0 { ::std::f32::consts::FRAC_1_SQRT_2 } else { 1f32 };
+            cosines[x * 8 + u] = scale * ((2 * x + 1) as f32 * u as f32 * ::std::f32::consts::PI / 16f32).cos();
+        }
+    }
+    let mut rows = [0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            rows[v * 8 + x] = (0..8).fold(0f32, |sum, u| sum + cosines[x * 8 + u] * coefficients[v * 8 + u]) / 2f32;
+        }
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            samples[y * 8 + x] = (0..8).fold(0f32, |sum, v| sum + cosines[y * 8 + v] * rows[v * 8 + x]) / 2f32;
+        }
+    }
+}
+
+/// Upsample the components of a decoded JPEG image and convert them to grayscale or RGB pixels.
+fn jpeg_pixels(frame: &JpegFrame) -> io::Result<Image> {
+    let channels = frame.components.len();
+    let mut pixels = Vec::with_capacity(frame.width * frame.height * channels);
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let mut values = [0f32; 3];
+            for (value, component) in values.iter_mut().zip(&frame.components) {
+                let component_x = x * component.horizontal / frame.max_horizontal;
+                let component_y = y * component.vertical / frame.max_vertical;
+                *value = component.samples[component_y * component.blocks_per_line * 8 + component_x] as f32;
+            }
+            if channels == 1 {
+                pixels.push(values[0] as u8);
+            } else {
+                // YCbCr as defined by JFIF
+                let (luma, blue, red) = (values[0], values[1] - 128f32, values[2] - 128f32);
+                for &value in &[luma + 1.402f32 * red,
+                                luma - 0.344136f32 * blue - 0.714136f32 * red,
+                                luma + 1.772f32 * blue] {
+                    pixels.push(value.round().max(0f32).min(255f32) as u8);
+                }
+            }
+        }
+    }
+
+    Ok(Image {
+        width: frame.width,
+        height: frame.height,
+        channels: channels,
+        pixels: pixels,
+    })
+}
+
+/// Check that an image of `width` x `height` pixels can be decoded.
+fn check_size(width: usize, height: usize) -> io::Result<()> {
+    if width == 0 || height == 0 {
+        return Err(invalid_data("The image has no pixels"));
+    }
+    if width > MAX_PIXELS || height > MAX_PIXELS / width {
+        return Err(invalid_data(&format!("The image of {} x {} pixels is too large", width, height)));
+    }
+    Ok(())
+}
+
+fn read_be(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0u32, |value, &byte| (value << 8) | byte as u32)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,139 @@
+//! Provides inference on datasets that are too large to be processed as a single minibatch.
+//!
+//! A [ChunkedInference][1] splits the samples into chunks whose activations, gradients and
+//! weights fit into a configured memory budget and runs them through a copy of the network one after another.
+//! The copy is created once, so its activation tensors are reused for every chunk.
+//! [1]: ./struct.ChunkedInference.html
+use std::cmp;
+use std::sync::Arc;
+use co::{IBackend, ITensorDesc};
+use layer::Layer;
+use logging::Subsystem;
+use log::LogLevel;
+use util::{ArcLock, LayerOps, tensor_to_vec};
+
+/// Runs the samples of a dataset through a network in chunks that respect a memory budget.
+pub struct ChunkedInference<B: IBackend + LayerOps<f32> + 'static> {
+    network: Layer<B>,
+    chunk_size: usize,
+}
+
+impl<B: IBackend + LayerOps<f32> + 'static> ChunkedInference<B> {
+    /// Create a ChunkedInference whose network takes at most `max_memory` bytes.
+    ///
+    /// The weights of the network, as estimated by [weight_memory][1], are taken from
+    /// `max_memory` first. The chunk size is the number of samples whose activations, as estimated by
+    /// [activation_memory_per_sample][2], fit into the rest, but at most the batch size
+    /// of `network`. If it is smaller than the batch size, the network is copied with
+    /// the chunk size as its batch size (see [Layer::with_batch_size][3]).
+    /// [1]: ./fn.weight_memory.html
+    /// [2]: ./fn.activation_memory_per_sample.html
+    /// [3]: ../layer/struct.Layer.html#method.with_batch_size
+    ///
+    /// Returns an error if the network doesn't have exactly one input, if not even the
+    /// weights and the activations of a single sample fit into `max_memory` or if the batch size of the
+    /// network can't be changed.
+    pub fn new(network: &Layer<B>, max_memory: usize) -> Result<ChunkedInference<B>, String> {
+        let input_shapes = network.input_shapes();
+        if input_shapes.len() != 1 {
+            return Err(format!("Chunked inference needs a network with one input, but {} has {}", network.name, input_shapes.len()));
+        }
+        let batch_size = input_shapes[0].1.get(0).cloned().unwrap_or(1);
+        let weights_memory = weight_memory(network);
+        let memory_per_sample = activation_memory_per_sample(network);
+        if weights_memory + memory_per_sample > max_memory {
+            return Err(format!("The weights of {} take {} bytes and the activations of a single sample {} bytes, which exceeds the budget of {} bytes",
+                               network.name, weights_memory, memory_per_sample, max_memory));
+        }
+
+        let chunk_size = cmp::min((max_memory - weights_memory) / cmp::max(memory_per_sample, 1), batch_size);
+        let network = try!(network.with_batch_size(chunk_size));
+        log_event!(Subsystem::Forward, LogLevel::Info, "chunked_inference", network = network.name,
+                   chunk_size = chunk_size, weight_memory = weights_memory, memory_per_sample = memory_per_sample);
+        Ok(ChunkedInference {
+            network: network,
+            chunk_size: chunk_size,
+        })
+    }
+
+    /// Returns the number of samples that are run through the network at once.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns the network that the chunks are run through.
+    pub fn network(&self) -> &Layer<B> {
+        &self.network
+    }
+
+    /// Run the samples in `inputs`, which holds the values of one sample after another,
+    /// through the network and return the values of its first output for every sample.
+    ///
+    /// The last chunk is filled up with zeros if there are not enough samples left;
+    /// the outputs of these padding samples are not returned.
+    ///
+    /// Returns an error if the number of values in `inputs` isn't a multiple of the size of a sample.
+    pub fn run(&mut self, inputs: &[f32]) -> Result<Vec<f32>, String> {
+        let (input_name, input_shape) = self.network.input_shapes().remove(0);
+        let sample_size = input_shape.size() / cmp::max(input_shape[0], 1);
+        if sample_size == 0 || inputs.len() % sample_size != 0 {
+            return Err(format!("Got {} input values, which is not a multiple of the sample size {}", inputs.len(), sample_size));
+        }
+
+        let mut outputs = Vec::new();
+        let mut chunk = vec![0f32; self.chunk_size * sample_size];
+        for chunk_values in inputs.chunks(self.chunk_size * sample_size) {
+            let chunk_samples = chunk_values.len() / sample_size;
+            chunk[..chunk_values.len()].copy_from_slice(chunk_values);
+            for value in &mut chunk[chunk_values.len()..] {
+                *value = 0f32;
+            }
+            try!(self.network.fill_input(&input_name, &chunk));
+
+            let output = tensor_to_vec(&self.network.forward(&[])[0]);
+            let output_sample_size = output.len() / self.chunk_size;
+            outputs.extend_from_slice(&output[..chunk_samples * output_sample_size]);
+        }
+        Ok(outputs)
+    }
+}
+
+impl<B: IBackend + LayerOps<f32> + 'static> ::std::fmt::Debug for ChunkedInference<B> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "ChunkedInference {{ network: {}, chunk_size: {} }}", self.network.name, self.chunk_size)
+    }
+}
+
+/// Estimate the number of bytes that the activations of a single sample take in `network`.
+///
+/// This is the size of the inputs and of all distinct outputs of the layers of the network,
+/// divided by its batch size. Outputs that are computed in-place are only counted once.
+/// Every input and output also has a gradient of the same size, which is counted as well.
+pub fn activation_memory_per_sample<B: IBackend + LayerOps<f32> + 'static>(network: &Layer<B>) -> usize {
+    let input_shapes = network.input_shapes();
+    let batch_size = input_shapes.get(0).and_then(|&(_, ref shape)| shape.get(0).cloned()).unwrap_or(1);
+
+    let mut tensors: Vec<ArcLock<_>> = Vec::new();
+    for layer in network.layer_outputs() {
+        for output in layer.outputs {
+            if !tensors.iter().any(|tensor| Arc::ptr_eq(tensor, &output)) {
+                tensors.push(output);
+            }
+        }
+    }
+    let num_values = input_shapes.iter().fold(0, |sum, &(_, ref shape)| sum + shape.size())
+        + tensors.iter().fold(0, |sum, tensor| sum + tensor.read().unwrap().desc().size());
+    // every value and its gradient are an f32 of 4 bytes each
+    num_values * 2 * 4 / cmp::max(batch_size, 1)
+}
+
+/// Estimate the number of bytes that the learnable weights of `network` take.
+///
+/// This is the size of all learnable weights and of their gradients. Unlike the activations
+/// it doesn't depend on the batch size, so it is taken up once for the whole network.
+pub fn weight_memory<B: IBackend + LayerOps<f32> + 'static>(network: &Layer<B>) -> usize {
+    let num_values = network.learnable_weights_data().iter()
+        .fold(0, |sum, weight| sum + weight.read().unwrap().desc().size());
+    // every value and its gradient are an f32 of 4 bytes each
+    num_values * 2 * 4
+}
@@ -5,16 +5,20 @@
 use co::prelude::*;
 use layers::*;
 use weight::WeightConfig;
-use util::{ArcLock, native_backend, LayerOps};
+use logging::{Shape, Subsystem};
+use log::LogLevel;
+use util::{ArcLock, native_backend, write_to_memory, crc32, f16_to_f32, f32_to_f16, tensor_from_slice, tensor_to_vec, write_tensor, LayerOps};
 use std::fmt;
 use std::cmp;
 use std::collections::{HashMap, HashSet};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, BufReader};
 use std::path::Path;
 use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 use leaf_capnp::layer as capnp_layer;
+use leaf_capnp::tensor as capnp_tensor;
+use leaf_capnp::WeightEncoding as CapnpWeightEncoding;
 use leaf_capnp::layer_config as capnp_layer_config;
 use leaf_capnp::layer_config::layer_type as capnp_layer_type;
 use capnp_util::*;
@@ -41,6 +45,13 @@ pub struct Layer<B: IBackend> {
     /// Determines if layer will skip comutations for [backward][1] step.
     /// [1]: ./trait.ILayer.html#method.backward
     needs_backward: bool,
+    /// Determines if the weights of the layer are updated during training.
+    trainable: bool,
+    /// Determines if the layer can be created again from its `config`.
+    ///
+    /// This is not the case for layers created with [from_worker][1].
+    /// [1]: #method.from_worker
+    restorable: bool,
 
     /// The vector that stores shared references to the weights in the form of blobs.
     pub weights_data: Vec<ArcLock<SharedTensor<f32>>>,
@@ -48,12 +59,18 @@ pub struct Layer<B: IBackend> {
     pub weights_gradient: Vec<ArcLock<SharedTensor<f32>>>,
     // contains all the learnable weights (does not include bias(?) and shared weights)
     learnable_weights: Vec<ArcLock<SharedTensor<f32>>>,
-    // learning rate for each weight
+    // gradient for each learnable weight
+    learnable_weights_gradient: Vec<ArcLock<SharedTensor<f32>>>,
+    // learning rate for each learnable weight
     weights_lr: Vec<Option<f32>>,
-    // weight decay for each weight
+    // weight decay for each learnable weight
     weights_weight_decay: Vec<Option<f32>>,
-    // display name for each weight
+    // display name for each learnable weight
     weights_display_names: Vec<String>,
+    // path of each learnable weight relative to the container of the layer, see `learnable_weights_paths`
+    weights_paths: Vec<String>,
+    // for each named weight the layer owns, its position and the gradients of the layers that share it
+    shared_weights_gradients: Vec<(usize, ArcLock<Vec<ArcLock<SharedTensor<f32>>>>)>,
 
     /// Vector indicating whether to compute the diff of each weight blob.
     ///
@@ -86,6 +103,14 @@ pub struct Layer<B: IBackend> {
     ///
     /// Does not contain anonymous blobs.
     pub blob_names: HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)>,
+
+    /// The hooks registered on the layer, see [register_hook][1].
+    /// [1]: #method.register_hook
+    hooks: LayerHooks,
+
+    /// The label of each class of the first output, indexed by class id, see [set_labels][1].
+    /// [1]: #method.set_labels
+    labels: Vec<String>,
 }
 
 impl<B: IBackend> Layer<B> {
@@ -99,47 +124,76 @@ impl<B: IBackend> Layer<B> {
     /// [2]: ./trait.ILayer.html
     ///
     /// Called during initialization of containter layers.
+    ///
+    /// Returns an error if one of the outputs of the layer is already in the `registry`
+    /// and is not computed in-place, or if the number of inputs or outputs doesn't match
+    /// what the layer implementation requires (see e.g. [exact_num_input_blobs][3] and
+    /// [exact_num_output_blobs][4]).
+    /// [3]: ./trait.ILayer.html#method.exact_num_input_blobs
+    /// [4]: ./trait.ILayer.html#method.exact_num_output_blobs
     pub fn connect(
         &mut self,
         registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)>,
-        weight_registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>, Option<f32>, Option<f32>)>) {
+        weight_registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>, Option<f32>, Option<f32>, ArcLock<Vec<ArcLock<SharedTensor<f32>>>>)>) -> Result<(), String> {
+        if let Some(exact_num_input_blobs) = self.worker.exact_num_input_blobs() {
+            if self.config.inputs_len() != exact_num_input_blobs {
+                return Err(format!("Layer {} requires exactly {} input blobs, but is connected to {}: {:?}",
+                                   self.name, exact_num_input_blobs, self.config.inputs_len(), self.config.inputs));
+            }
+        }
+        if self.config.inputs_len() < self.worker.min_input_blobs() {
+            return Err(format!("Layer {} requires at least {} input blobs, but is connected to {}: {:?}",
+                               self.name, self.worker.min_input_blobs(), self.config.inputs_len(), self.config.inputs));
+        }
         // connect to all required inputs
         for input_name in &self.config.inputs.clone() {
             self.connect_input(input_name, registry)
         }
         // setup outputs
         for (output_id, _) in self.config.outputs.clone().iter().rev().enumerate() {
-            self.append_output(output_id, registry);
+            try!(self.append_output(output_id, registry));
         }
         let config = self.config.clone();
-        for (output_id, _) in self.config.outputs.clone().iter().rev().enumerate() {
-            self.append_weight(&config, weight_registry, 0, output_id);
+        let num_weights = self.worker.exact_num_weight_blobs().unwrap_or(self.config.outputs.len());
+        for weight_id in 0..num_weights {
+            self.append_weight(&config, weight_registry, 0, weight_id);
         }
 
         // If the layer specifies that AutoTopBlobs() -> true and the LayerParameter
         // specified fewer than the required number (as specified by
         // exact_num_top_blobs() or min_output_blobs()), allocate them here.
         let auto_output_blobs = self.worker.auto_output_blobs();
-        debug!("Layer {} - auto_output_blobs: {}", &self.name, &auto_output_blobs);
+        log_event!(Subsystem::Init, LogLevel::Debug, "auto_output_blobs", layer = self.name, enabled = auto_output_blobs);
         let min_output_blobs = self.worker.min_output_blobs();
-        let exact_num_output_blobs = self.worker.exact_num_output_blobs().unwrap_or(0);
+        let exact_num_output_blobs = self.worker.exact_num_output_blobs();
         if auto_output_blobs {
-            let needed_num_outputs = cmp::max(min_output_blobs, exact_num_output_blobs);
-            for _ in 0..(needed_num_outputs - self.output_blobs_data.len()) {
+            let needed_num_outputs = cmp::max(min_output_blobs, exact_num_output_blobs.unwrap_or(0));
+            for _ in 0..needed_num_outputs.saturating_sub(self.output_blobs_data.len()) {
                 // Add "anonymous" output blobs -- do not add to registry
                 // as we don't want these blobs to be usable as input
                 // to other layers.
-                info!("Adding anonymous output blob for layer {}", &self.name);
+                log_event!(Subsystem::Init, LogLevel::Debug, "anonymous_output", layer = self.name);
                 self.create_anonymous_output();
             }
         }
+        let num_outputs = self.output_blobs_data.len();
+        if exact_num_output_blobs.map_or(false, |exact_num_output_blobs| num_outputs != exact_num_output_blobs) {
+            return Err(format!("Layer {} requires exactly {} output blobs, but has {}: {:?}",
+                               self.name, exact_num_output_blobs.unwrap(), num_outputs, self.output_blob_names));
+        }
+        if num_outputs < min_output_blobs {
+            return Err(format!("Layer {} requires at least {} output blobs, but has {}: {:?}",
+                               self.name, min_output_blobs, num_outputs, self.output_blob_names));
+        }
 
         self.worker.init(self.backend.clone());
         self.reshape();
         self.worker.resize_shared_workspace(self.backend.clone(), None);
         for t in &self.output_blobs_data {
-            debug!("Layer {} - output shape: {:?}", self.name, t.read().unwrap().desc());
+            log_event!(Subsystem::Init, LogLevel::Debug, "output_shape", layer = self.name, shape = Shape(t.read().unwrap().desc()));
         }
+
+        Ok(())
     }
 
     /// Append blob as [input blob][1] to the Layer.
@@ -155,12 +209,9 @@ impl<B: IBackend> Layer<B> {
         let input_id = self.config.inputs.iter().position(|input_name| input_name == blob_name).unwrap();
 
         if !available_blobs.contains_key(&*blob_name) {
-            error!("Unknown input blob {} (layer '{}', input_id: {})",
-                   blob_name,
-                   self.name,
-                   input_id);
+            log_event!(Subsystem::Init, LogLevel::Error, "unknown_input", layer = self.name, input = blob_name, input_id = input_id);
         }
-        info!("Input {:<15} -> Layer {:>15}", blob_name, self.name);
+        log_event!(Subsystem::Init, LogLevel::Debug, "connect_input", layer = self.name, input = blob_name);
 
         self.input_blob_names.push(blob_name.to_owned());
         self.input_blobs_data.push(available_blobs.get(&*blob_name).expect(&format!("Unknown blob name {}", blob_name)).0.clone());
@@ -185,9 +236,11 @@ impl<B: IBackend> Layer<B> {
     /// Finally, the new blob will be added to the registry, so that the other layers can
     /// connect it as their input.
     /// [2]: ../layer/struct.LayerConfig.html
+    ///
+    /// Returns an error if another layer already added a blob with the same name to the registry.
     fn append_output(&mut self,
                   output_id: usize,
-                  registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)>) {
+                  registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)>) -> Result<(), String> {
         let layer_config = &self.config;
 
         let blob_name = layer_config.output(output_id).unwrap().clone();
@@ -195,19 +248,16 @@ impl<B: IBackend> Layer<B> {
         let blob_gradient: ArcLock<SharedTensor<f32>>;
 
         if layer_config.input(output_id).is_some() && *layer_config.input(output_id).unwrap() == blob_name {
-            info!("Layer {:<15} -> Output {:>15} (in-place)", layer_config.name, blob_name);
+            log_event!(Subsystem::Init, LogLevel::Debug, "connect_output", layer = layer_config.name, output = blob_name, in_place = true);
             blob_data = registry[&blob_name].0.clone();
             blob_gradient = registry[&blob_name].1.clone();
         } else if registry.contains_key(&blob_name) {
             // If we are not doing in-place computation but have duplicated blobs, raise an
             // error.
-            error!("Top blob {} produced by multiple sources.", blob_name);
-            return
+            log_event!(Subsystem::Init, LogLevel::Error, "duplicate_output", layer = layer_config.name, output = blob_name);
+            return Err(duplicate_output_message(&layer_config.name, &blob_name, "another layer", layer_config.input(output_id).map(|input| &**input)));
         } else {
-            {
-                info!("Layer {:<15} -> Output {:>15}", self.name, blob_name);
-                info!("Output {} = {}", output_id, blob_name);
-            }
+            log_event!(Subsystem::Init, LogLevel::Debug, "connect_output", layer = self.name, output = blob_name, output_id = output_id);
 
             let backend: Rc<IBackend<F=B::F>> = self.backend.clone();
             blob_data = Arc::new(RwLock::new(SharedTensor::new(backend.device(), &vec![1,1,1]).unwrap())); // [1,1,1] for CUDA
@@ -218,6 +268,8 @@ impl<B: IBackend> Layer<B> {
         self.output_blobs_gradient.push(blob_gradient.clone());
         self.blob_names.insert(blob_name.clone(), (blob_data.clone(), blob_gradient.clone()));
         registry.insert(blob_name.clone(), (blob_data.clone(), blob_gradient.clone()));
+
+        Ok(())
     }
 
     /// Append anonymous blob as [output blob][1] to the Layer.
@@ -231,7 +283,7 @@ impl<B: IBackend> Layer<B> {
     fn create_anonymous_output(&mut self) {
         let blob_name = "(automatic)".to_owned();
 
-        info!("{} -> {}", self.name, blob_name);
+        log_event!(Subsystem::Init, LogLevel::Debug, "connect_output", layer = self.name, output = blob_name);
 
         let backend: Rc<IBackend<F=B::F>> = self.backend.clone();
         let output_data = Arc::new(RwLock::new(SharedTensor::new(backend.device(), &vec![1,1,1]).unwrap())); // [1,1,1] for CUDA
@@ -240,14 +292,13 @@ impl<B: IBackend> Layer<B> {
         self.output_blobs_gradient.push(output_gradient);
     }
 
-    fn append_weight(&mut self, layer_config: &LayerConfig, registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>, Option<f32>, Option<f32>)>, layer_id: usize, weight_id: usize) {
+    fn append_weight(&mut self, layer_config: &LayerConfig, registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>, Option<f32>, Option<f32>, ArcLock<Vec<ArcLock<SharedTensor<f32>>>>)>, layer_id: usize, weight_id: usize) {
         if self.worker.auto_weight_blobs() {
-            info!("Layer {} - appending weight", &layer_config.name);
+            log_event!(Subsystem::Init, LogLevel::Debug, "append_weight", layer = layer_config.name, weight_id = weight_id);
             let weights_len = self.weights_data.len();
-            let weight_name = if weights_len > weight_id {
-                layer_config.param(weight_id).unwrap().name.clone()
-            } else {
-                "".to_owned()
+            let weight_name = match layer_config.param(weight_id) {
+                Some(weight_config) => weight_config.name.clone(),
+                None => "".to_owned(),
             };
 
             // use weight_name (or weight_id as a fallback) as display_name
@@ -256,16 +307,30 @@ impl<B: IBackend> Layer<B> {
             } else {
                 format!("{}-{}", self.name, weight_id)
             };
-            self.weights_display_names.push(display_name.clone());
+            // named weights can be shared by the layers of a container, so they are not placed below the layer
+            let path = if !weight_name.is_empty() {
+                weight_name.clone()
+            } else if weight_id == 0 {
+                format!("{}/weight", self.name)
+            } else {
+                format!("{}/weight-{}", self.name, weight_id)
+            };
             // create name for registry
             let registry_name = format!("SHARED_WEIGHT_{}", display_name);
 
             // add to tracking vectors
             let net_weight_id = weights_len;
-            let output_data = self.output_blobs_data[weight_id].read().unwrap();
-            debug!("Layer {} - creating weight and gradient of size {:?}", &layer_config.name, output_data.desc());
-            let weight_data = Arc::new(RwLock::new(SharedTensor::<f32>::new(output_data.latest_device(), output_data.desc()).unwrap()));
-            let weight_gradient = Arc::new(RwLock::new(SharedTensor::<f32>::new(output_data.latest_device(), output_data.desc()).unwrap()));
+            // the weight starts with the shape of the output of the same position, if there is one
+            let (device, shape) = match self.output_blobs_data.get(weight_id) {
+                Some(output) => {
+                    let output_data = output.read().unwrap();
+                    (output_data.latest_device().clone(), output_data.desc().clone())
+                },
+                None => (self.backend.device().clone(), vec![1, 1, 1]), // [1,1,1] for CUDA
+            };
+            log_event!(Subsystem::Init, LogLevel::Debug, "create_weight", layer = layer_config.name, weight = display_name, shape = Shape(&shape));
+            let weight_data = Arc::new(RwLock::new(SharedTensor::<f32>::new(&device, &shape).unwrap()));
+            let weight_gradient = Arc::new(RwLock::new(SharedTensor::<f32>::new(&device, &shape).unwrap()));
             self.weights_data.push(weight_data.clone());
             self.weights_gradient.push(weight_gradient.clone());
 
@@ -279,51 +344,62 @@ impl<B: IBackend> Layer<B> {
             if weight_name.is_empty() || !registry.contains_key(&registry_name) {
                 // self.weight_owners.push(None);
                 if !weight_name.is_empty() {
-                    registry.insert(weight_name.clone(),
-                        (weight_data.clone(), weight_gradient.clone(), weight_config.lr_mult, weight_config.decay_mult));
+                    let sharing_gradients = Arc::new(RwLock::new(Vec::new()));
+                    registry.insert(registry_name.clone(),
+                        (weight_data.clone(), weight_gradient.clone(), weight_config.lr_mult, weight_config.decay_mult, sharing_gradients.clone()));
+                    self.shared_weights_gradients.push((net_weight_id, sharing_gradients));
                 }
                 let learnable_weight_id = self.learnable_weights.len();
                 self.learnable_weights.push(weight_data.clone());
+                self.learnable_weights_gradient.push(weight_gradient.clone());
                 // self.learnable_weight_ids.push(learnable_weight_id);
                 self.weights_lr.push(weight_config.lr_mult);
                 self.weights_weight_decay.push(weight_config.decay_mult);
+                self.weights_display_names.push(display_name);
+                self.weights_paths.push(path);
             } else {
-                // Named weight blob with name we've seen before: share weights
+                // Named weight blob with name we've seen before: share weights.
+                // The weight is only learnable in the layer that owns it; this layer computes its own
+                // gradient, which the owner adds to its gradient in `backward_parameters`.
 
-                let (shared_weight_data, shared_weight_gradient, shared_lr, shared_decay_mult) = registry.get(&registry_name).unwrap().clone();
-                info!("Sharing weight blob '{}'", weight_name.clone());
+                let (shared_weight_data, shared_weight_gradient, shared_lr, shared_decay_mult, sharing_gradients) = registry.get(&registry_name).unwrap().clone();
+                log_event!(Subsystem::Init, LogLevel::Debug, "share_weight", layer = layer_config.name, weight = weight_name);
+                self.weights_data[net_weight_id] = shared_weight_data.clone();
+                sharing_gradients.write().unwrap().push(weight_gradient.clone());
 
                 // can only share parameters if both have same lr_mult
                 if let Some(lr_mult) = weight_config.lr_mult {
                     if let Some(owner_lr_mult) = shared_lr {
                         if !lr_mult.eq(&owner_lr_mult) {
-                            error!("Shared param '{}' has mismatched lr_mult.",
-                                   weight_name.clone());
+                            log_event!(Subsystem::Init, LogLevel::Error, "mismatched_lr_mult", layer = layer_config.name, weight = weight_name);
                         }
                     } else {
                         // this is the first shared instance that has a lr_mult value so we take that
                         registry.remove(&registry_name).unwrap();
-                        registry.insert(registry_name.clone(), (shared_weight_data.clone(), shared_weight_gradient.clone(), weight_config.lr_mult, shared_decay_mult));
+                        registry.insert(registry_name.clone(), (shared_weight_data.clone(), shared_weight_gradient.clone(), weight_config.lr_mult, shared_decay_mult, sharing_gradients.clone()));
                     }
                 }
                 // can only share weights if both have same decay_mult
                 if let Some(decay_mult) = weight_config.decay_mult {
                     if let Some(owner_decay_mult) = shared_decay_mult {
                         if !decay_mult.eq(&owner_decay_mult) {
-                            error!("Shared param '{}' has mismatched decay_mult.",
-                                   weight_name.clone());
+                            log_event!(Subsystem::Init, LogLevel::Error, "mismatched_decay_mult", layer = layer_config.name, weight = weight_name);
                         }
                     } else {
                         // this is the first shared instance that has a decay_mult value so we take that
                         registry.remove(&registry_name).unwrap();
-                        registry.insert(registry_name, (shared_weight_data.clone(), shared_weight_gradient.clone(), shared_lr, weight_config.decay_mult));
+                        registry.insert(registry_name, (shared_weight_data.clone(), shared_weight_gradient.clone(), shared_lr, weight_config.decay_mult, sharing_gradients));
                     }
                 }
             }
         }
     }
 
-    fn reshape(&mut self) {
+    /// Reshape the outputs and weights of the layer to fit its current inputs.
+    ///
+    /// Depending on the [layer implementation][1] this might also reinitialize the weights.
+    /// [1]: ./trait.ILayer.html
+    pub fn reshape(&mut self) {
         match self.is_using_in_place() {
             false => {
                 self.worker.reshape(self.backend.clone(),
@@ -388,11 +464,7 @@ impl<B: IBackend> Layer<B> {
         if !layer_contributes_loss {
             self.needs_backward = false;
         }
-        {
-            info!("{} needs backward computation: {}",
-                  self.name,
-                  self.needs_backward);
-        }
+        log_event!(Subsystem::Init, LogLevel::Debug, "needs_backward", layer = self.name, needs_backward = self.needs_backward);
 
         for (input_id, input_name) in self.input_blob_names.iter().enumerate() {
             if layer_contributes_loss {
@@ -424,6 +496,48 @@ impl<B: IBackend> Layer<B> {
         }
     }
 
+    /// Replace the tensors of the input and output blobs with the name `blob_name`.
+    ///
+    /// Used by container layers to rewire a layer after the layer that produces
+    /// the blob has been replaced.
+    pub fn replace_blob(&mut self,
+                        blob_name: &str,
+                        data: ArcLock<SharedTensor<f32>>,
+                        gradient: ArcLock<SharedTensor<f32>>) {
+        for (input_id, input_name) in self.input_blob_names.iter().enumerate() {
+            if input_name == blob_name {
+                self.input_blobs_data[input_id] = data.clone();
+                self.input_blobs_gradient[input_id] = gradient.clone();
+            }
+        }
+        for (output_id, output_name) in self.output_blob_names.iter().enumerate() {
+            if output_name == blob_name {
+                self.output_blobs_data[output_id] = data.clone();
+                self.output_blobs_gradient[output_id] = gradient.clone();
+            }
+        }
+        if self.blob_names.contains_key(blob_name) {
+            self.blob_names.insert(blob_name.to_owned(), (data, gradient));
+        }
+    }
+
+    /// Replace the layer with the name `layer_name` inside a container layer
+    /// with a new layer created from `config`.
+    ///
+    /// See [Sequential::replace_layer][1] for how the new layer is connected.
+    /// [1]: ../layers/container/struct.Sequential.html#method.replace_layer
+    pub fn replace_layer(&mut self, layer_name: &str, config: LayerConfig) -> Result<(), String> {
+        try!(self.worker.replace_layer(self.backend.clone(), layer_name, &config));
+        if let LayerType::Sequential(ref mut container_config) = self.config.layer_type {
+            if let Some(layer_config) = container_config.layers.iter_mut().find(|layer| layer.name == layer_name) {
+                *layer_config = config;
+            }
+        }
+        self.expose_outputs();
+
+        Ok(())
+    }
+
     /// Expose the internal inputs of a container layer.
     fn expose_inputs(&mut self) {
         if let Some(inputs) = self.worker.inputs_data() {
@@ -446,9 +560,14 @@ impl<B: IBackend> Layer<B> {
 
     /// Uses the underlying layer implementation to compute a forward step.
     ///
+    /// The computation is only queued on backends that run asynchronously, like CUDA,
+    /// so several forward steps can be issued before any of them finishes.
+    /// Syncing an output to another device, e.g. to read it on the host, waits for the
+    /// computation; use [synchronize][1] to wait for it explicitly, e.g. to measure its time.
+    /// [1]: #method.synchronize
+    ///
     /// See [ILayer.forward](./trait.ILayer.html#method.forward)
     pub fn forward(&mut self, inputs: &[ArcLock<SharedTensor<f32>>]) -> Vec<ArcLock<SharedTensor<f32>>> {
-        debug!("LAYER: {:?}", &self.name);
         for (input_i, input) in inputs.iter().enumerate() {
             let reshaped_shape = self.input_blobs_data[input_i].read().unwrap().desc().clone();
             self.input_blobs_data[input_i] = input.clone();
@@ -472,10 +591,71 @@ impl<B: IBackend> Layer<B> {
                 self.worker.forward(&self.backend, &self.input_blobs_data, &self.weights_data, &mut self.output_blobs_data);
             }
         });
-        debug!("{:<15} - Forward time: {:.5} ms", &self.name, forward_time / 0.001);
+        log_event!(Subsystem::Forward, LogLevel::Debug, "forward", layer = self.name,
+                   shapes = self.output_blobs_data.iter().map(|output| Shape(output.read().unwrap().desc()).to_string()).collect::<Vec<_>>().join(","),
+                   duration_ms = format!("{:.5}", forward_time / 0.001));
+        call_hooks(&self.name, &mut self.hooks.forward, self.output_blobs_data.iter());
         self.output_blobs_data.clone()
     }
 
+    /// Compute a forward step with the inputs matched by their declared names.
+    ///
+    /// Unlike [forward][1], which assigns the inputs by position, every input of the layer
+    /// has to be provided under its name (see [input_shapes][2]). This prevents mixing up
+    /// the inputs of networks with multiple inputs, e.g. data and label.
+    /// [1]: #method.forward
+    /// [2]: #method.input_shapes
+    ///
+    /// Returns an error if an input is missing, an unknown input is provided
+    /// or an input does not have the size of the declared input.
+    pub fn forward_named(&mut self, inputs: &HashMap<&str, ArcLock<SharedTensor<f32>>>) -> Result<Vec<ArcLock<SharedTensor<f32>>>, String> {
+        let input_shapes = self.input_shapes();
+        if let Some(unknown) = inputs.keys().find(|name| !input_shapes.iter().any(|&(ref input_name, _)| input_name == *name)) {
+            return Err(format!("Layer {} has no input named {}", self.name, unknown));
+        }
+
+        let mut ordered_inputs = Vec::with_capacity(input_shapes.len());
+        for (input_name, input_shape) in input_shapes {
+            let input = match inputs.get(input_name.as_str()) {
+                Some(input) => input.clone(),
+                None => return Err(format!("Input {} of layer {} was not provided", input_name, self.name)),
+            };
+            let input_size = input.read().unwrap().desc().size();
+            if input_size != input_shape.size() {
+                return Err(format!("Input {} expects {} values, but {} were provided", input_name, input_shape.size(), input_size));
+            }
+            ordered_inputs.push(input);
+        }
+
+        Ok(self.forward(&ordered_inputs))
+    }
+
+    /// Write `data` into the input tensor with the name `input_name`.
+    ///
+    /// The input tensors are allocated once during initialization and are reused,
+    /// so feeding new data this way does not allocate or reshape tensors.
+    /// Afterwards call [forward][1] without inputs to compute the output for the new data.
+    /// [1]: #method.forward
+    ///
+    /// Returns an error if no input with that name exists or if the length of `data`
+    /// does not match the size of the input tensor.
+    pub fn fill_input(&mut self, input_name: &str, data: &[f32]) -> Result<(), String> {
+        let input_id = match self.input_names().iter().position(|name| name == input_name) {
+            Some(input_id) => input_id,
+            None => return Err(format!("Layer {} has no input named {}", self.name, input_name)),
+        };
+
+        let native = native_backend();
+        let mut input = self.input_blobs_data[input_id].write().unwrap();
+        if input.desc().size() != data.len() {
+            return Err(format!("Input {} expects {} values, but {} were provided", input_name, input.desc().size(), data.len()));
+        }
+        match input.add_device(native.device()) { _ => input.sync(native.device()).unwrap() }
+        write_to_memory(input.get_mut(native.device()).unwrap(), data);
+
+        Ok(())
+    }
+
     /// Uses the underlying layer implementation to compute a backward step.
     ///
     /// See [ILayer.backward](./trait.ILayer.html#method.backward)
@@ -502,21 +682,29 @@ impl<B: IBackend> Layer<B> {
                          &mut self.weights_data, &mut self.weights_gradient,
                          &mut self.output_blobs_data, &mut self.output_blobs_gradient);
 
-        if self.is_using_in_place() {
-            self.worker.backward_input(&self.backend,
-                                 &self.weights_data,
-                                 &vec![],
-                                 &vec![],
-                                 &self.input_blobs_data,
-                                 &mut self.input_blobs_gradient)
-        } else {
-            self.worker.backward_input(&self.backend,
-                                 &self.weights_data,
-                                 &self.output_blobs_data,
-                                 &self.output_blobs_gradient,
-                                 &self.input_blobs_data,
-                                 &mut self.input_blobs_gradient)
-        }
+        let backward_time = timeit_loops!(1, {
+            if self.is_using_in_place() {
+                self.worker.backward_input(&self.backend,
+                                     &self.weights_data,
+                                     &vec![],
+                                     &vec![],
+                                     &self.input_blobs_data,
+                                     &mut self.input_blobs_gradient)
+            } else {
+                self.worker.backward_input(&self.backend,
+                                     &self.weights_data,
+                                     &self.output_blobs_data,
+                                     &self.output_blobs_gradient,
+                                     &self.input_blobs_data,
+                                     &mut self.input_blobs_gradient)
+            }
+        });
+        log_event!(Subsystem::Backward, LogLevel::Debug, "backward_input", layer = self.name,
+                   duration_ms = format!("{:.5}", backward_time / 0.001));
+        let computed_gradients = self.input_blobs_gradient.iter().zip(self.input_need_backwards.iter())
+            .filter(|&(_, &need_backward)| need_backward)
+            .map(|(gradient, _)| gradient);
+        call_hooks(&self.name, &mut self.hooks.backward, computed_gradients);
 
         self.input_blobs_gradient.clone()
     }
@@ -526,20 +714,121 @@ impl<B: IBackend> Layer<B> {
     /// "Parameters" here refers to weights and also possibly bias, depending on the layer.
     ///
     /// This method is mostly used when doing backpropagation.
+    ///
+    /// The gradient of a weight that is [shared][1] with later layers of the container is the sum
+    /// of the gradients of all layers that use it. As backpropagation runs in reverse order,
+    /// the gradients of the later layers are already computed and are added to the gradient
+    /// of the layer that owns the weight.
+    /// [1]: ../weight/struct.WeightConfig.html#structfield.name
     pub fn backward_parameters(&mut self) {
+        if !self.trainable {
+            return
+        }
         self.worker.sync(&self.backend,
                          &mut self.input_blobs_data, &mut self.input_blobs_gradient,
                          &mut self.weights_data, &mut self.weights_gradient,
                          &mut self.output_blobs_data, &mut self.output_blobs_gradient);
 
-        self.worker.backward_parameters(&self.backend,
-                             &self.output_blobs_data,
-                             &self.output_blobs_gradient,
-                             &self.input_blobs_data,
-                             &mut self.weights_gradient)
+        let backward_time = timeit_loops!(1, {
+            self.worker.backward_parameters(&self.backend,
+                                 &self.output_blobs_data,
+                                 &self.output_blobs_gradient,
+                                 &self.input_blobs_data,
+                                 &mut self.weights_gradient)
+        });
+        log_event!(Subsystem::Backward, LogLevel::Debug, "backward_parameters", layer = self.name,
+                   duration_ms = format!("{:.5}", backward_time / 0.001));
+
+        for &(weight_id, ref sharing_gradients) in &self.shared_weights_gradients {
+            let sharing_gradients = sharing_gradients.read().unwrap();
+            if sharing_gradients.is_empty() {
+                continue;
+            }
+            let mut gradient = tensor_to_vec(&self.weights_gradient[weight_id]);
+            for sharing_gradient in sharing_gradients.iter() {
+                for (sum, value) in gradient.iter_mut().zip(tensor_to_vec(sharing_gradient)) {
+                    *sum += value;
+                }
+            }
+            write_tensor(&self.weights_gradient[weight_id], &gradient).unwrap();
+        }
+    }
+
+    /// Set if the weights of the layer should be updated during training.
+    ///
+    /// The weights of a layer that is not trainable keep their values, as no gradient
+    /// is computed for them and their learning rate is zero.
+    /// Gradients still flow through the layer to its inputs.
+    pub fn set_trainable(&mut self, trainable: bool) {
+        self.trainable = trainable;
+    }
+
+    /// Only train the layers from the layer `layer_name` onwards inside a container layer.
+    ///
+    /// The layers before it are only used for the forward step and are skipped during
+    /// backpropagation. Their weights are not updated.
+    pub fn train_from(&mut self, layer_name: &str) -> Result<(), String> {
+        self.worker.train_from(layer_name)
+    }
+
+    /// Set if the layer computes its outputs for training or for inference.
+    ///
+    /// Layers start in the training phase. Some layers only behave differently during
+    /// training, e.g. a [Noise][1] layer only adds noise while training and otherwise
+    /// passes its input through. Container layers set the phase of all their layers.
+    /// [1]: ../layers/common/noise/struct.Noise.html
+    pub fn set_training(&mut self, training: bool) {
+        self.worker.set_training(training)
+    }
+
+    /// Register `hook` to be called after every [forward][1] step of the layer `layer_name`.
+    /// [1]: #method.forward
+    ///
+    /// See [register_hook][2].
+    /// [2]: #method.register_hook
+    pub fn register_forward_hook<F: FnMut(&str, &SharedTensor<f32>) + 'static>(&mut self, layer_name: &str, hook: F) -> Result<(), String> {
+        self.register_hook(layer_name, HookKind::Forward, Box::new(hook))
+    }
+
+    /// Register `hook` to be called after every [backward_input][1] step of the layer `layer_name`.
+    /// [1]: #method.backward_input
+    ///
+    /// See [register_hook][2].
+    /// [2]: #method.register_hook
+    pub fn register_backward_hook<F: FnMut(&str, &SharedTensor<f32>) + 'static>(&mut self, layer_name: &str, hook: F) -> Result<(), String> {
+        self.register_hook(layer_name, HookKind::Backward, Box::new(hook))
+    }
+
+    /// Register `hook` on the layer `layer_name`, which is either this layer or a layer
+    /// inside this container layer, no matter how deeply nested.
+    ///
+    /// A forward hook is called with the name of the layer and each of its outputs, a backward hook
+    /// with each of the computed gradients w.r.t. its inputs. The hook receives a snapshot of the tensor
+    /// on a Native backend, so it can read the values and keep the tensor without affecting the network.
+    /// Hooks are called in the order they were registered. Taking the snapshots costs a copy of
+    /// the tensors, but layers without hooks are not affected.
+    ///
+    /// The hooks are not kept if the layer is replaced, e.g. by [replace_layer][1] or [optimize][2].
+    /// [1]: #method.replace_layer
+    /// [2]: #method.optimize
+    ///
+    /// Returns an error if there is no layer named `layer_name`.
+    pub fn register_hook(&mut self, layer_name: &str, kind: HookKind, hook: LayerHook) -> Result<(), String> {
+        if layer_name != self.name {
+            return self.worker.register_hook(layer_name, kind, hook);
+        }
+        log_event!(Subsystem::Init, LogLevel::Debug, "register_hook", layer = self.name, kind = format!("{:?}", kind));
+        match kind {
+            HookKind::Forward => self.hooks.forward.push(hook),
+            HookKind::Backward => self.hooks.backward.push(hook),
+        }
+        Ok(())
     }
 
     /// Synchronize the layers backend.
+    ///
+    /// Blocks until all computations queued on the backend, e.g. by [forward][1], are finished.
+    /// [1]: #method.forward
     pub fn synchronize(&self) {
         self.backend.synchronize().unwrap();
     }
@@ -584,6 +873,15 @@ impl<B: IBackend> Layer<B> {
     ///
     /// You can find the capnp schema [here](../../../../capnp/leaf.capnp).
     ///
+    /// The file is written next to `path` first and then renamed, so an interrupted save
+    /// leaves an existing file at `path` untouched. A checksum of the weights is saved with
+    /// them, which [load][1] verifies.
+    /// [1]: #method.load
+    ///
+    /// Returns an error of kind `InvalidInput` if the Layer was created with [from_worker][2],
+    /// since [load][1] couldn't create its implementation again.
+    /// [2]: #method.from_worker
+    ///
     /// ```
     /// # #[cfg(feature = "native")]
     /// # mod native {
@@ -617,23 +915,63 @@ impl<B: IBackend> Layer<B> {
     /// # }
     /// ```
     pub fn save<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.save_with_encoding(path, WeightEncoding::Float32)
+    }
+
+    /// Serialize the Layer to a Cap'n Proto file like [save][1], with the weights stored in `encoding`.
+    /// [1]: #method.save
+    ///
+    /// [WeightEncoding::Float16][2] and [WeightEncoding::Int8][2] make the file smaller
+    /// at the cost of precision. [load][3] converts the weights back to `f32`.
+    /// [2]: ./enum.WeightEncoding.html
+    /// [3]: #method.load
+    pub fn save_with_encoding<P: AsRef<Path>>(&mut self, path: P, encoding: WeightEncoding) -> io::Result<()> {
+        try!(self.check_restorable().map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error)));
         let path = path.as_ref();
-        let ref mut out = try!(File::create(path));
+        // write to a temporary file first, so an interrupted save never leaves a truncated file at `path`
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(format!(".{}.tmp", ::std::process::id()));
+        let saved = File::create(&temp_path).and_then(|mut out| {
+            let mut message = ::capnp::message::Builder::new_default();
+            {
+                let mut layer = message.init_root::<capnp_layer::Builder>();
+                self.write_capnp_with_encoding(&mut layer, encoding);
+            }
+            try!(::capnp::serialize_packed::write_message(&mut out, &message));
+            out.sync_all()
+        }).and_then(|_| fs::rename(&temp_path, path));
 
-        let mut message = ::capnp::message::Builder::new_default();
-        {
-            let mut layer = message.init_root::<capnp_layer::Builder>();
-            self.write_capnp(&mut layer);
+        // don't leave the temporary file behind if a step failed
+        if saved.is_err() {
+            let _ = fs::remove_file(&temp_path);
         }
-        ::capnp::serialize_packed::write_message(out, &message).unwrap();
+        saved
+    }
 
-        Ok(())
+    /// Returns an error if the Layer was created with [from_worker][1], so its config
+    /// doesn't describe its implementation.
+    /// [1]: #method.from_worker
+    fn check_restorable(&self) -> Result<(), String> {
+        if self.restorable {
+            Ok(())
+        } else {
+            Err(format!("Layer {} was created from a layer implementation, so it can't be created again from its config", self.name))
+        }
     }
 
     /// Read a Cap'n Proto file at the specified path and deserialize the Layer inside it.
     ///
     /// You can find the capnp schema [here](../../../../capnp/leaf.capnp).
     ///
+    /// Returns an error of kind `InvalidData` if the weights don't match the checksum saved with them,
+    /// or if a saved weight has a different shape than the weight of the Layer it belongs to.
+    ///
+    /// Weights of the Layer that have no value in the file keep their initial values and weights in
+    /// the file that match no weight of the Layer are ignored; both are logged as warnings.
+    /// Use [load_strict][1] to reject such files, or [load_with_report][2] to inspect them.
+    /// [1]: #method.load_strict
+    /// [2]: #method.load_with_report
+    ///
     /// ```
     /// # extern crate leaf;
     /// # extern crate collenchyma;
@@ -668,52 +1006,141 @@ impl<B: IBackend> Layer<B> {
     /// # }
     /// ```
     pub fn load<LB: IBackend + LayerOps<f32> + 'static, P: AsRef<Path>>(backend: Rc<LB>, path: P) -> io::Result<Layer<LB>> {
+        Self::load_with_report(backend, path).map(|(layer, _)| layer)
+    }
+
+    /// Read a Cap'n Proto file like [load][1], but require every weight to be restored.
+    /// [1]: #method.load
+    ///
+    /// Returns an error of kind `InvalidData` that lists the unmatched weights in the file
+    /// and the uninitialized weights of the Layer, if there are any.
+    pub fn load_strict<LB: IBackend + LayerOps<f32> + 'static, P: AsRef<Path>>(backend: Rc<LB>, path: P) -> io::Result<Layer<LB>> {
+        let (layer, report) = try!(Self::load_with_report(backend, path));
+        if !report.is_complete() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("The saved weights don't match the weights of layer {}: unmatched weights in the file: {:?}, uninitialized weights: {:?}",
+                                              layer.name, report.unmatched, report.uninitialized)));
+        }
+        Ok(layer)
+    }
+
+    /// Read a Cap'n Proto file like [load][1] and describe which weights were restored.
+    /// [1]: #method.load
+    ///
+    /// See [LoadReport][2].
+    /// [2]: ./struct.LoadReport.html
+    pub fn load_with_report<LB: IBackend + LayerOps<f32> + 'static, P: AsRef<Path>>(backend: Rc<LB>, path: P) -> io::Result<(Layer<LB>, LoadReport)> {
         let path = path.as_ref();
         let ref mut file = try!(File::open(path));
         let mut reader = BufReader::new(file);
 
-        let message_reader = ::capnp::serialize_packed::read_message(&mut reader,
-                                                                     ::capnp::message::ReaderOptions::new()).unwrap();
-        let read_layer = message_reader.get_root::<capnp_layer::Reader>().unwrap();
+        let message_reader = try!(::capnp::serialize_packed::read_message(&mut reader, ::capnp::message::ReaderOptions::new())
+                                  .map_err(|e| invalid_data(&format!("Can not read the Layer: {}", e))));
+        let read_layer = try!(message_reader.get_root::<capnp_layer::Reader>()
+                              .map_err(|e| invalid_data(&format!("Can not read the Layer: {}", e))));
+        try!(verify_weights_checksum(read_layer));
 
         let name = read_layer.get_name().unwrap().to_owned();
         let layer_config = LayerConfig::read_capnp(read_layer.get_config().unwrap());
         let mut layer = Layer::from_config(backend, &layer_config);
         layer.name = name;
+        let read_labels = read_layer.get_labels().unwrap();
+        layer.labels = (0..read_labels.len()).map(|i| read_labels.get(i).unwrap().to_owned()).collect();
 
         let read_weights = read_layer.get_weights_data().unwrap();
 
+        let paths = layer.learnable_weights_paths();
         let names = layer.learnable_weights_names();
         let weights_data = layer.learnable_weights_data();
 
         let native_backend = Backend::<Native>::default().unwrap();
-        for (i, (name, weight)) in names.iter().zip(weights_data).enumerate() {
-            for j in 0..read_weights.len() {
-                let capnp_weight = read_weights.get(i as u32);
-                if capnp_weight.get_name().unwrap() != name {
-                    continue
-                }
-
+        let mut report = LoadReport { loaded: Vec::new(), uninitialized: Vec::new(), unmatched: Vec::new() };
+        let mut matched_names = HashSet::new();
+        for ((path, name), weight) in paths.iter().zip(names.iter()).zip(weights_data) {
+            if let Some(j) = find_saved_weight(read_layer, path, name) {
+                let capnp_weight = read_weights.get(j);
+                matched_names.insert(capnp_weight.get_name().unwrap().to_owned());
                 let mut weight_lock = weight.write().unwrap();
                 weight_lock.sync(native_backend.device()).unwrap();
 
                 let capnp_tensor = capnp_weight.get_tensor().unwrap();
-                let mut shape = Vec::new();
-                let capnp_shape = capnp_tensor.get_shape().unwrap();
-                for k in 0..capnp_shape.len() {
-                    shape.push(capnp_shape.get(k) as usize)
+                let (shape, data) = try!(read_tensor(capnp_tensor));
+                if &shape != weight_lock.desc() {
+                    return Err(invalid_data(&format!("The saved weight {} of layer {} has the shape {:?}, but the layer expects {:?}",
+                                                     path, layer.name, shape, weight_lock.desc())));
                 }
-                weight_lock.reshape(&shape).unwrap();
 
-                let mut native_slice = weight_lock.get_mut(native_backend.device()).unwrap().as_mut_native().unwrap().as_mut_slice::<f32>();
-                let data = capnp_tensor.get_data().unwrap();
-                for k in 0..data.len() {
-                    native_slice[k as usize] = data.get(k);
+                let native_slice = weight_lock.get_mut(native_backend.device()).unwrap().as_mut_native().unwrap().as_mut_slice::<f32>();
+                native_slice.copy_from_slice(&data);
+                report.loaded.push(path.clone());
+            } else {
+                log_event!(Subsystem::Init, LogLevel::Warn, "uninitialized_weight", layer = layer.name, weight = path);
+                report.uninitialized.push(path.clone());
+            }
+        }
+        for j in 0..read_weights.len() {
+            let saved_name = read_weights.get(j).get_name().unwrap();
+            if !matched_names.contains(saved_name) {
+                log_event!(Subsystem::Init, LogLevel::Warn, "unmatched_weight", layer = layer.name, weight = saved_name);
+                report.unmatched.push(saved_name.to_owned());
+            }
+        }
+
+        Ok((layer, report))
+    }
+
+    /// Copy the weights of a Layer saved at `path` into this Layer, matching them by name.
+    ///
+    /// This makes it possible to warm-start a network whose architecture differs from the
+    /// saved one, e.g. with more outputs of a [Linear][1] layer. Weights with the same shape
+    /// are copied completely. Weights with the same number of dimensions but different
+    /// sizes only get the values in the overlap of both shapes copied, e.g. the first rows of
+    /// a Linear weight whose `output_size` changed; all other values are left unchanged.
+    /// [1]: ../layers/common/linear/index.html
+    ///
+    /// Returns a [WeightTransfer][2] for each weight of this Layer that describes what was copied.
+    /// [2]: ./struct.WeightTransfer.html
+    pub fn transplant_weights<P: AsRef<Path>>(&mut self, path: P) -> io::Result<Vec<WeightTransfer>> {
+        let path = path.as_ref();
+        let ref mut file = try!(File::open(path));
+        let mut reader = BufReader::new(file);
+
+        let message_reader = try!(::capnp::serialize_packed::read_message(&mut reader, ::capnp::message::ReaderOptions::new())
+                                  .map_err(|e| invalid_data(&format!("Can not read the Layer: {}", e))));
+        let read_layer = try!(message_reader.get_root::<capnp_layer::Reader>()
+                              .map_err(|e| invalid_data(&format!("Can not read the Layer: {}", e))));
+        try!(verify_weights_checksum(read_layer));
+        let read_weights = read_layer.get_weights_data().unwrap();
+
+        let native_backend = native_backend();
+        let mut transfers = Vec::new();
+        let paths = self.learnable_weights_paths();
+        for ((path, name), weight) in paths.iter().zip(self.learnable_weights_names()).zip(self.learnable_weights_data()) {
+            let mut weight_lock = weight.write().unwrap();
+            match weight_lock.add_device(native_backend.device()) { _ => weight_lock.sync(native_backend.device()).unwrap() }
+            let shape = weight_lock.desc().clone();
+
+            let mut transfer = WeightTransfer {
+                name: name.clone(),
+                shape: shape.clone(),
+                source_shape: None,
+                copied: 0,
+            };
+            if let Some(i) = find_saved_weight(read_layer, path, &name) {
+                let capnp_weight = read_weights.get(i);
+                let capnp_tensor = capnp_weight.get_tensor().unwrap();
+                let (source_shape, source) = try!(read_tensor(capnp_tensor));
+
+                if source_shape.len() == shape.len() {
+                    let native_slice = weight_lock.get_mut(native_backend.device()).unwrap().as_mut_native().unwrap().as_mut_slice::<f32>();
+                    transfer.copied = copy_overlap(&source, &source_shape, native_slice, &shape);
                 }
+                transfer.source_shape = Some(source_shape);
             }
+            transfers.push(transfer);
         }
 
-        Ok(layer)
+        Ok(transfers)
     }
 
     /// Sets whether the layer should compute gradients w.r.t. a
@@ -745,6 +1172,105 @@ impl<B: IBackend> Layer<B> {
         &self.input_blob_names
     }
 
+    /// Returns the names of all the output blobs.
+    ///
+    /// Anonymous output blobs are not included.
+    pub fn output_blob_names(&self) -> &[String] {
+        &self.output_blob_names
+    }
+
+    /// Returns the names and shapes of the inputs the layer expects.
+    ///
+    /// The shapes are read from the allocated input tensors, so they also reflect
+    /// reshapes that happened during initialization.
+    pub fn input_shapes(&self) -> Vec<(String, Vec<usize>)> {
+        self.input_names().into_iter()
+            .zip(self.input_blobs_data.iter())
+            .map(|(name, input)| (name, input.read().unwrap().desc().clone()))
+            .collect()
+    }
+
+    /// Returns the names of the inputs that can be fed to the layer.
+    ///
+    /// For container layers these are the names of the container inputs.
+    fn input_names(&self) -> Vec<String> {
+        if let Some(names) = self.worker.inputs_names() { names }
+        else { self.input_blob_names.clone() }
+    }
+
+    /// Returns the names of the outputs of the layer, in the order they are returned by [forward][1].
+    /// [1]: #method.forward
+    ///
+    /// For container layers these are the names of the blobs that are the container outputs.
+    pub fn output_names(&self) -> Vec<String> {
+        if let Some(names) = self.worker.outputs_names() { names }
+        else { self.output_blob_names.clone() }
+    }
+
+    /// Returns the labels of the classes of the first output, indexed by class id.
+    ///
+    /// Empty if no labels were [set][1].
+    /// [1]: #method.set_labels
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Set the labels of the classes of the first output, where `labels[i]` is the label of class `i`.
+    ///
+    /// The labels are saved with the Layer by [save][1] and restored by [load][2],
+    /// so the class ids of a deployed network can't get out of sync with their labels.
+    /// They are used by [predict_labeled][3].
+    /// [1]: #method.save
+    /// [2]: #method.load
+    /// [3]: #method.predict_labeled
+    pub fn set_labels<S: Into<String>>(&mut self, labels: Vec<S>) {
+        self.labels = labels.into_iter().map(|label| label.into()).collect();
+    }
+
+    /// Run the minibatch `inputs` through the Layer and return the [labeled][1] classes of
+    /// each sample with their probabilities.
+    /// [1]: #method.set_labels
+    ///
+    /// The first output is expected to hold the probabilities of the classes, with the
+    /// batchsize as its first dimension. If it is computed by a [LogSoftmax][2] layer,
+    /// its log probabilities are converted to probabilities.
+    /// For each sample the pairs of (label, probability) are ordered from most to least probable,
+    /// like in [top_k][3].
+    /// [2]: ../layers/common/log_softmax/index.html
+    /// [3]: ../util/fn.top_k.html
+    ///
+    /// Returns an error if the Layer has no labels or if their number doesn't match
+    /// the number of classes.
+    pub fn predict_labeled(&mut self, inputs: &[ArcLock<SharedTensor<f32>>]) -> Result<Vec<Vec<(String, f32)>>, String> {
+        if self.labels.is_empty() {
+            return Err(format!("Layer {} has no labels", self.name));
+        }
+        let output = self.forward(inputs)[0].clone();
+        let output_shape = output.read().unwrap().desc().clone();
+        let batch_size = output_shape.get(0).cloned().unwrap_or(1);
+        let num_classes = output_shape.size() / cmp::max(batch_size, 1);
+        if num_classes != self.labels.len() {
+            return Err(format!("Layer {} has {} labels, but its output has {} classes",
+                               self.name, self.labels.len(), num_classes));
+        }
+
+        let is_log_probability = self.layer_outputs().iter().rev()
+            .find(|layer| layer.outputs.iter().any(|layer_output| Arc::ptr_eq(layer_output, &output)))
+            .map_or(false, |layer| match layer.layer_type { LayerType::LogSoftmax(_) => true, _ => false });
+        let mut values = tensor_to_vec(&output);
+        if is_log_probability {
+            for value in &mut values {
+                *value = value.exp();
+            }
+        }
+
+        Ok(values.chunks(num_classes).map(|sample| {
+            let mut classes = sample.iter().cloned().enumerate().collect::<Vec<_>>();
+            classes.sort_by(|&(_, one), &(_, two)| two.partial_cmp(&one).unwrap_or(cmp::Ordering::Equal));
+            classes.into_iter().map(|(class_id, probability)| (self.labels[class_id].clone(), probability)).collect()
+        }).collect())
+    }
+
     /// Returns the [loss weight][1] associated with the weight blob
     /// with id `weight_id`.
     /// [1]: http://caffe.berkeleyvision.org/tutorial/loss.html
@@ -758,7 +1284,7 @@ impl<B: IBackend> Layer<B> {
     /// layers inside it.
     pub fn learnable_weights_data(&self) -> Vec<ArcLock<SharedTensor<f32>>> {
         if let Some(weights) = self.worker.learnable_weights() { weights }
-        else { self.weights_data.clone() }
+        else { self.learnable_weights.clone() }
     }
 
     /// Returns the gradients for all the learnable weights in the layer.
@@ -767,7 +1293,7 @@ impl<B: IBackend> Layer<B> {
     /// layers inside it.
     pub fn learnable_weights_gradients(&self) -> Vec<ArcLock<SharedTensor<f32>>> {
         if let Some(gradients) = self.worker.learnable_weights_gradients() { gradients }
-        else { self.weights_gradient.clone() }
+        else { self.learnable_weights_gradient.clone() }
     }
 
     /// Returns the names of all the learnable weights in the layer.
@@ -779,6 +1305,37 @@ impl<B: IBackend> Layer<B> {
         else { self.weights_display_names.clone() }
     }
 
+    /// Returns the hierarchical name of each of the learnable weights in the layer.
+    ///
+    /// The path starts with the name of the layer, followed by the names of the
+    /// containers and the layer the weight belongs to, e.g. `net/encoder/linear1/weight`.
+    /// Unlike the [names][1], the paths of the weights of different layers are distinct
+    /// as long as the layers of each container have distinct names.
+    /// Weights with a [configured name][2] are placed directly below the container of the layer,
+    /// e.g. `net/encoder1/weight` for a weight named `encoder1/weight`, so all layers that share
+    /// the weight report the same path.
+    /// [1]: #method.learnable_weights_names
+    /// [2]: ../weight/struct.WeightConfig.html#structfield.name
+    ///
+    /// The paths are used to match the weights when the layer is [saved][3] and [loaded][4].
+    /// [3]: #method.save
+    /// [4]: #method.load
+    pub fn learnable_weights_paths(&self) -> Vec<String> {
+        match self.worker.learnable_weights_paths() {
+            Some(paths) => paths.iter().map(|path| format!("{}/{}", self.name, path)).collect(),
+            None => self.weights_paths.clone(),
+        }
+    }
+
+    /// Returns the name of the layer that owns each of the learnable weights in the layer.
+    ///
+    /// If the layer is a container layer it will return the names of the
+    /// layers inside it.
+    pub fn learnable_weights_layer_names(&self) -> Vec<String> {
+        if let Some(names) = self.worker.learnable_weights_layer_names() { names }
+        else { self.learnable_weights_data().iter().map(|_| self.name.clone()).collect::<Vec<_>>() }
+    }
+
     /// Returns the learning rate for all the learnable weights in the layer.
     ///
     /// If the layer is a container layer it will return all learning rates of the
@@ -787,10 +1344,335 @@ impl<B: IBackend> Layer<B> {
         if let Some(lr) = self.worker.learnable_weights_lr() { lr }
         // else { self.weights_lr.clone() }
         else {
-            self.learnable_weights_data().iter().map(|_| Some(1f32)).collect::<Vec<_>>() }
+            let lr = if self.trainable { 1f32 } else { 0f32 };
+            self.learnable_weights_data().iter().map(|_| Some(lr)).collect::<Vec<_>>() }
+    }
+
+    /// Returns the multipliers of the weight decay for all the learnable weights in the layer,
+    /// as configured with [WeightConfig.decay_mult][1].
+    /// [1]: ../weight/struct.WeightConfig.html#structfield.decay_mult
+    ///
+    /// A multiplier that is not configured is `1.0`, see [WeightConfig::decay_mult][2].
+    /// [2]: ../weight/struct.WeightConfig.html#method.decay_mult
+    ///
+    /// If the layer is a container layer it will return all multipliers of the
+    /// layers inside it.
+    pub fn learnable_weights_decay_mult(&self) -> Vec<f32> {
+        if let Some(decay_mult) = self.worker.learnable_weights_decay_mult() { decay_mult }
+        else { self.weights_weight_decay.iter().map(|decay_mult| decay_mult.unwrap_or(1f32)).collect() }
+    }
+
+    /// Returns the layers in the order they are executed during a forward step.
+    ///
+    /// If the layer is a container layer it will return the layers inside it,
+    /// otherwise only the layer itself.
+    pub fn execution_order(&self) -> Vec<LayerNode> {
+        if let Some(nodes) = self.worker.execution_order() { nodes }
+        else { vec![LayerNode::from(self)] }
+    }
+
+    /// Returns the outputs of the layers in the order they are executed during a forward step.
+    ///
+    /// If the layer is a container layer it will return the outputs of the layers inside it,
+    /// including those of nested containers, otherwise only the outputs of the layer itself.
+    /// The tensors hold the values of the last [forward][1] step. Layers that compute their
+    /// output in-place share the tensor with the layer they follow, so only the last of them
+    /// sees its own values.
+    /// [1]: #method.forward
+    pub fn layer_outputs(&self) -> Vec<LayerOutputs> {
+        if let Some(outputs) = self.worker.layer_outputs() { outputs }
+        else {
+            vec![LayerOutputs {
+                name: self.name.clone(),
+                layer_type: self.config.layer_type.clone(),
+                output_names: self.output_blob_names.clone(),
+                outputs: self.output_blobs_data.clone(),
+            }]
+        }
+    }
+
+    /// Returns the dependencies between the layers as an adjacency list.
+    ///
+    /// Every layer of the [execution order][1] is listed together with the names
+    /// of the layers that use one of its outputs as input.
+    /// [1]: #method.execution_order
+    pub fn dependency_graph(&self) -> Vec<(String, Vec<String>)> {
+        let nodes = self.execution_order();
+        nodes.iter().enumerate().map(|(i, node)| {
+            let dependents = nodes.iter().skip(i + 1)
+                .filter(|other| other.inputs.iter().any(|input| node.outputs.contains(input)))
+                .map(|other| other.name.clone())
+                .collect();
+            (node.name.clone(), dependents)
+        }).collect()
+    }
+
+    /// Returns a description of the layer in the [DOT language][1] of Graphviz.
+    /// [1]: http://www.graphviz.org/content/dot-language
+    ///
+    /// The description contains the inputs and the layers of the [execution order][2]
+    /// with their number of parameters, connected by the blobs and their shapes.
+    /// It can be rendered with e.g. `dot -Tpng network.dot -o network.png`.
+    /// [2]: #method.execution_order
+    pub fn to_dot(&self) -> String {
+        fn escape(name: &str) -> String {
+            name.replace("\\", "\\\\").replace("\"", "\\\"")
+        }
+
+        let mut dot = format!("digraph \"{}\" {{\n", escape(&self.name));
+        // maps the blob names to the node that produced them and their shape
+        let mut producers = HashMap::<String, (String, Vec<usize>)>::new();
+        for (input_name, input_shape) in self.input_shapes() {
+            let node_id = format!("input:{}", input_name);
+            dot.push_str(&format!("    \"{}\" [shape=ellipse, label=\"{}\"];\n", escape(&node_id), escape(&input_name)));
+            producers.insert(input_name, (node_id, input_shape));
+        }
+        for node in self.execution_order() {
+            dot.push_str(&format!("    \"{}\" [shape=box, label=\"{}\\nparameters: {}\"];\n",
+                                  escape(&node.name), escape(&node.name), node.num_parameters));
+            for input_name in &node.inputs {
+                if let Some(&(ref producer, ref shape)) = producers.get(input_name) {
+                    dot.push_str(&format!("    \"{}\" -> \"{}\" [label=\"{} {:?}\"];\n",
+                                          escape(producer), escape(&node.name), escape(input_name), shape));
+                }
+            }
+            for (output_name, output_shape) in node.outputs.iter().zip(node.output_shapes.iter()) {
+                producers.insert(output_name.clone(), (node.name.clone(), output_shape.clone()));
+            }
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The output tensors of a Layer, see [Layer::layer_outputs][1].
+/// [1]: ./struct.Layer.html#method.layer_outputs
+pub struct LayerOutputs {
+    /// The name of the Layer.
+    pub name: String,
+    /// The type of the Layer.
+    pub layer_type: LayerType,
+    /// The names of the output blobs of the Layer.
+    pub output_names: Vec<String>,
+    /// The output tensors of the Layer.
+    pub outputs: Vec<ArcLock<SharedTensor<f32>>>,
+}
+
+/// A function that is called with the name of a layer and a snapshot of one of its tensors,
+/// see [Layer::register_hook][1].
+/// [1]: ./struct.Layer.html#method.register_hook
+pub type LayerHook = Box<FnMut(&str, &SharedTensor<f32>)>;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// The step of a Layer after which a [LayerHook][1] is called.
+/// [1]: ./type.LayerHook.html
+pub enum HookKind {
+    /// Called with the outputs after the forward step.
+    Forward,
+    /// Called with the gradients w.r.t. the inputs after the backward step.
+    Backward,
+}
+
+#[derive(Default)]
+struct LayerHooks {
+    forward: Vec<LayerHook>,
+    backward: Vec<LayerHook>,
+}
+
+impl fmt::Debug for LayerHooks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LayerHooks {{ forward: {}, backward: {} }}", self.forward.len(), self.backward.len())
+    }
+}
+
+/// Call every hook in `hooks` with a native snapshot of each of the `tensors`.
+fn call_hooks<'a, I: Iterator<Item=&'a ArcLock<SharedTensor<f32>>>>(layer_name: &str, hooks: &mut Vec<LayerHook>, tensors: I) {
+    if hooks.is_empty() {
+        return
+    }
+    let native = native_backend();
+    for tensor in tensors {
+        let shape = tensor.read().unwrap().desc().clone();
+        let snapshot = tensor_from_slice(&native, &shape, &tensor_to_vec(tensor)).unwrap();
+        for hook in hooks.iter_mut() {
+            hook(layer_name, &snapshot);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Describes how a Layer is connected to other Layers through its blobs.
+pub struct LayerNode {
+    /// The name of the Layer.
+    pub name: String,
+    /// The names of the input blobs of the Layer.
+    pub inputs: Vec<String>,
+    /// The names of the output blobs of the Layer.
+    pub outputs: Vec<String>,
+    /// The shapes of the output blobs of the Layer.
+    pub output_shapes: Vec<Vec<usize>>,
+    /// The number of learnable parameters of the Layer.
+    pub num_parameters: usize,
+}
+
+impl<'a, B: IBackend> From<&'a Layer<B>> for LayerNode {
+    fn from(layer: &'a Layer<B>) -> LayerNode {
+        LayerNode {
+            name: layer.name.clone(),
+            inputs: layer.input_blob_names.clone(),
+            outputs: layer.output_blob_names.clone(),
+            output_shapes: layer.output_blobs_data.iter().map(|output| output.read().unwrap().desc().clone()).collect(),
+            num_parameters: layer.learnable_weights_data().iter().map(|weight| weight.read().unwrap().desc().size()).fold(0, |sum, size| sum + size),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Describes how a Layer was built by [Layer::from_config_with_report][1].
+/// [1]: ./struct.Layer.html#method.from_config_with_report
+///
+/// The report contains the same information that is logged with the `leaf::init`
+/// target during construction, so it can be inspected without configuring a logger.
+pub struct BuildReport {
+    /// The connections of the layers in the order they are executed.
+    pub connections: Vec<LayerNode>,
+    /// The names and shapes of the inputs of the Layer.
+    pub inputs: Vec<(String, Vec<usize>)>,
+    /// The names and shapes of the blobs that were created for the outputs of the layers.
+    ///
+    /// Outputs that are computed in-place reuse the blob of their input and are not listed.
+    pub blobs: Vec<(String, Vec<usize>)>,
+    /// The names and shapes of the learnable weights that were created.
+    pub weights: Vec<(String, Vec<usize>)>,
+}
+
+impl<'a, B: IBackend> From<&'a Layer<B>> for BuildReport {
+    fn from(layer: &'a Layer<B>) -> BuildReport {
+        let connections = layer.execution_order();
+        let mut blobs = Vec::new();
+        for node in &connections {
+            for (output_name, output_shape) in node.outputs.iter().zip(node.output_shapes.iter()) {
+                if !node.inputs.contains(output_name) {
+                    blobs.push((output_name.clone(), output_shape.clone()));
+                }
+            }
+        }
+        let weights = layer.learnable_weights_names().into_iter()
+            .zip(layer.learnable_weights_data().iter().map(|weight| weight.read().unwrap().desc().clone()))
+            .collect();
+
+        BuildReport {
+            connections: connections,
+            inputs: layer.input_shapes(),
+            blobs: blobs,
+            weights: weights,
+        }
     }
 }
 
+impl fmt::Display for BuildReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(ref name, ref shape) in &self.inputs {
+            try!(writeln!(f, "input {} {:?}", name, shape));
+        }
+        for node in &self.connections {
+            try!(writeln!(f, "layer {}: {:?} -> {:?}", node.name, node.inputs, node.outputs));
+        }
+        for &(ref name, ref shape) in &self.blobs {
+            try!(writeln!(f, "blob {} {:?}", name, shape));
+        }
+        for &(ref name, ref shape) in &self.weights {
+            try!(writeln!(f, "weight {} {:?}", name, shape));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Describes what [Layer::transplant_weights][1] copied into a weight.
+/// [1]: ./struct.Layer.html#method.transplant_weights
+pub struct WeightTransfer {
+    /// The name of the weight.
+    pub name: String,
+    /// The shape of the weight.
+    pub shape: Vec<usize>,
+    /// The shape of the saved weight with the same name, if there is one.
+    pub source_shape: Option<Vec<usize>>,
+    /// The number of values that were copied.
+    ///
+    /// This is `0` if there is no saved weight with the same name, or if it has
+    /// a different number of dimensions.
+    pub copied: usize,
+}
+
+impl WeightTransfer {
+    /// Returns `true` if all values of the weight were copied.
+    pub fn is_complete(&self) -> bool {
+        self.copied == self.shape.iter().fold(1, |prod, i| prod * i)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Describes which weights [Layer::load_with_report][1] restored from a file.
+/// [1]: ./struct.Layer.html#method.load_with_report
+pub struct LoadReport {
+    /// The [paths][1] of the weights of the Layer that were restored.
+    /// [1]: ./struct.Layer.html#method.learnable_weights_paths
+    pub loaded: Vec<String>,
+    /// The paths of the weights of the Layer that have no value in the file.
+    ///
+    /// These weights keep the values they were initialized with.
+    pub uninitialized: Vec<String>,
+    /// The names of the weights in the file that match no weight of the Layer.
+    pub unmatched: Vec<String>,
+}
+
+impl LoadReport {
+    /// Returns `true` if every weight of the Layer was restored and every weight in the file was used.
+    pub fn is_complete(&self) -> bool {
+        self.uninitialized.is_empty() && self.unmatched.is_empty()
+    }
+}
+
+/// Describe that the layer `layer_name` can't output `blob_name`, because `producer` already outputs it,
+/// e.g. "another layer" or "layer first".
+///
+/// If the layer has an input at the position of the output, `in_place_input` is its name.
+pub fn duplicate_output_message(layer_name: &str, blob_name: &str, producer: &str, in_place_input: Option<&str>) -> String {
+    let hint = match in_place_input {
+        Some(input_name) => format!("To compute {} in-place, name its output {} like its input, otherwise choose a unique output name.", layer_name, input_name),
+        None => "Only a layer that computes in-place can output an existing blob, by naming its output like its input at the same position.".to_owned(),
+    };
+    format!("Layer {} can't output blob {}, because it is already produced by {}. {}", layer_name, blob_name, producer, hint)
+}
+
+/// Copy the values of `source` that lie in the overlap of `source_shape` and `target_shape` into `target`.
+///
+/// Both shapes need to have the same number of dimensions. Returns the number of copied values.
+fn copy_overlap(source: &[f32], source_shape: &[usize], target: &mut [f32], target_shape: &[usize]) -> usize {
+    let overlap = source_shape.iter().zip(target_shape.iter()).map(|(s, t)| cmp::min(*s, *t)).collect::<Vec<_>>();
+    let size = overlap.iter().fold(1, |prod, i| prod * i);
+    for index in 0..size {
+        let mut remainder = index;
+        let mut source_index = 0;
+        let mut target_index = 0;
+        let mut source_stride = 1;
+        let mut target_stride = 1;
+        for d in (0..overlap.len()).rev() {
+            let position = remainder % overlap[d];
+            remainder /= overlap[d];
+            source_index += position * source_stride;
+            target_index += position * target_stride;
+            source_stride *= source_shape[d];
+            target_stride *= target_shape[d];
+        }
+        target[target_index] = source[source_index];
+    }
+    size
+}
+
 #[allow(unsafe_code)]
 unsafe impl<B: IBackend> Send for Layer<B> {}
 
@@ -799,6 +1681,13 @@ impl<'a, B: IBackend> CapnpWrite<'a> for Layer<B> {
 
     /// Write the Layer into a capnp message.
     fn write_capnp(&self, builder: &mut Self::Builder) {
+        self.write_capnp_with_encoding(builder, WeightEncoding::Float32);
+    }
+}
+
+impl<B: IBackend> Layer<B> {
+    /// Write the Layer into a capnp message, with the weights stored in `encoding`.
+    fn write_capnp_with_encoding(&self, builder: &mut capnp_layer::Builder, encoding: WeightEncoding) {
         builder.set_name(&self.name);
         {
             let mut layer_config = builder.borrow().init_config();
@@ -806,8 +1695,8 @@ impl<'a, B: IBackend> CapnpWrite<'a> for Layer<B> {
         }
         {
             let native_backend = Backend::<Native>::default().unwrap();
-            let mut weights = builder.borrow().init_weights_data(self.learnable_weights_names().len() as u32);
-            let names = self.learnable_weights_names();
+            let names = self.learnable_weights_paths();
+            let mut weights = builder.borrow().init_weights_data(names.len() as u32);
             let weights_data = self.learnable_weights_data();
 
             for (i, (name, weight)) in names.iter().zip(weights_data).enumerate() {
@@ -824,36 +1713,222 @@ impl<'a, B: IBackend> CapnpWrite<'a> for Layer<B> {
                         tensor_shape.set(i as u32, *dim as u64);
                     }
                 }
-                {
-                    let native_slice = weight_lock.get(native_backend.device()).unwrap().as_native().unwrap().as_slice::<f32>();
-                    let mut tensor_data = tensor.borrow().init_data(native_slice.len() as u32);
-                    for (i, datum) in native_slice.iter().enumerate() {
-                        tensor_data.set(i as u32, *datum);
-                    }
-                }
+                let native_slice = weight_lock.get(native_backend.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+                write_tensor_data(&mut tensor, native_slice, encoding);
+            }
+        }
+        {
+            let mut labels = builder.borrow().init_labels(self.labels.len() as u32);
+            for (i, label) in self.labels.iter().enumerate() {
+                labels.set(i as u32, label);
             }
         }
+        let checksum = weights_checksum(builder.borrow().as_reader());
+        builder.set_weights_checksum(&[checksum as u8, (checksum >> 8) as u8, (checksum >> 16) as u8, (checksum >> 24) as u8]);
     }
 }
 
+/// Find the position of the saved weight with the hierarchical name `path` in a capnp Layer.
+///
+/// The paths are compared without the name of the outermost layer, so weights can also be
+/// transplanted between networks with different names.
+/// Files written before the weights were saved under their [paths][1] only contain the
+/// flat `legacy_name` (see [learnable_weights_names][2]), which is looked up as a fallback.
+/// [1]: ./struct.Layer.html#method.learnable_weights_paths
+/// [2]: ./struct.Layer.html#method.learnable_weights_names
+fn find_saved_weight(layer: capnp_layer::Reader, path: &str, legacy_name: &str) -> Option<u32> {
+    fn below_root(path: &str) -> &str {
+        path.splitn(2, '/').nth(1).unwrap_or(path)
+    }
+    let weights = layer.get_weights_data().unwrap();
+    (0..weights.len()).find(|&i| below_root(weights.get(i).get_name().unwrap()) == below_root(path))
+        .or_else(|| (0..weights.len()).find(|&i| weights.get(i).get_name().unwrap() == legacy_name))
+}
+
+/// Compute the CRC-32 checksum of the names, shapes and values of the weights of a capnp Layer.
+fn weights_checksum(layer: capnp_layer::Reader) -> u32 {
+    let mut crc = 0;
+    let weights = layer.get_weights_data().unwrap();
+    for i in 0..weights.len() {
+        let weight = weights.get(i);
+        crc = crc32(crc, weight.get_name().unwrap().as_bytes());
+        let tensor = weight.get_tensor().unwrap();
+        let shape = tensor.get_shape().unwrap();
+        for k in 0..shape.len() {
+            let dim = shape.get(k);
+            crc = crc32(crc, &(0..8).map(|byte| (dim >> (byte * 8)) as u8).collect::<Vec<u8>>());
+        }
+        let data = tensor.get_data().unwrap();
+        for k in 0..data.len() {
+            let bits = data.get(k).to_bits();
+            crc = crc32(crc, &[bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]);
+        }
+        crc = crc32(crc, tensor.get_encoded_data().unwrap());
+        let scale = tensor.get_scale().to_bits();
+        crc = crc32(crc, &[scale as u8, (scale >> 8) as u8, (scale >> 16) as u8, (scale >> 24) as u8]);
+    }
+    crc
+}
+
+/// Check the weights of a capnp Layer against the checksum that was saved with them.
+///
+/// Files written before checksums were introduced have none and are accepted.
+fn verify_weights_checksum(layer: capnp_layer::Reader) -> io::Result<()> {
+    let saved = layer.get_weights_checksum().unwrap();
+    if saved.is_empty() {
+        return Ok(());
+    }
+    let checksum = weights_checksum(layer);
+    if saved.len() != 4 || saved.iter().rev().fold(0u32, |value, &byte| (value << 8) | byte as u32) != checksum {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "The weights don't match their checksum, the file is corrupted"));
+    }
+    Ok(())
+}
+
+/// Write the `values` of a tensor into a capnp message in `encoding`.
+fn write_tensor_data(tensor: &mut capnp_tensor::Builder, values: &[f32], encoding: WeightEncoding) {
+    tensor.set_encoding(encoding.to_capnp());
+    match encoding {
+        WeightEncoding::Float32 => {
+            let mut bytes = Vec::with_capacity(values.len() * 4);
+            for value in values {
+                let bits = value.to_bits();
+                bytes.extend_from_slice(&[bits as u8, (bits >> 8) as u8, (bits >> 16) as u8, (bits >> 24) as u8]);
+            }
+            tensor.set_encoded_data(&bytes);
+        }
+        WeightEncoding::Float16 => {
+            let mut bytes = Vec::with_capacity(values.len() * 2);
+            for value in values {
+                let half = f32_to_f16(*value);
+                bytes.push(half as u8);
+                bytes.push((half >> 8) as u8);
+            }
+            tensor.set_encoded_data(&bytes);
+        }
+        WeightEncoding::Int8 => {
+            let max = values.iter().fold(0f32, |max, value| max.max(value.abs()));
+            let scale = max / 127f32;
+            let bytes = values.iter().map(|value| {
+                let quantized = if scale > 0f32 { (value / scale).round() } else { 0f32 };
+                quantized as i8 as u8
+            }).collect::<Vec<u8>>();
+            tensor.set_encoded_data(&bytes);
+            tensor.set_scale(scale);
+        }
+    }
+}
+
+/// Read the shape and the values of a tensor from a capnp message, converting the values to `f32`.
+///
+/// Returns an error of kind `InvalidData` if the number of values doesn't match the shape.
+fn read_tensor(tensor: capnp_tensor::Reader) -> io::Result<(Vec<usize>, Vec<f32>)> {
+    let capnp_shape = try!(tensor.get_shape().map_err(|_| invalid_data("The shape of a weight can not be read")));
+    let shape = (0..capnp_shape.len()).map(|k| capnp_shape.get(k) as usize).collect::<Vec<_>>();
+    let size = shape.iter().fold(1, |prod, i| prod * i);
+
+    let encoding = try!(tensor.get_encoding().map_err(|_| invalid_data("Unknown weight encoding")));
+    let bytes_per_value = match encoding {
+        CapnpWeightEncoding::Float32 => 4,
+        CapnpWeightEncoding::Float32List => 0,
+        CapnpWeightEncoding::Float16 => 2,
+        CapnpWeightEncoding::Int8 => 1,
+    };
+    let encoded_data = if bytes_per_value > 0 {
+        let encoded_data = try!(tensor.get_encoded_data().map_err(|_| invalid_data("The data of a weight can not be read")));
+        if encoded_data.len() != bytes_per_value * size {
+            return Err(invalid_data(&format!("A weight of shape {:?} has {} bytes of data instead of {}",
+                                             shape, encoded_data.len(), bytes_per_value * size)));
+        }
+        encoded_data
+    } else {
+        &[]
+    };
+
+    let data = match encoding {
+        CapnpWeightEncoding::Float32 => {
+            encoded_data.chunks(4)
+                .map(|bytes| f32::from_bits(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24))
+                .collect()
+        }
+        CapnpWeightEncoding::Float32List => {
+            let data = try!(tensor.get_data().map_err(|_| invalid_data("The data of a weight can not be read")));
+            if data.len() as usize != size {
+                return Err(invalid_data(&format!("A weight of shape {:?} has {} values instead of {}", shape, data.len(), size)));
+            }
+            (0..data.len()).map(|k| data.get(k)).collect()
+        }
+        CapnpWeightEncoding::Float16 => {
+            encoded_data.chunks(2)
+                .map(|half| f16_to_f32(half[0] as u16 | (half[1] as u16) << 8))
+                .collect()
+        }
+        CapnpWeightEncoding::Int8 => {
+            let scale = tensor.get_scale();
+            encoded_data.iter().map(|byte| *byte as i8 as f32 * scale).collect()
+        }
+    };
+    Ok((shape, data))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
 impl<B: IBackend + LayerOps<f32> + 'static> Layer<B> {
     /// Creates a new Layer from a [LayerConfig][1].
     /// [1]: ./struct.LayerConfig.html
     pub fn from_config(backend: Rc<B>, config: &LayerConfig) -> Layer<B> {
+        let worker = Layer::<B>::worker_from_config(backend.clone(), config);
+        let mut layer = Layer::from_worker(backend, config, worker);
+        layer.restorable = true;
+        layer
+    }
+
+    /// Creates a new Layer from a [LayerConfig][1] and describes how it was built.
+    /// [1]: ./struct.LayerConfig.html
+    ///
+    /// Like [from_config][2], but also returns a [BuildReport][3] with the connections
+    /// between the layers and the blobs and weights that were created.
+    /// [2]: #method.from_config
+    /// [3]: ./struct.BuildReport.html
+    pub fn from_config_with_report(backend: Rc<B>, config: &LayerConfig) -> (Layer<B>, BuildReport) {
+        let layer = Layer::from_config(backend, config);
+        let report = BuildReport::from(&layer);
+        (layer, report)
+    }
+
+    /// Creates a new Layer around an already constructed [layer implementation][1].
+    /// [1]: ./trait.ILayer.html
+    ///
+    /// This makes it possible to use layer implementations that are not part of Leaf.
+    /// The `config` provides the name, inputs, outputs and weight configuration of the Layer,
+    /// but its `layer_type` is not used to create the implementation.
+    /// As a consequence such a Layer can't be restored from its config: [save][2],
+    /// [save_for_inference][3] and [with_batch_size][4] return an error for it.
+    /// [2]: #method.save
+    /// [3]: #method.save_for_inference
+    /// [4]: #method.with_batch_size
+    pub fn from_worker(backend: Rc<B>, config: &LayerConfig, worker: Box<ILayer<B>>) -> Layer<B> {
         let cl = config.clone();
         let cfg = Box::<LayerConfig>::new(cl);
         let mut layer = Layer {
             name: cfg.name.clone(),
 
             needs_backward: true,
+            trainable: true,
+            restorable: false,
 
             weights_data: Vec::new(),
             weights_gradient: Vec::new(),
             learnable_weights: Vec::new(),
+            learnable_weights_gradient: Vec::new(),
             weight_propagate_down: Vec::new(),
             weights_lr: Vec::new(),
             weights_weight_decay: Vec::new(),
             weights_display_names: Vec::new(),
+            weights_paths: Vec::new(),
+            shared_weights_gradients: Vec::new(),
 
             input_blobs_data: Vec::new(),
             input_blobs_gradient: Vec::new(),
@@ -867,9 +1942,13 @@ impl<B: IBackend + LayerOps<f32> + 'static> Layer<B> {
 
             blob_names: HashMap::new(),
 
-            backend: backend.clone(),
+            hooks: LayerHooks::default(),
+
+            labels: Vec::new(),
 
-            worker: Layer::<B>::worker_from_config(backend, &cfg),
+            backend: backend,
+
+            worker: worker,
             config: cfg,
         };
         layer.expose_inputs();
@@ -878,24 +1957,126 @@ impl<B: IBackend + LayerOps<f32> + 'static> Layer<B> {
         layer
     }
 
+    /// Optimize a container layer for execution.
+    ///
+    /// The configuration of the container is optimized according to `level`
+    /// (see [SequentialConfig::optimize][1]) and the container is created again from it.
+    /// The weights of the layers that are kept are copied into the new container.
+    /// Since the container is created from its configuration, layers that were
+    /// added with [Sequential::add_layer_instance][2] are not kept.
+    /// [1]: ../layers/container/struct.SequentialConfig.html#method.optimize
+    /// [2]: ../layers/container/struct.Sequential.html#method.add_layer_instance
+    ///
+    /// Layers that are not containers, as well as layers created with [Layer::from_worker][3],
+    /// are left as they are.
+    /// [3]: #method.from_worker
+    pub fn optimize(&mut self, level: OptimizationLevel) {
+        if !self.restorable {
+            return;
+        }
+        let mut config = (*self.config).clone();
+        match config.layer_type {
+            LayerType::Sequential(ref mut container_config) => container_config.optimize(level),
+            _ => return,
+        }
+
+        *self = self.with_config(&config);
+    }
+
+    /// Save the Layer for inference to a Cap'n Proto file at the specified path.
+    ///
+    /// Like [save][1], but only what is needed to compute the outputs is saved:
+    /// layers that are only used for training (see [LayerType::is_training_only][2]) are removed
+    /// and the container is [optimized][3] with `OptimizationLevel::Full`.
+    /// The Layer itself is not changed. The file can be read with [load][4].
+    /// [1]: #method.save
+    /// [2]: ./enum.LayerType.html#method.is_training_only
+    /// [3]: #method.optimize
+    /// [4]: #method.load
+    pub fn save_for_inference<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        try!(self.check_restorable().map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error)));
+        let mut config = (*self.config).clone();
+        if let LayerType::Sequential(ref mut container_config) = config.layer_type {
+            container_config.remove_training_layers();
+            container_config.optimize(OptimizationLevel::Full);
+        }
+
+        self.with_config(&config).save(path)
+    }
+
+    /// Create a copy of the container with the batch size `batch_size` and the weights of this Layer.
+    ///
+    /// See [SequentialConfig::set_batch_size][1]. The Layer itself is not changed.
+    /// [1]: ../layers/container/struct.SequentialConfig.html#method.set_batch_size
+    ///
+    /// Returns an error if the Layer is not a container, was created with [from_worker][2]
+    /// or its batch size can't be changed.
+    /// [2]: #method.from_worker
+    pub fn with_batch_size(&self, batch_size: usize) -> Result<Layer<B>, String> {
+        try!(self.check_restorable());
+        let mut config = (*self.config).clone();
+        match config.layer_type {
+            LayerType::Sequential(ref mut container_config) => try!(container_config.set_batch_size(batch_size)),
+            _ => return Err(format!("Layer {} is not a container, so its batch size can not be changed", self.name)),
+        }
+
+        Ok(self.with_config(&config))
+    }
+
+    /// Create a new Layer from `config` that takes over the weights of this Layer with the same
+    /// [path][1].
+    /// [1]: #method.learnable_weights_paths
+    fn with_config(&self, config: &LayerConfig) -> Layer<B> {
+        let mut layer = Layer::from_config(self.backend.clone(), config);
+        layer.name = self.name.clone();
+        let paths = self.learnable_weights_paths();
+        let weights_data = self.learnable_weights_data();
+        let native_backend = native_backend();
+        for (path, new_weight) in layer.learnable_weights_paths().iter().zip(layer.learnable_weights_data()) {
+            if let Some(weight_id) = paths.iter().position(|weight_path| weight_path == path) {
+                let mut weight_lock = weights_data[weight_id].write().unwrap();
+                let mut new_weight_lock = new_weight.write().unwrap();
+                match weight_lock.add_device(native_backend.device()) { _ => weight_lock.sync(native_backend.device()).unwrap() }
+                match new_weight_lock.add_device(native_backend.device()) { _ => new_weight_lock.sync(native_backend.device()).unwrap() }
+                let values = weight_lock.get(native_backend.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+                write_to_memory(new_weight_lock.get_mut(native_backend.device()).unwrap(), values);
+            }
+        }
+        layer.labels = self.labels.clone();
+        layer
+    }
+
     /// Helper for [from_config] to match a [LayerType][2] to its [implementation][3].
     /// [1]: #method.from_config
     /// [2]: ./enum.LayerType.html
     /// [3]: ../layers/index.html
     fn worker_from_config(backend: Rc<B>, config: &LayerConfig) -> Box<ILayer<B>> {
         match config.layer_type.clone() {
+            LayerType::Constant(layer_config) => Box::new(Constant::from_config(&layer_config)),
             #[cfg(all(feature="cuda", not(feature="native")))]
             LayerType::Convolution(layer_config) => Box::new(Convolution::from_config(&layer_config)),
+            LayerType::FusedLinear(layer_config) => Box::new(FusedLinear::from_config(&layer_config)),
+            LayerType::GroupNorm(layer_config) => Box::new(GroupNorm::from_config(&layer_config)),
+            LayerType::InstanceNorm(layer_config) => Box::new(InstanceNorm::from_config(&layer_config)),
+            LayerType::LayerNorm(layer_config) => Box::new(LayerNorm::from_config(&layer_config)),
             LayerType::Linear(layer_config) => Box::new(Linear::from_config(&layer_config)),
-            LayerType::LogSoftmax => Box::new(LogSoftmax::default()),
+            LayerType::LogSoftmax(layer_config) => Box::new(LogSoftmax::from_config(&layer_config)),
+            LayerType::Noise(layer_config) => Box::new(Noise::from_config(&config.name, &layer_config)),
             #[cfg(all(feature="cuda", not(feature="native")))]
             LayerType::Pooling(layer_config) => Box::new(Pooling::from_config(&layer_config)),
+            LayerType::Scale(layer_config) => Box::new(Scale::from_config(&layer_config)),
             LayerType::Sequential(layer_config) => Box::new(Sequential::from_config(backend, &layer_config)),
-            LayerType::Softmax => Box::new(Softmax::default()),
+            LayerType::Softmax(layer_config) => Box::new(Softmax::from_config(&layer_config)),
+            LayerType::Maxout(layer_config) => Box::new(Maxout::from_config(&layer_config)),
+            LayerType::PReLU(layer_config) => Box::new(PReLU::from_config(&layer_config)),
             LayerType::ReLU => Box::new(ReLU),
             LayerType::Sigmoid => Box::new(Sigmoid),
+            LayerType::KLDivergence => Box::new(KLDivergence::default()),
             LayerType::NegativeLogLikelihood(layer_config) => Box::new(NegativeLogLikelihood::from_config(&layer_config)),
+            LayerType::Accuracy(layer_config) => Box::new(Accuracy::from_config(&layer_config)),
+            LayerType::Flatten(layer_config) => Box::new(Flatten::from_config(&layer_config)),
             LayerType::Reshape(layer_config) => Box::new(Reshape::from_config(&layer_config)),
+            LayerType::Silence => Box::new(Silence),
         }
     }
 }
@@ -1108,6 +2289,8 @@ pub trait ILayer<B: IBackend> : ComputeOutput<f32, B> + ComputeInputGradient<f32
     ///
     /// This method should be overridden to return a positive value if your
     /// layer expects some minimum number of output blobs.
+    /// Fewer output blobs are rejected by [Layer::connect][1].
+    /// [1]: ./struct.Layer.html#method.connect
     fn min_output_blobs(&self) -> usize {
         0
     }
@@ -1116,24 +2299,48 @@ pub trait ILayer<B: IBackend> : ComputeOutput<f32, B> + ComputeInputGradient<f32
     ///
     /// This method should be overridden to return a positive value if your
     /// layer expects some exact number of output blobs.
+    /// A different number of output blobs is rejected by [Layer::connect][1].
+    /// [1]: ./struct.Layer.html#method.connect
     fn exact_num_output_blobs(&self) -> Option<usize> {
         None
     }
     /// Return whether weight blobs are created automatically for the layer.
     ///
     /// If this method returns true, Network::init will create a weight blob
-    /// for every output blob.
+    /// for every output blob, or as many as [exact_num_weight_blobs][1] returns.
+    /// [1]: #method.exact_num_weight_blobs
     fn auto_weight_blobs(&self) -> bool {
         false
     }
+    /// Returns the exact number of weight blobs that are created automatically for the layer,
+    /// or `None` to create one for every output blob.
+    ///
+    /// This method should be overridden if [auto_weight_blobs][1] returns true and the
+    /// number of weights of your layer doesn't depend on its outputs.
+    /// [1]: #method.auto_weight_blobs
+    fn exact_num_weight_blobs(&self) -> Option<usize> {
+        None
+    }
     /// Returns the exact number of input blobs required by the layer,
     /// or `None` if no exact number is required.
     ///
     /// This method should be overridden to return a positive value if your
     /// layer expects some exact number of input blobs.
+    /// A different number of input blobs is rejected by [Layer::connect][1].
+    /// [1]: ./struct.Layer.html#method.connect
     fn exact_num_input_blobs(&self) -> Option<usize> {
         None
     }
+    /// Returns the minimum number of input blobs required by the layer,
+    /// or 0 if no minimum number is required.
+    ///
+    /// This method should be overridden to return a positive value if your
+    /// layer expects some minimum number of input blobs.
+    /// Fewer input blobs are rejected by [Layer::connect][1].
+    /// [1]: ./struct.Layer.html#method.connect
+    fn min_input_blobs(&self) -> usize {
+        0
+    }
     /// Return whether to allow force_backward for a given input blob index.
     ///
     /// If allow_force_backward(i) == false, we will ignore the force_backward
@@ -1194,6 +2401,65 @@ pub trait ILayer<B: IBackend> : ComputeOutput<f32, B> + ComputeInputGradient<f32
         None
     }
 
+    /// Return the names of the input tensors of the layer.
+    ///
+    /// This should only be overridden by container layers,
+    /// where the tensors are not easily exposable.
+    fn inputs_names(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Return the names of the output tensors of the layer.
+    ///
+    /// This should only be overridden by container layers,
+    /// where the tensors are not easily exposable.
+    fn outputs_names(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Replace the contained layer with the name `layer_name` by a new layer created from `config`.
+    ///
+    /// This should only be overridden by container layers.
+    fn replace_layer(&mut self, backend: Rc<B>, layer_name: &str, config: &LayerConfig) -> Result<(), String> {
+        Err(format!("Layer {} can not be replaced, as only container layers contain other layers.", layer_name))
+    }
+
+    /// Return the contained layers in the order they are executed.
+    ///
+    /// This should only be overridden by container layers.
+    fn execution_order(&self) -> Option<Vec<LayerNode>> {
+        None
+    }
+
+    /// Return the outputs of the contained layers in the order they are executed.
+    ///
+    /// This should only be overridden by container layers.
+    fn layer_outputs(&self) -> Option<Vec<LayerOutputs>> {
+        None
+    }
+
+    /// Only train the contained layers from the layer `layer_name` onwards.
+    ///
+    /// This should only be overridden by container layers.
+    fn train_from(&mut self, layer_name: &str) -> Result<(), String> {
+        Err(format!("Can not train from layer {}, as only container layers contain other layers.", layer_name))
+    }
+
+    /// Set if the layer computes its outputs for training or for inference, see [Layer::set_training][1].
+    /// [1]: ./struct.Layer.html#method.set_training
+    ///
+    /// This should be overridden by layers that behave differently during training
+    /// and by container layers.
+    fn set_training(&mut self, training: bool) {}
+
+    /// Register `hook` on the contained layer `layer_name`, see [Layer::register_hook][1].
+    /// [1]: ./struct.Layer.html#method.register_hook
+    ///
+    /// This should only be overridden by container layers.
+    fn register_hook(&mut self, layer_name: &str, kind: HookKind, hook: LayerHook) -> Result<(), String> {
+        Err(format!("Can not register a hook on layer {}, as only container layers contain other layers.", layer_name))
+    }
+
     /// Return the output tensors of the layer.
     ///
     /// This should only be overridden by container layers,
@@ -1226,6 +2492,15 @@ pub trait ILayer<B: IBackend> : ComputeOutput<f32, B> + ComputeInputGradient<f32
         None
     }
 
+    /// Return the paths of the learnable weights inside the layer relative to the layer,
+    /// see [Layer::learnable_weights_paths][1].
+    /// [1]: ./struct.Layer.html#method.learnable_weights_paths
+    ///
+    /// This should only be overridden by container layers.
+    fn learnable_weights_paths(&self) -> Option<Vec<String>> {
+        None
+    }
+
     /// Return the names of the learnable weights inside the layer.
     ///
     /// This should only be overridden by container layers,
@@ -1234,6 +2509,14 @@ pub trait ILayer<B: IBackend> : ComputeOutput<f32, B> + ComputeInputGradient<f32
         None
     }
 
+    /// Return the names of the layers that own the learnable weights inside the layer.
+    ///
+    /// This should only be overridden by container layers,
+    /// where the weights are not easily exposable.
+    fn learnable_weights_layer_names(&self) -> Option<Vec<String>> {
+        None
+    }
+
     /// Return the learning rates for the learnable weights inside the layer.
     ///
     /// This should only be overridden by container layers,
@@ -1241,6 +2524,14 @@ pub trait ILayer<B: IBackend> : ComputeOutput<f32, B> + ComputeInputGradient<f32
     fn learnable_weights_lr(&self) -> Option<Vec<Option<f32>>> {
         None
     }
+
+    /// Return the multipliers of the weight decay for the learnable weights inside the layer.
+    ///
+    /// This should only be overridden by container layers,
+    /// where the weights are not easily exposable.
+    fn learnable_weights_decay_mult(&self) -> Option<Vec<f32>> {
+        None
+    }
 }
 
 /// A Layer that can compute the output for a given input.
@@ -1282,6 +2573,54 @@ impl<B: IBackend> fmt::Debug for ILayer<B> {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// The optimizations [Layer::optimize][1] applies to a container layer.
+/// [1]: ./struct.Layer.html#method.optimize
+///
+/// Every level includes the optimizations of the previous levels.
+pub enum OptimizationLevel {
+    /// Leave the layer as it is.
+    None,
+    /// Remove layers that don't contribute to the outputs of the container.
+    Basic,
+    /// Additionally fuse activations into the preceding layer (see
+    /// [SequentialConfig::fuse_activations][2]) and compute layers in-place where legal.
+    /// [2]: ../layers/container/struct.SequentialConfig.html#method.fuse_activations
+    ///
+    /// The resulting layer computes the same outputs, but layers might no longer be
+    /// addressable by their name, so it is meant for deployment.
+    Full,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// The formats [Layer::save_with_encoding][1] can store weights in.
+/// [1]: ./struct.Layer.html#method.save_with_encoding
+pub enum WeightEncoding {
+    /// 32 bit floats, which store the weights exactly.
+    Float32,
+    /// 16 bit floats, which halve the size of the weights.
+    ///
+    /// Values are rounded to the nearest half-precision float, with a precision of about
+    /// three decimal digits. Values beyond `65504` become infinite.
+    Float16,
+    /// 8 bit integers and one scale per weight tensor, which quarter the size of the weights.
+    ///
+    /// The largest absolute value of a tensor is mapped to `127`, so the values of a tensor
+    /// are stored with a resolution of `1/127` of that value.
+    Int8,
+}
+
+impl WeightEncoding {
+    /// Return the corresponding Cap'n Proto value.
+    fn to_capnp(&self) -> CapnpWeightEncoding {
+        match *self {
+            WeightEncoding::Float32 => CapnpWeightEncoding::Float32,
+            WeightEncoding::Float16 => CapnpWeightEncoding::Float16,
+            WeightEncoding::Int8 => CapnpWeightEncoding::Int8,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Layer Configuration Struct
 pub struct LayerConfig {
@@ -1309,45 +2648,80 @@ pub struct LayerConfig {
 /// The Layer Types
 pub enum LayerType {
     // Common layers
+    /// Constant Layer
+    Constant(ConstantConfig),
     /// Convolution Layer
     #[cfg(all(feature="cuda", not(feature="native")))]
     Convolution(ConvolutionConfig),
+    /// FusedLinear Layer
+    FusedLinear(FusedLinearConfig),
+    /// GroupNorm Layer
+    GroupNorm(GroupNormConfig),
+    /// InstanceNorm Layer
+    InstanceNorm(InstanceNormConfig),
+    /// LayerNorm Layer
+    LayerNorm(LayerNormConfig),
     /// Linear Layer
     Linear(LinearConfig),
     /// LogSoftmax Layer
-    LogSoftmax,
+    LogSoftmax(LogSoftmaxConfig),
+    /// Noise Layer
+    Noise(NoiseConfig),
     /// Pooling Layer
     #[cfg(all(feature="cuda", not(feature="native")))]
     Pooling(PoolingConfig),
+    /// Scale Layer
+    Scale(ScaleConfig),
     /// Sequential Layer
     Sequential(SequentialConfig),
     /// Softmax Layer
-    Softmax,
+    Softmax(SoftmaxConfig),
     // Activation layers
+    /// Maxout Layer
+    Maxout(MaxoutConfig),
+    /// PReLU Layer
+    PReLU(PReLUConfig),
     /// ReLU Layer
     ReLU,
     /// Sigmoid Layer
     Sigmoid,
     // Loss layers
+    /// KLDivergence Layer
+    KLDivergence,
     /// NegativeLogLikelihood Layer
     NegativeLogLikelihood(NegativeLogLikelihoodConfig),
     // Utility layers
+    /// Accuracy Layer
+    Accuracy(AccuracyConfig),
+    /// Flatten Layer
+    Flatten(FlattenConfig),
     /// Reshape Layer
     Reshape(ReshapeConfig),
+    /// Silence Layer
+    Silence,
 }
 
 impl LayerType {
     /// Returns wether the LayerType supports in-place operations.
     pub fn supports_in_place(&self) -> bool {
         match *self {
+            LayerType::Constant(_) => false,
             #[cfg(all(feature="cuda", not(feature="native")))]
             LayerType::Convolution(_) => false,
+            LayerType::FusedLinear(_) => false,
+            LayerType::GroupNorm(_) => false,
+            LayerType::InstanceNorm(_) => false,
+            LayerType::LayerNorm(_) => false,
             LayerType::Linear(_) => false,
-            LayerType::LogSoftmax => false,
+            LayerType::LogSoftmax(_) => false,
+            LayerType::Noise(_) => false,
             #[cfg(all(feature="cuda", not(feature="native")))]
             LayerType::Pooling(_) => false,
+            LayerType::Scale(_) => false,
             LayerType::Sequential(_) => false,
-            LayerType::Softmax => false,
+            LayerType::Softmax(_) => false,
+            LayerType::Maxout(_) => false,
+            LayerType::PReLU(_) => false,
             #[cfg(all(feature="cuda", not(feature="native")))]
             LayerType::ReLU => true,
             #[cfg(feature="native")]
@@ -1356,11 +2730,40 @@ impl LayerType {
             LayerType::Sigmoid => true,
             #[cfg(feature="native")]
             LayerType::Sigmoid => false,
+            LayerType::KLDivergence => false,
             LayerType::NegativeLogLikelihood(_) => false,
+            LayerType::Accuracy(_) => false,
+            LayerType::Flatten(_) => true,
             LayerType::Reshape(_) => true,
+            LayerType::Silence => false,
+        }
+    }
+
+    /// Returns wether the LayerType only consumes blobs without producing outputs.
+    ///
+    /// A [Sequential][1] container doesn't connect layers of these types to the
+    /// previous and next layer.
+    /// [1]: ../layers/container/struct.Sequential.html
+    pub fn is_sink(&self) -> bool {
+        match *self {
+            LayerType::Silence => true,
+            _ => false,
         }
     }
 
+    /// Returns wether the LayerType is only needed for training.
+    ///
+    /// Layers of these types are removed by [SequentialConfig::remove_training_layers][1].
+    /// [1]: ../layers/container/struct.SequentialConfig.html#method.remove_training_layers
+    pub fn is_training_only(&self) -> bool {
+        match *self {
+            LayerType::KLDivergence => true,
+            LayerType::NegativeLogLikelihood(_) => true,
+            LayerType::Accuracy(_) => true,
+            LayerType::Noise(_) => true,
+            _ => false,
+        }
+    }
 }
 
 impl<'a> CapnpWrite<'a> for LayerType {
@@ -1369,14 +2772,22 @@ impl<'a> CapnpWrite<'a> for LayerType {
     /// Write the LayerType into a capnp message.
     fn write_capnp(&self, builder: &mut Self::Builder) {
         match self {
+            &LayerType::Constant(ref cfg) => { let ref mut config = builder.borrow().init_constant(); cfg.write_capnp(config); },
             #[cfg(all(feature="cuda", not(feature="native")))]
             &LayerType::Convolution(ref cfg) => { let ref mut config = builder.borrow().init_convolution(); cfg.write_capnp(config); },
+            &LayerType::FusedLinear(ref cfg) => { let ref mut config = builder.borrow().init_fused_linear(); cfg.write_capnp(config); },
+            &LayerType::GroupNorm(ref cfg) => { let ref mut config = builder.borrow().init_group_norm(); cfg.write_capnp(config); },
+            &LayerType::InstanceNorm(ref cfg) => { let ref mut config = builder.borrow().init_instance_norm(); cfg.write_capnp(config); },
+            &LayerType::LayerNorm(ref cfg) => { let ref mut config = builder.borrow().init_layer_norm(); cfg.write_capnp(config); },
             &LayerType::Linear(ref cfg) => { let ref mut config = builder.borrow().init_linear(); cfg.write_capnp(config); },
-            &LayerType::LogSoftmax => { builder.set_log_softmax(()) },
+            &LayerType::LogSoftmax(ref cfg) => { let ref mut config = builder.borrow().init_log_softmax_config(); cfg.write_capnp(config); },
             #[cfg(all(feature="cuda", not(feature="native")))]
             &LayerType::Pooling(ref cfg) => { let ref mut config = builder.borrow().init_pooling(); cfg.write_capnp(config); },
+            &LayerType::Scale(ref cfg) => { let ref mut config = builder.borrow().init_scale(); cfg.write_capnp(config); },
             &LayerType::Sequential(ref cfg) => { let ref mut config = builder.borrow().init_sequential(); cfg.write_capnp(config); },
-            &LayerType::Softmax => { builder.set_softmax(()) },
+            &LayerType::Softmax(ref cfg) => { let ref mut config = builder.borrow().init_softmax_config(); cfg.write_capnp(config); },
+            &LayerType::Maxout(ref cfg) => { let ref mut config = builder.borrow().init_maxout(); cfg.write_capnp(config); },
+            &LayerType::PReLU(ref cfg) => { let ref mut config = builder.borrow().init_prelu(); cfg.write_capnp(config); },
             #[cfg(all(feature="cuda", not(feature="native")))]
             &LayerType::ReLU => { builder.set_relu(()) },
             #[cfg(feature="native")]
@@ -1385,8 +2796,13 @@ impl<'a> CapnpWrite<'a> for LayerType {
             &LayerType::Sigmoid => { builder.set_sigmoid(()) },
             #[cfg(feature="native")]
             &LayerType::Sigmoid => { builder.set_sigmoid(()) },
+            &LayerType::KLDivergence => { builder.set_kl_divergence(()) },
             &LayerType::NegativeLogLikelihood(ref cfg) => { let ref mut config = builder.borrow().init_negative_log_likelihood(); cfg.write_capnp(config); },
+            &LayerType::Accuracy(ref cfg) => { let ref mut config = builder.borrow().init_accuracy(); cfg.write_capnp(config); },
+            &LayerType::Noise(ref cfg) => { let ref mut config = builder.borrow().init_noise(); cfg.write_capnp(config); },
+            &LayerType::Flatten(ref cfg) => { let ref mut config = builder.borrow().init_flatten(); cfg.write_capnp(config); },
             &LayerType::Reshape(ref cfg) => { let ref mut config = builder.borrow().init_reshape(); cfg.write_capnp(config); },
+            &LayerType::Silence => { builder.set_silence(()) },
         }
     }
 }
@@ -1396,22 +2812,37 @@ impl<'a> CapnpRead<'a> for LayerType {
 
     fn read_capnp(reader: Self::Reader) -> Self {
         match reader.which().unwrap() {
+            capnp_layer_type::Which::Constant(read_config) => { let config = ConstantConfig::read_capnp(read_config.unwrap()); LayerType::Constant(config) },
             #[cfg(all(feature="cuda", not(feature="native")))]
             capnp_layer_type::Which::Convolution(read_config) => { let config = ConvolutionConfig::read_capnp(read_config.unwrap()); LayerType::Convolution(config) },
             #[cfg(not(all(feature="cuda", not(feature="native"))))]
             capnp_layer_type::Which::Convolution(_) => { panic!("Can not load Network because Convolution layer is not supported with the used feature flags.") },
+            capnp_layer_type::Which::FusedLinear(read_config) => { let config = FusedLinearConfig::read_capnp(read_config.unwrap()); LayerType::FusedLinear(config) },
+            capnp_layer_type::Which::GroupNorm(read_config) => { let config = GroupNormConfig::read_capnp(read_config.unwrap()); LayerType::GroupNorm(config) },
+            capnp_layer_type::Which::InstanceNorm(read_config) => { let config = InstanceNormConfig::read_capnp(read_config.unwrap()); LayerType::InstanceNorm(config) },
+            capnp_layer_type::Which::LayerNorm(read_config) => { let config = LayerNormConfig::read_capnp(read_config.unwrap()); LayerType::LayerNorm(config) },
             capnp_layer_type::Which::Linear(read_config) => { let config = LinearConfig::read_capnp(read_config.unwrap()); LayerType::Linear(config) },
-            capnp_layer_type::Which::LogSoftmax(read_config) => { LayerType::LogSoftmax },
+            capnp_layer_type::Which::LogSoftmax(_) => { LayerType::LogSoftmax(LogSoftmaxConfig::default()) },
+            capnp_layer_type::Which::LogSoftmaxConfig(read_config) => { let config = LogSoftmaxConfig::read_capnp(read_config.unwrap()); LayerType::LogSoftmax(config) },
             #[cfg(all(feature="cuda", not(feature="native")))]
             capnp_layer_type::Which::Pooling(read_config) => { let config = PoolingConfig::read_capnp(read_config.unwrap()); LayerType::Pooling(config) },
             #[cfg(not(all(feature="cuda", not(feature="native"))))]
             capnp_layer_type::Which::Pooling(_) => { panic!("Can not load Network because Pooling layer is not supported with the used feature flags.") },
+            capnp_layer_type::Which::Scale(read_config) => { let config = ScaleConfig::read_capnp(read_config.unwrap()); LayerType::Scale(config) },
             capnp_layer_type::Which::Sequential(read_config) => { let config = SequentialConfig::read_capnp(read_config.unwrap()); LayerType::Sequential(config) },
-            capnp_layer_type::Which::Softmax(_) => { LayerType::Softmax },
+            capnp_layer_type::Which::Softmax(_) => { LayerType::Softmax(SoftmaxConfig::default()) },
+            capnp_layer_type::Which::SoftmaxConfig(read_config) => { let config = SoftmaxConfig::read_capnp(read_config.unwrap()); LayerType::Softmax(config) },
+            capnp_layer_type::Which::Maxout(read_config) => { let config = MaxoutConfig::read_capnp(read_config.unwrap()); LayerType::Maxout(config) },
+            capnp_layer_type::Which::Prelu(read_config) => { let config = PReLUConfig::read_capnp(read_config.unwrap()); LayerType::PReLU(config) },
             capnp_layer_type::Which::Relu(_) => { LayerType::ReLU },
             capnp_layer_type::Which::Sigmoid(_) => { LayerType::Sigmoid },
+            capnp_layer_type::Which::KlDivergence(_) => { LayerType::KLDivergence },
             capnp_layer_type::Which::NegativeLogLikelihood(read_config) => { let config = NegativeLogLikelihoodConfig::read_capnp(read_config.unwrap()); LayerType::NegativeLogLikelihood(config) },
+            capnp_layer_type::Which::Accuracy(read_config) => { let config = AccuracyConfig::read_capnp(read_config.unwrap()); LayerType::Accuracy(config) },
+            capnp_layer_type::Which::Noise(read_config) => { let config = NoiseConfig::read_capnp(read_config.unwrap()); LayerType::Noise(config) },
+            capnp_layer_type::Which::Flatten(read_config) => { let config = FlattenConfig::read_capnp(read_config.unwrap()); LayerType::Flatten(config) },
             capnp_layer_type::Which::Reshape(read_config) => { let config = ReshapeConfig::read_capnp(read_config.unwrap()); LayerType::Reshape(config) },
+            capnp_layer_type::Which::Silence(_) => { LayerType::Silence },
         }
     }
 }
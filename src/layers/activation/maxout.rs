@@ -0,0 +1,173 @@
+//! Applies the Maxout function over groups of channels.
+//!
+//! Non-linearity activation function: y_c = max(x_(c * k), ..., x_(c * k + k - 1))
+//!
+//! Each output channel is the maximum of `k` consecutive input channels, the linear
+//! pieces of the unit. Together with a preceding linear or convolution layer that
+//! computes the pieces, a Maxout unit can learn a convex activation function itself
+//! instead of using a fixed one like [ReLU][relu]. See [Maxout Networks][paper].
+//!
+//! The gradient is only routed to the piece that was the maximum.
+//!
+//! ## Input Data
+//!
+//! If the input has more than one dimension, the first dimension is the batch size and
+//! the second one holds the channels, e.g. `[batch, channels, height, width]`.
+//! A one dimensional input is a single sample of channels.
+//! The number of channels has to be a multiple of `k`.
+//!
+//! [relu]: ../relu/index.html
+//! [paper]: https://arxiv.org/abs/1302.4389
+use co::{IBackend, SharedTensor};
+use layer::*;
+use util::{ArcLock, native_backend, write_to_memory};
+use leaf_capnp::maxout_config as capnp_config;
+use capnp_util::*;
+use layers::channel_dims;
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// Maxout Activation Layer
+pub struct Maxout {
+    num_pieces: usize,
+}
+
+impl Maxout {
+    /// Create a Maxout layer from a MaxoutConfig.
+    pub fn from_config(config: &MaxoutConfig) -> Maxout {
+        if config.num_pieces == 0 {
+            panic!("A Maxout layer needs at least one piece per unit");
+        }
+        Maxout {
+            num_pieces: config.num_pieces,
+        }
+    }
+
+    /// Returns the index of the input value that is the maximum for each output value.
+    fn argmax(&self, input_shape: &[usize], input: &[f32]) -> Vec<usize> {
+        let (batch_size, channels, channel_size) = channel_dims(input_shape);
+        let units = channels / self.num_pieces;
+        let mut indices = Vec::with_capacity(batch_size * units * channel_size);
+        for sample in 0..batch_size {
+            for unit in 0..units {
+                for position in 0..channel_size {
+                    let piece_index = |piece: usize| ((sample * channels + unit * self.num_pieces + piece) * channel_size) + position;
+                    let max_index = (1..self.num_pieces).map(&piece_index)
+                        .fold(piece_index(0), |max_index, index| if input[index] > input[max_index] { index } else { max_index });
+                    indices.push(max_index);
+                }
+            }
+        }
+        indices
+    }
+}
+
+impl<B: IBackend> ILayer<B> for Maxout {
+    impl_ilayer_activation!();
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        let input_shape = input_data[0].read().unwrap().desc().clone();
+        let channel_dim = if input_shape.len() > 1 { 1 } else { 0 };
+        let channels = input_shape.get(channel_dim).cloned().unwrap_or(1);
+        if channels % self.num_pieces != 0 {
+            panic!("A Maxout layer with {} pieces per unit can't take an input with {} channels", self.num_pieces, channels);
+        }
+        let mut output_shape = input_shape.clone();
+        if !output_shape.is_empty() {
+            output_shape[channel_dim] = channels / self.num_pieces;
+        }
+
+        input_gradient[0].write().unwrap().resize(&input_shape).unwrap();
+        output_data[0].write().unwrap().resize(&output_shape).unwrap();
+        output_gradient[0].write().unwrap().resize(&output_shape).unwrap();
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for Maxout {
+    fn compute_output(&self,
+                      backend: &B,
+                      _weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let output = self.argmax(input_data[0].desc(), input).iter().map(|&index| input[index]).collect::<Vec<_>>();
+        write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &output);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for Maxout {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let mut input_gradient = vec![0f32; input.len()];
+        for (&index, gradient) in self.argmax(input_data[0].desc(), input).iter().zip(output_gradient) {
+            input_gradient[index] = *gradient;
+        }
+        write_to_memory(input_gradients[0].get_mut(native.device()).unwrap(), &input_gradient);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for Maxout { }
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// Specifies configuration parameters for a Maxout Layer.
+pub struct MaxoutConfig {
+    /// The number of linear pieces, i.e. input channels, of each unit.
+    ///
+    /// Default: `2`
+    pub num_pieces: usize,
+}
+
+impl ::std::default::Default for MaxoutConfig {
+    fn default() -> MaxoutConfig {
+        MaxoutConfig {
+            num_pieces: 2,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for MaxoutConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the MaxoutConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_num_pieces(self.num_pieces as u64);
+    }
+}
+
+impl<'a> CapnpRead<'a> for MaxoutConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        MaxoutConfig {
+            num_pieces: reader.get_num_pieces() as usize,
+        }
+    }
+}
+
+impl Into<LayerType> for MaxoutConfig {
+    fn into(self) -> LayerType {
+        LayerType::Maxout(self)
+    }
+}
@@ -18,6 +18,14 @@
 //!
 //! The activation function is also sometimes called transfer function.
 //!
+//! Besides these elementwise functions, the [Maxout][mod_maxout] layer computes the
+//! maximum over groups of channels, so it reduces the number of channels.
+//!
+//! The negative slope of [PReLU][mod_prelu] is learned per channel, so unlike the other
+//! activation layers it owns a weight.
+//!
+//! [mod_maxout]: ./maxout/index.html
+//! [mod_prelu]: ./prelu/index.html
 //! [mod_sigmoid]: ./sigmoid/index.html
 //! [mod_relu]: ./relu/index.html
 //! [struct_layerconfig]: ../../layer/struct.LayerConfig.html
@@ -29,10 +37,14 @@ macro_rules! impl_ilayer_activation {
     )
 }
 
+pub use self::maxout::{Maxout, MaxoutConfig};
+pub use self::prelu::{PReLU, PReLUConfig};
 pub use self::relu::ReLU;
 pub use self::sigmoid::Sigmoid;
 pub use self::tanh::TanH;
 
+pub mod maxout;
+pub mod prelu;
 pub mod relu;
 pub mod sigmoid;
 pub mod tanh;
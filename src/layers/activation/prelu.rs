@@ -0,0 +1,185 @@
+//! Applies the parametric rectified linear unit function.
+//!
+//! Non-linearity activation function: y_c = max(0, x) + a_c * min(0, x)
+//!
+//! Unlike [ReLU][relu] the slope `a_c` of the negative part is learned, one for every
+//! channel. With a slope of `0` it is equal to ReLU. See [Delving Deep into Rectifiers][paper].
+//!
+//! The slopes are stored in the weight of the layer, with the shape `[channels]`.
+//!
+//! ## Input Data
+//!
+//! If the input has more than one dimension, the first dimension is the batch size and
+//! the second one holds the channels, e.g. `[batch, channels, height, width]`.
+//! A one dimensional input is a single sample of channels.
+//!
+//! [relu]: ../relu/index.html
+//! [paper]: https://arxiv.org/abs/1502.01852
+use co::{IBackend, SharedTensor};
+use layer::*;
+use util::{ArcLock, native_backend, write_to_memory};
+use leaf_capnp::prelu_config as capnp_config;
+use capnp_util::*;
+use layers::channel_dims;
+
+#[derive(Debug, Clone, Copy)]
+/// PReLU Activation Layer
+pub struct PReLU {
+    initial_slope: f32,
+}
+
+impl PReLU {
+    /// Create a PReLU layer from a PReLUConfig.
+    pub fn from_config(config: &PReLUConfig) -> PReLU {
+        PReLU {
+            initial_slope: config.initial_slope,
+        }
+    }
+
+    /// Returns the channel of every input value.
+    fn channels(input_shape: &[usize]) -> Vec<usize> {
+        let (batch_size, channels, channel_size) = channel_dims(input_shape);
+        (0..batch_size * channels * channel_size).map(|index| index / channel_size % channels).collect()
+    }
+}
+
+impl<B: IBackend> ILayer<B> for PReLU {
+    impl_ilayer_activation!();
+
+    fn auto_weight_blobs(&self) -> bool {
+        true
+    }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        let input_shape = input_data[0].read().unwrap().desc().clone();
+        input_gradient[0].write().unwrap().resize(&input_shape).unwrap();
+        output_data[0].write().unwrap().resize(&input_shape).unwrap();
+        output_gradient[0].write().unwrap().resize(&input_shape).unwrap();
+
+        let (_, channels, _) = channel_dims(&input_shape);
+        if let Some(weight) = weights_data.get(0) {
+            let mut weight = weight.write().unwrap();
+            weight.resize(&vec![channels]).unwrap();
+            let native = native_backend();
+            match weight.add_device(native.device()) { _ => weight.sync(native.device()).unwrap() }
+            write_to_memory(weight.get_mut(native.device()).unwrap(), &vec![self.initial_slope; channels]);
+        }
+        if let Some(weight) = weights_gradient.get(0) {
+            weight.write().unwrap().resize(&vec![channels]).unwrap();
+        }
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for PReLU {
+    fn compute_output(&self,
+                      backend: &B,
+                      weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let slope = weights[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let output = input.iter().zip(Self::channels(input_data[0].desc()))
+            .map(|(&x, channel)| if x > 0f32 { x } else { slope[channel] * x })
+            .collect::<Vec<_>>();
+        write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &output);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for PReLU {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let slope = weights_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let input_gradient = input.iter().zip(output_gradient).zip(Self::channels(input_data[0].desc()))
+            .map(|((&x, &dy), channel)| if x > 0f32 { dy } else { slope[channel] * dy })
+            .collect::<Vec<_>>();
+        write_to_memory(input_gradients[0].get_mut(native.device()).unwrap(), &input_gradient);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for PReLU {
+    fn compute_parameters_gradient(&self,
+                                   backend: &B,
+                                   output_data: &[&SharedTensor<f32>],
+                                   output_gradients: &[&SharedTensor<f32>],
+                                   input_data: &[&SharedTensor<f32>],
+                                   parameters_gradients: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let (_, channels, _) = channel_dims(input_data[0].desc());
+        let mut slope_gradient = vec![0f32; channels];
+        for ((&x, &dy), channel) in input.iter().zip(output_gradient).zip(Self::channels(input_data[0].desc())) {
+            if x <= 0f32 {
+                slope_gradient[channel] += dy * x;
+            }
+        }
+
+        match parameters_gradients[0].add_device(native.device()) { _ => parameters_gradients[0].sync(native.device()).unwrap() }
+        write_to_memory(parameters_gradients[0].get_mut(native.device()).unwrap(), &slope_gradient);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Specifies configuration parameters for a PReLU Layer.
+pub struct PReLUConfig {
+    /// The slope of the negative part that every channel starts with.
+    ///
+    /// Default: `0.25`
+    pub initial_slope: f32,
+}
+
+impl ::std::default::Default for PReLUConfig {
+    fn default() -> PReLUConfig {
+        PReLUConfig {
+            initial_slope: 0.25f32,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for PReLUConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the PReLUConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_initial_slope(self.initial_slope);
+    }
+}
+
+impl<'a> CapnpRead<'a> for PReLUConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        PReLUConfig {
+            initial_slope: reader.get_initial_slope(),
+        }
+    }
+}
+
+impl Into<LayerType> for PReLUConfig {
+    fn into(self) -> LayerType {
+        LayerType::PReLU(self)
+    }
+}
@@ -0,0 +1,188 @@
+//! Outputs a tensor that does not depend on the input data.
+//!
+//! The output tensor can either be learnable, in which case it is a weight of the
+//! layer that is updated by the [Solver][1], or fixed to a constant value.
+//!
+//! Typical uses are bias-only models, learned positional embeddings or the
+//! initial hidden state of recurrent networks.
+//!
+//! Inputs of the layer are accepted but ignored, so the layer can be placed
+//! anywhere in a [Sequential][2] container. No gradient is propagated to them.
+//!
+//! [1]: ../../../solver/struct.Solver.html
+//! [2]: ../../container/struct.Sequential.html
+use co::{IBackend, ITensorDesc, SharedTensor};
+use layer::*;
+use util::{ArcLock, native_backend, write_to_memory};
+use weight::FillerType;
+use leaf_capnp::constant_config as capnp_config;
+use capnp_util::*;
+
+#[derive(Debug, Clone)]
+/// Constant Layer
+pub struct Constant {
+    shape: Vec<usize>,
+    value: f32,
+    learnable: bool,
+}
+
+impl Constant {
+    /// Create a Constant layer from a ConstantConfig.
+    pub fn from_config(config: &ConstantConfig) -> Constant {
+        Constant {
+            shape: config.shape.clone(),
+            value: config.value,
+            learnable: config.learnable,
+        }
+    }
+}
+
+impl<B: IBackend> ILayer<B> for Constant {
+    fn exact_num_output_blobs(&self) -> Option<usize> { Some(1) }
+
+    fn auto_weight_blobs(&self) -> bool {
+        self.learnable
+    }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        output_data[0].write().unwrap().resize(&self.shape).unwrap();
+        output_gradient[0].write().unwrap().resize(&self.shape).unwrap();
+        if let Some(weight) = weights_data.get(0) {
+            weight.write().unwrap().resize(&self.shape).unwrap();
+            let filler = FillerType::Constant {
+                value: self.value,
+            };
+            filler.fill(&mut weight.write().unwrap());
+        }
+        if let Some(weight) = weights_gradient.get(0) {
+            weight.write().unwrap().resize(&self.shape).unwrap();
+        }
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for Constant {
+    fn compute_output(&self,
+                      backend: &B,
+                      weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let output = match weights.get(0) {
+            Some(weight) => weight.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned(),
+            None => vec![self.value; output_data[0].desc().size()],
+        };
+
+        write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &output);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for Constant {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        for input_gradient in input_gradients {
+            let zeros = vec![0f32; input_gradient.desc().size()];
+            write_to_memory(input_gradient.get_mut(native.device()).unwrap(), &zeros);
+        }
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for Constant {
+    fn compute_parameters_gradient(&self,
+                                   backend: &B,
+                                   output_data: &[&SharedTensor<f32>],
+                                   output_gradients: &[&SharedTensor<f32>],
+                                   input_data: &[&SharedTensor<f32>],
+                                   parameters_gradients: &mut [&mut SharedTensor<f32>]) {
+        // the output is the weight itself, so the output gradient is the weight gradient
+        if let Some(weight_gradient) = parameters_gradients.get_mut(0) {
+            let native = native_backend();
+            let output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            write_to_memory(weight_gradient.get_mut(native.device()).unwrap(), output_gradient);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Specifies configuration parameters for a Constant Layer.
+pub struct ConstantConfig {
+    /// The shape of the output tensor.
+    pub shape: Vec<usize>,
+    /// The value the output is filled with.
+    ///
+    /// If the output is learnable this is the initial value of the weight.
+    ///
+    /// Default: `0.0`
+    pub value: f32,
+    /// Whether the output is a learnable weight or stays fixed.
+    ///
+    /// Default: `true`
+    pub learnable: bool,
+}
+
+impl ConstantConfig {
+    /// Create a ConstantConfig that describes a learnable Constant layer with a provided shape.
+    pub fn of_shape(shape: &[usize]) -> ConstantConfig {
+        ConstantConfig {
+            shape: shape.to_owned(),
+            value: 0f32,
+            learnable: true,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for ConstantConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the ConstantConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        {
+            let mut shape = builder.borrow().init_shape(self.shape.len() as u32);
+            for (i, dim) in self.shape.iter().enumerate() {
+                shape.set(i as u32, *dim as u64);
+            }
+        }
+        builder.set_value(self.value);
+        builder.set_learnable(self.learnable);
+    }
+}
+
+impl<'a> CapnpRead<'a> for ConstantConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        let read_shape = reader.get_shape().unwrap();
+        let mut shape = Vec::new();
+        for i in 0..read_shape.len() {
+            shape.push(read_shape.get(i) as usize)
+        }
+
+        ConstantConfig {
+            shape: shape,
+            value: reader.get_value(),
+            learnable: reader.get_learnable(),
+        }
+    }
+}
+
+impl Into<LayerType> for ConstantConfig {
+    fn into(self) -> LayerType {
+        LayerType::Constant(self)
+    }
+}
@@ -226,11 +226,44 @@ pub struct ConvolutionConfig {
     /// The size of the kernel
     pub filter_shape: Vec<usize>,
     /// The stride size
+    ///
+    /// Default: `[1]`
     pub stride: Vec<usize>,
     /// The padding size
+    ///
+    /// Default: `[0]`
     pub padding: Vec<usize>,
 }
 
+impl ConvolutionConfig {
+    /// Create a ConvolutionConfig that describes a Convolution layer with `num_output`
+    /// feature maps and a kernel of the shape `filter_shape`.
+    ///
+    /// A single value in `filter_shape`, `stride` or `padding` is used for all spatial dimensions.
+    pub fn new(num_output: usize, filter_shape: &[usize]) -> ConvolutionConfig {
+        ConvolutionConfig {
+            num_output: num_output,
+            filter_shape: filter_shape.to_owned(),
+            stride: vec![1],
+            padding: vec![0],
+        }
+    }
+
+    /// Set the [stride][1].
+    /// [1]: #structfield.stride
+    pub fn stride(mut self, stride: &[usize]) -> ConvolutionConfig {
+        self.stride = stride.to_owned();
+        self
+    }
+
+    /// Set the [padding][1].
+    /// [1]: #structfield.padding
+    pub fn padding(mut self, padding: &[usize]) -> ConvolutionConfig {
+        self.padding = padding.to_owned();
+        self
+    }
+}
+
 impl Into<LayerType> for ConvolutionConfig {
     fn into(self) -> LayerType {
         LayerType::Convolution(self)
@@ -304,13 +337,7 @@ mod tests {
     #[test]
     #[cfg(feature="cuda")]
     fn correct_shapes() {
-        let cfg = ConvolutionConfig {
-            num_output: 64,
-
-            filter_shape: vec![11],
-            padding: vec![2],
-            stride: vec![4],
-        };
+        let cfg = ConvolutionConfig::new(64, &[11]).stride(&[4]).padding(&[2]);
         let layer = Convolution::<Backend<Cuda>>::from_config(&cfg);
         let num_spatial_dims = layer.num_spatial_dims(&vec![1, 3, 224, 224]);
         assert_eq!(2, num_spatial_dims);
@@ -0,0 +1,217 @@
+//! Applies a linear transformation followed by an activation function `y = f(a * x)`.
+//!
+//! This computes the same as a [Linear][linear] layer followed by a [ReLU][relu] or
+//! [Sigmoid][sigmoid] layer, but the activation is applied in-place to the output of the
+//! matrix multiplication right after it is computed. The activation still takes a second pass
+//! over the output, but no separate output tensor and gradient are allocated for it,
+//! which reduces the memory usage of MLPs.
+//!
+//! The input data is handled like in the [Linear][linear] layer.
+//!
+//! There is usually no need to configure this layer directly:
+//! [SequentialConfig::fuse_activations][fuse] replaces matching Linear and activation layers
+//! of a network.
+//!
+//! [linear]: ../linear/index.html
+//! [relu]: ../../activation/relu/index.html
+//! [sigmoid]: ../../activation/sigmoid/index.html
+//! [fuse]: ../../container/struct.SequentialConfig.html#method.fuse_activations
+use std::rc::Rc;
+use co::backend::IBackend;
+use co::tensor::SharedTensor;
+use layer::*;
+use util::{ArcLock, LayerOps};
+#[cfg(feature="native")]
+use util::native_backend;
+use super::linear::{Linear, LinearConfig};
+use leaf_capnp::fused_linear_config as capnp_config;
+use leaf_capnp::FusedActivation as CapnpFusedActivation;
+use capnp_util::*;
+
+#[derive(Debug)]
+/// FusedLinear Layer
+pub struct FusedLinear {
+    linear: Linear,
+    activation: FusedActivation,
+}
+
+impl FusedLinear {
+    /// Create a FusedLinear layer from a FusedLinearConfig.
+    pub fn from_config(config: &FusedLinearConfig) -> FusedLinear {
+        FusedLinear {
+            linear: Linear::from_config(&LinearConfig::new(config.output_size)),
+            activation: config.activation,
+        }
+    }
+
+    /// Apply the activation function to the output of the linear transformation in-place.
+    #[cfg(feature="native")]
+    fn activate<B: IBackend + LayerOps<f32>>(&self, _backend: &B, output: &mut SharedTensor<f32>) {
+        let native = native_backend();
+        match output.add_device(native.device()) { _ => output.sync(native.device()).unwrap() }
+        for value in output.get_mut(native.device()).unwrap().as_mut_native().unwrap().as_mut_slice::<f32>() {
+            *value = match self.activation {
+                FusedActivation::ReLU => value.max(0f32),
+                FusedActivation::Sigmoid => 1f32 / (1f32 + (-*value).exp()),
+            };
+        }
+    }
+
+    /// Apply the activation function to the output of the linear transformation in-place.
+    #[cfg(all(feature="cuda", not(feature="native")))]
+    fn activate<B: IBackend + LayerOps<f32>>(&self, backend: &B, output: &mut SharedTensor<f32>) {
+        match self.activation {
+            FusedActivation::ReLU => backend.relu_pointwise_plain(output).unwrap(),
+            FusedActivation::Sigmoid => backend.sigmoid_pointwise_plain(output).unwrap(),
+        }
+    }
+
+    /// Compute the gradient w.r.t. the output of the linear transformation.
+    ///
+    /// Both activations can compute their derivative from their output alone,
+    /// so the value before the activation doesn't have to be kept.
+    fn activation_gradient<B: IBackend + LayerOps<f32>>(&self, backend: &B, output: &SharedTensor<f32>, output_gradient: &SharedTensor<f32>) -> SharedTensor<f32> {
+        let mut gradient = SharedTensor::<f32>::new(IBackend::device(backend), output_gradient.desc()).unwrap();
+        match self.activation {
+            FusedActivation::ReLU => backend.relu_grad_plain(output, output_gradient, output, &mut gradient).unwrap(),
+            FusedActivation::Sigmoid => backend.sigmoid_grad_plain(output, output_gradient, output, &mut gradient).unwrap(),
+        }
+        gradient
+    }
+}
+
+impl<B: IBackend + LayerOps<f32>> ILayer<B> for FusedLinear {
+    impl_ilayer_common!();
+
+    fn auto_weight_blobs(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, backend: Rc<B>) {
+        ILayer::<B>::init(&mut self.linear, backend);
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        ILayer::<B>::reshape(&mut self.linear, backend, input_data, input_gradient, weights_data, weights_gradient, output_data, output_gradient);
+    }
+}
+
+impl<B: IBackend + LayerOps<f32>> ComputeOutput<f32, B> for FusedLinear {
+    fn compute_output(&self,
+                      backend: &B,
+                      weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        self.linear.compute_output(backend, weights, input_data, output_data);
+        self.activate(backend, output_data[0]);
+    }
+}
+
+impl<B: IBackend + LayerOps<f32>> ComputeInputGradient<f32, B> for FusedLinear {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let linear_gradient = self.activation_gradient(backend, output_data[0], output_gradients[0]);
+        self.linear.compute_input_gradient(backend, weights_data, output_data, &[&linear_gradient], input_data, input_gradients);
+    }
+}
+
+impl<B: IBackend + LayerOps<f32>> ComputeParametersGradient<f32, B> for FusedLinear {
+    fn compute_parameters_gradient(&self,
+                                   backend: &B,
+                                   output_data: &[&SharedTensor<f32>],
+                                   output_gradients: &[&SharedTensor<f32>],
+                                   input_data: &[&SharedTensor<f32>],
+                                   parameters_gradients: &mut [&mut SharedTensor<f32>]) {
+        let linear_gradient = self.activation_gradient(backend, output_data[0], output_gradients[0]);
+        self.linear.compute_parameters_gradient(backend, output_data, &[&linear_gradient], input_data, parameters_gradients);
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// Specifies configuration parameters for a FusedLinear Layer.
+pub struct FusedLinearConfig {
+    /// The number of output values
+    pub output_size: usize,
+    /// The activation function applied to the output values
+    pub activation: FusedActivation,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// The activation functions that can be fused into a [FusedLinear][1] layer.
+/// [1]: ./struct.FusedLinear.html
+pub enum FusedActivation {
+    /// Rectified linear units, see [ReLU](../../activation/relu/index.html)
+    ReLU,
+    /// Sigmoid function, see [Sigmoid](../../activation/sigmoid/index.html)
+    Sigmoid,
+}
+
+impl FusedActivation {
+    /// Return the FusedActivation that computes the same as a layer of `layer_type`, if any.
+    pub fn from_layer_type(layer_type: &LayerType) -> Option<FusedActivation> {
+        match *layer_type {
+            LayerType::ReLU => Some(FusedActivation::ReLU),
+            LayerType::Sigmoid => Some(FusedActivation::Sigmoid),
+            _ => None,
+        }
+    }
+
+    /// Return the corresponding Cap'n Proto value.
+    fn to_capnp(&self) -> CapnpFusedActivation {
+        match *self {
+            FusedActivation::ReLU => CapnpFusedActivation::Relu,
+            FusedActivation::Sigmoid => CapnpFusedActivation::Sigmoid,
+        }
+    }
+
+    /// Return the enum value for a Cap'n Proto value.
+    fn from_capnp(value: CapnpFusedActivation) -> Self {
+        match value {
+            CapnpFusedActivation::Relu => FusedActivation::ReLU,
+            CapnpFusedActivation::Sigmoid => FusedActivation::Sigmoid,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for FusedLinearConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the FusedLinearConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.borrow().set_output_size(self.output_size as u64);
+        builder.borrow().set_activation(self.activation.to_capnp());
+    }
+}
+
+impl<'a> CapnpRead<'a> for FusedLinearConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        let output_size = reader.get_output_size() as usize;
+        let activation = FusedActivation::from_capnp(reader.get_activation().unwrap());
+
+        FusedLinearConfig {
+            output_size: output_size,
+            activation: activation,
+        }
+    }
+}
+
+impl Into<LayerType> for FusedLinearConfig {
+    fn into(self) -> LayerType {
+        LayerType::FusedLinear(self)
+    }
+}
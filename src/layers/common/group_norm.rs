@@ -0,0 +1,169 @@
+//! Normalizes groups of channels of each sample with a learnable gain and bias.
+//!
+//! The channels of every sample are split into `num_groups` groups of consecutive channels.
+//! The values of each group are normalized to zero mean and unit variance, then every
+//! channel is scaled and shifted by its gain and bias.
+//!
+//! As the statistics are computed per sample the layer does not depend on the batch size,
+//! which makes it suitable for training with small minibatches, e.g. in object detection
+//! and segmentation.
+//! With one group it normalizes each sample as a whole; with one group per channel
+//! it is equal to [InstanceNorm][1].
+//! [1]: ../instance_norm/index.html
+//!
+//! ## Input Data
+//!
+//! The input has the shape `[N, C, ...]`: **the first dimension is treated as batch size**,
+//! the second as channels and all remaining dimensions are spatial.
+//!
+//! See [normalization][2] for how gain and bias are stored.
+//! [2]: ../normalization/index.html
+use co::{IBackend, SharedTensor};
+use layer::*;
+use util::ArcLock;
+use super::normalization::{NormalizationLayer, NormalizationShape};
+use leaf_capnp::group_norm_config as capnp_config;
+use capnp_util::*;
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// GroupNorm Layer
+pub struct GroupNorm {
+    num_groups: usize,
+    epsilon: f32,
+}
+
+impl GroupNorm {
+    /// Create a GroupNorm layer from a GroupNormConfig.
+    pub fn from_config(config: &GroupNormConfig) -> GroupNorm {
+        GroupNorm {
+            num_groups: config.num_groups,
+            epsilon: config.epsilon,
+        }
+    }
+}
+
+impl NormalizationLayer for GroupNorm {
+    fn epsilon(&self) -> f32 {
+        self.epsilon
+    }
+
+    fn normalization_shape(&self, input_shape: &[usize]) -> NormalizationShape {
+        if input_shape.len() < 2 {
+            panic!("The input of a GroupNorm layer needs a batch and a channel dimension, got {:?}.", input_shape);
+        }
+        NormalizationShape {
+            batch_size: input_shape[0],
+            channels: input_shape[1],
+            spatial_size: input_shape.iter().skip(2).fold(1, |prod, i| prod * i),
+            groups: self.num_groups,
+        }
+    }
+}
+
+impl<B: IBackend> ILayer<B> for GroupNorm {
+    impl_ilayer_common!();
+
+    fn auto_weight_blobs(&self) -> bool {
+        true
+    }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        self.reshape_normalization(input_data, weights_data, weights_gradient, output_data, output_gradient);
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for GroupNorm {
+    fn compute_output(&self,
+                      backend: &B,
+                      weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        self.normalization_output(weights, input_data, output_data);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for GroupNorm {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        self.normalization_input_gradient(weights_data, output_gradients, input_data, input_gradients);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for GroupNorm {
+    fn compute_parameters_gradient(&self,
+                                   backend: &B,
+                                   output_data: &[&SharedTensor<f32>],
+                                   output_gradients: &[&SharedTensor<f32>],
+                                   input_data: &[&SharedTensor<f32>],
+                                   parameters_gradients: &mut [&mut SharedTensor<f32>]) {
+        self.normalization_parameters_gradient(output_gradients, input_data, parameters_gradients);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Specifies configuration parameters for a GroupNorm Layer.
+pub struct GroupNormConfig {
+    /// The number of groups the channels are split into.
+    ///
+    /// The number of channels must be divisible by it.
+    ///
+    /// Default: `32`
+    pub num_groups: usize,
+    /// The value added to the variance to avoid a division by zero.
+    ///
+    /// Default: `0.00001`
+    pub epsilon: f32,
+}
+
+impl ::std::default::Default for GroupNormConfig {
+    fn default() -> GroupNormConfig {
+        GroupNormConfig {
+            num_groups: 32,
+            epsilon: 1e-5f32,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for GroupNormConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the GroupNormConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_num_groups(self.num_groups as u64);
+        builder.set_epsilon(self.epsilon);
+    }
+}
+
+impl<'a> CapnpRead<'a> for GroupNormConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        GroupNormConfig {
+            num_groups: reader.get_num_groups() as usize,
+            epsilon: reader.get_epsilon(),
+        }
+    }
+}
+
+impl Into<LayerType> for GroupNormConfig {
+    fn into(self) -> LayerType {
+        LayerType::GroupNorm(self)
+    }
+}
@@ -0,0 +1,153 @@
+//! Normalizes every channel of each sample with a learnable gain and bias.
+//!
+//! The values of each channel of a sample are normalized to zero mean and unit variance,
+//! then scaled and shifted by the gain and bias of the channel.
+//! This is the same as a [GroupNorm][1] with one group per channel and is commonly
+//! used for style transfer and image generation.
+//! [1]: ../group_norm/index.html
+//!
+//! ## Input Data
+//!
+//! The input has the shape `[N, C, ...]`: **the first dimension is treated as batch size**,
+//! the second as channels and all remaining dimensions are spatial.
+//!
+//! See [normalization][2] for how gain and bias are stored.
+//! [2]: ../normalization/index.html
+use co::{IBackend, SharedTensor};
+use layer::*;
+use util::ArcLock;
+use super::normalization::{NormalizationLayer, NormalizationShape};
+use leaf_capnp::instance_norm_config as capnp_config;
+use capnp_util::*;
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// InstanceNorm Layer
+pub struct InstanceNorm {
+    epsilon: f32,
+}
+
+impl InstanceNorm {
+    /// Create a InstanceNorm layer from a InstanceNormConfig.
+    pub fn from_config(config: &InstanceNormConfig) -> InstanceNorm {
+        InstanceNorm {
+            epsilon: config.epsilon,
+        }
+    }
+}
+
+impl NormalizationLayer for InstanceNorm {
+    fn epsilon(&self) -> f32 {
+        self.epsilon
+    }
+
+    fn normalization_shape(&self, input_shape: &[usize]) -> NormalizationShape {
+        if input_shape.len() < 2 {
+            panic!("The input of a InstanceNorm layer needs a batch and a channel dimension, got {:?}.", input_shape);
+        }
+        NormalizationShape {
+            batch_size: input_shape[0],
+            channels: input_shape[1],
+            spatial_size: input_shape.iter().skip(2).fold(1, |prod, i| prod * i),
+            groups: input_shape[1],
+        }
+    }
+}
+
+impl<B: IBackend> ILayer<B> for InstanceNorm {
+    impl_ilayer_common!();
+
+    fn auto_weight_blobs(&self) -> bool {
+        true
+    }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        self.reshape_normalization(input_data, weights_data, weights_gradient, output_data, output_gradient);
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for InstanceNorm {
+    fn compute_output(&self,
+                      backend: &B,
+                      weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        self.normalization_output(weights, input_data, output_data);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for InstanceNorm {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        self.normalization_input_gradient(weights_data, output_gradients, input_data, input_gradients);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for InstanceNorm {
+    fn compute_parameters_gradient(&self,
+                                   backend: &B,
+                                   output_data: &[&SharedTensor<f32>],
+                                   output_gradients: &[&SharedTensor<f32>],
+                                   input_data: &[&SharedTensor<f32>],
+                                   parameters_gradients: &mut [&mut SharedTensor<f32>]) {
+        self.normalization_parameters_gradient(output_gradients, input_data, parameters_gradients);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Specifies configuration parameters for a InstanceNorm Layer.
+pub struct InstanceNormConfig {
+    /// The value added to the variance to avoid a division by zero.
+    ///
+    /// Default: `0.00001`
+    pub epsilon: f32,
+}
+
+impl ::std::default::Default for InstanceNormConfig {
+    fn default() -> InstanceNormConfig {
+        InstanceNormConfig {
+            epsilon: 1e-5f32,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for InstanceNormConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the InstanceNormConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_epsilon(self.epsilon);
+    }
+}
+
+impl<'a> CapnpRead<'a> for InstanceNormConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        InstanceNormConfig {
+            epsilon: reader.get_epsilon(),
+        }
+    }
+}
+
+impl Into<LayerType> for InstanceNormConfig {
+    fn into(self) -> LayerType {
+        LayerType::InstanceNorm(self)
+    }
+}
@@ -0,0 +1,156 @@
+//! Normalizes each sample over its features with a learnable gain and bias.
+//!
+//! For every sample the mean `μ` and the variance `σ²` of its values are computed and
+//! the output is `y = γ * (x - μ) / sqrt(σ² + ε) + β`, where `γ` (gain) and `β` (bias)
+//! hold one value per feature.
+//!
+//! Unlike batch normalization the statistics do not depend on the other samples of the
+//! minibatch, so the layer behaves the same during training and inference and can be used
+//! with small batches and in recurrent networks.
+//!
+//! ## Input Data
+//!
+//! If the input has more than one dimension **the first dimension is treated as batch size**
+//! and all other dimensions are normalized together. A one dimensional input is a single sample.
+//!
+//! See [normalization][1] for how gain and bias are stored.
+//! [1]: ../normalization/index.html
+use co::{IBackend, SharedTensor};
+use layer::*;
+use util::ArcLock;
+use super::normalization::{NormalizationLayer, NormalizationShape};
+use leaf_capnp::layer_norm_config as capnp_config;
+use capnp_util::*;
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// LayerNorm Layer
+pub struct LayerNorm {
+    epsilon: f32,
+}
+
+impl LayerNorm {
+    /// Create a LayerNorm layer from a LayerNormConfig.
+    pub fn from_config(config: &LayerNormConfig) -> LayerNorm {
+        LayerNorm {
+            epsilon: config.epsilon,
+        }
+    }
+}
+
+impl NormalizationLayer for LayerNorm {
+    fn epsilon(&self) -> f32 {
+        self.epsilon
+    }
+
+    fn normalization_shape(&self, input_shape: &[usize]) -> NormalizationShape {
+        let batch_size = match input_shape.len() {
+            1 => 1,
+            _ => input_shape[0],
+        };
+        NormalizationShape {
+            batch_size: batch_size,
+            channels: input_shape.iter().fold(1, |prod, i| prod * i) / batch_size,
+            spatial_size: 1,
+            groups: 1,
+        }
+    }
+}
+
+impl<B: IBackend> ILayer<B> for LayerNorm {
+    impl_ilayer_common!();
+
+    fn auto_weight_blobs(&self) -> bool {
+        true
+    }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        self.reshape_normalization(input_data, weights_data, weights_gradient, output_data, output_gradient);
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for LayerNorm {
+    fn compute_output(&self,
+                      backend: &B,
+                      weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        self.normalization_output(weights, input_data, output_data);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for LayerNorm {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        self.normalization_input_gradient(weights_data, output_gradients, input_data, input_gradients);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for LayerNorm {
+    fn compute_parameters_gradient(&self,
+                                   backend: &B,
+                                   output_data: &[&SharedTensor<f32>],
+                                   output_gradients: &[&SharedTensor<f32>],
+                                   input_data: &[&SharedTensor<f32>],
+                                   parameters_gradients: &mut [&mut SharedTensor<f32>]) {
+        self.normalization_parameters_gradient(output_gradients, input_data, parameters_gradients);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Specifies configuration parameters for a LayerNorm Layer.
+pub struct LayerNormConfig {
+    /// The value added to the variance to avoid a division by zero.
+    ///
+    /// Default: `0.00001`
+    pub epsilon: f32,
+}
+
+impl ::std::default::Default for LayerNormConfig {
+    fn default() -> LayerNormConfig {
+        LayerNormConfig {
+            epsilon: 1e-5f32,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for LayerNormConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the LayerNormConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_epsilon(self.epsilon);
+    }
+}
+
+impl<'a> CapnpRead<'a> for LayerNormConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        LayerNormConfig {
+            epsilon: reader.get_epsilon(),
+        }
+    }
+}
+
+impl Into<LayerType> for LayerNormConfig {
+    fn into(self) -> LayerType {
+        LayerType::LayerNorm(self)
+    }
+}
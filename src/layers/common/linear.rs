@@ -18,13 +18,25 @@
 //!
 //! In the context of convolutional neural networks this layer is also
 //! called a "fully-connected layer" if it is used at the end of the network.
+//!
+//! ## Multi-threading
+//!
+//! On a Native Backend the matrix multiplications can be split into
+//! [num_threads][1] parts, which are computed in parallel by a [ThreadPool][2]
+//! that is owned by the layer.
+//! [1]: ./struct.LinearConfig.html#structfield.num_threads
+//! [2]: ../../../util/struct.ThreadPool.html
 use std::rc::Rc;
 use co::backend::IBackend;
 use co::tensor::SharedTensor;
 use coblas::transpose::Transpose;
 use coblas::plugin::*;
 use layer::*;
-use util::{ArcLock, native_scalar, LayerOps};
+use util::{ArcLock, native_scalar, LayerOps, ThreadPool};
+#[cfg(feature="native")]
+use util::native_backend;
+#[cfg(feature="native")]
+use rblas;
 use weight::FillerType;
 use leaf_capnp::linear_config as capnp_config;
 use capnp_util::*;
@@ -33,9 +45,12 @@ use capnp_util::*;
 /// Linear Layer
 pub struct Linear {
     output_size: usize,
+    transpose: bool,
 
     one: SharedTensor<f32>,
     zero: SharedTensor<f32>,
+
+    thread_pool: Option<ThreadPool>,
 }
 
 impl Linear {
@@ -46,9 +61,12 @@ impl Linear {
 
         Linear {
             output_size: config.output_size,
+            transpose: config.transpose,
 
             one: one,
             zero: zero,
+
+            thread_pool: if config.num_threads > 1 { Some(ThreadPool::new(config.num_threads)) } else { None },
         }
     }
 
@@ -57,6 +75,21 @@ impl Linear {
         input_shape.iter().skip(1).fold(1, |prod, i| prod * i)
     }
 
+    /// Compute `op(a) * op(b)` into `c`, in parallel on a Native Backend.
+    ///
+    /// Falls back to a single gemm if no thread pool is configured or the backend is not native.
+    fn gemm<B: IBackend + LayerOps<f32>>(&self, backend: &B, a: &SharedTensor<f32>, a_trans: Transpose, b: &SharedTensor<f32>, b_trans: Transpose, c: &mut SharedTensor<f32>) {
+        #[cfg(feature="native")]
+        {
+            if let Some(ref thread_pool) = self.thread_pool {
+                if IBackend::device(backend) == native_backend().device() && c.desc()[0] > 1 {
+                    return parallel_native_gemm(thread_pool, a, a_trans, b, b_trans, c);
+                }
+            }
+        }
+        backend.gemm_plain(&self.one, a_trans, a, b_trans, b, &self.zero, c).unwrap();
+    }
+
     fn calculate_output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
         let n = input_shape[0]; // batch size
         vec![n, self.output_size]
@@ -64,7 +97,7 @@ impl Linear {
 
     fn calculate_weight_shape(&self, input_shape: &[usize]) -> Vec<usize> {
         let m = Self::calculate_input_size(input_shape);
-        vec![self.output_size, m]
+        if self.transpose { vec![m, self.output_size] } else { vec![self.output_size, m] }
     }
 }
 
@@ -123,7 +156,8 @@ impl<B: IBackend + LayerOps<f32>> ComputeOutput<f32, B> for Linear {
                       weights: &[&SharedTensor<f32>],
                       input_data: &[&SharedTensor<f32>],
                       output_data: &mut [&mut SharedTensor<f32>]) {
-        backend.gemm_plain(&self.one, Transpose::NoTrans, input_data[0], Transpose::Trans, weights[0], &self.zero, output_data[0]).unwrap();
+        let weight_trans = if self.transpose { Transpose::NoTrans } else { Transpose::Trans };
+        self.gemm(backend, input_data[0], Transpose::NoTrans, weights[0], weight_trans, output_data[0]);
         let has_bias_term = false; // TODO: implement bias term
         if has_bias_term {
             let bias_multiplier = unimplemented!();
@@ -142,7 +176,8 @@ impl<B: IBackend + LayerOps<f32>> ComputeInputGradient<f32, B> for Linear {
                               input_data: &[&SharedTensor<f32>],
                               input_gradients: &mut [&mut SharedTensor<f32>]) {
         // Gradient with respect to input data
-        backend.gemm_plain(&self.one, Transpose::NoTrans, output_gradients[0], Transpose::NoTrans, weights_data[0], &self.zero, input_gradients[0]).unwrap();
+        let weight_trans = if self.transpose { Transpose::Trans } else { Transpose::NoTrans };
+        self.gemm(backend, output_gradients[0], Transpose::NoTrans, weights_data[0], weight_trans, input_gradients[0]);
     }
 }
 
@@ -154,7 +189,11 @@ impl<B: IBackend + LayerOps<f32>> ComputeParametersGradient<f32, B> for Linear {
                                    input_data: &[&SharedTensor<f32>],
                                    parameters_gradients: &mut [&mut SharedTensor<f32>]) {
         // gradient w.r.t. weights
-        backend.gemm_plain(&self.one, Transpose::Trans, output_gradients[0], Transpose::NoTrans, input_data[0], &self.zero, parameters_gradients[0]).unwrap();
+        if self.transpose {
+            self.gemm(backend, input_data[0], Transpose::Trans, output_gradients[0], Transpose::NoTrans, parameters_gradients[0]);
+        } else {
+            self.gemm(backend, output_gradients[0], Transpose::Trans, input_data[0], Transpose::NoTrans, parameters_gradients[0]);
+        }
 
         // TODO: implement gradient w.r.t bias
         // if (bias_term_ && this->param_propagate_down_[1]) {
@@ -167,13 +206,83 @@ impl<B: IBackend + LayerOps<f32>> ComputeParametersGradient<f32, B> for Linear {
     }
 }
 
+/// A row-major matrix in native memory, that either owns its values (`Vec<f32>`)
+/// or borrows them mutably (`&mut [f32]`).
+#[cfg(feature="native")]
+#[derive(Debug)]
+struct NativeMatrix<V> {
+    rows: usize,
+    cols: usize,
+    values: V,
+}
+
+#[cfg(feature="native")]
+impl NativeMatrix<Vec<f32>> {
+    /// Copy the rows `start..end` of a row-major matrix with `cols` columns.
+    fn copy_rows(values: &[f32], cols: usize, start: usize, end: usize) -> NativeMatrix<Vec<f32>> {
+        NativeMatrix { rows: end - start, cols: cols, values: values[start * cols..end * cols].to_vec() }
+    }
+
+    /// Copy the columns `start..end` of a row-major matrix with `cols` columns.
+    fn copy_cols(values: &[f32], cols: usize, start: usize, end: usize) -> NativeMatrix<Vec<f32>> {
+        let block = values.chunks(cols).flat_map(|row| row[start..end].iter().cloned()).collect::<Vec<f32>>();
+        NativeMatrix { rows: values.len() / cols, cols: end - start, values: block }
+    }
+}
+
+#[cfg(feature="native")]
+#[allow(unsafe_code)]
+impl<V: AsRef<[f32]> + AsMut<[f32]>> rblas::Matrix<f32> for NativeMatrix<V> {
+    fn rows(&self) -> i32 { self.rows as i32 }
+    fn cols(&self) -> i32 { self.cols as i32 }
+    unsafe fn as_ptr(&self) -> *const f32 { self.values.as_ref().as_ptr() }
+    unsafe fn as_mut_ptr(&mut self) -> *mut f32 { self.values.as_mut().as_mut_ptr() }
+}
+
+/// The number of rows and columns of a tensor that is viewed as a matrix of its first
+/// dimension times the remaining dimensions, like the gemm of a Native Backend.
+#[cfg(feature="native")]
+fn matrix_size(desc: &[usize]) -> (usize, usize) {
+    (desc[0], desc.iter().skip(1).fold(1, |prod, i| prod * i))
+}
+
+/// Compute `op(a) * op(b)` into `c` with the rows of `c` split across the threads of `thread_pool`.
+///
+/// Every thread copies its block of rows of `op(a)` and all of `b`, and writes its block of rows
+/// directly into the native memory of `c`. If `a` is transposed a block of rows of `op(a)` is a
+/// block of columns of `a`.
+#[cfg(feature="native")]
+fn parallel_native_gemm(thread_pool: &ThreadPool, a: &SharedTensor<f32>, a_trans: Transpose, b: &SharedTensor<f32>, b_trans: Transpose, c: &mut SharedTensor<f32>) {
+    let native = native_backend();
+    let (_, a_cols) = matrix_size(a.desc());
+    let (b_rows, b_cols) = matrix_size(b.desc());
+    let (rows, cols) = matrix_size(c.desc());
+    let a_values = a.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+    let b_values = b.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+    let c_values = c.get_mut(native.device()).unwrap().as_mut_native().unwrap().as_mut_slice::<f32>();
+
+    let (a_blas_trans, b_blas_trans) = (a_trans.to_rblas(), b_trans.to_rblas());
+    let rows_per_thread = (rows + thread_pool.num_threads() - 1) / thread_pool.num_threads();
+    let mut jobs: Vec<Box<FnOnce() + Send>> = Vec::with_capacity(thread_pool.num_threads());
+    for (block, c_block_values) in c_values.chunks_mut(rows_per_thread * cols).enumerate() {
+        let start = block * rows_per_thread;
+        let end = start + c_block_values.len() / cols;
+        jobs.push(Box::new(move || {
+            let a_block = match a_trans {
+                Transpose::NoTrans => NativeMatrix::copy_rows(a_values, a_cols, start, end),
+                _ => NativeMatrix::copy_cols(a_values, a_cols, start, end),
+            };
+            let b_matrix = NativeMatrix::copy_rows(b_values, b_cols, 0, b_rows);
+            let mut c_block = NativeMatrix { rows: end - start, cols: cols, values: c_block_values };
+            rblas::Gemm::gemm(&1f32, a_blas_trans, &a_block, b_blas_trans, &b_matrix, &0f32, &mut c_block);
+        }));
+    }
+    thread_pool.execute(jobs);
+}
+
 impl ::std::default::Default for Linear {
     fn default() -> Linear {
-        let config = LinearConfig {
-            output_size: 10,
-        };
-
-        Self::from_config(&config)
+        Self::from_config(&LinearConfig::new(10))
     }
 }
 
@@ -184,6 +293,47 @@ impl ::std::default::Default for Linear {
 pub struct LinearConfig {
     /// The number of output values
     pub output_size: usize,
+    /// Store the weight transposed, with the shape `[input_size, output_size]`.
+    ///
+    /// This allows a decoder to share the weight of its encoder, see
+    /// [models::autoencoder][1].
+    /// [1]: ../../../models/fn.autoencoder.html
+    ///
+    /// Default: `false`
+    pub transpose: bool,
+    /// The number of threads that compute the matrix multiplications on a Native Backend.
+    ///
+    /// With more than one thread the layer starts its own [ThreadPool][2], which splits
+    /// every matrix multiplication along the rows of its result.
+    /// [2]: ../../../util/struct.ThreadPool.html
+    ///
+    /// Default: `1`
+    pub num_threads: usize,
+}
+
+impl LinearConfig {
+    /// Create a LinearConfig that describes a Linear layer with `output_size` outputs.
+    pub fn new(output_size: usize) -> LinearConfig {
+        LinearConfig {
+            output_size: output_size,
+            transpose: false,
+            num_threads: 1,
+        }
+    }
+
+    /// Set if the weight is stored transposed, see [transpose][1].
+    /// [1]: #structfield.transpose
+    pub fn transpose(mut self, transpose: bool) -> LinearConfig {
+        self.transpose = transpose;
+        self
+    }
+
+    /// Set the number of threads, see [num_threads][1].
+    /// [1]: #structfield.num_threads
+    pub fn num_threads(mut self, num_threads: usize) -> LinearConfig {
+        self.num_threads = num_threads;
+        self
+    }
 }
 
 impl<'a> CapnpWrite<'a> for LinearConfig {
@@ -192,6 +342,8 @@ impl<'a> CapnpWrite<'a> for LinearConfig {
     /// Write the LinearConfig into a capnp message.
     fn write_capnp(&self, builder: &mut Self::Builder) {
         builder.borrow().set_output_size(self.output_size as u64);
+        builder.borrow().set_transpose(self.transpose);
+        builder.borrow().set_num_threads(self.num_threads as u64);
     }
 }
 
@@ -200,9 +352,13 @@ impl<'a> CapnpRead<'a> for LinearConfig {
 
     fn read_capnp(reader: Self::Reader) -> Self {
         let output_size = reader.get_output_size() as usize;
+        let transpose = reader.get_transpose();
+        let num_threads = reader.get_num_threads() as usize;
 
         LinearConfig {
-            output_size: output_size
+            output_size: output_size,
+            transpose: transpose,
+            num_threads: num_threads,
         }
     }
 }
@@ -1,16 +1,32 @@
 //! Computes the logarithmic softmax of its input.
 //!
+//! If the input has more than one dimension **the first dimension is treated as batch size**
+//! and the logarithmic softmax is computed for every sample on its own.
 use co::{IBackend, SharedTensor};
+use coblas::plugin::{Copy, Scal};
 use conn;
 use layer::*;
-use util::ArcLock;
+use util::{ArcLock, native_backend, native_scalar};
+use leaf_capnp::softmax_config as capnp_config;
+use capnp_util::*;
 
 #[derive(Debug, Clone)]
 #[allow(missing_copy_implementations)]
 /// LogSoftmax Layer
-pub struct LogSoftmax;
+pub struct LogSoftmax {
+    temperature: f32,
+}
+
+impl LogSoftmax {
+    /// Create a LogSoftmax layer from a LogSoftmaxConfig.
+    pub fn from_config(config: &LogSoftmaxConfig) -> LogSoftmax {
+        LogSoftmax {
+            temperature: config.temperature,
+        }
+    }
+}
 
-impl<B: IBackend + conn::LogSoftmax<f32>> ILayer<B> for LogSoftmax {
+impl<B: IBackend + conn::LogSoftmax<f32> + Copy<f32> + Scal<f32>> ILayer<B> for LogSoftmax {
     fn reshape(&mut self,
                backend: ::std::rc::Rc<B>,
                input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
@@ -26,17 +42,26 @@ impl<B: IBackend + conn::LogSoftmax<f32>> ILayer<B> for LogSoftmax {
     }
 }
 
-impl<B: IBackend + conn::LogSoftmax<f32>> ComputeOutput<f32, B> for LogSoftmax {
+impl<B: IBackend + conn::LogSoftmax<f32> + Copy<f32> + Scal<f32>> ComputeOutput<f32, B> for LogSoftmax {
     fn compute_output(&self,
                       backend: &B,
                       _weights: &[&SharedTensor<f32>],
                       input_data: &[&SharedTensor<f32>],
                       output_data: &mut [&mut SharedTensor<f32>]) {
-        backend.log_softmax_plain(input_data[0], output_data[0]).unwrap();
+        if IBackend::device(backend) == native_backend().device() {
+            native_log_softmax(self.temperature, input_data[0], output_data[0]);
+        } else if self.temperature == 1f32 {
+            backend.log_softmax_plain(input_data[0], output_data[0]).unwrap();
+        } else {
+            let mut scaled_input = SharedTensor::<f32>::new(IBackend::device(backend), input_data[0].desc()).unwrap();
+            backend.copy_plain(input_data[0], &mut scaled_input).unwrap();
+            backend.scal(&mut native_scalar(1f32 / self.temperature), &mut scaled_input).unwrap();
+            backend.log_softmax_plain(&scaled_input, output_data[0]).unwrap();
+        }
     }
 }
 
-impl<B: IBackend + conn::LogSoftmax<f32>> ComputeInputGradient<f32, B> for LogSoftmax {
+impl<B: IBackend + conn::LogSoftmax<f32> + Copy<f32> + Scal<f32>> ComputeInputGradient<f32, B> for LogSoftmax {
     fn compute_input_gradient(&self,
                               backend: &B,
                               weights_data: &[&SharedTensor<f32>],
@@ -44,14 +69,105 @@ impl<B: IBackend + conn::LogSoftmax<f32>> ComputeInputGradient<f32, B> for LogSo
                               output_gradients: &[&SharedTensor<f32>],
                               input_data: &[&SharedTensor<f32>],
                               input_gradients: &mut [&mut SharedTensor<f32>]) {
+        if IBackend::device(backend) == native_backend().device() {
+            return native_log_softmax_grad(self.temperature, output_data[0], output_gradients[0], input_gradients[0]);
+        }
         backend.log_softmax_grad_plain(output_data[0], output_gradients[0], input_gradients[0]).unwrap();
+        if self.temperature != 1f32 {
+            backend.scal(&mut native_scalar(1f32 / self.temperature), input_gradients[0]).unwrap();
+        }
+    }
+}
+
+impl<B: IBackend + conn::LogSoftmax<f32> + Copy<f32> + Scal<f32>> ComputeParametersGradient<f32, B> for LogSoftmax { }
+
+// The number of values of every sample of a tensor with the shape `desc`.
+fn sample_size(desc: &[usize]) -> usize {
+    desc.iter().skip(if desc.len() > 1 { 1 } else { 0 }).fold(1, |prod, i| prod * i)
+}
+
+/// Compute the logarithmic softmax of every sample of `input` divided by `temperature` in native memory.
+///
+/// The Native Backend of collenchyma-nn normalizes over all values of a tensor, which would mix
+/// the samples of a batch.
+fn native_log_softmax(temperature: f32, input: &SharedTensor<f32>, output: &mut SharedTensor<f32>) {
+    let native = native_backend();
+    let sample_size = sample_size(input.desc());
+    let input_values = input.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+    let output_values = output.get_mut(native.device()).unwrap().as_mut_native().unwrap().as_mut_slice::<f32>();
+    for (input_sample, output_sample) in input_values.chunks(sample_size).zip(output_values.chunks_mut(sample_size)) {
+        let max_input = input_sample.iter().fold(::std::f32::NEG_INFINITY, |max, &x| max.max(x / temperature));
+        let logsum = max_input + input_sample.iter().fold(0f32, |sum, &x| sum + (x / temperature - max_input).exp()).ln();
+        for (y, &x) in output_sample.iter_mut().zip(input_sample) {
+            *y = x / temperature - logsum;
+        }
     }
 }
 
-impl<B: IBackend + conn::LogSoftmax<f32>> ComputeParametersGradient<f32, B> for LogSoftmax { }
+/// Compute the gradient of `native_log_softmax` for every sample in native memory.
+fn native_log_softmax_grad(temperature: f32, output: &SharedTensor<f32>, output_gradient: &SharedTensor<f32>, input_gradient: &mut SharedTensor<f32>) {
+    let native = native_backend();
+    let sample_size = sample_size(output.desc());
+    let output_values = output.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+    let output_gradient_values = output_gradient.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+    let input_gradient_values = input_gradient.get_mut(native.device()).unwrap().as_mut_native().unwrap().as_mut_slice::<f32>();
+    let samples = output_values.chunks(sample_size).zip(output_gradient_values.chunks(sample_size)).zip(input_gradient_values.chunks_mut(sample_size));
+    for ((output_sample, output_gradient_sample), input_gradient_sample) in samples {
+        let sum = output_gradient_sample.iter().fold(0f32, |sum, dy| sum + dy);
+        for ((dx, &y), &dy) in input_gradient_sample.iter_mut().zip(output_sample).zip(output_gradient_sample) {
+            *dx = (dy - y.exp() * sum) / temperature;
+        }
+    }
+}
 
 impl ::std::default::Default for LogSoftmax {
     fn default() -> LogSoftmax {
-        LogSoftmax
+        LogSoftmax::from_config(&LogSoftmaxConfig::default())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+/// Specifies configuration parameters for a LogSoftmax Layer.
+pub struct LogSoftmaxConfig {
+    /// The temperature the input is divided by before the logarithmic softmax is computed.
+    ///
+    /// Temperatures above `1` produce a softer probability distribution over the classes,
+    /// as used e.g. for [knowledge distillation][1].
+    /// [1]: https://arxiv.org/abs/1503.02531
+    ///
+    /// Default: `1.0`
+    pub temperature: f32,
+}
+
+impl ::std::default::Default for LogSoftmaxConfig {
+    fn default() -> LogSoftmaxConfig {
+        LogSoftmaxConfig {
+            temperature: 1f32,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for LogSoftmaxConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the LogSoftmaxConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_temperature(self.temperature);
+    }
+}
+
+impl<'a> CapnpRead<'a> for LogSoftmaxConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        LogSoftmaxConfig {
+            temperature: reader.get_temperature(),
+        }
+    }
+}
+
+impl Into<LayerType> for LogSoftmaxConfig {
+    fn into(self) -> LayerType {
+        LayerType::LogSoftmax(self)
     }
 }
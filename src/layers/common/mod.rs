@@ -10,20 +10,36 @@ macro_rules! impl_ilayer_common {
     )
 }
 
+pub use self::constant::{Constant, ConstantConfig};
 #[cfg(all(feature="cuda", not(feature="native")))]
 pub use self::convolution::{Convolution, ConvolutionConfig};
+pub use self::fused_linear::{FusedActivation, FusedLinear, FusedLinearConfig};
+pub use self::group_norm::{GroupNorm, GroupNormConfig};
+pub use self::instance_norm::{InstanceNorm, InstanceNormConfig};
+pub use self::layer_norm::{LayerNorm, LayerNormConfig};
 pub use self::linear::{Linear, LinearConfig};
-pub use self::log_softmax::LogSoftmax;
+pub use self::log_softmax::{LogSoftmax, LogSoftmaxConfig};
+pub use self::noise::{Noise, NoiseConfig, NoiseMode};
 #[cfg(all(feature="cuda", not(feature="native")))]
 pub use self::pooling::{Pooling, PoolingConfig, PoolingMode};
-pub use self::softmax::Softmax;
+pub use self::normalization::{NormalizationLayer, NormalizationShape};
+pub use self::scale::{Scale, ScaleConfig};
+pub use self::softmax::{Softmax, SoftmaxConfig};
 
+pub mod constant;
 #[cfg(all(feature="cuda", not(feature="native")))]
 pub mod convolution;
+pub mod fused_linear;
+pub mod group_norm;
+pub mod instance_norm;
+pub mod layer_norm;
 pub mod linear;
 pub mod log_softmax;
+pub mod noise;
+pub mod normalization;
 #[cfg(all(feature="cuda", not(feature="native")))]
 pub mod pooling;
+pub mod scale;
 pub mod softmax;
 
 /// Provides common utilities for Layers that utilize a filter with stride and padding.
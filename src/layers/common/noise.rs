@@ -0,0 +1,229 @@
+//! Adds random noise to its input during training.
+//!
+//! The noise is only applied in the training phase, see [Layer::set_training][5].
+//! Otherwise the layer passes its input through.
+//!
+//! Depending on its [NoiseMode][1] the layer either
+//!
+//! - adds gaussian noise with a standard deviation of `level` to every value, or
+//! - sets every value to zero with a probability of `level` and scales the remaining
+//!   values by `1 / (1 - level)`, like dropout.
+//!
+//! In both cases the expected value of the output is the input, so the layer is the identity
+//! at test time. It is only needed for training (see [LayerType::is_training_only][2]) and
+//! removed e.g. by [Layer::save_for_inference][3]. This is useful for denoising autoencoders
+//! and as regularization.
+//!
+//! The random numbers are drawn from the [random number stream][4] of the layer,
+//! so the noise is reproducible for a given seed.
+//!
+//! [1]: ./enum.NoiseMode.html
+//! [2]: ../../../layer/enum.LayerType.html#method.is_training_only
+//! [3]: ../../../layer/struct.Layer.html#method.save_for_inference
+//! [4]: ../../../rng/struct.RngStream.html
+//! [5]: ../../../layer/struct.Layer.html#method.set_training
+use std::cell::RefCell;
+use co::{IBackend, SharedTensor};
+use rand::Rng;
+use rand::distributions::{IndependentSample, Normal};
+use layer::*;
+use rng::RngStream;
+use util::{ArcLock, native_backend, write_to_memory};
+use leaf_capnp::noise_config as capnp_config;
+use leaf_capnp::NoiseMode as CapnpNoiseMode;
+use capnp_util::*;
+
+#[derive(Debug, Clone)]
+/// Noise Layer
+pub struct Noise {
+    mode: NoiseMode,
+    level: f32,
+    training: bool,
+
+    rng: RefCell<RngStream>,
+    /// The factors of the last Bernoulli noise, which scale the gradient in the backward pass.
+    mask: RefCell<Vec<f32>>,
+}
+
+impl Noise {
+    /// Create a Noise layer with the name `name` from a NoiseConfig.
+    ///
+    /// The name of the layer determines its random number stream.
+    pub fn from_config(name: &str, config: &NoiseConfig) -> Noise {
+        match config.mode {
+            NoiseMode::Gaussian if config.level < 0f32 => {
+                panic!("The standard deviation of the gaussian noise of layer {} can't be negative, but is {}", name, config.level);
+            },
+            NoiseMode::Bernoulli if config.level < 0f32 || config.level >= 1f32 => {
+                panic!("The probability of the bernoulli noise of layer {} has to be in [0, 1), but is {}", name, config.level);
+            },
+            _ => {}
+        }
+        Noise {
+            mode: config.mode,
+            level: config.level,
+            training: true,
+
+            rng: RefCell::new(RngStream::for_layer(name)),
+            mask: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl<B: IBackend> ILayer<B> for Noise {
+    impl_ilayer_common!();
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn set_training(&mut self, training: bool) {
+        self.training = training;
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        let input_shape = input_data[0].read().unwrap().desc().clone();
+        input_gradient[0].write().unwrap().resize(&input_shape).unwrap();
+        output_data[0].write().unwrap().resize(&input_shape).unwrap();
+        output_gradient[0].write().unwrap().resize(&input_shape).unwrap();
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for Noise {
+    fn compute_output(&self,
+                      backend: &B,
+                      _weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let mut rng = self.rng.borrow_mut();
+        let output = match self.mode {
+            _ if !self.training => {
+                *self.mask.borrow_mut() = vec![1f32; input.len()];
+                input.to_owned()
+            },
+            NoiseMode::Gaussian => {
+                let normal = Normal::new(0f64, self.level as f64);
+                input.iter().map(|value| value + normal.ind_sample(&mut *rng) as f32).collect::<Vec<_>>()
+            },
+            NoiseMode::Bernoulli => {
+                let scale = 1f32 / (1f32 - self.level);
+                let mut mask = self.mask.borrow_mut();
+                *mask = input.iter().map(|_| if rng.gen::<f32>() < self.level { 0f32 } else { scale }).collect();
+                input.iter().zip(mask.iter()).map(|(value, factor)| value * factor).collect::<Vec<_>>()
+            },
+        };
+        write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &output);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for Noise {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let input_gradient = match self.mode {
+            // additive noise doesn't change the gradient
+            NoiseMode::Gaussian => output_gradient.to_owned(),
+            NoiseMode::Bernoulli => {
+                let mask = self.mask.borrow();
+                output_gradient.iter().zip(mask.iter()).map(|(gradient, factor)| gradient * factor).collect::<Vec<_>>()
+            },
+        };
+        write_to_memory(input_gradients[0].get_mut(native.device()).unwrap(), &input_gradient);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for Noise { }
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// The kinds of noise a [Noise][1] layer applies.
+/// [1]: ./struct.Noise.html
+pub enum NoiseMode {
+    /// Add gaussian noise with a mean of zero.
+    Gaussian,
+    /// Multiply with Bernoulli noise, i.e. set values to zero at random.
+    Bernoulli,
+}
+
+impl NoiseMode {
+    /// Return the corresponding Cap'n Proto value.
+    fn to_capnp(&self) -> CapnpNoiseMode {
+        match *self {
+            NoiseMode::Gaussian => CapnpNoiseMode::Gaussian,
+            NoiseMode::Bernoulli => CapnpNoiseMode::Bernoulli,
+        }
+    }
+
+    /// Return the enum value for a Cap'n Proto value.
+    fn from_capnp(value: CapnpNoiseMode) -> Self {
+        match value {
+            CapnpNoiseMode::Gaussian => NoiseMode::Gaussian,
+            CapnpNoiseMode::Bernoulli => NoiseMode::Bernoulli,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+/// Specifies configuration parameters for a Noise Layer.
+pub struct NoiseConfig {
+    /// The kind of noise.
+    ///
+    /// Default: Gaussian
+    pub mode: NoiseMode,
+    /// The standard deviation of gaussian noise, or the probability that
+    /// bernoulli noise sets a value to zero.
+    ///
+    /// Default: `0.1`
+    pub level: f32,
+}
+
+impl ::std::default::Default for NoiseConfig {
+    fn default() -> NoiseConfig {
+        NoiseConfig {
+            mode: NoiseMode::Gaussian,
+            level: 0.1f32,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for NoiseConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the NoiseConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.borrow().set_mode(self.mode.to_capnp());
+        builder.borrow().set_level(self.level);
+    }
+}
+
+impl<'a> CapnpRead<'a> for NoiseConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        NoiseConfig {
+            mode: NoiseMode::from_capnp(reader.get_mode().unwrap()),
+            level: reader.get_level(),
+        }
+    }
+}
+
+impl Into<LayerType> for NoiseConfig {
+    fn into(self) -> LayerType {
+        LayerType::Noise(self)
+    }
+}
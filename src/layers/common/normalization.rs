@@ -0,0 +1,177 @@
+//! Provides the computation shared by the normalization layers.
+//!
+//! All normalization layers split each sample into groups, normalize the values of every
+//! group to zero mean and unit variance and then apply a learnable gain and bias per channel:
+//!
+//! - [LayerNorm][1] uses a single group per sample and one channel per feature.
+//! - [GroupNorm][2] splits the channels of a sample into a configured number of groups.
+//! - [InstanceNorm][3] uses one group per channel.
+//!
+//! Gain and bias are stored in one weight of shape `[2, channels]`; the first row is the gain,
+//! initialized to `1`, and the second row is the bias, initialized to `0`.
+//!
+//! [1]: ../layer_norm/index.html
+//! [2]: ../group_norm/index.html
+//! [3]: ../instance_norm/index.html
+use co::SharedTensor;
+use util::{ArcLock, native_backend, write_to_memory};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Describes how the input of a normalization layer is split into groups.
+pub struct NormalizationShape {
+    /// The number of samples.
+    pub batch_size: usize,
+    /// The number of channels of a sample, each with its own gain and bias.
+    pub channels: usize,
+    /// The number of values of each channel.
+    pub spatial_size: usize,
+    /// The number of groups the channels of a sample are split into.
+    pub groups: usize,
+}
+
+impl NormalizationShape {
+    /// The number of values that are normalized together.
+    pub fn group_size(&self) -> usize {
+        self.channels / self.groups * self.spatial_size
+    }
+}
+
+/// Provides the forward and backward computation for Layers that normalize groups of their input.
+///
+/// This is used by the LayerNorm, GroupNorm and InstanceNorm layers.
+pub trait NormalizationLayer {
+    /// The value added to the variance to avoid a division by zero.
+    fn epsilon(&self) -> f32;
+
+    /// Describes how an input of shape `input_shape` is split into groups.
+    fn normalization_shape(&self, input_shape: &[usize]) -> NormalizationShape;
+
+    /// Resizes the output to the input shape and initializes the gain and bias.
+    fn reshape_normalization(&self,
+                             input_data: &[ArcLock<SharedTensor<f32>>],
+                             weights_data: &[ArcLock<SharedTensor<f32>>],
+                             weights_gradient: &[ArcLock<SharedTensor<f32>>],
+                             output_data: &[ArcLock<SharedTensor<f32>>],
+                             output_gradient: &[ArcLock<SharedTensor<f32>>]) {
+        let input_shape = input_data[0].read().unwrap().desc().clone();
+        output_data[0].write().unwrap().resize(&input_shape).unwrap();
+        output_gradient[0].write().unwrap().resize(&input_shape).unwrap();
+
+        let shape = self.normalization_shape(&input_shape);
+        if shape.groups == 0 || shape.channels % shape.groups != 0 {
+            panic!("The {} channels of the input {:?} can not be split into {} groups.", shape.channels, input_shape, shape.groups);
+        }
+        let weight_shape = vec![2, shape.channels];
+        if let Some(weight) = weights_data.get(0) {
+            let mut weight = weight.write().unwrap();
+            weight.resize(&weight_shape).unwrap();
+            let mut initial = vec![1f32; shape.channels];
+            initial.extend(vec![0f32; shape.channels]);
+            let native = native_backend();
+            match weight.add_device(native.device()) { _ => weight.sync(native.device()).unwrap() }
+            write_to_memory(weight.get_mut(native.device()).unwrap(), &initial);
+        }
+        if let Some(weight) = weights_gradient.get(0) {
+            weight.write().unwrap().resize(&weight_shape).unwrap();
+        }
+    }
+
+    /// Normalizes the input and applies gain and bias.
+    fn normalization_output(&self,
+                            weights: &[&SharedTensor<f32>],
+                            input_data: &[&SharedTensor<f32>],
+                            output_data: &mut [&mut SharedTensor<f32>]) {
+        let shape = self.normalization_shape(input_data[0].desc());
+
+        let native = native_backend();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let weight = weights[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let (gain, bias) = weight.split_at(shape.channels);
+
+        let mut output = Vec::with_capacity(input.len());
+        for (i, group) in input.chunks(shape.group_size()).enumerate() {
+            let (normalized, _) = normalize(group, self.epsilon());
+            output.extend(normalized.iter().enumerate().map(|(j, &x)| {
+                let channel = channel_of(&shape, i, j);
+                gain[channel] * x + bias[channel]
+            }));
+        }
+
+        write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &output);
+    }
+
+    /// Computes the gradient w.r.t. the input.
+    fn normalization_input_gradient(&self,
+                                    weights_data: &[&SharedTensor<f32>],
+                                    output_gradients: &[&SharedTensor<f32>],
+                                    input_data: &[&SharedTensor<f32>],
+                                    input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let shape = self.normalization_shape(input_data[0].desc());
+        let group_size = shape.group_size();
+        let n = group_size as f32;
+
+        let native = native_backend();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let gain = &weights_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>()[..shape.channels];
+
+        let mut input_gradient = Vec::with_capacity(input.len());
+        for (i, (group, group_gradient)) in input.chunks(group_size).zip(output_gradient.chunks(group_size)).enumerate() {
+            let (normalized, inv_std) = normalize(group, self.epsilon());
+            // gradient w.r.t. the normalized values
+            let gradient = group_gradient.iter().enumerate().map(|(j, &dy)| dy * gain[channel_of(&shape, i, j)]).collect::<Vec<f32>>();
+            let mean_gradient = gradient.iter().fold(0f32, |sum, &d| sum + d) / n;
+            let mean_projection = gradient.iter().zip(&normalized).fold(0f32, |sum, (&d, &x)| sum + d * x) / n;
+            input_gradient.extend(gradient.iter().zip(&normalized).map(|(&d, &x)| inv_std * (d - mean_gradient - x * mean_projection)));
+        }
+
+        match input_gradients[0].add_device(native.device()) { _ => input_gradients[0].sync(native.device()).unwrap() }
+        write_to_memory(input_gradients[0].get_mut(native.device()).unwrap(), &input_gradient);
+    }
+
+    /// Computes the gradient w.r.t. gain and bias.
+    fn normalization_parameters_gradient(&self,
+                                         output_gradients: &[&SharedTensor<f32>],
+                                         input_data: &[&SharedTensor<f32>],
+                                         parameters_gradients: &mut [&mut SharedTensor<f32>]) {
+        let shape = self.normalization_shape(input_data[0].desc());
+        let group_size = shape.group_size();
+
+        let native = native_backend();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let mut gain_gradient = vec![0f32; shape.channels];
+        let mut bias_gradient = vec![0f32; shape.channels];
+        for (i, (group, group_gradient)) in input.chunks(group_size).zip(output_gradient.chunks(group_size)).enumerate() {
+            let (normalized, _) = normalize(group, self.epsilon());
+            for (j, (&x, &dy)) in normalized.iter().zip(group_gradient).enumerate() {
+                let channel = channel_of(&shape, i, j);
+                gain_gradient[channel] += dy * x;
+                bias_gradient[channel] += dy;
+            }
+        }
+        gain_gradient.extend(bias_gradient);
+
+        match parameters_gradients[0].add_device(native.device()) { _ => parameters_gradients[0].sync(native.device()).unwrap() }
+        write_to_memory(parameters_gradients[0].get_mut(native.device()).unwrap(), &gain_gradient);
+    }
+}
+
+/// Normalize `values` to zero mean and unit variance.
+///
+/// Returns the normalized values and the reciprocal of the standard deviation.
+fn normalize(values: &[f32], epsilon: f32) -> (Vec<f32>, f32) {
+    let n = values.len() as f32;
+    let mean = values.iter().fold(0f32, |sum, &x| sum + x) / n;
+    let variance = values.iter().fold(0f32, |sum, &x| sum + (x - mean) * (x - mean)) / n;
+    let inv_std = 1f32 / (variance + epsilon).sqrt();
+
+    (values.iter().map(|&x| (x - mean) * inv_std).collect(), inv_std)
+}
+
+/// The channel of the value at `index` inside the `group_index`th group of the input.
+fn channel_of(shape: &NormalizationShape, group_index: usize, index: usize) -> usize {
+    let channels_per_group = shape.channels / shape.groups;
+    (group_index % shape.groups) * channels_per_group + index / shape.spatial_size
+}
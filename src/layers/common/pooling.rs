@@ -155,11 +155,44 @@ pub struct PoolingConfig {
     /// The shape of the filter
     pub filter_shape: Vec<usize>,
     /// The stride size
+    ///
+    /// Default: the filter shape, so the pooling windows don't overlap
     pub stride: Vec<usize>,
     /// The padding size
+    ///
+    /// Default: `[0]`
     pub padding: Vec<usize>,
 }
 
+impl PoolingConfig {
+    /// Create a PoolingConfig that describes a Pooling layer with the mode `mode`
+    /// and non-overlapping windows of the shape `filter_shape`.
+    ///
+    /// A single value in `filter_shape`, `stride` or `padding` is used for all spatial dimensions.
+    pub fn new(mode: PoolingMode, filter_shape: &[usize]) -> PoolingConfig {
+        PoolingConfig {
+            mode: mode,
+            filter_shape: filter_shape.to_owned(),
+            stride: filter_shape.to_owned(),
+            padding: vec![0],
+        }
+    }
+
+    /// Set the [stride][1].
+    /// [1]: #structfield.stride
+    pub fn stride(mut self, stride: &[usize]) -> PoolingConfig {
+        self.stride = stride.to_owned();
+        self
+    }
+
+    /// Set the [padding][1].
+    /// [1]: #structfield.padding
+    pub fn padding(mut self, padding: &[usize]) -> PoolingConfig {
+        self.padding = padding.to_owned();
+        self
+    }
+}
+
 impl Into<LayerType> for PoolingConfig {
     fn into(self) -> LayerType {
         LayerType::Pooling(self)
@@ -0,0 +1,198 @@
+//! Multiplies every channel of the input by a learnable factor and optionally adds a learnable bias.
+//!
+//! y_c = s_c * x_c + b_c
+//!
+//! The factors are initialized to `1` and the biases to `0`, so the layer starts as the identity.
+//! Together with a normalization of the input it forms the affine part of a batch normalization
+//! in two stages, as Caffe does.
+//!
+//! The factors are stored in the first weight and the biases in the second weight, which only
+//! exists with a bias; both have the shape `[channels]`. They are [broadcast][1] to the input.
+//! [1]: ../../index.html#broadcasting
+//!
+//! ## Input Data
+//!
+//! If the input has more than one dimension, the first dimension is the batch size and
+//! the second one holds the channels, e.g. `[batch, channels, height, width]`.
+//! A one dimensional input is a single sample of channels.
+use co::{IBackend, SharedTensor};
+use layer::*;
+use layers::channel_dims;
+use util::{ArcLock, broadcast_to, native_backend, sum_to_shape, write_to_memory};
+use leaf_capnp::scale_config as capnp_config;
+use capnp_util::*;
+
+#[derive(Debug, Clone, Copy)]
+/// Scale Layer
+pub struct Scale {
+    bias: bool,
+}
+
+impl Scale {
+    /// Create a Scale layer from a ScaleConfig.
+    pub fn from_config(config: &ScaleConfig) -> Scale {
+        Scale {
+            bias: config.bias,
+        }
+    }
+
+    /// Returns the shape of the input as `[batch, channels, channel size]`
+    /// and the shape the weights are broadcast from.
+    fn broadcast_shapes(input_shape: &[usize]) -> (Vec<usize>, Vec<usize>) {
+        let (batch_size, channels, channel_size) = channel_dims(input_shape);
+        (vec![batch_size, channels, channel_size], vec![1, channels, 1])
+    }
+}
+
+impl<B: IBackend> ILayer<B> for Scale {
+    impl_ilayer_common!();
+
+    fn auto_weight_blobs(&self) -> bool {
+        true
+    }
+
+    fn exact_num_weight_blobs(&self) -> Option<usize> {
+        Some(if self.bias { 2 } else { 1 })
+    }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        let input_shape = input_data[0].read().unwrap().desc().clone();
+        input_gradient[0].write().unwrap().resize(&input_shape).unwrap();
+        output_data[0].write().unwrap().resize(&input_shape).unwrap();
+        output_gradient[0].write().unwrap().resize(&input_shape).unwrap();
+
+        let (_, channels, _) = channel_dims(&input_shape);
+        let native = native_backend();
+        // the factors start at 1, the biases at 0
+        for (weight, &initial) in weights_data.iter().zip(&[1f32, 0f32]) {
+            let mut weight = weight.write().unwrap();
+            weight.resize(&vec![channels]).unwrap();
+            match weight.add_device(native.device()) { _ => weight.sync(native.device()).unwrap() }
+            write_to_memory(weight.get_mut(native.device()).unwrap(), &vec![initial; channels]);
+        }
+        for weight in weights_gradient.iter() {
+            weight.write().unwrap().resize(&vec![channels]).unwrap();
+        }
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for Scale {
+    fn compute_output(&self,
+                      backend: &B,
+                      weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        let (input_shape, weight_shape) = Self::broadcast_shapes(input_data[0].desc());
+        let native = native_backend();
+        let broadcast_weight = |weight: &SharedTensor<f32>| {
+            let values = weight.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            broadcast_to(values, &weight_shape, &input_shape).unwrap()
+        };
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let mut output = input.iter().zip(broadcast_weight(weights[0])).map(|(x, scale)| scale * x).collect::<Vec<_>>();
+        if self.bias {
+            for (y, bias) in output.iter_mut().zip(broadcast_weight(weights[1])) {
+                *y += bias;
+            }
+        }
+        write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &output);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for Scale {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let (input_shape, weight_shape) = Self::broadcast_shapes(input_data[0].desc());
+        let native = native_backend();
+        let scale = weights_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let input_gradient = output_gradient.iter().zip(broadcast_to(scale, &weight_shape, &input_shape).unwrap())
+            .map(|(dy, scale)| scale * dy)
+            .collect::<Vec<_>>();
+        write_to_memory(input_gradients[0].get_mut(native.device()).unwrap(), &input_gradient);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for Scale {
+    fn compute_parameters_gradient(&self,
+                                   backend: &B,
+                                   output_data: &[&SharedTensor<f32>],
+                                   output_gradients: &[&SharedTensor<f32>],
+                                   input_data: &[&SharedTensor<f32>],
+                                   parameters_gradients: &mut [&mut SharedTensor<f32>]) {
+        let (input_shape, weight_shape) = Self::broadcast_shapes(input_data[0].desc());
+        let native = native_backend();
+        let input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let scaled_gradient = input.iter().zip(output_gradient).map(|(x, dy)| dy * x).collect::<Vec<_>>();
+        let mut gradients = vec![sum_to_shape(&scaled_gradient, &input_shape, &weight_shape).unwrap()];
+        if self.bias {
+            gradients.push(sum_to_shape(output_gradient, &input_shape, &weight_shape).unwrap());
+        }
+        for (parameters_gradient, gradient) in parameters_gradients.iter_mut().zip(gradients) {
+            match parameters_gradient.add_device(native.device()) { _ => parameters_gradient.sync(native.device()).unwrap() }
+            write_to_memory(parameters_gradient.get_mut(native.device()).unwrap(), &gradient);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Specifies configuration parameters for a Scale Layer.
+pub struct ScaleConfig {
+    /// Whether a learnable bias is added after scaling.
+    ///
+    /// Default: `false`
+    pub bias: bool,
+}
+
+impl ::std::default::Default for ScaleConfig {
+    fn default() -> ScaleConfig {
+        ScaleConfig {
+            bias: false,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for ScaleConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the ScaleConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_bias(self.bias);
+    }
+}
+
+impl<'a> CapnpRead<'a> for ScaleConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        ScaleConfig {
+            bias: reader.get_bias(),
+        }
+    }
+}
+
+impl Into<LayerType> for ScaleConfig {
+    fn into(self) -> LayerType {
+        LayerType::Scale(self)
+    }
+}
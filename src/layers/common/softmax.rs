@@ -2,16 +2,30 @@
 //!
 //! For the logarithmic softmax see the `LogSoftmax` layer.
 use co::{IBackend, SharedTensor};
+use coblas::plugin::{Copy, Scal};
 use conn;
 use layer::*;
-use util::ArcLock;
+use util::{ArcLock, native_scalar};
+use leaf_capnp::softmax_config as capnp_config;
+use capnp_util::*;
 
 #[derive(Debug, Clone)]
 #[allow(missing_copy_implementations)]
 /// Softmax Layer
-pub struct Softmax;
+pub struct Softmax {
+    temperature: f32,
+}
 
-impl<B: IBackend + conn::Softmax<f32>> ILayer<B> for Softmax {
+impl Softmax {
+    /// Create a Softmax layer from a SoftmaxConfig.
+    pub fn from_config(config: &SoftmaxConfig) -> Softmax {
+        Softmax {
+            temperature: config.temperature,
+        }
+    }
+}
+
+impl<B: IBackend + conn::Softmax<f32> + Copy<f32> + Scal<f32>> ILayer<B> for Softmax {
     fn reshape(&mut self,
                backend: ::std::rc::Rc<B>,
                input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
@@ -27,17 +41,24 @@ impl<B: IBackend + conn::Softmax<f32>> ILayer<B> for Softmax {
     }
 }
 
-impl<B: IBackend + conn::Softmax<f32>> ComputeOutput<f32, B> for Softmax {
+impl<B: IBackend + conn::Softmax<f32> + Copy<f32> + Scal<f32>> ComputeOutput<f32, B> for Softmax {
     fn compute_output(&self,
                       backend: &B,
                       _weights: &[&SharedTensor<f32>],
                       input_data: &[&SharedTensor<f32>],
                       output_data: &mut [&mut SharedTensor<f32>]) {
-        backend.softmax_plain(input_data[0], output_data[0]).unwrap();
+        if self.temperature == 1f32 {
+            backend.softmax_plain(input_data[0], output_data[0]).unwrap();
+        } else {
+            let mut scaled_input = SharedTensor::<f32>::new(IBackend::device(backend), input_data[0].desc()).unwrap();
+            backend.copy_plain(input_data[0], &mut scaled_input).unwrap();
+            backend.scal(&mut native_scalar(1f32 / self.temperature), &mut scaled_input).unwrap();
+            backend.softmax_plain(&scaled_input, output_data[0]).unwrap();
+        }
     }
 }
 
-impl<B: IBackend + conn::Softmax<f32>> ComputeInputGradient<f32, B> for Softmax {
+impl<B: IBackend + conn::Softmax<f32> + Copy<f32> + Scal<f32>> ComputeInputGradient<f32, B> for Softmax {
     fn compute_input_gradient(&self,
                               backend: &B,
                               weights_data: &[&SharedTensor<f32>],
@@ -46,13 +67,62 @@ impl<B: IBackend + conn::Softmax<f32>> ComputeInputGradient<f32, B> for Softmax
                               input_data: &[&SharedTensor<f32>],
                               input_gradients: &mut [&mut SharedTensor<f32>]) {
         backend.softmax_grad_plain(output_data[0], output_gradients[0], input_gradients[0]).unwrap();
+        if self.temperature != 1f32 {
+            backend.scal(&mut native_scalar(1f32 / self.temperature), input_gradients[0]).unwrap();
+        }
     }
 }
 
-impl<B: IBackend + conn::Softmax<f32>> ComputeParametersGradient<f32, B> for Softmax { }
+impl<B: IBackend + conn::Softmax<f32> + Copy<f32> + Scal<f32>> ComputeParametersGradient<f32, B> for Softmax { }
 
 impl ::std::default::Default for Softmax {
     fn default() -> Softmax {
-        Softmax
+        Softmax::from_config(&SoftmaxConfig::default())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+/// Specifies configuration parameters for a Softmax Layer.
+pub struct SoftmaxConfig {
+    /// The temperature the input is divided by before the softmax is computed.
+    ///
+    /// Temperatures above `1` produce a softer probability distribution over the classes,
+    /// as used e.g. for [knowledge distillation][1].
+    /// [1]: https://arxiv.org/abs/1503.02531
+    ///
+    /// Default: `1.0`
+    pub temperature: f32,
+}
+
+impl ::std::default::Default for SoftmaxConfig {
+    fn default() -> SoftmaxConfig {
+        SoftmaxConfig {
+            temperature: 1f32,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for SoftmaxConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the SoftmaxConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_temperature(self.temperature);
+    }
+}
+
+impl<'a> CapnpRead<'a> for SoftmaxConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        SoftmaxConfig {
+            temperature: reader.get_temperature(),
+        }
+    }
+}
+
+impl Into<LayerType> for SoftmaxConfig {
+    fn into(self) -> LayerType {
+        LayerType::Softmax(self)
     }
 }
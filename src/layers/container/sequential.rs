@@ -5,7 +5,10 @@ use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 use co::{IBackend, SharedTensor};
 use layer::*;
-use util::{ArcLock, LayerOps};
+use layers::{FlattenConfig, FusedActivation, FusedLinearConfig};
+use logging::{Shape, Subsystem};
+use log::LogLevel;
+use util::{ArcLock, LayerOps, tensor_to_vec, write_tensor};
 use leaf_capnp::sequential_config as capnp_config;
 use leaf_capnp::shaped_input as capnp_shaped_input;
 use capnp_util::*;
@@ -15,13 +18,20 @@ pub struct Sequential<B: IBackend + LayerOps<f32>> {
     layers: Vec<RefCell<Layer<B>>>,
 
     input_tensor_names: Vec<String>,
+    label_tensor_names: Vec<String>,
     input_data_tensors: Vec<ArcLock<SharedTensor<f32>>>,
     input_gradient_tensors: Vec<ArcLock<SharedTensor<f32>>>,
 
     output_data_tensors: Vec<ArcLock<SharedTensor<f32>>>,
     output_gradient_tensors: Vec<ArcLock<SharedTensor<f32>>>,
+    // the layer id and the output id within the layer of every output
+    output_ids: Vec<(usize, usize)>,
+
+    train_from: usize,
 
     registry: HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)>,
+    // the named weights of the layers, so layers added later can share them
+    weight_registry: HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>, Option<f32>, Option<f32>, ArcLock<Vec<ArcLock<SharedTensor<f32>>>>)>,
 }
 
 impl<B: IBackend + LayerOps<f32> + 'static> Sequential<B> {
@@ -31,21 +41,31 @@ impl<B: IBackend + LayerOps<f32> + 'static> Sequential<B> {
             layers: vec![],
 
             input_tensor_names: vec![],
+            label_tensor_names: vec![],
             input_data_tensors: vec![],
             input_gradient_tensors: vec![],
 
             output_data_tensors: vec![],
             output_gradient_tensors: vec![],
+            output_ids: vec![],
+
+            train_from: 0,
 
             registry: HashMap::new(),
+            weight_registry: HashMap::new(),
         }
     }
 
     /// Create a Sequential layer from a SequentialConfig.
+    ///
+    /// Panics if the layers can't be connected (see [init_layers][1]).
+    /// [1]: #method.init_layers
     pub fn from_config(backend: Rc<B>, config: &SequentialConfig) -> Sequential<B> {
         let mut layer = Self::empty();
 
-        layer.init_layers(backend, &config.clone());
+        if let Err(e) = layer.init_layers(backend, &config.clone()) {
+            panic!("{}", e);
+        }
 
         layer
     }
@@ -57,48 +77,88 @@ impl<B: IBackend + LayerOps<f32> + 'static> Sequential<B> {
     /// to be executed for each tensor and layer.
     ///
     /// [1]: ./struct.SequentialConfig.html
-    pub fn init_layers(&mut self, backend: Rc<B>, in_config: &SequentialConfig) {
+    ///
+    /// Layers without configured outputs are connected to the next layer through blobs named
+    /// `SEQUENTIAL_{i}` after their position `i`, and the last layer outputs `SEQUENTIAL_OUTPUT_{i}`.
+    /// If such a name is already chosen for an input or output in the config, a suffix
+    /// `_1`, `_2`, ... is added until the name is unique, so the generated names never collide
+    /// with the configured ones. The names only have to be unique inside the container,
+    /// as every container connects its layers separately.
+    ///
+    /// Unless [auto_flatten][2] is disabled, a Linear layer `{name}` whose input has more than two
+    /// dimensions receives it through an inserted Flatten layer `{name}/flatten`.
+    /// [2]: ./struct.SequentialConfig.html#structfield.auto_flatten
+    ///
+    /// Returns an error if two layers or two inputs of the container have the same name,
+    /// if two layers output a blob with the same name without computing in-place or if a declared
+    /// output is neither the name of a layer nor of a blob.
+    pub fn init_layers(&mut self, backend: Rc<B>, in_config: &SequentialConfig) -> Result<(), String> {
+        try!(check_layer_names(in_config.layers.iter().map(|layer| &layer.name)));
         let mut config = in_config.clone();
+        let configured_names = config.inputs.iter().chain(config.label_inputs.iter()).map(|input| input.0.clone())
+            .chain(config.layers.iter().flat_map(|layer| layer.inputs.iter().chain(layer.outputs.iter()).cloned()))
+            .collect::<HashSet<_>>();
         let mut registry = HashMap::<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)>::new();
-        let weight_registry = &mut HashMap::<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>, Option<f32>, Option<f32>)>::new();
+        let mut weight_registry = HashMap::<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>, Option<f32>, Option<f32>, ArcLock<Vec<ArcLock<SharedTensor<f32>>>>)>::new();
 
         for (input_name, input_shape) in config.inputs.clone() {
-            self.init_input_blob(backend.clone(), &input_name, &input_shape, &mut registry);
+            try!(self.init_input_blob(backend.clone(), &input_name, &input_shape, &mut registry));
         }
 
+        // sinks like Silence layers only consume the blobs they are configured with,
+        // so they are left out when the layers are chained
+        let chained_ids = (0..config.layers.len()).filter(|&i| !config.layers[i].layer_type.is_sink()).collect::<Vec<_>>();
         // add input names to first layer so they correctly connect
-        if let Some(first_layer) = config.layers.first_mut() {
+        if let Some(&first_id) = chained_ids.first() {
             for container_input in &self.input_tensor_names {
-                first_layer.add_input(&container_input);
+                config.layers[first_id].add_input(&container_input);
             }
         }
+        for (label_name, label_shape) in config.label_inputs.clone() {
+            try!(self.init_input_blob(backend.clone(), &label_name, &label_shape, &mut registry));
+            self.label_tensor_names.push(label_name);
+        }
         // connect each layer to the next one
-        for (i, _) in config.layers.clone().iter().enumerate() {
-            match i == (config.layers.len() - 1) {
-                false => {
+        for (n, &i) in chained_ids.iter().enumerate() {
+            match chained_ids.get(n + 1) {
+                Some(&next) => {
                     // layers have already been manually connected
-                    if config.layers[i].outputs.get(0).is_some() && config.layers[i + 1].inputs.get(0).is_some() &&
-                       config.layers[i].outputs.get(0) == config.layers[i + 1].inputs.get(0) {
+                    if config.layers[i].outputs.get(0).is_some() && config.layers[next].inputs.get(0).is_some() &&
+                       config.layers[i].outputs.get(0) == config.layers[next].inputs.get(0) {
                         continue;
                     }
                     if let Some(in_place) = config.find_in_place_output(i) {
                         config.layers[i].add_output(&in_place);
-                        config.layers[i + 1].add_input(&in_place);
+                        config.layers[next].add_input(&in_place);
                     } else {
-                        config.layers[i].add_output(&format!("SEQUENTIAL_{}", i));
-                        config.layers[i + 1].add_input(&format!("SEQUENTIAL_{}", i));
+                        let blob_name = generated_blob_name(&format!("SEQUENTIAL_{}", i), &configured_names);
+                        config.layers[i].add_output(&blob_name);
+                        config.layers[next].add_input(&blob_name);
                     }
                 },
                 // last layer
-                true => {
-                    config.layers[i].add_output(&format!("SEQUENTIAL_OUTPUT_{}", i));
+                None => {
+                    if config.layers[i].outputs.is_empty() {
+                        let blob_name = generated_blob_name(&format!("SEQUENTIAL_OUTPUT_{}", i), &configured_names);
+                        config.layers[i].add_output(&blob_name);
+                    }
                 },
             }
         }
+        self.connect_label_inputs(&mut config);
 
         let mut shared_workspace = None;
+        let mut layer_names = config.layers.iter().map(|layer| layer.name.clone()).collect::<HashSet<_>>();
+        let mut blob_names = configured_names.clone();
         for layer_config in &config.layers {
-            self.init_layer(backend.clone(), &layer_config, &mut registry, weight_registry);
+            let mut layer_config = layer_config.clone();
+            if config.auto_flatten {
+                if let Some(flatten_config) = flatten_input(&mut layer_config, &registry, &mut layer_names, &mut blob_names) {
+                    try!(self.init_layer(backend.clone(), &flatten_config, &mut registry, &mut weight_registry));
+                    shared_workspace = self.resize_shared_workspace(backend.clone(), shared_workspace);
+                }
+            }
+            try!(self.init_layer(backend.clone(), &layer_config, &mut registry, &mut weight_registry));
             shared_workspace = self.resize_shared_workspace(backend.clone(), shared_workspace);
         }
 
@@ -120,19 +180,26 @@ impl<B: IBackend + LayerOps<f32> + 'static> Sequential<B> {
             }
         }
 
-        // Outputs of the last layer are considered output of the container
-        if let Some(last_layer) = self.layers.last() {
-            for data_tensor in &last_layer.borrow().output_blobs_data {
-                self.output_data_tensors.push(data_tensor.clone());
+        // The declared outputs are considered output of the container.
+        // Without declared outputs the outputs of the last layer that is not a sink are used.
+        if config.outputs.is_empty() {
+            if let Some(layer_id) = self.layers.iter().rposition(|layer| !layer.borrow().config.layer_type.is_sink()) {
+                let num_outputs = self.layers[layer_id].borrow().output_blobs_data.len();
+                self.output_ids = (0..num_outputs).map(|output_id| (layer_id, output_id)).collect();
             }
-            for gradient_tensor in &last_layer.borrow().output_blobs_gradient {
-                self.output_gradient_tensors.push(gradient_tensor.clone());
+        } else {
+            for output_name in &config.outputs {
+                self.output_ids.extend(try!(self.find_output(output_name)));
             }
         }
+        self.collect_outputs();
 
         self.registry = registry;
+        self.weight_registry = weight_registry;
 
-        info!("Sequential container initialization done.");
+        log_event!(Subsystem::Init, LogLevel::Debug, "sequential_initialized", layers = self.layers.len());
+
+        Ok(())
     }
 
     /// Initialize a input tensor for the Sequential container.
@@ -147,15 +214,13 @@ impl<B: IBackend + LayerOps<f32> + 'static> Sequential<B> {
                   backend: Rc<B>,
                   tensor_name: &str,
                   input_shape: &[usize],
-                  registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)> ) {
+                  registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)> ) -> Result<(), String> {
 
         if registry.contains_key(tensor_name) {
-            // If we are not doing in-place computation but see two layers trying
-            // to produce the same tensor, raise an error.
-            error!("Output tensor {} produced by multiple sources.", tensor_name);
-            return
+            log_event!(Subsystem::Init, LogLevel::Error, "duplicate_input", input = tensor_name);
+            Err(format!("The container has more than one input named {}. Inputs and label inputs need unique names.", tensor_name))
         } else {
-            info!("Input {} -> {}", self.input_data_tensors.len(), tensor_name);
+            log_event!(Subsystem::Init, LogLevel::Debug, "container_input", input = tensor_name, input_id = self.input_data_tensors.len(), shape = Shape(input_shape));
 
             let ibackend: Rc<IBackend<F=B::F>> = backend;
             let data_tensor: ArcLock<SharedTensor<f32>> = Arc::new(RwLock::new(SharedTensor::new(ibackend.device(), &input_shape).unwrap()));
@@ -165,6 +230,29 @@ impl<B: IBackend + LayerOps<f32> + 'static> Sequential<B> {
             self.input_gradient_tensors.push(gradient_tensor.clone());
             self.input_tensor_names.push(tensor_name.to_owned());
             registry.insert(tensor_name.to_owned(), (data_tensor, gradient_tensor));
+            Ok(())
+        }
+    }
+
+    /// Connect the label inputs of the container to the layers that consume them.
+    ///
+    /// Labels that are not used as input by any layer are connected to the last layer.
+    /// The labels are moved behind the other inputs of a layer, so e.g. a loss layer
+    /// receives the output of the previous layer first and the label second.
+    fn connect_label_inputs(&self, config: &mut SequentialConfig) {
+        for label_name in &self.label_tensor_names {
+            let is_used = config.layers.iter().any(|layer| layer.inputs.contains(label_name));
+            if !is_used {
+                if let Some(last_layer) = config.layers.iter_mut().filter(|layer| !layer.layer_type.is_sink()).last() {
+                    last_layer.add_input(label_name);
+                }
+            }
+        }
+        for layer in &mut config.layers {
+            let (labels, mut inputs): (Vec<String>, Vec<String>) = layer.inputs.iter().cloned()
+                .partition(|input_name| self.label_tensor_names.contains(input_name));
+            inputs.extend(labels);
+            layer.inputs = inputs;
         }
     }
 
@@ -180,19 +268,144 @@ impl<B: IBackend + LayerOps<f32> + 'static> Sequential<B> {
                   backend: Rc<B>,
                   layer_config: &LayerConfig,
                   registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)>,
-                  weight_registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>, Option<f32>, Option<f32>)>) {
+                  weight_registry: &mut HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>, Option<f32>, Option<f32>, ArcLock<Vec<ArcLock<SharedTensor<f32>>>>)>) -> Result<(), String> {
         // Setup layer.
         if let Err(e) = layer_config.validate() {
-            error!("{}", e);
+            log_event!(Subsystem::Init, LogLevel::Error, "invalid_config", layer = layer_config.name, error = e);
         }
 
-        info!("Creating Layer {}", &layer_config.name);
+        log_event!(Subsystem::Init, LogLevel::Debug, "create_layer", layer = layer_config.name);
         let mut layer = Layer::from_config(backend, &layer_config);
 
         // Figure out this layer's input and output
-        layer.connect(registry, weight_registry);
+        try!(self.check_output_names(&layer.config, registry));
+        try!(layer.connect(registry, weight_registry));
 
         self.layers.push(RefCell::new(layer));
+        Ok(())
+    }
+
+    /// Check that no layer of the container except the one at `replaced_id` is named `layer_name`.
+    fn check_unique_layer_name(&self, layer_name: &str, replaced_id: Option<usize>) -> Result<(), String> {
+        let is_taken = self.layers.iter().enumerate()
+            .any(|(layer_id, layer)| Some(layer_id) != replaced_id && layer.borrow().name == layer_name);
+        if is_taken {
+            log_event!(Subsystem::Init, LogLevel::Error, "duplicate_layer", layer = layer_name);
+            return Err(duplicate_layer_message(layer_name));
+        }
+        Ok(())
+    }
+
+    /// Check that the outputs of a layer are not already produced by a container input or another layer.
+    ///
+    /// Outputs that are computed in-place have the same name as the input at the same
+    /// position and are allowed to exist already.
+    fn check_output_names(&self,
+                          layer_config: &LayerConfig,
+                          registry: &HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)>) -> Result<(), String> {
+        for (output_id, output_name) in layer_config.outputs.iter().enumerate() {
+            let input_name = layer_config.input(output_id);
+            if input_name == Some(output_name) || !registry.contains_key(output_name) {
+                continue
+            }
+            let producer = if self.input_tensor_names.contains(output_name) {
+                "the container input of the same name".to_owned()
+            } else {
+                match self.layers.iter().find(|layer| layer.borrow().blob_names.contains_key(output_name)) {
+                    Some(layer) => format!("layer {}", layer.borrow().name),
+                    None => "another layer".to_owned(),
+                }
+            };
+            return Err(duplicate_output_message(&layer_config.name, output_name, &producer, input_name.map(|input| &**input)));
+        }
+        Ok(())
+    }
+
+    /// Find the layer id and output id of the outputs that a declared output refers to.
+    ///
+    /// The name of a layer refers to all outputs of the layer, the name of a blob to the
+    /// output of the last layer that produces it.
+    fn find_output(&self, output_name: &str) -> Result<Vec<(usize, usize)>, String> {
+        if let Some(layer_id) = self.layers.iter().position(|layer| layer.borrow().name == output_name) {
+            let num_outputs = self.layers[layer_id].borrow().output_blobs_data.len();
+            return Ok((0..num_outputs).map(|output_id| (layer_id, output_id)).collect());
+        }
+        for (layer_id, layer) in self.layers.iter().enumerate().rev() {
+            if let Some(output_id) = layer.borrow().output_blob_names().iter().position(|name| name == output_name) {
+                return Ok(vec![(layer_id, output_id)]);
+            }
+        }
+        log_event!(Subsystem::Init, LogLevel::Error, "unknown_output", output = output_name);
+        Err(format!("The container has no layer or blob named {}, which is declared as output", output_name))
+    }
+
+    /// Collects the output tensors of the container from the output layers again.
+    ///
+    /// Used after the layers of the container have been changed.
+    fn collect_outputs(&mut self) {
+        self.output_data_tensors = vec![];
+        self.output_gradient_tensors = vec![];
+        for &(layer_id, output_id) in &self.output_ids {
+            let layer = self.layers[layer_id].borrow();
+            if let (Some(data_tensor), Some(gradient_tensor)) = (layer.output_blobs_data.get(output_id), layer.output_blobs_gradient.get(output_id)) {
+                self.output_data_tensors.push(data_tensor.clone());
+                self.output_gradient_tensors.push(gradient_tensor.clone());
+            }
+        }
+    }
+
+    /// Appends an already created [Layer][1] at the end of the container.
+    /// [1]: ../../../layer/struct.Layer.html
+    ///
+    /// This allows placing layers with a custom [ILayer][2] implementation (see
+    /// [Layer::from_worker][3]) inside a container.
+    /// If the layer has no configured inputs it is connected to the outputs of the current
+    /// last layer (or the container inputs if the container is empty).
+    /// If the outputs of the container are the outputs of the current last layer,
+    /// the outputs of the new layer become the outputs of the container instead.
+    /// Weights with a [configured name][4] are shared with the layers of the container
+    /// that use the same name.
+    ///
+    /// The layer has to be added before the container itself is wrapped in a [Layer][1].
+    /// [2]: ../../../layer/trait.ILayer.html
+    /// [3]: ../../../layer/struct.Layer.html#method.from_worker
+    /// [4]: ../../../weight/struct.WeightConfig.html#structfield.name
+    ///
+    /// Returns an error if the container already has a layer with the same name
+    /// or if an output of the layer is already produced by another layer.
+    pub fn add_layer_instance(&mut self, mut layer: Layer<B>) -> Result<(), String> {
+        let layer_id = self.layers.len();
+        try!(self.check_unique_layer_name(&layer.name, None));
+        if layer.config.inputs_len() == 0 {
+            let input_names = match self.layers.last() {
+                Some(last_layer) => last_layer.borrow().config.outputs.clone(),
+                None => self.input_tensor_names.iter()
+                    .filter(|input_name| !self.label_tensor_names.contains(input_name))
+                    .cloned().collect(),
+            };
+            for input_name in &input_names {
+                layer.config.add_input(input_name);
+            }
+        }
+        if layer.config.outputs_len() == 0 {
+            let used_names = self.registry.keys().cloned().collect::<HashSet<_>>();
+            layer.config.add_output(&generated_blob_name(&format!("SEQUENTIAL_OUTPUT_{}", layer_id), &used_names));
+        }
+
+        log_event!(Subsystem::Init, LogLevel::Debug, "add_layer", layer = layer.name);
+        try!(self.check_output_names(&layer.config, &self.registry));
+        try!(layer.connect(&mut self.registry, &mut self.weight_registry));
+        layer.init_backprop(&mut HashSet::new(), &mut HashSet::new());
+
+        let uses_last_layer_outputs = self.output_ids.iter().all(|&(id, _)| id + 1 == layer_id);
+        if uses_last_layer_outputs {
+            self.output_data_tensors = layer.output_blobs_data.clone();
+            self.output_gradient_tensors = layer.output_blobs_gradient.clone();
+            self.output_ids = (0..layer.output_blobs_data.len()).map(|output_id| (layer_id, output_id)).collect();
+        }
+
+        self.layers.push(RefCell::new(layer));
+        Ok(())
     }
 }
 
@@ -209,6 +422,25 @@ impl<B: IBackend + LayerOps<f32> + 'static> ILayer<B> for Sequential<B> {
         Some(self.input_gradient_tensors.clone())
     }
 
+    fn inputs_names(&self) -> Option<Vec<String>> {
+        Some(self.input_tensor_names.clone())
+    }
+
+    fn outputs_names(&self) -> Option<Vec<String>> {
+        Some(self.output_ids.iter().map(|&(layer_id, output_id)| {
+            let layer = self.layers[layer_id].borrow();
+            layer.output_blob_names().get(output_id).cloned().unwrap_or_else(|| layer.name.clone())
+        }).collect())
+    }
+
+    fn execution_order(&self) -> Option<Vec<LayerNode>> {
+        Some(self.layers.iter().map(|layer| LayerNode::from(&*layer.borrow())).collect())
+    }
+
+    fn layer_outputs(&self) -> Option<Vec<LayerOutputs>> {
+        Some(self.layers.iter().flat_map(|layer| layer.borrow().layer_outputs()).collect())
+    }
+
     fn outputs_data(&self) -> Option<Vec<ArcLock<SharedTensor<f32>>>> {
         Some(self.output_data_tensors.clone())
     }
@@ -232,8 +464,166 @@ impl<B: IBackend + LayerOps<f32> + 'static> ILayer<B> for Sequential<B> {
         Some(names)
     }
 
+    fn learnable_weights_paths(&self) -> Option<Vec<String>> {
+        let paths = self.layers.iter().flat_map(|layer| layer.borrow().learnable_weights_paths()).collect();
+        Some(paths)
+    }
+
+    fn learnable_weights_layer_names(&self) -> Option<Vec<String>> {
+        let names = self.layers.iter().flat_map(|layer| layer.borrow().learnable_weights_layer_names()).collect();
+        Some(names)
+    }
+
+    fn learnable_weights_lr(&self) -> Option<Vec<Option<f32>>> {
+        let lr = self.layers.iter().flat_map(|layer| layer.borrow().learnable_weights_lr()).collect();
+        Some(lr)
+    }
+
+    fn learnable_weights_decay_mult(&self) -> Option<Vec<f32>> {
+        let decay_mult = self.layers.iter().flat_map(|layer| layer.borrow().learnable_weights_decay_mult()).collect();
+        Some(decay_mult)
+    }
+
+    fn train_from(&mut self, layer_name: &str) -> Result<(), String> {
+        let layer_id = match self.layers.iter().position(|layer| layer.borrow().name == layer_name) {
+            Some(layer_id) => layer_id,
+            None => return Err(format!("Sequential container has no layer named {}", layer_name)),
+        };
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer.borrow_mut().set_trainable(i >= layer_id);
+        }
+        self.train_from = layer_id;
+
+        Ok(())
+    }
+
+    fn set_training(&mut self, training: bool) {
+        for layer in &self.layers {
+            layer.borrow_mut().set_training(training);
+        }
+    }
+
+    fn register_hook(&mut self, layer_name: &str, kind: HookKind, hook: LayerHook) -> Result<(), String> {
+        let layer = self.layers.iter().find(|layer| {
+            let layer = layer.borrow();
+            layer.name == layer_name || layer.layer_outputs().iter().any(|nested| nested.name == layer_name)
+        });
+        match layer {
+            Some(layer) => layer.borrow_mut().register_hook(layer_name, kind, hook),
+            None => Err(format!("Sequential container has no layer named {}", layer_name)),
+        }
+    }
+
+    /// Replace the layer with the name `layer_name` by a new layer created from `config`.
+    ///
+    /// If `config` has no inputs or outputs, the new layer uses the inputs and outputs
+    /// of the replaced layer. The following layers are connected to the outputs of
+    /// the new layer, and only the layers whose input shapes changed are reshaped.
+    /// Weights keep their values unless their shape changed, so the weights of
+    /// the other layers are kept.
+    /// Weights with a [configured name][2] are shared with the other layers of the container
+    /// that use the same name. A layer that owns a weight which other layers share can't be replaced.
+    /// [2]: ../../../weight/struct.WeightConfig.html#structfield.name
+    fn replace_layer(&mut self, backend: Rc<B>, layer_name: &str, config: &LayerConfig) -> Result<(), String> {
+        let layer_id = match self.layers.iter().position(|layer| layer.borrow().name == layer_name) {
+            Some(layer_id) => layer_id,
+            None => return Err(format!("Sequential container has no layer named {}", layer_name)),
+        };
+
+        try!(self.check_unique_layer_name(&config.name, Some(layer_id)));
+        let (owned_weights, old_gradients) = {
+            let old_layer = self.layers[layer_id].borrow();
+            let learnable_weights = old_layer.learnable_weights_data();
+            let owned_weights = self.weight_registry.iter()
+                .filter(|&(_, entry)| learnable_weights.iter().any(|weight| Arc::ptr_eq(weight, &entry.0)))
+                .map(|(registry_name, entry)| (registry_name.clone(), entry.4.read().unwrap().len()))
+                .collect::<Vec<_>>();
+            (owned_weights, old_layer.weights_gradient.clone())
+        };
+        if owned_weights.iter().any(|&(_, num_sharing)| num_sharing > 0) {
+            return Err(format!("Layer {} can't be replaced, because other layers share its weights", layer_name));
+        }
+        let (old_inputs, old_outputs) = {
+            let old_layer = self.layers[layer_id].borrow();
+            (old_layer.config.inputs.clone(), old_layer.config.outputs.clone())
+        };
+        let mut layer_config = config.clone();
+        if layer_config.inputs_len() == 0 {
+            for input_name in &old_inputs {
+                layer_config.add_input(input_name);
+            }
+        }
+        if layer_config.outputs_len() == 0 {
+            for output_name in &old_outputs {
+                layer_config.add_output(output_name);
+            }
+        }
+
+        let input_shapes = self.layers.iter().skip(layer_id + 1)
+            .map(|layer| layer.borrow().input_blobs_data.iter().map(|input| input.read().unwrap().desc().clone()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        // outputs that were not computed in-place are allocated again by the new layer
+        let mut old_blobs = Vec::new();
+        for output_name in &old_outputs {
+            if !old_inputs.contains(output_name) {
+                if let Some(blob) = self.registry.remove(output_name) {
+                    old_blobs.push((output_name.clone(), blob));
+                }
+            }
+        }
+        if let Err(e) = self.check_output_names(&layer_config, &self.registry) {
+            self.registry.extend(old_blobs);
+            return Err(e);
+        }
+        log_event!(Subsystem::Init, LogLevel::Info, "replace_layer", layer = layer_name, replacement = layer_config.name);
+        // the replaced layer no longer owns or shares any named weights
+        for (registry_name, _) in owned_weights {
+            self.weight_registry.remove(&registry_name);
+        }
+        for entry in self.weight_registry.values() {
+            entry.4.write().unwrap().retain(|gradient| !old_gradients.iter().any(|old_gradient| Arc::ptr_eq(old_gradient, gradient)));
+        }
+        let mut layer = Layer::from_config(backend, &layer_config);
+        try!(layer.connect(&mut self.registry, &mut self.weight_registry));
+        layer.init_backprop(&mut HashSet::new(), &mut HashSet::new());
+        self.layers[layer_id] = RefCell::new(layer);
+
+        for output_name in &layer_config.outputs {
+            if let Some(&(ref data, ref gradient)) = self.registry.get(output_name) {
+                for following_layer in self.layers.iter().skip(layer_id + 1) {
+                    following_layer.borrow_mut().replace_blob(output_name, data.clone(), gradient.clone());
+                }
+            }
+        }
+        for (following_layer, old_shapes) in self.layers.iter().skip(layer_id + 1).zip(input_shapes) {
+            let mut following_layer = following_layer.borrow_mut();
+            let shapes = following_layer.input_blobs_data.iter().map(|input| input.read().unwrap().desc().clone()).collect::<Vec<_>>();
+            if shapes != old_shapes {
+                // reshaping fills the weights again, so restore those whose shape didn't change
+                let old_weights = following_layer.weights_data.iter()
+                    .map(|weight| {
+                        let shape = weight.read().unwrap().desc().clone();
+                        (shape, tensor_to_vec(weight))
+                    })
+                    .collect::<Vec<_>>();
+                following_layer.reshape();
+                for (weight, &(ref old_shape, ref old_values)) in following_layer.weights_data.iter().zip(&old_weights) {
+                    let shape = weight.read().unwrap().desc().clone();
+                    if shape == *old_shape {
+                        try!(write_tensor(weight, old_values));
+                    }
+                }
+            }
+        }
+
+        self.collect_outputs();
+
+        Ok(())
+    }
+
     fn resize_shared_workspace(&mut self, backend: Rc<B>, workspace: Option<ArcLock<SharedTensor<u8>>>) -> Option<ArcLock<SharedTensor<u8>>> {
-        debug!("Resizing shared workspace {:?}", workspace.is_some());
+        log_event!(Subsystem::Init, LogLevel::Debug, "resize_shared_workspace", existing = workspace.is_some());
         let mut shared_workspace = workspace;
 
         for layer in &self.layers {
@@ -249,15 +639,13 @@ impl<B: IBackend + LayerOps<f32> + 'static> ILayer<B> for Sequential<B> {
                weights_data: &[ArcLock<SharedTensor<f32>>],
                output_data: &mut [ArcLock<SharedTensor<f32>>]) {
         for layer in &self.layers {
-            for (i, (input, input_name)) in input_data.iter().zip(self.input_tensor_names.iter()).enumerate() {
-                if &layer.borrow().input_blob_names[i] == input_name {
-                    layer.borrow_mut().input_blobs_data[i] = input.clone();
+            let mut layer = layer.borrow_mut();
+            for (input, input_name) in input_data.iter().zip(self.input_tensor_names.iter()) {
+                if let Some(i) = layer.input_blob_names.iter().position(|name| name == input_name) {
+                    layer.input_blobs_data[i] = input.clone();
                 }
             }
-            layer.borrow_mut().forward(&[]);
-        }
-        if let Some(last_layer) = self.layers.last() {
-            last_layer.borrow_mut().synchronize();
+            layer.forward(&[]);
         }
     }
 
@@ -268,12 +656,15 @@ impl<B: IBackend + LayerOps<f32> + 'static> ILayer<B> for Sequential<B> {
                 output_gradients: &[ArcLock<SharedTensor<f32>>],
                 input_data: &[ArcLock<SharedTensor<f32>>],
                 input_gradients: &mut [ArcLock<SharedTensor<f32>>]) {
-        if let Some(last_layer) = self.layers.last() {
-            for (i, output_gradient) in output_gradients.iter().enumerate() {
-                last_layer.borrow_mut().output_blobs_gradient[i] = output_gradient.clone();
-            }
+        // only the outputs of the last layer take part in backpropagation
+        let last_layer_id = self.layers.len().wrapping_sub(1);
+        let last_layer_gradients = output_gradients.iter()
+            .zip(self.output_ids.iter())
+            .filter(|&(_, &(layer_id, _))| layer_id == last_layer_id);
+        for (output_gradient, &(_, output_id)) in last_layer_gradients {
+            self.layers[last_layer_id].borrow_mut().output_blobs_gradient[output_id] = output_gradient.clone();
         }
-        for layer in self.layers.iter().rev() {
+        for layer in self.layers.iter().skip(self.train_from).rev() {
             layer.borrow_mut().backward_input(&[]);
         }
         if let Some(first_layer) = self.layers.iter().rev().last() {
@@ -287,7 +678,7 @@ impl<B: IBackend + LayerOps<f32> + 'static> ILayer<B> for Sequential<B> {
                 output_gradients: &[ArcLock<SharedTensor<f32>>],
                 input_data: &[ArcLock<SharedTensor<f32>>],
                 weights_gradients: &mut [ArcLock<SharedTensor<f32>>]) {
-        for layer in self.layers.iter().rev() {
+        for layer in self.layers.iter().skip(self.train_from).rev() {
             layer.borrow_mut().backward_parameters();
         }
         if let Some(first_layer) = self.layers.iter().rev().last() {
@@ -343,6 +734,27 @@ pub struct SequentialConfig {
     /// [layer_config]: ../../../layer/struct.LayerConfig.html
     pub inputs: Vec<(String, Vec<usize>)>,
 
+    /// Defines the names and shapes of the label input tensors.
+    ///
+    /// Label inputs are inputs of the container that follow the other inputs,
+    /// but unlike them they are not connected to the first layer.
+    /// Instead they are routed to the layers that declare them as input,
+    /// or to the last layer if no layer does. This allows expressing a network
+    /// together with its loss layer, which consumes the labels, in one container.
+    ///
+    /// Default: `[]`
+    pub label_inputs: Vec<(String, Vec<usize>)>,
+
+    /// Defines the outputs of the container by the names of layers or blobs.
+    ///
+    /// The name of a layer declares all outputs of the layer, the name of a blob only that blob.
+    /// The outputs are returned in the order they are declared here.
+    /// Only the outputs of the last layer take part in backpropagation;
+    /// outputs of earlier layers are only available in the forward pass.
+    ///
+    /// Default: `[]`, which uses the outputs of the last layer.
+    pub outputs: Vec<String>,
+
     /// Defines if the container will force every layer to do [backpropagation][1].
     /// [1]: https://en.wikipedia.org/wiki/Backpropagation
     ///
@@ -351,6 +763,85 @@ pub struct SequentialConfig {
     ///
     /// Default: `false`
     pub force_backward: bool,
+
+    /// Defines if the container flattens the input of Linear layers that has more than two dimensions.
+    ///
+    /// A Flatten layer that keeps the batch dimension is inserted in front of such a Linear
+    /// layer, e.g. between a stack of Convolution layers and the fully connected classifier.
+    /// Set to `false` if the shapes are wired explicitly, e.g. with [Flatten][1] or
+    /// [Reshape][2] layers, and Linear layers should fold the dimensions of their input themselves.
+    /// [1]: ../../utility/struct.Flatten.html
+    /// [2]: ../../utility/struct.Reshape.html
+    ///
+    /// Default: `true`
+    pub auto_flatten: bool,
+}
+
+/// Check that no two layers of a container have the same name.
+fn check_layer_names<'a, I: Iterator<Item=&'a String>>(layer_names: I) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for layer_name in layer_names {
+        if !seen.insert(layer_name) {
+            log_event!(Subsystem::Init, LogLevel::Error, "duplicate_layer", layer = layer_name);
+            return Err(duplicate_layer_message(layer_name));
+        }
+    }
+    Ok(())
+}
+
+fn duplicate_layer_message(layer_name: &str) -> String {
+    format!("The container has more than one layer named {}. Layers need unique names.", layer_name)
+}
+
+/// Insert a Flatten layer in front of a Linear layer whose first input has more than two dimensions,
+/// e.g. the output of a Convolution or Pooling layer.
+///
+/// The input of `layer_config` is replaced with the flattened blob and the Flatten layer
+/// that computes it is returned. It copies instead of flattening in-place, so the shape
+/// of the blob stays intact for the layer that produces it.
+fn flatten_input(layer_config: &mut LayerConfig,
+                 registry: &HashMap<String, (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>)>,
+                 layer_names: &mut HashSet<String>,
+                 blob_names: &mut HashSet<String>) -> Option<LayerConfig> {
+    match layer_config.layer_type {
+        LayerType::Linear(_) | LayerType::FusedLinear(_) => {},
+        _ => return None,
+    }
+    let input_name = match layer_config.inputs.get(0) {
+        Some(input_name) => input_name.clone(),
+        None => return None,
+    };
+    let input_shape = match registry.get(&input_name) {
+        Some(&(ref data, _)) => data.read().unwrap().desc().clone(),
+        None => return None,
+    };
+    if input_shape.len() <= 2 {
+        return None;
+    }
+
+    let flatten_name = generated_blob_name(&format!("{}/flatten", layer_config.name), layer_names);
+    let output_name = generated_blob_name(&format!("{}/flattened", layer_config.name), blob_names);
+    log_event!(Subsystem::Init, LogLevel::Info, "auto_flatten", layer = layer_config.name, input = input_name, shape = Shape(&input_shape));
+    layer_names.insert(flatten_name.clone());
+    blob_names.insert(output_name.clone());
+    let mut flatten_config = LayerConfig::new(&flatten_name, FlattenConfig::new());
+    flatten_config.add_input(&input_name);
+    flatten_config.add_output(&output_name);
+    layer_config.inputs[0] = output_name;
+    Some(flatten_config)
+}
+
+/// Return `base_name`, or `base_name` with the first suffix `_1`, `_2`, ... that makes it
+/// distinct from all `used_names`.
+fn generated_blob_name(base_name: &str, used_names: &HashSet<String>) -> String {
+    if !used_names.contains(base_name) {
+        return base_name.to_owned()
+    }
+    let blob_name = (1..).map(|suffix| format!("{}_{}", base_name, suffix))
+        .find(|name| !used_names.contains(name))
+        .unwrap();
+    log_event!(Subsystem::Init, LogLevel::Debug, "rename_generated_blob", blob = base_name, renamed = blob_name);
+    blob_name
 }
 
 impl SequentialConfig {
@@ -360,6 +851,10 @@ impl SequentialConfig {
             if layer.layer_type.supports_in_place() {
                 // look through all previous layers until we find the first one that is not doing in-place.
                 for prev_layer in self.layers.iter().take(n).collect::<Vec<_>>().iter().rev() {
+                    // overwriting the output of a output layer would change the container output
+                    if self.is_output_layer(prev_layer) {
+                        return None
+                    }
                     if !prev_layer.layer_type.supports_in_place() {
                         if let Some(output_name) = prev_layer.outputs.get(0) {
                             return Some(output_name.to_owned())
@@ -376,6 +871,12 @@ impl SequentialConfig {
         None
     }
 
+    /// Returns `true` if the layer or one of its configured output blobs is declared as [output][1].
+    /// [1]: #structfield.outputs
+    fn is_output_layer(&self, layer: &LayerConfig) -> bool {
+        self.outputs.iter().any(|output| *output == layer.name || layer.outputs.contains(output))
+    }
+
     /// Add layer at the end of the sequential container.
     pub fn add_layer(&mut self, layer: LayerConfig) {
         self.layers.push(layer);
@@ -386,9 +887,238 @@ impl SequentialConfig {
         self.inputs.push((input_name.to_owned(), shape.to_owned()));
     }
 
+    /// Add a label input to the network.
+    ///
+    /// See [label_inputs][1].
+    /// [1]: #structfield.label_inputs
+    pub fn add_label_input(&mut self, input_name: &str, shape: &[usize]) {
+        self.label_inputs.push((input_name.to_owned(), shape.to_owned()));
+    }
+
+    /// Mark the outputs of the layer `layer_name`, or the blob of that name, as outputs of the container.
+    ///
+    /// See [outputs][1].
+    /// [1]: #structfield.outputs
+    pub fn add_output(&mut self, layer_name: &str) {
+        self.outputs.push(layer_name.to_owned());
+    }
+
+    /// Replace every Linear layer that is directly followed by a ReLU or Sigmoid layer
+    /// with a single [FusedLinear][1] layer, including the layers of nested containers.
+    /// [1]: ../../common/fused_linear/index.html
+    ///
+    /// The fused layer keeps the name, inputs and weights of the Linear layer and the outputs
+    /// of the activation layer. Pairs are left alone if the output of the Linear layer is
+    /// needed on its own, i.e. it is an [output][2] of the container or the input of another layer.
+    /// Linear layers with a [transposed][3] weight are not fused either.
+    /// [2]: #structfield.outputs
+    /// [3]: ../../common/linear/struct.LinearConfig.html#structfield.transpose
+    ///
+    /// Fusing is meant for deployment: the fused network computes the same outputs, but
+    /// the activation layers can no longer be addressed by name.
+    pub fn fuse_activations(&mut self) {
+        let old_layers = ::std::mem::replace(&mut self.layers, vec![]);
+        let mut i = 0;
+        while i < old_layers.len() {
+            match self.fused_layer(&old_layers, i) {
+                Some(fused_layer) => {
+                    let ref activation_name = old_layers[i + 1].name;
+                    for output in &mut self.outputs {
+                        if output == activation_name {
+                            *output = fused_layer.name.clone();
+                        }
+                    }
+                    self.layers.push(fused_layer);
+                    i += 2;
+                },
+                None => {
+                    let mut layer = old_layers[i].clone();
+                    if let LayerType::Sequential(ref mut container_config) = layer.layer_type {
+                        container_config.fuse_activations();
+                    }
+                    self.layers.push(layer);
+                    i += 1;
+                },
+            }
+        }
+    }
+
+    /// Optimize the layers of the container (and of nested containers) for execution.
+    ///
+    /// See [OptimizationLevel][1] for the applied optimizations.
+    /// The layers are never reordered: every layer of a Sequential container is connected
+    /// to the outputs of its predecessor, so the execution order is already fixed.
+    /// [1]: ../../../layer/enum.OptimizationLevel.html
+    pub fn optimize(&mut self, level: OptimizationLevel) {
+        if level == OptimizationLevel::None {
+            return
+        }
+        self.eliminate_dead_layers();
+        if level == OptimizationLevel::Full {
+            self.fuse_activations();
+            self.convert_to_in_place();
+        }
+        for layer in &mut self.layers {
+            if let LayerType::Sequential(ref mut container_config) = layer.layer_type {
+                container_config.optimize(level);
+            }
+        }
+    }
+
+    /// Remove the layers that are only needed for training, including those of nested containers.
+    ///
+    /// See [LayerType::is_training_only][1]. Layers that used the outputs of a removed layer
+    /// use its input instead. Label inputs that are no longer used by any layer are removed as well.
+    /// [1]: ../../../layer/enum.LayerType.html#method.is_training_only
+    pub fn remove_training_layers(&mut self) {
+        let (training_layers, layers): (Vec<LayerConfig>, Vec<LayerConfig>) = self.layers.drain(..)
+            .partition(|layer| layer.layer_type.is_training_only());
+        self.layers = layers;
+        for training_layer in training_layers {
+            log_event!(Subsystem::Init, LogLevel::Info, "remove_training_layer", layer = training_layer.name);
+            self.outputs.retain(|output| *output != training_layer.name && !training_layer.outputs.contains(output));
+            if let Some(input_name) = training_layer.inputs.get(0) {
+                for layer in &mut self.layers {
+                    for name in &mut layer.inputs {
+                        if training_layer.outputs.contains(name) {
+                            *name = input_name.clone();
+                        }
+                    }
+                }
+            }
+        }
+        for layer in &mut self.layers {
+            if let LayerType::Sequential(ref mut container_config) = layer.layer_type {
+                container_config.remove_training_layers();
+            }
+        }
+        let layers = &self.layers;
+        self.label_inputs.retain(|&(ref label_name, _)| layers.iter().any(|layer| layer.inputs.contains(label_name)));
+    }
+
+    /// Change the batch size of the container to `batch_size`.
+    ///
+    /// The first dimension of the inputs and label inputs is replaced, including those
+    /// of nested containers.
+    ///
+    /// Returns an error if the container has a [Reshape][1] layer whose shape doesn't start with `0`
+    /// and so includes the batch size, e.g. one that wasn't created with [ReshapeConfig::of_shape_with_batch][2].
+    /// [1]: ../../utility/reshape/index.html
+    /// [2]: ../../utility/reshape/struct.ReshapeConfig.html#method.of_shape_with_batch
+    pub fn set_batch_size(&mut self, batch_size: usize) -> Result<(), String> {
+        for layer in &mut self.layers {
+            match layer.layer_type {
+                LayerType::Reshape(ref reshape_config) if reshape_config.shape.get(0) != Some(&0) => {
+                    return Err(format!("The batch size can not be changed, because layer {} reshapes to a fixed shape", layer.name))
+                }
+                LayerType::Sequential(ref mut container_config) => try!(container_config.set_batch_size(batch_size)),
+                _ => {}
+            }
+        }
+        for &mut (_, ref mut shape) in self.inputs.iter_mut().chain(self.label_inputs.iter_mut()) {
+            if let Some(dim) = shape.get_mut(0) {
+                *dim = batch_size;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the layers behind the last declared output layer.
+    ///
+    /// They only compute values that are never returned by the container, like a loss layer
+    /// behind the actual network. Label inputs that are no longer used by any layer are removed as well.
+    fn eliminate_dead_layers(&mut self) {
+        let last_output_id = match self.layers.iter().rposition(|layer| self.is_output_layer(layer)) {
+            Some(layer_id) => layer_id,
+            None => return,
+        };
+        if last_output_id + 1 == self.layers.len() {
+            return
+        }
+        for dead_layer in self.layers.split_off(last_output_id + 1) {
+            log_event!(Subsystem::Init, LogLevel::Info, "remove_dead_layer", layer = dead_layer.name);
+        }
+        let layers = &self.layers;
+        self.label_inputs.retain(|&(ref label_name, _)| layers.iter().any(|layer| layer.inputs.contains(label_name)));
+    }
+
+    /// Let layers that support it compute their output in the tensor of their input.
+    ///
+    /// Layers without explicitly configured inputs and outputs already do so when
+    /// the container is created (see [find_in_place_output][1]), so this only affects
+    /// explicitly connected layers. A layer is only converted if its input is not
+    /// used by any other layer and is neither an input nor an output of the container.
+    /// [1]: #method.find_in_place_output
+    fn convert_to_in_place(&mut self) {
+        for n in 0..self.layers.len() {
+            let (input_name, output_name) = {
+                let layer = &self.layers[n];
+                if !layer.layer_type.supports_in_place() || layer.inputs.len() != 1 || layer.outputs.len() != 1 {
+                    continue
+                }
+                (layer.inputs[0].clone(), layer.outputs[0].clone())
+            };
+            if input_name == output_name {
+                continue
+            }
+            let is_container_input = self.inputs.iter().chain(self.label_inputs.iter()).any(|input| input.0 == input_name);
+            let is_used_elsewhere = self.layers.iter().enumerate()
+                .any(|(i, layer)| i != n && layer.inputs.contains(&input_name));
+            let is_produced_by_output = self.layers.iter()
+                .any(|layer| layer.outputs.contains(&input_name) && self.is_output_layer(layer));
+            let is_declared_output = self.outputs.contains(&output_name);
+            if is_container_input || is_used_elsewhere || is_produced_by_output || is_declared_output {
+                continue
+            }
+
+            log_event!(Subsystem::Init, LogLevel::Info, "compute_in_place", layer = self.layers[n].name);
+            for layer in &mut self.layers {
+                for name in layer.inputs.iter_mut().chain(layer.outputs.iter_mut()) {
+                    if *name == output_name {
+                        *name = input_name.clone();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Create the FusedLinear layer that replaces the n-th layer of `layers` and its successor, if they can be fused.
+    fn fused_layer(&self, layers: &[LayerConfig], n: usize) -> Option<LayerConfig> {
+        let (layer, next_layer) = match (layers.get(n), layers.get(n + 1)) {
+            (Some(layer), Some(next_layer)) => (layer, next_layer),
+            _ => return None,
+        };
+        let output_size = match layer.layer_type {
+            LayerType::Linear(ref linear_config) if !linear_config.transpose => linear_config.output_size,
+            _ => return None,
+        };
+        let activation = match FusedActivation::from_layer_type(&next_layer.layer_type) {
+            Some(activation) => activation,
+            None => return None,
+        };
+
+        let is_connected = layer.outputs == next_layer.inputs;
+        let is_output = self.is_output_layer(layer);
+        let is_used_elsewhere = layers.iter().enumerate()
+            .filter(|&(i, _)| i != n + 1)
+            .any(|(_, other_layer)| layer.outputs.iter().any(|output_name| other_layer.inputs.contains(output_name)));
+        if !is_connected || is_output || is_used_elsewhere {
+            return None
+        }
+
+        let mut fused_layer = LayerConfig::new(&layer.name, FusedLinearConfig {
+            output_size: output_size,
+            activation: activation,
+        });
+        fused_layer.inputs = layer.inputs.clone();
+        fused_layer.outputs = next_layer.outputs.clone();
+        fused_layer.params = layer.params.clone();
+        fused_layer.propagate_down = layer.propagate_down.clone();
+        Some(fused_layer)
+    }
+
     /// Write a input into a capnp message.
-    fn write_capnp_shaped_input(&self, builder: &mut capnp_shaped_input::Builder, i: usize) {
-        let input = self.inputs.get(i).unwrap();
+    fn write_capnp_shaped_input(builder: &mut capnp_shaped_input::Builder, input: &(String, Vec<usize>)) {
         let ref name = input.0;
         let ref shape = input.1;
         builder.set_name(name);
@@ -397,6 +1127,24 @@ impl SequentialConfig {
             dimensions.set(i as u32, *dim as u64);
         }
     }
+
+    /// Read a list of inputs from a capnp message.
+    fn read_capnp_shaped_inputs(read_inputs: ::capnp::struct_list::Reader<capnp_shaped_input::Owned>) -> Vec<(String, Vec<usize>)> {
+        let mut inputs = Vec::new();
+        for i in 0..read_inputs.len() {
+            let input = read_inputs.get(i);
+
+            let name = input.get_name().unwrap().to_owned();
+            let mut shape = Vec::new();
+            let read_shape = input.get_shape().unwrap();
+            for j in 0..read_shape.len() {
+                shape.push(read_shape.get(j) as usize)
+            }
+
+            inputs.push((name, shape))
+        }
+        inputs
+    }
 }
 
 impl<'a> CapnpWrite<'a> for SequentialConfig {
@@ -413,12 +1161,26 @@ impl<'a> CapnpWrite<'a> for SequentialConfig {
         }
         {
             let mut inputs = builder.borrow().init_inputs(self.inputs.len() as u32);
-            for (i, _) in self.inputs.iter().enumerate() {
+            for (i, input) in self.inputs.iter().enumerate() {
                 let mut shaped_input = inputs.borrow().get(i as u32);
-                self.write_capnp_shaped_input(&mut shaped_input, i);
+                Self::write_capnp_shaped_input(&mut shaped_input, input);
+            }
+        }
+        {
+            let mut label_inputs = builder.borrow().init_label_inputs(self.label_inputs.len() as u32);
+            for (i, input) in self.label_inputs.iter().enumerate() {
+                let mut shaped_input = label_inputs.borrow().get(i as u32);
+                Self::write_capnp_shaped_input(&mut shaped_input, input);
+            }
+        }
+        {
+            let mut outputs = builder.borrow().init_outputs(self.outputs.len() as u32);
+            for (i, output) in self.outputs.iter().enumerate() {
+                outputs.set(i as u32, output);
             }
         }
         builder.set_force_backward(self.force_backward);
+        builder.set_auto_flatten(self.auto_flatten);
     }
 }
 
@@ -432,26 +1194,23 @@ impl<'a> CapnpRead<'a> for SequentialConfig {
             layers.push(LayerConfig::read_capnp(read_layers.get(i)))
         }
 
-        let read_inputs = reader.get_inputs().unwrap();
-        let mut inputs = Vec::new();
-        for i in 0..read_inputs.len() {
-            let input = read_inputs.get(i);
-
-            let name = input.get_name().unwrap().to_owned();
-            let mut shape = Vec::new();
-            let read_shape = input.get_shape().unwrap();
-            for j in 0..read_shape.len() {
-                shape.push(read_shape.get(j) as usize)
-            }
-
-            inputs.push((name, shape))
+        let inputs = Self::read_capnp_shaped_inputs(reader.get_inputs().unwrap());
+        let label_inputs = Self::read_capnp_shaped_inputs(reader.get_label_inputs().unwrap());
+        let read_outputs = reader.get_outputs().unwrap();
+        let mut outputs = Vec::new();
+        for i in 0..read_outputs.len() {
+            outputs.push(read_outputs.get(i).unwrap().to_owned())
         }
         let force_backward = reader.get_force_backward();
+        let auto_flatten = reader.get_auto_flatten();
 
         SequentialConfig {
             layers: layers,
             inputs: inputs,
+            label_inputs: label_inputs,
+            outputs: outputs,
             force_backward: force_backward,
+            auto_flatten: auto_flatten,
         }
     }
 }
@@ -467,7 +1226,10 @@ impl ::std::default::Default for SequentialConfig {
         SequentialConfig {
             layers: vec![],
             inputs: vec![],
+            label_inputs: vec![],
+            outputs: vec![],
             force_backward: false,
+            auto_flatten: true,
         }
     }
 }
@@ -0,0 +1,110 @@
+//! Computes the [Kullback-Leibler divergence][1] of a predicted from a target distribution.
+//! [1]: https://en.wikipedia.org/wiki/Kullback%E2%80%93Leibler_divergence
+//!
+//! The first input are the logarithmic probabilities of the prediction, e.g. the output
+//! of a [LogSoftmax][2] layer, the second input are the target probabilities.
+//! The loss is the divergence summed over the classes and averaged over the batch.
+//!
+//! Together with the temperature of [Softmax][3] and [LogSoftmax][2] this is used for
+//! knowledge distillation, where the target probabilities are the output of a teacher network.
+//! As the gradient of the loss shrinks with `1 / T²`, the loss is commonly scaled by `T²`.
+//!
+//! The gradient is only propagated to the predicted logarithmic probabilities.
+//!
+//! [2]: ../../common/log_softmax/index.html
+//! [3]: ../../common/softmax/index.html
+use co::{IBackend, ITensorDesc, SharedTensor};
+use layer::*;
+use util::{ArcLock, native_backend};
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// KLDivergence Loss Layer
+pub struct KLDivergence;
+
+impl KLDivergence {
+    fn batch_size(input_shape: &[usize]) -> usize {
+        match input_shape.len() {
+            1 => 1,
+            _ => input_shape[0],
+        }
+    }
+}
+
+impl<B: IBackend> ILayer<B> for KLDivergence {
+    impl_ilayer_loss!();
+
+    fn exact_num_input_blobs(&self) -> Option<usize> { Some(2) }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        let log_probabilities = input_data[0].read().unwrap();
+        let target = input_data[1].read().unwrap();
+        if log_probabilities.desc().size() != target.desc().size() {
+            panic!("The predicted log probabilities {:?} and the target probabilities {:?} of a KLDivergence layer must have the same shape.",
+                   log_probabilities.desc(), target.desc());
+        }
+
+        input_gradient[0].write().unwrap().resize(log_probabilities.desc()).unwrap();
+        output_data[0].write().unwrap().resize(&vec![1]).unwrap();
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for KLDivergence {
+    fn compute_output(&self,
+                      backend: &B,
+                      _weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        let batch_size = Self::batch_size(input_data[0].desc());
+
+        let native = native_backend();
+        let native_log_probabilities = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let native_target = input_data[1].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        // 0 * log(0) is defined as 0
+        let divergence = native_target.iter().zip(native_log_probabilities)
+            .filter(|&(&target, _)| target > 0f32)
+            .fold(0f32, |sum, (&target, &log_probability)| sum + target * (target.ln() - log_probability));
+        let loss = divergence / batch_size as f32;
+
+        ::util::write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &[loss]);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for KLDivergence {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let batch_size = Self::batch_size(input_data[0].desc());
+
+        let native = native_backend();
+        let native_target = input_data[1].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let writable_gradient = native_target.iter().map(|&target| -target / batch_size as f32).collect::<Vec<f32>>();
+
+        input_gradients[0].sync(native.device()).unwrap();
+        ::util::write_to_memory(input_gradients[0].get_mut(native.device()).unwrap(), &writable_gradient);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for KLDivergence { }
+
+impl ::std::default::Default for KLDivergence {
+    fn default() -> KLDivergence {
+        KLDivergence
+    }
+}
@@ -5,7 +5,8 @@
 macro_rules! impl_ilayer_loss {
     () => (
         fn exact_num_output_blobs(&self) -> Option<usize> { Some(1) }
-        fn exact_num_input_blobs(&self) -> Option<usize> { Some(1) }
+        // the prediction and the label or target
+        fn min_input_blobs(&self) -> usize { 2 }
         fn auto_output_blobs(&self) -> bool { true }
 
         fn loss_weight(&self, output_id: usize) -> Option<f32> {
@@ -18,6 +19,8 @@ macro_rules! impl_ilayer_loss {
     )
 }
 
+pub use self::kl_divergence::KLDivergence;
 pub use self::negative_log_likelihood::{NegativeLogLikelihood, NegativeLogLikelihoodConfig};
 
+pub mod kl_divergence;
 pub mod negative_log_likelihood;
@@ -2,22 +2,34 @@
 //!
 use co::{IBackend, ITensorDesc, SharedTensor};
 use layer::*;
-use util::{ArcLock, native_backend};
+use util::{ArcLock, class_ids, native_backend};
 use leaf_capnp::negative_log_likelihood_config as capnp_config;
 use capnp_util::*;
 
 #[derive(Debug, Clone)]
 #[allow(missing_copy_implementations)]
 /// NegativeLogLikelihood Loss Layer
+///
+/// The second input holds the class id of every sample, see [class_ids][2].
+/// [2]: ../../../util/fn.class_ids.html
+///
+/// An optional third input can hold a [mask][1] with one value per label.
+/// Samples with a mask value of `0` don't contribute to the loss or the gradient,
+/// and the loss is averaged over the unmasked samples only.
+/// [1]: ../../index.html#masking
+///
+/// The number of classes is the trailing dimension of the first input.
 pub struct NegativeLogLikelihood {
     num_classes: usize,
+    configured_num_classes: Option<usize>,
 }
 
 impl NegativeLogLikelihood {
     /// Create a NegativeLogLikelihood layer from a NegativeLogLikelihoodConfig.
     pub fn from_config(config: &NegativeLogLikelihoodConfig) -> NegativeLogLikelihood {
         NegativeLogLikelihood {
-            num_classes: config.num_classes,
+            num_classes: config.num_classes.unwrap_or(0),
+            configured_num_classes: config.num_classes,
         }
     }
 
@@ -29,6 +41,15 @@ impl NegativeLogLikelihood {
         input_shape.iter().skip(softmax_axis + 1).fold(1, |prod, i| prod * i)
     }
 
+    /// Convert the labels into class ids and check that they belong to one of the classes.
+    fn class_ids(&self, labels: &[f32]) -> Vec<usize> {
+        let class_ids = class_ids(labels);
+        if let Some(&class_id) = class_ids.iter().find(|&&class_id| class_id >= self.num_classes) {
+            panic!("NegativeLogLikelihood layer got class id {} but only has {} classes", class_id, self.num_classes);
+        }
+        class_ids
+    }
+
     fn batch_size(input_shape: &[usize]) -> usize {
         match input_shape.len() {
             1 => 1,
@@ -56,6 +77,15 @@ impl<B: IBackend> ILayer<B> for NegativeLogLikelihood {
         let data = input_data[0].read().unwrap();
         let label = input_data[1].read().unwrap();
 
+        let num_classes = data.desc().last().cloned().unwrap_or(1);
+        if let Some(configured_num_classes) = self.configured_num_classes {
+            if configured_num_classes != num_classes {
+                panic!("NegativeLogLikelihood layer is configured for {} classes, but its input {:?} has {} classes",
+                       configured_num_classes, data.desc(), num_classes);
+            }
+        }
+        self.num_classes = num_classes;
+
         input_gradient[0].write().unwrap().resize(data.desc()).unwrap();
         output_data[0].write().unwrap().resize(label.desc()).unwrap();
     }
@@ -76,14 +106,23 @@ impl<B: IBackend> ComputeOutput<f32, B> for NegativeLogLikelihood {
         let native_labels = labels.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
         let native_probabilities = probabilities.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
 
+        let native_mask = input_data.get(2).map(|mask| mask.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>());
+
         let mut writable_loss = Vec::<f32>::new();
-        for &label_value in native_labels {
-            let probability_value = native_probabilities[label_value as usize];
-            writable_loss.push(-probability_value);
+        for (batch_n, class_id) in self.class_ids(native_labels).into_iter().enumerate() {
+            let index = (self.num_classes * batch_n) + class_id;
+            let mask_value = native_mask.map_or(1f32, |mask| mask[batch_n]);
+            writable_loss.push(-native_probabilities[index] * mask_value);
         }
 
         let mut loss = writable_loss.iter().fold(0f32, |sum, &val| sum + val);
-        loss = loss / (batch_size as f32);
+        let num_samples = match native_mask {
+            Some(mask) => mask.iter().fold(0f32, |sum, &val| sum + val),
+            None => batch_size as f32,
+        };
+        if num_samples > 0f32 {
+            loss = loss / num_samples;
+        }
         writable_loss = vec![loss];
 
         ::util::write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &writable_loss);
@@ -104,11 +143,12 @@ impl<B: IBackend> ComputeInputGradient<f32, B> for NegativeLogLikelihood {
 
         let native = native_backend();
         let native_labels = labels.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let native_mask = input_data.get(2).map(|mask| mask.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>());
         let mut writable_gradient = vec![0f32; input_gradients[0].desc().size()];
 
-        for (batch_n, &label_value) in native_labels.iter().enumerate() {
-            let index = (num_classes * batch_n) + label_value as usize;
-            writable_gradient[index] = -1f32;
+        for (batch_n, class_id) in self.class_ids(native_labels).into_iter().enumerate() {
+            let index = (num_classes * batch_n) + class_id;
+            writable_gradient[index] = -native_mask.map_or(1f32, |mask| mask[batch_n]);
         }
         input_gradients[0].sync(native.device()).unwrap();
         ::util::write_to_memory(input_gradients[0].get_mut(native.device()).unwrap(), &writable_gradient);
@@ -122,7 +162,18 @@ impl<B: IBackend> ComputeParametersGradient<f32, B> for NegativeLogLikelihood {
 /// Specifies configuration parameters for a NegativeLogLikelihood Layer.
 pub struct NegativeLogLikelihoodConfig {
     /// How many different classes can be classified.
-    pub num_classes: usize,
+    ///
+    /// The number of classes is taken from the trailing dimension of the input.
+    /// If it is set, the layer panics when the input has a different number of classes.
+    pub num_classes: Option<usize>,
+}
+
+impl ::std::default::Default for NegativeLogLikelihoodConfig {
+    fn default() -> NegativeLogLikelihoodConfig {
+        NegativeLogLikelihoodConfig {
+            num_classes: None,
+        }
+    }
 }
 
 impl<'a> CapnpWrite<'a> for NegativeLogLikelihoodConfig {
@@ -130,7 +181,8 @@ impl<'a> CapnpWrite<'a> for NegativeLogLikelihoodConfig {
 
     /// Write the NegativeLogLikelihoodConfig into a capnp message.
     fn write_capnp(&self, builder: &mut Self::Builder) {
-        builder.set_num_classes(self.num_classes as u64);
+        // 0 means that the number of classes is not configured
+        builder.set_num_classes(self.num_classes.unwrap_or(0) as u64);
     }
 }
 
@@ -138,7 +190,10 @@ impl<'a> CapnpRead<'a> for NegativeLogLikelihoodConfig {
     type Reader = capnp_config::Reader<'a>;
 
     fn read_capnp(reader: Self::Reader) -> Self {
-        let num_classes = reader.get_num_classes() as usize;
+        let num_classes = match reader.get_num_classes() {
+            0 => None,
+            num_classes => Some(num_classes as usize),
+        };
 
         NegativeLogLikelihoodConfig {
             num_classes: num_classes
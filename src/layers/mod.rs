@@ -39,6 +39,51 @@
 //! For more information about how these layers work together, see the
 //! documentation for the general [Layer module][3].
 //!
+//! ## Masking
+//!
+//! Sequences of different lengths are trained in batches by padding them to the
+//! same length, e.g. with [pad_sequences][4]. A mask tensor marks which positions hold data:
+//! it has one value for each position of the leading dimensions of the data,
+//! e.g. the shape `[batch_size, max_length]` for data of shape `[batch_size, max_length, features]`,
+//! which is `1` for data and `0` for padding.
+//!
+//! Layers that compute every position independently, like activations, don't need the mask;
+//! the values they compute for padding are simply ignored later on.
+//! Layers that have to ignore the padding take the mask as their last input,
+//! after all other inputs. Inside a [Sequential][5] container the mask can be declared
+//! as a label input, so it is routed to those layers.
+//! Loss layers that accept a mask are [NegativeLogLikelihood][6].
+//!
+//! ## Broadcasting
+//!
+//! Elementwise operations between tensors of different shapes, like scaling each channel
+//! or adding a bias to every sample, follow the broadcasting rules of NumPy:
+//! shapes are aligned at their trailing dimensions and a dimension of `1`, or a missing one,
+//! is repeated to match the other tensor. E.g. a scale of shape `[C, 1, 1]` applies to
+//! every channel of an input of shape `[N, C, H, W]`, without tiling it manually.
+//! The gradient w.r.t. a broadcast tensor sums over the repeated values.
+//! The [util][7] module implements these rules for layers that broadcast their inputs:
+//! [broadcast_shape][8] checks that two shapes are compatible, with an error that names both
+//! shapes, [broadcast_to][9] repeats values to a larger shape and [sum_to_shape][10] sums
+//! a gradient back to the shape of the broadcast tensor.
+//! E.g. [Scale][11] broadcasts its factors and biases to the input this way.
+//!
+//! ## Configuration
+//!
+//! Layers with options are configured through a `*Config` struct, like [LinearConfig][12].
+//! Prefer creating it with its constructor and the builder methods over a struct literal,
+//! so code doesn't break when fields with a default value are added.
+//!
+//! [4]: ../util/fn.pad_sequences.html
+//! [5]: ./container/struct.SequentialConfig.html#structfield.label_inputs
+//! [6]: ./loss/negative_log_likelihood/struct.NegativeLogLikelihood.html
+//! [7]: ../util/index.html
+//! [8]: ../util/fn.broadcast_shape.html
+//! [9]: ../util/fn.broadcast_to.html
+//! [10]: ../util/fn.sum_to_shape.html
+//! [11]: ./common/scale/index.html
+//! [12]: ./common/linear/struct.LinearConfig.html
+//!
 //! [2]: https://en.wikipedia.org/wiki/Activation_function
 //! [3]: ../layer/index.html
 //!
@@ -53,6 +98,8 @@
 /// [2]: ./layers/activation/index.html
 
 pub use self::activation::{
+    Maxout, MaxoutConfig,
+    PReLU, PReLUConfig,
     ReLU,
     Sigmoid,
     TanH,
@@ -65,18 +112,29 @@ pub use self::common::{
 };
 
 pub use self::common::{
+    Constant, ConstantConfig,
+    FusedActivation, FusedLinear, FusedLinearConfig,
+    GroupNorm, GroupNormConfig,
+    InstanceNorm, InstanceNormConfig,
+    LayerNorm, LayerNormConfig,
     Linear, LinearConfig,
-    LogSoftmax,
-    Softmax,
+    LogSoftmax, LogSoftmaxConfig,
+    Noise, NoiseConfig, NoiseMode,
+    Scale, ScaleConfig,
+    Softmax, SoftmaxConfig,
 };
 
 pub use self::loss::{
+    KLDivergence,
     NegativeLogLikelihood, NegativeLogLikelihoodConfig,
 };
 
 pub use self::utility::{
-    Flatten,
+    Accuracy, AccuracyConfig,
+    Flatten, FlattenConfig,
+    Lambda,
     Reshape, ReshapeConfig,
+    Silence,
 };
 
 pub use self::container::{
@@ -88,3 +146,15 @@ pub mod common;
 pub mod loss;
 pub mod utility;
 pub mod container;
+
+/// Returns the batch size, the number of channels and the size of each channel of an input.
+///
+/// If the input has more than one dimension, the first dimension is the batch size and
+/// the second one holds the channels. A one dimensional input is a single sample of channels.
+fn channel_dims(input_shape: &[usize]) -> (usize, usize, usize) {
+    match input_shape.len() {
+        0 => (1, 1, 1),
+        1 => (1, input_shape[0], 1),
+        _ => (input_shape[0], input_shape[1], input_shape[2..].iter().fold(1, |prod, dim| prod * dim)),
+    }
+}
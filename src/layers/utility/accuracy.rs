@@ -0,0 +1,166 @@
+//! Computes the top-k accuracy of a prediction.
+//!
+//! The first input holds the scores of every class for each sample, e.g. the output
+//! of a [Softmax][1] layer, with the batch size as its first dimension.
+//! The second input holds the class id of every sample, see [class_ids][2].
+//!
+//! The output is a tensor with a single value: the fraction of samples whose class
+//! is among the `top_k` classes with the highest scores. A class that has the same
+//! score as the class of the sample doesn't push it out of the top k.
+//!
+//! Like in Caffe this allows to monitor the accuracy as part of the network,
+//! e.g. during the test pass of a [Solver][3], instead of computing it from the outputs.
+//! The layer has no gradient and is only needed for training
+//! (see [LayerType::is_training_only][4]).
+//!
+//! [1]: ../../common/softmax/index.html
+//! [2]: ../../../util/fn.class_ids.html
+//! [3]: ../../../solver/struct.Solver.html
+//! [4]: ../../../layer/enum.LayerType.html#method.is_training_only
+use co::{IBackend, ITensorDesc, SharedTensor};
+use layer::*;
+use util::{ArcLock, class_ids, native_backend, write_to_memory};
+use leaf_capnp::accuracy_config as capnp_config;
+use capnp_util::*;
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// Accuracy Utility Layer
+pub struct Accuracy {
+    top_k: usize,
+}
+
+impl Accuracy {
+    /// Create an Accuracy layer from an AccuracyConfig.
+    pub fn from_config(config: &AccuracyConfig) -> Accuracy {
+        Accuracy {
+            top_k: config.top_k,
+        }
+    }
+
+    fn batch_size(input_shape: &[usize]) -> usize {
+        match input_shape.len() {
+            1 => 1,
+            _ => input_shape[0],
+        }
+    }
+}
+
+impl<B: IBackend> ILayer<B> for Accuracy {
+    fn exact_num_output_blobs(&self) -> Option<usize> { Some(1) }
+    fn exact_num_input_blobs(&self) -> Option<usize> { Some(2) }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn allow_force_backward(&self, input_id: usize) -> bool {
+        false
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        let data = input_data[0].read().unwrap();
+
+        input_gradient[0].write().unwrap().resize(data.desc()).unwrap();
+        output_data[0].write().unwrap().resize(&vec![1]).unwrap();
+        output_gradient[0].write().unwrap().resize(&vec![1]).unwrap();
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for Accuracy {
+    fn compute_output(&self,
+                      backend: &B,
+                      _weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        let scores = input_data[0];
+        let labels = input_data[1];
+
+        let batch_size = Self::batch_size(scores.desc());
+        let num_classes = scores.desc().size() / batch_size;
+
+        let native = native_backend();
+        let native_scores = scores.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let native_labels = labels.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+
+        let mut correct = 0;
+        for (sample, class_id) in native_scores.chunks(num_classes).zip(class_ids(native_labels)) {
+            if class_id >= num_classes {
+                panic!("Accuracy layer got class id {} but only has {} classes", class_id, num_classes);
+            }
+            let rank = sample.iter().filter(|&&score| score > sample[class_id]).count();
+            if rank < self.top_k {
+                correct += 1;
+            }
+        }
+
+        write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &[correct as f32 / batch_size as f32]);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for Accuracy {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        // the accuracy is not differentiable
+        let native = native_backend();
+        let zeros = vec![0f32; input_gradients[0].desc().size()];
+        write_to_memory(input_gradients[0].get_mut(native.device()).unwrap(), &zeros);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for Accuracy { }
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// Specifies configuration parameters for an Accuracy Layer.
+pub struct AccuracyConfig {
+    /// How many of the classes with the highest scores are considered a correct prediction.
+    ///
+    /// Default: `1`
+    pub top_k: usize,
+}
+
+impl ::std::default::Default for AccuracyConfig {
+    fn default() -> AccuracyConfig {
+        AccuracyConfig {
+            top_k: 1,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for AccuracyConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the AccuracyConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_top_k(self.top_k as u64);
+    }
+}
+
+impl<'a> CapnpRead<'a> for AccuracyConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        AccuracyConfig {
+            top_k: reader.get_top_k() as usize,
+        }
+    }
+}
+
+impl Into<LayerType> for AccuracyConfig {
+    fn into(self) -> LayerType {
+        LayerType::Accuracy(self)
+    }
+}
@@ -3,7 +3,178 @@
 //! Input of shape n * c * h * w becomes
 //! a simple vector output of shape n * (c*h*w).
 //!
+//! Which dimensions are flattened is configured with a `start_axis` and an
+//! `end_axis` like the Flatten layer of Caffe, so e.g. the batch and channel
+//! dimensions can be kept while only the spatial dimensions are flattened.
+//!
+//! Like the [Reshape][1] layer, this layer should be used as in-place operation.
+//! With different input and output tensors the data is copied, which leaves
+//! the shape of the input untouched for the layer that produces it.
+//! [1]: ../reshape/index.html
+use co::{IBackend, SharedTensor};
+use coblas::plugin::Copy;
+use layer::*;
+use util::ArcLock;
+use leaf_capnp::flatten_config as capnp_config;
+use capnp_util::*;
+
 #[derive(Debug, Clone)]
 #[allow(missing_copy_implementations)]
 /// Flattening Utility Layer
-pub struct Flatten;
+pub struct Flatten {
+    start_axis: usize,
+    end_axis: Option<usize>,
+}
+
+impl Flatten {
+    /// Create a Flatten layer from a FlattenConfig.
+    pub fn from_config(config: &FlattenConfig) -> Flatten {
+        Flatten {
+            start_axis: config.start_axis,
+            end_axis: config.end_axis,
+        }
+    }
+
+    /// Calculate the output shape for an input of shape `input_shape`.
+    fn calculate_output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        let end_axis = self.end_axis.unwrap_or(input_shape.len().saturating_sub(1));
+        if self.start_axis > end_axis || end_axis >= input_shape.len() {
+            panic!("Flatten layer can't flatten the axes {} to {} of an input of shape {:?}",
+                   self.start_axis, end_axis, input_shape);
+        }
+        let mut shape = input_shape[..self.start_axis].to_vec();
+        shape.push(input_shape[self.start_axis..end_axis + 1].iter().fold(1, |product, dim| product * dim));
+        shape.extend(&input_shape[end_axis + 1..]);
+        shape
+    }
+}
+
+impl<B: IBackend + Copy<f32>> ILayer<B> for Flatten {
+    fn compute_in_place(&self) -> bool {
+        true
+    }
+
+    fn auto_output_blobs(&self) -> bool {
+        false
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        // when computed in-place the output still has the shape of the input
+        let input_shape = match input_data.get(0) {
+            Some(input) => input.read().unwrap().desc().clone(),
+            None => output_data[0].read().unwrap().desc().clone(),
+        };
+        let output_shape = self.calculate_output_shape(&input_shape);
+        output_data[0].write().unwrap().resize(&output_shape).unwrap();
+        output_gradient[0].write().unwrap().resize(&output_shape).unwrap();
+    }
+}
+
+impl<B: IBackend + Copy<f32>> ComputeOutput<f32, B> for Flatten {
+    fn compute_output(&self,
+                      backend: &B,
+                      _weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        if let Some(input) = input_data.get(0) {
+            backend.copy_plain(input, output_data[0]).unwrap();
+        }
+    }
+}
+
+impl<B: IBackend + Copy<f32>> ComputeInputGradient<f32, B> for Flatten {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        if let Some(output_gradient) = output_gradients.get(0) {
+            backend.copy_plain(output_gradient, input_gradients[0]).unwrap();
+        }
+    }
+}
+
+impl<B: IBackend + Copy<f32>> ComputeParametersGradient<f32, B> for Flatten {}
+
+#[derive(Debug, Clone, Copy)]
+/// Specifies configuration parameters for a Flatten Layer.
+pub struct FlattenConfig {
+    /// The first axis that is flattened.
+    ///
+    /// Default: `1`, which keeps the batch dimension
+    pub start_axis: usize,
+    /// The last axis that is flattened, `None` for the last axis of the input.
+    ///
+    /// Default: `None`
+    pub end_axis: Option<usize>,
+}
+
+impl FlattenConfig {
+    /// Create a FlattenConfig that describes a Flatten layer which flattens
+    /// all dimensions but the batch dimension.
+    pub fn new() -> FlattenConfig {
+        FlattenConfig::default()
+    }
+
+    /// Set the first axis that is flattened.
+    pub fn start_axis(mut self, start_axis: usize) -> FlattenConfig {
+        self.start_axis = start_axis;
+        self
+    }
+
+    /// Set the last axis that is flattened.
+    pub fn end_axis(mut self, end_axis: usize) -> FlattenConfig {
+        self.end_axis = Some(end_axis);
+        self
+    }
+}
+
+impl Default for FlattenConfig {
+    fn default() -> FlattenConfig {
+        FlattenConfig {
+            start_axis: 1,
+            end_axis: None,
+        }
+    }
+}
+
+impl<'a> CapnpWrite<'a> for FlattenConfig {
+    type Builder = capnp_config::Builder<'a>;
+
+    /// Write the FlattenConfig into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_start_axis(self.start_axis as u64);
+        builder.set_end_axis(self.end_axis.map(|end_axis| end_axis as i64).unwrap_or(-1));
+    }
+}
+
+impl<'a> CapnpRead<'a> for FlattenConfig {
+    type Reader = capnp_config::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        let end_axis = match reader.get_end_axis() {
+            end_axis if end_axis < 0 => None,
+            end_axis => Some(end_axis as usize),
+        };
+
+        FlattenConfig {
+            start_axis: reader.get_start_axis() as usize,
+            end_axis: end_axis,
+        }
+    }
+}
+
+impl Into<LayerType> for FlattenConfig {
+    fn into(self) -> LayerType {
+        LayerType::Flatten(self)
+    }
+}
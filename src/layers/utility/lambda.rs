@@ -0,0 +1,147 @@
+//! Utility layer that applies user-supplied closures to the data.
+//!
+//! The Lambda layer is meant for quick experiments with custom operations,
+//! without having to implement the full [ILayer][1] machinery.
+//! The closures operate on plain slices of the native memory, so the input
+//! and output tensors are synchronized to the host before each computation.
+//!
+//! As closures can not be serialized, the Lambda layer has no [LayerType][2].
+//! It is created with [Layer::from_worker][3] instead:
+//!
+//! ```
+//! # #[cfg(feature = "native")]
+//! # mod native {
+//! # use std::rc::Rc;
+//! # use leaf::layer::*;
+//! # use leaf::layers::*;
+//! # use leaf::util;
+//! # pub fn test() {
+//! let double = Lambda::new(|input, output| {
+//!     for (o, i) in output.iter_mut().zip(input) { *o = 2f32 * i; }
+//! }).with_gradient(|_input, _output, output_gradient, input_gradient| {
+//!     for (ig, og) in input_gradient.iter_mut().zip(output_gradient) { *ig = 2f32 * og; }
+//! });
+//! let backend = Rc::new(util::native_backend());
+//! // the layer type of the config is not used for layers created from a worker
+//! let _ = Layer::from_worker(backend, &LayerConfig::new("double", LayerType::Sigmoid), Box::new(double));
+//! # }}
+//! #
+//! # #[cfg(not(feature = "native"))]
+//! # mod native {
+//! # pub fn test() {}
+//! # }
+//! #
+//! # fn main() {
+//! #     if cfg!(feature = "native") {
+//! #         ::native::test();
+//! #    }
+//! # }
+//! ```
+//!
+//! [1]: ../../../layer/trait.ILayer.html
+//! [2]: ../../../layer/enum.LayerType.html
+//! [3]: ../../../layer/struct.Layer.html#method.from_worker
+use std::fmt;
+use co::{IBackend, ITensorDesc, SharedTensor};
+use layer::*;
+use util::{ArcLock, native_backend, write_to_memory};
+
+/// Lambda Utility Layer
+pub struct Lambda {
+    forward: Box<Fn(&[f32], &mut [f32])>,
+    backward: Option<Box<Fn(&[f32], &[f32], &[f32], &mut [f32])>>,
+}
+
+impl Lambda {
+    /// Create a Lambda layer that computes its output with `forward`.
+    ///
+    /// `forward` receives the input and the output, which has the same shape as the input.
+    /// Without a [gradient][1] the layer does not propagate gradients to its input.
+    /// [1]: #method.with_gradient
+    pub fn new<F>(forward: F) -> Lambda
+        where F: Fn(&[f32], &mut [f32]) + 'static {
+        Lambda {
+            forward: Box::new(forward),
+            backward: None,
+        }
+    }
+
+    /// Set the closure that computes the input gradient.
+    ///
+    /// `backward` receives the input, the output, the output gradient and the input gradient.
+    pub fn with_gradient<G>(mut self, backward: G) -> Lambda
+        where G: Fn(&[f32], &[f32], &[f32], &mut [f32]) + 'static {
+        self.backward = Some(Box::new(backward));
+        self
+    }
+}
+
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Lambda {{ has_gradient: {} }}", self.backward.is_some())
+    }
+}
+
+impl<B: IBackend> ILayer<B> for Lambda {
+    fn exact_num_output_blobs(&self) -> Option<usize> { Some(1) }
+    fn exact_num_input_blobs(&self) -> Option<usize> { Some(1) }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn reshape(&mut self,
+               backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        if let Some(inp) = input_data.get(0) {
+            let read_inp = inp.read().unwrap();
+            let input_desc = read_inp.desc();
+            input_gradient[0].write().unwrap().resize(input_desc).unwrap();
+            output_data[0].write().unwrap().resize(input_desc).unwrap();
+            output_gradient[0].write().unwrap().resize(input_desc).unwrap();
+        }
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for Lambda {
+    fn compute_output(&self,
+                      backend: &B,
+                      _weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let native_input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let mut output = vec![0f32; output_data[0].desc().size()];
+        (self.forward)(native_input, &mut output);
+
+        write_to_memory(output_data[0].get_mut(native.device()).unwrap(), &output);
+    }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for Lambda {
+    fn compute_input_gradient(&self,
+                              backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        let mut input_gradient = vec![0f32; input_gradients[0].desc().size()];
+        if let Some(ref backward) = self.backward {
+            let native_input = input_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            let native_output = output_data[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            let native_output_gradient = output_gradients[0].get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            backward(native_input, native_output, native_output_gradient, &mut input_gradient);
+        }
+
+        write_to_memory(input_gradients[0].get_mut(native.device()).unwrap(), &input_gradient);
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for Lambda {}
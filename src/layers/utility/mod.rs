@@ -9,8 +9,14 @@
 //! specific data access layers for e.g. a database like LevelDB.
 //!
 //! [1]: ../../layer/index.html
-pub use self::flatten::Flatten;
+pub use self::accuracy::{Accuracy, AccuracyConfig};
+pub use self::flatten::{Flatten, FlattenConfig};
+pub use self::lambda::Lambda;
 pub use self::reshape::{Reshape, ReshapeConfig};
+pub use self::silence::Silence;
 
+pub mod accuracy;
 pub mod flatten;
+pub mod lambda;
 pub mod reshape;
+pub mod silence;
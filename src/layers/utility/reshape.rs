@@ -15,6 +15,12 @@
 //! - `C` : number of feature maps
 //! - `H` : height
 //! - `W` : width
+//!
+//! Like in Caffe, a `0` in the target shape copies the dimension at the same position
+//! from the input. A Reshape layer created with [ReshapeConfig::of_shape_with_batch][1]
+//! starts its shape with a `0`, so it keeps the batch dimension of its input and the
+//! network still works when it is created with another batch size.
+//! [1]: ./struct.ReshapeConfig.html#method.of_shape_with_batch
 use co::{IBackend, SharedTensor};
 use layer::*;
 use util::ArcLock;
@@ -34,6 +40,15 @@ impl Reshape {
             shape: config.shape.clone(),
         }
     }
+
+    /// Calculate the output shape for an input of shape `input_shape`.
+    ///
+    /// A `0` in the target shape is replaced with the dimension of the input at the same position.
+    fn calculate_output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        self.shape.iter().enumerate()
+            .map(|(i, &dim)| if dim == 0 { input_shape.get(i).cloned().unwrap_or(1) } else { dim })
+            .collect()
+    }
 }
 
 impl<B: IBackend> ILayer<B> for Reshape {
@@ -53,8 +68,14 @@ impl<B: IBackend> ILayer<B> for Reshape {
                weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
                output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
                output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
-        output_data[0].write().unwrap().resize(&self.shape).unwrap();
-        output_gradient[0].write().unwrap().resize(&self.shape).unwrap();
+        // when computed in-place the output still has the shape of the input
+        let input_shape = match input_data.get(0) {
+            Some(input) => input.read().unwrap().desc().clone(),
+            None => output_data[0].read().unwrap().desc().clone(),
+        };
+        let output_shape = self.calculate_output_shape(&input_shape);
+        output_data[0].write().unwrap().resize(&output_shape).unwrap();
+        output_gradient[0].write().unwrap().resize(&output_shape).unwrap();
     }
 }
 
@@ -86,15 +107,33 @@ pub struct ReshapeConfig {
     ///
     /// Preceding dimensions are treated as independent inputs
     ///
+    /// A `0` copies the dimension of the input at the same position.
+    ///
     /// Defaults to `1`
     pub shape: Vec<usize>,
 }
 
 impl ReshapeConfig {
     /// Create a ReshapeConfig that describes a Reshape layer with a provided shape.
+    ///
+    /// The shape includes the batch dimension, so the layer only works for one batch size.
     pub fn of_shape(shape: &[usize]) -> ReshapeConfig {
         ReshapeConfig {
-            shape: shape.to_owned()
+            shape: shape.to_owned(),
+        }
+    }
+
+    /// Create a ReshapeConfig that describes a Reshape layer which reshapes every sample
+    /// of its input to `sample_shape`.
+    ///
+    /// The shape is `sample_shape` preceded by a `0`, so the batch dimension is taken from
+    /// the input and the layer works for any batch size, e.g. after [SequentialConfig::set_batch_size][1].
+    /// [1]: ../../container/struct.SequentialConfig.html#method.set_batch_size
+    pub fn of_shape_with_batch(sample_shape: &[usize]) -> ReshapeConfig {
+        let mut shape = vec![0];
+        shape.extend_from_slice(sample_shape);
+        ReshapeConfig {
+            shape: shape,
         }
     }
 }
@@ -104,9 +143,11 @@ impl<'a> CapnpWrite<'a> for ReshapeConfig {
 
     /// Write the ReshapeConfig into a capnp message.
     fn write_capnp(&self, builder: &mut Self::Builder) {
-        let mut shape = builder.borrow().init_shape(self.shape.len() as u32);
-        for (i, dim) in self.shape.iter().enumerate() {
-            shape.set(i as u32, *dim as u64);
+        {
+            let mut shape = builder.borrow().init_shape(self.shape.len() as u32);
+            for (i, dim) in self.shape.iter().enumerate() {
+                shape.set(i as u32, *dim as u64);
+            }
         }
     }
 }
@@ -122,7 +163,7 @@ impl<'a> CapnpRead<'a> for ReshapeConfig {
         }
 
         ReshapeConfig {
-            shape: shape
+            shape: shape,
         }
     }
 }
@@ -0,0 +1,73 @@
+//! Consumes its inputs without producing any output.
+//!
+//! A Silence layer marks blobs as used, e.g. additional outputs of a layer that
+//! are not needed by the rest of the network. The inputs have to be named explicitly.
+//!
+//! Inside a [Sequential][1] container the layer is not connected to the previous and
+//! next layer like other layers, so the layer after it uses the outputs of the layer
+//! before it, and it is never the output layer of the container.
+//!
+//! No gradient is propagated to the inputs.
+//!
+//! [1]: ../../container/struct.Sequential.html
+use co::{IBackend, ITensorDesc, SharedTensor};
+use layer::*;
+use util::{ArcLock, native_backend, write_to_memory};
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// Silence Utility Layer
+pub struct Silence;
+
+impl<B: IBackend> ILayer<B> for Silence {
+    fn exact_num_output_blobs(&self) -> Option<usize> { Some(0) }
+    fn min_input_blobs(&self) -> usize { 1 }
+
+    fn sync_native(&self) -> bool {
+        true
+    }
+
+    fn allow_force_backward(&self, _input_id: usize) -> bool {
+        false
+    }
+
+    fn reshape(&mut self,
+               _backend: ::std::rc::Rc<B>,
+               input_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               input_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               weights_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_data: &mut Vec<ArcLock<SharedTensor<f32>>>,
+               output_gradient: &mut Vec<ArcLock<SharedTensor<f32>>>) {
+        for (data, gradient) in input_data.iter().zip(input_gradient.iter()) {
+            let shape = data.read().unwrap().desc().clone();
+            gradient.write().unwrap().resize(&shape).unwrap();
+        }
+    }
+}
+
+impl<B: IBackend> ComputeOutput<f32, B> for Silence {
+    fn compute_output(&self,
+                      _backend: &B,
+                      _weights: &[&SharedTensor<f32>],
+                      input_data: &[&SharedTensor<f32>],
+                      output_data: &mut [&mut SharedTensor<f32>]) { }
+}
+
+impl<B: IBackend> ComputeInputGradient<f32, B> for Silence {
+    fn compute_input_gradient(&self,
+                              _backend: &B,
+                              weights_data: &[&SharedTensor<f32>],
+                              output_data: &[&SharedTensor<f32>],
+                              output_gradients: &[&SharedTensor<f32>],
+                              input_data: &[&SharedTensor<f32>],
+                              input_gradients: &mut [&mut SharedTensor<f32>]) {
+        let native = native_backend();
+        for input_gradient in input_gradients {
+            let zeros = vec![0f32; input_gradient.desc().size()];
+            write_to_memory(input_gradient.get_mut(native.device()).unwrap(), &zeros);
+        }
+    }
+}
+
+impl<B: IBackend> ComputeParametersGradient<f32, B> for Silence { }
@@ -113,15 +113,34 @@ extern crate timeit;
 extern crate log;
 extern crate rand;
 extern crate num;
+extern crate rayon;
 extern crate capnp;
 extern crate collenchyma as co;
 extern crate collenchyma_blas as coblas;
 extern crate collenchyma_nn as conn;
+#[cfg(feature="native")]
+extern crate rblas;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+#[macro_use]
+pub mod logging;
+pub mod analysis;
+pub mod bench;
 pub mod layer;
+pub mod csv;
+pub mod data;
+pub mod ensemble;
+pub mod image;
+pub mod inference;
 pub mod layers;
+pub mod mnist;
+pub mod models;
+pub mod npy;
+pub mod rng;
 pub mod solver;
 pub mod solvers;
 pub mod weight;
+pub mod zoo;
 
 pub mod util;
 mod capnp_util;
@@ -0,0 +1,129 @@
+//! Provides structured log events for the subsystems of Leaf.
+//!
+//! Leaf logs through the [log][1] crate, so any logger implementation like `env_logger` can be used.
+//! Every event is logged with the subsystem that emits it as its target:
+//! `leaf::init` for the construction of layers, `leaf::forward` and `leaf::backward` for the
+//! computations of layers and `leaf::solver` for training.
+//! The message of an event consists of `key=value` fields, starting with the type of the event,
+//! e.g. `event=forward layer=conv1 shapes=[128,32,27,27] duration_ms=1.23400`.
+//! Values that contain whitespace are quoted.
+//! [1]: https://doc.rust-lang.org/log/log/index.html
+//!
+//! Besides the filter of the logger, the verbosity of each subsystem can be changed at runtime
+//! with [set_log_level][2].
+//! [2]: ./fn.set_log_level.html
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use log::{LogLevel, LogLevelFilter};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// The parts of Leaf that emit log events.
+pub enum Subsystem {
+    /// The construction and connection of layers.
+    Init,
+    /// The forward computations of layers.
+    Forward,
+    /// The backward computations of layers.
+    Backward,
+    /// The training of a network by a Solver.
+    Solver,
+}
+
+impl Subsystem {
+    /// Returns the log target of the events of the subsystem.
+    pub fn target(&self) -> &'static str {
+        match *self {
+            Subsystem::Init => "leaf::init",
+            Subsystem::Forward => "leaf::forward",
+            Subsystem::Backward => "leaf::backward",
+            Subsystem::Solver => "leaf::solver",
+        }
+    }
+
+    fn level(&self) -> &'static AtomicUsize {
+        match *self {
+            Subsystem::Init => &INIT_LEVEL,
+            Subsystem::Forward => &FORWARD_LEVEL,
+            Subsystem::Backward => &BACKWARD_LEVEL,
+            Subsystem::Solver => &SOLVER_LEVEL,
+        }
+    }
+}
+
+static INIT_LEVEL: AtomicUsize = AtomicUsize::new(LogLevelFilter::Trace as usize);
+static FORWARD_LEVEL: AtomicUsize = AtomicUsize::new(LogLevelFilter::Trace as usize);
+static BACKWARD_LEVEL: AtomicUsize = AtomicUsize::new(LogLevelFilter::Trace as usize);
+static SOLVER_LEVEL: AtomicUsize = AtomicUsize::new(LogLevelFilter::Trace as usize);
+
+/// Set the most verbose level of the events a subsystem emits.
+///
+/// By default all events are passed to the logger, which applies its own filter.
+/// E.g. `set_log_level(Subsystem::Forward, LogLevelFilter::Off)` silences the timing of each forward step.
+pub fn set_log_level(subsystem: Subsystem, level: LogLevelFilter) {
+    subsystem.level().store(level as usize, Ordering::SeqCst);
+}
+
+/// Returns the most verbose level of the events a subsystem emits.
+///
+/// See [set_log_level][1].
+/// [1]: ./fn.set_log_level.html
+pub fn log_level(subsystem: Subsystem) -> LogLevelFilter {
+    match subsystem.level().load(Ordering::SeqCst) {
+        0 => LogLevelFilter::Off,
+        1 => LogLevelFilter::Error,
+        2 => LogLevelFilter::Warn,
+        3 => LogLevelFilter::Info,
+        4 => LogLevelFilter::Debug,
+        _ => LogLevelFilter::Trace,
+    }
+}
+
+/// Returns `true` if an event of `level` emitted by `subsystem` would be logged.
+pub fn event_enabled(subsystem: Subsystem, level: LogLevel) -> bool {
+    level <= log_level(subsystem) && log_enabled!(target: subsystem.target(), level)
+}
+
+/// Log an event of `subsystem` with the `fields` of the event.
+///
+/// The [log_event!][1] macro only formats the fields if the event is enabled.
+/// [1]: ../macro.log_event!.html
+pub fn log_event(subsystem: Subsystem, level: LogLevel, event: &str, fields: &[(&str, String)]) {
+    if !event_enabled(subsystem, level) {
+        return
+    }
+    let mut message = format!("event={}", event);
+    for &(key, ref value) in fields {
+        if value.is_empty() || value.chars().any(|c| c.is_whitespace() || c == '"') {
+            message.push_str(&format!(" {}={:?}", key, value));
+        } else {
+            message.push_str(&format!(" {}={}", key, value));
+        }
+    }
+    log!(target: subsystem.target(), level, "{}", message);
+}
+
+#[derive(Debug, Copy, Clone)]
+/// Formats a tensor shape as a log field value without whitespace, e.g. `[1,28,28]`.
+pub struct Shape<'a>(pub &'a [usize]);
+
+impl<'a> fmt::Display for Shape<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dims = self.0.iter().map(|dim| dim.to_string()).collect::<Vec<_>>();
+        write!(f, "[{}]", dims.join(","))
+    }
+}
+
+#[macro_export]
+/// Log a structured event, e.g. `log_event!(Subsystem::Init, LogLevel::Info, "connect", layer = name, input = blob)`.
+///
+/// See [log_event][1].
+/// [1]: ./logging/fn.log_event.html
+macro_rules! log_event {
+    ($subsystem:expr, $level:expr, $event:expr $(, $key:ident = $value:expr)*) => {{
+        let subsystem = $subsystem;
+        let level = $level;
+        if $crate::logging::event_enabled(subsystem, level) {
+            $crate::logging::log_event(subsystem, level, $event, &[$((stringify!($key), format!("{}", $value))),*]);
+        }
+    }}
+}
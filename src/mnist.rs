@@ -0,0 +1,94 @@
+//! Provides loading of the [MNIST][1] dataset of handwritten digits.
+//! [1]: http://yann.lecun.com/exdb/mnist/
+//!
+//! MNIST is distributed as pairs of files in the IDX format, one with the images
+//! and one with the labels. [load_mnist][2] reads such a pair into a [Dataset][3]
+//! that can be used with [Solver::fit][4].
+//! [2]: ./fn.load_mnist.html
+//! [3]: ../solver/dataset/struct.Dataset.html
+//! [4]: ../solver/struct.Solver.html#method.fit
+//!
+//! The files have to be decompressed first, gzipped files are not supported.
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use solver::Dataset;
+
+/// The magic number of IDX files with unsigned byte data.
+///
+/// The last byte is the number of dimensions and is checked separately.
+const IDX_UBYTE_MAGIC: u32 = 0x0000_0800;
+
+/// Load the MNIST images and labels from the IDX files at the specified paths.
+///
+/// See [read_mnist][1].
+/// [1]: ./fn.read_mnist.html
+pub fn load_mnist<P: AsRef<Path>, Q: AsRef<Path>>(images_path: P, labels_path: Q) -> io::Result<Dataset> {
+    let images = try!(File::open(images_path.as_ref()));
+    let labels = try!(File::open(labels_path.as_ref()));
+    read_mnist(&mut BufReader::new(images), &mut BufReader::new(labels))
+}
+
+/// Read MNIST images and labels in the IDX format from `images` and `labels`.
+///
+/// Every image becomes a sample whose input are the `rows * columns` pixels scaled
+/// from `0..255` to `0..1` and whose target is the digit as class id, as expected
+/// by [NegativeLogLikelihood][1].
+/// [1]: ../layers/loss/struct.NegativeLogLikelihood.html
+///
+/// Returns an error if the images are not three-dimensional, the labels are not
+/// one-dimensional or the number of images and labels differ.
+pub fn read_mnist<R: Read, S: Read>(images: &mut R, labels: &mut S) -> io::Result<Dataset> {
+    let (image_shape, pixels) = try!(read_idx(images));
+    if image_shape.len() != 3 {
+        return Err(invalid_data(&format!("Expected images with 3 dimensions, got {}", image_shape.len())));
+    }
+    let (label_shape, labels) = try!(read_idx(labels));
+    if label_shape.len() != 1 {
+        return Err(invalid_data(&format!("Expected labels with 1 dimension, got {}", label_shape.len())));
+    }
+    if image_shape[0] != label_shape[0] {
+        return Err(invalid_data(&format!("Got {} images but {} labels", image_shape[0], label_shape[0])));
+    }
+
+    let mut dataset = Dataset::new(&[]);
+    let image_size = image_shape[1] * image_shape[2];
+    if image_size > 0 {
+        for (image, &label) in pixels.chunks(image_size).zip(labels.iter()) {
+            let input = image.iter().map(|&pixel| pixel as f32 / 255f32).collect();
+            dataset.add_sample(input, vec![label as f32]);
+        }
+    }
+    Ok(dataset)
+}
+
+/// Read an IDX file with unsigned byte data from `reader`.
+///
+/// Returns the shape and the data of the stored array.
+pub fn read_idx<R: Read>(reader: &mut R) -> io::Result<(Vec<usize>, Vec<u8>)> {
+    let magic = try!(read_u32(reader));
+    if magic & 0xFFFF_FF00 != IDX_UBYTE_MAGIC {
+        return Err(invalid_data(&format!("Not an IDX file with unsigned byte data (magic number {:#010x})", magic)));
+    }
+    let num_dimensions = (magic & 0xFF) as usize;
+    let mut shape = Vec::with_capacity(num_dimensions);
+    for _ in 0..num_dimensions {
+        shape.push(try!(read_u32(reader)) as usize);
+    }
+
+    let size = shape.iter().fold(1, |size, &dim| size * dim);
+    let mut data = vec![0u8; size];
+    try!(reader.read_exact(&mut data));
+    Ok((shape, data))
+}
+
+/// Read a big-endian `u32`, as used for all integers in the IDX format.
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    try!(reader.read_exact(&mut bytes));
+    Ok(bytes.iter().fold(0u32, |value, &byte| (value << 8) | byte as u32))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,206 @@
+//! Provides the configurations of common network architectures.
+//!
+//! The functions return a [SequentialConfig][1] that can be used as the network of a
+//! [SolverConfig][2] or changed further before a Layer is created from it.
+//! Classifiers end with a [LogSoftmax][3] layer, so they are trained with a
+//! [NegativeLogLikelihood][4] objective on the class ids.
+//! [1]: ../layers/container/struct.SequentialConfig.html
+//! [2]: ../solver/struct.SolverConfig.html
+//! [3]: ../layers/common/struct.LogSoftmax.html
+//! [4]: ../layers/loss/struct.NegativeLogLikelihood.html
+//!
+//! The input of the networks is named `data` and its first dimension is the batch size.
+//!
+//! The convolutional networks [lenet][5], [alexnet][6], [overfeat][7] and [vgg_a][8] are
+//! only available with the CUDA backend, like the Convolution and Pooling layers.
+//! Every Convolution and hidden Linear layer is followed by a ReLU named `<layer>/relu`.
+//! [5]: ./fn.lenet.html
+//! [6]: ./fn.alexnet.html
+//! [7]: ./fn.overfeat.html
+//! [8]: ./fn.vgg_a.html
+//!
+//! An [autoencoder][9] learns a compressed representation of its inputs without labels.
+//! [9]: ./fn.autoencoder.html
+use layer::{LayerConfig, LayerType};
+use layers::{LinearConfig, LogSoftmaxConfig, SequentialConfig};
+use weight::WeightConfig;
+#[cfg(all(feature="cuda", not(feature="native")))]
+use layers::{ConvolutionConfig, PoolingConfig, PoolingMode};
+
+/// Create a logistic regression classifier for inputs of `input_dim` values and `classes` classes.
+///
+/// The network consists of a single Linear layer `linear1` followed by a LogSoftmax.
+pub fn logistic_regression(batch_size: usize, input_dim: usize, classes: usize) -> SequentialConfig {
+    mlp(batch_size, &[input_dim, classes])
+}
+
+/// Create a multilayer perceptron classifier with the layer sizes `sizes`.
+///
+/// The first size is the number of input values and the last one the number of classes;
+/// every size in between adds a hidden Linear layer with a ReLU activation.
+/// The Linear layers are named `linear1`, `linear2` and so on.
+///
+/// Panics if there are less than two sizes.
+pub fn mlp(batch_size: usize, sizes: &[usize]) -> SequentialConfig {
+    if sizes.len() < 2 {
+        panic!("A multilayer perceptron needs at least an input and an output size, got {:?}", sizes);
+    }
+    let mut net_cfg = SequentialConfig::default();
+    net_cfg.add_input("data", &[batch_size, sizes[0]]);
+    let num_linear = sizes.len() - 1;
+    for (i, &output_size) in sizes[1..].iter().enumerate() {
+        let name = format!("linear{}", i + 1);
+        net_cfg.add_layer(LayerConfig::new(&name, LayerType::Linear(LinearConfig::new(output_size))));
+        if i + 1 < num_linear {
+            net_cfg.add_layer(LayerConfig::new(&format!("{}/relu", name), LayerType::ReLU));
+        }
+    }
+    net_cfg.add_layer(LayerConfig::new("log_softmax", LogSoftmaxConfig::default()));
+    net_cfg
+}
+
+/// Create an autoencoder that compresses inputs of `sizes[0]` values to `sizes[sizes.len() - 1]` values
+/// and reconstructs them.
+///
+/// The encoder consists of the Linear layers `encoder1`, `encoder2`, ... with the output sizes
+/// `sizes[1..]`. The decoder mirrors it with the Linear layers `decoder{n}`, ..., `decoder1`,
+/// where `decoder{i}` reverses `encoder{i}`. Every Linear layer is followed by a Sigmoid named
+/// `<layer>/sigmoid`, so the inputs are expected to be in the range `[0, 1]`.
+/// The code is the output of `encoder{n}/sigmoid`, which can be declared as an additional output
+/// with [SequentialConfig::add_output][1].
+/// [1]: ../layers/container/struct.SequentialConfig.html#method.add_output
+///
+/// With `tied_weights` every decoder layer uses the [transposed][2] weight of its encoder layer
+/// instead of its own, which halves the number of weights. The shared weights are named
+/// `encoder{i}/weight` and are updated with the gradients of both layers.
+/// [2]: ../layers/common/linear/struct.LinearConfig.html#structfield.transpose
+///
+/// Panics if there are less than two sizes.
+pub fn autoencoder(batch_size: usize, sizes: &[usize], tied_weights: bool) -> SequentialConfig {
+    if sizes.len() < 2 {
+        panic!("An autoencoder needs at least an input and a code size, got {:?}", sizes);
+    }
+    let mut net_cfg = SequentialConfig::default();
+    net_cfg.add_input("data", &[batch_size, sizes[0]]);
+    let num_layers = sizes.len() - 1;
+    let layers = (1..num_layers + 1).map(|i| (i, false, sizes[i]))
+        .chain((1..num_layers + 1).rev().map(|i| (i, true, sizes[i - 1])));
+    for (i, is_decoder, output_size) in layers {
+        let name = if is_decoder { format!("decoder{}", i) } else { format!("encoder{}", i) };
+        let transpose = is_decoder && tied_weights;
+        let mut layer_cfg = LayerConfig::new(&name, LayerType::Linear(LinearConfig::new(output_size).transpose(transpose)));
+        if tied_weights {
+            layer_cfg.params.push(WeightConfig { name: format!("encoder{}/weight", i), ..WeightConfig::default() });
+        }
+        net_cfg.add_layer(layer_cfg);
+        net_cfg.add_layer(LayerConfig::new(&format!("{}/sigmoid", name), LayerType::Sigmoid));
+    }
+    net_cfg
+}
+
+/// Create the LeNet classifier for 28x28 grayscale images like MNIST with 10 classes.
+///
+/// See [LeCun et al., 1998](http://yann.lecun.com/exdb/publis/pdf/lecun-01a.pdf).
+#[cfg(all(feature="cuda", not(feature="native")))]
+pub fn lenet(batch_size: usize) -> SequentialConfig {
+    let mut net_cfg = SequentialConfig::default();
+    net_cfg.add_input("data", &[batch_size, 1, 28, 28]);
+    add_convolution(&mut net_cfg, "conv1", 20, 5, 0, 1);
+    add_max_pooling(&mut net_cfg, "pool1", 2, 2);
+    add_convolution(&mut net_cfg, "conv2", 50, 5, 0, 1);
+    add_max_pooling(&mut net_cfg, "pool2", 2, 2);
+    add_classifier(&mut net_cfg, &[500, 10]);
+    net_cfg
+}
+
+/// Create the AlexNet classifier for 224x224 RGB images with 1000 classes.
+///
+/// This is the single-GPU variant without local response normalization, as described in
+/// [Krizhevsky, 2014](https://arxiv.org/abs/1404.5997).
+#[cfg(all(feature="cuda", not(feature="native")))]
+pub fn alexnet(batch_size: usize) -> SequentialConfig {
+    let mut net_cfg = SequentialConfig::default();
+    net_cfg.add_input("data", &[batch_size, 3, 224, 224]);
+    add_convolution(&mut net_cfg, "conv1", 64, 11, 2, 4);
+    add_max_pooling(&mut net_cfg, "pool1", 3, 2);
+    add_convolution(&mut net_cfg, "conv2", 192, 5, 2, 1);
+    add_max_pooling(&mut net_cfg, "pool2", 3, 2);
+    add_convolution(&mut net_cfg, "conv3", 384, 3, 1, 1);
+    add_convolution(&mut net_cfg, "conv4", 256, 3, 1, 1);
+    add_convolution(&mut net_cfg, "conv5", 256, 3, 1, 1);
+    add_max_pooling(&mut net_cfg, "pool3", 3, 2);
+    add_classifier(&mut net_cfg, &[4096, 4096, 1000]);
+    net_cfg
+}
+
+/// Create the fast OverFeat classifier for 231x231 RGB images with 1000 classes.
+///
+/// See [Sermanet et al., 2013](https://arxiv.org/abs/1312.6229).
+#[cfg(all(feature="cuda", not(feature="native")))]
+pub fn overfeat(batch_size: usize) -> SequentialConfig {
+    let mut net_cfg = SequentialConfig::default();
+    net_cfg.add_input("data", &[batch_size, 3, 231, 231]);
+    add_convolution(&mut net_cfg, "conv1", 96, 11, 0, 4);
+    add_max_pooling(&mut net_cfg, "pool1", 2, 2);
+    add_convolution(&mut net_cfg, "conv2", 256, 5, 0, 1);
+    add_max_pooling(&mut net_cfg, "pool2", 2, 2);
+    add_convolution(&mut net_cfg, "conv3", 512, 3, 1, 1);
+    add_convolution(&mut net_cfg, "conv4", 1024, 3, 1, 1);
+    add_convolution(&mut net_cfg, "conv5", 1024, 3, 1, 1);
+    add_max_pooling(&mut net_cfg, "pool5", 2, 2);
+    add_classifier(&mut net_cfg, &[3072, 4096, 1000]);
+    net_cfg
+}
+
+/// Create the VGG-A classifier (VGG with 11 weight layers) for 224x224 RGB images with 1000 classes.
+///
+/// See [Simonyan and Zisserman, 2014](https://arxiv.org/abs/1409.1556).
+#[cfg(all(feature="cuda", not(feature="native")))]
+pub fn vgg_a(batch_size: usize) -> SequentialConfig {
+    let mut net_cfg = SequentialConfig::default();
+    net_cfg.add_input("data", &[batch_size, 3, 224, 224]);
+    add_convolution(&mut net_cfg, "conv1", 64, 3, 1, 1);
+    add_max_pooling(&mut net_cfg, "pool1", 2, 2);
+    add_convolution(&mut net_cfg, "conv2", 128, 3, 1, 1);
+    add_max_pooling(&mut net_cfg, "pool2", 2, 2);
+    add_convolution(&mut net_cfg, "conv3", 256, 3, 1, 1);
+    add_convolution(&mut net_cfg, "conv4", 256, 3, 1, 1);
+    add_max_pooling(&mut net_cfg, "pool3", 2, 2);
+    add_convolution(&mut net_cfg, "conv5", 512, 3, 1, 1);
+    add_convolution(&mut net_cfg, "conv6", 512, 3, 1, 1);
+    add_max_pooling(&mut net_cfg, "pool4", 2, 2);
+    add_convolution(&mut net_cfg, "conv7", 512, 3, 1, 1);
+    add_convolution(&mut net_cfg, "conv8", 512, 3, 1, 1);
+    add_max_pooling(&mut net_cfg, "pool5", 2, 2);
+    add_classifier(&mut net_cfg, &[4096, 4096, 1000]);
+    net_cfg
+}
+
+/// Add a Convolution layer with square filters followed by a ReLU.
+#[cfg(all(feature="cuda", not(feature="native")))]
+fn add_convolution(net_cfg: &mut SequentialConfig, name: &str, num_output: usize, filter_size: usize, padding: usize, stride: usize) {
+    let conv_cfg = ConvolutionConfig::new(num_output, &[filter_size]).stride(&[stride]).padding(&[padding]);
+    net_cfg.add_layer(LayerConfig::new(name, conv_cfg));
+    net_cfg.add_layer(LayerConfig::new(&format!("{}/relu", name), LayerType::ReLU));
+}
+
+/// Add a max Pooling layer with square filters.
+#[cfg(all(feature="cuda", not(feature="native")))]
+fn add_max_pooling(net_cfg: &mut SequentialConfig, name: &str, filter_size: usize, stride: usize) {
+    let pool_cfg = PoolingConfig::new(PoolingMode::Max, &[filter_size]).stride(&[stride]);
+    net_cfg.add_layer(LayerConfig::new(name, pool_cfg));
+}
+
+/// Add the fully connected layers `fc1`, `fc2`, ... with the output sizes `sizes`,
+/// with a ReLU after every hidden layer and a LogSoftmax at the end.
+#[cfg(all(feature="cuda", not(feature="native")))]
+fn add_classifier(net_cfg: &mut SequentialConfig, sizes: &[usize]) {
+    for (i, &output_size) in sizes.iter().enumerate() {
+        let name = format!("fc{}", i + 1);
+        net_cfg.add_layer(LayerConfig::new(&name, LayerType::Linear(LinearConfig::new(output_size))));
+        if i + 1 < sizes.len() {
+            net_cfg.add_layer(LayerConfig::new(&format!("{}/relu", name), LayerType::ReLU));
+        }
+    }
+    net_cfg.add_layer(LayerConfig::new("log_softmax", LogSoftmaxConfig::default()));
+}
@@ -0,0 +1,348 @@
+//! Provides loading and saving of tensors in the [NumPy][1] `.npy` and `.npz` formats.
+//! [1]: http://docs.scipy.org/doc/numpy/neps/npy-format.html
+//!
+//! Arrays of floating point, integer and boolean types are converted to `f32` when
+//! loading. Tensors are always saved as little-endian `f32` arrays (`'<f4'`).
+//! Arrays in Fortran order are not supported.
+//!
+//! `.npz` archives as written by `numpy.savez` can be loaded and saved.
+//! Archives that are written with `numpy.savez_compressed` are not supported.
+//!
+//! All loaded tensors are allocated on a Native Backend.
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use co::SharedTensor;
+use util::{crc32, native_backend, write_to_memory};
+
+const MAGIC: &'static [u8] = b"\x93NUMPY";
+
+/// Load a tensor from a `.npy` file at the specified path.
+pub fn load_npy<P: AsRef<Path>>(path: P) -> io::Result<SharedTensor<f32>> {
+    let file = try!(File::open(path.as_ref()));
+    read_npy(&mut BufReader::new(file))
+}
+
+/// Save a tensor to a `.npy` file at the specified path.
+///
+/// The tensor is synced to a Native Backend to read it.
+pub fn save_npy<P: AsRef<Path>>(path: P, tensor: &mut SharedTensor<f32>) -> io::Result<()> {
+    let file = try!(File::create(path.as_ref()));
+    let mut writer = BufWriter::new(file);
+    try!(write_npy(&mut writer, tensor));
+    writer.flush()
+}
+
+/// Read a tensor in the `.npy` format from `reader`.
+pub fn read_npy<R: Read>(reader: &mut R) -> io::Result<SharedTensor<f32>> {
+    let mut magic = [0u8; 6];
+    try!(reader.read_exact(&mut magic));
+    if magic != MAGIC {
+        return Err(invalid_data("Not a .npy file"));
+    }
+    let mut version = [0u8; 2];
+    try!(reader.read_exact(&mut version));
+    let header_len = match version[0] {
+        1 => {
+            let mut len = [0u8; 2];
+            try!(reader.read_exact(&mut len));
+            read_le(&len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            try!(reader.read_exact(&mut len));
+            read_le(&len) as usize
+        }
+        _ => return Err(invalid_data(&format!("Unsupported .npy version {}.{}", version[0], version[1]))),
+    };
+    let mut header = vec![0u8; header_len];
+    try!(reader.read_exact(&mut header));
+    let header = try!(String::from_utf8(header).map_err(|_| invalid_data("The .npy header is not valid text")));
+    let header = try!(Header::parse(&header));
+
+    let size = header.shape.iter().fold(1, |size, &dim| size * dim);
+    let mut bytes = vec![0u8; size * header.dtype.size];
+    try!(reader.read_exact(&mut bytes));
+    let data = bytes.chunks(header.dtype.size).map(|value| header.dtype.to_f32(value)).collect::<Vec<f32>>();
+
+    // collenchyma tensors need at least one dimension
+    let shape = if header.shape.is_empty() { vec![1] } else { header.shape };
+    let native = native_backend();
+    let mut tensor = SharedTensor::<f32>::new(native.device(), &shape).unwrap();
+    write_to_memory(tensor.get_mut(native.device()).unwrap(), &data);
+    Ok(tensor)
+}
+
+/// Write a tensor in the `.npy` format to `writer`.
+///
+/// The tensor is synced to a Native Backend to read it.
+pub fn write_npy<W: Write>(writer: &mut W, tensor: &mut SharedTensor<f32>) -> io::Result<()> {
+    let native = native_backend();
+    let _ = tensor.add_device(native.device());
+    try!(tensor.sync(native.device()).map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e))));
+
+    let dims = tensor.desc().iter().map(|dim| dim.to_string()).collect::<Vec<_>>();
+    let shape = match dims.len() {
+        1 => format!("({},)", dims[0]),
+        _ => format!("({})", dims.join(", ")),
+    };
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}", shape);
+    // the header is padded with spaces and a newline so the data is 64 byte aligned
+    let unpadded_len = MAGIC.len() + 2 + 2 + header.len() + 1;
+    let padding = (64 - unpadded_len % 64) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    try!(writer.write_all(MAGIC));
+    try!(writer.write_all(&[1, 0]));
+    try!(writer.write_all(&write_le(header.len() as u64, 2)));
+    try!(writer.write_all(header.as_bytes()));
+    let data = tensor.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+    for value in data {
+        try!(writer.write_all(&write_le(value.to_bits() as u64, 4)));
+    }
+    Ok(())
+}
+
+/// Load all tensors from a `.npz` file at the specified path.
+///
+/// Returns the tensors with their names, in the order they are stored in the archive.
+/// The `.npy` extension of the names is removed.
+pub fn load_npz<P: AsRef<Path>>(path: P) -> io::Result<Vec<(String, SharedTensor<f32>)>> {
+    let mut file = BufReader::new(try!(File::open(path.as_ref())));
+    let mut tensors = Vec::new();
+    loop {
+        let mut signature = [0u8; 4];
+        try!(file.read_exact(&mut signature));
+        if read_le(&signature) != 0x04034b50 {
+            // all local files have been read when the central directory begins
+            break;
+        }
+        let mut header = [0u8; 26];
+        try!(file.read_exact(&mut header));
+        let flags = read_le(&header[2..4]);
+        let compression = read_le(&header[4..6]);
+        let mut size = read_le(&header[14..18]);
+        let name_len = read_le(&header[22..24]) as usize;
+        let extra_len = read_le(&header[24..26]) as usize;
+        if compression != 0 {
+            return Err(invalid_data("Compressed .npz files are not supported"));
+        }
+        if flags & 0x08 != 0 {
+            return Err(invalid_data("The .npz file does not specify the size of its entries"));
+        }
+
+        let mut name = vec![0u8; name_len];
+        try!(file.read_exact(&mut name));
+        let name = try!(String::from_utf8(name).map_err(|_| invalid_data("Invalid name in .npz file")));
+        let mut extra = vec![0u8; extra_len];
+        try!(file.read_exact(&mut extra));
+        if size == 0xFFFFFFFF {
+            size = try!(zip64_size(&extra));
+        }
+
+        let mut entry = file.by_ref().take(size);
+        let tensor = try!(read_npy(&mut entry));
+        // skip anything after the array data
+        try!(io::copy(&mut entry, &mut io::sink()));
+        tensors.push((name.trim_end_matches(".npy").to_owned(), tensor));
+    }
+    Ok(tensors)
+}
+
+/// Save tensors to a `.npz` file at the specified path.
+///
+/// Each tensor is stored under its name, like the keyword arguments of `numpy.savez`.
+/// The tensors are synced to a Native Backend to read them.
+pub fn save_npz<P: AsRef<Path>>(path: P, tensors: &mut [(&str, &mut SharedTensor<f32>)]) -> io::Result<()> {
+    let mut writer = BufWriter::new(try!(File::create(path.as_ref())));
+    let mut central_directory = Vec::new();
+    let mut offset = 0;
+    for &mut (name, ref mut tensor) in tensors.iter_mut() {
+        let name = format!("{}.npy", name);
+        let mut data = Vec::new();
+        try!(write_npy(&mut data, tensor));
+        let crc = crc32(0, &data);
+
+        // fields shared by the local file header and the central directory:
+        // version needed, flags, compression, time, date, crc, compressed size, size, name length
+        let mut fields = Vec::new();
+        fields.extend(write_le(20, 2));
+        fields.extend(write_le(0, 2));
+        fields.extend(write_le(0, 2));
+        fields.extend(write_le(0, 2));
+        fields.extend(write_le(0x21, 2));
+        fields.extend(write_le(crc as u64, 4));
+        fields.extend(write_le(data.len() as u64, 4));
+        fields.extend(write_le(data.len() as u64, 4));
+        fields.extend(write_le(name.len() as u64, 2));
+
+        try!(writer.write_all(&write_le(0x04034b50, 4)));
+        try!(writer.write_all(&fields));
+        try!(writer.write_all(&write_le(0, 2)));
+        try!(writer.write_all(name.as_bytes()));
+        try!(writer.write_all(&data));
+
+        central_directory.extend(write_le(0x02014b50, 4));
+        central_directory.extend(write_le(20, 2));
+        central_directory.extend(fields);
+        // extra length, comment length, disk number, internal and external attributes
+        central_directory.extend(write_le(0, 2));
+        central_directory.extend(write_le(0, 2));
+        central_directory.extend(write_le(0, 2));
+        central_directory.extend(write_le(0, 2));
+        central_directory.extend(write_le(0, 4));
+        central_directory.extend(write_le(offset as u64, 4));
+        central_directory.extend(name.as_bytes());
+
+        offset += 30 + name.len() + data.len();
+    }
+    try!(writer.write_all(&central_directory));
+
+    // end of central directory record
+    try!(writer.write_all(&write_le(0x06054b50, 4)));
+    try!(writer.write_all(&write_le(0, 2)));
+    try!(writer.write_all(&write_le(0, 2)));
+    try!(writer.write_all(&write_le(tensors.len() as u64, 2)));
+    try!(writer.write_all(&write_le(tensors.len() as u64, 2)));
+    try!(writer.write_all(&write_le(central_directory.len() as u64, 4)));
+    try!(writer.write_all(&write_le(offset as u64, 4)));
+    try!(writer.write_all(&write_le(0, 2)));
+    writer.flush()
+}
+
+#[derive(Debug)]
+/// The parsed header of a `.npy` file.
+struct Header {
+    dtype: DType,
+    shape: Vec<usize>,
+}
+
+impl Header {
+    /// Parse the header, which is the text of a Python dictionary literal.
+    fn parse(header: &str) -> io::Result<Header> {
+        let descr = try!(Self::value(header, "descr"));
+        let descr = match descr.chars().next() {
+            Some(quote) if quote == '\'' || quote == '"' => descr[1..].split(quote).next().unwrap(),
+            _ => return Err(invalid_data("Invalid data type in .npy header")),
+        };
+        let dtype = try!(DType::from_descr(descr));
+
+        let fortran_order = try!(Self::value(header, "fortran_order"));
+        if fortran_order.starts_with("True") {
+            return Err(invalid_data("Arrays in Fortran order are not supported"));
+        }
+
+        let shape = try!(Self::value(header, "shape"));
+        let shape = match shape.find(')') {
+            Some(end) => &shape[1..end],
+            None => return Err(invalid_data("Invalid shape in .npy header")),
+        };
+        let mut dims = Vec::new();
+        for dim in shape.split(',').map(|dim| dim.trim()).filter(|dim| !dim.is_empty()) {
+            let dim = try!(dim.trim_end_matches('L').parse::<usize>().map_err(|_| invalid_data("Invalid shape in .npy header")));
+            dims.push(dim);
+        }
+
+        Ok(Header { dtype: dtype, shape: dims })
+    }
+
+    /// Return the text following the key `key`.
+    fn value<'a>(header: &'a str, key: &str) -> io::Result<&'a str> {
+        let key_pos = try!(header.find(&format!("'{}'", key)).ok_or(invalid_data(&format!("Missing '{}' in .npy header", key))));
+        let rest = &header[key_pos + key.len() + 2..];
+        let colon = try!(rest.find(':').ok_or(invalid_data("Invalid .npy header")));
+        Ok(rest[colon + 1..].trim_start())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Kind {
+    Float,
+    Int,
+    UInt,
+    Bool,
+}
+
+#[derive(Debug, Copy, Clone)]
+/// The data type of a `.npy` array.
+struct DType {
+    kind: Kind,
+    size: usize,
+    big_endian: bool,
+}
+
+impl DType {
+    /// Create a DType from an array-protocol type string, e.g. `'<f4'`.
+    fn from_descr(descr: &str) -> io::Result<DType> {
+        let unsupported = invalid_data(&format!("Unsupported data type '{}' in .npy file", descr));
+        let mut chars = descr.chars();
+        let big_endian = match chars.next() {
+            Some('<') | Some('|') | Some('=') => false,
+            Some('>') => true,
+            _ => return Err(unsupported),
+        };
+        let kind = match chars.next() {
+            Some('f') => Kind::Float,
+            Some('i') => Kind::Int,
+            Some('u') => Kind::UInt,
+            Some('b') => Kind::Bool,
+            _ => return Err(unsupported),
+        };
+        let size = match chars.as_str().parse::<usize>() {
+            Ok(size) => size,
+            Err(_) => return Err(unsupported),
+        };
+        match (kind, size) {
+            (Kind::Float, 4) | (Kind::Float, 8) |
+            (Kind::Int, 1) | (Kind::Int, 2) | (Kind::Int, 4) | (Kind::Int, 8) |
+            (Kind::UInt, 1) | (Kind::UInt, 2) | (Kind::UInt, 4) | (Kind::UInt, 8) |
+            (Kind::Bool, 1) => Ok(DType { kind: kind, size: size, big_endian: big_endian }),
+            _ => Err(unsupported),
+        }
+    }
+
+    /// Convert the bytes of a single value to f32.
+    fn to_f32(&self, bytes: &[u8]) -> f32 {
+        let bits = if self.big_endian {
+            bytes.iter().fold(0u64, |bits, &byte| (bits << 8) | byte as u64)
+        } else {
+            read_le(bytes)
+        };
+        match (self.kind, self.size) {
+            (Kind::Float, 4) => f32::from_bits(bits as u32),
+            (Kind::Float, 8) => f64::from_bits(bits) as f32,
+            (Kind::Int, 1) => bits as u8 as i8 as f32,
+            (Kind::Int, 2) => bits as u16 as i16 as f32,
+            (Kind::Int, 4) => bits as u32 as i32 as f32,
+            (Kind::Int, _) => bits as i64 as f32,
+            _ => bits as f32,
+        }
+    }
+}
+
+/// Read the uncompressed size from the zip64 extended information of a zip entry.
+fn zip64_size(extra: &[u8]) -> io::Result<u64> {
+    let mut i = 0;
+    while i + 4 <= extra.len() {
+        let id = read_le(&extra[i..i + 2]);
+        let len = read_le(&extra[i + 2..i + 4]) as usize;
+        if id == 0x0001 && len >= 8 && i + 12 <= extra.len() {
+            return Ok(read_le(&extra[i + 4..i + 12]));
+        }
+        i += 4 + len;
+    }
+    Err(invalid_data("Missing size of .npz entry"))
+}
+
+fn read_le(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0u64, |value, &byte| (value << 8) | byte as u64)
+}
+
+fn write_le(value: u64, num_bytes: usize) -> Vec<u8> {
+    (0..num_bytes).map(|i| (value >> (8 * i)) as u8).collect()
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
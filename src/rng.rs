@@ -0,0 +1,94 @@
+//! Provides reproducible random number streams for stochastic layers.
+//!
+//! Every stochastic layer draws its random numbers from its own [RngStream][1], which
+//! is derived from the global seed and the name of the layer. The numbers of a stream
+//! are computed from a counter instead of a shared state, so the results of a layer
+//! only depend on the seed, its name and how many numbers it has drawn before,
+//! regardless of the order in which layers are executed or the thread they run on.
+//! [1]: ./struct.RngStream.html
+//!
+//! The global seed is set with [set_seed][2]; it defaults to `0`.
+//! [2]: ./fn.set_seed.html
+use std::sync::atomic::{AtomicUsize, Ordering};
+use rand::Rng;
+
+// the seed is stored in two halves of 32 bits, because usize can be narrower than u64
+static SEED_HIGH: AtomicUsize = AtomicUsize::new(0);
+static SEED_LOW: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the global seed that the random number streams of layers are derived from.
+///
+/// Only streams created afterwards, i.e. by layers that are created afterwards, use the new seed.
+pub fn set_seed(seed: u64) {
+    SEED_HIGH.store((seed >> 32) as usize, Ordering::SeqCst);
+    SEED_LOW.store((seed & 0xffffffff) as usize, Ordering::SeqCst);
+}
+
+/// Returns the global seed that the random number streams of layers are derived from.
+pub fn seed() -> u64 {
+    ((SEED_HIGH.load(Ordering::SeqCst) as u64) << 32) | SEED_LOW.load(Ordering::SeqCst) as u64
+}
+
+#[derive(Debug, Clone)]
+#[allow(missing_copy_implementations)]
+/// A counter-based stream of random numbers.
+///
+/// The `n`th number of the stream is a hash of the key of the stream and `n`,
+/// using the finalizer of [SplitMix64][1]. Streams with different keys are independent.
+/// [1]: http://xoroshiro.di.unimi.it/splitmix64.c
+pub struct RngStream {
+    key: u64,
+    counter: u64,
+}
+
+impl RngStream {
+    /// Create the stream with the name `name` for the seed `seed`.
+    pub fn new(seed: u64, name: &str) -> RngStream {
+        // FNV-1a hash of the name
+        let name_hash = name.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        RngStream {
+            key: mix(seed ^ mix(name_hash)),
+            counter: 0,
+        }
+    }
+
+    /// Create the stream of the layer `layer_name` for the global seed.
+    ///
+    /// See [set_seed][1].
+    /// [1]: ./fn.set_seed.html
+    pub fn for_layer(layer_name: &str) -> RngStream {
+        Self::new(seed(), layer_name)
+    }
+
+    /// Returns the number of random numbers that have been drawn from the stream.
+    pub fn counter(&self) -> u64 {
+        self.counter
+    }
+
+    /// Set the position in the stream, e.g. to repeat the numbers drawn since then.
+    pub fn set_counter(&mut self, counter: u64) {
+        self.counter = counter;
+    }
+}
+
+impl Rng for RngStream {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let value = mix(self.key.wrapping_add(self.counter.wrapping_mul(0x9e3779b97f4a7c15)));
+        self.counter = self.counter.wrapping_add(1);
+        value
+    }
+}
+
+/// The finalizer of SplitMix64.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,94 @@
+//! Provides the interface for sources of minibatches that a [Solver][1] can be trained on.
+//! [1]: ../struct.Solver.html
+//!
+//! Unlike an [IDataset][2], a batcher does not need to know all its samples in advance,
+//! so minibatches can be drawn from any source, e.g. a file that is read on the fly or a
+//! simulation. An [IteratorBatcher][3] assembles minibatches from any iterator over samples.
+//! See [Solver::train_batches][4].
+//! [2]: ../dataset/trait.IDataset.html
+//! [3]: ./struct.IteratorBatcher.html
+//! [4]: ../struct.Solver.html#method.train_batches
+use std::sync::{Arc, RwLock};
+use co::SharedTensor;
+use util::{ArcLock, native_backend, tensor_from_slice};
+
+/// A source of minibatches of inputs and targets.
+pub trait IBatcher {
+    /// Return the next minibatch, or `None` if there are no more samples.
+    ///
+    /// The first dimension of the inputs and the targets is the batch size.
+    /// Returns an error if the minibatch can not be assembled, e.g. from samples of the wrong size.
+    fn next_batch(&mut self) -> Option<Result<(ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>), String>>;
+}
+
+impl ::std::fmt::Debug for IBatcher {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "({})", "IBatcher")
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Assembles the samples of an iterator over pairs of input and target into minibatches.
+///
+/// Samples that do not fill a complete minibatch at the end of the iterator are skipped,
+/// like in [Solver::fit][1].
+/// [1]: ../struct.Solver.html#method.fit
+pub struct IteratorBatcher<I> {
+    samples: I,
+    batch_size: usize,
+    input_shape: Vec<usize>,
+    target_shape: Vec<usize>,
+}
+
+impl<I: Iterator<Item=(Vec<f32>, Vec<f32>)>> IteratorBatcher<I> {
+    /// Create an IteratorBatcher for minibatches of `batch_size` samples.
+    ///
+    /// `input_shape` and `target_shape` are the shapes of a single sample, without the batch dimension.
+    /// Returns an error if `batch_size` is `0`.
+    pub fn new<T: IntoIterator<IntoIter=I, Item=(Vec<f32>, Vec<f32>)>>(samples: T, batch_size: usize, input_shape: &[usize], target_shape: &[usize]) -> Result<IteratorBatcher<I>, String> {
+        if batch_size == 0 {
+            return Err("The batch size of an IteratorBatcher has to be at least 1".to_owned());
+        }
+        Ok(IteratorBatcher {
+            samples: samples.into_iter(),
+            batch_size: batch_size,
+            input_shape: input_shape.to_owned(),
+            target_shape: target_shape.to_owned(),
+        })
+    }
+}
+
+impl<I: Iterator<Item=(Vec<f32>, Vec<f32>)>> IBatcher for IteratorBatcher<I> {
+    /// Returns an error if the size of an input or a target doesn't match its shape.
+    fn next_batch(&mut self) -> Option<Result<(ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>), String>> {
+        let mut inputs = Vec::new();
+        let mut targets = Vec::new();
+        for _ in 0..self.batch_size {
+            let (input, target) = match self.samples.next() {
+                Some(sample) => sample,
+                None => return None,
+            };
+            inputs.extend(input);
+            targets.extend(target);
+        }
+
+        let native = native_backend();
+        let mut batch_input_shape = vec![self.batch_size];
+        batch_input_shape.extend(&self.input_shape);
+        let mut batch_target_shape = vec![self.batch_size];
+        batch_target_shape.extend(&self.target_shape);
+        let batch = tensor_from_slice(&native, &batch_input_shape, &inputs).and_then(|inputs| {
+            let targets = try!(tensor_from_slice(&native, &batch_target_shape, &targets));
+            Ok((Arc::new(RwLock::new(inputs)), Arc::new(RwLock::new(targets))))
+        });
+        Some(batch)
+    }
+}
+
+impl<I: Iterator<Item=(Vec<f32>, Vec<f32>)>> Iterator for IteratorBatcher<I> {
+    type Item = Result<(ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_batch()
+    }
+}
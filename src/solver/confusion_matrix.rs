@@ -1,9 +1,16 @@
 //! TODO: DOC
 use std::collections::VecDeque;
 use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
 
 use co::SharedTensor;
-use util::native_backend;
+use util::{class_ids, native_backend};
+use leaf_capnp::confusion_matrix as capnp_confusion_matrix;
+use leaf_capnp::confusion_matrix::capacity as capnp_capacity;
+use leaf_capnp::sample as capnp_sample;
+use capnp_util::*;
 /// A [ConfusionMatrix][wiki].
 ///
 /// [wiki]: https://en.wikipedia.org/wiki/Confusion_matrix
@@ -48,7 +55,9 @@ impl ConfusionMatrix {
     /// The prediction for each sample of the batch is found by
     /// determining which output value had the smallest loss.
     pub fn get_predictions(&self, network_out: &mut SharedTensor<f32>) -> Vec<usize> {
-        let native_infered = network_out.get(native_backend().device()).unwrap().as_native().unwrap();
+        let native = native_backend();
+        match network_out.add_device(native.device()) { _ => network_out.sync(native.device()).unwrap() }
+        let native_infered = network_out.get(native.device()).unwrap().as_native().unwrap();
         let predictions_slice = native_infered.as_slice::<f32>();
 
         let mut predictions = Vec::<usize>::new();
@@ -60,10 +69,38 @@ impl ConfusionMatrix {
         predictions
     }
 
+    /// Get the target classes from a tensor of labels.
+    ///
+    /// The tensor is synced to a Native Backend to read it.
+    /// See [class_ids][1] for how labels are converted.
+    /// [1]: ../../util/fn.class_ids.html
+    pub fn get_targets(&self, labels: &mut SharedTensor<f32>) -> Vec<usize> {
+        let native = native_backend();
+        match labels.add_device(native.device()) { _ => labels.sync(native.device()).unwrap() }
+        class_ids(labels.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>())
+    }
+
     /// Set the `capacity` of the ConfusionMatrix
+    ///
+    /// If more samples than `capacity` are held, the oldest ones are removed.
     pub fn set_capacity(&mut self, capacity: Option<usize>) {
         self.capacity = capacity;
-        // TODO: truncate if over capacity
+        if let Some(capacity) = capacity {
+            while self.samples.len() > capacity {
+                self.samples.pop_front();
+            }
+        }
+    }
+
+    /// Returns the maximum number of samples held, see [set_capacity][1].
+    /// [1]: #method.set_capacity
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Remove all collected samples.
+    pub fn clear(&mut self) {
+        self.samples.clear();
     }
 
     /// Return all collected samples.
@@ -77,6 +114,166 @@ impl ConfusionMatrix {
         let num_correct = self.samples.iter().filter(|&&s| s.correct()).count();
         Accuracy { num_samples: num_samples, num_correct: num_correct }
     }
+
+    /// Return the number of classes the ConfusionMatrix analyzes.
+    pub fn num_classes(&self) -> usize {
+        self.num_classes
+    }
+
+    /// Add all samples of `other` to the ConfusionMatrix.
+    ///
+    /// Can be used to combine the ConfusionMatrices of multiple evaluation shards.
+    /// Panics if `other` analyzes a different number of classes.
+    pub fn merge(&mut self, other: &ConfusionMatrix) {
+        if self.num_classes != other.num_classes {
+            panic!("Can not merge ConfusionMatrix of {} classes with one of {} classes.", other.num_classes, self.num_classes);
+        }
+        for sample in &other.samples {
+            self.add_sample(sample.prediction, sample.target);
+        }
+    }
+
+    /// Return the number of samples for each combination of target and predicted class.
+    ///
+    /// The outer index is the target class, the inner index the predicted class.
+    /// Returns an error if a sample has a target or prediction that is not one of the classes.
+    pub fn counts(&self) -> Result<Vec<Vec<usize>>, String> {
+        let mut counts = vec![vec![0; self.num_classes]; self.num_classes];
+        for sample in &self.samples {
+            if sample.target >= self.num_classes || sample.prediction >= self.num_classes {
+                return Err(format!("The sample ({}) is outside of the {} classes of the ConfusionMatrix", sample, self.num_classes));
+            }
+            counts[sample.target][sample.prediction] += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Return the accuracy of the predictions for the class `class`.
+    ///
+    /// That is the ratio of samples that were correctly predicted to belong or not
+    /// belong to the class. Returns `None` if there are no samples.
+    pub fn class_accuracy(&self, class: usize) -> Option<f32> {
+        let num_correct = self.samples.iter().filter(|s| (s.prediction == class) == (s.target == class)).count();
+        Self::ratio(num_correct, self.samples.len())
+    }
+
+    /// Return the [precision][1] of the predictions for the class `class`.
+    /// [1]: https://en.wikipedia.org/wiki/Precision_and_recall
+    ///
+    /// Returns `None` if the class was never predicted.
+    pub fn precision(&self, class: usize) -> Option<f32> {
+        let predicted = self.samples.iter().filter(|s| s.prediction == class);
+        let (num_predicted, num_correct) = predicted.fold((0, 0), |(n, c), s| (n + 1, c + s.correct() as usize));
+        Self::ratio(num_correct, num_predicted)
+    }
+
+    /// Return the [recall][1] of the predictions for the class `class`.
+    /// [1]: https://en.wikipedia.org/wiki/Precision_and_recall
+    ///
+    /// Returns `None` if there are no samples of the class.
+    pub fn recall(&self, class: usize) -> Option<f32> {
+        let relevant = self.samples.iter().filter(|s| s.target == class);
+        let (num_relevant, num_correct) = relevant.fold((0, 0), |(n, c), s| (n + 1, c + s.correct() as usize));
+        Self::ratio(num_correct, num_relevant)
+    }
+
+    fn ratio(part: usize, total: usize) -> Option<f32> {
+        match total {
+            0 => None,
+            _ => Some(part as f32 / total as f32),
+        }
+    }
+
+    /// Serialize the ConfusionMatrix and its samples to a Cap'n Proto file at the specified path.
+    ///
+    /// You can find the capnp schema [here](../../../../capnp/leaf.capnp).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let ref mut out = try!(File::create(path));
+
+        let mut message = ::capnp::message::Builder::new_default();
+        {
+            let mut confusion_matrix = message.init_root::<capnp_confusion_matrix::Builder>();
+            self.write_capnp(&mut confusion_matrix);
+        }
+        try!(::capnp::serialize_packed::write_message(out, &message));
+
+        Ok(())
+    }
+
+    /// Read a Cap'n Proto file at the specified path and deserialize the ConfusionMatrix inside it.
+    ///
+    /// You can find the capnp schema [here](../../../../capnp/leaf.capnp).
+    ///
+    /// Returns an error of kind `InvalidData` if the file does not contain a valid ConfusionMatrix.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ConfusionMatrix> {
+        let path = path.as_ref();
+        let ref mut file = try!(File::open(path));
+        let mut reader = BufReader::new(file);
+
+        let message_reader = try!(::capnp::serialize_packed::read_message(&mut reader, ::capnp::message::ReaderOptions::new())
+                                  .map_err(|e| invalid_data(&format!("Can not read the ConfusionMatrix: {}", e))));
+        let read_confusion_matrix = try!(message_reader.get_root::<capnp_confusion_matrix::Reader>()
+                                         .map_err(|e| invalid_data(&format!("Can not read the ConfusionMatrix: {}", e))));
+        if read_confusion_matrix.get_capacity().which().is_err() || read_confusion_matrix.get_samples().is_err() {
+            return Err(invalid_data("The ConfusionMatrix is incomplete"));
+        }
+
+        let confusion_matrix = ConfusionMatrix::read_capnp(read_confusion_matrix);
+        try!(confusion_matrix.counts().map_err(|e| invalid_data(&e)));
+        Ok(confusion_matrix)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<'a> CapnpWrite<'a> for ConfusionMatrix {
+    type Builder = capnp_confusion_matrix::Builder<'a>;
+
+    /// Write the ConfusionMatrix into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_num_classes(self.num_classes as u64);
+        {
+            let mut capacity = builder.borrow().init_capacity();
+            match self.capacity {
+                Some(capacity_value) => capacity.set_limited(capacity_value as u64),
+                None => capacity.set_unlimited(()),
+            }
+        }
+        {
+            let mut samples = builder.borrow().init_samples(self.samples.len() as u32);
+            for (i, sample) in self.samples.iter().enumerate() {
+                let mut capnp_sample = samples.borrow().get(i as u32);
+                sample.write_capnp(&mut capnp_sample);
+            }
+        }
+    }
+}
+
+impl<'a> CapnpRead<'a> for ConfusionMatrix {
+    type Reader = capnp_confusion_matrix::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        let num_classes = reader.get_num_classes() as usize;
+        let capacity = match reader.get_capacity().which().unwrap() {
+            capnp_capacity::Which::Unlimited(_) => None,
+            capnp_capacity::Which::Limited(capacity) => Some(capacity as usize),
+        };
+
+        let read_samples = reader.get_samples().unwrap();
+        let mut samples = VecDeque::new();
+        for i in 0..read_samples.len() {
+            samples.push_back(Sample::read_capnp(read_samples.get(i)));
+        }
+
+        ConfusionMatrix {
+            num_classes: num_classes,
+            capacity: capacity,
+            samples: samples,
+        }
+    }
 }
 
 /// A single prediction Sample.
@@ -93,6 +290,27 @@ impl Sample {
     }
 }
 
+impl<'a> CapnpWrite<'a> for Sample {
+    type Builder = capnp_sample::Builder<'a>;
+
+    /// Write the Sample into a capnp message.
+    fn write_capnp(&self, builder: &mut Self::Builder) {
+        builder.set_prediction(self.prediction as u64);
+        builder.set_target(self.target as u64);
+    }
+}
+
+impl<'a> CapnpRead<'a> for Sample {
+    type Reader = capnp_sample::Reader<'a>;
+
+    fn read_capnp(reader: Self::Reader) -> Self {
+        Sample {
+            prediction: reader.get_prediction() as usize,
+            target: reader.get_target() as usize,
+        }
+    }
+}
+
 impl fmt::Display for Sample {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Prediction: {:?}, Target: {:?}", self.prediction, self.target)
@@ -109,6 +327,26 @@ pub struct Accuracy {
 }
 
 impl Accuracy {
+    /// Returns the number of samples.
+    pub fn num_samples(&self) -> usize {
+        self.num_samples
+    }
+
+    /// Returns the number of correctly predicted samples.
+    pub fn num_correct(&self) -> usize {
+        self.num_correct
+    }
+
+    /// Returns the percentage of correctly predicted samples.
+    ///
+    /// Returns `None` if there are no samples.
+    pub fn percent(&self) -> Option<f32> {
+        match self.num_samples {
+            0 => None,
+            _ => Some(self.ratio()),
+        }
+    }
+
     fn ratio(&self) -> f32 {
         (self.num_correct as f32) / (self.num_samples as f32) * 100f32
     }
@@ -0,0 +1,223 @@
+//! Provides the interface for datasets that a [Solver][1] can be trained on.
+//! [1]: ../struct.Solver.html
+//!
+//! A dataset is a collection of samples, where each sample consists of the input
+//! for the network and the target that the objective compares the network output with.
+//! The Solver takes care of assembling the samples into minibatches,
+//! see [Solver::fit][2].
+//! [2]: ../struct.Solver.html#method.fit
+//!
+//! For a validation loop the samples can be split with [stratified_split][3].
+//! [3]: ./fn.stratified_split.html
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use rand::Rng;
+use co::SharedTensor;
+use rng::RngStream;
+use util::{ArcLock, class_ids, native_backend, write_batch_sample};
+use super::IBatcher;
+
+/// A collection of samples that can be used for training.
+pub trait IDataset {
+    /// Return the number of samples in the dataset.
+    fn len(&self) -> usize;
+
+    /// Return the shape of a single target, without the batch dimension.
+    ///
+    /// For class labels as used by [NegativeLogLikelihood][1] this is empty.
+    /// [1]: ../../layers/loss/struct.NegativeLogLikelihood.html
+    fn target_shape(&self) -> Vec<usize>;
+
+    /// Return the input and the target of the sample at `index`.
+    fn sample(&self, index: usize) -> (Vec<f32>, Vec<f32>);
+
+    /// Assemble the samples at `indices` into a minibatch of inputs and targets.
+    ///
+    /// `input_shape` is the shape of a single input, without the batch dimension.
+    /// The returned tensors are allocated on a Native Backend and can be passed to
+    /// [Solver::train_minibatch][1].
+    /// [1]: ../struct.Solver.html#method.train_minibatch
+    fn minibatch(&self, indices: &[usize], input_shape: &[usize]) -> (ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>) {
+        let native = native_backend();
+        let mut batch_input_shape = vec![indices.len()];
+        batch_input_shape.extend(input_shape);
+        let mut batch_target_shape = vec![indices.len()];
+        batch_target_shape.extend(self.target_shape());
+
+        let mut inputs = SharedTensor::<f32>::new(native.device(), &batch_input_shape).unwrap();
+        let mut targets = SharedTensor::<f32>::new(native.device(), &batch_target_shape).unwrap();
+        for (i, &index) in indices.iter().enumerate() {
+            let (input, target) = self.sample(index);
+            write_batch_sample(&mut inputs, &input, i);
+            write_batch_sample(&mut targets, &target, i);
+        }
+        (Arc::new(RwLock::new(inputs)), Arc::new(RwLock::new(targets)))
+    }
+}
+
+impl<'a> ::std::fmt::Debug for IDataset + 'a {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "({})", "IDataset")
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A dataset that holds all its samples in memory.
+pub struct Dataset {
+    inputs: Vec<Vec<f32>>,
+    targets: Vec<Vec<f32>>,
+    target_shape: Vec<usize>,
+}
+
+impl Dataset {
+    /// Create an empty Dataset whose targets have the shape `target_shape`.
+    pub fn new(target_shape: &[usize]) -> Dataset {
+        Dataset {
+            inputs: Vec::new(),
+            targets: Vec::new(),
+            target_shape: target_shape.to_owned(),
+        }
+    }
+
+    /// Add a sample to the Dataset.
+    pub fn add_sample(&mut self, input: Vec<f32>, target: Vec<f32>) {
+        self.inputs.push(input);
+        self.targets.push(target);
+    }
+}
+
+impl IDataset for Dataset {
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn target_shape(&self) -> Vec<usize> {
+        self.target_shape.clone()
+    }
+
+    fn sample(&self, index: usize) -> (Vec<f32>, Vec<f32>) {
+        (self.inputs[index].clone(), self.targets[index].clone())
+    }
+}
+
+#[derive(Debug)]
+/// Assembles minibatches from a subset of the samples of an [IDataset][1].
+/// [1]: ./trait.IDataset.html
+///
+/// The samples are used in the order of their indices; samples that do not fill a complete
+/// minibatch at the end are skipped, like in [Solver::fit][2], unless [pad_last_batch][4] is set.
+/// Call [reset][3] to start another pass over the samples.
+/// [2]: ../struct.Solver.html#method.fit
+/// [3]: #method.reset
+/// [4]: #method.pad_last_batch
+pub struct DatasetBatcher<'a> {
+    dataset: &'a IDataset,
+    indices: Vec<usize>,
+    position: usize,
+    batch_size: usize,
+    input_shape: Vec<usize>,
+    pad_last_batch: bool,
+}
+
+impl<'a> DatasetBatcher<'a> {
+    /// Create a DatasetBatcher for minibatches of `batch_size` samples of `dataset` at `indices`.
+    ///
+    /// `input_shape` is the shape of a single input, without the batch dimension.
+    pub fn new(dataset: &'a IDataset, indices: Vec<usize>, batch_size: usize, input_shape: &[usize]) -> DatasetBatcher<'a> {
+        DatasetBatcher {
+            dataset: dataset,
+            indices: indices,
+            position: 0,
+            batch_size: batch_size,
+            input_shape: input_shape.to_owned(),
+            pad_last_batch: false,
+        }
+    }
+
+    /// Set if the samples at the end that do not fill a complete minibatch are used.
+    ///
+    /// The last minibatch is then filled up with the first samples again, as the network
+    /// expects complete minibatches. Those samples are used twice, e.g. they count twice
+    /// in a validation loss, but no sample is left out.
+    pub fn pad_last_batch(mut self, pad_last_batch: bool) -> Self {
+        self.pad_last_batch = pad_last_batch;
+        self
+    }
+
+    /// Return the indices of the samples in the order they are used.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// Start again with the first minibatch.
+    pub fn reset(&mut self) {
+        self.position = 0;
+    }
+}
+
+impl<'a> IBatcher for DatasetBatcher<'a> {
+    fn next_batch(&mut self) -> Option<Result<(ArcLock<SharedTensor<f32>>, ArcLock<SharedTensor<f32>>), String>> {
+        let num_remaining = self.indices.len().saturating_sub(self.position);
+        if self.batch_size == 0 || num_remaining == 0 || (num_remaining < self.batch_size && !self.pad_last_batch) {
+            return None
+        }
+        let minibatch = self.indices.iter().cycle().skip(self.position).take(self.batch_size).cloned().collect::<Vec<_>>();
+        self.position += self.batch_size;
+        Some(Ok(self.dataset.minibatch(&minibatch, &self.input_shape)))
+    }
+}
+
+/// Split `dataset` into minibatches for training and for validation, with the same share
+/// of every class in both parts.
+///
+/// About `validation_fraction` of the samples of every class are used for validation.
+/// The class of a sample is its target, or the index of the largest value for one-hot targets.
+/// The split only depends on `seed` (see [rng][1]), so it can be reproduced; the training
+/// samples are shuffled so that the classes are mixed in the minibatches.
+/// The last validation minibatch is [padded][2], so every validation sample is used.
+/// [1]: ../../rng/index.html
+/// [2]: ./struct.DatasetBatcher.html#method.pad_last_batch
+///
+/// `input_shape` is the shape of a single input, without the batch dimension.
+/// Returns an error if `validation_fraction` is not in `[0, 1]` or a sample has no target.
+pub fn stratified_split<'a>(dataset: &'a IDataset, validation_fraction: f32, seed: u64, batch_size: usize, input_shape: &[usize])
+                            -> Result<(DatasetBatcher<'a>, DatasetBatcher<'a>), String> {
+    if !(validation_fraction >= 0f32 && validation_fraction <= 1f32) {
+        return Err(format!("The validation fraction has to be in [0, 1], but is {}", validation_fraction));
+    }
+    let mut classes: HashMap<usize, Vec<usize>> = HashMap::new();
+    for index in 0..dataset.len() {
+        let class = try!(sample_class(&dataset.sample(index).1).map_err(|error| format!("Sample {}: {}", index, error)));
+        classes.entry(class).or_insert_with(Vec::new).push(index);
+    }
+    // the classes are split in a fixed order, so the split only depends on the seed
+    let mut class_ids = classes.keys().cloned().collect::<Vec<_>>();
+    class_ids.sort();
+
+    let mut rng = RngStream::new(seed, "stratified_split");
+    let mut train = Vec::new();
+    let mut validation = Vec::new();
+    for class in class_ids {
+        let mut indices = classes.remove(&class).unwrap();
+        rng.shuffle(&mut indices);
+        let num_validation = (indices.len() as f32 * validation_fraction).round() as usize;
+        validation.extend_from_slice(&indices[..num_validation]);
+        train.extend_from_slice(&indices[num_validation..]);
+    }
+    rng.shuffle(&mut train);
+    validation.sort();
+
+    Ok((DatasetBatcher::new(dataset, train, batch_size, input_shape),
+        DatasetBatcher::new(dataset, validation, batch_size, input_shape).pad_last_batch(true)))
+}
+
+/// Return the class of a sample from its target.
+fn sample_class(target: &[f32]) -> Result<usize, String> {
+    match target.len() {
+        0 => Err("A sample without target has no class".to_owned()),
+        1 => Ok(class_ids(target)[0]),
+        _ => Ok(target.iter().enumerate().fold(0, |max_index, (index, &value)| {
+            if value > target[max_index] { index } else { max_index }
+        })),
+    }
+}
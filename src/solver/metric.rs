@@ -0,0 +1,70 @@
+//! Provides the interface for metrics that are computed during training.
+//!
+//! Metrics are attached to a [Solver][1] with [add_metric][2] and updated with the
+//! network output and the target of every trained minibatch, so they don't need an
+//! additional forward pass. If [SolverConfig.metrics_interval][3] is set, the metrics are
+//! reported to the `leaf::solver` log target and reset after every interval.
+//!
+//! [1]: ../struct.Solver.html
+//! [2]: ../struct.Solver.html#method.add_metric
+//! [3]: ../struct.SolverConfig.html#structfield.metrics_interval
+use co::SharedTensor;
+use super::ConfusionMatrix;
+
+/// A metric that is computed from the network outputs and targets of minibatches.
+pub trait IMetric {
+    /// Returns the name under which the metric is reported.
+    fn name(&self) -> String;
+
+    /// Update the metric with the network output and the target of one minibatch.
+    fn update(&mut self, output: &mut SharedTensor<f32>, target: &mut SharedTensor<f32>);
+
+    /// Returns the value of the metric for the minibatches since the last reset.
+    ///
+    /// Returns `None` if the metric has not been updated since then.
+    fn value(&self) -> Option<f32>;
+
+    /// Reset the metric, discarding all updates.
+    fn reset(&mut self);
+}
+
+impl ::std::fmt::Debug for IMetric {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "({})", "IMetric")
+    }
+}
+
+/// The number of samples a ConfusionMatrix without capacity keeps when it is used as metric.
+///
+/// This bounds its memory if the metrics are not reset by [SolverConfig.metrics_interval][1].
+/// [1]: ../struct.SolverConfig.html#structfield.metrics_interval
+pub const DEFAULT_METRIC_CAPACITY: usize = 10000;
+
+impl IMetric for ConfusionMatrix {
+    fn name(&self) -> String {
+        "accuracy".to_owned()
+    }
+
+    /// Add the samples of the minibatch.
+    ///
+    /// If the ConfusionMatrix has no capacity, its capacity is set to [DEFAULT_METRIC_CAPACITY][1].
+    /// [1]: ./constant.DEFAULT_METRIC_CAPACITY.html
+    fn update(&mut self, output: &mut SharedTensor<f32>, target: &mut SharedTensor<f32>) {
+        if self.capacity().is_none() {
+            self.set_capacity(Some(DEFAULT_METRIC_CAPACITY));
+        }
+        let predictions = self.get_predictions(output);
+        let targets = self.get_targets(target);
+        self.add_samples(&predictions, &targets);
+    }
+
+    /// Returns the percentage of correct predictions, like the Display of [Accuracy][1].
+    /// [1]: ../confusion_matrix/struct.Accuracy.html
+    fn value(&self) -> Option<f32> {
+        self.accuracy().percent()
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
@@ -2,25 +2,71 @@
 //!
 //! See [Solvers][solvers]
 //! [solvers]: ../solvers/index.html
+//!
+//! ## Training multiple networks
+//!
+//! Setups like Generative Adversarial Networks train two networks in alternation,
+//! where the gradient for one network is computed through the other network.
+//! This can be done with one Solver per network, each with its own objective and
+//! [SolverConfig][config], e.g. for a generator and a discriminator:
+//!
+//! 1. Train the discriminator on real and generated samples with [train_minibatch][train].
+//! 2. Compute the gradient of the discriminator objective w.r.t. the generated samples
+//!    with [compute_input_gradient][input_gradient], which does not update the discriminator.
+//! 3. Train the generator with that gradient via [train_with_gradient][with_gradient].
+//!
+//! [config]: ./struct.SolverConfig.html
+//! [train]: ./struct.Solver.html#method.train_minibatch
+//! [input_gradient]: ./struct.Solver.html#method.compute_input_gradient
+//! [with_gradient]: ./struct.Solver.html#method.train_with_gradient
+//!
+//! ## Training multiple tasks
+//!
+//! A single network can also be trained on several tasks, each with its own data and
+//! one of the [objectives][objectives] of the Solver. [train_tasks][train_tasks] chooses
+//! the task of every iteration with a [TaskSampler][sampler], e.g. weighted at random or
+//! following a curriculum.
+//!
+//! [objectives]: ./struct.SolverConfig.html#structfield.objectives
+//! [train_tasks]: ./struct.Solver.html#method.train_tasks
+//! [sampler]: ./task/struct.TaskSampler.html
 
+pub mod batcher;
 pub mod confusion_matrix;
+pub mod dataset;
+pub mod metric;
+pub mod search;
+pub mod task;
 
+pub use self::batcher::{IBatcher, IteratorBatcher};
 pub use self::confusion_matrix::ConfusionMatrix;
+pub use self::dataset::{IDataset, Dataset, DatasetBatcher, stratified_split};
+pub use self::metric::IMetric;
+pub use self::search::{Candidate, ParamSearch, SearchResult};
+pub use self::task::{TaskSampler, TaskSchedule, TaskSummary};
 
+use std::ops::Range;
 use std::rc::Rc;
+use std::sync::{Arc, RwLock};
 use std::marker::PhantomData;
+use std::path::PathBuf;
+use rand::Rng;
+use rng::{self, RngStream};
 use co::prelude::*;
 use layer::*;
 use layers::SequentialConfig;
+use logging::{Shape, Subsystem};
+use log::LogLevel;
 use solvers::*;
-use util::{ArcLock, LayerOps, SolverOps};
+use util::{ArcLock, LayerOps, SolverOps, native_backend, native_scalar, tensor_to_vec, write_to_memory};
 
 #[derive(Debug)]
 /// Solver that optimizes a [Layer][1] with a given objective.
 /// [1]: ../layer/index.html
 pub struct Solver<SolverB: IBackend + SolverOps<f32>, B: IBackend + LayerOps<f32>> {
     net: Layer<B>,
-    objective: Layer<SolverB>,
+    objectives: Vec<WeightedObjective<SolverB>>,
+    metrics: Vec<Box<IMetric>>,
     /// The implementation of the Solver
     pub worker: Box<ISolver<SolverB, B>>,
 
@@ -28,6 +74,35 @@ pub struct Solver<SolverB: IBackend + SolverOps<f32>, B: IBackend + LayerOps<f32
 
     /// The current iteration / number of times weights have been updated
     iter: usize,
+    /// The number of epochs that have been completed by [fit][1]
+    /// [1]: #method.fit
+    epoch: usize,
+    /// The random numbers that shuffle the samples in [fit][1]
+    /// [1]: #method.fit
+    shuffle_rng: RngStream,
+
+    /// The sum of the weight gradients of the sub-batches since the last update,
+    /// one tensor per weight on the device of the solver backend
+    accumulated_gradients: Vec<SharedTensor<f32>>,
+    /// The number of sub-batches whose gradients have been accumulated since the last update
+    num_accumulated: usize,
+
+    /// The average loss of the samples trained with [partial_fit][1]
+    /// [1]: #method.partial_fit
+    online_loss: f32,
+    /// The number of samples trained with [partial_fit][1]
+    /// [1]: #method.partial_fit
+    num_online_samples: usize,
+
+    /// The lowest validation loss reported so far
+    best_validation_loss: f32,
+    /// The number of validation losses since the last improvement of the best validation loss
+    plateau_evaluations: usize,
+    /// The lowest validation loss after an epoch of [fit_with_validation][1]
+    /// [1]: #method.fit_with_validation
+    best_epoch_validation_loss: f32,
+    /// The number of epochs since the last improvement of the best epoch validation loss
+    epochs_without_improvement: usize,
 
     solver_backend: PhantomData<SolverB>,
 }
@@ -37,16 +112,110 @@ impl<SolverB: IBackend + SolverOps<f32> + 'static, B: IBackend + LayerOps<f32> +
     /// [1]: ./struct.SolverConfig.html
     ///
     /// This is the **preferred method** to create a Solver for training a neural network.
-    pub fn from_config(net_backend: Rc<B>, obj_backend: Rc<SolverB>, config: &SolverConfig) -> Solver<SolverB, B> {
-        let network = Layer::from_config(net_backend, &config.network);
+    ///
+    /// If [SolverConfig.objectives][2] is not empty, one objective is created for each entry
+    /// instead of [SolverConfig.objective][3].
+    /// [2]: ./struct.SolverConfig.html#structfield.objectives
+    /// [3]: ./struct.SolverConfig.html#structfield.objective
+    ///
+    /// Returns an error if the weights can not be loaded from [SolverConfig.weights_path][4],
+    /// if the network has no layer named [SolverConfig.train_from][5], if it has no output
+    /// named like one of the [outputs][6] of an objective or if [SolverConfig.online_decay][7]
+    /// is not in the range `[0, 1)`.
+    /// [4]: ./struct.SolverConfig.html#structfield.weights_path
+    /// [5]: ./struct.SolverConfig.html#structfield.train_from
+    /// [6]: ./struct.ObjectiveConfig.html#structfield.outputs
+    /// [7]: ./struct.SolverConfig.html#structfield.online_decay
+    pub fn from_config(net_backend: Rc<B>, obj_backend: Rc<SolverB>, config: &SolverConfig) -> Result<Solver<SolverB, B>, String> {
+        if config.objectives.is_empty() {
+            let objective = Box::new(Layer::from_config(obj_backend.clone(), &config.objective));
+            return Self::with_objective(net_backend, obj_backend, config, objective)
+        }
+
+        let network = try!(Self::create_network(net_backend, config));
+        let output_names = network.output_names();
+        let mut objectives = Vec::with_capacity(config.objectives.len());
+        for objective_config in &config.objectives {
+            let mut output_ids = Vec::with_capacity(objective_config.outputs.len());
+            for output in &objective_config.outputs {
+                match output_names.iter().position(|name| name == output) {
+                    Some(output_id) => output_ids.push(output_id),
+                    None => return Err(format!("The network has no output named {}, which is used by objective {}",
+                                               output, objective_config.objective.name)),
+                }
+            }
+            objectives.push(WeightedObjective {
+                loss: Box::new(Layer::from_config(obj_backend.clone(), &objective_config.objective)),
+                weight: objective_config.weight,
+                output_ids: output_ids,
+                num_targets: objective_config.num_targets,
+            });
+        }
+        Ok(Self::new(network, obj_backend, config, objectives))
+    }
+
+    /// Create Solver from [SolverConfig][1] with a custom objective.
+    /// [1]: ./struct.SolverConfig.html
+    ///
+    /// The `objective` is used instead of creating one from [SolverConfig.objective][1].
+    /// This allows using losses that are implemented outside of Leaf via [ILoss][2].
+    /// [2]: ./trait.ILoss.html
+    ///
+    /// The objective is applied to the first output of the network.
+    /// Returns an error in the same cases as [from_config][3].
+    /// [3]: #method.from_config
+    pub fn with_objective(net_backend: Rc<B>, obj_backend: Rc<SolverB>, config: &SolverConfig, objective: Box<ILoss<SolverB>>) -> Result<Solver<SolverB, B>, String> {
+        let network = try!(Self::create_network(net_backend, config));
+        let objectives = vec![WeightedObjective { loss: objective, weight: 1f32, output_ids: vec![0], num_targets: 1 }];
+        Ok(Self::new(network, obj_backend, config, objectives))
+    }
+
+    fn create_network(net_backend: Rc<B>, config: &SolverConfig) -> Result<Layer<B>, String> {
+        if let Some(decay) = config.online_decay {
+            if !(decay >= 0f32 && decay < 1f32) {
+                return Err(format!("The online_decay has to be in the range [0, 1), but is {}", decay));
+            }
+        }
+        let mut network = Layer::from_config(net_backend, &config.network);
+        if let Some(ref weights_path) = config.weights_path {
+            let transfers = match network.transplant_weights(weights_path) {
+                Ok(transfers) => transfers,
+                Err(e) => return Err(format!("Can not load the weights of the network from {}: {}", weights_path.display(), e)),
+            };
+            for transfer in transfers.iter().filter(|transfer| !transfer.is_complete()) {
+                log_event!(Subsystem::Solver, LogLevel::Warn, "incomplete_weight", weight = transfer.name,
+                           copied = transfer.copied, shape = Shape(&transfer.shape));
+            }
+        }
+        if let Some(ref layer_name) = config.train_from {
+            try!(network.train_from(layer_name));
+        }
+        Ok(network)
+    }
+
+    fn new(network: Layer<B>, obj_backend: Rc<SolverB>, config: &SolverConfig, objectives: Vec<WeightedObjective<SolverB>>) -> Solver<SolverB, B> {
         let mut worker = config.solver.with_config(obj_backend.clone(), &config);
         worker.init(&network);
 
         Solver {
             worker: worker,
             net: network,
-            objective: Layer::from_config(obj_backend, &config.objective),
+            objectives: objectives,
+            metrics: Vec::new(),
             iter: 0,
+            epoch: 0,
+            shuffle_rng: RngStream::new(rng::seed(), "fit_shuffle"),
+
+            accumulated_gradients: Vec::new(),
+            num_accumulated: 0,
+
+            online_loss: 0f32,
+            num_online_samples: 0,
+
+            best_validation_loss: ::std::f32::INFINITY,
+            plateau_evaluations: 0,
+            best_epoch_validation_loss: ::std::f32::INFINITY,
+            epochs_without_improvement: 0,
 
             config: config.clone(),
             solver_backend: PhantomData::<SolverB>,
@@ -57,7 +226,7 @@ impl<SolverB: IBackend + SolverOps<f32> + 'static, B: IBackend + LayerOps<f32> +
 
 impl<SolverB: IBackend + SolverOps<f32> + 'static, B: IBackend + LayerOps<f32> + 'static> Solver<SolverB, B>{
     fn init(&mut self, backend: Rc<B>) {
-        info!("Initializing solver from configuration");
+        log_event!(Subsystem::Solver, LogLevel::Info, "init_solver", network = self.config.network.name);
 
         let mut config = self.config.clone();
         self.init_net(backend, &mut config);
@@ -69,20 +238,759 @@ impl<SolverB: IBackend + SolverOps<f32> + 'static, B: IBackend + LayerOps<f32> +
     }
 
     /// Train the network with one minibatch
+    ///
+    /// With several [objectives][1], `mb_target` is the target of every objective.
+    /// [1]: ./struct.SolverConfig.html#structfield.objectives
+    ///
+    /// With a [minibatch_size][2] above `1` the minibatch is a sub-batch, whose gradients
+    /// are accumulated until the weights are updated.
+    /// [2]: ./struct.SolverConfig.html#structfield.minibatch_size
     pub fn train_minibatch(&mut self, mb_data: ArcLock<SharedTensor<f32>>, mb_target: ArcLock<SharedTensor<f32>>) -> ArcLock<SharedTensor<f32>> {
-        // forward through network and classifier
-        let network_out = self.net.forward(&[mb_data])[0].clone();
-        let _ = self.objective.forward(&[network_out.clone(), mb_target]);
+        self.train_step(mb_data, &[mb_target]).0[0].clone()
+    }
+
+    /// Train the network with one minibatch, using separate targets for each objective.
+    ///
+    /// The targets are in the order of [SolverConfig.objectives][1], where each objective
+    /// takes [num_targets][2] of them. A single target is used for every objective that takes one target.
+    /// Returns all outputs of the network.
+    /// [1]: ./struct.SolverConfig.html#structfield.objectives
+    /// [2]: ./struct.ObjectiveConfig.html#structfield.num_targets
+    pub fn train_minibatch_with_targets(&mut self, mb_data: ArcLock<SharedTensor<f32>>, mb_targets: &[ArcLock<SharedTensor<f32>>]) -> Vec<ArcLock<SharedTensor<f32>>> {
+        self.train_step(mb_data, mb_targets).0
+    }
+
+    /// Compute the loss of each objective for one minibatch without training the network.
+    ///
+    /// The targets are assigned to the objectives like in [train_minibatch_with_targets][1].
+    /// The losses are in the order of [SolverConfig.objectives][2] and are not scaled
+    /// by the weight of the objective.
+    /// [1]: #method.train_minibatch_with_targets
+    /// [2]: ./struct.SolverConfig.html#structfield.objectives
+    pub fn objective_losses(&mut self, mb_data: ArcLock<SharedTensor<f32>>, mb_targets: &[ArcLock<SharedTensor<f32>>]) -> Vec<f32> {
+        self.net.set_training(false);
+        let network_out = self.net.forward(&[mb_data]);
+        self.net.set_training(true);
+        compute_losses(&mut self.objectives, &network_out, mb_targets)
+    }
+
+    /// Train the network with one minibatch given as plain values and return the loss.
+    ///
+    /// The tensors for the data of shape `data_shape` and the targets of shape `target_shape`
+    /// are created on a Native Backend, which makes it easy to train from data that is
+    /// collected on the fly, e.g. samples of a replay buffer.
+    /// With several [objectives][1], `targets` is the target of every objective.
+    /// [1]: ./struct.SolverConfig.html#structfield.objectives
+    ///
+    /// Returns an error if the number of values doesn't match the shape of the data or the targets.
+    pub fn train_on_slices(&mut self, data: &[f32], data_shape: &[usize], targets: &[f32], target_shape: &[usize]) -> Result<f32, String> {
+        if data.len() != data_shape.iter().fold(1, |prod, dim| prod * dim) {
+            return Err(format!("Got {} values for data of shape {:?}", data.len(), data_shape));
+        }
+        if targets.len() != target_shape.iter().fold(1, |prod, dim| prod * dim) {
+            return Err(format!("Got {} values for targets of shape {:?}", targets.len(), target_shape));
+        }
+        let mb_data = native_tensor(data_shape, data);
+        let mb_target = native_tensor(target_shape, targets);
+        Ok(self.train_step(mb_data, &[mb_target]).1)
+    }
+
+    /// Train the network with a single sample as it arrives and return its loss.
+    ///
+    /// This is the entry point for online learning, where there is no fixed dataset, e.g. in
+    /// recommendation or anomaly detection. The weights are updated after every sample, unless
+    /// gradients are accumulated over several samples with [SolverConfig.minibatch_size][1].
+    /// The network has to be created with a batch size of `1`, e.g. with
+    /// [SequentialConfig::set_batch_size][2]. `target` holds the values of the target of the
+    /// sample, e.g. a single class id.
+    /// [1]: ./struct.SolverConfig.html#structfield.minibatch_size
+    /// [2]: ../layers/container/struct.SequentialConfig.html#method.set_batch_size
+    ///
+    /// The loss is computed before the weights are updated, so it measures how well the sample
+    /// was predicted. The running average of the losses is available with [online_loss][3],
+    /// where older samples are weighted down according to [SolverConfig.online_decay][4].
+    /// [3]: #method.online_loss
+    /// [4]: ./struct.SolverConfig.html#structfield.online_decay
+    ///
+    /// Panics if the batch size of the network is not `1` or `sample` doesn't have the size of its input.
+    pub fn partial_fit(&mut self, sample: &[f32], target: &[f32]) -> f32 {
+        let input_shape = self.net.input_shapes()[0].1.clone();
+        if input_shape.get(0) != Some(&1) {
+            panic!("partial_fit requires a network with a batch size of 1, but its input has the shape {:?}", input_shape);
+        }
+        let loss = match self.train_on_slices(sample, &input_shape, target, &[1, target.len()]) {
+            Ok(loss) => loss,
+            Err(error) => panic!("{}", error),
+        };
+
+        self.num_online_samples += 1;
+        let decay = match self.config.online_decay {
+            // the first sample replaces the initial value
+            Some(decay) if self.num_online_samples > 1 => decay,
+            _ => 1f32 - 1f32 / self.num_online_samples as f32,
+        };
+        self.online_loss = decay * self.online_loss + (1f32 - decay) * loss;
+        loss
+    }
+
+    /// Returns the running average of the losses of the samples trained with [partial_fit][1],
+    /// or `None` if no sample has been trained yet.
+    ///
+    /// See [SolverConfig.online_decay][2].
+    /// [1]: #method.partial_fit
+    /// [2]: ./struct.SolverConfig.html#structfield.online_decay
+    pub fn online_loss(&self) -> Option<f32> {
+        match self.num_online_samples {
+            0 => None,
+            _ => Some(self.online_loss),
+        }
+    }
 
+    /// Train the network with one minibatch and return the network outputs and the loss.
+    fn train_step(&mut self, mb_data: ArcLock<SharedTensor<f32>>, mb_targets: &[ArcLock<SharedTensor<f32>>]) -> (Vec<ArcLock<SharedTensor<f32>>>, f32) {
+        let all_objectives = 0..self.objectives.len();
+        self.train_objectives(mb_data, mb_targets, all_objectives)
+    }
+
+    /// Train the network with one minibatch on the objectives `objective_ids` only
+    /// and return the network outputs and the loss.
+    ///
+    /// The metrics are only updated if the first objective is trained.
+    fn train_objectives(&mut self, mb_data: ArcLock<SharedTensor<f32>>, mb_targets: &[ArcLock<SharedTensor<f32>>], objective_ids: Range<usize>) -> (Vec<ArcLock<SharedTensor<f32>>>, f32) {
         // forward through network and classifier
-        let classifier_gradient = self.objective.backward(&[]);
-        self.net.backward(&classifier_gradient[0 .. 1]);
+        let network_out = self.net.forward(&[mb_data.clone()]);
+        let loss = compute_loss(&mut self.objectives[objective_ids.clone()], &network_out, mb_targets);
+        if objective_ids.start == 0 {
+            self.update_metrics(&network_out, mb_targets);
+        }
+
+        // backward through classifier and network
+        let output_gradients = self.compute_output_gradients(&network_out, objective_ids.clone());
+        self.net.backward(&output_gradients);
+        if !self.accumulate_gradients() {
+            return (network_out, loss);
+        }
+
+        let updated = {
+            let objectives = &mut self.objectives[objective_ids];
+            let mut evaluate = |net: &mut Layer<B>| {
+                let network_out = net.forward(&[mb_data.clone()]);
+                compute_loss(objectives, &network_out, mb_targets)
+            };
+            self.worker.update_with_loss(&self.config, &mut self.net, self.iter, &mut evaluate)
+        };
+        if updated {
+            self.iter += 1;
+        } else {
+            self.apply_update();
+        }
+        if let Some(interval) = self.config.metrics_interval {
+            if interval > 0 && self.iter % interval == 0 {
+                self.report_metrics();
+            }
+        }
+
+        (network_out, loss)
+    }
+
+    /// Attach a metric that is updated with the network output and target of every trained minibatch.
+    ///
+    /// The metric uses the output and the target of the first objective.
+    /// See [metric][1].
+    /// [1]: ./metric/index.html
+    pub fn add_metric(&mut self, metric: Box<IMetric>) {
+        self.metrics.push(metric);
+    }
+
+    /// Returns the metrics attached to the solver.
+    pub fn metrics(&self) -> &[Box<IMetric>] {
+        &self.metrics
+    }
+
+    /// Log the values of all metrics and reset them.
+    ///
+    /// This is done automatically every [SolverConfig.metrics_interval][1] iterations.
+    /// [1]: ./struct.SolverConfig.html#structfield.metrics_interval
+    pub fn report_metrics(&mut self) {
+        for metric in &mut self.metrics {
+            if let Some(value) = metric.value() {
+                log_event!(Subsystem::Solver, LogLevel::Info, "metric", iteration = self.iter, name = metric.name(), value = value);
+            }
+            metric.reset();
+        }
+    }
+
+    fn update_metrics(&mut self, network_out: &[ArcLock<SharedTensor<f32>>], mb_targets: &[ArcLock<SharedTensor<f32>>]) {
+        if self.metrics.is_empty() {
+            return
+        }
+        let output = &network_out[self.objectives[0].output_ids[0]];
+        for metric in &mut self.metrics {
+            metric.update(&mut output.write().unwrap(), &mut mb_targets[0].write().unwrap());
+        }
+    }
+
+    /// Compute the gradient w.r.t. each network output from the objectives `objective_ids`
+    /// of the last loss computation.
+    ///
+    /// The gradients of each objective are scaled by its weight. Gradients of objectives
+    /// for the same output are summed up, outputs without objective get a zero gradient.
+    fn compute_output_gradients(&mut self, network_out: &[ArcLock<SharedTensor<f32>>], objective_ids: Range<usize>) -> Vec<ArcLock<SharedTensor<f32>>> {
+        let mut gradients: Vec<Option<ArcLock<SharedTensor<f32>>>> = vec![None; network_out.len()];
+        for objective in &mut self.objectives[objective_ids] {
+            let objective_gradients = objective.loss.compute_gradients();
+            if objective_gradients.len() < objective.output_ids.len() {
+                panic!("Got {} gradients from an objective for {} network outputs", objective_gradients.len(), objective.output_ids.len());
+            }
+            for (gradient, &output_id) in objective_gradients.into_iter().zip(objective.output_ids.iter()) {
+                gradients[output_id] = Some(match gradients[output_id].take() {
+                    // avoid the roundtrip through the native backend for a single objective
+                    None if objective.weight == 1f32 => gradient,
+                    previous => {
+                        let shape = gradient.read().unwrap().desc().clone();
+                        let mut values = tensor_to_vec(&gradient).iter().map(|value| value * objective.weight).collect::<Vec<_>>();
+                        if let Some(previous) = previous {
+                            for (value, previous_value) in values.iter_mut().zip(tensor_to_vec(&previous)) {
+                                *value += previous_value;
+                            }
+                        }
+                        native_tensor(&shape, &values)
+                    }
+                });
+            }
+        }
+        gradients.into_iter().zip(network_out.iter()).map(|(gradient, output)| {
+            gradient.unwrap_or_else(|| {
+                let shape = output.read().unwrap().desc().clone();
+                native_tensor(&shape, &vec![0f32; shape.size()])
+            })
+        }).collect()
+    }
+
+    /// Train the network with one minibatch, using a gradient for the network output
+    /// that was computed outside of the solver.
+    ///
+    /// The objective of the solver is not used. This allows training a network
+    /// with the gradient that another network computed for its input
+    /// (see [compute_input_gradient][1]).
+    /// [1]: #method.compute_input_gradient
+    pub fn train_with_gradient(&mut self, mb_data: ArcLock<SharedTensor<f32>>, output_gradient: ArcLock<SharedTensor<f32>>) -> ArcLock<SharedTensor<f32>> {
+        let network_out = self.forward(mb_data)[0].clone();
+        self.apply_output_gradients(&[output_gradient]);
+
+        network_out
+    }
+
+    /// Compute the outputs of the network for one minibatch.
+    ///
+    /// Together with [apply_output_gradients][1] this trains the network with gradients
+    /// that depend on the outputs but are computed outside of the solver, e.g. from the
+    /// rewards of a policy-gradient method.
+    /// [1]: #method.apply_output_gradients
+    pub fn forward(&mut self, mb_data: ArcLock<SharedTensor<f32>>) -> Vec<ArcLock<SharedTensor<f32>>> {
+        self.net.forward(&[mb_data])
+    }
+
+    /// Backpropagate gradients w.r.t. the network outputs of the last [forward][1] and
+    /// update the weights.
+    ///
+    /// The objectives of the solver are not used. There has to be one gradient for every
+    /// output of the network, with the shape of that output.
+    /// Another forward pass through the network in between, e.g. with [Layer::forward][2]
+    /// on the [network][3], replaces the outputs the gradients are propagated for.
+    /// [1]: #method.forward
+    /// [2]: ../layer/struct.Layer.html#method.forward
+    /// [3]: #method.mut_network
+    pub fn apply_output_gradients(&mut self, output_gradients: &[ArcLock<SharedTensor<f32>>]) {
+        let num_outputs = self.net.output_names().len();
+        if output_gradients.len() != num_outputs {
+            panic!("Got {} output gradients for a network with {} outputs", output_gradients.len(), num_outputs);
+        }
+        self.net.backward(output_gradients);
+
+        self.update_weights();
+    }
+
+    /// Compute the gradient of the objective w.r.t. the input of the network
+    /// for one minibatch, without updating the weights of the network.
+    pub fn compute_input_gradient(&mut self, mb_data: ArcLock<SharedTensor<f32>>, mb_target: ArcLock<SharedTensor<f32>>) -> ArcLock<SharedTensor<f32>> {
+        let network_out = self.net.forward(&[mb_data]);
+        let _ = compute_loss(&mut self.objectives, &network_out, &[mb_target]);
 
+        let all_objectives = 0..self.objectives.len();
+        let output_gradients = self.compute_output_gradients(&network_out, all_objectives);
+        self.net.backward_input(&output_gradients)[0].clone()
+    }
+
+    /// Train the network on `dataset` for `epochs` epochs.
+    ///
+    /// See [fit_with_callback][1].
+    /// [1]: #method.fit_with_callback
+    pub fn fit(&mut self, dataset: &IDataset, epochs: usize) -> Vec<EpochSummary> {
+        self.fit_with_callback(dataset, epochs, |_, _| {})
+    }
+
+    /// Train the network on `dataset` for `epochs` epochs, calling `callback` after each epoch.
+    ///
+    /// The order of the samples is shuffled at the start of every epoch, with random numbers
+    /// drawn from an [RngStream][3] of the global seed, so training is reproducible.
+    /// The samples are assembled into minibatches with the batchsize of the network input,
+    /// which is the first dimension of its input shape. Samples that do not fill a
+    /// complete minibatch at the end of an epoch are skipped for that epoch; if the dataset
+    /// has fewer samples than a minibatch, no minibatch is trained and the loss of the epoch is `0`.
+    ///
+    /// The callback receives the Solver and the [summary][1] of the finished epoch,
+    /// which can be used e.g. for logging, evaluation or adjusting the [SolverConfig][2].
+    /// [1]: ./struct.EpochSummary.html
+    /// [2]: ./struct.SolverConfig.html
+    /// [3]: ../rng/struct.RngStream.html
+    pub fn fit_with_callback<F>(&mut self, dataset: &IDataset, epochs: usize, callback: F) -> Vec<EpochSummary>
+        where F: FnMut(&mut Self, &EpochSummary) {
+        self.fit_epochs(dataset, None, epochs, callback)
+    }
+
+    /// Train the network on `dataset` for `epochs` epochs and compute the loss on `validation`
+    /// after each epoch.
+    ///
+    /// Training works like in [fit_with_callback][1]. The validation loss is computed with
+    /// [validate][2] and reported with [report_validation_loss][3] before `callback` is called,
+    /// so the learning rate is reduced on plateaus if [SolverConfig.reduce_lr_on_plateau][4] is set.
+    ///
+    /// If [SolverConfig.early_stopping_patience][5] is set, training stops before `epochs` epochs
+    /// once the validation loss has not improved on its lowest value for that many epochs.
+    /// The returned summaries only contain the epochs that were trained.
+    /// [1]: #method.fit_with_callback
+    /// [2]: #method.validate
+    /// [3]: #method.report_validation_loss
+    /// [4]: ./struct.SolverConfig.html#structfield.reduce_lr_on_plateau
+    /// [5]: ./struct.SolverConfig.html#structfield.early_stopping_patience
+    pub fn fit_with_validation<F>(&mut self, dataset: &IDataset, validation: &IDataset, epochs: usize, callback: F) -> Vec<EpochSummary>
+        where F: FnMut(&mut Self, &EpochSummary) {
+        self.fit_epochs(dataset, Some(validation), epochs, callback)
+    }
+
+    fn fit_epochs<F>(&mut self, dataset: &IDataset, validation: Option<&IDataset>, epochs: usize, mut callback: F) -> Vec<EpochSummary>
+        where F: FnMut(&mut Self, &EpochSummary) {
+        let input_shape = self.net.input_shapes()[0].1.clone();
+        let batch_size = input_shape[0];
+
+        let mut order = (0..dataset.len()).collect::<Vec<_>>();
+        let mut summaries = Vec::with_capacity(epochs);
+        if order.len() < batch_size {
+            log_event!(Subsystem::Solver, LogLevel::Warn, "dataset_smaller_than_minibatch",
+                       samples = order.len(), batch_size = batch_size);
+        }
+        for _ in 0..epochs {
+            self.shuffle_rng.shuffle(&mut order);
+
+            let num_minibatches = order.len() / batch_size;
+            let mut loss_sum = 0f32;
+            for minibatch in order.chunks(batch_size).take(num_minibatches) {
+                let (inputs, targets) = dataset.minibatch(minibatch, &input_shape[1..]);
+                let (_, loss) = self.train_step(inputs, &[targets]);
+                loss_sum += loss;
+            }
+
+            self.epoch += 1;
+            let validation_loss = validation.and_then(|validation| self.validate(validation));
+            if let Some(validation_loss) = validation_loss {
+                self.report_validation_loss(validation_loss);
+            }
+            let summary = EpochSummary {
+                epoch: self.epoch,
+                num_minibatches: num_minibatches,
+                loss: if num_minibatches > 0 { loss_sum / num_minibatches as f32 } else { 0f32 },
+                validation_loss: validation_loss,
+            };
+            match summary.validation_loss {
+                Some(validation_loss) => log_event!(Subsystem::Solver, LogLevel::Info, "epoch", epoch = summary.epoch,
+                                                    loss = summary.loss, validation_loss = validation_loss),
+                None => log_event!(Subsystem::Solver, LogLevel::Info, "epoch", epoch = summary.epoch, loss = summary.loss),
+            }
+            callback(self, &summary);
+            summaries.push(summary);
+
+            if let Some(validation_loss) = validation_loss {
+                if self.stops_early(validation_loss) {
+                    log_event!(Subsystem::Solver, LogLevel::Info, "early_stop", epoch = self.epoch,
+                               best_validation_loss = self.best_epoch_validation_loss);
+                    break;
+                }
+            }
+        }
+        summaries
+    }
+
+    /// Track the validation loss of an epoch and return `true` if it has not improved for
+    /// [SolverConfig.early_stopping_patience][1] epochs.
+    /// [1]: ./struct.SolverConfig.html#structfield.early_stopping_patience
+    fn stops_early(&mut self, validation_loss: f32) -> bool {
+        if validation_loss < self.best_epoch_validation_loss {
+            self.best_epoch_validation_loss = validation_loss;
+            self.epochs_without_improvement = 0;
+        } else {
+            self.epochs_without_improvement += 1;
+        }
+        self.config.early_stopping_patience.map_or(false, |patience| self.epochs_without_improvement >= patience)
+    }
+
+    /// Train the network on all minibatches of `batcher` and return their average loss.
+    ///
+    /// Unlike [fit][1] this does not count epochs; call it again with a new batcher for
+    /// another pass over the data.
+    /// [1]: #method.fit
+    ///
+    /// Returns `None` if the batcher has no minibatch, or an error if a minibatch
+    /// can not be assembled.
+    pub fn train_batches(&mut self, batcher: &mut IBatcher) -> Result<Option<f32>, String> {
+        let mut num_minibatches = 0;
+        let mut loss_sum = 0f32;
+        while let Some(batch) = batcher.next_batch() {
+            let (inputs, targets) = try!(batch);
+            let (_, loss) = self.train_step(inputs, &[targets]);
+            loss_sum += loss;
+            num_minibatches += 1;
+        }
+        match num_minibatches {
+            0 => Ok(None),
+            _ => Ok(Some(loss_sum / num_minibatches as f32)),
+        }
+    }
+
+    /// Train the network on several tasks that share its weights for at most `num_iterations` iterations.
+    ///
+    /// Each task consists of a source of minibatches in `tasks` and the objective at the same
+    /// position in [SolverConfig.objectives][1], or [SolverConfig.objective][2] for a single task.
+    /// In every iteration `sampler` chooses one task, whose next minibatch is trained with only
+    /// the objective of that task; the outputs of the other objectives get a zero gradient.
+    /// The [iteration][3] of the solver is passed to the sampler, so curricula continue
+    /// where they stopped when this is called repeatedly.
+    /// Tasks that run out of minibatches are not chosen anymore, and the training stops
+    /// early when all tasks have run out. The metrics are only updated for the first task.
+    /// [1]: ./struct.SolverConfig.html#structfield.objectives
+    /// [2]: ./struct.SolverConfig.html#structfield.objective
+    /// [3]: #method.iteration
+    ///
+    /// Returns a [summary][4] for every task.
+    /// [4]: ./task/struct.TaskSummary.html
+    ///
+    /// Returns an error if a minibatch can not be assembled, or if the gradients of several
+    /// sub-batches are accumulated ([SolverConfig.minibatch_size][5] is larger than `1`),
+    /// because the sub-batches of one update would be mixed from different tasks.
+    /// Panics if the number of tasks doesn't match the number of objectives.
+    /// [5]: ./struct.SolverConfig.html#structfield.minibatch_size
+    pub fn train_tasks(&mut self, tasks: &mut [&mut IBatcher], sampler: &mut TaskSampler, num_iterations: usize) -> Result<Vec<TaskSummary>, String> {
+        if tasks.len() != self.objectives.len() {
+            panic!("Got {} tasks for a solver with {} objectives", tasks.len(), self.objectives.len());
+        }
+        if self.config.minibatch_size > 1 {
+            return Err(format!("Tasks can not be trained with a minibatch_size of {}, because the accumulated sub-batches would belong to different tasks",
+                               self.config.minibatch_size));
+        }
+        let mut available = vec![true; tasks.len()];
+        let mut num_minibatches = vec![0; tasks.len()];
+        let mut loss_sums = vec![0f32; tasks.len()];
+        let mut step = 0;
+        while step < num_iterations {
+            let task = match sampler.next_task(self.iter, &available) {
+                Some(task) => task,
+                None => break,
+            };
+            match tasks[task].next_batch() {
+                Some(batch) => {
+                    let (inputs, targets) = try!(batch);
+                    let (_, loss) = self.train_objectives(inputs, &[targets], task..task + 1);
+                    log_event!(Subsystem::Solver, LogLevel::Debug, "train_task", task = task, loss = loss);
+                    num_minibatches[task] += 1;
+                    loss_sums[task] += loss;
+                    step += 1;
+                },
+                None => available[task] = false,
+            }
+        }
+        Ok(num_minibatches.into_iter().zip(loss_sums).map(|(num_minibatches, loss_sum)| TaskSummary {
+            num_minibatches: num_minibatches,
+            loss: match num_minibatches {
+                0 => None,
+                _ => Some(loss_sum / num_minibatches as f32),
+            },
+        }).collect())
+    }
+
+    /// Compute the average loss of the network on `dataset` without updating the weights.
+    ///
+    /// The samples are assembled into minibatches in their original order like in [fit][1].
+    /// Samples that do not fill a complete minibatch are skipped.
+    /// Returns `None` if the dataset has fewer samples than a minibatch.
+    /// [1]: #method.fit_with_callback
+    ///
+    /// The network is not in the training phase meanwhile, see [Layer::set_training][2].
+    /// [2]: ../layer/struct.Layer.html#method.set_training
+    pub fn validate(&mut self, dataset: &IDataset) -> Option<f32> {
+        let input_shape = self.net.input_shapes()[0].1.clone();
+        let batch_size = input_shape[0];
+
+        let order = (0..dataset.len()).collect::<Vec<_>>();
+        let num_minibatches = order.len() / batch_size;
+        if num_minibatches == 0 {
+            return None;
+        }
+        let mut loss_sum = 0f32;
+        self.net.set_training(false);
+        for minibatch in order.chunks(batch_size).take(num_minibatches) {
+            let (inputs, targets) = dataset.minibatch(minibatch, &input_shape[1..]);
+            let network_out = self.net.forward(&[inputs]);
+            loss_sum += compute_loss(&mut self.objectives, &network_out, &[targets]);
+        }
+        self.net.set_training(true);
+        Some(loss_sum / num_minibatches as f32)
+    }
+
+    /// Compute the average loss of the network on all minibatches of `batcher` without
+    /// updating the weights.
+    ///
+    /// See [validate][1].
+    /// [1]: #method.validate
+    ///
+    /// Returns `None` if the batcher has no minibatch, or an error if a minibatch
+    /// can not be assembled.
+    pub fn validate_batches(&mut self, batcher: &mut IBatcher) -> Result<Option<f32>, String> {
+        let mut num_minibatches = 0;
+        let mut loss_sum = 0f32;
+        self.net.set_training(false);
+        while let Some(batch) = batcher.next_batch() {
+            let (inputs, targets) = match batch {
+                Ok(batch) => batch,
+                Err(error) => {
+                    self.net.set_training(true);
+                    return Err(error);
+                },
+            };
+            let network_out = self.net.forward(&[inputs]);
+            loss_sum += compute_loss(&mut self.objectives, &network_out, &[targets]);
+            num_minibatches += 1;
+        }
+        self.net.set_training(true);
+        match num_minibatches {
+            0 => Ok(None),
+            _ => Ok(Some(loss_sum / num_minibatches as f32)),
+        }
+    }
+
+    /// Report a validation loss, reducing the learning rate if it has stopped improving.
+    ///
+    /// If [SolverConfig.reduce_lr_on_plateau][1] is set and the validation loss has not improved
+    /// on the best reported loss for more than `patience` reports, the `base_lr` of the
+    /// [config][2] is multiplied by `factor`, as well as the `lr` of its parameter groups.
+    /// Returns `true` if the learning rate was reduced.
+    /// [1]: ./struct.SolverConfig.html#structfield.reduce_lr_on_plateau
+    /// [2]: #method.config
+    pub fn report_validation_loss(&mut self, loss: f32) -> bool {
+        let plateau = match self.config.reduce_lr_on_plateau {
+            Some(plateau) => plateau,
+            None => return false,
+        };
+
+        if loss < self.best_validation_loss * (1f32 - plateau.threshold) {
+            self.best_validation_loss = loss;
+            self.plateau_evaluations = 0;
+            return false;
+        }
+        self.plateau_evaluations += 1;
+        if self.plateau_evaluations <= plateau.patience {
+            return false;
+        }
+
+        self.plateau_evaluations = 0;
+        let reduced_lr = (self.config.base_lr * plateau.factor).max(plateau.min_lr);
+        if reduced_lr >= self.config.base_lr {
+            return false;
+        }
+        log_event!(Subsystem::Solver, LogLevel::Info, "reduce_lr", evaluations = plateau.patience + 1, base_lr = reduced_lr);
+        self.config.base_lr = reduced_lr;
+        for group in &mut self.config.param_groups {
+            group.lr = group.lr.map(|lr| (lr * plateau.factor).max(plateau.min_lr));
+        }
+        true
+    }
+
+    /// Run a learning rate range test on `dataset` and return the loss for each learning rate.
+    ///
+    /// The network is trained for `num_steps` minibatches, while the learning rate increases
+    /// exponentially from `min_lr` to `max_lr`. The sweep stops early once the loss exceeds
+    /// four times the lowest loss seen so far.
+    /// A good `base_lr` is usually somewhat below the learning rate at which the loss decreases
+    /// fastest, well before it starts to diverge.
+    ///
+    /// The minibatches are assembled like in [fit][1], shuffled with a stream of the
+    /// [global seed][2], so the sweep is reproducible. Afterwards the weights of the network,
+    /// the configuration and the iteration are restored and the solver is reinitialized,
+    /// which discards e.g. the momentum history.
+    /// [1]: #method.fit_with_callback
+    /// [2]: ../rng/fn.set_seed.html
+    ///
+    /// Returns an error unless `0 < min_lr < max_lr`.
+    pub fn lr_find(&mut self, dataset: &IDataset, min_lr: f32, max_lr: f32, num_steps: usize) -> Result<Vec<(f32, f32)>, String> {
+        if !(min_lr > 0f32 && min_lr < max_lr && max_lr.is_finite()) {
+            return Err(format!("The learning rate range test needs 0 < min_lr < max_lr, got {} and {}", min_lr, max_lr));
+        }
+        let input_shape = self.net.input_shapes()[0].1.clone();
+        let batch_size = input_shape[0];
+        let num_minibatches = dataset.len() / batch_size;
+        if num_minibatches == 0 {
+            return Ok(Vec::new());
+        }
+
+        let native = native_backend();
+        let initial_weights = self.net.learnable_weights_data().iter().map(|weight| {
+            let mut weight = weight.write().unwrap();
+            match weight.add_device(native.device()) { _ => weight.sync(native.device()).unwrap() }
+            weight.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned()
+        }).collect::<Vec<_>>();
+        let initial_config = self.config.clone();
+        let initial_iter = self.iter;
+
+        let mut rng = RngStream::new(rng::seed(), "lr_find");
+        let mut order = (0..dataset.len()).collect::<Vec<_>>();
+        let mut curve = Vec::with_capacity(num_steps);
+        let mut best_loss = ::std::f32::INFINITY;
+        for step in 0..num_steps {
+            let minibatch_id = step % num_minibatches;
+            if minibatch_id == 0 {
+                rng.shuffle(&mut order);
+            }
+            let progress = if num_steps > 1 { step as f32 / (num_steps - 1) as f32 } else { 0f32 };
+            let lr = min_lr * (max_lr / min_lr).powf(progress);
+            self.config.lr_policy = LRPolicy::Fixed;
+            self.config.base_lr = lr;
+
+            let minibatch = &order[minibatch_id * batch_size..(minibatch_id + 1) * batch_size];
+            let (inputs, targets) = dataset.minibatch(minibatch, &input_shape[1..]);
+            let (_, loss) = self.train_step(inputs, &[targets]);
+            curve.push((lr, loss));
+
+            best_loss = best_loss.min(loss);
+            if !loss.is_finite() || loss > 4f32 * best_loss {
+                break;
+            }
+        }
+
+        for (weight, values) in self.net.learnable_weights_data().iter().zip(initial_weights) {
+            let mut weight = weight.write().unwrap();
+            match weight.add_device(native.device()) { _ => weight.sync(native.device()).unwrap() }
+            write_to_memory(weight.get_mut(native.device()).unwrap(), &values);
+        }
+        self.config = initial_config;
+        self.iter = initial_iter;
+        self.accumulated_gradients.clear();
+        self.num_accumulated = 0;
+        self.worker.init(&self.net);
+
+        Ok(curve)
+    }
+
+    /// Compute and apply the weight update from the current gradients of the network,
+    /// once the gradients of [SolverConfig.minibatch_size][1] sub-batches have been accumulated.
+    /// [1]: ./struct.SolverConfig.html#structfield.minibatch_size
+    fn update_weights(&mut self) {
+        if self.accumulate_gradients() {
+            self.apply_update();
+        }
+    }
+
+    /// Add the current weight gradients of the network to the accumulated gradients.
+    ///
+    /// Returns `true` once the gradients of [SolverConfig.minibatch_size][1] sub-batches have
+    /// been accumulated. The gradients of the network are then replaced by the average of the
+    /// accumulated gradients, so the update is scaled like the update for a single sub-batch.
+    /// [1]: ./struct.SolverConfig.html#structfield.minibatch_size
+    ///
+    /// The gradients are summed with axpy on the device of the solver backend, like the updates
+    /// of the [SGD solvers][2], so they don't have to be synchronized to native memory.
+    /// [2]: ../solvers/sgd/index.html
+    fn accumulate_gradients(&mut self) -> bool {
+        if self.config.minibatch_size <= 1 && self.num_accumulated == 0 {
+            return true;
+        }
+        let gradients = self.net.learnable_weights_gradients();
+        let backend = self.worker.backend();
+        if self.num_accumulated == 0 {
+            let device = IBackend::device(backend);
+            self.accumulated_gradients = gradients.iter()
+                .map(|gradient| SharedTensor::<f32>::new(device, gradient.read().unwrap().desc()).unwrap())
+                .collect();
+            for (accumulated, gradient) in self.accumulated_gradients.iter_mut().zip(gradients.iter()) {
+                backend.copy(&mut gradient.write().unwrap(), accumulated).unwrap();
+            }
+        } else {
+            let mut one = native_scalar(1f32);
+            for (accumulated, gradient) in self.accumulated_gradients.iter_mut().zip(gradients.iter()) {
+                backend.axpy(&mut one, &mut gradient.write().unwrap(), accumulated).unwrap();
+            }
+        }
+        self.num_accumulated += 1;
+        if self.num_accumulated < self.config.minibatch_size {
+            log_event!(Subsystem::Solver, LogLevel::Debug, "accumulate_gradients",
+                       sub_batches = self.num_accumulated, minibatch_size = self.config.minibatch_size);
+            return false;
+        }
+
+        let mut scale = native_scalar(1f32 / self.num_accumulated as f32);
+        for (gradient, mut accumulated) in gradients.iter().zip(self.accumulated_gradients.drain(..)) {
+            backend.scal(&mut scale, &mut accumulated).unwrap();
+            backend.copy(&mut accumulated, &mut gradient.write().unwrap()).unwrap();
+        }
+        self.num_accumulated = 0;
+        true
+    }
+
+    /// Compute and apply the weight update from the current gradients of the network.
+    fn apply_update(&mut self) {
         self.worker.compute_update(&self.config, &mut self.net, self.iter);
         self.net.update_weights(self.worker.backend());
         self.iter += 1;
+    }
 
-        network_out
+    /// Returns the current iteration, i.e. the number of times the weights have been updated.
+    pub fn iteration(&self) -> usize {
+        self.iter
+    }
+
+    /// Set the current iteration, e.g. when resuming training from a checkpoint.
+    ///
+    /// The iteration determines the learning rate and momentum of the [SolverConfig][1] policies.
+    /// [1]: ./struct.SolverConfig.html
+    pub fn set_iteration(&mut self, iter: usize) {
+        self.iter = iter;
+    }
+
+    /// Set the base learning rate of the solver.
+    ///
+    /// This replaces the `base_lr` of the [config][1], so the configured [LRPolicy][2] is
+    /// still applied on top of it. Use [LRPolicy::Fixed][2] to control the learning rate
+    /// completely from outside of the solver, e.g. for custom schedules.
+    /// The learning rates of [parameter groups][3] are not affected.
+    /// [1]: #method.config
+    /// [2]: ./enum.LRPolicy.html
+    /// [3]: ./struct.SolverConfig.html#structfield.param_groups
+    pub fn set_learning_rate(&mut self, lr: f32) {
+        self.config.base_lr = lr;
+    }
+
+    /// Returns the number of epochs that have been completed by [fit][1].
+    /// [1]: #method.fit
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    /// Returns the configuration of the solver.
+    pub fn config(&self) -> &SolverConfig {
+        &self.config
+    }
+
+    /// Returns the configuration of the solver, e.g. for adjusting the learning rate between epochs.
+    pub fn mut_config(&mut self) -> &mut SolverConfig {
+        &mut self.config
     }
 
     /// Returns the network trained by the solver.
@@ -103,6 +1011,83 @@ impl<SolverB: IBackend + SolverOps<f32> + 'static, B: IBackend + LayerOps<f32> +
     }
 }
 
+#[derive(Debug)]
+/// An objective of the Solver and the network outputs it is applied to.
+struct WeightedObjective<SolverB: IBackend> {
+    loss: Box<ILoss<SolverB>>,
+    weight: f32,
+    output_ids: Vec<usize>,
+    num_targets: usize,
+}
+
+/// Compute the weighted sum of the losses of `objectives` for the network outputs.
+///
+/// See [compute_losses][1].
+/// [1]: ./fn.compute_losses.html
+fn compute_loss<SolverB: IBackend>(objectives: &mut [WeightedObjective<SolverB>],
+                                   network_out: &[ArcLock<SharedTensor<f32>>],
+                                   targets: &[ArcLock<SharedTensor<f32>>]) -> f32 {
+    let losses = compute_losses(objectives, network_out, targets);
+    objectives.iter().zip(losses).fold(0f32, |sum, (objective, loss)| sum + objective.weight * loss)
+}
+
+/// Compute the loss of each of the `objectives` for the network outputs.
+///
+/// A single target is used for all objectives that take one target, otherwise the targets
+/// are assigned to the objectives in order.
+///
+/// Panics if the number of targets doesn't match the objectives.
+fn compute_losses<SolverB: IBackend>(objectives: &mut [WeightedObjective<SolverB>],
+                                     network_out: &[ArcLock<SharedTensor<f32>>],
+                                     targets: &[ArcLock<SharedTensor<f32>>]) -> Vec<f32> {
+    let num_targets = objectives.iter().fold(0, |sum, objective| sum + objective.num_targets);
+    let share_target = targets.len() == 1 && objectives.iter().all(|objective| objective.num_targets == 1);
+    if !share_target && targets.len() != num_targets {
+        panic!("Got {} targets for objectives that take {} targets", targets.len(), num_targets);
+    }
+
+    let mut losses = Vec::with_capacity(objectives.len());
+    let mut first_target = 0;
+    for objective in objectives.iter_mut() {
+        let objective_targets = if share_target { targets } else { &targets[first_target..first_target + objective.num_targets] };
+        first_target += objective.num_targets;
+        let outputs = objective.output_ids.iter().map(|&output_id| network_out[output_id].clone()).collect::<Vec<_>>();
+        losses.push(read_loss(&objective.loss.compute_loss_of_outputs(&outputs, objective_targets)));
+    }
+    losses
+}
+
+/// Read the loss from the output of an objective.
+fn read_loss(loss: &ArcLock<SharedTensor<f32>>) -> f32 {
+    // the loss is the first value of the objective output
+    tensor_to_vec(loss)[0]
+}
+
+/// Create a tensor with `values` on the native backend.
+fn native_tensor(shape: &[usize], values: &[f32]) -> ArcLock<SharedTensor<f32>> {
+    let native = native_backend();
+    let mut tensor = SharedTensor::<f32>::new(native.device(), &shape.to_owned()).unwrap();
+    write_to_memory(tensor.get_mut(native.device()).unwrap(), values);
+    Arc::new(RwLock::new(tensor))
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Summary of one epoch of training with [Solver::fit][1].
+/// [1]: ./struct.Solver.html#method.fit
+pub struct EpochSummary {
+    /// The number of the epoch, starting at 1.
+    pub epoch: usize,
+    /// The number of minibatches the network was trained with.
+    pub num_minibatches: usize,
+    /// The average loss of the minibatches, or `0` if no minibatch was trained.
+    pub loss: f32,
+    /// The average loss on the validation dataset after the epoch,
+    /// if trained with [Solver::fit_with_validation][2] and the validation dataset
+    /// has at least one complete minibatch.
+    /// [2]: ./struct.Solver.html#method.fit_with_validation
+    pub validation_loss: Option<f32>,
+}
+
 /// Implementation of a specific Solver.
 ///
 /// See [Solvers][1]
@@ -124,6 +1109,19 @@ pub trait ISolver<SolverB, B: IBackend + LayerOps<f32>> {
     /// [2]: ./struct.Solver.html#method.step
     fn compute_update(&mut self, param: &SolverConfig, network: &mut Layer<B>, iter: usize);
 
+    /// Update the weights of the net, evaluating the objective for other weights if required.
+    ///
+    /// This is used by solvers that search for the step size, like [L-BFGS][1].
+    /// `loss` computes the loss of the current minibatch for the current weights of the network.
+    /// [1]: ../solvers/lbfgs/index.html
+    ///
+    /// Returns `false` if the weights have not been updated, in which case the update from
+    /// [compute_update][2] is applied. This is the default.
+    /// [2]: #tymethod.compute_update
+    fn update_with_loss(&mut self, param: &SolverConfig, network: &mut Layer<B>, iter: usize, loss: &mut FnMut(&mut Layer<B>) -> f32) -> bool {
+        false
+    }
+
     /// Returns the backend used by the solver.
     fn backend(&self) -> &SolverB;
 }
@@ -134,6 +1132,75 @@ impl<SolverB, B: IBackend + LayerOps<f32>> ::std::fmt::Debug for ISolver<SolverB
     }
 }
 
+/// Objective that is minimized by a [Solver][1].
+/// [1]: ./struct.Solver.html
+///
+/// Every [Layer][2] can be used as an objective, where the network outputs followed by the
+/// targets are the inputs of the layer. Implementing this trait directly allows using losses
+/// that are not available as a [LayerType][3].
+/// [2]: ../layer/struct.Layer.html
+/// [3]: ../layer/enum.LayerType.html
+///
+/// An objective is applied to one or more network outputs and takes one or more targets,
+/// see [ObjectiveConfig][4].
+/// [4]: ./struct.ObjectiveConfig.html
+pub trait ILoss<B: IBackend> {
+    /// Compute the loss of the network output `output` for the expected `target`.
+    fn compute_loss(&mut self, output: ArcLock<SharedTensor<f32>>, target: ArcLock<SharedTensor<f32>>) -> ArcLock<SharedTensor<f32>>;
+
+    /// Compute the gradient of the loss w.r.t. the network output of the last
+    /// call to [compute_loss][1].
+    /// [1]: #tymethod.compute_loss
+    fn compute_gradient(&mut self) -> ArcLock<SharedTensor<f32>>;
+
+    /// Compute the loss of the network outputs `outputs` for the expected `targets`.
+    ///
+    /// This is what the Solver calls. The default implementation passes the first output
+    /// and target to [compute_loss][1]; objectives for several outputs or targets override
+    /// it together with [compute_gradients][2].
+    /// [1]: #tymethod.compute_loss
+    /// [2]: #method.compute_gradients
+    fn compute_loss_of_outputs(&mut self, outputs: &[ArcLock<SharedTensor<f32>>], targets: &[ArcLock<SharedTensor<f32>>]) -> ArcLock<SharedTensor<f32>> {
+        self.compute_loss(outputs[0].clone(), targets[0].clone())
+    }
+
+    /// Compute the gradients of the loss w.r.t. the network outputs of the last
+    /// call to [compute_loss_of_outputs][1], in the same order.
+    /// [1]: #method.compute_loss_of_outputs
+    ///
+    /// Further gradients, e.g. w.r.t. the targets, are ignored.
+    /// The default implementation returns the gradient of [compute_gradient][2].
+    /// [2]: #tymethod.compute_gradient
+    fn compute_gradients(&mut self) -> Vec<ArcLock<SharedTensor<f32>>> {
+        vec![self.compute_gradient()]
+    }
+}
+
+impl<B: IBackend> ::std::fmt::Debug for ILoss<B> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "({})", "ILoss")
+    }
+}
+
+impl<B: IBackend> ILoss<B> for Layer<B> {
+    fn compute_loss(&mut self, output: ArcLock<SharedTensor<f32>>, target: ArcLock<SharedTensor<f32>>) -> ArcLock<SharedTensor<f32>> {
+        self.forward(&[output, target])[0].clone()
+    }
+
+    fn compute_gradient(&mut self) -> ArcLock<SharedTensor<f32>> {
+        self.backward(&[])[0].clone()
+    }
+
+    fn compute_loss_of_outputs(&mut self, outputs: &[ArcLock<SharedTensor<f32>>], targets: &[ArcLock<SharedTensor<f32>>]) -> ArcLock<SharedTensor<f32>> {
+        let inputs = outputs.iter().chain(targets.iter()).cloned().collect::<Vec<_>>();
+        self.forward(&inputs)[0].clone()
+    }
+
+    fn compute_gradients(&mut self) -> Vec<ArcLock<SharedTensor<f32>>> {
+        self.backward(&[])
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Configuration for a Solver
 pub struct SolverConfig {
@@ -144,11 +1211,33 @@ pub struct SolverConfig {
     pub network: LayerConfig,
     /// The [LayerConfig][1] that is used to initialize the objective.
     /// [1]: ../layer/struct.LayerConfig.html
+    ///
+    /// The objective is applied to the first output of the network.
+    /// It is not used if `objectives` is not empty.
     pub objective: LayerConfig,
+    /// Several weighted objectives, each applied to named outputs of the network.
+    ///
+    /// This allows training multi-task networks, where e.g. one output classifies the input
+    /// and another regresses a bounding box. The loss that is minimized is the weighted sum
+    /// of the losses of all objectives. See [ObjectiveConfig][1].
+    /// [1]: ./struct.ObjectiveConfig.html
+    ///
+    /// If empty, only `objective` is used.
+    ///
+    /// Default: []
+    pub objectives: Vec<ObjectiveConfig>,
     /// The [Solver implementation][1] to be used.
     /// [1]: ../solvers/index.html
     pub solver: SolverKind,
-    /// Accumulate gradients over `minibatch_size` instances.
+    /// Accumulate gradients over `minibatch_size` sub-batches before the weights are updated.
+    ///
+    /// Every call to e.g. [Solver::train_minibatch][1] computes the gradients of one sub-batch
+    /// with the batch size of the network. Only every `minibatch_size`-th call updates the
+    /// weights with the average of the accumulated gradients and counts as an iteration,
+    /// which allows training with larger minibatches than fit into memory at once.
+    /// Solvers that evaluate the objective themselves, like L-BFGS, only evaluate it
+    /// on the last sub-batch.
+    /// [1]: ./struct.Solver.html#method.train_minibatch
     ///
     /// Default: 1
     pub minibatch_size: usize,
@@ -213,6 +1302,128 @@ pub struct SolverConfig {
     ///
     /// Default: 0
     pub momentum: f32,
+    /// The momentum policy to be used.
+    ///
+    /// With a policy other than `Fixed` the momentum changes from `momentum`
+    /// to `max_momentum` over the course of training.
+    ///
+    /// Default: Fixed
+    pub momentum_policy: MomentumPolicy,
+    /// The momentum that is reached by the momentum policy.
+    ///
+    /// Default: 0.99
+    pub max_momentum: f32,
+    /// The number of iterations used by the momentum policy.
+    ///
+    /// Default: 1000
+    pub momentum_stepsize: usize,
+    /// Add annealed [gaussian noise][1] to the gradients before each weight update.
+    /// [1]: http://arxiv.org/abs/1511.06807
+    ///
+    /// The noise can help to train very deep networks with plain SGD.
+    /// See [GradientNoiseConfig][2] for how the noise is annealed.
+    ///
+    /// [2]: ./struct.GradientNoiseConfig.html
+    ///
+    /// If set to `None` no noise is added.
+    ///
+    /// Default: None
+    pub gradient_noise: Option<GradientNoiseConfig>,
+    /// The trust coefficient of the [LARS solver][1].
+    /// [1]: ../solvers/sgd/lars/index.html
+    ///
+    /// It scales the ratio of the weight norm to the gradient norm
+    /// that is used as local learning rate of each weight blob.
+    ///
+    /// Default: 0.001
+    pub trust_coefficient: f32,
+    /// The number of past iterations the [L-BFGS solver][1] uses to approximate the curvature.
+    /// [1]: ../solvers/lbfgs/index.html
+    ///
+    /// Default: 10
+    pub history_size: usize,
+    /// Reduce the learning rate when the validation loss stops improving.
+    ///
+    /// The validation losses are reported by [Solver::fit_with_validation][1]
+    /// or manually with [Solver::report_validation_loss][2].
+    /// See [PlateauConfig][3] for when and by how much the `base_lr` is reduced.
+    ///
+    /// [1]: ./struct.Solver.html#method.fit_with_validation
+    /// [2]: ./struct.Solver.html#method.report_validation_loss
+    /// [3]: ./struct.PlateauConfig.html
+    ///
+    /// If set to `None` the validation loss does not affect the learning rate.
+    ///
+    /// Default: None
+    pub reduce_lr_on_plateau: Option<PlateauConfig>,
+    /// Stop [Solver::fit_with_validation][1] when the validation loss has not improved
+    /// for `early_stopping_patience` epochs.
+    ///
+    /// [1]: ./struct.Solver.html#method.fit_with_validation
+    ///
+    /// If set to `None` all epochs are trained.
+    ///
+    /// Default: None
+    pub early_stopping_patience: Option<usize>,
+    /// Groups of weights that are trained with their own learning rate, momentum or weight decay.
+    ///
+    /// The weights of a layer belong to the first [ParamGroup][1] whose pattern matches the
+    /// name of the layer. Weights that belong to no group use the global settings.
+    /// This allows e.g. fine-tuning the pretrained part of a network with a smaller learning rate
+    /// than a freshly initialized head.
+    /// [1]: ./struct.ParamGroup.html
+    ///
+    /// Parameter groups are used by the [SGD solvers][2].
+    /// [2]: ../solvers/sgd/index.html
+    ///
+    /// Default: []
+    pub param_groups: Vec<ParamGroup>,
+    /// The name of the first layer of the network that should be trained.
+    ///
+    /// All layers before it are only used in the forward pass;
+    /// they are skipped during backpropagation and their weights are not updated.
+    /// This is useful for e.g. fine-tuning only the head of a pretrained network.
+    ///
+    /// If set to `None` the whole network is trained.
+    ///
+    /// Default: None
+    pub train_from: Option<String>,
+    /// A file with the weights of a [saved][1] Layer to start training from, e.g. to fine-tune
+    /// a pretrained network or to resume training.
+    /// [1]: ../layer/struct.Layer.html#method.save
+    ///
+    /// The weights are copied into the network when the Solver is created, matched by name
+    /// (see [Layer::transplant_weights][2]). The network is still created from `network`,
+    /// so its architecture may differ from the saved one; weights without a saved counterpart
+    /// keep their initial values and are logged as warnings.
+    /// [2]: ../layer/struct.Layer.html#method.transplant_weights
+    ///
+    /// If set to `None` the network starts with freshly initialized weights.
+    ///
+    /// Default: None
+    pub weights_path: Option<PathBuf>,
+    /// The number of iterations after which the [metrics][1] of the Solver are reported and reset.
+    /// [1]: ./struct.Solver.html#method.add_metric
+    ///
+    /// If set to `None` the metrics accumulate until [Solver::report_metrics][2] is called.
+    /// [2]: ./struct.Solver.html#method.report_metrics
+    ///
+    /// Default: None
+    pub metrics_interval: Option<usize>,
+    /// The factor the running average of the losses of [Solver::partial_fit][1] is decayed with
+    /// for every sample, so recent samples count more than old ones.
+    /// [1]: ./struct.Solver.html#method.partial_fit
+    ///
+    /// E.g. with a decay of `0.99` the average mostly covers the last few hundred samples,
+    /// which tracks a changing distribution of the data. If set to `None` all samples count equally.
+    /// The decay has to be in the range `[0, 1)`.
+    ///
+    /// It only affects the reported [Solver::online_loss][2]; the weight updates of `partial_fit`
+    /// are computed by the configured solver like for any other minibatch.
+    /// [2]: ./struct.Solver.html#method.online_loss
+    ///
+    /// Default: None
+    pub online_decay: Option<f32>,
 }
 
 impl Default for SolverConfig {
@@ -221,6 +1432,7 @@ impl Default for SolverConfig {
             name: "".to_owned(),
             network: LayerConfig::new("default", SequentialConfig::default()),
             objective: LayerConfig::new("default", SequentialConfig::default()),
+            objectives: Vec::new(),
             solver: SolverKind::SGD(SGDKind::Momentum),
 
             minibatch_size: 1,
@@ -236,6 +1448,20 @@ impl Default for SolverConfig {
             regularization_method: None,
 
             momentum: 0f32,
+            momentum_policy: MomentumPolicy::Fixed,
+            max_momentum: 0.99f32,
+            momentum_stepsize: 1000,
+            gradient_noise: None,
+            trust_coefficient: 0.001f32,
+            history_size: 10,
+            reduce_lr_on_plateau: None,
+            early_stopping_patience: None,
+            param_groups: Vec::new(),
+
+            train_from: None,
+            weights_path: None,
+            metrics_interval: None,
+            online_decay: None,
         }
     }
 }
@@ -254,13 +1480,31 @@ impl SolverConfig {
     /// [2]: ./struct.Solver.html
     /// [3]: ../solvers/index.html
     pub fn get_learning_rate(&self, iter: usize) -> f32 {
+        self.scheduled_learning_rate(self.base_lr(), iter)
+    }
+
+    /// Return the learning rate for the weights of the layer `layer_name` for a supplied iteration.
+    ///
+    /// If the layer belongs to a [parameter group][1] with its own `lr`,
+    /// it is used instead of the `base_lr` for the [LRPolicy][2].
+    /// [1]: #structfield.param_groups
+    /// [2]: ./enum.LRPolicy.html
+    pub fn get_layer_learning_rate(&self, layer_name: &str, iter: usize) -> f32 {
+        match self.param_group(layer_name).and_then(|group| group.lr) {
+            Some(lr) => self.scheduled_learning_rate(lr, iter),
+            None => self.get_learning_rate(iter),
+        }
+    }
+
+    /// Apply the learning rate policy to `base_lr`.
+    fn scheduled_learning_rate(&self, base_lr: f32, iter: usize) -> f32 {
         match self.lr_policy() {
             LRPolicy::Fixed => {
-                self.base_lr()
+                base_lr
             }
             LRPolicy::Step => {
                 let current_step = self.step(iter);
-                self.base_lr() * self.gamma().powf(current_step as f32)
+                base_lr * self.gamma().powf(current_step as f32)
             }
             // LRPolicy::Multistep => {
             //     // TODO: the current step can be calculated on-demand
@@ -275,7 +1519,7 @@ impl SolverConfig {
             //     unimplemented!();
             // }
             LRPolicy::Exp => {
-                self.base_lr() * self.gamma().powf(iter as f32)
+                base_lr * self.gamma().powf(iter as f32)
             }
             // LRPolicy::Inv => {
             //     //   rate = this->param_.base_lr() *
@@ -298,6 +1542,64 @@ impl SolverConfig {
         }
     }
 
+    /// Return the momentum for a supplied iteration.
+    ///
+    /// The way the momentum is calculated depends on the configured [MomentumPolicy][1].
+    ///
+    /// [1]: ./enum.MomentumPolicy.html
+    ///
+    /// Used by the [SGD solvers][2] to calculate the momentum for the current iteration.
+    ///
+    /// [2]: ../solvers/sgd/index.html
+    pub fn get_momentum(&self, iter: usize) -> f32 {
+        self.scheduled_momentum(self.momentum, iter)
+    }
+
+    /// Return the momentum for the weights of the layer `layer_name` for a supplied iteration.
+    ///
+    /// If the layer belongs to a [parameter group][1] with its own `momentum`,
+    /// it is used instead of the global `momentum` for the [MomentumPolicy][2].
+    /// [1]: #structfield.param_groups
+    /// [2]: ./enum.MomentumPolicy.html
+    pub fn get_layer_momentum(&self, layer_name: &str, iter: usize) -> f32 {
+        match self.param_group(layer_name).and_then(|group| group.momentum) {
+            Some(momentum) => self.scheduled_momentum(momentum, iter),
+            None => self.get_momentum(iter),
+        }
+    }
+
+    /// Apply the momentum policy to `momentum`.
+    fn scheduled_momentum(&self, momentum: f32, iter: usize) -> f32 {
+        match self.momentum_policy {
+            MomentumPolicy::Fixed => {
+                momentum
+            }
+            MomentumPolicy::Step => {
+                if iter < self.momentum_stepsize { momentum } else { self.max_momentum }
+            }
+            MomentumPolicy::Linear => {
+                let progress = (iter as f32 / self.momentum_stepsize as f32).min(1f32);
+                momentum + (self.max_momentum - momentum) * progress
+            }
+        }
+    }
+
+    /// Return the weight decay for the weights of the layer `layer_name`.
+    ///
+    /// The `weight_decay` of the [parameter group][1] of the layer takes precedence
+    /// over the global [weight_decay][2].
+    /// [1]: #structfield.param_groups
+    /// [2]: #structfield.weight_decay
+    pub fn get_layer_weight_decay(&self, layer_name: &str) -> Option<f32> {
+        self.param_group(layer_name).and_then(|group| group.weight_decay).or(self.weight_decay)
+    }
+
+    /// Return the first [parameter group][1] that matches the layer `layer_name`.
+    /// [1]: #structfield.param_groups
+    pub fn param_group(&self, layer_name: &str) -> Option<&ParamGroup> {
+        self.param_groups.iter().find(|group| group.matches(layer_name))
+    }
+
     /// Return current step at iteration `iter`.
     ///
     /// Small helper for learning rate calculation.
@@ -333,6 +1635,10 @@ pub enum SolverKind {
     /// See [SGDKind][1] for all available SGD solvers.
     /// [1]: ./enum.SGDKind.html
     SGD(SGDKind),
+    /// Limited-memory BFGS for full-batch training.
+    /// See [implementation][1]
+    /// [1]: ../solvers/lbfgs/index.html
+    LBFGS,
 }
 
 impl SolverKind {
@@ -342,6 +1648,9 @@ impl SolverKind {
             SolverKind::SGD(sgd) => {
                 sgd.with_config(backend, config)
             }
+            SolverKind::LBFGS => {
+                Box::new(LBFGS::<B>::new(backend))
+            }
         }
     }
 }
@@ -352,6 +1661,10 @@ pub enum SGDKind {
     /// Stochastic Gradient Descent with Momentum. See [implementation][1]
     /// [1] ../solvers/
     Momentum,
+    /// Stochastic Gradient Descent with Momentum and Layer-wise Adaptive Rate Scaling.
+    /// See [implementation][1]
+    /// [1]: ../solvers/sgd/lars/index.html
+    LARS,
 }
 
 impl SGDKind {
@@ -361,6 +1674,9 @@ impl SGDKind {
             SGDKind::Momentum => {
                 Box::new(Momentum::<B>::new(backend))
             }
+            SGDKind::LARS => {
+                Box::new(LARS::<B>::new(backend))
+            }
         }
     }
 }
@@ -396,6 +1712,25 @@ pub enum LRPolicy {
     // Sigmoid,
 }
 
+#[derive(Debug, Copy, Clone)]
+/// Momentum Policy for the [SGD solvers][1]
+/// [1]: ../solvers/sgd/index.html
+///
+/// The variables mentioned below are defined in the [SolverConfig][2] apart from
+/// iter, which is the current iteration of the solver, that is supplied as a parameter
+/// for the momentum calculation.
+///
+/// [2]: ./struct.SolverConfig.html
+pub enum MomentumPolicy {
+    /// always return momentum
+    Fixed,
+    /// return momentum until iter reaches momentum_stepsize, then max_momentum
+    Step,
+    /// increase linearly from momentum to max_momentum over momentum_stepsize iterations.
+    /// return momentum + (max_momentum - momentum) * min(iter / momentum_stepsize, 1)
+    Linear,
+}
+
 #[derive(Debug, Copy, Clone)]
 /// [Regularization][1] method for a [Solver][2].
 /// [1]: https://cs231n.github.io/neural-networks-2/#reg
@@ -404,3 +1739,178 @@ pub enum RegularizationMethod {
     /// L2 regularization
     L2,
 }
+
+#[derive(Debug, Copy, Clone)]
+/// Configures the gaussian noise that is added to the gradients by a [Solver][1].
+/// [1]: ./struct.Solver.html
+///
+/// The noise at iteration `iter` has a mean of `0` and the variance
+/// `eta / (1 + iter) ^ gamma`, so it decays over the course of training.
+pub struct GradientNoiseConfig {
+    /// The variance of the noise at the first iteration.
+    ///
+    /// Default: 0.01
+    pub eta: f32,
+    /// The rate at which the variance decays.
+    ///
+    /// Default: 0.55
+    pub gamma: f32,
+}
+
+impl GradientNoiseConfig {
+    /// Return the standard deviation of the noise for a supplied iteration.
+    pub fn std_dev(&self, iter: usize) -> f32 {
+        (self.eta / (1f32 + iter as f32).powf(self.gamma)).sqrt()
+    }
+}
+
+impl Default for GradientNoiseConfig {
+    fn default() -> GradientNoiseConfig {
+        GradientNoiseConfig {
+            eta: 0.01f32,
+            gamma: 0.55f32,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+/// Configures how a [Solver][1] reduces the learning rate when the validation loss stops improving.
+/// [1]: ./struct.Solver.html#method.report_validation_loss
+pub struct PlateauConfig {
+    /// The factor the `base_lr` is multiplied with on a plateau.
+    ///
+    /// Default: 0.1
+    pub factor: f32,
+    /// The number of reported validation losses without improvement that are tolerated
+    /// before the learning rate is reduced.
+    ///
+    /// Default: 10
+    pub patience: usize,
+    /// The relative decrease of the validation loss compared to the best loss so far
+    /// that counts as improvement.
+    ///
+    /// Default: 0.0001
+    pub threshold: f32,
+    /// The lower bound for the `base_lr`.
+    ///
+    /// Default: 0
+    pub min_lr: f32,
+}
+
+impl Default for PlateauConfig {
+    fn default() -> PlateauConfig {
+        PlateauConfig {
+            factor: 0.1f32,
+            patience: 10,
+            threshold: 0.0001f32,
+            min_lr: 0f32,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An objective that is applied to one or more outputs of the network.
+///
+/// See [SolverConfig.objectives][1].
+/// [1]: ./struct.SolverConfig.html#structfield.objectives
+///
+/// The inputs of the objective are the network outputs in the order of `outputs`,
+/// followed by its targets.
+pub struct ObjectiveConfig {
+    /// The [LayerConfig][2] that is used to initialize the objective.
+    /// [2]: ../layer/struct.LayerConfig.html
+    pub objective: LayerConfig,
+    /// The factor the loss and the gradients of the objective are multiplied with.
+    ///
+    /// Default: 1
+    pub weight: f32,
+    /// The names of the network outputs the objective is applied to,
+    /// see [Layer::output_names][3].
+    /// [3]: ../layer/struct.Layer.html#method.output_names
+    pub outputs: Vec<String>,
+    /// The number of targets the objective takes.
+    ///
+    /// Default: 1
+    pub num_targets: usize,
+}
+
+impl ObjectiveConfig {
+    /// Create an objective with weight 1 for the network output `output` and one target.
+    pub fn new(objective: LayerConfig, output: &str) -> ObjectiveConfig {
+        Self::with_outputs(objective, &[output], 1)
+    }
+
+    /// Create an objective with weight 1 for the network outputs `outputs` and `num_targets` targets.
+    pub fn with_outputs(objective: LayerConfig, outputs: &[&str], num_targets: usize) -> ObjectiveConfig {
+        ObjectiveConfig {
+            objective: objective,
+            weight: 1f32,
+            outputs: outputs.iter().map(|&output| output.to_owned()).collect(),
+            num_targets: num_targets,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A group of weights that is trained with its own settings.
+///
+/// See [SolverConfig.param_groups][1].
+/// [1]: ./struct.SolverConfig.html#structfield.param_groups
+pub struct ParamGroup {
+    /// The pattern for the names of the layers whose weights belong to the group.
+    ///
+    /// A `*` in the pattern matches any sequence of characters,
+    /// e.g. `conv*` matches all layers whose name starts with `conv`.
+    pub layers: String,
+    /// The base learning rate of the group, replacing [SolverConfig.base_lr][1].
+    /// [1]: ./struct.SolverConfig.html#structfield.base_lr
+    ///
+    /// Default: None
+    pub lr: Option<f32>,
+    /// The momentum of the group, replacing [SolverConfig.momentum][2].
+    /// [2]: ./struct.SolverConfig.html#structfield.momentum
+    ///
+    /// Default: None
+    pub momentum: Option<f32>,
+    /// The weight decay of the group, replacing [SolverConfig.weight_decay][3].
+    /// [3]: ./struct.SolverConfig.html#structfield.weight_decay
+    ///
+    /// Default: None
+    pub weight_decay: Option<f32>,
+}
+
+impl ParamGroup {
+    /// Create a group for the layers matching `layers` that uses the global settings.
+    pub fn new(layers: &str) -> ParamGroup {
+        ParamGroup {
+            layers: layers.to_owned(),
+            lr: None,
+            momentum: None,
+            weight_decay: None,
+        }
+    }
+
+    /// Returns whether the layer `layer_name` belongs to the group.
+    pub fn matches(&self, layer_name: &str) -> bool {
+        let mut parts = self.layers.split('*');
+        let first = parts.next().unwrap_or("");
+        if !layer_name.starts_with(first) {
+            return false;
+        }
+        let mut rest = &layer_name[first.len()..];
+        let parts = parts.collect::<Vec<_>>();
+        match parts.split_last() {
+            // the pattern contains no wildcard
+            None => rest.is_empty(),
+            Some((last, middle)) => {
+                for part in middle {
+                    match rest.find(part) {
+                        Some(position) => rest = &rest[position + part.len()..],
+                        None => return false,
+                    }
+                }
+                rest.ends_with(last)
+            }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Provides a search over the hyperparameters of a [SolverConfig][1].
+//! [1]: ../struct.SolverConfig.html
+//!
+//! A [ParamSearch][2] trains one [Solver][3] for every combination of the given learning rates,
+//! momentums and layer output sizes and compares them by their validation loss.
+//! Instead of trying the complete grid, a random selection of the combinations can be trained
+//! with [ParamSearch::random][4].
+//! [2]: ./struct.ParamSearch.html
+//! [3]: ../struct.Solver.html
+//! [4]: ./struct.ParamSearch.html#method.random
+use std::rc::Rc;
+use rand::Rng;
+use co::prelude::*;
+use layer::{LayerConfig, LayerType};
+use logging::Subsystem;
+use log::LogLevel;
+use rng::RngStream;
+use util::{LayerOps, SolverOps};
+use super::{EpochSummary, IDataset, Solver, SolverConfig};
+
+#[derive(Debug, Clone)]
+/// One combination of hyperparameters tried by a [ParamSearch][1].
+/// [1]: ./struct.ParamSearch.html
+pub struct Candidate {
+    /// The learning rate, see [SolverConfig.base_lr][1].
+    /// [1]: ../struct.SolverConfig.html#structfield.base_lr
+    pub base_lr: f32,
+    /// The momentum, see [SolverConfig.momentum][1].
+    /// [1]: ../struct.SolverConfig.html#structfield.momentum
+    pub momentum: f32,
+    /// The output size of each searched layer, by layer name.
+    pub output_sizes: Vec<(String, usize)>,
+}
+
+impl Candidate {
+    /// Return a copy of `base` with the hyperparameters of the Candidate.
+    ///
+    /// The output sizes are set on the Linear and FusedLinear layers of the Sequential network
+    /// of `base` with the same name.
+    ///
+    /// Returns an error if the network is not a Sequential container or one of the layers
+    /// can not be found or has no output size.
+    pub fn apply(&self, base: &SolverConfig) -> Result<SolverConfig, String> {
+        let mut config = base.clone();
+        config.base_lr = self.base_lr;
+        config.momentum = self.momentum;
+        for &(ref layer_name, output_size) in &self.output_sizes {
+            try!(set_output_size(&mut config.network, layer_name, output_size));
+        }
+        Ok(config)
+    }
+}
+
+fn set_output_size(network: &mut LayerConfig, layer_name: &str, output_size: usize) -> Result<(), String> {
+    let layers = match network.layer_type {
+        LayerType::Sequential(ref mut sequential_config) => &mut sequential_config.layers,
+        _ => return Err("Output sizes can only be searched for a Sequential network".to_owned()),
+    };
+    let layer = match layers.iter_mut().find(|layer| layer.name == layer_name) {
+        Some(layer) => layer,
+        None => return Err(format!("The network has no layer named {}", layer_name)),
+    };
+    match layer.layer_type {
+        LayerType::Linear(ref mut linear_config) => linear_config.output_size = output_size,
+        LayerType::FusedLinear(ref mut linear_config) => linear_config.output_size = output_size,
+        _ => return Err(format!("Layer {} has no output size", layer_name)),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+/// The outcome of training one [Candidate][1].
+/// [1]: ./struct.Candidate.html
+pub struct SearchResult {
+    /// The hyperparameters the Solver was trained with.
+    pub candidate: Candidate,
+    /// The lowest validation loss after any epoch.
+    pub validation_loss: f32,
+    /// The summaries of all epochs the Candidate was trained for.
+    pub epochs: Vec<EpochSummary>,
+}
+
+#[derive(Debug, Clone)]
+/// Searches for the hyperparameters with the lowest validation loss.
+///
+/// Every Candidate is trained with [Solver::fit_with_validation][1] for at most `epochs` epochs.
+/// With [patience][2] set, the training of a Candidate stops early once its validation loss
+/// has not improved for that many epochs (see [SolverConfig.early_stopping_patience][3]).
+/// [1]: ../struct.Solver.html#method.fit_with_validation
+/// [2]: #method.patience
+/// [3]: ../struct.SolverConfig.html#structfield.early_stopping_patience
+pub struct ParamSearch {
+    epochs: usize,
+    patience: Option<usize>,
+    learning_rates: Vec<f32>,
+    momentums: Vec<f32>,
+    output_sizes: Vec<(String, Vec<usize>)>,
+    random: Option<(usize, u64)>,
+}
+
+impl ParamSearch {
+    /// Create a ParamSearch that trains every Candidate for `epochs` epochs.
+    ///
+    /// Hyperparameters without values to search keep the value of the base SolverConfig.
+    pub fn new(epochs: usize) -> ParamSearch {
+        ParamSearch {
+            epochs: epochs,
+            patience: None,
+            learning_rates: Vec::new(),
+            momentums: Vec::new(),
+            output_sizes: Vec::new(),
+            random: None,
+        }
+    }
+
+    /// Stop training a Candidate when its validation loss has not improved for `patience` epochs.
+    ///
+    /// Overrides the `early_stopping_patience` of the base SolverConfig.
+    pub fn patience(mut self, patience: usize) -> ParamSearch {
+        self.patience = Some(patience);
+        self
+    }
+
+    /// Search the learning rates `learning_rates`.
+    pub fn learning_rates(mut self, learning_rates: Vec<f32>) -> ParamSearch {
+        self.learning_rates = learning_rates;
+        self
+    }
+
+    /// Search the momentums `momentums`.
+    pub fn momentums(mut self, momentums: Vec<f32>) -> ParamSearch {
+        self.momentums = momentums;
+        self
+    }
+
+    /// Search the output sizes `output_sizes` of the Linear or FusedLinear layer `layer_name`,
+    /// e.g. the size of a hidden layer.
+    ///
+    /// Can be called for multiple layers.
+    pub fn output_sizes(mut self, layer_name: &str, output_sizes: Vec<usize>) -> ParamSearch {
+        self.output_sizes.push((layer_name.to_owned(), output_sizes));
+        self
+    }
+
+    /// Only train `num_candidates` combinations, chosen at random.
+    ///
+    /// The choice only depends on `seed` (see [rng][1]).
+    /// [1]: ../../rng/index.html
+    pub fn random(mut self, num_candidates: usize, seed: u64) -> ParamSearch {
+        self.random = Some((num_candidates, seed));
+        self
+    }
+
+    /// Return the Candidates that are trained for the base SolverConfig `base`.
+    pub fn candidates(&self, base: &SolverConfig) -> Vec<Candidate> {
+        let learning_rates = if self.learning_rates.is_empty() { vec![base.base_lr] } else { self.learning_rates.clone() };
+        let momentums = if self.momentums.is_empty() { vec![base.momentum] } else { self.momentums.clone() };
+
+        let mut candidates = Vec::new();
+        for &base_lr in &learning_rates {
+            for &momentum in &momentums {
+                candidates.push(Candidate { base_lr: base_lr, momentum: momentum, output_sizes: Vec::new() });
+            }
+        }
+        for &(ref layer_name, ref output_sizes) in &self.output_sizes {
+            let mut combined = Vec::with_capacity(candidates.len() * output_sizes.len());
+            for candidate in &candidates {
+                for &output_size in output_sizes {
+                    let mut candidate = candidate.clone();
+                    candidate.output_sizes.push((layer_name.clone(), output_size));
+                    combined.push(candidate);
+                }
+            }
+            candidates = combined;
+        }
+
+        if let Some((num_candidates, seed)) = self.random {
+            let mut rng = RngStream::new(seed, "param_search");
+            rng.shuffle(&mut candidates);
+            candidates.truncate(num_candidates);
+        }
+        candidates
+    }
+
+    /// Train every Candidate on `dataset` and return the results, starting with the lowest
+    /// validation loss on `validation`.
+    ///
+    /// See [run_with_callback][1].
+    /// [1]: #method.run_with_callback
+    pub fn run<SolverB, B>(&self, net_backend: Rc<B>, obj_backend: Rc<SolverB>, base: &SolverConfig,
+                           dataset: &IDataset, validation: &IDataset) -> Result<Vec<SearchResult>, String>
+        where SolverB: IBackend + SolverOps<f32> + 'static, B: IBackend + LayerOps<f32> + 'static {
+        self.run_with_callback(net_backend, obj_backend, base, dataset, validation, |_, _| {})
+    }
+
+    /// Train every Candidate on `dataset` and return the results, starting with the lowest
+    /// validation loss on `validation`.
+    ///
+    /// Each Candidate is trained with a new Solver created from [Candidate::apply][1] on `base`.
+    /// `callback` is called after every epoch with the Candidate and the [summary][2] of the epoch.
+    /// Returns an error if the Solver for a Candidate can not be created, or if `validation`
+    /// is too small to compute a validation loss.
+    /// [1]: ./struct.Candidate.html#method.apply
+    /// [2]: ../struct.EpochSummary.html
+    pub fn run_with_callback<SolverB, B, F>(&self, net_backend: Rc<B>, obj_backend: Rc<SolverB>, base: &SolverConfig,
+                                            dataset: &IDataset, validation: &IDataset, mut callback: F) -> Result<Vec<SearchResult>, String>
+        where SolverB: IBackend + SolverOps<f32> + 'static, B: IBackend + LayerOps<f32> + 'static,
+              F: FnMut(&Candidate, &EpochSummary) {
+        let mut results = Vec::new();
+        for candidate in self.candidates(base) {
+            let mut config = try!(candidate.apply(base));
+            if self.patience.is_some() {
+                config.early_stopping_patience = self.patience;
+            }
+            let mut solver = try!(Solver::from_config(net_backend.clone(), obj_backend.clone(), &config));
+            let epochs = solver.fit_with_validation(dataset, validation, self.epochs,
+                                                    |_, summary| callback(&candidate, summary));
+
+            let mut validation_losses = epochs.iter().filter_map(|summary| summary.validation_loss);
+            let validation_loss = match validation_losses.next() {
+                Some(first_loss) => validation_losses.fold(first_loss, f32::min),
+                None => return Err(format!("The validation dataset has fewer than {} samples, the batch size of the network",
+                                           solver.network().input_shapes()[0].1[0])),
+            };
+
+            let output_sizes = candidate.output_sizes.iter()
+                                        .map(|&(ref layer_name, output_size)| format!("{}={}", layer_name, output_size))
+                                        .collect::<Vec<_>>().join(",");
+            log_event!(Subsystem::Solver, LogLevel::Info, "search_candidate", base_lr = candidate.base_lr,
+                       momentum = candidate.momentum, output_sizes = output_sizes, epochs = epochs.len(),
+                       validation_loss = validation_loss);
+            results.push(SearchResult {
+                candidate: candidate,
+                validation_loss: validation_loss,
+                epochs: epochs,
+            });
+        }
+
+        results.sort_by(|a, b| a.validation_loss.partial_cmp(&b.validation_loss).unwrap_or(::std::cmp::Ordering::Equal));
+        Ok(results)
+    }
+}
@@ -0,0 +1,128 @@
+//! Provides the schedules for training a network on several tasks.
+//!
+//! In multi-task or curriculum training every task has its own source of minibatches
+//! and its own objective, while all tasks share the weights of the network.
+//! A [TaskSampler][1] chooses the task that is trained in each iteration according to
+//! a [TaskSchedule][2]. See [Solver::train_tasks][3].
+//! [1]: ./struct.TaskSampler.html
+//! [2]: ./enum.TaskSchedule.html
+//! [3]: ../struct.Solver.html#method.train_tasks
+use rand::Rng;
+use rng::{self, RngStream};
+
+#[derive(Debug, Clone)]
+/// Defines which task is trained in each iteration.
+pub enum TaskSchedule {
+    /// Train the tasks in turn.
+    RoundRobin,
+    /// Choose each task with a probability proportional to its weight.
+    Weighted(Vec<f32>),
+    /// Choose each task with a probability proportional to its weight, where the weights
+    /// change linearly from `start` in the first iteration to `end` after `iterations` iterations.
+    ///
+    /// This allows curricula that start with an easy task and shift to harder ones.
+    Curriculum {
+        /// The weights of the tasks in the first iteration.
+        start: Vec<f32>,
+        /// The weights of the tasks from iteration `iterations` on.
+        end: Vec<f32>,
+        /// The number of iterations over which the weights change.
+        iterations: usize,
+    },
+}
+
+impl TaskSchedule {
+    /// Returns the weights of the `num_tasks` tasks in iteration `iter`.
+    ///
+    /// Panics if a schedule with weights doesn't have a weight for every task.
+    pub fn weights(&self, num_tasks: usize, iter: usize) -> Vec<f32> {
+        let weights = match *self {
+            TaskSchedule::RoundRobin => vec![1f32; num_tasks],
+            TaskSchedule::Weighted(ref weights) => weights.clone(),
+            TaskSchedule::Curriculum { ref start, ref end, iterations } => {
+                if start.len() != end.len() {
+                    panic!("The curriculum has {} start weights but {} end weights", start.len(), end.len());
+                }
+                let progress = if iterations == 0 { 1f32 } else { (iter as f32 / iterations as f32).min(1f32) };
+                start.iter().zip(end).map(|(start, end)| start + (end - start) * progress).collect()
+            },
+        };
+        if weights.len() != num_tasks {
+            panic!("The task schedule has {} weights for {} tasks", weights.len(), num_tasks);
+        }
+        weights
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Chooses the task for each iteration of multi-task training.
+///
+/// The random choices are drawn from an [RngStream][1] of the global seed,
+/// so they are reproducible (see [rng][2]).
+/// [1]: ../../rng/struct.RngStream.html
+/// [2]: ../../rng/index.html
+pub struct TaskSampler {
+    schedule: TaskSchedule,
+    rng: RngStream,
+    /// The task that is next in turn with a RoundRobin schedule
+    next_in_turn: usize,
+}
+
+impl TaskSampler {
+    /// Create a TaskSampler that chooses the tasks according to `schedule`.
+    pub fn new(schedule: TaskSchedule) -> TaskSampler {
+        TaskSampler {
+            schedule: schedule,
+            rng: RngStream::new(rng::seed(), "task_sampler"),
+            next_in_turn: 0,
+        }
+    }
+
+    /// Returns the schedule of the sampler.
+    pub fn schedule(&self) -> &TaskSchedule {
+        &self.schedule
+    }
+
+    /// Choose the task that is trained in iteration `iter`.
+    ///
+    /// Only tasks for which `available` is `true` are chosen, e.g. to skip tasks that
+    /// have run out of minibatches. Returns `None` if no available task has a positive weight.
+    pub fn next_task(&mut self, iter: usize, available: &[bool]) -> Option<usize> {
+        let num_tasks = available.len();
+        let task = match self.schedule {
+            TaskSchedule::RoundRobin => {
+                (0..num_tasks).map(|offset| (self.next_in_turn + offset) % num_tasks).find(|&task| available[task])
+            },
+            _ => {
+                let weights = self.schedule.weights(num_tasks, iter).iter().zip(available)
+                    .map(|(&weight, &is_available)| if is_available { weight.max(0f32) } else { 0f32 })
+                    .collect::<Vec<f32>>();
+                let total = weights.iter().fold(0f32, |sum, weight| sum + weight);
+                if total <= 0f32 {
+                    None
+                } else {
+                    let mut remaining = self.rng.gen::<f32>() * total;
+                    let last = weights.iter().rposition(|&weight| weight > 0f32);
+                    weights.iter().position(|&weight| {
+                        remaining -= weight;
+                        weight > 0f32 && remaining < 0f32
+                    }).or(last)
+                }
+            },
+        };
+        if let Some(task) = task {
+            self.next_in_turn = (task + 1) % num_tasks;
+        }
+        task
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Summary of the training of one task with [Solver::train_tasks][1].
+/// [1]: ../struct.Solver.html#method.train_tasks
+pub struct TaskSummary {
+    /// The number of minibatches the network was trained with on the task.
+    pub num_minibatches: usize,
+    /// The average loss of the minibatches, or `None` if the task was not trained.
+    pub loss: Option<f32>,
+}
@@ -0,0 +1,215 @@
+//! A [Limited-memory BFGS][1] solver.
+//! [1]: https://en.wikipedia.org/wiki/Limited-memory_BFGS
+//!
+//! L-BFGS is a quasi-Newton method: instead of following the gradient directly,
+//! it approximates the inverse of the Hessian from the differences of the weights and
+//! gradients of the last [history_size][2] iterations and follows the resulting direction.
+//! This typically needs far fewer iterations than SGD for small, smooth problems like
+//! logistic regression, but the approximation is only meaningful if the gradient is not noisy.
+//! The solver should therefore be used with full-batch training, i.e. by repeatedly
+//! training on the same minibatch that contains the whole dataset.
+//!
+//! When training with [train_minibatch][3] the step size along the direction is determined
+//! by a backtracking line search that evaluates the objective of the minibatch until it has
+//! decreased sufficiently ([Armijo condition][4]). The outputs of the network and the objective
+//! then hold the values of the last evaluation.
+//! Without access to the objective, as with [train_with_gradient][5], a fixed step of the
+//! learning rate is taken instead.
+//!
+//! Weights with a learning rate of `0`, e.g. the layers before [train_from][6], are left unchanged
+//! and a weight that is shared by several layers is updated once.
+//!
+//! [2]: ../../solver/struct.SolverConfig.html#structfield.history_size
+//! [3]: ../../solver/struct.Solver.html#method.train_minibatch
+//! [4]: https://en.wikipedia.org/wiki/Wolfe_conditions#Armijo_rule_and_curvature
+//! [5]: ../../solver/struct.Solver.html#method.train_with_gradient
+//! [6]: ../../solver/struct.SolverConfig.html#structfield.train_from
+use co::prelude::*;
+use layer::*;
+use solver::*;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::Arc;
+use logging::Subsystem;
+use log::LogLevel;
+use util::*;
+
+/// The fraction of the expected decrease of the loss that a line search step has to achieve.
+const ARMIJO_FACTOR: f32 = 1e-4;
+/// The number of times the step size is halved before the line search gives up.
+const MAX_LINE_SEARCH_STEPS: usize = 20;
+
+#[derive(Debug)]
+/// Limited-memory BFGS.
+///
+/// See [module description][1] for more information.
+/// [1]: ./index.html
+pub struct LBFGS<SolverB: IBackend + SolverOps<f32>> {
+    /// The weight differences, gradient differences and their inverse dot product
+    /// of the last iterations, starting with the oldest.
+    history: VecDeque<(Vec<f32>, Vec<f32>, f32)>,
+    /// The weights and gradients of the previous iteration.
+    previous: Option<(Vec<f32>, Vec<f32>)>,
+    /// The backend used for computing the gradient.
+    backend: Rc<SolverB>,
+}
+
+impl<SolverB: IBackend + SolverOps<f32>> LBFGS<SolverB> {
+    /// Create a new L-BFGS solver.
+    ///
+    /// Should not be called directly.
+    /// Use [Solver::from_config][2] instead.
+    ///
+    /// [2]: ../../solver/struct.Solver.html#method.from_config
+    pub fn new(backend: Rc<SolverB>) -> LBFGS<SolverB> {
+        LBFGS {
+            history: VecDeque::new(),
+            previous: None,
+            backend: backend,
+        }
+    }
+
+    /// Record the current weights and gradients and return the search direction.
+    ///
+    /// The weights have to be moved against the returned direction.
+    fn compute_direction(&mut self, config: &SolverConfig, weights: &[f32], gradient: &[f32]) -> Vec<f32> {
+        if let Some((previous_weights, previous_gradient)) = self.previous.take() {
+            let s = weights.iter().zip(&previous_weights).map(|(w, p)| w - p).collect::<Vec<f32>>();
+            let y = gradient.iter().zip(&previous_gradient).map(|(g, p)| g - p).collect::<Vec<f32>>();
+            let sy = dot(&s, &y);
+            // skip updates that would make the approximation not positive definite
+            if sy > 1e-10 {
+                self.history.push_back((s, y, 1f32 / sy));
+                while self.history.len() > config.history_size {
+                    self.history.pop_front();
+                }
+            }
+        }
+        self.previous = Some((weights.to_owned(), gradient.to_owned()));
+
+        // two-loop recursion
+        let mut direction = gradient.to_owned();
+        let mut alphas = Vec::with_capacity(self.history.len());
+        for &(ref s, ref y, rho) in self.history.iter().rev() {
+            let alpha = rho * dot(s, &direction);
+            for (d, y) in direction.iter_mut().zip(y) {
+                *d -= alpha * y;
+            }
+            alphas.push(alpha);
+        }
+        if let Some(&(ref s, ref y, _)) = self.history.back() {
+            let scale = dot(s, y) / dot(y, y);
+            for d in direction.iter_mut() {
+                *d *= scale;
+            }
+        }
+        for (&(ref s, ref y, rho), alpha) in self.history.iter().zip(alphas.into_iter().rev()) {
+            let beta = rho * dot(y, &direction);
+            for (d, s) in direction.iter_mut().zip(s) {
+                *d += (alpha - beta) * s;
+            }
+        }
+        direction
+    }
+}
+
+impl<SolverB: IBackend + SolverOps<f32>, NetB: IBackend + LayerOps<f32> + 'static> ISolver<SolverB, NetB> for LBFGS<SolverB> {
+    fn compute_update(&mut self, config: &SolverConfig, net: &mut Layer<NetB>, iter: usize) {
+        let rate = config.get_learning_rate(iter);
+        let (weights_data, weights_gradient) = trained_weights(net);
+        let weights = read_flat(&weights_data);
+        let gradient = read_flat(&weights_gradient);
+
+        let direction = self.compute_direction(config, &weights, &gradient);
+        let update = direction.iter().map(|d| rate * d).collect::<Vec<f32>>();
+        // the update is applied to all weights, so the others get an update of zero
+        for weight_gradient in net.learnable_weights_gradients() {
+            if !weights_gradient.iter().any(|trained| Arc::ptr_eq(trained, &weight_gradient)) {
+                ::weight::FillerType::Constant { value: 0f32 }.fill(&mut weight_gradient.write().unwrap());
+            }
+        }
+        write_flat(&weights_gradient, &update);
+    }
+
+    fn update_with_loss(&mut self, config: &SolverConfig, net: &mut Layer<NetB>, iter: usize, loss: &mut FnMut(&mut Layer<NetB>) -> f32) -> bool {
+        let (weights_data, weights_gradient) = trained_weights(net);
+        let weights = read_flat(&weights_data);
+        let gradient = read_flat(&weights_gradient);
+        let initial_loss = loss(net);
+
+        let direction = self.compute_direction(config, &weights, &gradient);
+        let expected_decrease = dot(&gradient, &direction);
+        // without curvature information the scale of the direction is unknown
+        let mut step = if self.history.is_empty() {
+            (1f32 / gradient.iter().fold(0f32, |sum, g| sum + g.abs())).min(1f32)
+        } else {
+            1f32
+        };
+
+        for _ in 0..MAX_LINE_SEARCH_STEPS {
+            let candidate = weights.iter().zip(&direction).map(|(w, d)| w - step * d).collect::<Vec<f32>>();
+            write_flat(&weights_data, &candidate);
+            if loss(net) <= initial_loss - ARMIJO_FACTOR * step * expected_decrease {
+                return true;
+            }
+            step *= 0.5f32;
+        }
+
+        log_event!(Subsystem::Solver, LogLevel::Info, "line_search_failed", iteration = iter);
+        write_flat(&weights_data, &weights);
+        self.history.clear();
+        self.previous = None;
+        true
+    }
+
+    fn backend(&self) -> &SolverB {
+        &self.backend
+    }
+}
+
+/// Returns the data and gradients of the weights of `net` that are trained.
+///
+/// Weights with a learning rate of `0` are skipped.
+fn trained_weights<B: IBackend + LayerOps<f32> + 'static>(net: &Layer<B>) -> (Vec<ArcLock<SharedTensor<f32>>>, Vec<ArcLock<SharedTensor<f32>>>) {
+    let mut weights_data = Vec::new();
+    let mut weights_gradient = Vec::new();
+    for ((weight_data, weight_gradient), lr) in net.learnable_weights_data().into_iter()
+                                                   .zip(net.learnable_weights_gradients())
+                                                   .zip(net.learnable_weights_lr()) {
+        if lr == Some(0f32) {
+            continue;
+        }
+        weights_data.push(weight_data);
+        weights_gradient.push(weight_gradient);
+    }
+    (weights_data, weights_gradient)
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).fold(0f32, |sum, (a, b)| sum + a * b)
+}
+
+/// Concatenate the values of `tensors` into one vector.
+fn read_flat(tensors: &[ArcLock<SharedTensor<f32>>]) -> Vec<f32> {
+    let native = native_backend();
+    let mut values = Vec::new();
+    for tensor in tensors {
+        let mut tensor = tensor.write().unwrap();
+        match tensor.add_device(native.device()) { _ => tensor.sync(native.device()).unwrap() }
+        values.extend(tensor.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>());
+    }
+    values
+}
+
+/// Write the concatenated `values` back into `tensors`.
+fn write_flat(tensors: &[ArcLock<SharedTensor<f32>>], values: &[f32]) {
+    let native = native_backend();
+    let mut offset = 0;
+    for tensor in tensors {
+        let mut tensor = tensor.write().unwrap();
+        let size = tensor.desc().size();
+        match tensor.add_device(native.device()) { _ => tensor.sync(native.device()).unwrap() }
+        write_to_memory(tensor.get_mut(native.device()).unwrap(), &values[offset..offset + size]);
+        offset += size;
+    }
+}
@@ -28,13 +28,19 @@
 //! [backprop]: https://en.wikipedia.org/wiki/Backpropagation
 
 #[allow(unused_import_braces)]
-pub use self::sgd::{Momentum};
+pub use self::lbfgs::LBFGS;
+pub use self::sgd::{LARS, Momentum};
+pub mod lbfgs;
 pub mod sgd;
 
 use co::{IBackend, MemoryType, SharedTensor};
+use rand::distributions::{IndependentSample, Normal};
 use conn::NN;
+use logging::Subsystem;
+use log::LogLevel;
 use solver::*;
 use layer::*;
+use rng::RngStream;
 use util::*;
 
 trait SGDSolver<SolverB: IBackend + SolverOps<f32>, NetB: IBackend + LayerOps<f32>> : ISolver<SolverB, NetB> {
@@ -43,7 +49,8 @@ trait SGDSolver<SolverB: IBackend + SolverOps<f32>, NetB: IBackend + LayerOps<f3
                             weight_blob: &ArcLock<SharedTensor<f32>>,
                             history_blob_id: usize,
                             global_lr: &f32,
-                            blob_lr: &f32);
+                            blob_lr: &f32,
+                            momentum: &f32);
 
     /// [Clip gradients][1] when they exceed [SolverConfig.clip_gradients][2].
     /// [1]: http://arxiv.org/abs/1211.5063
@@ -86,11 +93,8 @@ trait SGDSolver<SolverB: IBackend + SolverOps<f32>, NetB: IBackend + LayerOps<f3
             let l2norm_diff = sumsq_diff.sqrt();
             if l2norm_diff > clip_threshold {
                 let scale_factor = clip_threshold / l2norm_diff;
-                info!("Gradient clipping: scaling down gradients (L2 norm {} > {})
-                        by scale factor {}",
-                      l2norm_diff,
-                      clip_threshold,
-                      scale_factor);
+                log_event!(Subsystem::Solver, LogLevel::Info, "clip_gradients",
+                           l2_norm = l2norm_diff, threshold = clip_threshold, scale_factor = scale_factor);
 
                 let mut scale_shared = native_scalar(scale_factor);
 
@@ -102,48 +106,49 @@ trait SGDSolver<SolverB: IBackend + SolverOps<f32>, NetB: IBackend + LayerOps<f3
         }
     }
 
-    /// Scale the gradient to counteract the [SolverConfig.minibatch_size][1]
+    /// Add gaussian noise to the gradient according to [SolverConfig.gradient_noise][1].
     /// [1]: ../solver/struct.SolverConfig.html
     ///
-    /// To counteract that we are accumulating the gradients over multiple samples,
-    /// we need to scale the gradients down to the equivalent of a single sample.</br>
-    /// E.g. with a `minibatch_size` of 4 we need to scale the gradient by 0.25 (= 1/4).
-    fn normalize(&self, config: &SolverConfig, weight_blob: &ArcLock<SharedTensor<f32>>) {
-        if config.minibatch_size > 1 {
-            let scale_factor = 1f32 / config.minibatch_size as f32;
-            let mut gradient = weight_blob.write().unwrap();
+    /// The noise is drawn from `rng`, the [RngStream][2] of the solver, so it is reproducible
+    /// with the [global seed][3]. It is sampled on the CPU, so the gradient is synchronized to native memory.
+    /// [2]: ../rng/struct.RngStream.html
+    /// [3]: ../rng/fn.set_seed.html
+    fn add_gradient_noise(config: &SolverConfig, weight_gradient: &ArcLock<SharedTensor<f32>>, iter: usize, rng: &mut RngStream) {
+        if let Some(noise_config) = config.gradient_noise {
+            let normal = Normal::new(0f64, noise_config.std_dev(iter) as f64);
             let native = native_backend();
 
-            let mut scale_factor_shared = native_scalar(scale_factor);
-            // self.backend().scal_plain(&scale_factor_shared, &mut gradient).unwrap();
-            self.backend().scal(&mut scale_factor_shared, &mut gradient).unwrap();
+            let mut gradient = weight_gradient.write().unwrap();
+            match gradient.add_device(native.device()) { _ => gradient.sync(native.device()).unwrap() }
+            let noisy_gradient = gradient.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>().iter()
+                .map(|value| value + normal.ind_sample(rng) as f32)
+                .collect::<Vec<f32>>();
+            write_to_memory(gradient.get_mut(native.device()).unwrap(), &noisy_gradient);
         }
     }
 
     /// [Regularize][1] the gradient according to the configured [RegularizationMethod][2].
     /// [1]: https://cs231n.github.io/neural-networks-2/#reg
     /// [2]: ../solver/enum.RegularizationMethod.html
-    fn regularize(&self, config: &SolverConfig, weight_gradient: &ArcLock<SharedTensor<f32>>, blob_weight_decay: Option<f32>) {
-        if let Some(global_weight_decay) = config.weight_decay {
-            if let Some(regularization_method) = config.regularization_method {
-                match blob_weight_decay {
-                    Some(weight_decay_mult) => {
-                        let local_decay = global_weight_decay * weight_decay_mult;
-                        match regularization_method {
-                            RegularizationMethod::L2 => {
-                                let native = native_backend();
-                                let decay_shared = native_scalar(local_decay);
-                                let gradient = &mut weight_gradient.write().unwrap();
-                                // gradient.regularize_l2(self.backend(), &decay_shared);
-                                // backend.axpy_plain(&decay_shared, &self.data, &mut self.diff).unwrap();
-                                // TODO: solver
-                                unimplemented!();
-                            }
-                        }
-                    }
-                    None => {
-                        error!("Weight decay multiplier for gradient missing.");
-                    }
+    ///
+    /// `weight_decay` is the decay of the weight blob, i.e. the decay of its layer
+    /// (see [SolverConfig::get_layer_weight_decay][3]) multiplied by its [decay_mult][4].
+    /// [3]: ../solver/struct.SolverConfig.html#method.get_layer_weight_decay
+    /// [4]: ../weight/struct.WeightConfig.html#structfield.decay_mult
+    fn regularize(&self, config: &SolverConfig, weight: &ArcLock<SharedTensor<f32>>, weight_gradient: &ArcLock<SharedTensor<f32>>, weight_decay: Option<f32>) {
+        if let (Some(weight_decay), Some(regularization_method)) = (weight_decay, config.regularization_method) {
+            match regularization_method {
+                RegularizationMethod::L2 => {
+                    let native = native_backend();
+                    let mut weight = weight.write().unwrap();
+                    let mut gradient = weight_gradient.write().unwrap();
+                    match weight.add_device(native.device()) { _ => weight.sync(native.device()).unwrap() }
+                    match gradient.add_device(native.device()) { _ => gradient.sync(native.device()).unwrap() }
+                    let regularized_gradient = gradient.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>().iter()
+                        .zip(weight.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>())
+                        .map(|(g, w)| g + weight_decay * w)
+                        .collect::<Vec<f32>>();
+                    write_to_memory(gradient.get_mut(native.device()).unwrap(), &regularized_gradient);
                 }
             }
         }
@@ -0,0 +1,189 @@
+//! [Layer-wise Adaptive Rate Scaling (LARS)][1] on top of SGD with Momentum.
+//! [1]: https://arxiv.org/abs/1708.03888
+//!
+//! When training with very large minibatches the learning rate has to be scaled up,
+//! which makes training unstable for weights whose gradient is large compared to the weight itself.
+//! LARS computes a local learning rate for every weight blob from the ratio of the
+//! norm of the weight to the norm of its gradient:
+//!
+//! `local_lr = trust_coefficient * ||w|| / (||g|| + weight_decay * ||w||)`
+//!
+//! The update of every blob is then computed like in [Momentum][2] with the
+//! global learning rate multiplied by its local learning rate.
+//! If the weight or its gradient is zero, e.g. for a freshly initialized bias, the trust ratio
+//! is undefined and the blob is updated with the global learning rate alone.
+//!
+//! The weight decay is only used with the [L2 RegularizationMethod][3], in which case
+//! `weight_decay * w` is added to the gradient before any gradient noise.
+//! The weight decay of a blob is scaled by its [decay_mult][4].
+//!
+//! [2]: ../momentum/index.html
+//! [3]: ../../../solver/enum.RegularizationMethod.html
+//! [4]: ../../../weight/struct.WeightConfig.html#structfield.decay_mult
+use co::prelude::*;
+use layer::*;
+use rng::{self, RngStream};
+use solver::*;
+use solvers::SGDSolver;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use util::*;
+
+#[derive(Debug)]
+/// Stochastic Gradient Descent with Momentum and Layer-wise Adaptive Rate Scaling.
+///
+/// See [module description][1] for more information.
+/// [1]: ./index.html
+pub struct LARS<SolverB: IBackend + SolverOps<f32>> {
+    /// The gradient update from the previous iteration for each blob.
+    history: Vec<ArcLock<SharedTensor<f32>>>,
+    /// The backend used for computing the gradient.
+    backend: Rc<SolverB>,
+
+    /// Scalar that temporarily holds learing rate for weight update computations
+    lr: SharedTensor<f32>,
+    /// Scalar that temporarily holds momentum for weight update computations
+    momentum: SharedTensor<f32>,
+    /// The random numbers for the [gradient noise][3], derived from the global seed.
+    /// [3]: ../../../solver/struct.SolverConfig.html#structfield.gradient_noise
+    noise_rng: RngStream,
+}
+
+impl<SolverB: IBackend + SolverOps<f32>> LARS<SolverB> {
+    /// Create a new LARS solver.
+    ///
+    /// Should not be called directly.
+    /// Use [Solver::from_config][2] instead.
+    ///
+    /// [2]: ../../../solver/struct.Solver.html#method.from_config
+    pub fn new(backend: Rc<SolverB>) -> LARS<SolverB> {
+        let (lr, momentum) = {
+            let device = IBackend::device(backend.as_ref());
+
+            (SharedTensor::<f32>::new(device, &1).unwrap(),
+             SharedTensor::<f32>::new(device, &1).unwrap())
+        };
+
+        LARS {
+            history: Vec::new(),
+            backend: backend,
+
+            lr: lr,
+            momentum: momentum,
+            noise_rng: RngStream::new(rng::seed(), "gradient_noise"),
+        }
+    }
+
+    /// Compute the local learning rate of a weight blob and apply the weight decay to its gradient.
+    ///
+    /// Returns `None` if the norm of the weight or its gradient is zero.
+    fn local_learning_rate(config: &SolverConfig, weight: &ArcLock<SharedTensor<f32>>, weight_gradient: &ArcLock<SharedTensor<f32>>, weight_decay: Option<f32>) -> Option<f32> {
+        let native = native_backend();
+        let mut weight = weight.write().unwrap();
+        let mut gradient = weight_gradient.write().unwrap();
+        match weight.add_device(native.device()) { _ => weight.sync(native.device()).unwrap() }
+        match gradient.add_device(native.device()) { _ => gradient.sync(native.device()).unwrap() }
+
+        let weight_values = weight.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>();
+        let weight_norm = weight_values.iter().fold(0f32, |sum, w| sum + w * w).sqrt();
+        let weight_decay = match config.regularization_method {
+            Some(RegularizationMethod::L2) => weight_decay.unwrap_or(0f32),
+            None => 0f32,
+        };
+        let gradient_values = gradient.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned();
+        // the norm of the raw gradient, the weight decay is added separately in the trust ratio
+        let gradient_norm = gradient_values.iter().fold(0f32, |sum, g| sum + g * g).sqrt();
+        let decayed_gradient = gradient_values.iter()
+            .zip(weight_values)
+            .map(|(g, w)| g + weight_decay * w)
+            .collect::<Vec<f32>>();
+        write_to_memory(gradient.get_mut(native.device()).unwrap(), &decayed_gradient);
+
+        if weight_norm > 0f32 && gradient_norm > 0f32 {
+            Some(config.trust_coefficient * weight_norm / (gradient_norm + weight_decay * weight_norm))
+        } else {
+            None
+        }
+    }
+}
+
+impl<B: IBackend + SolverOps<f32>, NetB: IBackend + LayerOps<f32> + 'static> SGDSolver<B, NetB> for LARS<B> {
+    fn compute_update_value(&mut self,
+                            config: &SolverConfig,
+                            weight_gradient: &ArcLock<SharedTensor<f32>>,
+                            history_blob_id: usize,
+                            global_lr: &f32,
+                            blob_lr: &f32,
+                            momentum: &f32) {
+        ::weight::FillerType::Constant {
+            value: global_lr * blob_lr
+        }.fill(&mut self.lr);
+
+        ::weight::FillerType::Constant {
+            value: *momentum
+        }.fill(&mut self.momentum);
+
+        let backend = ISolver::<B, NetB>::backend(self);
+        let device = IBackend::device(backend);
+
+        let history_blob = &self.history[history_blob_id];
+
+        let _ = weight_gradient.write().unwrap().add_device(device);
+        weight_gradient.write().unwrap().sync(device).unwrap();
+        let _ = history_blob.write().unwrap().add_device(device);
+        history_blob.write().unwrap().sync(device).unwrap();
+
+        Axpby::axpby_plain(backend,
+                           &self.lr,
+                           &weight_gradient.read().unwrap(),
+                           &self.momentum,
+                           &mut history_blob.write().unwrap()).unwrap();
+
+        backend.copy_plain(
+            &history_blob.read().unwrap(), &mut weight_gradient.write().unwrap()).unwrap();
+    }
+}
+
+impl<SolverB: IBackend + SolverOps<f32>, NetB: IBackend + LayerOps<f32> + 'static> ISolver<SolverB, NetB> for LARS<SolverB> {
+    /// Initialize the LARS solver, allocating memory for its history.
+    fn init(&mut self, net: &Layer<NetB>) {
+        self.history = Vec::with_capacity(net.learnable_weights_gradients().len());
+
+        for weight_gradient in net.learnable_weights_gradients() {
+            let shape = weight_gradient.read().unwrap().desc().clone();
+            let mut tensor = SharedTensor::new(IBackend::device(&*self.backend),
+                                               &shape).unwrap();
+
+            let filler = ::weight::FillerType::Constant { value: 0f32 };
+            filler.fill(&mut tensor);
+
+            let history_tensor = Arc::new(RwLock::new(tensor));
+            self.history.push(history_tensor);
+        }
+    }
+
+    fn compute_update(&mut self, config: &SolverConfig, net: &mut Layer<NetB>, iter: usize) {
+        SGDSolver::<SolverB, NetB>::clip_gradients(self, config, net);
+        let weights = net.learnable_weights_data();
+        let layer_names = net.learnable_weights_layer_names();
+        let decay_mults = net.learnable_weights_decay_mult();
+        for (weight_id, weight_gradient) in net.learnable_weights_gradients().iter().enumerate() {
+            let layer_name = &layer_names[weight_id];
+            let weight_decay = config.get_layer_weight_decay(layer_name).map(|decay| decay * decay_mults[weight_id]);
+            let learning_rate = config.get_layer_learning_rate(layer_name, iter);
+            let local_rate = Self::local_learning_rate(config, &weights[weight_id], weight_gradient, weight_decay);
+            <Self as SGDSolver<SolverB, NetB>>::add_gradient_noise(config, weight_gradient, iter, &mut self.noise_rng);
+
+            SGDSolver::<SolverB, NetB>::compute_update_value(self, config,
+                                      weight_gradient,
+                                      weight_id,
+                                      &local_rate.map_or(learning_rate, |rate| learning_rate * rate),
+                                      &net.learnable_weights_lr()[weight_id].unwrap(),
+                                      &config.get_layer_momentum(layer_name, iter));
+        }
+    }
+
+    fn backend(&self) -> &SolverB {
+        &self.backend
+    }
+}
@@ -43,18 +43,22 @@ macro_rules! impl_isolver_sgd {
             }
 
             fn compute_update(&mut self, config: &SolverConfig, net: &mut Layer<NetB>, iter: usize) {
-                let rate = config.get_learning_rate(iter);
-
                 SGDSolver::<SolverB, NetB>::clip_gradients(self, config, net);
+                let weights = net.learnable_weights_data();
+                let layer_names = net.learnable_weights_layer_names();
+                let decay_mults = net.learnable_weights_decay_mult();
                 for (weight_id, weight_gradient) in net.learnable_weights_gradients().iter().enumerate() {
-                    SGDSolver::<SolverB, NetB>::normalize(self, config, weight_gradient);
-                    // SGDSolver::<SolverB, NetB>::regularize(self, config, weight_gradient, net.weights_weight_decay()[weight_id]);
+                    let layer_name = &layer_names[weight_id];
+                    let weight_decay = config.get_layer_weight_decay(layer_name).map(|decay| decay * decay_mults[weight_id]);
+                    SGDSolver::<SolverB, NetB>::regularize(self, config, &weights[weight_id], weight_gradient, weight_decay);
+                    <Self as SGDSolver<SolverB, NetB>>::add_gradient_noise(config, weight_gradient, iter, &mut self.noise_rng);
 
                     SGDSolver::<SolverB, NetB>::compute_update_value(self, config,
                                               weight_gradient,
                                               weight_id,
-                                              &rate,
-                                              &net.learnable_weights_lr()[weight_id].unwrap());
+                                              &config.get_layer_learning_rate(layer_name, iter),
+                                              &net.learnable_weights_lr()[weight_id].unwrap(),
+                                              &config.get_layer_momentum(layer_name, iter));
                 }
             }
 
@@ -65,6 +69,8 @@ macro_rules! impl_isolver_sgd {
     )
 }
 
+pub use self::lars::LARS;
 pub use self::momentum::Momentum;
 
+pub mod lars;
 pub mod momentum;
@@ -15,6 +15,7 @@
 use co::prelude::*;
 use coblas::plugin::Copy;
 use layer::*;
+use rng::{self, RngStream};
 use solver::*;
 use solvers::SGDSolver;
 use std::rc::Rc;
@@ -36,6 +37,9 @@ pub struct Momentum<SolverB: IBackend + SolverOps<f32>> {
     lr: SharedTensor<f32>,
     /// Scalar that temporarily holds momentum for weight update computations
     momentum: SharedTensor<f32>,
+    /// The random numbers for the [gradient noise][3], derived from the global seed.
+    /// [3]: ../../../solver/struct.SolverConfig.html#structfield.gradient_noise
+    noise_rng: RngStream,
 }
 
 impl<SolverB: IBackend + SolverOps<f32>> Momentum<SolverB> {
@@ -59,6 +63,7 @@ impl<SolverB: IBackend + SolverOps<f32>> Momentum<SolverB> {
 
             lr: lr,
             momentum: momentum,
+            noise_rng: RngStream::new(rng::seed(), "gradient_noise"),
         }
     }
 
@@ -70,13 +75,14 @@ impl<B: IBackend + SolverOps<f32>, NetB: IBackend + LayerOps<f32> + 'static> SGD
                             weight_gradient: &ArcLock<SharedTensor<f32>>,
                             history_blob_id: usize,
                             global_lr: &f32,
-                            blob_lr: &f32) {
+                            blob_lr: &f32,
+                            momentum: &f32) {
         ::weight::FillerType::Constant {
             value: global_lr * blob_lr
         }.fill(&mut self.lr);
 
         ::weight::FillerType::Constant {
-            value: config.momentum
+            value: *momentum
         }.fill(&mut self.momentum);
 
         let backend = ISolver::<B, NetB>::backend(self);
@@ -4,6 +4,7 @@ use co::prelude::*;
 use coblas::plugin::*;
 use conn;
 use num::traits::{NumCast, cast};
+use rayon;
 
 /// Shared Lock used for our tensors
 pub type ArcLock<T> = Arc<RwLock<T>>;
@@ -18,6 +19,53 @@ pub fn native_backend() -> Backend<Native> {
     Backend::new(backend_config).unwrap()
 }
 
+/// A fixed set of worker threads that run jobs in parallel.
+///
+/// Layers like [Linear][1] use it to split their work for a batch on a Native Backend.
+/// The threads are started once and reused for every call of [execute][2].
+/// [1]: ../layers/common/linear/index.html
+/// [2]: #method.execute
+pub struct ThreadPool {
+    pool: rayon::ThreadPool,
+}
+
+impl ThreadPool {
+    /// Start a pool with `num_threads` worker threads.
+    pub fn new(num_threads: usize) -> ThreadPool {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(::std::cmp::max(num_threads, 1))
+            .build()
+            .unwrap();
+
+        ThreadPool {
+            pool: pool,
+        }
+    }
+
+    /// Returns the number of worker threads of the pool.
+    pub fn num_threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Run all `jobs` on the worker threads and wait until they are finished.
+    ///
+    /// The jobs may borrow from the caller, as they are guaranteed to be finished
+    /// when this function returns. Panics if one of the jobs panicked.
+    pub fn execute<'a>(&self, jobs: Vec<Box<FnOnce() + Send + 'a>>) {
+        self.pool.scope(|scope| {
+            for job in jobs {
+                scope.spawn(move |_| job());
+            }
+        });
+    }
+}
+
+impl ::std::fmt::Debug for ThreadPool {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "ThreadPool {{ num_threads: {} }}", self.num_threads())
+    }
+}
+
 /// Write into a native Collenchyma Memory.
 pub fn write_to_memory<T: NumCast + ::std::marker::Copy>(mem: &mut MemoryType, data: &[T]) {
     write_to_memory_offset(mem, data, 0);
@@ -65,6 +113,322 @@ pub fn native_scalar<T: NumCast + ::std::marker::Copy>(scalar: T) -> SharedTenso
     shared_scalar
 }
 
+/// Read the values of a tensor, e.g. an output of a network.
+///
+/// The tensor is synced to a Native Backend to read it.
+pub fn tensor_to_vec(tensor: &ArcLock<SharedTensor<f32>>) -> Vec<f32> {
+    let native = native_backend();
+    let mut tensor = tensor.write().unwrap();
+    let _ = tensor.add_device(native.device());
+    tensor.sync(native.device()).unwrap();
+    tensor.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned()
+}
+
+/// Write `data` into a tensor, e.g. an input of a network.
+///
+/// The values are written on a Native Backend; other devices are synced when the tensor is used there.
+/// Returns an error if the length of `data` does not match the size of the tensor.
+pub fn write_tensor(tensor: &ArcLock<SharedTensor<f32>>, data: &[f32]) -> Result<(), String> {
+    let native = native_backend();
+    let mut tensor = tensor.write().unwrap();
+    if tensor.desc().size() != data.len() {
+        return Err(format!("The tensor of shape {:?} expects {} values, but {} were provided", tensor.desc(), tensor.desc().size(), data.len()));
+    }
+    match tensor.add_device(native.device()) { _ => tensor.sync(native.device()).unwrap() }
+    write_to_memory(tensor.get_mut(native.device()).unwrap(), data);
+    Ok(())
+}
+
+/// Create a tensor of shape `shape` with the values of `data` on the device of `backend`.
+///
+/// The values are written on a Native Backend and synced to the device of `backend`.
+/// Returns an error if the length of `data` does not match the size of the shape.
+pub fn tensor_from_slice<B: IBackend>(backend: &B, shape: &[usize], data: &[f32]) -> Result<SharedTensor<f32>, String> {
+    let size = shape.iter().fold(1, |prod, dim| prod * dim);
+    if size != data.len() {
+        return Err(format!("A tensor of shape {:?} needs {} values, but {} were provided", shape, size, data.len()));
+    }
+    let native = native_backend();
+    let mut tensor = SharedTensor::<f32>::new(native.device(), &shape.to_owned()).unwrap();
+    write_to_memory(tensor.get_mut(native.device()).unwrap(), data);
+    match tensor.add_device(backend.device()) { _ => tensor.sync(backend.device()).unwrap() }
+    Ok(tensor)
+}
+
+#[cfg(feature = "ndarray")]
+/// Create a tensor with the shape and values of `array` on the device of `backend`.
+///
+/// See [tensor_from_slice][1].
+/// [1]: ./fn.tensor_from_slice.html
+pub fn tensor_from_array<B: IBackend>(backend: &B, array: &::ndarray::ArrayD<f32>) -> SharedTensor<f32> {
+    let values = array.iter().cloned().collect::<Vec<_>>();
+    tensor_from_slice(backend, array.shape(), &values).unwrap()
+}
+
+#[cfg(feature = "ndarray")]
+/// Read the shape and values of a tensor into an array.
+///
+/// The tensor is synced to a Native Backend to read it.
+pub fn tensor_to_array(tensor: &ArcLock<SharedTensor<f32>>) -> ::ndarray::ArrayD<f32> {
+    let shape = tensor.read().unwrap().desc().clone();
+    ::ndarray::ArrayD::from_shape_vec(::ndarray::IxDyn(&shape), tensor_to_vec(tensor)).unwrap()
+}
+
+/// Pad sequences of different lengths into one batch and create the mask for it.
+///
+/// Every sequence consists of timesteps with `feature_size` values each.
+/// The sequences are padded with zeros to the length of the longest sequence,
+/// which results in a tensor of shape `[batch_size, max_length, feature_size]`.
+/// The mask has the shape `[batch_size, max_length]` and is `1` for the timesteps
+/// of a sequence and `0` for the padding (see [Masking][1]).
+/// [1]: ../layers/index.html#masking
+///
+/// Both tensors are allocated on a Native Backend.
+///
+/// Returns an error if `feature_size` is zero or a sequence can not be split into
+/// timesteps of `feature_size` values.
+pub fn pad_sequences(sequences: &[Vec<f32>], feature_size: usize) -> Result<(SharedTensor<f32>, SharedTensor<f32>), String> {
+    if feature_size == 0 {
+        return Err("The feature size of the sequences has to be larger than zero".to_owned());
+    }
+    let native = native_backend();
+    let mut lengths = Vec::with_capacity(sequences.len());
+    for sequence in sequences {
+        if sequence.len() % feature_size != 0 {
+            return Err(format!("A sequence of {} values can not be split into timesteps of size {}", sequence.len(), feature_size));
+        }
+        lengths.push(sequence.len() / feature_size);
+    }
+    let max_length = lengths.iter().cloned().max().unwrap_or(0);
+
+    let mut padded = vec![0f32; sequences.len() * max_length * feature_size];
+    let mut mask = vec![0f32; sequences.len() * max_length];
+    for (i, (sequence, &length)) in sequences.iter().zip(lengths.iter()).enumerate() {
+        let offset = i * max_length * feature_size;
+        padded[offset..offset + sequence.len()].copy_from_slice(sequence);
+        for timestep in mask[i * max_length..i * max_length + length].iter_mut() {
+            *timestep = 1f32;
+        }
+    }
+
+    let mut padded_tensor = SharedTensor::<f32>::new(native.device(), &vec![sequences.len(), max_length, feature_size]).unwrap();
+    write_to_memory(padded_tensor.get_mut(native.device()).unwrap(), &padded);
+    let mut mask_tensor = SharedTensor::<f32>::new(native.device(), &vec![sequences.len(), max_length]).unwrap();
+    write_to_memory(mask_tensor.get_mut(native.device()).unwrap(), &mask);
+
+    Ok((padded_tensor, mask_tensor))
+}
+
+/// Extract the `k` most probable classes for each sample of a batch.
+///
+/// `probabilities` is expected to be the output of a Softmax layer, with
+/// the batchsize as its first dimension. The tensor is synced to a
+/// Native Backend to read it.
+///
+/// For each sample the returned pairs of (class index, probability) are ordered
+/// from most to least probable. If `k` is larger than the number of classes,
+/// all classes are returned. A batch without samples gives an empty result.
+pub fn top_k(probabilities: &mut SharedTensor<f32>, k: usize) -> Vec<Vec<(usize, f32)>> {
+    let native_backend = native_backend();
+
+    let batch_size = probabilities.desc().get(0).cloned().unwrap_or(1);
+    if batch_size == 0 {
+        return Vec::new();
+    }
+    let sample_size = probabilities.desc().size() / batch_size;
+
+    let _ = probabilities.add_device(native_backend.device());
+    probabilities.sync(native_backend.device()).unwrap();
+    let native_probabilities = probabilities.get(native_backend.device()).unwrap().as_native().unwrap();
+
+    native_probabilities.as_slice::<f32>().chunks(sample_size).map(|sample| {
+        let mut classes = sample.iter().cloned().enumerate().collect::<Vec<_>>();
+        classes.sort_by(|&(_, one), &(_, two)| two.partial_cmp(&one).unwrap_or(::std::cmp::Ordering::Equal));
+        classes.truncate(k);
+        classes
+    }).collect()
+}
+
+/// Convert labels into class ids.
+///
+/// Like all tensors in a network, label tensors hold `f32` values, which represent
+/// every class id below 2^24 exactly. The labels are rounded to the nearest class id,
+/// so a label that is slightly off, e.g. because it was computed, isn't truncated to
+/// the class below it.
+///
+/// Labels are not stored in integer tensors, as layers only exchange `SharedTensor<f32>`
+/// through the [ILayer][1] interface.
+/// [1]: ../layer/trait.ILayer.html
+///
+/// Panics if a label is negative, not finite or not below 2^24.
+pub fn class_ids(labels: &[f32]) -> Vec<usize> {
+    labels.iter().map(|&label| {
+        assert!(label.is_finite() && label > -0.5f32 && label < MAX_EXACT_CLASS_ID, "Label {} is not a valid class id", label);
+        label.round() as usize
+    }).collect()
+}
+
+/// The smallest class id that can not be told apart from its neighbours in a `f32` label.
+const MAX_EXACT_CLASS_ID: f32 = 16777216f32;
+
+/// Compute the shape of the result when tensors of the shapes `a` and `b` are broadcast against each other.
+///
+/// The shapes are compared from their trailing dimensions on, like in NumPy.
+/// Two dimensions are compatible if they are equal or one of them is `1`;
+/// a missing dimension of the shorter shape counts as `1`.
+/// The result has the larger of both dimensions everywhere.
+/// See [Broadcasting][1] for how layers use this.
+/// [1]: ../layers/index.html#broadcasting
+///
+/// E.g. `[4, 3, 2]` and `[3, 1]` broadcast to `[4, 3, 2]`, while `[4, 3]` and `[4]` are incompatible.
+pub fn broadcast_shape(a: &[usize], b: &[usize]) -> Result<Vec<usize>, String> {
+    let length = ::std::cmp::max(a.len(), b.len());
+    let mut shape = vec![0; length];
+    for i in 0..length {
+        let a_dim = if i < a.len() { a[a.len() - 1 - i] } else { 1 };
+        let b_dim = if i < b.len() { b[b.len() - 1 - i] } else { 1 };
+        shape[length - 1 - i] = match (a_dim, b_dim) {
+            (a_dim, b_dim) if a_dim == b_dim => a_dim,
+            (1, b_dim) => b_dim,
+            (a_dim, 1) => a_dim,
+            _ => return Err(format!("Shapes {:?} and {:?} can not be broadcast, their dimensions {} and {} differ and neither is 1",
+                                    a, b, a_dim, b_dim)),
+        };
+    }
+    Ok(shape)
+}
+
+/// Repeat the `values` of a tensor with `shape` along the broadcast dimensions to fill `target_shape`.
+///
+/// Fails if `shape` can't be broadcast to `target_shape` without changing `target_shape`
+/// (see [broadcast_shape][1]), or if the number of `values` doesn't match `shape`.
+/// [1]: ./fn.broadcast_shape.html
+pub fn broadcast_to(values: &[f32], shape: &[usize], target_shape: &[usize]) -> Result<Vec<f32>, String> {
+    let source_strides = try!(broadcast_strides(values.len(), shape, target_shape));
+    let size = target_shape.iter().fold(1, |prod, i| prod * i);
+    Ok((0..size).map(|index| values[source_index(index, target_shape, &source_strides)]).collect())
+}
+
+/// Sum the `values` of a tensor with `target_shape` along the broadcast dimensions, so the result has `shape`.
+///
+/// This is the reverse of [broadcast_to][1]: the gradient w.r.t. a broadcast tensor,
+/// e.g. a bias, is the sum of the gradients of all values it was repeated to.
+/// [1]: ./fn.broadcast_to.html
+pub fn sum_to_shape(values: &[f32], target_shape: &[usize], shape: &[usize]) -> Result<Vec<f32>, String> {
+    let size = shape.iter().fold(1, |prod, i| prod * i);
+    let source_strides = try!(broadcast_strides(size, shape, target_shape));
+    if values.len() != target_shape.iter().fold(1, |prod, i| prod * i) {
+        return Err(format!("{} values don't match the shape {:?}", values.len(), target_shape));
+    }
+    let mut sums = vec![0f32; size];
+    for (index, value) in values.iter().enumerate() {
+        sums[source_index(index, target_shape, &source_strides)] += *value;
+    }
+    Ok(sums)
+}
+
+/// Compute the strides of a tensor with `shape` aligned to the dimensions of `target_shape`.
+///
+/// Broadcast dimensions have a stride of `0`, so all their indices map to the same value.
+fn broadcast_strides(size: usize, shape: &[usize], target_shape: &[usize]) -> Result<Vec<usize>, String> {
+    if size != shape.iter().fold(1, |prod, i| prod * i) {
+        return Err(format!("{} values don't match the shape {:?}", size, shape));
+    }
+    if try!(broadcast_shape(shape, target_shape))[..] != target_shape[..] {
+        return Err(format!("Shape {:?} can not be broadcast to {:?}", shape, target_shape));
+    }
+    let offset = target_shape.len() - shape.len();
+    let mut strides = vec![0; target_shape.len()];
+    let mut stride = 1;
+    for i in (0..shape.len()).rev() {
+        if shape[i] != 1 {
+            strides[offset + i] = stride;
+        }
+        stride *= shape[i];
+    }
+    Ok(strides)
+}
+
+/// Map the index of a value in a tensor with `target_shape` to the index of the value it was broadcast from.
+fn source_index(index: usize, target_shape: &[usize], source_strides: &[usize]) -> usize {
+    let mut remainder = index;
+    let mut source_index = 0;
+    for i in (0..target_shape.len()).rev() {
+        source_index += (remainder % target_shape[i]) * source_strides[i];
+        remainder /= target_shape[i];
+    }
+    source_index
+}
+
+/// Convert a `f32` to the bits of the nearest IEEE 754 half-precision float.
+///
+/// Values that are too large for a half-precision float become infinite,
+/// values that are too small become zero.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7fffff;
+    if exponent == 0xff {
+        // infinity stays infinity, NaN stays NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    let (half, shift) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // subnormal: shift the mantissa, including the implicit leading 1, into place
+        (0, (14 - exponent) as u32)
+    } else {
+        ((exponent as u32) << 10, 13)
+    };
+    let mantissa = if exponent <= 0 { mantissa | 0x800000 } else { mantissa };
+    let half = half | (mantissa >> shift);
+    // round to nearest, ties to even; a carry into the exponent is still correct
+    let remainder = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let rounded = if remainder > halfway || (remainder == halfway && half & 1 == 1) { half + 1 } else { half };
+    sign | rounded as u16
+}
+
+/// Convert the bits of an IEEE 754 half-precision float to a `f32`.
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    let bits = match exponent {
+        0 => {
+            // zero or subnormal
+            let magnitude = mantissa as f32 * 2f32.powi(-24);
+            return if sign != 0 { -magnitude } else { magnitude };
+        }
+        0x1f => sign | 0x7f800000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Update the CRC-32 checksum `crc`, as used by the zip format, with `data`.
+///
+/// Start with a `crc` of `0`; data that is checksummed in parts results in the same
+/// checksum as when it is checksummed at once.
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
+
 /// Casts a Vec<usize> to as Vec<i32>
 pub fn cast_vec_usize_to_i32(input: Vec<usize>) -> Vec<i32> {
     let mut out = Vec::new();
@@ -113,14 +477,14 @@ pub trait LayerOps<F> : conn::Convolution<F>
                       + conn::Sigmoid<F> + conn::SigmoidPointwise<F>
                       + conn::Tanh<F> + conn::TanhPointwise<F>
                       + conn::Softmax<F> + conn::LogSoftmax<F>
-                      + Gemm<F> {}
+                      + Gemm<F> + Copy<F> + Scal<F> {}
 #[cfg(feature="native")]
 /// Encapsulates all traits used in Layers.
 pub trait LayerOps<F> : conn::Relu<F>
                       + conn::Sigmoid<F>
                       + conn::Tanh<F>
                       + conn::Softmax<F> + conn::LogSoftmax<F>
-                      + Gemm<F> {}
+                      + Gemm<F> + Copy<F> + Scal<F> {}
 
 #[cfg(all(feature="cuda", not(feature="native")))]
 impl<T: conn::Convolution<f32>
@@ -129,10 +493,10 @@ impl<T: conn::Convolution<f32>
       + conn::Sigmoid<f32> + conn::SigmoidPointwise<f32>
       + conn::Tanh<f32> + conn::TanhPointwise<f32>
       + conn::Softmax<f32> + conn::LogSoftmax<f32>
-      + Gemm<f32>> LayerOps<f32> for T {}
+      + Gemm<f32> + Copy<f32> + Scal<f32>> LayerOps<f32> for T {}
 #[cfg(feature="native")]
 impl<T: conn::Relu<f32>
       + conn::Sigmoid<f32>
       + conn::Tanh<f32>
       + conn::Softmax<f32> + conn::LogSoftmax<f32>
-      + Gemm<f32>> LayerOps<f32> for T {}
+      + Gemm<f32> + Copy<f32> + Scal<f32>> LayerOps<f32> for T {}
@@ -13,6 +13,9 @@ pub struct WeightConfig {
     /// layers, but never required otherwise. To share a weight between two
     /// layers, give it a (non-empty) name.
     ///
+    /// A shared weight is a learnable weight of the first layer that uses it only,
+    /// and its gradient is the sum of the gradients of all layers that use it.
+    ///
     /// Default: ""
     pub name: String,
     /// Whether to require shared weights to have the same shape, or just the same
@@ -0,0 +1,290 @@
+//! Provides downloading and caching of pretrained models.
+//!
+//! A model is a file written by [Layer::save][1], which contains the configuration and
+//! the weights of the network. The models that are available are listed in a manifest,
+//! a text file with one model per line that consists of its name, the URL it can be
+//! downloaded from and the SHA-256 checksum of the file, separated by whitespace.
+//! Empty lines and lines starting with `#` are ignored:
+//!
+//! ```text
+//! # name   url                                         sha256
+//! mnist    https://example.com/models/mnist.capnp      9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//! ```
+//!
+//! The name of a model is used as its file name in the cache directory, so it must not
+//! contain path separators or `..`, or be `.`. Models and manifests are only downloaded
+//! from `https://` URLs, or read from local `file://` URLs, and the checksum of
+//! a model is a SHA-256 hex string of 64 digits.
+//!
+//! Downloaded models are stored in a cache directory and only downloaded again if
+//! the checksum of the cached file doesn't match. A manifest is verified against
+//! its own checksum before it is used. Downloads use the `curl` command,
+//! which has to be installed.
+//! [1]: ../layer/struct.Layer.html#method.save
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use co::IBackend;
+use layer::Layer;
+use logging::Subsystem;
+use log::LogLevel;
+use util::LayerOps;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A pretrained model listed in a manifest.
+pub struct ZooModel {
+    /// The name of the model, which is also the name of the file in the cache directory.
+    pub name: String,
+    /// The URL the model is downloaded from.
+    pub url: String,
+    /// The SHA-256 checksum of the model file as a hex string.
+    pub sha256: String,
+}
+
+impl ZooModel {
+    /// Check that the name, URL and checksum of the model are valid.
+    ///
+    /// See the [module documentation][1] for the requirements.
+    /// [1]: ./index.html
+    pub fn check(&self) -> io::Result<()> {
+        if self.name.is_empty() || self.name == "." || self.name.contains("..") ||
+           self.name.contains('/') || self.name.contains('\\') {
+            return Err(invalid_data(&format!("The model name {:?} is not a valid file name", self.name)));
+        }
+        try!(check_url(&self.url));
+        if self.sha256.len() != 64 || !self.sha256.chars().all(|c| c.is_digit(16)) {
+            return Err(invalid_data(&format!("The checksum {:?} of model {} is not a SHA-256 hex string", self.sha256, self.name)));
+        }
+        Ok(())
+    }
+}
+
+/// Parse the models listed in a manifest.
+///
+/// See the [module documentation][1] for the format.
+/// [1]: ./index.html
+pub fn parse_manifest(text: &str) -> io::Result<Vec<ZooModel>> {
+    let mut models = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 3 {
+            return Err(invalid_data(&format!("Line {} of the manifest does not consist of a name, URL and checksum", line_number + 1)));
+        }
+        let model = ZooModel {
+            name: fields[0].to_owned(),
+            url: fields[1].to_owned(),
+            sha256: fields[2].to_lowercase(),
+        };
+        if let Err(err) = model.check() {
+            return Err(invalid_data(&format!("Line {} of the manifest: {}", line_number + 1, err)));
+        }
+        models.push(model);
+    }
+    Ok(models)
+}
+
+/// Compute the SHA-256 checksum of the file at the specified path as a hex string.
+///
+/// This can be used to create the checksums of a manifest.
+pub fn file_checksum<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let file = try!(File::open(path.as_ref()));
+    let mut data = Vec::new();
+    try!(BufReader::new(file).read_to_end(&mut data));
+    Ok(sha256(&data).iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[derive(Debug, Clone)]
+/// Downloads the models of a manifest into a cache directory.
+pub struct Zoo {
+    cache_dir: PathBuf,
+    models: Vec<ZooModel>,
+}
+
+impl Zoo {
+    /// Create a Zoo for the `models` that caches them in `cache_dir`.
+    ///
+    /// The cache directory is created when the first model is fetched.
+    pub fn new<P: AsRef<Path>>(cache_dir: P, models: Vec<ZooModel>) -> Zoo {
+        Zoo {
+            cache_dir: cache_dir.as_ref().to_path_buf(),
+            models: models,
+        }
+    }
+
+    /// Create a Zoo for the models listed in the manifest at `manifest_url`.
+    ///
+    /// The manifest is always downloaded and stored in `cache_dir` as `manifest`.
+    /// It is only used if its SHA-256 checksum matches `manifest_sha256`,
+    /// which protects the checksums of the models in it.
+    pub fn from_manifest_url<P: AsRef<Path>>(cache_dir: P, manifest_url: &str, manifest_sha256: &str) -> io::Result<Zoo> {
+        let cache_dir = cache_dir.as_ref();
+        try!(check_url(manifest_url));
+        try!(fs::create_dir_all(cache_dir));
+        let path = cache_dir.join("manifest");
+        try!(download(manifest_url, &path));
+        let checksum = try!(file_checksum(&path));
+        if checksum != manifest_sha256.to_lowercase() {
+            let _ = fs::remove_file(&path);
+            return Err(invalid_data(&format!("The checksum {} of the manifest {} does not match {}", checksum, manifest_url, manifest_sha256)));
+        }
+
+        let mut text = String::new();
+        try!(try!(File::open(&path)).read_to_string(&mut text));
+        Ok(Zoo::new(cache_dir, try!(parse_manifest(&text))))
+    }
+
+    /// Returns the models of the Zoo.
+    pub fn models(&self) -> &[ZooModel] {
+        &self.models
+    }
+
+    /// Returns the path of the model with the specified name, downloading it if necessary.
+    ///
+    /// A cached model is only used if its checksum matches. A downloaded model is first
+    /// stored next to its final path and only moved there if its checksum matches,
+    /// so the cache never contains a partially downloaded model.
+    pub fn fetch(&self, name: &str) -> io::Result<PathBuf> {
+        let model = match self.models.iter().find(|model| model.name == name) {
+            Some(model) => model,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("There is no model named {} in the manifest", name))),
+        };
+        try!(model.check());
+        let path = self.cache_dir.join(&model.name);
+        if path.is_file() && try!(file_checksum(&path)) == model.sha256 {
+            return Ok(path);
+        }
+
+        try!(fs::create_dir_all(&self.cache_dir));
+        let download_path = self.cache_dir.join(format!("{}.part", model.name));
+        try!(download(&model.url, &download_path));
+        let checksum = try!(file_checksum(&download_path));
+        if checksum != model.sha256 {
+            let _ = fs::remove_file(&download_path);
+            return Err(invalid_data(&format!("The checksum {} of model {} does not match the manifest", checksum, model.name)));
+        }
+        try!(fs::rename(&download_path, &path));
+        Ok(path)
+    }
+
+    /// Fetch the model with the specified name and [load][1] it as a Layer.
+    /// [1]: ../layer/struct.Layer.html#method.load
+    pub fn load<B: IBackend + LayerOps<f32> + 'static>(&self, backend: Rc<B>, name: &str) -> io::Result<Layer<B>> {
+        let path = try!(self.fetch(name));
+        Layer::<B>::load(backend, path)
+    }
+}
+
+/// Download the file at `url` to `path` with `curl`.
+fn download(url: &str, path: &Path) -> io::Result<()> {
+    log_event!(Subsystem::Init, LogLevel::Info, "download", url = url, path = path.display());
+    let status = try!(Command::new("curl")
+                          .arg("--fail")
+                          .arg("--silent")
+                          .arg("--show-error")
+                          .arg("--location")
+                          // redirects must not leave https
+                          .arg("--proto").arg("=https,file")
+                          .arg("--proto-redir").arg("=https")
+                          .arg("--output").arg(path)
+                          // the URL can't be mistaken for an option
+                          .arg("--")
+                          .arg(url)
+                          .status());
+    if !status.success() {
+        return Err(io::Error::new(io::ErrorKind::Other, format!("Downloading {} failed with {}", url, status)));
+    }
+    Ok(())
+}
+
+/// Check that `url` is a `https://` or `file://` URL.
+fn check_url(url: &str) -> io::Result<()> {
+    if url.starts_with("https://") || url.starts_with("file://") {
+        Ok(())
+    } else {
+        Err(invalid_data(&format!("The URL {} is neither a https:// nor a file:// URL", url)))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Compute the SHA-256 digest of `data` (FIPS 180-4).
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+
+    // pad with a single 1 bit, zeros and the length in bits to a multiple of 64 bytes
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    let bit_length = (data.len() as u64).wrapping_mul(8);
+    for i in (0..8).rev() {
+        message.push((bit_length >> (i * 8)) as u8);
+    }
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = (block[i * 4] as u32) << 24 | (block[i * 4 + 1] as u32) << 16 |
+                   (block[i * 4 + 2] as u32) << 8 | block[i * 4 + 3] as u32;
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let (mut a, mut b, mut c, mut d) = (hash[0], hash[1], hash[2], hash[3]);
+        let (mut e, mut f, mut g, mut h) = (hash[4], hash[5], hash[6], hash[7]);
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (value, add) in hash.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+            *value = value.wrapping_add(*add);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (i, value) in hash.iter().enumerate() {
+        for j in 0..4 {
+            digest[i * 4 + j] = (value >> (24 - j * 8)) as u8;
+        }
+    }
+    digest
+}
@@ -0,0 +1,29 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod analysis_spec {
+    #[cfg(feature="native")]
+    #[test]
+    fn activation_stats_reports_dead_relu_units() {
+        use std::rc::Rc;
+        use std::sync::{Arc, RwLock};
+        use leaf::analysis::activation_stats;
+        use leaf::layer::{Layer, LayerConfig};
+        use leaf::models::mlp;
+        use leaf::util::{native_backend, tensor_from_slice};
+
+        let backend = Rc::new(native_backend());
+        let mut network = Layer::from_config(backend.clone(), &LayerConfig::new("network", mlp(2, &[3, 4, 2])));
+        let input = tensor_from_slice(&*backend, &[2, 3], &[0f32; 6]).unwrap();
+        let stats = activation_stats(&mut network, &[Arc::new(RwLock::new(input))], 5);
+
+        let relu = stats.iter().find(|summary| summary.layer == "linear1/relu").unwrap();
+        assert_eq!(1f32, relu.fraction_zero);
+        assert_eq!(vec![8, 0, 0, 0, 0], relu.histogram);
+        assert_eq!(vec![0, 1, 2, 3], relu.dead_units);
+
+        let linear2 = stats.iter().find(|summary| summary.layer == "linear2").unwrap();
+        assert!(!linear2.has_dead_units());
+    }
+}
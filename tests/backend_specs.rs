@@ -0,0 +1,158 @@
+extern crate leaf;
+extern crate collenchyma as co;
+extern crate rand;
+
+#[cfg(test)]
+mod backend_spec {
+    #[cfg(all(feature="native", feature="cuda"))]
+    mod native_cuda {
+        use std::rc::Rc;
+        use std::sync::{Arc, RwLock};
+        use co::prelude::*;
+        use leaf::layer::*;
+        use leaf::layers::*;
+        use leaf::rng::RngStream;
+        use leaf::util::{ArcLock, LayerOps, native_backend, tensor_from_slice, tensor_to_vec, write_to_memory};
+        use rand::Rng;
+
+        /// The number of randomized shapes every layer is compared on.
+        const NUM_CASES: usize = 10;
+
+        fn cuda_backend() -> Rc<Backend<Cuda>> {
+            Rc::new(Backend::<Cuda>::default().unwrap())
+        }
+
+        fn random_values(rng: &mut RngStream, len: usize) -> Vec<f32> {
+            (0..len).map(|_| rng.gen_range(-2f32, 2f32)).collect()
+        }
+
+        /// Overwrite the values of `tensor`, which may live on any device.
+        fn set_values(tensor: &ArcLock<SharedTensor<f32>>, values: &[f32]) {
+            let native = native_backend();
+            let mut tensor = tensor.write().unwrap();
+            let _ = tensor.add_device(native.device());
+            tensor.sync(native.device()).unwrap();
+            write_to_memory(tensor.get_mut(native.device()).unwrap(), values);
+        }
+
+        fn assert_close(what: &str, expected: &[f32], actual: &[f32]) {
+            assert_eq!(expected.len(), actual.len(), "{} differ in length", what);
+            for (i, (expected_value, actual_value)) in expected.iter().zip(actual).enumerate() {
+                let tolerance = 1e-4 * (1f32 + expected_value.abs());
+                assert!((expected_value - actual_value).abs() <= tolerance,
+                        "{} differ at {}: {} on native, {} on CUDA", what, i, expected_value, actual_value);
+            }
+        }
+
+        /// The outputs, input gradients and weight gradients of a network.
+        struct Results {
+            outputs: Vec<f32>,
+            input_gradients: Vec<f32>,
+            weights_gradients: Vec<Vec<f32>>,
+        }
+
+        fn run<B: IBackend + LayerOps<f32> + 'static>(backend: Rc<B>,
+                                                       config: &LayerConfig,
+                                                       weights: &[Vec<f32>],
+                                                       input: &[f32],
+                                                       output_gradient: &[f32]) -> Results {
+            let mut network = Layer::from_config(backend.clone(), config);
+            for (weight, values) in network.learnable_weights_data().iter().zip(weights) {
+                set_values(weight, values);
+            }
+            let input_shape = network.input_shapes()[0].1.clone();
+            let input = Arc::new(RwLock::new(tensor_from_slice(&*backend, &input_shape, input).unwrap()));
+            let output = network.forward(&[input])[0].clone();
+            let output_shape = output.read().unwrap().desc().clone();
+            let output_gradient = Arc::new(RwLock::new(tensor_from_slice(&*backend, &output_shape, output_gradient).unwrap()));
+            let input_gradients = network.backward_input(&[output_gradient]);
+            network.backward_parameters();
+
+            Results {
+                outputs: tensor_to_vec(&output),
+                input_gradients: tensor_to_vec(&input_gradients[0]),
+                weights_gradients: network.learnable_weights_gradients().iter().map(tensor_to_vec).collect(),
+            }
+        }
+
+        /// Run a network of the layers created by `layer_types` on randomized inputs of the shapes created by
+        /// `input_shape` on both backends with the same weights and compare the results.
+        fn compare_backends<F, S>(name: &str, layer_types: F, input_shape: S)
+            where F: Fn(&mut RngStream) -> Vec<LayerType>, S: Fn(&mut RngStream) -> Vec<usize> {
+            let mut rng = RngStream::new(0, name);
+            for case in 0..NUM_CASES {
+                let shape = input_shape(&mut rng);
+                let mut net_cfg = SequentialConfig::default();
+                net_cfg.add_input("data", &shape);
+                for (i, layer_type) in layer_types(&mut rng).into_iter().enumerate() {
+                    net_cfg.add_layer(LayerConfig::new(&format!("{}{}", name, i), layer_type));
+                }
+                let config = LayerConfig::new("network", net_cfg);
+
+                let native_network = Layer::from_config(Rc::new(native_backend()), &config);
+                let weights = native_network.learnable_weights_data().iter()
+                    .map(|weight| random_values(&mut rng, weight.read().unwrap().desc().size()))
+                    .collect::<Vec<_>>();
+                let input = random_values(&mut rng, shape.size());
+                let output_size = native_network.output_blobs_data[0].read().unwrap().desc().size();
+                let output_gradient = random_values(&mut rng, output_size);
+
+                let native = run(Rc::new(native_backend()), &config, &weights, &input, &output_gradient);
+                let cuda = run(cuda_backend(), &config, &weights, &input, &output_gradient);
+                let case_name = format!("{} case {} with input shape {:?}:", name, case, shape);
+                assert_close(&format!("{} outputs", case_name), &native.outputs, &cuda.outputs);
+                assert_close(&format!("{} input gradients", case_name), &native.input_gradients, &cuda.input_gradients);
+                for (i, (native_gradient, cuda_gradient)) in native.weights_gradients.iter().zip(&cuda.weights_gradients).enumerate() {
+                    assert_close(&format!("{} gradients of weight {}", case_name, i), native_gradient, cuda_gradient);
+                }
+            }
+        }
+
+        fn matrix_shape(rng: &mut RngStream) -> Vec<usize> {
+            vec![rng.gen_range(1, 9), rng.gen_range(1, 33)]
+        }
+
+        #[test]
+        fn linear_matches() {
+            compare_backends("linear",
+                             |rng| vec![LayerType::Linear(LinearConfig::new(rng.gen_range(1, 17)))],
+                             matrix_shape);
+        }
+
+        #[test]
+        fn softmax_matches() {
+            compare_backends("softmax", |_| vec![LayerType::Softmax(SoftmaxConfig::default())], matrix_shape);
+        }
+
+        #[test]
+        fn log_softmax_matches() {
+            compare_backends("log_softmax", |_| vec![LayerType::LogSoftmax(LogSoftmaxConfig::default())], matrix_shape);
+        }
+
+        #[test]
+        fn relu_matches() {
+            compare_backends("relu", |_| vec![LayerType::ReLU], matrix_shape);
+        }
+
+        #[test]
+        fn sigmoid_matches() {
+            compare_backends("sigmoid", |_| vec![LayerType::Sigmoid], matrix_shape);
+        }
+
+        #[test]
+        fn linear_relu_matches() {
+            compare_backends("linear_relu",
+                             |rng| vec![LayerType::Linear(LinearConfig::new(rng.gen_range(1, 17))), LayerType::ReLU],
+                             matrix_shape);
+        }
+
+        #[test]
+        fn linear_sigmoid_linear_matches() {
+            compare_backends("linear_sigmoid_linear",
+                             |rng| vec![LayerType::Linear(LinearConfig::new(rng.gen_range(1, 17))),
+                                        LayerType::Sigmoid,
+                                        LayerType::Linear(LinearConfig::new(rng.gen_range(1, 17)))],
+                             matrix_shape);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod bench_spec {
+    #[cfg(feature="native")]
+    #[test]
+    fn bench_network_measures_every_step() {
+        use std::rc::Rc;
+        use leaf::bench::{bench_network, BenchConfig};
+        use leaf::layer::{LayerConfig, LayerType};
+        use leaf::models::mlp;
+        use leaf::util::native_backend;
+
+        let backend = Rc::new(native_backend());
+        let config = LayerConfig::new("mlp", mlp(1, &[4, 8, 2]));
+        let bench_config = BenchConfig { warmup: 1, iterations: 3 };
+        let result = bench_network(backend.clone(), &config, 5, &bench_config).unwrap();
+        assert_eq!("mlp", result.name);
+        assert_eq!(5, result.batch_size);
+        for stats in &[&result.forward, &result.backward_input, &result.backward_parameters] {
+            assert_eq!(3, stats.samples.len());
+            assert!(stats.min <= stats.mean && stats.mean <= stats.max);
+            assert!(stats.std_dev >= 0f64);
+        }
+        assert!(result.samples_per_second() > 0f64);
+        assert!(result.to_string().starts_with("mlp with batch size 5:"));
+
+        assert!(bench_network(backend, &LayerConfig::new("relu", LayerType::ReLU), 5, &bench_config).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+extern crate leaf;
+
+#[cfg(test)]
+mod csv_spec {
+    use leaf::csv::*;
+    use leaf::solver::IDataset;
+
+    const IRIS: &'static str = "sepal_length,sepal_width,species\n\
+                                5.1,3.5,setosa\n\
+                                7.0,3.2,versicolor\n\
+                                \"4.9\",3.0,setosa\n";
+
+    #[test]
+    fn read_features_and_categorical_labels() {
+        let dataset = CsvReader::new("species").read(IRIS.as_bytes()).unwrap();
+        assert_eq!(3, dataset.len());
+        assert_eq!(2, dataset.num_features());
+        assert_eq!(vec![4.9f32, 3.0f32], dataset.features()[2]);
+        assert_eq!(&[0f32, 1f32, 0f32], dataset.labels());
+        assert_eq!(&["setosa".to_owned(), "versicolor".to_owned()], dataset.classes());
+    }
+
+    #[test]
+    fn select_columns_and_one_hot_encode() {
+        let dataset = CsvReader::new("species").feature_columns(vec!["sepal_width"]).one_hot(true)
+                                               .read(IRIS.as_bytes()).unwrap();
+        assert_eq!(vec![2], dataset.target_shape());
+        assert_eq!((vec![3.2f32], vec![0f32, 1f32]), dataset.sample(1));
+    }
+
+    #[test]
+    fn read_without_header() {
+        let dataset = CsvReader::new(0).has_header(false).delimiter(';')
+                                       .read("1;0.5;true\n0;1.5;false\n".as_bytes()).unwrap();
+        assert_eq!(vec![0usize; 0], dataset.target_shape());
+        assert_eq!((vec![1.5f32, 0f32], vec![0f32]), dataset.sample(1));
+    }
+
+    #[test]
+    fn reject_non_numeric_features() {
+        assert!(CsvReader::new(1).has_header(false).read("a,1\n".as_bytes()).is_err());
+    }
+}
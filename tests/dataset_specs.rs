@@ -0,0 +1,223 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod dataset_spec {
+    #[cfg(feature="native")]
+    #[test]
+    fn iterator_batcher_skips_incomplete_minibatch() {
+        use leaf::solver::{IBatcher, IteratorBatcher};
+        use leaf::util::tensor_to_vec;
+
+        let samples = (0..5).map(|i| (vec![i as f32, -i as f32], vec![i as f32]));
+        let mut batcher = IteratorBatcher::new(samples, 2, &[2], &[]).unwrap();
+        let (inputs, targets) = batcher.next_batch().unwrap().unwrap();
+        assert_eq!(&vec![2, 2], inputs.read().unwrap().desc());
+        assert_eq!(vec![0f32, 0f32, 1f32, -1f32], tensor_to_vec(&inputs));
+        assert_eq!(vec![0f32, 1f32], tensor_to_vec(&targets));
+        assert_eq!(1, batcher.count());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn iterator_batcher_rejects_invalid_batches() {
+        use leaf::solver::{IBatcher, IteratorBatcher};
+
+        let samples = vec![(vec![0f32, 1f32], vec![0f32]), (vec![2f32], vec![1f32])];
+        assert!(IteratorBatcher::new(samples.clone(), 0, &[2], &[]).is_err());
+        let mut batcher = IteratorBatcher::new(samples, 2, &[2], &[]).unwrap();
+        assert!(batcher.next_batch().unwrap().is_err());
+        assert!(batcher.next_batch().is_none());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn train_batches_reports_no_loss_without_minibatch() {
+        use leaf::solver::IteratorBatcher;
+
+        let mut solver = online_solver(2, None);
+        let mut empty = IteratorBatcher::new(vec![(vec![0.5f32, -1f32, 2f32], vec![1f32])], 2, &[3], &[]).unwrap();
+        assert_eq!(Ok(None), solver.train_batches(&mut empty));
+        let mut empty = IteratorBatcher::new(Vec::new(), 2, &[3], &[]).unwrap();
+        assert_eq!(Ok(None), solver.validate_batches(&mut empty));
+        let mut invalid = IteratorBatcher::new(vec![(vec![0.5f32], vec![1f32]); 2], 2, &[3], &[]).unwrap();
+        assert!(solver.train_batches(&mut invalid).is_err());
+        let samples = (0..4).map(|i| (vec![0.5f32, -1f32, i as f32], vec![(i % 2) as f32])).collect::<Vec<_>>();
+        let mut batcher = IteratorBatcher::new(samples, 2, &[3], &[]).unwrap();
+        assert!(solver.train_batches(&mut batcher).unwrap().unwrap().is_finite());
+        assert_eq!(2, solver.iteration());
+    }
+
+    #[test]
+    fn stratified_split_keeps_class_shares() {
+        use leaf::solver::{Dataset, IDataset, stratified_split};
+
+        let mut dataset = Dataset::new(&[]);
+        for i in 0..20 {
+            dataset.add_sample(vec![i as f32], vec![if i < 15 { 0f32 } else { 1f32 }]);
+        }
+        let (train, validation) = stratified_split(&dataset, 0.2f32, 7, 2, &[1]).unwrap();
+        assert_eq!(16, train.indices().len());
+        let num_positive = validation.indices().iter().filter(|&&index| dataset.sample(index).1[0] == 1f32).count();
+        assert_eq!(4, validation.indices().len());
+        assert_eq!(1, num_positive);
+        assert_eq!(validation.indices(), stratified_split(&dataset, 0.2f32, 7, 2, &[1]).unwrap().1.indices());
+
+        // large class ids don't need a slot for every smaller class
+        let mut sparse = Dataset::new(&[]);
+        for i in 0..4 {
+            sparse.add_sample(vec![i as f32], vec![if i < 2 { 0f32 } else { 1e6f32 }]);
+        }
+        assert_eq!(2, stratified_split(&sparse, 0.5f32, 7, 1, &[1]).unwrap().1.indices().len());
+    }
+
+    #[test]
+    fn stratified_split_rejects_invalid_input() {
+        use leaf::solver::{Dataset, stratified_split};
+
+        let mut dataset = Dataset::new(&[]);
+        dataset.add_sample(vec![0f32], vec![0f32]);
+        for &fraction in &[-0.1f32, 1.5f32, ::std::f32::NAN] {
+            assert!(stratified_split(&dataset, fraction, 7, 1, &[1]).is_err());
+        }
+        let mut without_targets = Dataset::new(&[0]);
+        without_targets.add_sample(vec![0f32], vec![]);
+        assert!(stratified_split(&without_targets, 0.5f32, 7, 1, &[1]).is_err());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn stratified_split_pads_the_last_validation_batch() {
+        use leaf::solver::{Dataset, IBatcher, stratified_split};
+        use leaf::util::tensor_to_vec;
+
+        let mut dataset = Dataset::new(&[]);
+        for i in 0..10 {
+            dataset.add_sample(vec![i as f32], vec![0f32]);
+        }
+        let (_, mut validation) = stratified_split(&dataset, 0.3f32, 7, 2, &[1]).unwrap();
+        assert_eq!(3, validation.indices().len());
+        let first = tensor_to_vec(&validation.next_batch().unwrap().unwrap().0);
+        let last = tensor_to_vec(&validation.next_batch().unwrap().unwrap().0);
+        assert_eq!(first[0], last[1]);
+        assert!(validation.next_batch().is_none());
+    }
+
+    #[test]
+    fn task_sampler_follows_schedule() {
+        use leaf::solver::{TaskSampler, TaskSchedule};
+
+        let mut round_robin = TaskSampler::new(TaskSchedule::RoundRobin);
+        let tasks = (0..5).map(|iter| round_robin.next_task(iter, &[true, false, true]).unwrap()).collect::<Vec<_>>();
+        assert_eq!(vec![0, 2, 0, 2, 0], tasks);
+        assert_eq!(None, round_robin.next_task(5, &[false, false, false]));
+
+        let mut weighted = TaskSampler::new(TaskSchedule::Weighted(vec![0f32, 1f32, 3f32]));
+        let tasks = (0..1000).map(|iter| weighted.next_task(iter, &[true, true, true]).unwrap()).collect::<Vec<_>>();
+        assert!(!tasks.contains(&0));
+        let share = tasks.iter().filter(|&&task| task == 2).count() as f32 / tasks.len() as f32;
+        assert!((share - 0.75f32).abs() < 0.05f32, "task 2 was chosen for {} of the iterations", share);
+        assert_eq!(Some(1), weighted.next_task(0, &[true, true, false]));
+
+        let curriculum = TaskSchedule::Curriculum { start: vec![1f32, 0f32], end: vec![0f32, 2f32], iterations: 10 };
+        assert_eq!(vec![1f32, 0f32], curriculum.weights(2, 0));
+        assert_eq!(vec![0.5f32, 1f32], curriculum.weights(2, 5));
+        assert_eq!(vec![0f32, 2f32], curriculum.weights(2, 20));
+        let mut sampler = TaskSampler::new(curriculum);
+        assert_eq!(Some(0), sampler.next_task(0, &[true, true]));
+        assert_eq!(Some(1), sampler.next_task(10, &[true, true]));
+    }
+
+    #[cfg(feature="native")]
+    fn online_config(batch_size: usize, online_decay: Option<f32>) -> ::leaf::solver::SolverConfig {
+        use leaf::layer::LayerConfig;
+        use leaf::layers::{NegativeLogLikelihoodConfig, SequentialConfig};
+        use leaf::models::logistic_regression;
+        use leaf::solver::SolverConfig;
+
+        let mut objective_cfg = SequentialConfig::default();
+        objective_cfg.add_input("network_out", &[batch_size, 2]);
+        objective_cfg.add_input("label", &[batch_size]);
+        objective_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig::default()));
+        SolverConfig {
+            network: LayerConfig::new("network", logistic_regression(batch_size, 3, 2)),
+            objective: LayerConfig::new("classifier", objective_cfg),
+            online_decay: online_decay,
+            ..SolverConfig::default()
+        }
+    }
+
+    #[cfg(feature="native")]
+    fn online_solver(batch_size: usize, online_decay: Option<f32>) -> ::leaf::solver::Solver<::co::prelude::Backend<::co::prelude::Native>, ::co::prelude::Backend<::co::prelude::Native>> {
+        use std::rc::Rc;
+        use leaf::solver::Solver;
+        use leaf::util::native_backend;
+
+        let backend = Rc::new(native_backend());
+        Solver::from_config(backend.clone(), backend.clone(), &online_config(batch_size, online_decay)).unwrap()
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn partial_fit_updates_weights_per_sample() {
+        use leaf::util::tensor_to_vec;
+
+        for &online_decay in &[None, Some(0.5f32)] {
+            let mut solver = online_solver(1, online_decay);
+            assert_eq!(None, solver.online_loss());
+            let mut losses = Vec::new();
+            for i in 0..4 {
+                let weights = solver.network().learnable_weights_data().iter().map(tensor_to_vec).collect::<Vec<_>>();
+                losses.push(solver.partial_fit(&[0.5f32, -1f32, i as f32], &[(i % 2) as f32]));
+                assert_eq!(i + 1, solver.iteration());
+                assert!(weights != solver.network().learnable_weights_data().iter().map(tensor_to_vec).collect::<Vec<_>>());
+            }
+
+            let expected = match online_decay {
+                None => losses.iter().fold(0f32, |sum, loss| sum + loss) / 4f32,
+                Some(decay) => losses[1..].iter().fold(losses[0], |average, loss| decay * average + (1f32 - decay) * loss),
+            };
+            assert!((expected - solver.online_loss().unwrap()).abs() < 1e-6);
+        }
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn online_decay_only_affects_the_online_loss() {
+        use leaf::util::{tensor_to_vec, write_tensor};
+
+        let mut undecayed = online_solver(1, None);
+        let mut decayed = online_solver(1, Some(0.9f32));
+        for (weight, other) in undecayed.network().learnable_weights_data().iter().zip(decayed.network().learnable_weights_data()) {
+            write_tensor(&other, &tensor_to_vec(weight)).unwrap();
+        }
+        for i in 0..4 {
+            let sample = [0.5f32, -1f32, i as f32];
+            assert_eq!(undecayed.partial_fit(&sample, &[(i % 2) as f32]), decayed.partial_fit(&sample, &[(i % 2) as f32]));
+        }
+        let weights = |solver: &::leaf::solver::Solver<_, _>| solver.network().learnable_weights_data().iter().map(tensor_to_vec).collect::<Vec<_>>();
+        assert_eq!(weights(&undecayed), weights(&decayed));
+        assert!(undecayed.online_loss() != decayed.online_loss());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn online_decay_has_to_be_a_fraction() {
+        use std::rc::Rc;
+        use leaf::solver::Solver;
+        use leaf::util::native_backend;
+
+        let backend = Rc::new(native_backend());
+        for &online_decay in &[-0.1f32, 1f32, 1.5f32, ::std::f32::NAN] {
+            assert!(Solver::from_config(backend.clone(), backend.clone(), &online_config(1, Some(online_decay))).is_err());
+        }
+        assert!(Solver::from_config(backend.clone(), backend.clone(), &online_config(1, Some(0f32))).is_ok());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    #[should_panic]
+    fn partial_fit_requires_batch_size_one() {
+        online_solver(2, None).partial_fit(&[0.5f32, -1f32, 1f32], &[1f32]);
+    }
+}
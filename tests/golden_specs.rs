@@ -0,0 +1,128 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+#[cfg(feature="native")]
+mod golden_spec {
+    use std::rc::Rc;
+    use std::sync::{Arc, RwLock};
+    use co::prelude::*;
+    use leaf::layer::*;
+    use leaf::layers::*;
+    use leaf::models;
+    use leaf::util::{native_backend, tensor_from_slice, tensor_to_vec, write_tensor};
+
+    // The reference values were computed independently in double precision
+    // from the same weights, inputs and output gradients.
+
+    /// Deterministic values in `[-scale, scale]` that don't repeat within small tensors.
+    fn values(len: usize, frequency: f64, phase: f64, scale: f64) -> Vec<f32> {
+        (0..len).map(|i| ((i as f64 * frequency + phase).sin() * scale) as f32).collect()
+    }
+
+    fn weight_values(len: usize) -> Vec<f32> {
+        values(len, 1.3, 0.5, 0.8)
+    }
+
+    fn input_values(len: usize) -> Vec<f32> {
+        values(len, 2.1, 1.0, 1.0)
+    }
+
+    fn gradient_values(len: usize) -> Vec<f32> {
+        values(len, 0.7, 2.0, 0.5)
+    }
+
+    fn assert_golden(what: &str, expected: &[f32], actual: &[f32]) {
+        assert_eq!(expected.len(), actual.len(), "{} differ in length", what);
+        for (i, (expected_value, actual_value)) in expected.iter().zip(actual).enumerate() {
+            assert!((expected_value - actual_value).abs() <= 1e-5 * (1f32 + expected_value.abs()),
+                    "{} differ at {}: expected {}, got {}", what, i, expected_value, actual_value);
+        }
+    }
+
+    /// The reference values of a network for a single forward and backward pass.
+    struct Golden {
+        outputs: &'static [f32],
+        input_gradients: &'static [f32],
+        weights_gradients: &'static [&'static [f32]],
+    }
+
+    /// Create the network `config`, fill its weights one after the other with `weight_values`
+    /// and compare a forward and backward pass with `golden`.
+    ///
+    /// Shared weights are only listed once by the network, so they are only filled once.
+    /// The forward pass is repeated to make sure that the outputs only depend on the inputs.
+    fn assert_network_golden(config: &LayerConfig, input: &[f32], golden: &Golden) {
+        let backend = Rc::new(native_backend());
+        let mut network = Layer::from_config(backend.clone(), config);
+        let weights = network.learnable_weights_data();
+        let num_weights = weights.iter().fold(0, |sum, weight| sum + weight.read().unwrap().desc().size());
+        let mut all_values = weight_values(num_weights).into_iter();
+        for weight in &weights {
+            let size = weight.read().unwrap().desc().size();
+            write_tensor(weight, &all_values.by_ref().take(size).collect::<Vec<f32>>()).unwrap();
+        }
+
+        let input_shape = network.input_shapes()[0].1.clone();
+        let input = Arc::new(RwLock::new(tensor_from_slice(&*backend, &input_shape, input).unwrap()));
+        for _ in 0..2 {
+            let output = network.forward(&[input.clone()])[0].clone();
+            assert_golden(&format!("outputs of {}", config.name), golden.outputs, &tensor_to_vec(&output));
+        }
+
+        let output_shape = network.output_blobs_data[0].read().unwrap().desc().clone();
+        let output_gradient = tensor_from_slice(&*backend, &output_shape, &gradient_values(output_shape.size())).unwrap();
+        let input_gradients = network.backward_input(&[Arc::new(RwLock::new(output_gradient))]);
+        network.backward_parameters();
+        assert_golden(&format!("input gradients of {}", config.name), golden.input_gradients, &tensor_to_vec(&input_gradients[0]));
+        let weights_gradients = network.learnable_weights_gradients();
+        assert_eq!(golden.weights_gradients.len(), weights_gradients.len());
+        for (i, (expected, actual)) in golden.weights_gradients.iter().zip(weights_gradients.iter()).enumerate() {
+            assert_golden(&format!("gradients of weight {} of {}", i, config.name), expected, &tensor_to_vec(actual));
+        }
+    }
+
+    #[test]
+    fn golden_mlp() {
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &[2, 3]);
+        net_cfg.add_layer(LayerConfig::new("linear1", LinearConfig::new(4)));
+        net_cfg.add_layer(LayerConfig::new("relu", LayerType::ReLU));
+        net_cfg.add_layer(LayerConfig::new("linear2", LinearConfig::new(2)));
+        net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+
+        assert_network_golden(&LayerConfig::new("mlp", net_cfg), &input_values(6), &Golden {
+            outputs: &[0.4365616, 0.3060085, 0.4371959, 0.3046023],
+            input_gradients: &[-0.0385751, -0.0019662, 0.0375231, 0.0366471, -0.0365877, -0.0562214],
+            weights_gradients: &[
+                &[-0.0387019, -0.0013159, 0.0400306, 0.0, 0.0, 0.0, 0.0187741, -0.0002651, -0.0185064, 0.0, 0.0, 0.0],
+                &[0.0264830, 0.0, 0.1048420, 0.0, -0.0126341, 0.0, -0.0540883, 0.0],
+            ],
+        });
+    }
+
+    #[test]
+    fn golden_log_softmax_classifier() {
+        assert_network_golden(&LayerConfig::new("classifier", models::logistic_regression(1, 4, 3)), &input_values(4), &Golden {
+            outputs: &[-0.7572745, -1.5406774, -1.1494175],
+            input_gradients: &[0.2721646, 0.2938574, -0.1149516, -0.3553562],
+            weights_gradients: &[
+                &[0.1692646, 0.0083641, -0.1777097, 0.1710680, 0.0823639, 0.0040699, -0.0864733, 0.0832414,
+                  -0.2516284, -0.0124340, 0.2641830, -0.2543095],
+            ],
+        });
+    }
+
+    #[test]
+    fn golden_tied_autoencoder() {
+        let input = input_values(8).iter().map(|value| value.abs()).collect::<Vec<f32>>();
+        assert_network_golden(&LayerConfig::new("autoencoder", models::autoencoder(2, &[4, 2], true)), &input, &Golden {
+            outputs: &[0.4819417, 0.6510657, 0.6001180, 0.3997419, 0.4824050, 0.7138518, 0.6363335, 0.3509554],
+            input_gradients: &[0.0182163, 0.0253919, -0.0046316, -0.0278698, -0.0131258, -0.0264487, -0.0010242, 0.0259008],
+            // the gradients of the encoder and the decoder are summed for the shared weight
+            weights_gradients: &[
+                &[-0.0029262, -0.0554977, -0.0153703, 0.0336907, -0.0378598, -0.0275056, -0.0308794, -0.0111708],
+            ],
+        });
+    }
+}
@@ -0,0 +1,168 @@
+extern crate leaf;
+
+#[cfg(test)]
+mod image_spec {
+    use std::io;
+    use leaf::image::*;
+    use leaf::solver::IDataset;
+
+    fn chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+        let len = data.len() as u32;
+        let mut chunk = vec![(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+        chunk.extend(chunk_type);
+        chunk.extend(data);
+        // the checksum is not verified by the decoder
+        chunk.extend(&[0, 0, 0, 0]);
+        chunk
+    }
+
+    fn png(header: &[u8], idat: &[u8]) -> Vec<u8> {
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        png.extend(chunk(b"IHDR", header));
+        png.extend(chunk(b"IDAT", idat));
+        png.extend(chunk(b"IEND", &[]));
+        png
+    }
+
+    /// A 2x2 RGB image with a stored (uncompressed) deflate block and a Sub and Up filtered row.
+    fn rgb_png() -> Vec<u8> {
+        let scanlines = [1, 10, 20, 30, 5, 5, 5,
+                         2, 1, 2, 3, 4, 5, 6];
+        stored_png(&scanlines)
+    }
+
+    fn stored_png(scanlines: &[u8]) -> Vec<u8> {
+        let mut idat = vec![0x78, 0x01, 1, scanlines.len() as u8, 0, !(scanlines.len() as u8), 0xFF];
+        idat.extend(scanlines);
+        png(&[0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0], &idat)
+    }
+
+    fn assert_invalid_data<T>(result: io::Result<T>) {
+        match result {
+            Err(ref err) if err.kind() == io::ErrorKind::InvalidData => {}
+            Err(err) => panic!("unexpected error {:?}", err),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn decode_png() {
+        let image = Image::decode(&rgb_png()).unwrap();
+        assert_eq!((2, 2, 3), (image.width, image.height, image.channels));
+        assert_eq!(vec![10, 20, 30, 15, 25, 35,
+                        11, 22, 33, 19, 30, 41], image.pixels);
+    }
+
+    #[test]
+    fn decode_png_with_fixed_huffman_codes() {
+        // a 4x2 grayscale image with an Up filtered row, compressed with fixed Huffman codes
+        let idat = [0x78, 0x01, 0x63, 0xE0, 0x12, 0x91, 0xD3, 0x60, 0x62, 0x04, 0x02, 0x00, 0x02, 0xDA, 0x00, 0x6B];
+        let image = Image::decode(&png(&[0, 0, 0, 4, 0, 0, 0, 2, 8, 0, 0, 0, 0], &idat)).unwrap();
+        assert_eq!((4, 2, 1), (image.width, image.height, image.channels));
+        assert_eq!(vec![10, 20, 30, 40, 11, 21, 31, 41], image.pixels);
+    }
+
+    #[test]
+    fn decode_png_with_dynamic_huffman_codes() {
+        // a 16x8 grayscale image, compressed with dynamic Huffman codes
+        let idat = [0x78, 0xDA, 0x5D, 0xCD, 0xC1, 0x09, 0x00, 0x30, 0x0C, 0x02, 0x40, 0xC7, 0x71, 0x1C, 0xC7, 0x71,
+                    0x1C, 0xC7, 0xC9, 0x38, 0x19, 0xA1, 0xB4, 0x8F, 0x10, 0xFA, 0x3A, 0x04, 0x51, 0x80, 0x91, 0xC2,
+                    0x01, 0x2E, 0x51, 0xE5, 0x01, 0xC5, 0x6E, 0x56, 0x06, 0xB4, 0xE5, 0x4E, 0x06, 0xEC, 0x70, 0xC1,
+                    0x6A, 0x3F, 0xE0, 0x6F, 0x15, 0xFF, 0xED, 0x01, 0x35, 0x1F, 0x40, 0xB1];
+        let image = Image::decode(&png(&[0, 0, 0, 16, 0, 0, 0, 8, 8, 0, 0, 0, 0], &idat)).unwrap();
+        assert_eq!((16, 8, 1), (image.width, image.height, image.channels));
+        let expected: Vec<u8> = (0..8).flat_map(|y| (0..16).map(move |x| ((x * x + 3 * y * y + x * y) % 7 * 40) as u8)).collect();
+        assert_eq!(expected, image.pixels);
+    }
+
+    #[test]
+    fn reject_invalid_png_sizes() {
+        // 100000 x 100000 pixels would need tens of gigabytes
+        assert_invalid_data(Image::decode(&png(&[0, 1, 0x86, 0xA0, 0, 1, 0x86, 0xA0, 8, 0, 0, 0, 0], &[])));
+        assert_invalid_data(Image::decode(&png(&[0, 0, 0, 0, 0, 0, 0, 2, 8, 0, 0, 0, 0], &[])));
+        // the deflate stream contains more data than the image
+        assert_invalid_data(Image::decode(&stored_png(&[0, 1, 2, 3, 4, 5, 6,
+                                                        0, 1, 2, 3, 4, 5, 6,
+                                                        0, 0, 0])));
+    }
+
+    #[test]
+    fn decode_gray_jpeg() {
+        let image = Image::load("tests/fixtures/gray_10x9.jpg").unwrap();
+        assert_eq!((10, 9, 1), (image.width, image.height, image.channels));
+        let expected: Vec<u8> = (0..9).flat_map(|y| (0..10).map(move |x| ((x * 20 + y * 7) % 256) as u8)).collect();
+        assert_eq!(expected, image.pixels);
+    }
+
+    #[test]
+    fn decode_color_jpeg() {
+        // 4:2:0 chroma subsampling with restart markers
+        let image = Image::load("tests/fixtures/color_18x10.jpg").unwrap();
+        assert_eq!((18, 10, 3), (image.width, image.height, image.channels));
+        for y in 0..10 {
+            for x in 0..18 {
+                let luma = ((x * 11 + y * 13) % 256) as f32;
+                let blue = (100 + (x / 2 + y / 2) % 3 * 20) as f32 - 128f32;
+                let red = (90 + x / 2 % 4 * 25) as f32 - 128f32;
+                let expected = [luma + 1.402 * red, luma - 0.344136 * blue - 0.714136 * red, luma + 1.772 * blue];
+                for channel in 0..3 {
+                    let pixel = image.pixels[(y * 18 + x) * 3 + channel] as f32;
+                    assert!((pixel - expected[channel].round().max(0f32).min(255f32)).abs() <= 3f32,
+                            "pixel ({}, {}) channel {} is {}", x, y, channel, pixel);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn convert_to_chw() {
+        let image = Image::decode(&rgb_png()).unwrap();
+        assert_eq!(vec![10f32, 15f32, 11f32, 19f32,
+                        20f32, 25f32, 22f32, 30f32,
+                        30f32, 35f32, 33f32, 41f32], image.to_chw());
+
+        let gray = image.to_channels(1).resize(1, 1).unwrap();
+        assert_eq!((1, 1, 1), (gray.width, gray.height, gray.channels));
+        assert!(image.resize(0, 1).is_err());
+    }
+
+    #[test]
+    fn image_dataset_loads_images() {
+        let mut dataset = ImageDataset::new(2, 3, 1).with_mean(&[10f32]);
+        dataset.add_image("tests/fixtures/gray_10x9.jpg", 2).unwrap();
+        assert_eq!(io::ErrorKind::NotFound, dataset.add_image("tests/fixtures/missing.jpg", 0).unwrap_err().kind());
+        assert_invalid_data(dataset.add_image("tests/fixtures/float32_list_layer.capnp", 0));
+
+        assert_eq!(1, dataset.len());
+        let (data, target) = dataset.sample(0);
+        assert_eq!(6, data.len());
+        assert_eq!(47f32 - 10f32, data[0]);
+        assert_eq!(vec![2f32], target);
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn filter_grid_encodes_as_png() {
+        use std::sync::{Arc, RwLock};
+        use leaf::util::{native_backend, tensor_from_slice};
+
+        let values = [0f32, 1f32, 2f32, 3f32, -1f32, -1f32, 1f32, 1f32];
+        let weight = Arc::new(RwLock::new(tensor_from_slice(&native_backend(), &[2, 1, 2, 2], &values).unwrap()));
+        let grid = filter_grid(&weight, &[1, 2, 2]).unwrap();
+        assert_eq!((5, 2, 1), (grid.width, grid.height, grid.channels));
+        assert_eq!(vec![0, 85, 0, 0, 0,
+                        170, 255, 0, 255, 255], grid.pixels);
+        assert!(filter_grid(&weight, &[2, 2, 2]).is_err());
+
+        let png = grid.encode_png();
+        assert_eq!(&[0xAE, 0x42, 0x60, 0x82], &png[png.len() - 4..]);
+        assert_eq!(grid.pixels, Image::decode(&png).unwrap().pixels);
+    }
+
+    #[test]
+    fn reject_unsupported_jpeg() {
+        assert_invalid_data(Image::decode(&[0xFF, 0xD8, 0xFF, 0xE0]));
+        let progressive = [0xFF, 0xD8, 0xFF, 0xC2, 0, 11, 8, 0, 8, 0, 8, 1, 1, 0x11, 0, 0xFF, 0xD9];
+        assert_invalid_data(Image::decode(&progressive));
+    }
+}
@@ -0,0 +1,114 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod inference_spec {
+    #[cfg(feature="native")]
+    #[test]
+    fn chunked_inference_respects_memory_budget() {
+        use std::rc::Rc;
+        use leaf::inference::{ChunkedInference, activation_memory_per_sample, weight_memory};
+        use leaf::layer::{Layer, LayerConfig, LayerType};
+        use leaf::layers::{LinearConfig, ReshapeConfig, SequentialConfig};
+        use leaf::util::{native_backend, tensor_to_vec};
+
+        let backend = Rc::new(native_backend());
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &[4, 3]);
+        net_cfg.add_layer(LayerConfig::new("linear1", LinearConfig::new(5)));
+        net_cfg.add_layer(LayerConfig::new("linear1/relu", LayerType::ReLU));
+        net_cfg.add_layer(LayerConfig::new("linear2", LinearConfig::new(2)));
+        let network = Layer::from_config(backend.clone(), &LayerConfig::new("network", net_cfg.clone()));
+        // 3 input values, 5 hidden values before and after the ReLU and 2 output values
+        // together with their gradients
+        assert_eq!(15 * 2 * 4, activation_memory_per_sample(&network));
+        // 3x5 and 5x2 weights together with their gradients
+        assert_eq!(25 * 2 * 4, weight_memory(&network));
+
+        assert!(ChunkedInference::new(&network, 120).is_err());
+        assert!(ChunkedInference::new(&network, 200 + 119).is_err());
+        assert_eq!(4, ChunkedInference::new(&network, 1000).unwrap().chunk_size());
+        let mut inference = ChunkedInference::new(&network, 200 + 3 * 120 + 119).unwrap();
+        assert_eq!(3, inference.chunk_size());
+        assert_eq!(vec![3, 3], inference.network().input_shapes()[0].1);
+
+        let inputs = (0..7 * 3).map(|value| value as f32 / 10f32 - 1f32).collect::<Vec<_>>();
+        let outputs = inference.run(&inputs).unwrap();
+        assert_eq!(7 * 2, outputs.len());
+        let mut single_sample = network.with_batch_size(1).unwrap();
+        for (sample, expected) in inputs.chunks(3).zip(outputs.chunks(2)) {
+            single_sample.fill_input("data", sample).unwrap();
+            let output = tensor_to_vec(&single_sample.forward(&[])[0]);
+            for (value, expected_value) in output.iter().zip(expected) {
+                assert!((value - expected_value).abs() < 1e-5);
+            }
+        }
+        assert!(inference.run(&inputs[1..]).is_err());
+
+        net_cfg.add_layer(LayerConfig::new("reshape", ReshapeConfig::of_shape(&[4, 2])));
+        let reshaping_network = Layer::from_config(backend.clone(), &LayerConfig::new("network", net_cfg));
+        assert!(ChunkedInference::new(&reshaping_network, 200 + 3 * 120).is_err());
+    }
+
+    #[cfg(feature="native")]
+    fn ensemble_member(name: &str, weight: &[f32]) -> ::leaf::layer::Layer<::co::Backend<::co::frameworks::Native>> {
+        use std::rc::Rc;
+        use leaf::layer::{Layer, LayerConfig};
+        use leaf::layers::{LinearConfig, SequentialConfig};
+        use leaf::util::{native_backend, write_tensor};
+
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &[2, 2]);
+        net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+        let member = Layer::from_config(Rc::new(native_backend()), &LayerConfig::new(name, net_cfg));
+        write_tensor(&member.learnable_weights_data()[0], weight).unwrap();
+        member
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn ensemble_combines_predictions_of_members() {
+        use leaf::ensemble::{Combination, Ensemble};
+
+        let inputs = vec![1f32, 2f32, 3f32, 1f32];
+        let identity = vec![1f32, 0f32, 0f32, 1f32];
+        let swap = vec![0f32, 1f32, 1f32, 0f32];
+
+        let mut average = Ensemble::new(Combination::Average);
+        assert!(average.predict(&inputs).is_err());
+        average.add_member(ensemble_member("identity", &identity), 1f32);
+        average.add_member(ensemble_member("swap", &swap), 3f32);
+        let expected = vec![1.75f32, 1.25f32, 1.5f32, 2.5f32];
+        for (value, expected_value) in average.predict(&inputs).unwrap().iter().zip(&expected) {
+            assert!((value - expected_value).abs() < 1e-5);
+        }
+        assert!(average.predict(&inputs[1..]).is_err());
+
+        let mut vote = Ensemble::new(Combination::MajorityVote);
+        vote.add_member(ensemble_member("identity", &identity), 1f32);
+        vote.add_member(ensemble_member("swap", &swap), 3f32);
+        assert_eq!(vec![0.75f32, 0.25f32, 0.25f32, 0.75f32], vote.predict(&inputs).unwrap());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn ensemble_loads_weights_per_member() {
+        use std::rc::Rc;
+        use leaf::ensemble::{Combination, Ensemble};
+        use leaf::util::{native_backend, tensor_to_vec};
+
+        let swap = vec![0f32, 1f32, 1f32, 0f32];
+        ensemble_member("swap", &swap).save("target/testensemblemember").unwrap();
+
+        let mut ensemble = Ensemble::new(Combination::Average);
+        ensemble.add_member(ensemble_member("identity", &[1f32, 0f32, 0f32, 1f32]), 1f32);
+        ensemble.add_member(ensemble_member("restored", &[0f32; 4]), 1f32);
+        ensemble.load_member_weights(1, "target/testensemblemember").unwrap();
+        assert_eq!(swap, tensor_to_vec(&ensemble.members()[1].learnable_weights_data()[0]));
+        assert_eq!(vec![1.5f32, 1.5f32, 2f32, 2f32], ensemble.predict(&[1f32, 2f32, 3f32, 1f32]).unwrap());
+
+        ensemble.load_member(Rc::new(native_backend()), "target/testensemblemember", 2f32).unwrap();
+        assert_eq!(3, ensemble.members().len());
+        assert_eq!(&[1f32, 1f32, 2f32], ensemble.weights());
+    }
+}
@@ -20,6 +20,34 @@ mod layer_spec {
         Rc::new(Backend::<Cuda>::default().unwrap())
     }
 
+    #[test]
+    fn linear_config_builder_fills_in_defaults() {
+        use leaf::layers::LinearConfig;
+
+        let linear = LinearConfig::new(3);
+        assert_eq!((3, false), (linear.output_size, linear.transpose));
+        assert!(LinearConfig::new(3).transpose(true).transpose);
+        assert_eq!(1, linear.num_threads);
+        assert_eq!(4, LinearConfig::new(3).num_threads(4).num_threads);
+    }
+
+    #[cfg(all(feature="cuda", not(feature="native")))]
+    #[test]
+    fn filter_config_builders_fill_in_defaults() {
+        use leaf::layers::*;
+
+        let convolution = ConvolutionConfig::new(64, &[3]);
+        assert_eq!((64, vec![3], vec![1], vec![0]),
+                   (convolution.num_output, convolution.filter_shape, convolution.stride, convolution.padding));
+        let convolution = ConvolutionConfig::new(64, &[11]).stride(&[4]).padding(&[2]);
+        assert_eq!((vec![4], vec![2]), (convolution.stride, convolution.padding));
+
+        let pooling = PoolingConfig::new(PoolingMode::Max, &[2]);
+        assert_eq!((vec![2], vec![2], vec![0]), (pooling.filter_shape, pooling.stride, pooling.padding));
+        let pooling = PoolingConfig::new(PoolingMode::Max, &[3]).stride(&[2]).padding(&[1]);
+        assert_eq!((vec![2], vec![1]), (pooling.stride, pooling.padding));
+    }
+
     #[cfg(all(feature="native", feature="cuda"))]
     mod native_cuda {
         use leaf::layer::*;
@@ -38,15 +66,25 @@ mod layer_spec {
 
     #[cfg(feature="native")]
     mod native {
+        use std::collections::HashMap;
+        use std::fs::File;
+        use std::io::{Read, Write};
+        use std::sync::{Arc, RwLock};
         use co::prelude::*;
         use leaf::layer::*;
         use leaf::layers::*;
+        use leaf::util::{pad_sequences, tensor_from_slice, tensor_to_vec, write_tensor, write_to_memory};
         use super::native_backend;
 
+        /// Returns a path in the target directory for the file `name`, which is unique to this test run.
+        fn temp_path(name: &str) -> String {
+            format!("target/{}-{}", name, ::std::process::id())
+        }
+
         fn simple_network() -> LayerConfig {
             let mut net_cfg = SequentialConfig::default();
             net_cfg.add_input("data", &vec![1, 1, 28, 28]);
-            net_cfg.add_layer(LayerConfig::new("linear", LayerType::Linear(LinearConfig { output_size: 10 })));
+            net_cfg.add_layer(LayerConfig::new("linear", LayerType::Linear(LinearConfig::new(10))));
 
             LayerConfig::new("network", net_cfg)
         }
@@ -56,10 +94,13 @@ mod layer_spec {
             let cfg = simple_network();
             let mut original_layer = Layer::from_config(native_backend(), &cfg);
 
-            original_layer.save("target/testnetwork").unwrap();
-            let mut loaded_layer = Layer::<Backend<Native>>::load(native_backend(), "target/testnetwork").unwrap();
+            let path = temp_path("testnetwork");
+            original_layer.save(&path).unwrap();
+            let loaded_layer = Layer::<Backend<Native>>::load(native_backend(), &path).unwrap();
 
             assert_eq!(original_layer.input_blob_names(), loaded_layer.input_blob_names());
+            assert_eq!(original_layer.input_shapes(), loaded_layer.input_shapes());
+            assert_eq!(vec![("data".to_owned(), vec![1, 1, 28, 28])], loaded_layer.input_shapes());
 
             let original_weights = original_layer.learnable_weights_data();
             let original_weight_lock = original_weights[0].read().unwrap();
@@ -71,6 +112,1499 @@ mod layer_spec {
 
             assert_eq!(original_weight, loaded_weight);
         }
+
+        #[test]
+        fn save_and_load_nested_layers_with_same_names() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            for &(container, output_size) in [("encoder", 2), ("decoder", 4)].iter() {
+                let mut container_cfg = SequentialConfig::default();
+                container_cfg.add_input("data", &vec![1, 6 - output_size]);
+                container_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(output_size)));
+                net_cfg.add_layer(LayerConfig::new(container, container_cfg));
+            }
+            let mut original_layer = Layer::from_config(native_backend(), &LayerConfig::new("net", net_cfg));
+            assert_eq!(vec!["linear-0".to_owned(), "linear-0".to_owned()], original_layer.learnable_weights_names());
+            assert_eq!(vec!["net/encoder/linear/weight".to_owned(), "net/decoder/linear/weight".to_owned()],
+                       original_layer.learnable_weights_paths());
+
+            let path = temp_path("testnestednetwork");
+            original_layer.save(&path).unwrap();
+            let loaded_layer = Layer::<Backend<Native>>::load(native_backend(), &path).unwrap();
+            for (original, loaded) in original_layer.learnable_weights_data().iter().zip(loaded_layer.learnable_weights_data()) {
+                let original_lock = original.read().unwrap();
+                let loaded_lock = loaded.read().unwrap();
+                assert_eq!(original_lock.desc(), loaded_lock.desc());
+                assert_eq!(original_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>(),
+                           loaded_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>());
+            }
+        }
+
+        #[test]
+        fn with_batch_size_keeps_weights_of_nested_layers_with_same_names() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            for &container in ["encoder", "decoder"].iter() {
+                let mut container_cfg = SequentialConfig::default();
+                container_cfg.add_input("data", &vec![1, 4]);
+                container_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(4)));
+                net_cfg.add_layer(LayerConfig::new(container, container_cfg));
+            }
+            let network = Layer::from_config(native_backend(), &LayerConfig::new("net", net_cfg));
+            assert_eq!(vec!["linear-0".to_owned(), "linear-0".to_owned()], network.learnable_weights_names());
+            for (offset, weight) in network.learnable_weights_data().iter().enumerate() {
+                let values = (0..16).map(|value| (value + 16 * offset) as f32).collect::<Vec<_>>();
+                write_tensor(weight, &values).unwrap();
+            }
+
+            let larger_network = network.with_batch_size(2).unwrap();
+            assert_eq!(network.learnable_weights_paths(), larger_network.learnable_weights_paths());
+            let weights = larger_network.learnable_weights_data().iter()
+                .map(|weight| tensor_to_vec(weight)).collect::<Vec<_>>();
+            assert_eq!((0..16).map(|value| value as f32).collect::<Vec<_>>(), weights[0]);
+            assert_eq!((16..32).map(|value| value as f32).collect::<Vec<_>>(), weights[1]);
+        }
+
+        #[test]
+        fn save_and_load_labels_for_prediction() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 3]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3)));
+            net_cfg.add_layer(LayerConfig::new("log_softmax", LogSoftmaxConfig::default()));
+            let mut original_layer = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            assert!(original_layer.predict_labeled(&[]).is_err());
+            original_layer.set_labels(vec!["cat", "dog"]);
+            assert!(original_layer.predict_labeled(&[]).is_err());
+            original_layer.set_labels(vec!["cat", "dog", "bird"]);
+            let path = temp_path("testlabelednetwork");
+            original_layer.save(&path).unwrap();
+
+            let mut loaded_layer = Layer::<Backend<Native>>::load(native_backend(), &path).unwrap();
+            assert_eq!(&["cat".to_owned(), "dog".to_owned(), "bird".to_owned()], loaded_layer.labels());
+            loaded_layer.fill_input("data", &[1f32, -2f32, 0.5f32]).unwrap();
+            let predictions = loaded_layer.predict_labeled(&[]).unwrap();
+            assert_eq!(1, predictions.len());
+            for sample in &predictions {
+                let mut labels = sample.iter().map(|&(ref label, _)| label.as_str()).collect::<Vec<_>>();
+                labels.sort();
+                assert_eq!(vec!["bird", "cat", "dog"], labels);
+                assert!(sample.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+                let total = sample.iter().fold(0f32, |total, &(_, probability)| total + probability);
+                assert!((total - 1f32).abs() < 1e-5);
+            }
+        }
+
+        #[test]
+        fn load_strict_accepts_complete_weights() {
+            let mut original_layer = Layer::from_config(native_backend(), &simple_network());
+            let path = temp_path("teststrictnetwork");
+            original_layer.save(&path).unwrap();
+            let (_, report) = Layer::<Backend<Native>>::load_with_report(native_backend(), &path).unwrap();
+            assert_eq!(vec!["network/linear/weight".to_owned()], report.loaded);
+            assert!(report.is_complete());
+            assert!(Layer::<Backend<Native>>::load_strict(native_backend(), &path).is_ok());
+        }
+
+        #[test]
+        fn load_rejects_corrupted_weights() {
+            let mut layer = Layer::from_config(native_backend(), &simple_network());
+            {
+                let weight = layer.learnable_weights_data()[0].clone();
+                let mut weight_lock = weight.write().unwrap();
+                let values = vec![f32::from_bits(0x3f123456); 7840];
+                write_to_memory(weight_lock.get_mut(native_backend().device()).unwrap(), &values);
+            }
+            let path = temp_path("testcorruptednetwork");
+            layer.save(&path).unwrap();
+            assert!(!::std::fs::read_dir("target").unwrap().any(|entry| {
+                let file_name = entry.unwrap().file_name().into_string().unwrap();
+                file_name.starts_with("testcorruptednetwork") && file_name.ends_with(".tmp")
+            }));
+            assert!(Layer::<Backend<Native>>::load(native_backend(), &path).is_ok());
+
+            let mut bytes = Vec::new();
+            File::open(&path).unwrap().read_to_end(&mut bytes).unwrap();
+            let position = bytes.windows(4).position(|window| window == [0x56, 0x34, 0x12, 0x3f]).unwrap();
+            bytes[position] = 0x57;
+            File::create(&path).unwrap().write_all(&bytes).unwrap();
+            let error = Layer::<Backend<Native>>::load(native_backend(), &path).unwrap_err();
+            assert_eq!(::std::io::ErrorKind::InvalidData, error.kind());
+        }
+
+        #[test]
+        fn failed_save_removes_temporary_file() {
+            let has_temp_file = |directory: &str, name: &str| ::std::fs::read_dir(directory).unwrap().any(|entry| {
+                let file_name = entry.unwrap().file_name().into_string().unwrap();
+                file_name.starts_with(name) && file_name.ends_with(".tmp")
+            });
+            let mut layer = Layer::from_config(native_backend(), &simple_network());
+
+            let missing_directory_path = format!("{}/network", temp_path("testmissingdirectory"));
+            assert!(layer.save(&missing_directory_path).is_err());
+
+            // the temporary file is written, but can't be renamed to a directory
+            let directory_path = temp_path("testsavetodirectory");
+            ::std::fs::create_dir_all(format!("{}/network", directory_path)).unwrap();
+            assert!(layer.save(&directory_path).is_err());
+            assert!(!has_temp_file("target", "testsavetodirectory"));
+            ::std::fs::remove_dir_all(&directory_path).unwrap();
+        }
+
+        #[test]
+        fn load_rejects_weights_with_a_different_shape() {
+            let mut layer = Layer::from_config(native_backend(), &simple_network());
+            {
+                let weight = layer.learnable_weights_data()[0].clone();
+                *weight.write().unwrap() = tensor_from_slice(&*native_backend(), &[10, 10], &[0.5f32; 100]).unwrap();
+            }
+            let path = temp_path("testreshapednetwork");
+            layer.save(&path).unwrap();
+            let error = Layer::<Backend<Native>>::load_with_report(native_backend(), &path).unwrap_err();
+            assert_eq!(::std::io::ErrorKind::InvalidData, error.kind());
+            let error = Layer::<Backend<Native>>::load_strict(native_backend(), &path).unwrap_err();
+            assert_eq!(::std::io::ErrorKind::InvalidData, error.kind());
+        }
+
+        #[test]
+        fn load_layer_with_float32_list_weights() {
+            // saved by a version of Leaf that stored weights as a list of floats
+            let layer = Layer::<Backend<Native>>::load(native_backend(), "tests/fixtures/float32_list_layer.capnp").unwrap();
+            let weight = layer.learnable_weights_data()[0].clone();
+            let weight_lock = weight.read().unwrap();
+            let values = weight_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            assert_eq!(&[1f32, -2f32, 0.5f32, 4f32], values);
+        }
+
+        #[test]
+        fn load_rejects_weights_with_missing_values() {
+            // a Linear weight of shape [2, 2], saved with an odd number of bytes of float16 data
+            let error = Layer::<Backend<Native>>::load(native_backend(), "tests/fixtures/odd_float16_layer.capnp").unwrap_err();
+            assert_eq!(::std::io::ErrorKind::InvalidData, error.kind());
+        }
+
+        #[test]
+        fn load_rejects_files_that_are_not_layers() {
+            let path = temp_path("testgarbagenetwork");
+            File::create(&path).unwrap().write_all(b"not a layer").unwrap();
+            let error = Layer::<Backend<Native>>::load(native_backend(), &path).unwrap_err();
+            assert_eq!(::std::io::ErrorKind::InvalidData, error.kind());
+        }
+
+        #[test]
+        fn save_with_reduced_precision() {
+            let mut original_layer = Layer::from_config(native_backend(), &simple_network());
+            let original_weight = original_layer.learnable_weights_data()[0].clone();
+            let original_values = original_weight.read().unwrap().get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_vec();
+            let max = original_values.iter().fold(0f32, |max, value| max.max(value.abs()));
+
+            for &(encoding, tolerance) in [(WeightEncoding::Float16, 1e-3f32 * max), (WeightEncoding::Int8, max / 254f32 + 1e-6)].iter() {
+                let path = temp_path("testencodednetwork");
+                original_layer.save_with_encoding(&path, encoding).unwrap();
+                let loaded_layer = Layer::<Backend<Native>>::load(native_backend(), &path).unwrap();
+                let loaded_weight = loaded_layer.learnable_weights_data()[0].clone();
+                let loaded_lock = loaded_weight.read().unwrap();
+                let loaded_values = loaded_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+                assert_eq!(original_values.len(), loaded_values.len());
+                for (original, loaded) in original_values.iter().zip(loaded_values.iter()) {
+                    assert!((original - loaded).abs() <= tolerance, "{:?}: {} != {}", encoding, original, loaded);
+                }
+            }
+        }
+
+        #[test]
+        fn transplant_weights_copies_overlapping_rows() {
+            let mut original_cfg = SequentialConfig::default();
+            original_cfg.add_input("data", &vec![1, 3]);
+            original_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+            let mut original = Layer::from_config(native_backend(), &LayerConfig::new("network", original_cfg));
+            let path = temp_path("testtransplantnetwork");
+            original.save(&path).unwrap();
+
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 3]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3)));
+            net_cfg.add_layer(LayerConfig::new("head", LinearConfig::new(1)));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            let transfers = network.transplant_weights(&path).unwrap();
+
+            assert_eq!(network.learnable_weights_names(), transfers.iter().map(|t| t.name.clone()).collect::<Vec<_>>());
+            assert_eq!(vec![3, 3], transfers[0].shape);
+            assert_eq!(Some(vec![2, 3]), transfers[0].source_shape);
+            assert_eq!(6, transfers[0].copied);
+            assert!(!transfers[0].is_complete());
+            assert_eq!(None, transfers[1].source_shape);
+            assert_eq!(0, transfers[1].copied);
+
+            let original_weight = original.learnable_weights_data()[0].clone();
+            let original_lock = original_weight.read().unwrap();
+            let weight = network.learnable_weights_data()[0].clone();
+            let weight_lock = weight.read().unwrap();
+            let original_values = original_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            let values = weight_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            assert_eq!(original_values, &values[..6]);
+        }
+
+        #[test]
+        fn fill_input_writes_into_input_tensor() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![3]);
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            assert!(network.fill_input("data", &[1f32, 1f32, 2f32]).is_ok());
+            assert!(network.fill_input("data", &[1f32, 1f32]).is_err());
+            assert!(network.fill_input("label", &[1f32, 1f32, 2f32]).is_err());
+
+            let output = network.forward(&[])[0].clone();
+            let output_lock = output.read().unwrap();
+            let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(&[0.7310585786f32, 0.7310586f32, 0.880797f32], output_native.as_slice::<f32>());
+        }
+
+        #[test]
+        fn queued_forward_passes_are_read_after_synchronize() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 2]);
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            let inputs = [[0f32, 0f32], [2f32, -2f32]].iter().map(|values| {
+                let mut input = SharedTensor::<f32>::new(native_backend().device(), &vec![1, 2]).unwrap();
+                write_to_memory(input.get_mut(native_backend().device()).unwrap(), values);
+                Arc::new(RwLock::new(input))
+            }).collect::<Vec<_>>();
+            // several forward passes are issued before any output is read
+            let first_output = network.forward(&inputs[0..1])[0].clone();
+            let second_output = network.forward(&inputs[1..2])[0].clone();
+            network.synchronize();
+
+            // both passes write into the same output blob, which holds the result of the last one
+            let expected = [1f32 / (1f32 + (-2f32).exp()), 1f32 / (1f32 + 2f32.exp())];
+            for output in &[first_output, second_output] {
+                let values = tensor_to_vec(output);
+                assert!((values[0] - expected[0]).abs() < 1e-6 && (values[1] - expected[1]).abs() < 1e-6, "{:?}", values);
+            }
+        }
+
+        #[test]
+        fn forward_named_matches_inputs_by_name() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("network_out", &vec![2, 2]);
+            net_cfg.add_input("label", &vec![2]);
+            net_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(2) }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("loss", net_cfg));
+
+            let mut network_out = SharedTensor::<f32>::new(native_backend().device(), &vec![2, 2]).unwrap();
+            write_to_memory(network_out.get_mut(native_backend().device()).unwrap(), &[-0.5f32, -1f32, -1f32, -0.5f32]);
+            let mut label = SharedTensor::<f32>::new(native_backend().device(), &vec![2]).unwrap();
+            write_to_memory(label.get_mut(native_backend().device()).unwrap(), &[0f32, 1f32]);
+            let mut inputs = HashMap::new();
+            inputs.insert("label", Arc::new(RwLock::new(label)));
+            assert!(network.forward_named(&inputs).is_err());
+            inputs.insert("network_out", inputs["label"].clone());
+            assert!(network.forward_named(&inputs).is_err());
+            inputs.insert("network_out", Arc::new(RwLock::new(network_out)));
+            inputs.insert("data", inputs["label"].clone());
+            assert!(network.forward_named(&inputs).is_err());
+            inputs.remove("data");
+            assert!(network.forward_named(&inputs).is_ok());
+        }
+
+        #[test]
+        fn label_inputs_are_routed_to_loss_layer() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2, 2]);
+            net_cfg.add_label_input("label", &vec![2]);
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            net_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(2) }));
+            let mut original_network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            let path = temp_path("testlabelnetwork");
+            original_network.save(&path).unwrap();
+            let mut network = Layer::<Backend<Native>>::load(native_backend(), &path).unwrap();
+            assert_eq!(vec![("data".to_owned(), vec![2, 2]), ("label".to_owned(), vec![2])], network.input_shapes());
+
+            let mut data = SharedTensor::<f32>::new(native_backend().device(), &vec![2, 2]).unwrap();
+            write_to_memory(data.get_mut(native_backend().device()).unwrap(), &[0f32, 2f32, 0f32, -2f32]);
+            let mut label = SharedTensor::<f32>::new(native_backend().device(), &vec![2]).unwrap();
+            write_to_memory(label.get_mut(native_backend().device()).unwrap(), &[1f32, 0f32]);
+            let mut inputs = HashMap::new();
+            inputs.insert("data", Arc::new(RwLock::new(data)));
+            inputs.insert("label", Arc::new(RwLock::new(label)));
+
+            let loss = network.forward_named(&inputs).unwrap()[0].clone();
+            let loss_lock = loss.read().unwrap();
+            let loss_native = loss_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(-1.380797f32, loss_native.as_slice::<f32>()[0]);
+        }
+
+        #[test]
+        fn negative_log_likelihood_rounds_labels() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("log_probabilities", &vec![2, 2]);
+            net_cfg.add_label_input("label", &vec![2]);
+            net_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(2) }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("loss", net_cfg));
+
+            network.fill_input("log_probabilities", &[-0.1f32, -0.2, -0.3, -0.4]).unwrap();
+            network.fill_input("label", &[0.99999994f32, 0.00000001]).unwrap();
+            let loss = network.forward(&[])[0].clone();
+            let loss_lock = loss.read().unwrap();
+            let loss_native = loss_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert!((loss_native.as_slice::<f32>()[0] - 0.5f32).abs() < 1e-6);
+        }
+
+        #[test]
+        fn save_for_inference_removes_loss_layer() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 2]);
+            net_cfg.add_label_input("label", &vec![1]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+            net_cfg.add_layer(LayerConfig::new("log_softmax", LogSoftmaxConfig::default()));
+            net_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(2) }));
+            let network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            let path = temp_path("testinferencenetwork");
+            network.save_for_inference(&path).unwrap();
+            let mut loaded_network = Layer::<Backend<Native>>::load(native_backend(), &path).unwrap();
+            assert_eq!(vec![("data".to_owned(), vec![1, 2])], loaded_network.input_shapes());
+
+            let weight = network.learnable_weights_data()[0].clone();
+            let weight_lock = weight.read().unwrap();
+            let weights = weight_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            let data = [1f32, -2f32];
+            let y = weights.chunks(2).map(|row| row[0] * data[0] + row[1] * data[1]).collect::<Vec<_>>();
+            let log_sum = y.iter().map(|value| value.exp()).sum::<f32>().ln();
+            let expected = y.iter().map(|value| value - log_sum).collect::<Vec<_>>();
+
+            loaded_network.fill_input("data", &data).unwrap();
+            let output = loaded_network.forward(&[])[0].clone();
+            let output_lock = output.read().unwrap();
+            let actual = output_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            assert_eq!(expected.len(), actual.len());
+            for (e, a) in expected.iter().zip(actual.iter()) {
+                assert!((e - a).abs() < 1e-5);
+            }
+        }
+
+        #[test]
+        fn negative_log_likelihood_ignores_padding() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("log_probabilities", &vec![4, 2]);
+            net_cfg.add_label_input("label", &vec![2, 2]);
+            net_cfg.add_label_input("mask", &vec![2, 2]);
+            net_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(2) }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("loss", net_cfg));
+
+            let (label, mask) = pad_sequences(&[vec![1f32], vec![0f32, 1f32]], 1).unwrap();
+            let mut log_probabilities = SharedTensor::<f32>::new(native_backend().device(), &vec![4, 2]).unwrap();
+            write_to_memory(log_probabilities.get_mut(native_backend().device()).unwrap(), &[-0.1f32, -0.2, -0.3, -0.4, -0.5, -0.6, -0.7, -0.8]);
+            let mut inputs = HashMap::new();
+            inputs.insert("log_probabilities", Arc::new(RwLock::new(log_probabilities)));
+            inputs.insert("label", Arc::new(RwLock::new(label)));
+            inputs.insert("mask", Arc::new(RwLock::new(mask)));
+
+            let loss = network.forward_named(&inputs).unwrap()[0].clone();
+            {
+                let loss_lock = loss.read().unwrap();
+                let loss_native = loss_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                // averaged over the three unmasked labels
+                assert!((loss_native.as_slice::<f32>()[0] - 0.5f32).abs() < 1e-6);
+            }
+
+            let input_gradient = network.backward(&[loss.clone()])[0].clone();
+            let input_gradient_lock = input_gradient.read().unwrap();
+            let input_gradient_native = input_gradient_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(&[0f32, -1f32, 0f32, 0f32, -1f32, 0f32, 0f32, -1f32], input_gradient_native.as_slice::<f32>());
+        }
+
+        #[test]
+        fn fused_activations_compute_the_same() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2, 4]);
+            net_cfg.add_layer(LayerConfig::new("linear1", LinearConfig::new(3)));
+            net_cfg.add_layer(LayerConfig::new("relu", LayerType::ReLU));
+            net_cfg.add_layer(LayerConfig::new("linear2", LinearConfig::new(2)));
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            let mut fused_cfg = net_cfg.clone();
+            fused_cfg.fuse_activations();
+            assert_eq!(vec!["linear1", "linear2"], fused_cfg.layers.iter().map(|layer| layer.name.as_str()).collect::<Vec<_>>());
+
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            let mut fused_network = Layer::from_config(native_backend(), &LayerConfig::new("network", fused_cfg));
+            for (weight, fused_weight) in network.learnable_weights_data().iter().zip(fused_network.learnable_weights_data().iter()) {
+                let weight_lock = weight.read().unwrap();
+                let mut fused_weight_lock = fused_weight.write().unwrap();
+                let values = weight_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+                write_to_memory(fused_weight_lock.get_mut(native_backend().device()).unwrap(), values);
+            }
+
+            let data = [1f32, -2f32, 0.5f32, 3f32, -1f32, 0f32, 2f32, -0.5f32];
+            network.fill_input("data", &data).unwrap();
+            fused_network.fill_input("data", &data).unwrap();
+            let output = network.forward(&[])[0].clone();
+            let fused_output = fused_network.forward(&[])[0].clone();
+            let input_gradient = network.backward(&[output.clone()])[0].clone();
+            let fused_input_gradient = fused_network.backward(&[fused_output.clone()])[0].clone();
+
+            let expected = vec![output, input_gradient].into_iter().chain(network.learnable_weights_gradients());
+            let actual = vec![fused_output, fused_input_gradient].into_iter().chain(fused_network.learnable_weights_gradients());
+            for (expected_tensor, actual_tensor) in expected.zip(actual) {
+                let expected_lock = expected_tensor.read().unwrap();
+                let actual_lock = actual_tensor.read().unwrap();
+                let expected_values = expected_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+                let actual_values = actual_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+                for (e, a) in expected_values.iter().zip(actual_values.iter()) {
+                    assert!((e - a).abs() < 1e-6);
+                }
+            }
+        }
+
+        #[test]
+        fn parallel_linear_matches_single_threaded_linear() {
+            use leaf::util::{tensor_to_vec, write_tensor};
+
+            for &transpose in &[false, true] {
+                let linear_network = |num_threads: usize| {
+                    let mut net_cfg = SequentialConfig::default();
+                    net_cfg.add_input("data", &vec![5, 2, 2]);
+                    net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3).transpose(transpose).num_threads(num_threads)));
+                    Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg))
+                };
+                let mut network = linear_network(1);
+                let mut parallel_network = linear_network(3);
+                let weight = (0..12).map(|i| i as f32 * 0.25f32 - 1f32).collect::<Vec<f32>>();
+                write_tensor(&network.learnable_weights_data()[0], &weight).unwrap();
+                write_tensor(&parallel_network.learnable_weights_data()[0], &weight).unwrap();
+
+                let data = (0..20).map(|i| (i % 7) as f32 - 3f32).collect::<Vec<f32>>();
+                network.fill_input("data", &data).unwrap();
+                parallel_network.fill_input("data", &data).unwrap();
+                let output = network.forward(&[])[0].clone();
+                let parallel_output = parallel_network.forward(&[])[0].clone();
+                assert_eq!(tensor_to_vec(&output), tensor_to_vec(&parallel_output));
+                let input_gradient = network.backward(&[output.clone()])[0].clone();
+                let parallel_input_gradient = parallel_network.backward(&[parallel_output.clone()])[0].clone();
+                assert_eq!(tensor_to_vec(&input_gradient), tensor_to_vec(&parallel_input_gradient));
+                assert_eq!(tensor_to_vec(&network.learnable_weights_gradients()[0]),
+                           tensor_to_vec(&parallel_network.learnable_weights_gradients()[0]));
+            }
+        }
+
+        #[test]
+        fn linear_with_transposed_weight_matches_linear() {
+            use leaf::util::{tensor_to_vec, write_tensor};
+
+            let linear_network = |transpose: bool| {
+                let mut net_cfg = SequentialConfig::default();
+                net_cfg.add_input("data", &vec![2, 3]);
+                net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2).transpose(transpose)));
+                Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg))
+            };
+            let mut network = linear_network(false);
+            let mut transposed_network = linear_network(true);
+            assert_eq!(&vec![3, 2], transposed_network.learnable_weights_data()[0].read().unwrap().desc());
+            write_tensor(&network.learnable_weights_data()[0], &[1f32, 2f32, 3f32, -1f32, 0f32, 0.5f32]).unwrap();
+            write_tensor(&transposed_network.learnable_weights_data()[0], &[1f32, -1f32, 2f32, 0f32, 3f32, 0.5f32]).unwrap();
+
+            let data = [1f32, -2f32, 0.5f32, 3f32, -1f32, 0f32];
+            network.fill_input("data", &data).unwrap();
+            transposed_network.fill_input("data", &data).unwrap();
+            let output = network.forward(&[])[0].clone();
+            let transposed_output = transposed_network.forward(&[])[0].clone();
+            assert_eq!(tensor_to_vec(&output), tensor_to_vec(&transposed_output));
+            let input_gradient = network.backward(&[output.clone()])[0].clone();
+            let transposed_input_gradient = transposed_network.backward(&[transposed_output.clone()])[0].clone();
+            assert_eq!(tensor_to_vec(&input_gradient), tensor_to_vec(&transposed_input_gradient));
+
+            let weight_gradient = tensor_to_vec(&network.learnable_weights_gradients()[0]);
+            let transposed_weight_gradient = tensor_to_vec(&transposed_network.learnable_weights_gradients()[0]);
+            for row in 0..2 {
+                for column in 0..3 {
+                    assert_eq!(weight_gradient[row * 3 + column], transposed_weight_gradient[column * 2 + row]);
+                }
+            }
+        }
+
+        #[test]
+        fn optimize_keeps_outputs_and_weights() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            let mut linear_cfg = LayerConfig::new("linear1", LinearConfig::new(3));
+            linear_cfg.add_output("hidden");
+            net_cfg.add_layer(linear_cfg);
+            let mut reshape_cfg = LayerConfig::new("reshape", ReshapeConfig::of_shape(&[1, 3]));
+            reshape_cfg.add_input("hidden");
+            reshape_cfg.add_output("reshaped");
+            net_cfg.add_layer(reshape_cfg);
+            let mut head_cfg = LayerConfig::new("linear2", LinearConfig::new(2));
+            head_cfg.add_input("reshaped");
+            net_cfg.add_layer(head_cfg);
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            net_cfg.add_layer(LayerConfig::new("unused", LinearConfig::new(1)));
+            net_cfg.add_output("sigmoid");
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            let read_weights = |network: &Layer<Backend<Native>>| network.learnable_weights_data().iter().map(|weight| {
+                let weight_lock = weight.read().unwrap();
+                weight_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned()
+            }).collect::<Vec<_>>();
+            let weights = read_weights(&network);
+
+            network.optimize(OptimizationLevel::Full);
+            let layers = match network.config.layer_type {
+                LayerType::Sequential(ref container_config) => container_config.layers.clone(),
+                _ => panic!("network is not a container"),
+            };
+            assert_eq!(vec!["linear1", "reshape", "linear2"], layers.iter().map(|layer| layer.name.as_str()).collect::<Vec<_>>());
+            assert_eq!(vec!["hidden".to_owned()], layers[1].outputs);
+            assert_eq!(vec!["linear1-0".to_owned(), "linear2-0".to_owned()], network.learnable_weights_names());
+            assert_eq!(&weights[..2], &read_weights(&network)[..]);
+
+            let data = [1f32, -2f32, 0.5f32, 3f32];
+            let hidden = weights[0].chunks(4).map(|row| row.iter().zip(data.iter()).map(|(w, x)| w * x).sum::<f32>()).collect::<Vec<_>>();
+            let expected = weights[1].chunks(3).map(|row| row.iter().zip(hidden.iter()).map(|(w, h)| w * h).sum::<f32>())
+                .map(|value| 1f32 / (1f32 + (-value).exp())).collect::<Vec<_>>();
+            network.fill_input("data", &data).unwrap();
+            let output = network.forward(&[])[0].clone();
+            let output_lock = output.read().unwrap();
+            let actual = output_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            assert_eq!(expected.len(), actual.len());
+            for (e, a) in expected.iter().zip(actual.iter()) {
+                assert!((e - a).abs() < 1e-5);
+            }
+        }
+
+        #[test]
+        fn duplicate_output_names_are_rejected() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            let mut first_cfg = LayerConfig::new("first", LinearConfig::new(3));
+            first_cfg.add_output("hidden");
+            let mut second_cfg = LayerConfig::new("second", LayerType::Sigmoid);
+            second_cfg.add_input("hidden");
+            second_cfg.add_output("activation");
+            let mut third_cfg = LayerConfig::new("third", LayerType::Sigmoid);
+            third_cfg.add_input("activation");
+            third_cfg.add_output("hidden");
+            net_cfg.add_layer(first_cfg);
+            net_cfg.add_layer(second_cfg);
+            net_cfg.add_layer(third_cfg);
+
+            let error = Sequential::empty().init_layers(native_backend(), &net_cfg).unwrap_err();
+            assert_eq!("Layer third can't output blob hidden, because it is already produced by layer first. \
+                        To compute third in-place, name its output activation like its input, otherwise choose a unique output name.", error);
+
+            let mut input_cfg = SequentialConfig::default();
+            input_cfg.add_input("data", &vec![3]);
+            input_cfg.add_label_input("data", &vec![1]);
+            assert!(Sequential::empty().init_layers(native_backend(), &input_cfg).is_err());
+        }
+
+        #[test]
+        fn sequential_accepts_layer_instances() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![3]);
+            let mut sequential = Sequential::from_config(native_backend(), &net_cfg);
+            let custom_layer = Layer::from_worker(native_backend(), &LayerConfig::new("custom", LayerType::Sigmoid), Box::new(Sigmoid));
+            sequential.add_layer_instance(custom_layer).unwrap();
+            let mut network = Layer::from_worker(native_backend(), &LayerConfig::new("network", net_cfg), Box::new(sequential));
+
+            network.fill_input("data", &[1f32, 1f32, 2f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            let output_lock = output.read().unwrap();
+            let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(&[0.7310585786f32, 0.7310586f32, 0.880797f32], output_native.as_slice::<f32>());
+        }
+
+        #[test]
+        fn sequential_layer_instances_share_named_weights() {
+            use leaf::weight::WeightConfig;
+
+            let shared_weight = || WeightConfig { name: "shared".to_owned(), ..WeightConfig::default() };
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 2]);
+            let mut first_cfg = LayerConfig::new("first", LinearConfig::new(2));
+            first_cfg.params.push(shared_weight());
+            net_cfg.add_layer(first_cfg);
+            let mut sequential = Sequential::from_config(native_backend(), &net_cfg);
+            let mut second_cfg = LayerConfig::new("second", LinearConfig::new(2));
+            second_cfg.params.push(shared_weight());
+            let second = Layer::from_worker(native_backend(), &second_cfg, Box::new(Linear::from_config(&LinearConfig::new(2))));
+            sequential.add_layer_instance(second).unwrap();
+            let mut network = Layer::from_worker(native_backend(), &LayerConfig::new("network", net_cfg), Box::new(sequential));
+            assert_eq!(vec!["shared".to_owned()], network.learnable_weights_names());
+
+            write_tensor(&network.learnable_weights_data()[0], &[1f32, 2f32, 0f32, 1f32]).unwrap();
+            network.fill_input("data", &[1f32, 1f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            assert_eq!(vec![5f32, 1f32], tensor_to_vec(&output));
+        }
+
+        #[test]
+        fn layers_from_workers_are_not_saved() {
+            let mut custom_layer = Layer::from_worker(native_backend(), &LayerConfig::new("custom", LayerType::ReLU), Box::new(Sigmoid));
+            let path = temp_path("testcustomlayer");
+            let error = custom_layer.save(&path).unwrap_err();
+            assert_eq!(::std::io::ErrorKind::InvalidInput, error.kind());
+            assert!(custom_layer.save_for_inference(&path).is_err());
+
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 3]);
+            let sequential = Sequential::from_config(native_backend(), &net_cfg);
+            let network = Layer::from_worker(native_backend(), &LayerConfig::new("network", net_cfg), Box::new(sequential));
+            assert!(network.with_batch_size(2).is_err());
+        }
+
+        #[test]
+        fn lambda_layer_applies_closures() {
+            let double = Lambda::new(|input, output| {
+                for (o, i) in output.iter_mut().zip(input) { *o = 2f32 * i; }
+            }).with_gradient(|_, _, output_gradient, input_gradient| {
+                for (ig, og) in input_gradient.iter_mut().zip(output_gradient) { *ig = 2f32 * og; }
+            });
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![3]);
+            let mut sequential = Sequential::from_config(native_backend(), &net_cfg);
+            sequential.add_layer_instance(Layer::from_worker(native_backend(), &LayerConfig::new("double", LayerType::Sigmoid), Box::new(double))).unwrap();
+            let mut network = Layer::from_worker(native_backend(), &LayerConfig::new("network", net_cfg), Box::new(sequential));
+
+            network.fill_input("data", &[1f32, 1f32, 2f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            {
+                let output_lock = output.read().unwrap();
+                let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                assert_eq!(&[2f32, 2f32, 4f32], output_native.as_slice::<f32>());
+            }
+
+            let input_gradient = network.backward(&[output.clone()])[0].clone();
+            let input_gradient_lock = input_gradient.read().unwrap();
+            let input_gradient_native = input_gradient_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(&[4f32, 4f32, 8f32], input_gradient_native.as_slice::<f32>());
+        }
+
+        #[test]
+        fn constant_layer_ignores_input() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![3]);
+            net_cfg.add_layer(LayerConfig::new("constant", ConstantConfig { shape: vec![2], value: 0.5f32, learnable: true }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            assert_eq!(1, network.learnable_weights_data().len());
+
+            network.fill_input("data", &[1f32, 1f32, 2f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            let output_lock = output.read().unwrap();
+            let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(&[0.5f32, 0.5f32], output_native.as_slice::<f32>());
+        }
+
+        #[test]
+        fn bernoulli_noise_is_reproducible_and_scales_gradient() {
+            let noisy_network = || {
+                let mut net_cfg = SequentialConfig::default();
+                net_cfg.add_input("data", &vec![100]);
+                net_cfg.add_layer(LayerConfig::new("noise", NoiseConfig { mode: NoiseMode::Bernoulli, level: 0.5f32 }));
+                Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg))
+            };
+            let forward_ones = |network: &mut Layer<Backend<Native>>| {
+                network.fill_input("data", &vec![1f32; 100]).unwrap();
+                let output = network.forward(&[])[0].clone();
+                let output_lock = output.read().unwrap();
+                output_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned()
+            };
+            let mut network = noisy_network();
+            let output = forward_ones(&mut network);
+            assert!(output.iter().all(|&value| value == 0f32 || value == 2f32));
+            assert!(output.contains(&0f32) && output.contains(&2f32));
+            assert_eq!(output, forward_ones(&mut noisy_network()));
+
+            let output = network.forward(&[])[0].clone();
+            let input_gradient = network.backward(&[output])[0].clone();
+            let input_gradient_lock = input_gradient.read().unwrap();
+            let input_gradient_native = input_gradient_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert!(input_gradient_native.as_slice::<f32>().iter().all(|&value| value == 0f32 || value == 4f32));
+        }
+
+        #[test]
+        fn noise_passes_input_through_outside_of_training() {
+            for &mode in &[NoiseMode::Gaussian, NoiseMode::Bernoulli] {
+                let mut net_cfg = SequentialConfig::default();
+                net_cfg.add_input("data", &vec![100]);
+                net_cfg.add_layer(LayerConfig::new("noise", NoiseConfig { mode: mode, level: 0.5f32 }));
+                let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+                network.fill_input("data", &vec![1f32; 100]).unwrap();
+
+                network.set_training(false);
+                let output = network.forward(&[])[0].clone();
+                assert_eq!(vec![1f32; 100], tensor_to_vec(&output));
+                let input_gradient = network.backward(&[output])[0].clone();
+                assert_eq!(vec![1f32; 100], tensor_to_vec(&input_gradient));
+
+                network.set_training(true);
+                let output = network.forward(&[])[0].clone();
+                assert!(tensor_to_vec(&output) != vec![1f32; 100]);
+            }
+        }
+
+        #[test]
+        fn maxout_routes_gradient_to_max_piece() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2, 4, 1]);
+            net_cfg.add_layer(LayerConfig::new("maxout", MaxoutConfig { num_pieces: 2 }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            network.fill_input("data", &[1f32, 3f32, -2f32, -5f32, 4f32, 0f32, 2f32, 6f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            {
+                let output_lock = output.read().unwrap();
+                assert_eq!(&vec![2, 2, 1], output_lock.desc());
+                let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                assert_eq!(&[3f32, -2f32, 4f32, 6f32], output_native.as_slice::<f32>());
+            }
+
+            let input_gradient = network.backward(&[output])[0].clone();
+            let input_gradient_lock = input_gradient.read().unwrap();
+            let input_gradient_native = input_gradient_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(&[0f32, 3f32, -2f32, 0f32, 4f32, 0f32, 0f32, 6f32], input_gradient_native.as_slice::<f32>());
+        }
+
+        #[test]
+        fn prelu_learns_slope_per_channel() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2, 2]);
+            net_cfg.add_layer(LayerConfig::new("prelu", PReLUConfig { initial_slope: 0.5f32 }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            assert_eq!(1, network.learnable_weights_data().len());
+
+            network.fill_input("data", &[1f32, -2f32, -4f32, 3f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            {
+                let output_lock = output.read().unwrap();
+                let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                assert_eq!(&[1f32, -1f32, -2f32, 3f32], output_native.as_slice::<f32>());
+            }
+
+            let input_gradient = network.backward(&[output])[0].clone();
+            {
+                let input_gradient_lock = input_gradient.read().unwrap();
+                let input_gradient_native = input_gradient_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                assert_eq!(&[1f32, -0.5f32, -1f32, 3f32], input_gradient_native.as_slice::<f32>());
+            }
+            let slope_gradient = network.learnable_weights_gradients()[0].clone();
+            let slope_gradient_lock = slope_gradient.read().unwrap();
+            let slope_gradient_native = slope_gradient_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(&[8f32, 2f32], slope_gradient_native.as_slice::<f32>());
+        }
+
+        #[test]
+        fn scale_layer_learns_scale_and_bias_per_channel() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2, 2]);
+            net_cfg.add_layer(LayerConfig::new("scale", ScaleConfig { bias: true }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            let weights = network.learnable_weights_data();
+            assert_eq!(2, weights.len());
+            for (weight, values) in weights.iter().zip(&[[2f32, 3f32], [1f32, -1f32]]) {
+                assert_eq!(&vec![2], weight.read().unwrap().desc());
+                write_tensor(weight, values).unwrap();
+            }
+
+            network.fill_input("data", &[1f32, 2f32, 3f32, 4f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            {
+                let output_lock = output.read().unwrap();
+                let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                assert_eq!(&[3f32, 5f32, 7f32, 11f32], output_native.as_slice::<f32>());
+            }
+
+            let input_gradient = network.backward(&[output])[0].clone();
+            {
+                let input_gradient_lock = input_gradient.read().unwrap();
+                let input_gradient_native = input_gradient_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                assert_eq!(&[6f32, 15f32, 14f32, 33f32], input_gradient_native.as_slice::<f32>());
+            }
+            let weight_gradients = network.learnable_weights_gradients();
+            assert_eq!(vec![24f32, 54f32], tensor_to_vec(&weight_gradients[0]));
+            assert_eq!(vec![10f32, 16f32], tensor_to_vec(&weight_gradients[1]));
+        }
+
+        #[test]
+        fn top_k_of_softmax_output() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2, 3]);
+            net_cfg.add_layer(LayerConfig::new("softmax", SoftmaxConfig::default()));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            network.fill_input("data", &[1f32, 3f32, 2f32, 0f32, 0f32, 5f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            let top = ::leaf::util::top_k(&mut output.write().unwrap(), 2);
+            assert_eq!(vec![1, 2], top[0].iter().map(|&(class, _)| class).collect::<Vec<_>>());
+            assert_eq!(vec![2, 0], top[1].iter().map(|&(class, _)| class).collect::<Vec<_>>());
+            assert!(top[0][0].1 > top[0][1].1);
+
+            let mut empty_batch = SharedTensor::<f32>::new(native_backend().device(), &vec![0, 3]).unwrap();
+            assert!(::leaf::util::top_k(&mut empty_batch, 2).is_empty());
+        }
+
+        #[test]
+        fn softmax_temperature_scales_input() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2]);
+            net_cfg.add_layer(LayerConfig::new("softmax", SoftmaxConfig { temperature: 2f32 }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            network.fill_input("data", &[0f32, 2f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            let output_lock = output.read().unwrap();
+            let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            let expected = 1f32 / (1f32 + 1f32.exp());
+            assert!((output_native.as_slice::<f32>()[0] - expected).abs() < 1e-6);
+        }
+
+        #[test]
+        fn negative_log_likelihood_of_batch() {
+            let mut loss_cfg = SequentialConfig::default();
+            loss_cfg.add_input("log_probabilities", &vec![2, 2]);
+            loss_cfg.add_input("label", &vec![2]);
+            loss_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(2) }));
+            let mut loss_layer = Layer::from_config(native_backend(), &LayerConfig::new("loss", loss_cfg));
+
+            loss_layer.fill_input("log_probabilities", &[-0.5f32, -1f32, -2f32, -0.25f32]).unwrap();
+            loss_layer.fill_input("label", &[0f32, 1f32]).unwrap();
+            let loss = loss_layer.forward(&[])[0].clone();
+            let loss_lock = loss.read().unwrap();
+            let loss_native = loss_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(0.75f32, loss_native.as_slice::<f32>()[0]);
+        }
+
+        #[test]
+        fn negative_log_likelihood_infers_number_of_classes() {
+            let mut loss_cfg = SequentialConfig::default();
+            loss_cfg.add_input("log_probabilities", &vec![2, 3]);
+            loss_cfg.add_input("label", &vec![2, 1]);
+            loss_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig::default()));
+            let mut loss_layer = Layer::from_config(native_backend(), &LayerConfig::new("loss", loss_cfg));
+
+            loss_layer.fill_input("log_probabilities", &[-0.5f32, -1f32, -2f32, -3f32, -0.25f32, -1f32]).unwrap();
+            loss_layer.fill_input("label", &[2f32, 1f32]).unwrap();
+            let loss = loss_layer.forward(&[])[0].clone();
+            let loss_lock = loss.read().unwrap();
+            let loss_native = loss_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(1.125f32, loss_native.as_slice::<f32>()[0]);
+        }
+
+        #[test]
+        #[should_panic(expected = "configured for 2 classes")]
+        fn negative_log_likelihood_rejects_mismatching_classes() {
+            let mut loss_cfg = SequentialConfig::default();
+            loss_cfg.add_input("log_probabilities", &vec![2, 3]);
+            loss_cfg.add_input("label", &vec![2]);
+            loss_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(2) }));
+            Layer::from_config(native_backend(), &LayerConfig::new("loss", loss_cfg));
+        }
+
+        #[test]
+        fn loss_layer_with_missing_label_is_rejected() {
+            let mut loss_cfg = SequentialConfig::default();
+            loss_cfg.add_input("log_probabilities", &vec![2, 2]);
+            loss_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(2) }));
+
+            let error = Sequential::empty().init_layers(native_backend(), &loss_cfg).unwrap_err();
+            assert_eq!("Layer nll requires at least 2 input blobs, but is connected to 1: [\"log_probabilities\"]", error);
+        }
+
+        #[test]
+        fn accuracy_of_top_k_predictions() {
+            let accuracy = |top_k: usize| {
+                let mut net_cfg = SequentialConfig::default();
+                net_cfg.add_input("probabilities", &vec![3, 3]);
+                net_cfg.add_label_input("label", &vec![3]);
+                net_cfg.add_layer(LayerConfig::new("accuracy", AccuracyConfig { top_k: top_k }));
+                let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+                network.fill_input("probabilities", &[0.7f32, 0.2f32, 0.1f32,
+                                                      0.3f32, 0.6f32, 0.1f32,
+                                                      0.2f32, 0.3f32, 0.5f32]).unwrap();
+                network.fill_input("label", &[0f32, 0f32, 1f32]).unwrap();
+                let output = network.forward(&[])[0].clone();
+                let output_lock = output.read().unwrap();
+                let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                output_native.as_slice::<f32>()[0]
+            };
+
+            assert_eq!(1f32 / 3f32, accuracy(1));
+            assert_eq!(1f32, accuracy(2));
+        }
+
+        #[test]
+        fn kl_divergence_of_target_distribution() {
+            let mut loss_cfg = SequentialConfig::default();
+            loss_cfg.add_input("log_probabilities", &vec![1, 2]);
+            loss_cfg.add_input("target", &vec![1, 2]);
+            loss_cfg.add_layer(LayerConfig::new("kl_divergence", LayerType::KLDivergence));
+            let mut loss_layer = Layer::from_config(native_backend(), &LayerConfig::new("loss", loss_cfg));
+
+            loss_layer.fill_input("log_probabilities", &[0.5f32.ln(), 0.5f32.ln()]).unwrap();
+            loss_layer.fill_input("target", &[0.25f32, 0.75f32]).unwrap();
+            let loss = loss_layer.forward(&[])[0].clone();
+            {
+                let loss_lock = loss.read().unwrap();
+                let loss_native = loss_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                let expected = 0.25f32 * (0.25f32 / 0.5f32).ln() + 0.75f32 * (0.75f32 / 0.5f32).ln();
+                assert!((loss_native.as_slice::<f32>()[0] - expected).abs() < 1e-6);
+            }
+
+            let gradient = loss_layer.backward(&[])[0].clone();
+            let gradient_lock = gradient.read().unwrap();
+            let gradient_native = gradient_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            assert_eq!(&[-0.25f32, -0.75f32], gradient_native.as_slice::<f32>());
+        }
+
+        #[test]
+        fn layer_norm_normalizes_each_sample() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2, 2]);
+            net_cfg.add_layer(LayerConfig::new("layer_norm", LayerNormConfig { epsilon: 0f32 }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            assert_eq!(1, network.learnable_weights_data().len());
+
+            network.fill_input("data", &[1f32, 3f32, -2f32, 8f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            {
+                let output_lock = output.read().unwrap();
+                let output_native = output_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+                assert_eq!(&[-1f32, 1f32, -1f32, 1f32], output_native.as_slice::<f32>());
+            }
+
+            // a gradient that is the same for all features of a sample only shifts the mean
+            let input_gradient = network.backward(&[output.clone()])[0].clone();
+            let input_gradient_lock = input_gradient.read().unwrap();
+            let input_gradient_native = input_gradient_lock.get(native_backend().device()).unwrap().as_native().unwrap();
+            let sum = input_gradient_native.as_slice::<f32>().iter().fold(0f32, |sum, g| sum + g);
+            assert!(sum.abs() < 1e-6);
+        }
+
+        #[test]
+        fn log_softmax_normalizes_each_sample() {
+            let data = [1f32, 2f32, 3f32, -1f32, 0.5f32, 0.5f32];
+            let output_gradient = [-1f32, 0f32, 0.5f32, 0f32, 2f32, 0f32];
+            let temperature = 2f32;
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2, 3]);
+            net_cfg.add_layer(LayerConfig::new("log_softmax", LogSoftmaxConfig { temperature: temperature }));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            let forward = |network: &mut Layer<Backend<Native>>, input: &[f32]| {
+                network.fill_input("data", input).unwrap();
+                tensor_to_vec(&network.forward(&[])[0])
+            };
+            let output = forward(&mut network, &data);
+            for (input_sample, output_sample) in data.chunks(3).zip(output.chunks(3)) {
+                let logsum = input_sample.iter().fold(0f32, |sum, x| sum + (x / temperature).exp()).ln();
+                for (x, y) in input_sample.iter().zip(output_sample) {
+                    assert!((x / temperature - logsum - y).abs() < 1e-5);
+                }
+            }
+
+            let gradient = Arc::new(RwLock::new(tensor_from_slice(&*native_backend(), &[2, 3], &output_gradient).unwrap()));
+            let input_gradient = tensor_to_vec(&network.backward(&[gradient])[0]);
+            let step = 1e-2f32;
+            for i in 0..data.len() {
+                let (mut plus, mut minus) = (data.to_vec(), data.to_vec());
+                plus[i] += step;
+                minus[i] -= step;
+                let loss = |output: Vec<f32>| output.iter().zip(&output_gradient).fold(0f32, |sum, (y, dy)| sum + y * dy);
+                let numerical = (loss(forward(&mut network, &plus)) - loss(forward(&mut network, &minus))) / (2f32 * step);
+                assert!((numerical - input_gradient[i]).abs() < 1e-2, "input gradient {} is {}, expected {}", i, input_gradient[i], numerical);
+            }
+        }
+
+        #[test]
+        fn group_norm_with_one_group_per_channel_is_instance_norm() {
+            let normalize = |layer_type: LayerType| {
+                let mut net_cfg = SequentialConfig::default();
+                net_cfg.add_input("data", &vec![1, 2, 2]);
+                net_cfg.add_layer(LayerConfig::new("norm", layer_type));
+                let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+                network.fill_input("data", &[1f32, 3f32, -2f32, 8f32]).unwrap();
+                let output = network.forward(&[])[0].clone();
+                let output_lock = output.read().unwrap();
+                output_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned()
+            };
+
+            let instance_norm = normalize(InstanceNormConfig { epsilon: 0f32 }.into());
+            assert_eq!(vec![-1f32, 1f32, -1f32, 1f32], instance_norm);
+            assert_eq!(instance_norm, normalize(GroupNormConfig { num_groups: 2, epsilon: 0f32 }.into()));
+            assert!(instance_norm != normalize(GroupNormConfig { num_groups: 1, epsilon: 0f32 }.into()));
+        }
+
+        #[test]
+        fn normalization_gradients_match_finite_differences() {
+            let input = [0.3f32, -1.2f32, 2.0f32, 0.7f32, -0.4f32, 1.5f32, 0.1f32, -2.2f32, 0.9f32, 1.1f32, -0.6f32, 0.4f32,
+                         -1.0f32, 0.2f32, 0.8f32, 1.9f32, -0.3f32, 0.5f32, 2.4f32, -1.7f32, 0.6f32, -0.2f32, 1.3f32, 0.0f32];
+            let output_gradient = (0..24).map(|i| ((i * 7) % 5) as f32 - 2f32).collect::<Vec<f32>>();
+            let weight = [1.5f32, 0.5f32, -1f32, 2f32, 0.1f32, -0.2f32, 0.3f32, 0.4f32];
+
+            for layer_type in vec![LayerType::LayerNorm(LayerNormConfig { epsilon: 1e-5 }),
+                                   GroupNormConfig { num_groups: 2, epsilon: 1e-5 }.into(),
+                                   InstanceNormConfig { epsilon: 1e-5 }.into()] {
+                let mut net_cfg = SequentialConfig::default();
+                // LayerNorm normalizes the 12 features of each sample, the others use the 4 channels
+                net_cfg.add_input("data", &vec![2, 4, 3]);
+                net_cfg.add_layer(LayerConfig::new("norm", layer_type.clone()));
+                let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+                let weights = network.learnable_weights_data()[0].clone();
+                let num_weights = weights.read().unwrap().desc().size();
+                let initial_weight = weight.iter().cycle().take(num_weights).cloned().collect::<Vec<f32>>();
+                write_tensor(&weights, &initial_weight).unwrap();
+
+                // the loss is the dot product of the output and the output gradient
+                let loss = |network: &mut Layer<Backend<Native>>, input: &[f32]| {
+                    network.fill_input("data", input).unwrap();
+                    let output = network.forward(&[])[0].clone();
+                    tensor_to_vec(&output).iter().zip(&output_gradient).fold(0f32, |sum, (&y, &dy)| sum + y * dy)
+                };
+                loss(&mut network, &input);
+                let gradient = Arc::new(RwLock::new(tensor_from_slice(&*native_backend(), &[2, 4, 3], &output_gradient).unwrap()));
+                let input_gradient = tensor_to_vec(&network.backward(&[gradient])[0]);
+                let weight_gradient = tensor_to_vec(&network.learnable_weights_gradients()[0]);
+
+                let step = 1e-2f32;
+                for i in 0..input.len() {
+                    let (mut plus, mut minus) = (input.to_vec(), input.to_vec());
+                    plus[i] += step;
+                    minus[i] -= step;
+                    let numerical = (loss(&mut network, &plus) - loss(&mut network, &minus)) / (2f32 * step);
+                    assert!((numerical - input_gradient[i]).abs() < 2e-2,
+                            "{:?}: input gradient {} is {}, expected {}", layer_type, i, input_gradient[i], numerical);
+                }
+                for i in 0..num_weights {
+                    let (mut plus, mut minus) = (initial_weight.clone(), initial_weight.clone());
+                    plus[i] += step;
+                    minus[i] -= step;
+                    write_tensor(&weights, &plus).unwrap();
+                    let loss_plus = loss(&mut network, &input);
+                    write_tensor(&weights, &minus).unwrap();
+                    let numerical = (loss_plus - loss(&mut network, &input)) / (2f32 * step);
+                    assert!((numerical - weight_gradient[i]).abs() < 2e-2,
+                            "{:?}: weight gradient {} is {}, expected {}", layer_type, i, weight_gradient[i], numerical);
+                }
+            }
+        }
+
+        #[test]
+        fn replace_layer_keeps_other_weights() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3)));
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            net_cfg.add_layer(LayerConfig::new("head", LinearConfig::new(2)));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            let weight_before = {
+                let weight = network.learnable_weights_data()[0].clone();
+                let weight_lock = weight.read().unwrap();
+                weight_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned()
+            };
+
+            assert!(network.replace_layer("tail", LayerConfig::new("tail", LinearConfig::new(5))).is_err());
+            network.replace_layer("head", LayerConfig::new("new_head", LinearConfig::new(5))).unwrap();
+
+            network.fill_input("data", &[1f32, 1f32, 2f32, 2f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            assert_eq!(&vec![1, 5], output.read().unwrap().desc());
+
+            let weight = network.learnable_weights_data()[0].clone();
+            let weight_lock = weight.read().unwrap();
+            assert_eq!(&weight_before[..], weight_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>());
+            assert_eq!(vec!["linear-0".to_owned(), "new_head-0".to_owned()], network.learnable_weights_names());
+        }
+
+        #[test]
+        fn replace_layer_shares_named_weights() {
+            use leaf::weight::WeightConfig;
+
+            let shared_linear = |name: &str| {
+                let mut linear_cfg = LayerConfig::new(name, LinearConfig::new(2));
+                linear_cfg.params.push(WeightConfig { name: "shared".to_owned(), ..WeightConfig::default() });
+                linear_cfg
+            };
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 2]);
+            net_cfg.add_layer(shared_linear("first"));
+            net_cfg.add_layer(shared_linear("second"));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            assert!(network.replace_layer("first", LayerConfig::new("sigmoid", LayerType::Sigmoid)).is_err());
+            network.replace_layer("second", shared_linear("new_second")).unwrap();
+            assert_eq!(vec!["shared".to_owned()], network.learnable_weights_names());
+            write_tensor(&network.learnable_weights_data()[0], &[1f32, 2f32, 0f32, 1f32]).unwrap();
+            network.fill_input("data", &[1f32, 1f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            assert_eq!(vec![5f32, 1f32], tensor_to_vec(&output));
+
+            // without a layer that shares its weight the owner can be replaced
+            network.replace_layer("new_second", LayerConfig::new("sigmoid", LayerType::Sigmoid)).unwrap();
+            network.replace_layer("first", shared_linear("new_first")).unwrap();
+            assert_eq!(vec!["shared".to_owned()], network.learnable_weights_names());
+        }
+
+        #[test]
+        fn replace_layer_keeps_weights_of_following_layers() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3)));
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            net_cfg.add_layer(LayerConfig::new("head", LinearConfig::new(2)));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            let weights_before = network.learnable_weights_data().iter()
+                .map(|weight| weight.read().unwrap().get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned())
+                .collect::<Vec<_>>();
+
+            network.replace_layer("sigmoid", LayerConfig::new("relu", LayerType::ReLU)).unwrap();
+            network.replace_layer("linear", LayerConfig::new("new_linear", LinearConfig::new(3))).unwrap();
+
+            let head_weight = network.learnable_weights_data()[1].clone();
+            let head_lock = head_weight.read().unwrap();
+            assert_eq!(&weights_before[1][..], head_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>());
+            assert_eq!(vec!["new_linear-0".to_owned(), "head-0".to_owned()], network.learnable_weights_names());
+        }
+
+        #[test]
+        fn replace_layer_keeps_weights_of_the_same_shape() {
+            use leaf::layers::ReshapeConfig;
+
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![2, 4]);
+            net_cfg.add_layer(LayerConfig::new("reshape", ReshapeConfig::of_shape(&[2, 4])));
+            net_cfg.add_layer(LayerConfig::new("head", LinearConfig::new(3)));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            let weight_before = {
+                let weight = network.learnable_weights_data()[0].clone();
+                let weight_lock = weight.read().unwrap();
+                weight_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned()
+            };
+
+            // the input of the head changes its shape, but not its size per sample
+            network.replace_layer("reshape", LayerConfig::new("reshape", ReshapeConfig::of_shape(&[2, 2, 2]))).unwrap();
+
+            let weight = network.learnable_weights_data()[0].clone();
+            let weight_lock = weight.read().unwrap();
+            assert_eq!(&vec![3, 4], weight_lock.desc());
+            assert_eq!(&weight_before[..], weight_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>());
+        }
+
+        #[test]
+        fn dependency_graph_follows_connections() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3)));
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            let network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            let order = network.execution_order().iter().map(|node| node.name.clone()).collect::<Vec<_>>();
+            assert_eq!(vec!["linear".to_owned(), "sigmoid".to_owned()], order);
+            assert_eq!(vec![("linear".to_owned(), vec!["sigmoid".to_owned()]), ("sigmoid".to_owned(), vec![])],
+                       network.dependency_graph());
+        }
+
+        #[test]
+        fn build_report_lists_connections_blobs_and_weights() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3)));
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            let (_, report) = Layer::from_config_with_report(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            assert_eq!(vec![("data".to_owned(), vec![1, 4])], report.inputs);
+            let connections = report.connections.iter().map(|node| (node.name.clone(), node.inputs.clone(), node.outputs.clone())).collect::<Vec<_>>();
+            assert_eq!(vec![("linear".to_owned(), vec!["data".to_owned()], vec!["SEQUENTIAL_0".to_owned()]),
+                            ("sigmoid".to_owned(), vec!["SEQUENTIAL_0".to_owned()], vec!["SEQUENTIAL_OUTPUT_1".to_owned()])],
+                       connections);
+            assert_eq!(vec![("SEQUENTIAL_0".to_owned(), vec![1, 3]), ("SEQUENTIAL_OUTPUT_1".to_owned(), vec![1, 3])], report.blobs);
+            assert_eq!(vec![("linear-0".to_owned(), vec![3, 4])], report.weights);
+        }
+
+        #[test]
+        fn silence_layer_is_not_chained() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3)));
+            let mut silence_cfg = LayerConfig::new("silence", LayerType::Silence);
+            silence_cfg.add_input("data");
+            net_cfg.add_layer(silence_cfg);
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            let connections = network.execution_order().iter().map(|node| (node.name.clone(), node.inputs.clone(), node.outputs.clone())).collect::<Vec<_>>();
+            assert_eq!(vec![("linear".to_owned(), vec!["data".to_owned()], vec!["SEQUENTIAL_0".to_owned()]),
+                            ("silence".to_owned(), vec!["data".to_owned()], vec![]),
+                            ("sigmoid".to_owned(), vec!["SEQUENTIAL_0".to_owned()], vec!["SEQUENTIAL_OUTPUT_2".to_owned()])],
+                       connections);
+            let outputs = network.forward(&[]);
+            assert_eq!(1, outputs.len());
+            assert_eq!(&vec![1, 3], outputs[0].read().unwrap().desc());
+        }
+
+        #[test]
+        fn dot_export_contains_layers_and_blobs() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3)));
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            let network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            let dot = network.to_dot();
+            assert!(dot.starts_with("digraph \"network\" {"));
+            assert!(dot.contains("\"input:data\" -> \"linear\" [label=\"data [1, 4]\"];"));
+            assert!(dot.contains("\"linear\" [shape=box, label=\"linear\\nparameters: 12\"];"));
+            assert!(dot.contains("\"linear\" -> \"sigmoid\" [label=\"SEQUENTIAL_0 [1, 3]\"];"));
+        }
+
+        #[test]
+        fn train_from_freezes_previous_layers() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 4]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(3)));
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            net_cfg.add_layer(LayerConfig::new("head", LinearConfig::new(2)));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            assert!(network.train_from("tail").is_err());
+            network.train_from("head").unwrap();
+            assert_eq!(vec![Some(0f32), Some(1f32)], network.learnable_weights_lr());
+        }
+
+        #[test]
+        fn sequential_returns_declared_outputs_in_order() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![3]);
+            net_cfg.add_layer(LayerConfig::new("reshape", ReshapeConfig::of_shape(&[1, 3])));
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            net_cfg.add_output("sigmoid");
+            net_cfg.add_output("linear");
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            network.fill_input("data", &[1f32, 1f32, 2f32]).unwrap();
+            let outputs = network.forward(&[]);
+            assert_eq!(2, outputs.len());
+
+            let sigmoid_lock = outputs[0].read().unwrap();
+            let linear_lock = outputs[1].read().unwrap();
+            let sigmoid = sigmoid_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            let linear = linear_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>();
+            for (s, l) in sigmoid.iter().zip(linear.iter()) {
+                assert!((s - 1f32 / (1f32 + (-l).exp())).abs() < 1e-6);
+            }
+        }
+
+        #[test]
+        fn reshape_with_batch_keeps_batch_size() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &[2, 6]);
+            assert_eq!(vec![0, 3, 2], ReshapeConfig::of_shape_with_batch(&[3, 2]).shape);
+            net_cfg.add_layer(LayerConfig::new("reshape", ReshapeConfig::of_shape_with_batch(&[3, 2])));
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(4)));
+            let network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg.clone()));
+            assert_eq!(&vec![2, 4], network.output_blobs_data[0].read().unwrap().desc());
+
+            let mut larger_network = network.with_batch_size(5).unwrap();
+            let data = (0..30).map(|value| value as f32).collect::<Vec<_>>();
+            larger_network.fill_input("data", &data).unwrap();
+            let output = larger_network.forward(&[])[0].clone();
+            assert_eq!(&vec![5, 4], output.read().unwrap().desc());
+
+            let mut fixed_cfg = net_cfg.clone();
+            fixed_cfg.layers[0] = LayerConfig::new("reshape", ReshapeConfig::of_shape(&[2, 3, 2]));
+            assert!(fixed_cfg.set_batch_size(5).is_err());
+            net_cfg.set_batch_size(5).unwrap();
+        }
+
+        #[test]
+        fn flatten_keeps_dimensions_outside_of_axes() {
+            let flatten_shape = |config: FlattenConfig| {
+                let mut net_cfg = SequentialConfig::default();
+                net_cfg.add_input("data", &[2, 3, 4, 5]);
+                net_cfg.add_layer(LayerConfig::new("flatten", config));
+                let network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+                let shape = network.output_blobs_data[0].read().unwrap().desc().clone();
+                shape
+            };
+            assert_eq!(vec![2, 60], flatten_shape(FlattenConfig::new()));
+            assert_eq!(vec![2, 3, 20], flatten_shape(FlattenConfig::new().start_axis(2)));
+            assert_eq!(vec![2, 12, 5], flatten_shape(FlattenConfig::new().end_axis(2)));
+            assert_eq!(vec![120], flatten_shape(FlattenConfig::new().start_axis(0)));
+        }
+
+        #[test]
+        fn flatten_between_spatial_and_linear_layers() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &[2, 3, 2, 2]);
+            net_cfg.add_layer(LayerConfig::new("flatten", FlattenConfig::new()));
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(4)));
+            let network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            assert_eq!(&vec![2, 4], network.output_blobs_data[0].read().unwrap().desc());
+
+            let mut larger_network = network.with_batch_size(5).unwrap();
+            let data = (0..60).map(|value| value as f32).collect::<Vec<_>>();
+            larger_network.fill_input("data", &data).unwrap();
+            let output = larger_network.forward(&[])[0].clone();
+            assert_eq!(&vec![5, 4], output.read().unwrap().desc());
+        }
+
+        #[test]
+        #[should_panic]
+        fn flatten_rejects_axes_outside_of_input() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &[2, 3]);
+            net_cfg.add_layer(LayerConfig::new("flatten", FlattenConfig::new().end_axis(2)));
+            Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+        }
+
+        #[test]
+        fn auto_flatten_inserts_flatten_before_linear() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &[2, 3, 2, 2]);
+            net_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(4)));
+            net_cfg.force_backward = true;
+            let network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg.clone()));
+            let nodes = network.execution_order();
+            assert_eq!(vec!["sigmoid", "linear/flatten", "linear"], nodes.iter().map(|node| node.name.as_str()).collect::<Vec<_>>());
+            assert_eq!(vec![vec![2, 3, 2, 2]], nodes[0].output_shapes);
+            assert_eq!(vec![vec![2, 12]], nodes[1].output_shapes);
+
+            net_cfg.auto_flatten = false;
+            let unflattened = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+            assert_eq!(vec!["sigmoid", "linear"], unflattened.execution_order().iter().map(|node| node.name.as_str()).collect::<Vec<_>>());
+            let weights = (0..48).map(|value| (value as f32 * 0.1).sin()).collect::<Vec<_>>();
+            write_tensor(&network.learnable_weights_data()[0], &weights).unwrap();
+            write_tensor(&unflattened.learnable_weights_data()[0], &weights).unwrap();
+
+            let data = (0..24).map(|value| value as f32 * 0.1 - 1f32).collect::<Vec<_>>();
+            let output_gradient = (0..8).map(|value| value as f32 * 0.25).collect::<Vec<_>>();
+            let mut results = Vec::new();
+            for network in &mut [network, unflattened] {
+                network.fill_input("data", &data).unwrap();
+                let output = tensor_to_vec(&network.forward(&[])[0]);
+                let output_shape = network.output_blobs_data[0].read().unwrap().desc().clone();
+                let gradient = Arc::new(RwLock::new(SharedTensor::<f32>::new(native_backend().device(), &output_shape).unwrap()));
+                write_tensor(&gradient, &output_gradient).unwrap();
+                let input_gradient = tensor_to_vec(&network.backward_input(&[gradient])[0]);
+                results.push((output, input_gradient));
+            }
+            assert_eq!(results[1], results[0]);
+        }
+
+        #[test]
+        fn sequential_returns_declared_blobs() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 3]);
+            let mut linear_cfg = LayerConfig::new("linear", LinearConfig::new(2));
+            linear_cfg.add_output("hidden");
+            net_cfg.add_layer(linear_cfg);
+            let mut sigmoid_cfg = LayerConfig::new("sigmoid", LayerType::Sigmoid);
+            sigmoid_cfg.add_input("hidden");
+            net_cfg.add_layer(sigmoid_cfg);
+            net_cfg.add_output("hidden");
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg.clone()));
+
+            let outputs = network.forward(&[]);
+            assert_eq!(1, outputs.len());
+            assert_eq!(&vec![1, 2], outputs[0].read().unwrap().desc());
+
+            net_cfg.add_output("missing");
+            let error = Sequential::empty().init_layers(native_backend(), &net_cfg).unwrap_err();
+            assert_eq!("The container has no layer or blob named missing, which is declared as output", error);
+        }
+
+        #[test]
+        fn output_names_of_container_follow_declared_outputs() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 3]);
+            let mut linear_cfg = LayerConfig::new("linear", LinearConfig::new(2));
+            linear_cfg.add_output("hidden");
+            net_cfg.add_layer(linear_cfg);
+            let mut sigmoid_cfg = LayerConfig::new("sigmoid", LayerType::Sigmoid);
+            sigmoid_cfg.add_input("hidden");
+            sigmoid_cfg.add_output("probabilities");
+            net_cfg.add_layer(sigmoid_cfg);
+            net_cfg.add_output("sigmoid");
+            net_cfg.add_output("hidden");
+            let network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            assert_eq!(vec!["probabilities".to_owned(), "hidden".to_owned()], network.output_names());
+        }
+
+        #[test]
+        fn hooks_receive_snapshots_of_nested_layers() {
+            use std::cell::RefCell;
+            use std::rc::Rc;
+            use std::sync::{Arc, RwLock};
+
+            let mut inner_cfg = SequentialConfig::default();
+            inner_cfg.add_input("hidden", &vec![1, 2]);
+            inner_cfg.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 3]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+            net_cfg.add_layer(LayerConfig::new("inner", inner_cfg));
+            let mut network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            let forward_calls = calls.clone();
+            network.register_forward_hook("sigmoid", move |name, tensor| {
+                let values = tensor.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned();
+                forward_calls.borrow_mut().push((name.to_owned(), tensor.desc().clone(), values));
+            }).unwrap();
+            let backward_calls = calls.clone();
+            network.register_backward_hook("linear", move |name, tensor| {
+                backward_calls.borrow_mut().push((name.to_owned(), tensor.desc().clone(), Vec::new()));
+            }).unwrap();
+            assert!(network.register_forward_hook("missing", |_, _| {}).is_err());
+
+            network.fill_input("data", &[1f32, 1f32, 2f32]).unwrap();
+            let output = network.forward(&[])[0].clone();
+            let output_gradient = SharedTensor::<f32>::new(native_backend().device(), &vec![1, 2]).unwrap();
+            network.backward(&[Arc::new(RwLock::new(output_gradient))]);
+
+            let calls = calls.borrow();
+            assert_eq!(2, calls.len());
+            assert_eq!(("sigmoid".to_owned(), vec![1, 2]), (calls[0].0.clone(), calls[0].1.clone()));
+            let output_lock = output.read().unwrap();
+            assert_eq!(output_lock.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>(), &calls[0].2[..]);
+            assert_eq!(("linear".to_owned(), vec![1, 3]), (calls[1].0.clone(), calls[1].1.clone()));
+        }
+
+        #[test]
+        fn sequential_rejects_duplicate_layer_names() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 3]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+
+            let error = Sequential::empty().init_layers(native_backend(), &net_cfg).unwrap_err();
+            assert_eq!("The container has more than one layer named linear. Layers need unique names.", error);
+        }
+
+        #[test]
+        fn generated_blob_names_avoid_configured_names() {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 3]);
+            let mut first_cfg = LayerConfig::new("first", LinearConfig::new(2));
+            first_cfg.add_output("SEQUENTIAL_1");
+            net_cfg.add_layer(first_cfg);
+            let mut second_cfg = LayerConfig::new("second", LinearConfig::new(4));
+            second_cfg.add_input("SEQUENTIAL_1");
+            net_cfg.add_layer(second_cfg);
+            net_cfg.add_layer(LayerConfig::new("third", LinearConfig::new(1)));
+            let network = Layer::from_config(native_backend(), &LayerConfig::new("network", net_cfg));
+
+            let connections = network.execution_order().iter()
+                .map(|node| (node.name.clone(), node.inputs.clone(), node.outputs.clone()))
+                .collect::<Vec<_>>();
+            assert_eq!(("second".to_owned(), vec!["SEQUENTIAL_1".to_owned()], vec!["SEQUENTIAL_1_1".to_owned()]), connections[1]);
+            assert_eq!(("third".to_owned(), vec!["SEQUENTIAL_1_1".to_owned()], vec!["SEQUENTIAL_OUTPUT_2".to_owned()]), connections[2]);
+        }
     }
 
     #[cfg(feature="cuda")]
@@ -107,9 +1641,9 @@ mod layer_spec {
         fn can_create_simple_network_sequential_layer() {
             let mut model = SequentialConfig::default();
             model.add_input("data", &vec![1, 784]);
-            model.add_layer(LayerConfig::new("linear1", LinearConfig { output_size: 1568 }));
+            model.add_layer(LayerConfig::new("linear1", LinearConfig::new(1568)));
             model.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
-            model.add_layer(LayerConfig::new("linear2", LinearConfig { output_size: 10 }));
+            model.add_layer(LayerConfig::new("linear2", LinearConfig::new(10)));
 
             let _ = Layer::from_config(cuda_backend(), &LayerConfig::new("model", LayerType::Sequential(model)));
         }
@@ -126,7 +1660,7 @@ mod layer_spec {
 
             let mut reshape_model = SequentialConfig::default();
             reshape_model.add_input("data", &vec![3]);
-            reshape_model.add_layer(LayerConfig::new("reshape", ReshapeConfig { shape: vec![1, 1, 3] }));
+            reshape_model.add_layer(LayerConfig::new("reshape", ReshapeConfig::of_shape(&[1, 1, 3])));
             reshape_model.add_layer(LayerConfig::new("sigmoid", LayerType::Sigmoid));
             let mut reshape_network = Layer::from_config(cuda_backend.clone(), &LayerConfig::new("reshape_model", LayerType::Sequential(reshape_model)));
 
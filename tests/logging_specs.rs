@@ -0,0 +1,64 @@
+extern crate leaf;
+extern crate log;
+
+#[cfg(test)]
+mod logging_spec {
+    use std::sync::{Arc, Mutex};
+    use log::{self, Log, LogLevelFilter, LogMetadata, LogRecord};
+    use leaf::logging::*;
+
+    struct CaptureLogger {
+        records: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _: &LogMetadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &LogRecord) {
+            self.records.lock().unwrap().push((record.target().to_owned(), format!("{}", record.args())));
+        }
+    }
+
+    #[test]
+    fn shape_has_no_whitespace() {
+        assert_eq!("[1,28,28]", Shape(&[1, 28, 28]).to_string());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn layers_log_structured_events_per_subsystem() {
+        use std::rc::Rc;
+        use leaf::layer::*;
+        use leaf::layers::*;
+        use leaf::util::native_backend;
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let logger_records = records.clone();
+        log::set_logger(move |max_level| {
+            max_level.set(LogLevelFilter::Trace);
+            Box::new(CaptureLogger { records: logger_records })
+        }).unwrap();
+
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &vec![1, 3]);
+        net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+        let mut network = Layer::from_config(Rc::new(native_backend()), &LayerConfig::new("network", net_cfg));
+        network.forward(&[]);
+        {
+            let records = records.lock().unwrap();
+            assert!(records.contains(&("leaf::init".to_owned(), "event=connect_input layer=linear input=data".to_owned())));
+            assert!(records.iter().any(|&(ref target, ref message)| {
+                target == "leaf::forward" && message.starts_with("event=forward layer=linear shapes=[1,2] duration_ms=")
+            }));
+        }
+
+        set_log_level(Subsystem::Forward, LogLevelFilter::Off);
+        assert_eq!(LogLevelFilter::Off, log_level(Subsystem::Forward));
+        records.lock().unwrap().clear();
+        network.forward(&[]);
+        set_log_level(Subsystem::Forward, LogLevelFilter::Trace);
+        assert!(!records.lock().unwrap().iter().any(|&(ref target, _)| target == "leaf::forward"));
+    }
+}
@@ -0,0 +1,116 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod mnist_spec {
+    use leaf::mnist::*;
+    use leaf::solver::IDataset;
+
+    fn idx(magic: u32, shape: &[u32], data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for value in Some(magic).iter().chain(shape) {
+            bytes.extend(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, *value as u8]);
+        }
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn read_idx_images_and_labels() {
+        let images = idx(0x803, &[2, 2, 3], &[0, 51, 255, 102, 0, 0, 255, 255, 255, 0, 0, 204]);
+        let labels = idx(0x801, &[2], &[7, 3]);
+        let (shape, data) = read_idx(&mut &images[..]).unwrap();
+        assert_eq!(vec![2, 2, 3], shape);
+        assert_eq!(12, data.len());
+
+        let dataset = read_mnist(&mut &images[..], &mut &labels[..]).unwrap();
+        assert_eq!(2, dataset.len());
+        assert_eq!(Vec::<usize>::new(), dataset.target_shape());
+        assert_eq!((vec![0f32, 0.2f32, 1f32, 0.4f32, 0f32, 0f32], vec![7f32]), dataset.sample(0));
+        assert_eq!((vec![1f32, 1f32, 1f32, 0f32, 0f32, 0.8f32], vec![3f32]), dataset.sample(1));
+    }
+
+    #[test]
+    fn read_mnist_rejects_invalid_files() {
+        let images = idx(0x803, &[2, 2, 3], &[0; 12]);
+        let labels = idx(0x801, &[2], &[7, 3]);
+        // float data instead of unsigned bytes
+        assert!(read_idx(&mut &idx(0xD01, &[2], &[0; 8])[..]).is_err());
+        // truncated data
+        assert!(read_idx(&mut &images[..images.len() - 1]).is_err());
+        // swapped files
+        assert!(read_mnist(&mut &labels[..], &mut &images[..]).is_err());
+        // more images than labels
+        assert!(read_mnist(&mut &images[..], &mut &idx(0x801, &[1], &[7])[..]).is_err());
+    }
+
+    /// Trains a multilayer perceptron on the full MNIST dataset, which has to be downloaded
+    /// and decompressed into the directory `MNIST_DIR` (default: `target/mnist`).
+    ///
+    /// Run with `cargo test --release --features slow_tests --test mnist_specs`.
+    #[cfg(all(feature="native", feature="slow_tests"))]
+    #[test]
+    fn train_mlp_on_mnist() {
+        use std::env;
+        use std::path::PathBuf;
+        use std::rc::Rc;
+        use co::prelude::*;
+        use leaf::inference::ChunkedInference;
+        use leaf::layer::*;
+        use leaf::layers::*;
+        use leaf::models;
+        use leaf::solver::*;
+        use leaf::util::native_backend;
+
+        let batch_size = 32;
+        let data_dir = PathBuf::from(env::var("MNIST_DIR").unwrap_or("target/mnist".to_owned()));
+        let load = |name: &str| {
+            load_mnist(data_dir.join(format!("{}-images-idx3-ubyte", name)), data_dir.join(format!("{}-labels-idx1-ubyte", name)))
+                .expect("MNIST files not found, set MNIST_DIR to the directory of the decompressed files")
+        };
+        let train = load("train");
+        let test = load("t10k");
+        assert_eq!(60000, train.len());
+        assert_eq!(10000, test.len());
+
+        let mut objective_cfg = SequentialConfig::default();
+        objective_cfg.add_input("network_out", &[batch_size, 10]);
+        objective_cfg.add_input("label", &[batch_size]);
+        objective_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(10) }));
+        let config = SolverConfig {
+            network: LayerConfig::new("mnist", models::mlp(batch_size, &[784, 100, 10])),
+            objective: LayerConfig::new("classifier", objective_cfg),
+            base_lr: 0.01f32,
+            ..SolverConfig::default()
+        };
+        let backend = Rc::new(native_backend());
+        let mut solver = Solver::from_config(backend.clone(), backend.clone(), &config).unwrap();
+
+        let initial_loss = solver.validate(&test).unwrap();
+        let summaries = solver.fit(&train, 2);
+        assert!(summaries[0].loss < initial_loss, "loss didn't decrease in the first epoch");
+        assert!(summaries[1].loss < summaries[0].loss, "loss didn't decrease in the second epoch");
+        assert!(solver.validate(&test).unwrap() < initial_loss);
+
+        let accuracy = |network: &Layer<Backend<Native>>| {
+            let mut inputs = Vec::new();
+            let mut labels = Vec::new();
+            for index in 0..test.len() {
+                let (input, target) = test.sample(index);
+                inputs.extend(input);
+                labels.push(target[0] as usize);
+            }
+            let outputs = ChunkedInference::new(network, 64 * 1024 * 1024).unwrap().run(&inputs).unwrap();
+            let num_correct = outputs.chunks(10).zip(labels.iter()).filter(|&(scores, &label)| {
+                scores.iter().enumerate().fold(0, |best, (class, &score)| if score > scores[best] { class } else { best }) == label
+            }).count();
+            num_correct as f32 / labels.len() as f32
+        };
+        let trained_accuracy = accuracy(solver.network());
+        assert!(trained_accuracy > 0.9, "test accuracy is only {}", trained_accuracy);
+
+        solver.mut_network().save("target/testmnistnetwork").unwrap();
+        let loaded = Layer::<Backend<Native>>::load(backend.clone(), "target/testmnistnetwork").unwrap();
+        assert_eq!(trained_accuracy, accuracy(&loaded));
+    }
+}
@@ -0,0 +1,79 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod models_spec {
+    use leaf::layer::LayerType;
+    use leaf::models::*;
+
+    #[test]
+    fn mlp_adds_relu_between_linear_layers() {
+        let net_cfg = mlp(4, &[3, 8, 2]);
+        assert_eq!(vec![("data".to_owned(), vec![4, 3])], net_cfg.inputs);
+        let names = net_cfg.layers.iter().map(|layer| layer.name.clone()).collect::<Vec<_>>();
+        assert_eq!(vec!["linear1", "linear1/relu", "linear2", "log_softmax"], names);
+        match net_cfg.layers[2].layer_type {
+            LayerType::Linear(ref linear_config) => assert_eq!(2, linear_config.output_size),
+            _ => panic!("Expected a Linear layer"),
+        }
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn logistic_regression_outputs_class_scores() {
+        use std::rc::Rc;
+        use std::sync::{Arc, RwLock};
+        use leaf::layer::{Layer, LayerConfig};
+        use leaf::util::{native_backend, tensor_from_slice};
+
+        let backend = Rc::new(native_backend());
+        let mut network = Layer::from_config(backend.clone(), &LayerConfig::new("network", logistic_regression(5, 3, 4)));
+        let input = tensor_from_slice(&*backend, &[5, 3], &[0.5f32; 15]).unwrap();
+        let output = network.forward(&[Arc::new(RwLock::new(input))])[0].clone();
+        assert_eq!(&vec![5, 4], output.read().unwrap().desc());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn autoencoder_ties_decoder_to_transposed_encoder_weight() {
+        use std::rc::Rc;
+        use leaf::layer::{Layer, LayerConfig};
+        use leaf::util::{native_backend, tensor_to_vec, write_tensor};
+
+        let mut network = Layer::from_config(Rc::new(native_backend()), &LayerConfig::new("autoencoder", autoencoder(1, &[3, 2], true)));
+        assert_eq!(vec!["encoder1/weight".to_owned()], network.learnable_weights_names());
+        assert_eq!(1, network.learnable_weights_decay_mult().len());
+        let weights = network.learnable_weights_data();
+        assert_eq!(&vec![2, 3], weights[0].read().unwrap().desc());
+
+        let weight = vec![1f32, 0f32, -1f32, 0.5f32, 2f32, 0f32];
+        write_tensor(&weights[0], &weight).unwrap();
+        let input = vec![0.2f32, 0.4f32, 0.6f32];
+        network.fill_input("data", &input).unwrap();
+        let output = tensor_to_vec(&network.forward(&[])[0]);
+
+        let sigmoid = |x: f32| 1f32 / (1f32 + (-x).exp());
+        let code = (0..2).map(|j| sigmoid((0..3).map(|i| weight[j * 3 + i] * input[i]).fold(0f32, |sum, x| sum + x))).collect::<Vec<_>>();
+        for i in 0..3 {
+            let expected = sigmoid((0..2).map(|j| weight[j * 3 + i] * code[j]).fold(0f32, |sum, x| sum + x));
+            assert!((output[i] - expected).abs() < 1e-5, "output {} is {}, expected {}", i, output[i], expected);
+        }
+    }
+
+    #[cfg(all(feature="cuda", not(feature="native")))]
+    #[test]
+    fn lenet_outputs_class_scores() {
+        use std::rc::Rc;
+        use std::sync::{Arc, RwLock};
+        use co::prelude::*;
+        use leaf::layer::{Layer, LayerConfig};
+
+        let framework = Cuda::new();
+        let hardwares = framework.hardwares()[0..1].to_vec();
+        let backend = Rc::new(Backend::new(BackendConfig::new(framework, &hardwares)).unwrap());
+        let mut network = Layer::from_config(backend.clone(), &LayerConfig::new("lenet", lenet(2)));
+        let input = SharedTensor::<f32>::new(backend.device(), &vec![2, 1, 28, 28]).unwrap();
+        let output = network.forward(&[Arc::new(RwLock::new(input))])[0].clone();
+        assert_eq!(&vec![2, 10], output.read().unwrap().desc());
+    }
+}
@@ -0,0 +1,59 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod npy_spec {
+    #[cfg(feature="native")]
+    mod native {
+        use co::prelude::*;
+        use leaf::npy::*;
+        use leaf::util::{native_backend, write_to_memory};
+
+        fn tensor(shape: &[usize], data: &[f32]) -> SharedTensor<f32> {
+            let native = native_backend();
+            let mut tensor = SharedTensor::<f32>::new(native.device(), &shape.to_owned()).unwrap();
+            write_to_memory(tensor.get_mut(native.device()).unwrap(), data);
+            tensor
+        }
+
+        fn data(tensor: &SharedTensor<f32>) -> Vec<f32> {
+            let native = native_backend();
+            tensor.get(native.device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned()
+        }
+
+        #[test]
+        fn save_and_load_npy() {
+            let mut original = tensor(&[2, 3], &[1f32, 2f32, 3f32, 4f32, 5f32, 6.5f32]);
+            save_npy("target/testtensor.npy", &mut original).unwrap();
+            let loaded = load_npy("target/testtensor.npy").unwrap();
+
+            assert_eq!(&vec![2, 3], loaded.desc());
+            assert_eq!(data(&original), data(&loaded));
+        }
+
+        #[test]
+        fn read_npy_converts_to_f32() {
+            let header = "{'descr': '<i4', 'fortran_order': False, 'shape': (3,), }";
+            let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+            bytes.extend(&[header.len() as u8, 0]);
+            bytes.extend(header.as_bytes());
+            bytes.extend(&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 7, 0, 0, 0]);
+
+            let loaded = read_npy(&mut &bytes[..]).unwrap();
+            assert_eq!(&vec![3], loaded.desc());
+            assert_eq!(vec![-1f32, 0f32, 7f32], data(&loaded));
+        }
+
+        #[test]
+        fn save_and_load_npz() {
+            let mut weights = tensor(&[2, 2], &[1f32, 2f32, 3f32, 4f32]);
+            let mut bias = tensor(&[2], &[0.5f32, -0.5f32]);
+            save_npz("target/testtensors.npz", &mut [("weights", &mut weights), ("bias", &mut bias)]).unwrap();
+            let loaded = load_npz("target/testtensors.npz").unwrap();
+
+            assert_eq!(vec!["weights", "bias"], loaded.iter().map(|&(ref name, _)| name.clone()).collect::<Vec<_>>());
+            assert_eq!(data(&weights), data(&loaded[0].1));
+            assert_eq!(data(&bias), data(&loaded[1].1));
+        }
+    }
+}
@@ -0,0 +1,34 @@
+extern crate leaf;
+extern crate rand;
+
+#[cfg(test)]
+mod rng_spec {
+    use leaf::rng::{RngStream, seed, set_seed};
+    use rand::Rng;
+
+    #[test]
+    fn streams_depend_on_seed_and_name_only() {
+        let numbers = |seed: u64, name: &str| {
+            let mut stream = RngStream::new(seed, name);
+            (0..4).map(|_| stream.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(7, "dropout1"), numbers(7, "dropout1"));
+        assert!(numbers(7, "dropout1") != numbers(7, "dropout2"));
+        assert!(numbers(7, "dropout1") != numbers(8, "dropout1"));
+
+        let mut stream = RngStream::new(7, "dropout1");
+        let first = stream.gen::<f32>();
+        stream.gen::<f32>();
+        assert_eq!(2, stream.counter());
+        stream.set_counter(0);
+        assert_eq!(first, stream.gen::<f32>());
+    }
+
+    #[test]
+    fn seed_keeps_all_64_bits() {
+        set_seed(0x123456789abcdef0);
+        assert_eq!(0x123456789abcdef0, seed());
+        set_seed(0);
+        assert_eq!(0, seed());
+    }
+}
@@ -0,0 +1,141 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+#[cfg(feature="native")]
+mod serialization_spec {
+    use std::rc::Rc;
+    use co::prelude::*;
+    use leaf::layer::*;
+    use leaf::layers::*;
+    use leaf::models;
+    use leaf::util::{native_backend, tensor_to_vec, write_to_memory};
+
+    /// Create a container for `layer_type` with an input of each of the `input_shapes`.
+    fn single_layer_network(name: &str, layer_type: LayerType, input_shapes: &[&[usize]]) -> LayerConfig {
+        let mut net_cfg = SequentialConfig::default();
+        // sinks aren't connected to the container inputs automatically
+        let is_sink = layer_type.is_sink();
+        let mut layer_cfg = LayerConfig::new(name, layer_type);
+        for (i, shape) in input_shapes.iter().enumerate() {
+            let input_name = format!("input{}", i);
+            net_cfg.add_input(&input_name, shape);
+            if is_sink {
+                layer_cfg.add_input(&input_name);
+            }
+        }
+        net_cfg.add_layer(layer_cfg);
+        LayerConfig::new("network", net_cfg)
+    }
+
+    /// Save a Layer created from `config`, load it again and assert that the config,
+    /// the labels and the weights survive bit-exactly.
+    fn assert_round_trip(name: &str, config: &LayerConfig) {
+        let backend = Rc::new(native_backend());
+        let mut original = Layer::from_config(backend.clone(), config);
+        original.set_labels(vec!["first", "second"]);
+        // distinctive values that use all bits of the mantissa
+        for (i, weight) in original.learnable_weights_data().iter().enumerate() {
+            let mut weight_lock = weight.write().unwrap();
+            let size = weight_lock.desc().size();
+            let values = (0..size).map(|j| ((i * 31 + j) as f32 * 0.7310586).sin() * 3f32).collect::<Vec<f32>>();
+            weight_lock.sync(backend.device()).unwrap();
+            write_to_memory(weight_lock.get_mut(backend.device()).unwrap(), &values);
+        }
+
+        let path = format!("target/testroundtrip_{}", name);
+        original.save(&path).unwrap();
+        let loaded = Layer::<Backend<Native>>::load_strict(backend.clone(), &path).unwrap();
+
+        assert_eq!(format!("{:?}", original.config), format!("{:?}", loaded.config), "config of {} changed", name);
+        assert_eq!(original.name, loaded.name);
+        assert_eq!(original.labels(), loaded.labels());
+        assert_eq!(original.learnable_weights_paths(), loaded.learnable_weights_paths(), "weights of {} changed", name);
+        for (original_weight, loaded_weight) in original.learnable_weights_data().iter().zip(loaded.learnable_weights_data().iter()) {
+            assert_eq!(original_weight.read().unwrap().desc(), loaded_weight.read().unwrap().desc());
+            let original_bits = tensor_to_vec(original_weight).iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+            let loaded_bits = tensor_to_vec(loaded_weight).iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+            assert_eq!(original_bits, loaded_bits, "weights of {} changed", name);
+        }
+    }
+
+    #[test]
+    fn round_trip_common_layers() {
+        let layers: Vec<(&str, LayerType, Vec<&[usize]>)> = vec![
+            ("constant", LayerType::Constant(ConstantConfig { shape: vec![2, 3], value: 0.25, learnable: true }), vec![&[2, 3]]),
+            ("fixed_constant", LayerType::Constant(ConstantConfig { shape: vec![4], value: -1.5, learnable: false }), vec![&[2, 3]]),
+            ("fused_linear", LayerType::FusedLinear(FusedLinearConfig { output_size: 3, activation: FusedActivation::Sigmoid }), vec![&[2, 4]]),
+            ("group_norm", LayerType::GroupNorm(GroupNormConfig { num_groups: 2, epsilon: 1e-3 }), vec![&[2, 4, 3]]),
+            ("instance_norm", LayerType::InstanceNorm(InstanceNormConfig { epsilon: 1e-4 }), vec![&[2, 3, 4]]),
+            ("layer_norm", LayerType::LayerNorm(LayerNormConfig { epsilon: 1e-4 }), vec![&[2, 5]]),
+            ("linear", LayerType::Linear(LinearConfig::new(3)), vec![&[2, 4]]),
+            ("transposed_linear", LayerType::Linear(LinearConfig::new(3).transpose(true)), vec![&[2, 4]]),
+            ("log_softmax", LayerType::LogSoftmax(LogSoftmaxConfig { temperature: 2f32 }), vec![&[2, 3]]),
+            ("noise", LayerType::Noise(NoiseConfig { mode: NoiseMode::Bernoulli, level: 0.3 }), vec![&[2, 3]]),
+            ("scale", LayerType::Scale(ScaleConfig { bias: true }), vec![&[2, 3]]),
+            ("softmax", LayerType::Softmax(SoftmaxConfig { temperature: 0.5 }), vec![&[2, 3]]),
+        ];
+        for (name, layer_type, input_shapes) in layers {
+            assert_round_trip(name, &single_layer_network(name, layer_type, &input_shapes));
+        }
+    }
+
+    #[test]
+    fn round_trip_activation_layers() {
+        let layers: Vec<(&str, LayerType, Vec<&[usize]>)> = vec![
+            ("maxout", LayerType::Maxout(MaxoutConfig { num_pieces: 2 }), vec![&[2, 6]]),
+            ("prelu", LayerType::PReLU(PReLUConfig { initial_slope: 0.1 }), vec![&[2, 3, 4]]),
+            ("relu", LayerType::ReLU, vec![&[2, 3]]),
+            ("sigmoid", LayerType::Sigmoid, vec![&[2, 3]]),
+        ];
+        for (name, layer_type, input_shapes) in layers {
+            assert_round_trip(name, &single_layer_network(name, layer_type, &input_shapes));
+        }
+    }
+
+    #[test]
+    fn round_trip_loss_and_utility_layers() {
+        let layers: Vec<(&str, LayerType, Vec<&[usize]>)> = vec![
+            ("kl_divergence", LayerType::KLDivergence, vec![&[2, 3], &[2, 3]]),
+            ("nll", LayerType::NegativeLogLikelihood(NegativeLogLikelihoodConfig { num_classes: Some(3) }), vec![&[2, 3], &[2]]),
+            ("nll_inferred", LayerType::NegativeLogLikelihood(NegativeLogLikelihoodConfig { num_classes: None }), vec![&[2, 3], &[2]]),
+            ("accuracy", LayerType::Accuracy(AccuracyConfig { top_k: 2 }), vec![&[2, 3], &[2]]),
+            ("flatten", LayerType::Flatten(FlattenConfig::new().start_axis(0)), vec![&[2, 3]]),
+            ("flatten_axes", LayerType::Flatten(FlattenConfig::new().end_axis(1)), vec![&[2, 3, 2]]),
+            ("reshape", LayerType::Reshape(ReshapeConfig::of_shape(&[3, 2])), vec![&[2, 3]]),
+            ("batch_reshape", LayerType::Reshape(ReshapeConfig::of_shape_with_batch(&[3, 1])), vec![&[2, 3]]),
+            ("silence", LayerType::Silence, vec![&[2, 3]]),
+        ];
+        for (name, layer_type, input_shapes) in layers {
+            assert_round_trip(name, &single_layer_network(name, layer_type, &input_shapes));
+        }
+    }
+
+    #[test]
+    fn round_trip_nested_containers() {
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &[2, 4]);
+        net_cfg.label_inputs.push(("label".to_owned(), vec![2]));
+        net_cfg.force_backward = true;
+        net_cfg.auto_flatten = false;
+        for &(container, output_size) in [("encoder", 2), ("decoder", 4)].iter() {
+            let mut container_cfg = SequentialConfig::default();
+            container_cfg.add_input("data", &[2, 6 - output_size]);
+            container_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(output_size)));
+            container_cfg.add_layer(LayerConfig::new("linear/relu", LayerType::ReLU));
+            net_cfg.add_layer(LayerConfig::new(container, container_cfg));
+        }
+        let mut log_softmax_cfg = LayerConfig::new("log_softmax", LogSoftmaxConfig::default());
+        log_softmax_cfg.add_output("log_probabilities");
+        net_cfg.add_layer(log_softmax_cfg);
+        let mut nll_cfg = LayerConfig::new("nll", NegativeLogLikelihoodConfig::default());
+        nll_cfg.add_input("log_probabilities");
+        nll_cfg.add_input("label");
+        net_cfg.add_layer(nll_cfg);
+        net_cfg.add_output("log_probabilities");
+        assert_round_trip("nested", &LayerConfig::new("network", net_cfg));
+
+        assert_round_trip("tied_autoencoder", &LayerConfig::new("autoencoder", models::autoencoder(2, &[6, 3], true)));
+        assert_round_trip("mlp", &LayerConfig::new("mlp", models::mlp(2, &[6, 5, 3])));
+    }
+}
@@ -0,0 +1,1013 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(feature="native")]
+mod solvers_spec {
+    use std::rc::Rc;
+    use std::sync::{Arc, RwLock};
+    use co::prelude::*;
+    use leaf::layer::*;
+    use leaf::layers::*;
+    use leaf::solver::*;
+    use leaf::util::{ArcLock, native_backend, tensor_from_slice, tensor_to_vec};
+    use leaf::weight::WeightConfig;
+
+    fn backend() -> Rc<Backend<Native>> {
+        Rc::new(native_backend())
+    }
+
+    fn tensor(shape: &[usize], data: &[f32]) -> ArcLock<SharedTensor<f32>> {
+        Arc::new(RwLock::new(tensor_from_slice(&native_backend(), shape, data).unwrap()))
+    }
+
+    fn weights(solver: &Solver<Backend<Native>, Backend<Native>>) -> Vec<Vec<f32>> {
+        solver.network().learnable_weights_data().iter().map(tensor_to_vec).collect()
+    }
+
+    /// A single Linear layer with `input_size` inputs and `output_size` outputs for minibatches of two samples.
+    fn linear_network(input_size: usize, output_size: usize) -> LayerConfig {
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &[2, input_size]);
+        net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(output_size)));
+        LayerConfig::new("network", net_cfg)
+    }
+
+    fn nll_objective(num_classes: usize) -> LayerConfig {
+        let mut loss_cfg = SequentialConfig::default();
+        loss_cfg.add_input("network_out", &[2, num_classes]);
+        loss_cfg.add_input("label", &[2]);
+        loss_cfg.add_layer(LayerConfig::new("nll", NegativeLogLikelihoodConfig { num_classes: Some(num_classes) }));
+        LayerConfig::new("loss", loss_cfg)
+    }
+
+    /// Returns the expected weight of a Linear layer after a SGD step without momentum
+    /// for the `input` of shape [batch, input_size] and the `output_gradient` of shape [batch, output_size].
+    fn sgd_step(weight: &[f32], input: &[f32], output_gradient: &[f32], input_size: usize, lr: f32) -> Vec<f32> {
+        let output_size = weight.len() / input_size;
+        let batch_size = input.len() / input_size;
+        let mut expected = weight.to_owned();
+        for o in 0..output_size {
+            for i in 0..input_size {
+                let gradient = (0..batch_size).fold(0f32, |sum, b| sum + output_gradient[b * output_size + o] * input[b * input_size + i]);
+                expected[o * input_size + i] -= lr * gradient;
+            }
+        }
+        expected
+    }
+
+    fn assert_close(expected: &[f32], actual: &[f32]) {
+        assert_eq!(expected.len(), actual.len());
+        for (e, a) in expected.iter().zip(actual) {
+            assert!((e - a).abs() < 1e-5, "expected {:?}, got {:?}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn alternating_solvers_train_generator_through_discriminator() {
+        let backend = backend();
+        let generator_cfg = SolverConfig {
+            network: linear_network(4, 3),
+            objective: nll_objective(3),
+            base_lr: 0.1f32,
+            ..SolverConfig::default()
+        };
+        let mut discriminator_net = SequentialConfig::default();
+        discriminator_net.add_input("data", &[2, 3]);
+        discriminator_net.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+        discriminator_net.add_layer(LayerConfig::new("log_softmax", LogSoftmaxConfig::default()));
+        let discriminator_cfg = SolverConfig {
+            network: LayerConfig::new("discriminator", discriminator_net),
+            objective: nll_objective(2),
+            base_lr: 0.5f32,
+            ..SolverConfig::default()
+        };
+        let mut generator = Solver::from_config(backend.clone(), backend.clone(), &generator_cfg).unwrap();
+        let mut discriminator = Solver::from_config(backend.clone(), backend.clone(), &discriminator_cfg).unwrap();
+
+        let noise = [0.5f32, -1f32, 0.25f32, 2f32, 1f32, 0f32, -0.5f32, 0.75f32];
+        let generated = generator.forward(tensor(&[2, 4], &noise))[0].clone();
+        let discriminator_before = weights(&discriminator);
+        let generator_before = weights(&generator);
+
+        // the generator wants its samples to be classified as real
+        let gradient = discriminator.compute_input_gradient(generated, tensor(&[2], &[1f32, 1f32]));
+        assert_eq!(&vec![2, 3], gradient.read().unwrap().desc());
+        assert_eq!(discriminator_before, weights(&discriminator));
+        assert_eq!(0, discriminator.iteration());
+
+        let gradient_values = tensor_to_vec(&gradient);
+        generator.train_with_gradient(tensor(&[2, 4], &noise), gradient);
+        assert_close(&sgd_step(&generator_before[0], &noise, &gradient_values, 4, 0.1f32), &weights(&generator)[0]);
+        assert_eq!(1, generator.iteration());
+        assert_eq!(0, discriminator.iteration());
+    }
+
+    #[test]
+    fn solver_averages_gradients_of_sub_batches() {
+        use leaf::util::write_tensor;
+
+        let config = SolverConfig {
+            network: linear_network(3, 2),
+            minibatch_size: 2,
+            base_lr: 0.5f32,
+            ..SolverConfig::default()
+        };
+        let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+        let initial_weight = vec![0.1f32, -0.2f32, 0.3f32, 0.4f32, 0.5f32, -0.6f32];
+        write_tensor(&solver.network().learnable_weights_data()[0], &initial_weight).unwrap();
+
+        let data = [1f32, 2f32, -1f32, 0.5f32, 0f32, 3f32, -1f32, 1f32, 2f32, 2f32, 0.5f32, -0.5f32];
+        let output_gradients = [1f32, -2f32, -1f32, 4f32, 0.5f32, 0.5f32, -2f32, 1f32];
+        for sub_batch in 0..2 {
+            solver.forward(tensor(&[2, 3], &data[sub_batch * 6..(sub_batch + 1) * 6]));
+            solver.apply_output_gradients(&[tensor(&[2, 2], &output_gradients[sub_batch * 4..(sub_batch + 1) * 4])]);
+            if sub_batch == 0 {
+                // the weights are only updated after the last sub-batch
+                assert_eq!(0, solver.iteration());
+                assert_eq!(initial_weight, weights(&solver)[0]);
+            }
+        }
+        // the update of the averaged gradients is a step with half the learning rate on both sub-batches
+        assert_eq!(1, solver.iteration());
+        assert_close(&sgd_step(&initial_weight, &data, &output_gradients, 3, 0.25f32), &weights(&solver)[0]);
+
+        // the accumulation starts over after an update
+        solver.forward(tensor(&[2, 3], &data[..6]));
+        solver.apply_output_gradients(&[tensor(&[2, 2], &output_gradients[..4])]);
+        assert_eq!(1, solver.iteration());
+    }
+
+    #[test]
+    fn solver_counts_iterations_per_accumulated_minibatch() {
+        // the gradient of the NLL w.r.t. the scores of a Linear layer never vanishes,
+        // so every update changes the weights
+        let config = SolverConfig {
+            network: linear_network(3, 2),
+            objective: nll_objective(2),
+            minibatch_size: 3,
+            ..SolverConfig::default()
+        };
+        let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+        let initial_weights = weights(&solver);
+        for step in 0..6 {
+            solver.train_on_slices(&[0.5f32, -1f32, step as f32, 1f32, 0.5f32, -1f32], &[2, 3], &[0f32, 1f32], &[2]).unwrap();
+            assert_eq!((step + 1) / 3, solver.iteration());
+            if step < 2 {
+                assert_eq!(initial_weights, weights(&solver));
+            }
+        }
+        assert!(initial_weights != weights(&solver));
+    }
+
+    /// A squared error loss that is implemented outside of Leaf.
+    struct SquaredError {
+        output: Vec<f32>,
+        target: Vec<f32>,
+        shape: Vec<usize>,
+    }
+
+    impl ILoss<Backend<Native>> for SquaredError {
+        fn compute_loss(&mut self, output: ArcLock<SharedTensor<f32>>, target: ArcLock<SharedTensor<f32>>) -> ArcLock<SharedTensor<f32>> {
+            self.shape = output.read().unwrap().desc().clone();
+            self.output = tensor_to_vec(&output);
+            self.target = tensor_to_vec(&target);
+            let loss = self.output.iter().zip(&self.target).fold(0f32, |sum, (o, t)| sum + 0.5f32 * (o - t) * (o - t));
+            tensor(&[1], &[loss])
+        }
+
+        fn compute_gradient(&mut self) -> ArcLock<SharedTensor<f32>> {
+            let gradient = self.output.iter().zip(&self.target).map(|(o, t)| o - t).collect::<Vec<f32>>();
+            tensor(&self.shape, &gradient)
+        }
+    }
+
+    #[test]
+    fn custom_objective_computes_loss_and_gradient() {
+        let backend = backend();
+        let config = SolverConfig {
+            network: linear_network(2, 2),
+            base_lr: 0.1f32,
+            ..SolverConfig::default()
+        };
+        let objective = Box::new(SquaredError { output: Vec::new(), target: Vec::new(), shape: Vec::new() });
+        let mut solver = Solver::with_objective(backend.clone(), backend.clone(), &config, objective).unwrap();
+        let weight_before = weights(&solver)[0].clone();
+
+        let data = [1f32, 2f32, -1f32, 0.5f32];
+        let target = [0.5f32, -0.5f32, 1f32, 0f32];
+        let output = {
+            let output = solver.forward(tensor(&[2, 2], &data))[0].clone();
+            tensor_to_vec(&output)
+        };
+        let expected_loss = output.iter().zip(&target).fold(0f32, |sum, (o, t)| sum + 0.5f32 * (o - t) * (o - t));
+        let gradient = output.iter().zip(&target).map(|(o, t)| o - t).collect::<Vec<f32>>();
+
+        let loss = solver.train_on_slices(&data, &[2, 2], &target, &[2, 2]).unwrap();
+        assert!((expected_loss - loss).abs() < 1e-5);
+        assert_close(&sgd_step(&weight_before, &data, &gradient, 2, 0.1f32), &weights(&solver)[0]);
+    }
+
+    #[test]
+    fn train_on_slices_rejects_mismatched_lengths() {
+        let config = SolverConfig {
+            network: linear_network(2, 2),
+            objective: nll_objective(2),
+            ..SolverConfig::default()
+        };
+        let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+        let weight_before = weights(&solver)[0].clone();
+        assert!(solver.train_on_slices(&[1f32, 2f32, -1f32], &[2, 2], &[0f32, 1f32], &[2]).is_err());
+        assert!(solver.train_on_slices(&[1f32, 2f32, -1f32, 0.5f32], &[2, 2], &[0f32], &[2]).is_err());
+        assert_eq!(weight_before, weights(&solver)[0]);
+        assert_eq!(0, solver.iteration());
+        assert!(solver.train_on_slices(&[1f32, 2f32, -1f32, 0.5f32], &[2, 2], &[0f32, 1f32], &[2]).is_ok());
+    }
+
+    #[test]
+    fn set_iteration_and_learning_rate_control_the_update() {
+        let backend = backend();
+        let config = SolverConfig {
+            network: linear_network(2, 2),
+            objective: nll_objective(2),
+            lr_policy: LRPolicy::Step,
+            base_lr: 1f32,
+            gamma: 0.5f32,
+            stepsize: 10,
+            ..SolverConfig::default()
+        };
+        let mut solver = Solver::from_config(backend.clone(), backend.clone(), &config).unwrap();
+        let data = [1f32, 2f32, -1f32, 0.5f32];
+
+        solver.set_iteration(20);
+        assert_eq!(20, solver.iteration());
+        solver.set_learning_rate(0.4f32);
+        assert_eq!(0.4f32, solver.config().base_lr);
+        assert_eq!(0.1f32, solver.config().get_learning_rate(solver.iteration()));
+
+        let weight_before = weights(&solver)[0].clone();
+        let gradient = [0.5f32, -1f32, 2f32, 0f32];
+        solver.train_with_gradient(tensor(&[2, 2], &data), tensor(&[2, 2], &gradient));
+        assert_eq!(21, solver.iteration());
+        assert_close(&sgd_step(&weight_before, &data, &gradient, 2, 0.1f32), &weights(&solver)[0]);
+
+        solver.set_learning_rate(0f32);
+        let weight_before = weights(&solver)[0].clone();
+        solver.train_with_gradient(tensor(&[2, 2], &data), tensor(&[2, 2], &gradient));
+        assert_eq!(weight_before, weights(&solver)[0]);
+    }
+
+    #[test]
+    fn forward_and_apply_output_gradients_update_the_weights() {
+        let config = SolverConfig {
+            network: linear_network(2, 2),
+            objective: nll_objective(2),
+            base_lr: 0.1f32,
+            ..SolverConfig::default()
+        };
+        let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+        let weight_before = weights(&solver)[0].clone();
+        let data = [1f32, 2f32, -1f32, 0.5f32];
+
+        let outputs = solver.forward(tensor(&[2, 2], &data));
+        assert_eq!(1, outputs.len());
+        let expected_output = (0..2).flat_map(|b| (0..2).map(|o| {
+            weight_before[o * 2] * data[b * 2] + weight_before[o * 2 + 1] * data[b * 2 + 1]
+        }).collect::<Vec<_>>()).collect::<Vec<_>>();
+        assert_close(&expected_output, &tensor_to_vec(&outputs[0]));
+        assert_eq!(weight_before, weights(&solver)[0]);
+
+        // e.g. a reward-weighted gradient that depends on the outputs
+        let gradient = tensor_to_vec(&outputs[0]).iter().map(|output| 0.5f32 * output).collect::<Vec<_>>();
+        solver.apply_output_gradients(&[tensor(&[2, 2], &gradient)]);
+        assert_close(&sgd_step(&weight_before, &data, &gradient, 2, 0.1f32), &weights(&solver)[0]);
+    }
+
+    #[test]
+    // step: return momentum until momentum_stepsize, then max_momentum
+    fn momentum_step() {
+        let cfg = SolverConfig{ momentum_policy: MomentumPolicy::Step, momentum: 0.5f32, max_momentum: 0.9f32, momentum_stepsize: 10, ..SolverConfig::default()};
+        assert!(cfg.get_momentum(0) == 0.5f32);
+        assert!(cfg.get_momentum(9) == 0.5f32);
+        assert!(cfg.get_momentum(10) == 0.9f32);
+    }
+
+    #[test]
+    // linear: return momentum + (max_momentum - momentum) * min(iter / momentum_stepsize, 1)
+    fn momentum_linear() {
+        let cfg = SolverConfig{ momentum_policy: MomentumPolicy::Linear, momentum: 0.5f32, max_momentum: 0.9f32, momentum_stepsize: 4, ..SolverConfig::default()};
+        assert!(cfg.get_momentum(0) == 0.5f32);
+        assert!(cfg.get_momentum(2) == 0.7f32);
+        assert!(cfg.get_momentum(4) == 0.9f32);
+        assert!(cfg.get_momentum(100) == 0.9f32);
+    }
+
+    #[test]
+    // gradient noise: variance of eta / (1 + iter) ^ gamma
+    fn gradient_noise_decays() {
+        let noise = GradientNoiseConfig { eta: 4f32, gamma: 1f32 };
+        assert_eq!(2f32, noise.std_dev(0));
+        assert_eq!(1f32, noise.std_dev(3));
+    }
+
+    #[test]
+    fn gradient_noise_is_added_to_the_update() {
+        let train = |gradient_noise: Option<GradientNoiseConfig>| {
+            let config = SolverConfig {
+                network: linear_network(2, 2),
+                objective: nll_objective(2),
+                base_lr: 0.1f32,
+                gradient_noise: gradient_noise,
+                ..SolverConfig::default()
+            };
+            let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+            let weight_before = weights(&solver)[0].clone();
+            let data = [1f32, 2f32, -1f32, 0.5f32];
+            let gradient = [0.5f32, -1f32, 2f32, 0f32];
+            solver.train_with_gradient(tensor(&[2, 2], &data), tensor(&[2, 2], &gradient));
+            (sgd_step(&weight_before, &data, &gradient, 2, 0.1f32), weights(&solver)[0].clone())
+        };
+
+        let (expected, actual) = train(Some(GradientNoiseConfig { eta: 0f32, gamma: 0.55f32 }));
+        assert_close(&expected, &actual);
+        let (expected, actual) = train(Some(GradientNoiseConfig { eta: 100f32, gamma: 0.55f32 }));
+        assert!(expected.iter().zip(&actual).all(|(e, a)| e != a));
+    }
+
+    #[test]
+    fn gradient_noise_is_reproducible_with_the_same_seed() {
+        use leaf::util::write_tensor;
+
+        let train = || {
+            let config = SolverConfig {
+                network: linear_network(2, 2),
+                objective: nll_objective(2),
+                base_lr: 0.1f32,
+                gradient_noise: Some(GradientNoiseConfig { eta: 100f32, gamma: 0.55f32 }),
+                ..SolverConfig::default()
+            };
+            let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+            write_tensor(&solver.network().learnable_weights_data()[0], &[1f32, 0f32, 0f32, 1f32]).unwrap();
+            for _ in 0..3 {
+                solver.train_with_gradient(tensor(&[2, 2], &[1f32, 2f32, -1f32, 0.5f32]), tensor(&[2, 2], &[0.5f32, -1f32, 2f32, 0f32]));
+            }
+            weights(&solver)[0].clone()
+        };
+
+        let first = train();
+        assert_eq!(first, train());
+        assert!(first.iter().zip(&[1f32, 0f32, 0f32, 1f32]).all(|(w, initial)| w != initial));
+    }
+
+    #[test]
+    // param groups: the first group matching the layer name replaces the global settings
+    fn param_group_by_layer_name() {
+        let mut cfg = SolverConfig{ lr_policy: LRPolicy::Step, base_lr: 5f32, gamma: 0.5f32, stepsize: 10, momentum: 0.9f32, ..SolverConfig::default()};
+        cfg.param_groups.push(ParamGroup { lr: Some(1f32), weight_decay: Some(0.1f32), ..ParamGroup::new("conv*") });
+        cfg.param_groups.push(ParamGroup { momentum: Some(0.5f32), ..ParamGroup::new("*") });
+        assert!(cfg.get_layer_learning_rate("conv1", 10) == 0.5f32);
+        assert!(cfg.get_layer_learning_rate("fc", 10) == 2.5f32);
+        assert!(cfg.get_layer_momentum("conv1", 0) == 0.9f32);
+        assert!(cfg.get_layer_momentum("fc", 0) == 0.5f32);
+        assert!(cfg.get_layer_weight_decay("conv1") == Some(0.1f32));
+        assert!(cfg.get_layer_weight_decay("fc") == None);
+    }
+
+    #[test]
+    fn param_group_lr_replaces_the_base_lr() {
+        let mut config = SolverConfig {
+            network: linear_network(2, 2),
+            objective: nll_objective(2),
+            base_lr: 0.1f32,
+            ..SolverConfig::default()
+        };
+        config.param_groups.push(ParamGroup { lr: Some(0.5f32), ..ParamGroup::new("lin*") });
+        let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+        let weight_before = weights(&solver)[0].clone();
+        let data = [1f32, 2f32, -1f32, 0.5f32];
+        let gradient = [0.5f32, -1f32, 2f32, 0f32];
+        solver.train_with_gradient(tensor(&[2, 2], &data), tensor(&[2, 2], &gradient));
+        assert_close(&sgd_step(&weight_before, &data, &gradient, 2, 0.5f32), &weights(&solver)[0]);
+    }
+
+    #[test]
+    fn weight_decay_is_scaled_by_decay_mult() {
+        let data = [1f32, 2f32, -1f32, 0.5f32];
+        let gradient = [0.5f32, -1f32, 2f32, 0f32];
+        let cases = [(None, Some(2f32), 0f32),
+                     (Some(RegularizationMethod::L2), None, 0.1f32),
+                     (Some(RegularizationMethod::L2), Some(0f32), 0f32),
+                     (Some(RegularizationMethod::L2), Some(2f32), 0.2f32)];
+        for &(regularization_method, decay_mult, decay) in &cases {
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &[2, 2]);
+            let mut linear = LayerConfig::new("linear", LinearConfig::new(2));
+            linear.params.push(WeightConfig { decay_mult: decay_mult, ..WeightConfig::default() });
+            net_cfg.add_layer(linear);
+            let config = SolverConfig {
+                network: LayerConfig::new("network", net_cfg),
+                objective: nll_objective(2),
+                base_lr: 0.1f32,
+                weight_decay: Some(0.1f32),
+                regularization_method: regularization_method,
+                ..SolverConfig::default()
+            };
+            let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+            let weight_before = weights(&solver)[0].clone();
+            solver.train_with_gradient(tensor(&[2, 2], &data), tensor(&[2, 2], &gradient));
+            let expected = sgd_step(&weight_before, &data, &gradient, 2, 0.1f32).iter().zip(&weight_before)
+                .map(|(stepped, w)| stepped - 0.1f32 * decay * w)
+                .collect::<Vec<f32>>();
+            assert_close(&expected, &weights(&solver)[0]);
+        }
+    }
+
+    /// A single Linear layer like `linear_network`, whose output is named `scores`.
+    fn scores_network(input_size: usize, output_size: usize) -> LayerConfig {
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &[2, input_size]);
+        let mut linear_cfg = LayerConfig::new("linear", LinearConfig::new(output_size));
+        linear_cfg.add_output("scores");
+        net_cfg.add_layer(linear_cfg);
+        net_cfg.add_output("scores");
+        LayerConfig::new("network", net_cfg)
+    }
+
+    #[test]
+    fn weighted_objectives_of_an_output_sum_their_gradients() {
+        let weighted_nll = |weight: f32| ObjectiveConfig { weight: weight, ..ObjectiveConfig::new(nll_objective(2), "scores") };
+        let config = SolverConfig {
+            network: scores_network(2, 2),
+            objectives: vec![weighted_nll(2f32), weighted_nll(0.5f32)],
+            base_lr: 0.1f32,
+            ..SolverConfig::default()
+        };
+        let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+        let weight_before = weights(&solver)[0].clone();
+        let data = [1f32, 2f32, -1f32, 0.5f32];
+        solver.train_minibatch_with_targets(tensor(&[2, 2], &data), &[tensor(&[2], &[0f32, 1f32]), tensor(&[2], &[1f32, 1f32])]);
+
+        // the NLL gradient is -1 at the label of each sample:
+        // 2 * [[-1, 0], [0, -1]] + 0.5 * [[0, -1], [0, -1]]
+        let output_gradient = [-2f32, -0.5f32, 0f32, -2.5f32];
+        assert_close(&sgd_step(&weight_before, &data, &output_gradient, 2, 0.1f32), &weights(&solver)[0]);
+    }
+
+    #[test]
+    fn objective_of_unknown_output_is_rejected() {
+        let config = SolverConfig {
+            network: scores_network(2, 2),
+            objectives: vec![ObjectiveConfig::new(nll_objective(2), "probabilities")],
+            ..SolverConfig::default()
+        };
+        assert!(Solver::from_config(backend(), backend(), &config).is_err());
+    }
+
+    #[test]
+    fn solver_assigns_targets_to_objectives_in_order() {
+        let config = SolverConfig {
+            network: scores_network(3, 4),
+            objectives: vec![
+                ObjectiveConfig::new(nll_objective(4), "scores"),
+                ObjectiveConfig { weight: 0.5f32, ..ObjectiveConfig::with_outputs(nll_objective(4), &["scores"], 1) },
+            ],
+            base_lr: 0.1f32,
+            ..SolverConfig::default()
+        };
+        let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+
+        let data = [0.5f32, -1f32, 2f32, 1f32, 0f32, -0.5f32];
+        let targets = [tensor(&[2], &[0f32, 3f32]), tensor(&[2], &[1f32, 2f32])];
+        let output = tensor_to_vec(&solver.forward(tensor(&[2, 3], &data))[0]);
+        // the labels of shape [2] are not split into samples, so the NLL loss is the sum of the negated scores of the labels
+        let losses = solver.objective_losses(tensor(&[2, 3], &data), &targets);
+        assert_close(&[-(output[0] + output[7]), -(output[1] + output[6])], &losses);
+        let shared_losses = solver.objective_losses(tensor(&[2, 3], &data), &[targets[0].clone()]);
+        assert_close(&[-(output[0] + output[7]), -(output[0] + output[7])], &shared_losses);
+
+        let weight_before = weights(&solver)[0].clone();
+        let outputs = solver.train_minibatch_with_targets(tensor(&[2, 3], &data), &targets);
+        assert_eq!(1, outputs.len());
+        // the NLL gradient is -1 at the label of each sample:
+        // [[-1, 0, 0, 0], [0, 0, 0, -1]] + 0.5 * [[0, -1, 0, 0], [0, 0, -1, 0]]
+        let output_gradient = [-1f32, -0.5f32, 0f32, 0f32, 0f32, 0f32, -0.5f32, -1f32];
+        assert_close(&sgd_step(&weight_before, &data, &output_gradient, 3, 0.1f32), &weights(&solver)[0]);
+    }
+
+    #[test]
+    fn solver_starts_from_saved_weights() {
+        use std::path::PathBuf;
+        use leaf::models::mlp;
+
+        let mut pretrained = Layer::from_config(backend(), &LayerConfig::new("network", mlp(2, &[3, 4, 2])));
+        pretrained.save("target/testpretrainednetwork").unwrap();
+
+        let config = SolverConfig {
+            network: LayerConfig::new("network", mlp(2, &[3, 4, 2])),
+            objective: LayerConfig::new("nll", NegativeLogLikelihoodConfig::default()),
+            weights_path: Some(PathBuf::from("target/testpretrainednetwork")),
+            ..SolverConfig::default()
+        };
+        let solver = Solver::from_config(backend(), backend(), &config).unwrap();
+        let weights = solver.network().learnable_weights_data();
+        for (pretrained_weight, weight) in pretrained.learnable_weights_data().iter().zip(weights.iter()) {
+            assert_eq!(tensor_to_vec(pretrained_weight), tensor_to_vec(weight));
+        }
+    }
+
+    #[test]
+    fn solver_rejects_invalid_weights_path_and_train_from() {
+        use std::path::PathBuf;
+        use leaf::models::mlp;
+
+        let config = SolverConfig {
+            network: LayerConfig::new("network", mlp(2, &[3, 4, 2])),
+            objective: LayerConfig::new("nll", NegativeLogLikelihoodConfig::default()),
+            ..SolverConfig::default()
+        };
+        let missing_weights = SolverConfig { weights_path: Some(PathBuf::from("target/testmissingnetwork")), ..config.clone() };
+        assert!(Solver::from_config(backend(), backend(), &missing_weights).is_err());
+        let unknown_layer = SolverConfig { train_from: Some("missing".to_owned()), ..config.clone() };
+        assert!(Solver::from_config(backend(), backend(), &unknown_layer).is_err());
+        assert!(Solver::from_config(backend(), backend(), &config).is_ok());
+    }
+
+    fn lars_solver(regularization_method: Option<RegularizationMethod>) -> Solver<Backend<Native>, Backend<Native>> {
+        let config = SolverConfig {
+            network: linear_network(2, 2),
+            objective: nll_objective(2),
+            solver: SolverKind::SGD(SGDKind::LARS),
+            base_lr: 0.1f32,
+            trust_coefficient: 0.5f32,
+            weight_decay: Some(0.1f32),
+            regularization_method: regularization_method,
+            ..SolverConfig::default()
+        };
+        Solver::from_config(backend(), backend(), &config).unwrap()
+    }
+
+    fn norm(values: &[f32]) -> f32 {
+        values.iter().fold(0f32, |sum, v| sum + v * v).sqrt()
+    }
+
+    #[test]
+    fn lars_scales_the_learning_rate_by_the_trust_ratio() {
+        let data = [1f32, 2f32, -1f32, 0.5f32];
+        let output_gradient = [0.5f32, -1f32, 2f32, 0f32];
+        for &(regularization_method, weight_decay) in &[(None, 0f32), (Some(RegularizationMethod::L2), 0.1f32)] {
+            let mut solver = lars_solver(regularization_method);
+            let weight = weights(&solver)[0].clone();
+            let gradient = sgd_step(&weight, &data, &output_gradient, 2, 1f32).iter().zip(&weight)
+                .map(|(stepped, w)| w - stepped)
+                .collect::<Vec<f32>>();
+            let local_rate = 0.5f32 * norm(&weight) / (norm(&gradient) + weight_decay * norm(&weight));
+            let expected = weight.iter().zip(&gradient).map(|(w, g)| w - 0.1f32 * local_rate * (g + weight_decay * w)).collect::<Vec<f32>>();
+
+            solver.train_with_gradient(tensor(&[2, 2], &data), tensor(&[2, 2], &output_gradient));
+            assert_close(&expected, &weights(&solver)[0]);
+        }
+    }
+
+    #[test]
+    fn lars_counts_the_weight_decay_once_in_the_trust_ratio() {
+        use leaf::util::write_tensor;
+
+        let mut solver = lars_solver(Some(RegularizationMethod::L2));
+        write_tensor(&solver.network().learnable_weights_data()[0], &[3f32, 0f32, 0f32, 4f32]).unwrap();
+        // the raw gradient is [[0.6, 0], [0, 0.8]] with a norm of 1 and the weight has a norm of 5,
+        // so the local learning rate is 0.5 * 5 / (1 + 0.1 * 5) = 5 / 3
+        solver.train_with_gradient(tensor(&[2, 2], &[1f32, 0f32, 0f32, 1f32]), tensor(&[2, 2], &[0.6f32, 0f32, 0f32, 0.8f32]));
+        // the decayed gradient is [[0.9, 0], [0, 1.2]]
+        let local_rate = 5f32 / 3f32;
+        let expected = [3f32 - 0.1f32 * local_rate * 0.9f32, 0f32, 0f32, 4f32 - 0.1f32 * local_rate * 1.2f32];
+        assert_close(&expected, &weights(&solver)[0]);
+    }
+
+    #[test]
+    fn lars_uses_the_learning_rate_for_zero_weights() {
+        use leaf::util::write_tensor;
+
+        let mut solver = lars_solver(Some(RegularizationMethod::L2));
+        for weight in solver.network().learnable_weights_data() {
+            let size = weight.read().unwrap().desc().size();
+            write_tensor(&weight, &vec![0f32; size]).unwrap();
+        }
+        let data = [1f32, 2f32, -1f32, 0.5f32];
+        let output_gradient = [0.5f32, -1f32, 2f32, 0f32];
+        solver.train_with_gradient(tensor(&[2, 2], &data), tensor(&[2, 2], &output_gradient));
+        assert_close(&sgd_step(&[0f32; 4], &data, &output_gradient, 2, 0.1f32), &weights(&solver)[0]);
+    }
+
+    /// Two Linear layers for minibatches of four samples, optionally sharing their weight.
+    fn two_layer_network(shared_weight: bool) -> LayerConfig {
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &[4, 2]);
+        let mut hidden = LayerConfig::new("hidden", LinearConfig::new(2));
+        let mut head = LayerConfig::new("head", LinearConfig::new(2));
+        if shared_weight {
+            hidden.params.push(WeightConfig { name: "shared".to_owned(), ..WeightConfig::default() });
+            head.params.push(WeightConfig { name: "shared".to_owned(), ..WeightConfig::default() });
+        }
+        net_cfg.add_layer(hidden);
+        net_cfg.add_layer(head);
+        LayerConfig::new("network", net_cfg)
+    }
+
+    fn lbfgs_solver(network: LayerConfig, train_from: Option<String>) -> Solver<Backend<Native>, Backend<Native>> {
+        let config = SolverConfig {
+            network: network,
+            solver: SolverKind::LBFGS,
+            train_from: train_from,
+            ..SolverConfig::default()
+        };
+        let objective = Box::new(SquaredError { output: Vec::new(), target: Vec::new(), shape: Vec::new() });
+        Solver::with_objective(backend(), backend(), &config, objective).unwrap()
+    }
+
+    /// Train on one full batch and return the losses of all iterations.
+    fn train_full_batch(solver: &mut Solver<Backend<Native>, Backend<Native>>, iterations: usize) -> Vec<f32> {
+        let data = [2f32, 0.5f32, 0.5f32, 2f32, 1f32, -1f32, -1f32, 1f32];
+        // the targets can be reached with both weights set to [[1, 0.5], [0.5, 1]]
+        let targets = [3f32, 2.625f32, 2.625f32, 3f32, 0.25f32, -0.25f32, -0.25f32, 0.25f32];
+        (0..iterations).map(|_| solver.train_on_slices(&data, &[4, 2], &targets, &[4, 2]).unwrap()).collect()
+    }
+
+    #[test]
+    fn lbfgs_minimizes_the_loss_of_a_full_batch() {
+        let mut solver = lbfgs_solver(two_layer_network(false), None);
+        let losses = train_full_batch(&mut solver, 20);
+        for pair in losses.windows(2) {
+            assert!(pair[1] <= pair[0], "the loss increased: {:?}", losses);
+        }
+        assert!(losses[19] < 0.01f32 * losses[0], "the loss didn't decrease: {:?}", losses);
+    }
+
+    #[test]
+    fn lbfgs_updates_a_shared_weight_once() {
+        let mut solver = lbfgs_solver(two_layer_network(true), None);
+        let before = weights(&solver)[0].clone();
+        let gradient = [0.5f32, -1f32, 2f32, 0f32, 1f32, 1f32, -0.5f32, 0.25f32];
+        solver.train_with_gradient(tensor(&[4, 2], &[1f32, 2f32, -1f32, 0.5f32, 0f32, 1f32, 2f32, 2f32]), tensor(&[4, 2], &gradient));
+
+        let update = tensor_to_vec(&solver.network().learnable_weights_gradients()[0]);
+        assert!(update.iter().any(|&value| value != 0f32));
+        let expected = before.iter().zip(&update).map(|(w, u)| w - u).collect::<Vec<f32>>();
+        for weight in weights(&solver) {
+            assert_close(&expected, &weight);
+        }
+    }
+
+    #[test]
+    fn lbfgs_trains_a_shared_weight() {
+        use leaf::util::write_tensor;
+
+        let mut solver = lbfgs_solver(two_layer_network(true), None);
+        assert_eq!(1, weights(&solver).len());
+        assert_eq!(1, solver.network().learnable_weights_decay_mult().len());
+        // start from fixed weights, so the number of iterations doesn't depend on the initialization
+        write_tensor(&solver.network().learnable_weights_data()[0], &[1f32, 0f32, 0f32, 1f32]).unwrap();
+        let losses = train_full_batch(&mut solver, 20);
+        for pair in losses.windows(2) {
+            assert!(pair[1] <= pair[0], "the loss increased: {:?}", losses);
+        }
+        assert!(losses[19] < 0.01f32 * losses[0], "the loss didn't decrease: {:?}", losses);
+    }
+
+    #[test]
+    fn lbfgs_leaves_frozen_weights_unchanged() {
+        let mut solver = lbfgs_solver(two_layer_network(false), Some("head".to_owned()));
+        let before = weights(&solver);
+
+        // without the line search the update is applied through the gradients
+        let gradient = [0.5f32, -1f32, 2f32, 0f32, 1f32, 1f32, -0.5f32, 0.25f32];
+        solver.train_with_gradient(tensor(&[4, 2], &[1f32; 8]), tensor(&[4, 2], &gradient));
+        let after = weights(&solver);
+        assert_eq!(before[0], after[0]);
+        assert!(before[1] != after[1]);
+
+        let losses = train_full_batch(&mut solver, 5);
+        assert!(losses[4] < losses[0]);
+        assert_eq!(before[0], weights(&solver)[0]);
+        assert!(after[1] != weights(&solver)[1]);
+    }
+
+    #[test]
+    fn solver_trains_tasks_with_their_objective() {
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &[2, 3]);
+        let mut linear_cfg = LayerConfig::new("linear", LinearConfig::new(4));
+        linear_cfg.add_output("scores");
+        net_cfg.add_layer(linear_cfg);
+        let mut log_softmax_cfg = LayerConfig::new("log_softmax", LogSoftmaxConfig::default());
+        log_softmax_cfg.add_input("scores");
+        log_softmax_cfg.add_output("log_probabilities");
+        net_cfg.add_layer(log_softmax_cfg);
+        net_cfg.add_output("log_probabilities");
+        net_cfg.add_output("scores");
+        let mut config = SolverConfig {
+            network: LayerConfig::new("network", net_cfg),
+            objectives: vec![
+                ObjectiveConfig::new(nll_objective(4), "log_probabilities"),
+                ObjectiveConfig::new(nll_objective(4), "scores"),
+            ],
+            ..SolverConfig::default()
+        };
+        let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+
+        let samples = |num_samples: usize| (0..num_samples).map(|i| (vec![i as f32, 1f32, -0.5f32], vec![(i % 4) as f32])).collect::<Vec<_>>();
+        let mut first_task = IteratorBatcher::new(samples(8), 2, &[3], &[]).unwrap();
+        let mut second_task = IteratorBatcher::new(samples(4), 2, &[3], &[]).unwrap();
+        let mut sampler = TaskSampler::new(TaskSchedule::RoundRobin);
+        // only the output of the objective of the trained task gets a gradient
+        let output_gradients = |solver: &Solver<Backend<Native>, Backend<Native>>| {
+            solver.network().output_blobs_gradient.iter().map(tensor_to_vec).collect::<Vec<_>>()
+        };
+        for task in 0..2 {
+            solver.train_tasks(&mut [&mut first_task, &mut second_task], &mut sampler, 1).unwrap();
+            let gradients = output_gradients(&solver);
+            assert!(gradients[task].iter().any(|&value| value != 0f32));
+            assert!(gradients[1 - task].iter().all(|&value| value == 0f32));
+        }
+
+        let summaries = solver.train_tasks(&mut [&mut first_task, &mut second_task], &mut sampler, 3).unwrap();
+        assert_eq!((2, 1), (summaries[0].num_minibatches, summaries[1].num_minibatches));
+        assert_eq!(5, solver.iteration());
+
+        // the training stops when all tasks have run out of minibatches
+        let summaries = solver.train_tasks(&mut [&mut first_task, &mut second_task], &mut sampler, 5).unwrap();
+        assert_eq!((1, 0), (summaries[0].num_minibatches, summaries[1].num_minibatches));
+        assert!(summaries[0].loss.unwrap().is_finite());
+        assert_eq!(None, summaries[1].loss);
+        assert_eq!(6, solver.iteration());
+
+        // sub-batches of different tasks can not be accumulated into one update
+        config.minibatch_size = 2;
+        let mut accumulating = Solver::from_config(backend(), backend(), &config).unwrap();
+        let mut first_task = IteratorBatcher::new(samples(2), 2, &[3], &[]).unwrap();
+        let mut second_task = IteratorBatcher::new(samples(2), 2, &[3], &[]).unwrap();
+        assert!(accumulating.train_tasks(&mut [&mut first_task, &mut second_task], &mut sampler, 1).is_err());
+    }
+
+    fn classification_dataset(num_samples: usize) -> Dataset {
+        let mut dataset = Dataset::new(&[]);
+        for i in 0..num_samples {
+            let class = i % 2;
+            dataset.add_sample(vec![class as f32, 1f32 - class as f32 + i as f32 * 0.1f32], vec![class as f32]);
+        }
+        dataset
+    }
+
+    fn classification_config() -> SolverConfig {
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &[2, 2]);
+        net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+        net_cfg.add_layer(LayerConfig::new("log_softmax", LogSoftmaxConfig::default()));
+        SolverConfig {
+            network: LayerConfig::new("network", net_cfg),
+            objective: nll_objective(2),
+            base_lr: 0.1f32,
+            ..SolverConfig::default()
+        }
+    }
+
+    fn classification_solver() -> Solver<Backend<Native>, Backend<Native>> {
+        Solver::from_config(backend(), backend(), &classification_config()).unwrap()
+    }
+
+    #[test]
+    fn fit_shuffles_reproducibly() {
+        use leaf::util::write_tensor;
+
+        let dataset = classification_dataset(7);
+        let mut first = classification_solver();
+        let mut second = classification_solver();
+        for (weight, other) in first.network().learnable_weights_data().iter().zip(second.network().learnable_weights_data()) {
+            write_tensor(&other, &tensor_to_vec(weight)).unwrap();
+        }
+
+        let losses = first.fit(&dataset, 3).iter().map(|summary| summary.loss).collect::<Vec<_>>();
+        assert_eq!(losses, second.fit(&dataset, 3).iter().map(|summary| summary.loss).collect::<Vec<_>>());
+        assert_eq!(9, first.iteration());
+        assert_eq!(weights(&first), weights(&second));
+    }
+
+    #[test]
+    fn fit_reports_no_loss_without_complete_minibatch() {
+        let mut solver = classification_solver();
+        let summaries = solver.fit(&classification_dataset(1), 2);
+        assert_eq!(vec![0, 0], summaries.iter().map(|summary| summary.num_minibatches).collect::<Vec<_>>());
+        assert_eq!(vec![0f32, 0f32], summaries.iter().map(|summary| summary.loss).collect::<Vec<_>>());
+        assert_eq!(2, solver.epoch());
+        assert_eq!(0, solver.iteration());
+    }
+
+    #[test]
+    fn validate_computes_the_average_loss() {
+        let mut solver = classification_solver();
+        let weights_before = weights(&solver);
+        let dataset = classification_dataset(5);
+        let mut loss_sum = 0f32;
+        for minibatch in &[[0, 1], [2, 3]] {
+            let (inputs, targets) = dataset.minibatch(minibatch, &[2]);
+            let output = solver.forward(inputs)[0].clone();
+            let (output, targets) = (tensor_to_vec(&output), tensor_to_vec(&targets));
+            loss_sum -= (0..2).fold(0f32, |sum, i| sum + output[i * 2 + targets[i] as usize]);
+        }
+        let loss = solver.validate(&dataset).unwrap();
+        assert!((loss - loss_sum / 2f32).abs() < 1e-5, "expected {}, got {}", loss_sum / 2f32, loss);
+        assert_eq!(weights_before, weights(&solver));
+        assert_eq!(0, solver.iteration());
+
+        assert_eq!(None, solver.validate(&classification_dataset(1)));
+        let summaries = solver.fit_with_validation(&dataset, &classification_dataset(1), 1, |_, _| {});
+        assert_eq!(None, summaries[0].validation_loss);
+    }
+
+    #[test]
+    fn fit_with_validation_stops_early() {
+        let mut solver = classification_solver();
+        solver.mut_config().base_lr = 0f32;
+        solver.mut_config().early_stopping_patience = Some(1);
+        let dataset = classification_dataset(4);
+        let summaries = solver.fit_with_validation(&dataset, &dataset, 5, |_, _| {});
+        assert_eq!(2, summaries.len());
+        assert_eq!(summaries[0].validation_loss, summaries[1].validation_loss);
+        assert_eq!(2, solver.epoch());
+    }
+
+    #[test]
+    fn param_search_builds_grid_of_candidates() {
+        let mut net_cfg = SequentialConfig::default();
+        net_cfg.add_input("data", &vec![4, 2]);
+        net_cfg.add_layer(LayerConfig::new("hidden", LayerType::Linear(LinearConfig::new(8))));
+        let base = SolverConfig {
+            network: LayerConfig::new("network", LayerType::Sequential(net_cfg)),
+            base_lr: 0.1f32,
+            ..SolverConfig::default()
+        };
+
+        let search = ParamSearch::new(1).learning_rates(vec![0.1f32, 0.01f32]).output_sizes("hidden", vec![4, 16]);
+        let candidates = search.candidates(&base);
+        assert_eq!(4, candidates.len());
+        assert_eq!(0.01f32, candidates[3].base_lr);
+        assert_eq!(base.momentum, candidates[3].momentum);
+
+        let config = candidates[3].apply(&base).unwrap();
+        match config.network.layer_type {
+            LayerType::Sequential(ref sequential_config) => match sequential_config.layers[0].layer_type {
+                LayerType::Linear(ref linear_config) => assert_eq!(16, linear_config.output_size),
+                _ => panic!("Expected a Linear layer"),
+            },
+            _ => panic!("Expected a Sequential network"),
+        }
+
+        assert_eq!(3, search.random(3, 1).candidates(&base).len());
+    }
+
+    #[test]
+    fn param_search_run_sorts_candidates_by_validation_loss() {
+        let dataset = classification_dataset(8);
+        let search = ParamSearch::new(3).learning_rates(vec![0f32, 0.5f32]).patience(1);
+        let mut num_callbacks = 0;
+        let results = search.run_with_callback(backend(), backend(), &classification_config(), &dataset, &dataset,
+                                               |_, _| num_callbacks += 1).unwrap();
+        assert_eq!(2, results.len());
+        assert!(results[0].validation_loss <= results[1].validation_loss);
+        assert_eq!(num_callbacks, results.iter().map(|result| result.epochs.len()).sum::<usize>());
+        for result in &results {
+            let lowest = result.epochs.iter().map(|summary| summary.validation_loss.unwrap()).fold(::std::f32::INFINITY, f32::min);
+            assert_eq!(lowest, result.validation_loss);
+        }
+        // without updates the validation loss does not improve after the first epoch
+        let unchanged = results.iter().find(|result| result.candidate.base_lr == 0f32).unwrap();
+        assert_eq!(2, unchanged.epochs.len());
+
+        assert_eq!(2, search.run(backend(), backend(), &classification_config(), &dataset, &dataset).unwrap().len());
+    }
+
+    #[test]
+    fn param_search_run_rejects_invalid_candidates() {
+        let dataset = classification_dataset(8);
+        let unknown_layer = ParamSearch::new(1).output_sizes("unknown", vec![2]);
+        assert!(unknown_layer.run(backend(), backend(), &classification_config(), &dataset, &dataset).is_err());
+        let no_output_size = ParamSearch::new(1).output_sizes("log_softmax", vec![2]);
+        assert!(no_output_size.run(backend(), backend(), &classification_config(), &dataset, &dataset).is_err());
+        let search = ParamSearch::new(1);
+        assert!(search.run(backend(), backend(), &classification_config(), &dataset, &classification_dataset(1)).is_err());
+    }
+
+    #[test]
+    fn lr_find_is_reproducible_and_restores_the_weights() {
+        use leaf::util::write_tensor;
+
+        let dataset = classification_dataset(8);
+        let mut first = classification_solver();
+        let mut second = classification_solver();
+        for (weight, other) in first.network().learnable_weights_data().iter().zip(second.network().learnable_weights_data()) {
+            write_tensor(&other, &tensor_to_vec(weight)).unwrap();
+        }
+        let weights_before = weights(&first);
+
+        let curve = first.lr_find(&dataset, 1e-3, 1f32, 6).unwrap();
+        assert!(curve.len() > 1);
+        assert_eq!(1e-3, curve[0].0);
+        assert!(curve.iter().all(|&(lr, loss)| lr.is_finite() && !loss.is_nan()));
+        assert_eq!(curve, second.lr_find(&dataset, 1e-3, 1f32, 6).unwrap());
+        assert_eq!(weights_before, weights(&first));
+        assert_eq!(0, first.iteration());
+
+        assert!(first.lr_find(&dataset, 0f32, 1f32, 6).is_err());
+        assert!(first.lr_find(&dataset, -1f32, 1f32, 6).is_err());
+        assert!(first.lr_find(&dataset, 1f32, 1e-3, 6).is_err());
+    }
+
+    #[test]
+    fn confusion_matrix_computes_per_class_metrics() {
+        use leaf::solver::confusion_matrix::ConfusionMatrix;
+
+        let mut first_shard = ConfusionMatrix::new(3);
+        first_shard.add_samples(&[0, 1, 1], &[0, 1, 2]);
+        let mut second_shard = ConfusionMatrix::new(3);
+        second_shard.add_samples(&[2, 0], &[2, 1]);
+        first_shard.merge(&second_shard);
+
+        assert_eq!(vec![vec![1, 0, 0], vec![1, 1, 0], vec![0, 1, 1]], first_shard.counts().unwrap());
+        assert_eq!(3, first_shard.accuracy().num_correct());
+        assert_eq!(Some(0.5f32), first_shard.precision(0));
+        assert_eq!(Some(0.5f32), first_shard.recall(1));
+        assert_eq!(Some(0.8f32), first_shard.class_accuracy(2));
+        assert_eq!(None, ConfusionMatrix::new(3).precision(0));
+        assert_eq!(vec![vec![0; 3]; 3], ConfusionMatrix::new(3).counts().unwrap());
+
+        first_shard.add_sample(3, 0);
+        assert!(first_shard.counts().is_err());
+    }
+
+    #[test]
+    fn confusion_matrix_set_capacity_removes_the_oldest_samples() {
+        use leaf::solver::confusion_matrix::ConfusionMatrix;
+
+        let mut confusion_matrix = ConfusionMatrix::new(2);
+        confusion_matrix.add_samples(&[0, 1, 1, 0], &[0, 0, 1, 1]);
+        confusion_matrix.set_capacity(Some(2));
+        assert_eq!(Some(2), confusion_matrix.capacity());
+        assert_eq!(vec![vec![0, 0], vec![1, 1]], confusion_matrix.counts().unwrap());
+        confusion_matrix.add_sample(0, 0);
+        assert_eq!(vec![vec![1, 0], vec![1, 0]], confusion_matrix.counts().unwrap());
+    }
+
+    #[test]
+    fn confusion_matrix_metric_reports_percent() {
+        use leaf::solver::confusion_matrix::ConfusionMatrix;
+        use leaf::solver::metric::DEFAULT_METRIC_CAPACITY;
+
+        let mut confusion_matrix = ConfusionMatrix::new(2);
+        assert_eq!(None, confusion_matrix.value());
+        let output = tensor(&[4, 2], &[0.9f32, 0.1f32, 0.2f32, 0.8f32, 0.6f32, 0.4f32, 0.3f32, 0.7f32]);
+        let target = tensor(&[4], &[0f32, 1f32, 1f32, 1f32]);
+        confusion_matrix.update(&mut output.write().unwrap(), &mut target.write().unwrap());
+        assert_eq!(Some(75f32), confusion_matrix.value());
+        assert_eq!(Some(75f32), confusion_matrix.accuracy().percent());
+        assert_eq!(Some(DEFAULT_METRIC_CAPACITY), confusion_matrix.capacity());
+        confusion_matrix.reset();
+        assert_eq!(None, confusion_matrix.value());
+    }
+
+    #[test]
+    fn solver_resets_metrics_after_metrics_interval() {
+        use leaf::solver::confusion_matrix::ConfusionMatrix;
+
+        for &(metrics_interval, reset) in &[(None, false), (Some(2), false), (Some(1), true)] {
+            let config = SolverConfig {
+                network: linear_network(2, 2),
+                objective: nll_objective(2),
+                metrics_interval: metrics_interval,
+                ..SolverConfig::default()
+            };
+            let mut solver = Solver::from_config(backend(), backend(), &config).unwrap();
+            solver.add_metric(Box::new(ConfusionMatrix::new(2)));
+            solver.train_minibatch(tensor(&[2, 2], &[1f32, 2f32, -1f32, 0.5f32]), tensor(&[2], &[0f32, 1f32]));
+            assert_eq!(reset, solver.metrics()[0].value().is_none());
+        }
+    }
+
+    #[test]
+    fn confusion_matrix_is_saved_and_loaded() {
+        use std::fs::File;
+        use std::io::{ErrorKind, Write};
+        use leaf::solver::confusion_matrix::ConfusionMatrix;
+
+        let path = ::std::env::temp_dir().join(format!("leaf-confusion-matrix-{}", ::std::process::id()));
+        let mut confusion_matrix = ConfusionMatrix::new(2);
+        confusion_matrix.set_capacity(Some(10));
+        confusion_matrix.add_samples(&[0, 1, 1], &[0, 0, 1]);
+        confusion_matrix.save(&path).unwrap();
+        let loaded = ConfusionMatrix::load(&path).unwrap();
+        assert_eq!(2, loaded.num_classes());
+        assert_eq!(confusion_matrix.counts().unwrap(), loaded.counts().unwrap());
+
+        File::create(&path).unwrap().write_all(b"not a confusion matrix").unwrap();
+        assert_eq!(ErrorKind::InvalidData, ConfusionMatrix::load(&path).unwrap_err().kind());
+        ::std::fs::remove_file(&path).unwrap();
+        assert_eq!(ErrorKind::NotFound, ConfusionMatrix::load(&path).unwrap_err().kind());
+    }
+}
@@ -0,0 +1,34 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod text_spec {
+    use leaf::data::text::*;
+
+    const CORPUS: [&'static str; 3] = ["The cat sat.", "The dog sat, the cat ran!", "A bird"];
+
+    #[test]
+    fn build_vocabulary_by_frequency() {
+        let vocabulary = VocabularyBuilder::new().min_count(2).build(&CORPUS);
+        assert_eq!(5, vocabulary.len());
+        assert_eq!(Some("the"), vocabulary.word(2));
+        assert_eq!(vec![2, 3, 4], vocabulary.encode("THE CAT SAT"));
+        assert_eq!(vec![2, UNKNOWN_ID], vocabulary.encode("the bird"));
+        assert_eq!(3, VocabularyBuilder::new().max_size(3).build(&CORPUS).len());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn encode_batch_pads_and_truncates() {
+        use leaf::util::native_backend;
+
+        let vocabulary = VocabularyBuilder::new().build(&CORPUS);
+        let (ids, mask) = vocabulary.encode_batch(&["the cat", "the dog sat down"], 3);
+        assert_eq!(&vec![2, 3], ids.desc());
+        let ids = ids.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned();
+        let mask = mask.get(native_backend().device()).unwrap().as_native().unwrap().as_slice::<f32>().to_owned();
+        assert_eq!(vec![2f32, 3f32, PADDING_ID as f32, 2f32, 5f32, 4f32], ids);
+        assert_eq!(vec![1f32, 1f32, 0f32, 1f32, 1f32, 1f32], mask);
+        assert_eq!(vec!["the", "cat"], vocabulary.decode(&[2, 3, PADDING_ID]));
+    }
+}
@@ -0,0 +1,115 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod util_spec {
+    use leaf::util::{broadcast_shape, broadcast_to, class_ids, sum_to_shape, f16_to_f32, f32_to_f16};
+
+    #[test]
+    fn class_ids_round_labels() {
+        assert_eq!(vec![0, 1, 2, 16777215], class_ids(&[0f32, 0.99999994f32, 2.2f32, 16777215f32]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn class_ids_reject_inexact_labels() {
+        class_ids(&[16777216f32]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn class_ids_reject_negative_labels() {
+        class_ids(&[-1f32]);
+    }
+
+    #[test]
+    fn broadcast_shape_aligns_trailing_dimensions() {
+        assert_eq!(vec![4, 3, 2], broadcast_shape(&[4, 3, 2], &[3, 1]).unwrap());
+        assert_eq!(vec![2, 3], broadcast_shape(&[1], &[2, 3]).unwrap());
+        assert!(broadcast_shape(&[4, 3], &[4]).is_err());
+    }
+
+    #[test]
+    fn broadcast_and_sum_per_channel_values() {
+        let scale = [1f32, 2f32];
+        let broadcast = broadcast_to(&scale, &[2, 1], &[2, 2, 3]).unwrap();
+        assert_eq!(vec![1f32, 1f32, 1f32, 2f32, 2f32, 2f32,
+                        1f32, 1f32, 1f32, 2f32, 2f32, 2f32], broadcast);
+
+        assert_eq!(vec![6f32, 12f32], sum_to_shape(&broadcast, &[2, 2, 3], &[2, 1]).unwrap());
+        assert!(broadcast_to(&scale, &[2], &[2, 3]).is_err());
+    }
+
+    #[test]
+    fn half_precision_conversion() {
+        assert_eq!(0x3c00, f32_to_f16(1f32));
+        assert_eq!(0xc000, f32_to_f16(-2f32));
+        assert_eq!(0x2e66, f32_to_f16(0.1f32));
+        assert_eq!(0x7bff, f32_to_f16(65504f32));
+        assert_eq!(0x7c00, f32_to_f16(1e6f32));
+        assert_eq!(0x0001, f32_to_f16(2f32.powi(-24)));
+        assert_eq!(0x0000, f32_to_f16(1e-10f32));
+
+        assert_eq!(1f32, f16_to_f32(0x3c00));
+        assert_eq!(-2f32, f16_to_f32(0xc000));
+        assert_eq!(2f32.powi(-24), f16_to_f32(0x0001));
+        assert_eq!(::std::f32::INFINITY, f16_to_f32(0x7c00));
+        assert!((f16_to_f32(f32_to_f16(0.1f32)) - 0.1f32).abs() < 1e-4);
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn write_and_read_tensor_values() {
+        use std::sync::{Arc, RwLock};
+        use co::prelude::*;
+        use leaf::util::{native_backend, tensor_to_vec, write_tensor};
+
+        let tensor = Arc::new(RwLock::new(SharedTensor::<f32>::new(native_backend().device(), &vec![2, 2]).unwrap()));
+        write_tensor(&tensor, &[1f32, 2f32, 3f32, 4f32]).unwrap();
+        assert_eq!(vec![1f32, 2f32, 3f32, 4f32], tensor_to_vec(&tensor));
+        assert!(write_tensor(&tensor, &[1f32]).is_err());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn tensor_from_slice_checks_size() {
+        use std::sync::{Arc, RwLock};
+        use leaf::util::{native_backend, tensor_from_slice, tensor_to_vec};
+
+        let tensor = tensor_from_slice(&native_backend(), &[3, 1], &[1f32, 2f32, 3f32]).unwrap();
+        assert_eq!(&vec![3, 1], tensor.desc());
+        assert_eq!(vec![1f32, 2f32, 3f32], tensor_to_vec(&Arc::new(RwLock::new(tensor))));
+        assert!(tensor_from_slice(&native_backend(), &[2, 2], &[1f32]).is_err());
+    }
+
+    #[cfg(feature="native")]
+    #[test]
+    fn pad_sequences_pads_and_masks() {
+        use std::sync::{Arc, RwLock};
+        use leaf::util::{pad_sequences, tensor_to_vec};
+
+        let (padded, mask) = pad_sequences(&[vec![1f32, 2f32], vec![3f32, 4f32, 5f32, 6f32]], 2).unwrap();
+        assert_eq!(&vec![2, 2, 2], padded.desc());
+        assert_eq!(vec![1f32, 2f32, 0f32, 0f32, 3f32, 4f32, 5f32, 6f32], tensor_to_vec(&Arc::new(RwLock::new(padded))));
+        assert_eq!(vec![1f32, 0f32, 1f32, 1f32], tensor_to_vec(&Arc::new(RwLock::new(mask))));
+
+        assert!(pad_sequences(&[vec![1f32]], 0).is_err());
+        assert!(pad_sequences(&[vec![1f32, 2f32, 3f32]], 2).is_err());
+    }
+
+    #[test]
+    fn thread_pool_runs_borrowing_jobs() {
+        use leaf::util::ThreadPool;
+
+        let pool = ThreadPool::new(3);
+        assert_eq!(3, pool.num_threads());
+        let mut values = vec![0usize; 10];
+        for _ in 0..2 {
+            let jobs = values.chunks_mut(3).enumerate().map(|(chunk, values)| {
+                Box::new(move || for value in values.iter_mut() { *value += chunk + 1 }) as Box<FnOnce() + Send>
+            }).collect();
+            pool.execute(jobs);
+        }
+        assert_eq!(vec![2, 2, 2, 4, 4, 4, 6, 6, 6, 8], values);
+    }
+}
@@ -0,0 +1,135 @@
+extern crate leaf;
+extern crate collenchyma as co;
+
+#[cfg(test)]
+mod zoo_spec {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{ErrorKind, Write};
+    use leaf::zoo::*;
+
+    const ABC_SHA256: &'static str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    /// Returns a file:// URL for the file at the path relative to the crate root.
+    fn file_url(path: &str) -> String {
+        format!("file://{}", env::current_dir().unwrap().join(path).display())
+    }
+
+    #[test]
+    fn parse_manifest_skips_comments() {
+        let manifest = format!("# name url sha256\n\nmnist https://localhost/mnist {}\n", ABC_SHA256.to_uppercase());
+        let models = parse_manifest(&manifest).unwrap();
+        assert_eq!(vec![ZooModel {
+            name: "mnist".to_owned(),
+            url: "https://localhost/mnist".to_owned(),
+            sha256: ABC_SHA256.to_owned(),
+        }], models);
+        assert!(parse_manifest("mnist https://localhost/mnist").is_err());
+    }
+
+    #[test]
+    fn parse_manifest_rejects_invalid_models() {
+        for line in &[format!("../mnist https://localhost/mnist {}", ABC_SHA256),
+                      format!("models/mnist https://localhost/mnist {}", ABC_SHA256),
+                      format!(".. https://localhost/mnist {}", ABC_SHA256),
+                      format!("mnist http://localhost/mnist {}", ABC_SHA256),
+                      format!("mnist -Ohttps://localhost/mnist {}", ABC_SHA256),
+                      "mnist https://localhost/mnist abcdef".to_owned()] {
+            assert_eq!(ErrorKind::InvalidData, parse_manifest(line).unwrap_err().kind());
+        }
+    }
+
+    #[test]
+    fn file_checksum_is_sha256() {
+        // test vectors of NIST for SHA-256
+        let million_a = vec![b'a'; 1000000];
+        let vectors: Vec<(&[u8], &str)> = vec![
+            (b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            (b"abc", ABC_SHA256),
+            (b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+             "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"),
+            (b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+             "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1"),
+            (&million_a, "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"),
+        ];
+        for (i, &(data, sha256)) in vectors.iter().enumerate() {
+            let path = format!("target/testchecksum{}", i);
+            File::create(&path).unwrap().write_all(data).unwrap();
+            assert_eq!(sha256, file_checksum(&path).unwrap());
+        }
+    }
+
+    #[cfg(feature="native")]
+    mod native {
+        use std::fs;
+        use std::rc::Rc;
+        use leaf::layer::*;
+        use leaf::layers::*;
+        use leaf::util::native_backend;
+        use leaf::zoo::*;
+
+        #[test]
+        fn load_uses_cached_model() {
+            let _ = fs::create_dir_all("target/testzoo");
+            let mut net_cfg = SequentialConfig::default();
+            net_cfg.add_input("data", &vec![1, 3]);
+            net_cfg.add_layer(LayerConfig::new("linear", LinearConfig::new(2)));
+            let mut network = Layer::from_config(Rc::new(native_backend()), &LayerConfig::new("network", net_cfg));
+            network.save("target/testzoo/linear").unwrap();
+
+            let models = vec![ZooModel {
+                name: "linear".to_owned(),
+                url: "https://localhost:1/linear".to_owned(),
+                sha256: file_checksum("target/testzoo/linear").unwrap(),
+            }];
+            let zoo = Zoo::new("target/testzoo", models);
+            let loaded = zoo.load(Rc::new(native_backend()), "linear").unwrap();
+            assert_eq!(vec![("data".to_owned(), vec![1, 3])], loaded.input_shapes());
+            assert!(zoo.load(Rc::new(native_backend()), "unknown").is_err());
+        }
+    }
+
+    #[test]
+    fn fetch_downloads_file_urls() {
+        let _ = fs::remove_dir_all("target/testzoofetch");
+        fs::create_dir_all("target/testzoofetchserved").unwrap();
+        File::create("target/testzoofetchserved/model").unwrap().write_all(b"abc").unwrap();
+        let zoo = Zoo::new("target/testzoofetch", vec![ZooModel {
+            name: "model".to_owned(),
+            url: file_url("target/testzoofetchserved/model"),
+            sha256: ABC_SHA256.to_owned(),
+        }]);
+        let path = zoo.fetch("model").unwrap();
+        assert_eq!(ABC_SHA256, file_checksum(&path).unwrap());
+    }
+
+    #[test]
+    fn fetch_rejects_mismatching_checksum() {
+        let _ = fs::remove_dir_all("target/testzoomismatch");
+        fs::create_dir_all("target/testzoomismatchserved").unwrap();
+        File::create("target/testzoomismatchserved/model").unwrap().write_all(b"abd").unwrap();
+        let zoo = Zoo::new("target/testzoomismatch", vec![ZooModel {
+            name: "model".to_owned(),
+            url: file_url("target/testzoomismatchserved/model"),
+            sha256: ABC_SHA256.to_owned(),
+        }]);
+        assert_eq!(ErrorKind::InvalidData, zoo.fetch("model").unwrap_err().kind());
+        assert!(!::std::path::Path::new("target/testzoomismatch/model").exists());
+        assert!(!::std::path::Path::new("target/testzoomismatch/model.part").exists());
+    }
+
+    #[test]
+    fn from_manifest_url_checks_the_manifest() {
+        fs::create_dir_all("target/testzoomanifestserved").unwrap();
+        let manifest = format!("model https://localhost/model {}\n", ABC_SHA256);
+        File::create("target/testzoomanifestserved/manifest").unwrap().write_all(manifest.as_bytes()).unwrap();
+        let manifest_sha256 = file_checksum("target/testzoomanifestserved/manifest").unwrap();
+        let url = file_url("target/testzoomanifestserved/manifest");
+
+        let zoo = Zoo::from_manifest_url("target/testzoomanifest", &url, &manifest_sha256).unwrap();
+        assert_eq!("model", zoo.models()[0].name);
+        let error = Zoo::from_manifest_url("target/testzoomanifest", &url, ABC_SHA256).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+        assert!(Zoo::from_manifest_url("target/testzoomanifest", "http://localhost/manifest", &manifest_sha256).is_err());
+    }
+}